@@ -0,0 +1,7 @@
+-- Webhook delivery options for bots
+-- webhook_secret is sent back as X-Giano-Webhook-Secret so receivers can verify deliveries
+-- webhook_allowed_updates NULL means all update types are delivered
+
+ALTER TABLE bots ADD COLUMN webhook_secret TEXT;
+ALTER TABLE bots ADD COLUMN webhook_max_connections INTEGER NOT NULL DEFAULT 40;
+ALTER TABLE bots ADD COLUMN webhook_allowed_updates TEXT[];
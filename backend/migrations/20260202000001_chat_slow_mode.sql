@@ -0,0 +1,4 @@
+-- Slow mode: minimum seconds between messages from a non-admin participant
+-- 0 disables slow mode
+
+ALTER TABLE chats ADD COLUMN slow_mode_seconds INTEGER NOT NULL DEFAULT 0;
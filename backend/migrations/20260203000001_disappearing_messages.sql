@@ -0,0 +1,14 @@
+-- Disappearing messages
+-- chats.disappear_after_seconds: timer applied to new messages (0 = off)
+-- messages.delete_at: fixed at send time, so changing the timer never shortens
+-- the lifetime of messages that were already sent
+
+ALTER TABLE chats ADD COLUMN disappear_after_seconds INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE messages ADD COLUMN delete_at TIMESTAMPTZ;
+
+CREATE INDEX idx_messages_delete_at ON messages(delete_at) WHERE delete_at IS NOT NULL;
+
+-- Replies must not block deleting the message they quote
+ALTER TABLE messages DROP CONSTRAINT IF EXISTS messages_reply_to_id_fkey;
+ALTER TABLE messages ADD CONSTRAINT messages_reply_to_id_fkey
+    FOREIGN KEY (reply_to_id) REFERENCES messages(id) ON DELETE SET NULL;
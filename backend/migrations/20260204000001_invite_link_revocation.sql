@@ -0,0 +1,5 @@
+-- Track when an invite link was revoked, separately from expiry/exhaustion
+ALTER TABLE invite_links ADD COLUMN revoked_at TIMESTAMP WITH TIME ZONE;
+
+-- Links deactivated before this migration were revoked by their creator
+UPDATE invite_links SET revoked_at = updated_at WHERE is_active = FALSE;
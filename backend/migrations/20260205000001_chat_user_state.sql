@@ -0,0 +1,12 @@
+-- Per-user view state of a chat (archived flag, folder label).
+-- Private to the user: archiving a chat does not affect other participants.
+CREATE TABLE IF NOT EXISTS chat_user_state (
+    chat_id UUID NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    archived BOOLEAN NOT NULL DEFAULT FALSE,
+    folder VARCHAR(64),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (chat_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_chat_user_state_user_folder ON chat_user_state(user_id, folder);
@@ -0,0 +1,9 @@
+-- Comment threads: a reply references the root message it belongs to
+ALTER TABLE messages ADD COLUMN thread_root_id UUID REFERENCES messages(id) ON DELETE CASCADE;
+ALTER TABLE messages ADD COLUMN reply_count INTEGER NOT NULL DEFAULT 0;
+
+-- Thread roots are soft-deleted so their replies stay readable
+ALTER TABLE messages ADD COLUMN deleted_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX IF NOT EXISTS idx_messages_thread_root ON messages(thread_root_id, created_at)
+    WHERE thread_root_id IS NOT NULL;
@@ -0,0 +1,2 @@
+-- Tokens issued at or before this instant are rejected (admin force-disconnect)
+ALTER TABLE users ADD COLUMN tokens_revoked_at TIMESTAMP WITH TIME ZONE;
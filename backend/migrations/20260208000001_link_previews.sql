@@ -0,0 +1,15 @@
+-- Server-generated link previews, one per message (first URL only)
+CREATE TABLE link_previews (
+    id          UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    message_id  UUID UNIQUE NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    url         TEXT NOT NULL,
+    title       TEXT,
+    description TEXT,
+    image_url   TEXT,
+    site_name   TEXT,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Per-chat toggle (chat admins) and per-user privacy opt-out for senders
+ALTER TABLE chats ADD COLUMN link_previews_enabled BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE user_settings ADD COLUMN link_previews_enabled BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,14 @@
+-- Formatting entities (bold, italic, code, links) for bot messages sent with a parse mode.
+-- Offsets and lengths are in UTF-16 code units of the message text.
+CREATE TABLE message_entities (
+    id              UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    message_id      UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    position        INTEGER NOT NULL,
+    type            VARCHAR(16) NOT NULL,
+    start_offset    INTEGER NOT NULL,
+    length          INTEGER NOT NULL,
+    url             TEXT,
+    language        VARCHAR(32)
+);
+
+CREATE INDEX idx_message_entities_message ON message_entities(message_id, position);
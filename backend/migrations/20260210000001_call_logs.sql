@@ -0,0 +1,17 @@
+-- Persistent record of 1:1 calls; live call state stays in memory (WsManager).
+-- outcome: ringing -> accepted | declined | missed, or busy when the callee was in another call
+CREATE TABLE call_logs (
+    id              UUID PRIMARY KEY,
+    chat_id         UUID NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    caller_id       UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    callee_id       UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    call_type       VARCHAR(10) NOT NULL,
+    outcome         VARCHAR(10) NOT NULL DEFAULT 'ringing'
+        CHECK (outcome IN ('ringing', 'accepted', 'declined', 'missed', 'busy')),
+    started_at      TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    accepted_at     TIMESTAMP WITH TIME ZONE,
+    ended_at        TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX idx_call_logs_caller ON call_logs(caller_id, started_at DESC);
+CREATE INDEX idx_call_logs_callee ON call_logs(callee_id, started_at DESC);
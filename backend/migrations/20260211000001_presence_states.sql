@@ -0,0 +1,3 @@
+-- Presence the user picked (online/away/busy/invisible), restored on reconnect.
+-- users.status keeps the status others see, so invisible users show as offline there.
+ALTER TABLE users ADD COLUMN presence VARCHAR(20) NOT NULL DEFAULT 'online';
@@ -0,0 +1,4 @@
+-- Version counters for optimistic concurrency on settings updates.
+-- A client sends the version it last saw; a stale version is rejected with 409.
+ALTER TABLE users ADD COLUMN profile_version INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE user_settings ADD COLUMN privacy_version INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,8 @@
+-- Per-recipient delivery tracking: a row means the recipient's client had the
+-- message (online at send time or acknowledged it). Reads live in read_receipts.
+CREATE TABLE message_deliveries (
+    message_id      UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    user_id         UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    delivered_at    TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    PRIMARY KEY (message_id, user_id)
+);
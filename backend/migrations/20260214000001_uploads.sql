@@ -0,0 +1,24 @@
+-- Files accepted by POST /upload, so messages can only attach the sender's
+-- own uploads
+CREATE TABLE uploads (
+    id              UUID PRIMARY KEY,
+    user_id         UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    type            VARCHAR(20) NOT NULL,
+    name            VARCHAR(255) NOT NULL,
+    size            BIGINT NOT NULL,
+    url             TEXT NOT NULL,
+    stored_name     VARCHAR(255) NOT NULL,
+    mime_type       VARCHAR(100),
+    width           INTEGER,
+    height          INTEGER,
+    created_at      TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_uploads_user ON uploads(user_id, created_at DESC);
+
+ALTER TABLE attachments
+    ADD COLUMN upload_id UUID REFERENCES uploads(id) ON DELETE SET NULL,
+    ADD COLUMN width INTEGER,
+    ADD COLUMN height INTEGER;
+
+CREATE UNIQUE INDEX idx_attachments_upload ON attachments(upload_id) WHERE upload_id IS NOT NULL;
@@ -0,0 +1,5 @@
+-- Per-chat message retention override, in days
+-- NULL: use MESSAGE_RETENTION_DAYS; 0: keep forever
+ALTER TABLE chats ADD COLUMN retention_days INTEGER;
+
+CREATE INDEX IF NOT EXISTS idx_messages_created_at ON messages(created_at);
@@ -0,0 +1,7 @@
+-- Quoted replies keep the replied-to id and a copy of its author and text,
+-- so a reply still renders after the original is deleted
+ALTER TABLE messages DROP CONSTRAINT IF EXISTS messages_reply_to_id_fkey;
+
+ALTER TABLE messages ADD COLUMN reply_author_id UUID;
+ALTER TABLE messages ADD COLUMN reply_author_name VARCHAR(255);
+ALTER TABLE messages ADD COLUMN reply_snippet TEXT;
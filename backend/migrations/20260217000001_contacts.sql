@@ -0,0 +1,34 @@
+-- Contact lists: owner_id has added contact_id
+CREATE TABLE contacts (
+    owner_id        UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    contact_id      UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at      TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    PRIMARY KEY (owner_id, contact_id),
+    CHECK (owner_id <> contact_id)
+);
+
+CREATE INDEX idx_contacts_contact ON contacts(contact_id);
+
+-- Imported identifiers that matched no user yet; claimed when a user with
+-- that username or phone number appears. Phone numbers are only stored as
+-- SHA-256 hashes.
+CREATE TABLE contact_imports (
+    owner_id        UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind            VARCHAR(20) NOT NULL,
+    identifier      VARCHAR(255) NOT NULL,
+    created_at      TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    PRIMARY KEY (owner_id, kind, identifier)
+);
+
+CREATE INDEX idx_contact_imports_identifier ON contact_imports(kind, identifier);
+
+-- SHA-256 of the normalized phone number, for matching imported contacts
+ALTER TABLE users ADD COLUMN phone_hash VARCHAR(64);
+CREATE INDEX idx_users_phone_hash ON users(phone_hash) WHERE phone_hash IS NOT NULL;
+
+UPDATE users
+SET phone_hash = encode(sha256(convert_to(
+    CASE WHEN btrim(phone) LIKE '+%' THEN '+' ELSE '' END
+        || regexp_replace(phone, '[^0-9]', '', 'g'),
+    'UTF8')), 'hex')
+WHERE phone ~ '[0-9]';
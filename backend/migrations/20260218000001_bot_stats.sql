@@ -0,0 +1,10 @@
+-- Hourly usage counters per bot, flushed from an in-memory buffer.
+-- `command` is the command name for metric 'command' and '' otherwise.
+CREATE TABLE bot_stats (
+    bot_id          UUID NOT NULL REFERENCES bots(id) ON DELETE CASCADE,
+    bucket          TIMESTAMP WITH TIME ZONE NOT NULL,
+    metric          VARCHAR(32) NOT NULL,
+    command         VARCHAR(64) NOT NULL DEFAULT '',
+    count           BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (bot_id, bucket, metric, command)
+);
@@ -0,0 +1,14 @@
+-- Moderation and security events, e.g. automatic flood mutes
+CREATE TABLE audit_log (
+    id              UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    chat_id         UUID REFERENCES chats(id) ON DELETE CASCADE,
+    -- NULL when the system acted on its own
+    actor_id        UUID REFERENCES users(id) ON DELETE SET NULL,
+    target_user_id  UUID REFERENCES users(id) ON DELETE CASCADE,
+    action          VARCHAR(50) NOT NULL,
+    details         JSONB NOT NULL DEFAULT '{}',
+    created_at      TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX idx_audit_log_chat ON audit_log(chat_id, created_at DESC);
+CREATE INDEX idx_audit_log_target ON audit_log(target_user_id, created_at DESC);
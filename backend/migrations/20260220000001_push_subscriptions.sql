@@ -0,0 +1,17 @@
+-- Web push subscriptions, one per browser/device. The endpoint is unique
+-- across users: a browser re-registering under another account moves it.
+CREATE TABLE IF NOT EXISTS push_subscriptions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    endpoint TEXT NOT NULL UNIQUE,
+    p256dh TEXT NOT NULL,
+    auth TEXT NOT NULL,
+    user_agent TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX IF NOT EXISTS idx_push_subscriptions_user ON push_subscriptions(user_id);
+
+-- Muted chats send no push notifications to that user
+ALTER TABLE chat_user_state ADD COLUMN IF NOT EXISTS muted BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,5 @@
+-- Full-text index for searching within a chat. The 'simple' configuration
+-- does no stemming or stop words, so it works the same for every language.
+-- Queries must use this exact expression for the index to apply.
+CREATE INDEX IF NOT EXISTS idx_messages_text_search
+    ON messages USING GIN (to_tsvector('simple', COALESCE(text, '')));
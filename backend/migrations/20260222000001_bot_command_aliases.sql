@@ -0,0 +1,9 @@
+-- Per-bot command aliases, e.g. /s -> /status. Commands are stored without
+-- the leading slash; an alias always points straight at a real command.
+CREATE TABLE IF NOT EXISTS bot_command_aliases (
+    bot_id UUID NOT NULL REFERENCES bots(id) ON DELETE CASCADE,
+    alias VARCHAR(32) NOT NULL,
+    command VARCHAR(32) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (bot_id, alias)
+);
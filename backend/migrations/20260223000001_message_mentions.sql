@@ -0,0 +1,13 @@
+-- @-mentions resolved when a message is sent
+CREATE TABLE message_mentions (
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    user_id    UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- 'user' for @username, 'everyone' / 'here' for whole-chat mentions
+    kind       VARCHAR(10) NOT NULL CHECK (kind IN ('user', 'everyone', 'here')),
+    PRIMARY KEY (message_id, user_id)
+);
+
+CREATE INDEX idx_message_mentions_user ON message_mentions(user_id);
+
+-- Opt-in: mentions still notify in chats the user has muted
+ALTER TABLE user_settings ADD COLUMN mention_notify_when_muted BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,17 @@
+-- Transactional outbox: realtime fan-out of a write is recorded in the same
+-- transaction as the write itself. The writer holds a lease on the event and
+-- delivers it right away; events whose lease runs out unprocessed (the
+-- writer crashed) are delivered by the background relay.
+CREATE TABLE event_outbox (
+    id           UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    kind         VARCHAR(32) NOT NULL,
+    chat_id      UUID NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    message_id   UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    locked_until TIMESTAMP WITH TIME ZONE NOT NULL,
+    attempts     INTEGER NOT NULL DEFAULT 0,
+    created_at   TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    processed_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX idx_event_outbox_pending ON event_outbox(locked_until) WHERE processed_at IS NULL;
+CREATE INDEX idx_event_outbox_message ON event_outbox(message_id);
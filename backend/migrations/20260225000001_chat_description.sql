@@ -0,0 +1,2 @@
+-- Editable group chat description (name and avatar already live on chats)
+ALTER TABLE chats ADD COLUMN description TEXT;
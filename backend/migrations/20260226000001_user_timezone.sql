@@ -0,0 +1,2 @@
+-- IANA timezone (e.g. Europe/Berlin) for server-rendered timestamps; NULL = UTC
+ALTER TABLE user_settings ADD COLUMN timezone TEXT;
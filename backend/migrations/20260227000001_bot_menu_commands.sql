@@ -0,0 +1,3 @@
+-- Commands a bot advertises to users (setMyCommands): a JSON array of
+-- {"command": "...", "description": "..."}, commands without the leading slash
+ALTER TABLE bots ADD COLUMN menu_commands JSONB NOT NULL DEFAULT '[]'::jsonb;
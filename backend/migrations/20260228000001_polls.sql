@@ -0,0 +1,36 @@
+-- Polls, each posted as a message whose text is the question
+CREATE TABLE polls (
+    id              UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    message_id      UUID NOT NULL UNIQUE REFERENCES messages(id) ON DELETE CASCADE,
+    chat_id         UUID NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    creator_id      UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    question        TEXT NOT NULL,
+    multiple_choice BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Tallies of anonymous polls never list voters
+    anonymous       BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Set once the poll is closed; no votes are accepted after that
+    closed_at       TIMESTAMP WITH TIME ZONE,
+    created_at      TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE poll_options (
+    id       UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    poll_id  UUID NOT NULL REFERENCES polls(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    text     TEXT NOT NULL,
+    UNIQUE (poll_id, position)
+);
+
+-- A vote fills one of the voter's slots: single-choice polls have only
+-- slot 0, so a new vote replaces the old one in a single upsert; in
+-- multiple-choice polls the slot is the option's position.
+CREATE TABLE poll_votes (
+    poll_id   UUID NOT NULL REFERENCES polls(id) ON DELETE CASCADE,
+    user_id   UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    slot      INTEGER NOT NULL,
+    option_id UUID NOT NULL REFERENCES poll_options(id) ON DELETE CASCADE,
+    voted_at  TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (poll_id, user_id, slot)
+);
+
+CREATE INDEX idx_poll_votes_option ON poll_votes(option_id);
@@ -0,0 +1,21 @@
+-- End-to-end encrypted chats: clients encrypt message text themselves and
+-- the server stores it as opaque ciphertext. Once enabled it stays on.
+ALTER TABLE chats ADD COLUMN e2ee BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Set on messages sent while their chat had e2ee on; their text is
+-- ciphertext and is never searched, previewed or parsed
+ALTER TABLE messages ADD COLUMN encrypted BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Public prekey bundle each participant publishes per chat so others can
+-- open E2EE sessions with them. All keys are opaque base64 strings.
+CREATE TABLE chat_key_bundles (
+    chat_id          UUID NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    user_id          UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    identity_key     TEXT NOT NULL,
+    signed_prekey    TEXT NOT NULL,
+    prekey_signature TEXT NOT NULL,
+    -- Handed out one per fetch, oldest first
+    one_time_prekeys TEXT[] NOT NULL DEFAULT '{}',
+    updated_at       TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (chat_id, user_id)
+);
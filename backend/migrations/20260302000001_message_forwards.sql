@@ -0,0 +1,6 @@
+-- Forwarded messages are copies that remember where they came from. The
+-- original author is copied at forward time (name always, id only when
+-- their privacy settings allow linking forwards back to them)
+ALTER TABLE messages ADD COLUMN forwarded_from_id UUID;
+ALTER TABLE messages ADD COLUMN forward_author_id UUID;
+ALTER TABLE messages ADD COLUMN forward_author_name VARCHAR(255);
@@ -0,0 +1,10 @@
+-- Per-device notification toggles. NULL means the device follows the user's
+-- global notification settings, so new sessions start out inheriting them
+ALTER TABLE sessions ADD COLUMN message_notifications BOOLEAN;
+ALTER TABLE sessions ADD COLUMN group_notifications BOOLEAN;
+ALTER TABLE sessions ADD COLUMN channel_notifications BOOLEAN;
+
+-- The login session a push subscription was registered from
+ALTER TABLE push_subscriptions
+    ADD COLUMN session_id UUID REFERENCES sessions(id) ON DELETE SET NULL;
+CREATE INDEX IF NOT EXISTS idx_push_subscriptions_session ON push_subscriptions(session_id);
@@ -0,0 +1,18 @@
+-- Per-participant read pointer: the newest message in the chat when the
+-- participant last marked it read. The message's timestamp is kept too so
+-- the pointer still orders after that message is deleted
+ALTER TABLE chat_participants ADD COLUMN last_read_message_id UUID;
+ALTER TABLE chat_participants ADD COLUMN last_read_message_at TIMESTAMP WITH TIME ZONE;
+
+-- Start from the newest message each participant has a read receipt for
+UPDATE chat_participants cp
+SET last_read_message_id = latest.message_id,
+    last_read_message_at = latest.created_at
+FROM (
+    SELECT DISTINCT ON (m.chat_id, r.user_id)
+        m.chat_id, r.user_id, m.id AS message_id, m.created_at
+    FROM read_receipts r
+    JOIN messages m ON m.id = r.message_id
+    ORDER BY m.chat_id, r.user_id, m.created_at DESC, m.id DESC
+) latest
+WHERE cp.chat_id = latest.chat_id AND cp.user_id = latest.user_id;
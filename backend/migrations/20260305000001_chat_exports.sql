@@ -0,0 +1,23 @@
+-- Message history exports. A row is created when a user asks for an export
+-- and a background job writes the archive. Once expires_at passes the file
+-- is removed and the row kept as 'expired' (it still counts against the
+-- daily export limit) until it is cleaned up
+CREATE TABLE chat_exports (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    chat_id UUID NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'running', 'ready', 'failed', 'expired')),
+    include_media BOOLEAN NOT NULL DEFAULT FALSE,
+    -- File name under the exports directory, set once the archive is ready
+    file_name VARCHAR(255),
+    size_bytes BIGINT,
+    message_count INTEGER,
+    error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMP WITH TIME ZONE,
+    expires_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX idx_chat_exports_user ON chat_exports(user_id, created_at);
+CREATE INDEX idx_chat_exports_expires ON chat_exports(expires_at) WHERE expires_at IS NOT NULL;
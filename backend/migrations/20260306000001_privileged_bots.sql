@@ -0,0 +1,3 @@
+-- First-party bots trusted to skip the bot API rate limit. Only server
+-- admins can set this, and every request that skips the limit is audited
+ALTER TABLE bots ADD COLUMN privileged BOOLEAN NOT NULL DEFAULT FALSE;
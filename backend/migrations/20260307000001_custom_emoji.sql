@@ -0,0 +1,30 @@
+-- Custom emoji: uploaded images referenced as :shortcode: in reactions and
+-- message text. chat_id NULL means the emoji is global (added by a server
+-- admin) and usable in every chat. A shortcode resolves to exactly one emoji
+-- in any chat, so it is unique per chat and across global emoji
+CREATE TABLE custom_emoji (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    chat_id UUID REFERENCES chats(id) ON DELETE CASCADE,
+    shortcode VARCHAR(32) NOT NULL,
+    -- File name under the uploads directory
+    stored_name VARCHAR(255) NOT NULL,
+    url TEXT NOT NULL,
+    mime_type VARCHAR(50) NOT NULL,
+    size_bytes INTEGER NOT NULL,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_custom_emoji_chat_shortcode
+    ON custom_emoji(chat_id, shortcode) WHERE chat_id IS NOT NULL;
+CREATE UNIQUE INDEX idx_custom_emoji_global_shortcode
+    ON custom_emoji(shortcode) WHERE chat_id IS NULL;
+
+-- Who may add custom emoji to a group: 'admins' or every 'members'
+ALTER TABLE chats ADD COLUMN custom_emoji_policy VARCHAR(10) NOT NULL DEFAULT 'admins'
+    CHECK (custom_emoji_policy IN ('admins', 'members'));
+
+-- Custom emoji reactions store ':shortcode:' in emoji plus the emoji id;
+-- deleting the emoji removes its reactions
+ALTER TABLE reactions ALTER COLUMN emoji TYPE VARCHAR(40);
+ALTER TABLE reactions ADD COLUMN custom_emoji_id UUID REFERENCES custom_emoji(id) ON DELETE CASCADE;
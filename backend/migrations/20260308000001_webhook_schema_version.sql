@@ -0,0 +1,4 @@
+-- Webhook payload schema version requested by each bot
+-- NULL means the bot always receives the latest version
+
+ALTER TABLE bots ADD COLUMN webhook_schema_version INTEGER;
@@ -0,0 +1,13 @@
+-- Messages a user bookmarked for themselves ("Saved Messages"). Private to
+-- the user. message_id and chat_id are deliberately not foreign keys: a
+-- saved message outlives its original and is listed as a tombstone once the
+-- message (or its chat) is gone
+CREATE TABLE saved_messages (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    message_id UUID NOT NULL,
+    chat_id UUID NOT NULL,
+    saved_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, message_id)
+);
+
+CREATE INDEX idx_saved_messages_user ON saved_messages(user_id, saved_at DESC);
@@ -0,0 +1,10 @@
+-- Messages a user hid from their own history. Per-user only: other
+-- participants, threads and replies still see the message
+CREATE TABLE hidden_messages (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    message_id UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    hidden_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, message_id)
+);
+
+CREATE INDEX idx_hidden_messages_message ON hidden_messages(message_id);
@@ -0,0 +1,5 @@
+-- Structured system messages (pins, members joining/leaving)
+-- system_event: event type, e.g. 'message_pinned'; NULL for other messages
+-- system_metadata: JSON object with the event's details
+ALTER TABLE messages ADD COLUMN IF NOT EXISTS system_event TEXT;
+ALTER TABLE messages ADD COLUMN IF NOT EXISTS system_metadata TEXT;
@@ -0,0 +1,5 @@
+-- Soft-deleted chats: hidden from their participants until restored, purged
+-- with their messages and attachments once CHAT_RECOVERY_DAYS have passed
+ALTER TABLE chats ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_chats_deleted_at ON chats(deleted_at) WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,14 @@
+-- Inline keyboard button presses forwarded to bots
+-- answered_at: set once the bot answered; a query is answered at most once
+CREATE TABLE IF NOT EXISTS callback_queries (
+    id              UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    bot_id          UUID NOT NULL REFERENCES bots(id) ON DELETE CASCADE,
+    user_id         UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    chat_id         UUID NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    message_id      UUID NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    data            VARCHAR(64) NOT NULL,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    answered_at     TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_callback_queries_created ON callback_queries(created_at);
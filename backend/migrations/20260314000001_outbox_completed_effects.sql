@@ -0,0 +1,3 @@
+-- Side effects of an event (offline queue, push, mentions, link previews,
+-- bots) are recorded as they complete, so a replayed event skips them
+ALTER TABLE event_outbox ADD COLUMN completed_effects TEXT[] NOT NULL DEFAULT '{}';
@@ -1,5 +1,68 @@
-use anyhow::Result;
-use sqlx::{postgres::PgPoolOptions, PgPool};
+use anyhow::{bail, Context, Result};
+use sqlx::{
+    postgres::{PgConnectOptions, PgPoolOptions},
+    PgPool,
+};
+use std::time::Duration;
+
+use crate::config::Config;
+
+/// Connection pool sizing and timeouts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DbPoolConfig {
+    pub max_connections: u32,
+    pub min_connections: u32,
+    /// How long to wait for a free connection before the query fails
+    pub acquire_timeout: Duration,
+    /// Idle connections above `min_connections` are closed after this long
+    pub idle_timeout: Option<Duration>,
+    /// Server-side `statement_timeout` applied to every connection
+    pub statement_timeout: Option<Duration>,
+}
+
+impl Default for DbPoolConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 10,
+            min_connections: 0,
+            acquire_timeout: Duration::from_secs(10),
+            idle_timeout: Some(Duration::from_secs(600)),
+            statement_timeout: Some(Duration::from_secs(30)),
+        }
+    }
+}
+
+impl From<&Config> for DbPoolConfig {
+    fn from(config: &Config) -> Self {
+        let optional = |seconds: u64| (seconds > 0).then_some(Duration::from_secs(seconds));
+        Self {
+            max_connections: config.db_max_connections,
+            min_connections: config.db_min_connections,
+            acquire_timeout: Duration::from_secs(config.db_acquire_timeout_seconds),
+            idle_timeout: optional(config.db_idle_timeout_seconds),
+            statement_timeout: optional(config.db_statement_timeout_seconds),
+        }
+    }
+}
+
+impl DbPoolConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.max_connections == 0 {
+            bail!("DB_MAX_CONNECTIONS must be greater than 0");
+        }
+        if self.min_connections > self.max_connections {
+            bail!(
+                "DB_MIN_CONNECTIONS ({}) must not exceed DB_MAX_CONNECTIONS ({})",
+                self.min_connections,
+                self.max_connections
+            );
+        }
+        if self.acquire_timeout.is_zero() {
+            bail!("DB_ACQUIRE_TIMEOUT_SECS must be greater than 0");
+        }
+        Ok(())
+    }
+}
 
 #[derive(Clone)]
 pub struct Database {
@@ -7,10 +70,32 @@ pub struct Database {
 }
 
 impl Database {
-    pub async fn new(database_url: &str) -> Result<Self> {
+    pub async fn new(database_url: &str, pool_config: &DbPoolConfig) -> Result<Self> {
+        pool_config.validate()?;
+
+        let mut connect_options: PgConnectOptions = database_url
+            .parse()
+            .context("DATABASE_URL is not a valid Postgres URL")?;
+        if let Some(timeout) = pool_config.statement_timeout {
+            connect_options = connect_options
+                .options([("statement_timeout", format!("{}ms", timeout.as_millis()))]);
+        }
+
+        tracing::info!(
+            "Database pool: max_connections={}, min_connections={}, acquire_timeout={:?}, idle_timeout={:?}, statement_timeout={:?}",
+            pool_config.max_connections,
+            pool_config.min_connections,
+            pool_config.acquire_timeout,
+            pool_config.idle_timeout,
+            pool_config.statement_timeout
+        );
+
         let pool = PgPoolOptions::new()
-            .max_connections(10)
-            .connect(database_url)
+            .max_connections(pool_config.max_connections)
+            .min_connections(pool_config.min_connections)
+            .acquire_timeout(pool_config.acquire_timeout)
+            .idle_timeout(pool_config.idle_timeout)
+            .connect_with(connect_options)
             .await?;
 
         Ok(Self { pool })
@@ -25,3 +110,32 @@ impl Database {
 }
 
 // Force rebuild to pick up new migrations
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_pool_config() {
+        assert!(DbPoolConfig::default().validate().is_ok());
+
+        let no_connections = DbPoolConfig {
+            max_connections: 0,
+            ..DbPoolConfig::default()
+        };
+        assert!(no_connections.validate().is_err());
+
+        let min_above_max = DbPoolConfig {
+            max_connections: 5,
+            min_connections: 6,
+            ..DbPoolConfig::default()
+        };
+        assert!(min_above_max.validate().is_err());
+
+        let no_acquire_timeout = DbPoolConfig {
+            acquire_timeout: Duration::ZERO,
+            ..DbPoolConfig::default()
+        };
+        assert!(no_acquire_timeout.validate().is_err());
+    }
+}
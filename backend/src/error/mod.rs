@@ -29,6 +29,10 @@ pub enum AppError {
     AccessDenied,
     #[error("Not message owner")]
     NotMessageOwner,
+    #[error("Message can no longer be {0}")]
+    MessageWindowExpired(&'static str),
+    #[error("System messages cannot be {0}")]
+    SystemMessage(&'static str),
     #[error("{0}")]
     Forbidden(String),
 
@@ -61,12 +65,38 @@ pub enum AppError {
     // Rate limiting errors
     #[error("Too many login attempts, retry after {0} seconds")]
     LoginRateLimitExceeded(u32),
+    #[error("Slow mode is active, retry after {0} seconds")]
+    SlowModeActive(u32),
+    #[error("Too many uploads, retry after {0} seconds")]
+    UploadRateLimitExceeded(u32),
+    #[error("Muted for flooding, retry after {0} seconds")]
+    FloodMuted(u32),
+    #[error("An announcement was sent recently, retry after {0} seconds")]
+    AnnouncementRateLimited(u32),
+    #[error("Export limit reached, retry after {0} seconds")]
+    ExportRateLimited(u32),
+    #[error("Reacting too fast, retry after {0} seconds")]
+    ReactionRateLimited(u32),
+    #[error("Previewing links too fast, retry after {0} seconds")]
+    UnfurlRateLimited(u32),
+
+    // Invite link errors
+    #[error("Invite link has expired")]
+    InviteLinkExpired,
+    #[error("Invite link has been revoked")]
+    InviteLinkRevoked,
+    #[error("Invite link has reached its maximum number of uses")]
+    InviteLinkExhausted,
 
     // Validation errors
     #[error("Empty message")]
     EmptyMessage,
+    #[error("Message exceeds {0} bytes")]
+    MessageTooLong(usize),
     #[error("Invalid participants")]
     InvalidParticipants,
+    #[error("Chat is full ({0} participants at most)")]
+    ChatFull(i64),
     #[error("File too large")]
     FileTooLarge,
     #[error("Invalid file type")]
@@ -75,6 +105,13 @@ pub enum AppError {
     CannotTerminateCurrent,
     #[error("{0}")]
     BadRequest(String),
+    /// Update based on a stale version; carries the current state
+    #[error("Modified by another request, reload and retry")]
+    VersionConflict(serde_json::Value),
+
+    // Availability errors
+    #[error("Service is in maintenance mode, please try again later")]
+    Maintenance,
 
     // Internal errors
     #[error("Database error: {0}")]
@@ -92,11 +129,15 @@ struct ErrorResponse {
 struct ErrorBody {
     code: String,
     message: String,
+    /// Current server state, for conflicts the client should merge
+    #[serde(skip_serializing_if = "Option::is_none")]
+    current: Option<serde_json::Value>,
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
-        let (status, code) = match &self {
+impl AppError {
+    /// HTTP status and stable machine-readable code for this error
+    pub fn status_and_code(&self) -> (StatusCode, &'static str) {
+        match self {
             AppError::InvalidCredentials => (StatusCode::UNAUTHORIZED, "INVALID_CREDENTIALS"),
             AppError::TokenExpired => (StatusCode::UNAUTHORIZED, "TOKEN_EXPIRED"),
             AppError::InvalidToken => (StatusCode::UNAUTHORIZED, "INVALID_TOKEN"),
@@ -106,6 +147,8 @@ impl IntoResponse for AppError {
             AppError::MissingName => (StatusCode::BAD_REQUEST, "MISSING_NAME"),
             AppError::AccessDenied => (StatusCode::FORBIDDEN, "ACCESS_DENIED"),
             AppError::NotMessageOwner => (StatusCode::FORBIDDEN, "NOT_MESSAGE_OWNER"),
+            AppError::MessageWindowExpired(_) => (StatusCode::FORBIDDEN, "MESSAGE_WINDOW_EXPIRED"),
+            AppError::SystemMessage(_) => (StatusCode::FORBIDDEN, "SYSTEM_MESSAGE"),
             AppError::Forbidden(_) => (StatusCode::FORBIDDEN, "FORBIDDEN"),
             AppError::UserNotFound => (StatusCode::NOT_FOUND, "USER_NOT_FOUND"),
             AppError::ChatNotFound => (StatusCode::NOT_FOUND, "CHAT_NOT_FOUND"),
@@ -119,20 +162,47 @@ impl IntoResponse for AppError {
             AppError::InvalidWebhookUrl => (StatusCode::BAD_REQUEST, "INVALID_WEBHOOK_URL"),
             AppError::WebhookError(_) => (StatusCode::BAD_GATEWAY, "WEBHOOK_ERROR"),
             AppError::LoginRateLimitExceeded(_) => (StatusCode::TOO_MANY_REQUESTS, "LOGIN_RATE_LIMIT_EXCEEDED"),
+            AppError::SlowModeActive(_) => (StatusCode::TOO_MANY_REQUESTS, "SLOW_MODE_ACTIVE"),
+            AppError::UploadRateLimitExceeded(_) => (StatusCode::TOO_MANY_REQUESTS, "UPLOAD_RATE_LIMIT_EXCEEDED"),
+            AppError::FloodMuted(_) => (StatusCode::TOO_MANY_REQUESTS, "FLOOD_MUTED"),
+            AppError::AnnouncementRateLimited(_) => (StatusCode::TOO_MANY_REQUESTS, "ANNOUNCEMENT_RATE_LIMITED"),
+            AppError::ExportRateLimited(_) => (StatusCode::TOO_MANY_REQUESTS, "EXPORT_RATE_LIMITED"),
+            AppError::ReactionRateLimited(_) => (StatusCode::TOO_MANY_REQUESTS, "REACTION_RATE_LIMITED"),
+            AppError::UnfurlRateLimited(_) => (StatusCode::TOO_MANY_REQUESTS, "UNFURL_RATE_LIMITED"),
+            AppError::InviteLinkExpired => (StatusCode::GONE, "INVITE_LINK_EXPIRED"),
+            AppError::InviteLinkRevoked => (StatusCode::GONE, "INVITE_LINK_REVOKED"),
+            AppError::InviteLinkExhausted => (StatusCode::GONE, "INVITE_LINK_EXHAUSTED"),
             AppError::EmptyMessage => (StatusCode::BAD_REQUEST, "EMPTY_MESSAGE"),
+            AppError::MessageTooLong(_) => (StatusCode::PAYLOAD_TOO_LARGE, "MESSAGE_TOO_LONG"),
             AppError::InvalidParticipants => (StatusCode::BAD_REQUEST, "INVALID_PARTICIPANTS"),
+            AppError::ChatFull(_) => (StatusCode::CONFLICT, "CHAT_FULL"),
             AppError::FileTooLarge => (StatusCode::BAD_REQUEST, "FILE_TOO_LARGE"),
             AppError::InvalidFileType => (StatusCode::BAD_REQUEST, "INVALID_FILE_TYPE"),
             AppError::CannotTerminateCurrent => (StatusCode::BAD_REQUEST, "CANNOT_TERMINATE_CURRENT"),
             AppError::BadRequest(_) => (StatusCode::BAD_REQUEST, "BAD_REQUEST"),
+            AppError::VersionConflict(_) => (StatusCode::CONFLICT, "VERSION_CONFLICT"),
+            AppError::Maintenance => (StatusCode::SERVICE_UNAVAILABLE, "MAINTENANCE"),
             AppError::Database(_) => (StatusCode::INTERNAL_SERVER_ERROR, "DATABASE_ERROR"),
             AppError::Internal(_) => (StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR"),
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let (status, code) = self.status_and_code();
+
+        let message = self.to_string();
+        let current = match self {
+            AppError::VersionConflict(current) => Some(current),
+            _ => None,
         };
 
         let body = ErrorResponse {
             error: ErrorBody {
                 code: code.to_string(),
-                message: self.to_string(),
+                message,
+                current,
             },
         };
 
@@ -8,33 +8,95 @@ pub mod services;
 pub mod ws;
 
 use anyhow::Result;
-use axum::{extract::DefaultBodyLimit, routing::get, Router};
+use axum::{
+    extract::DefaultBodyLimit,
+    http::{HeaderName, HeaderValue, Method},
+    middleware,
+    routing::get,
+    Router,
+};
 use config::Config;
-use db::Database;
+use db::{Database, DbPoolConfig};
+use error::{AppError, AppResult};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tower_http::{
-    cors::{Any, CorsLayer},
+    cors::{AllowHeaders, AllowOrigin, Any, CorsLayer},
     services::ServeDir,
     trace::TraceLayer,
 };
 
-use quic::{ConnectionManager, StreamAllocator};
-use services::bot_engine::{BotDispatcher, RateLimiter};
-use ws::WsManager;
+use quic::{
+    ConnectionManager, DeadLetterLog, QuicMetrics, QuicServerConfig, ResumptionStore,
+    StreamAllocator, WebSocketSendFuture,
+};
+use services::bot_engine::{BotDispatcher, FailMode, RateLimiter};
+use services::login_rate_limiter::{LoginRateLimitConfig, LoginRateLimiter};
+use services::slow_mode::SlowModeLimiter;
+use services::admin_stats::{EntityCountsCache, ENTITY_COUNTS_TTL};
+use services::upload_quota::{UploadQuota, UploadQuotaConfig};
+use services::flood_guard::{FloodGuard, FloodGuardConfig};
+use services::reaction_limiter::{ReactionDebouncer, ReactionLimitConfig, ReactionLimiter};
+use services::unfurl::{UnfurlConfig, UnfurlService};
+use services::offline_queue::{OfflineQueue, OfflineQueueConfig};
+use services::push::{PushConfig, PushService};
+use ws::{events::ServerEvent, WsManager};
 
 pub struct AppState {
     pub db: Database,
     pub config: Config,
     pub ws_manager: Arc<WsManager>,
     pub rate_limiter: Option<RateLimiter>,
+    pub login_rate_limiter: LoginRateLimiter,
+    pub slow_mode: SlowModeLimiter,
+    pub upload_quota: UploadQuota,
+    pub flood_guard: FloodGuard,
+    pub reaction_limiter: ReactionLimiter,
+    /// Coalesces reaction broadcasts per message
+    pub reaction_debouncer: Arc<ReactionDebouncer>,
+    /// Link previews requested before a message is sent
+    pub unfurl: UnfurlService,
+    /// New messages held for users without a connection
+    pub offline_queue: OfflineQueue,
+    /// Web push notifications for offline recipients
+    pub push: PushService,
     pub bot_dispatcher: Arc<BotDispatcher>,
     pub connection_manager: Arc<ConnectionManager>,
+    /// QUIC server settings, also advertised to clients choosing a transport
+    pub quic_config: QuicServerConfig,
+    /// Single-use tokens for fast QUIC re-authentication
+    pub quic_resumption: Arc<ResumptionStore>,
     pub stream_allocator: Arc<StreamAllocator>,
+    pub dead_letters: Arc<DeadLetterLog>,
+    /// QUIC transport metrics (connections, migrations, throughput)
+    pub quic_metrics: Arc<QuicMetrics>,
+    /// Cached table counts for the admin stats endpoint
+    pub entity_counts: EntityCountsCache,
+    /// Read-only mode; toggled at runtime via the admin API
+    pub maintenance_mode: AtomicBool,
+}
+
+impl AppState {
+    pub fn is_maintenance_mode(&self) -> bool {
+        self.maintenance_mode.load(Ordering::Relaxed)
+    }
+
+    pub fn set_maintenance_mode(&self, enabled: bool) {
+        self.maintenance_mode.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Reject a write while the service is in maintenance mode
+    pub fn ensure_writable(&self) -> AppResult<()> {
+        if self.is_maintenance_mode() {
+            return Err(AppError::Maintenance);
+        }
+        Ok(())
+    }
 }
 
 pub async fn create_app(config: Config) -> Result<(Router, Arc<AppState>)> {
     // Initialize database
-    let db = Database::new(&config.database_url).await?;
+    let db = Database::new(&config.database_url, &DbPoolConfig::from(&config)).await?;
 
     // Run migrations
     db.run_migrations().await?;
@@ -42,12 +104,12 @@ pub async fn create_app(config: Config) -> Result<(Router, Arc<AppState>)> {
     // Initialize WebSocket manager
     let ws_manager = WsManager::new();
 
-    // Initialize rate limiter with Redis (optional - gracefully handle connection failures)
-    let rate_limiter = match redis::Client::open(config.redis_url.as_str()) {
+    // Connect to Redis (optional - gracefully handle connection failures)
+    let redis = match redis::Client::open(config.redis_url.as_str()) {
         Ok(client) => match client.get_connection_manager().await {
             Ok(conn_manager) => {
                 tracing::info!("Redis connected for rate limiting");
-                Some(RateLimiter::with_defaults(conn_manager))
+                Some(conn_manager)
             }
             Err(e) => {
                 tracing::warn!(
@@ -63,48 +125,287 @@ pub async fn create_app(config: Config) -> Result<(Router, Arc<AppState>)> {
         }
     };
 
+    // Initialize bot rate limiter (requires Redis)
+    let fail_mode = if config.rate_limit_fail_closed {
+        FailMode::Closed
+    } else {
+        FailMode::Open
+    };
+    let rate_limiter = redis
+        .clone()
+        .map(|redis| RateLimiter::with_defaults(redis).with_fail_mode(fail_mode));
+
+    // Initialize chat slow-mode limiter (falls back to the database without Redis)
+    let slow_mode = SlowModeLimiter::new(redis.clone());
+
+    // Initialize per-user upload limits (not enforced without Redis)
+    let upload_quota = UploadQuota::new(redis.clone(), UploadQuotaConfig::from(&config));
+
+    // Initialize flood protection (not enforced without Redis)
+    let flood_guard = FloodGuard::new(redis.clone(), FloodGuardConfig::from(&config));
+
+    // Initialize reaction rate limiting (not enforced without Redis) and broadcast debouncing
+    let reaction_limiter = ReactionLimiter::new(redis.clone(), ReactionLimitConfig::from(&config));
+    let reaction_debouncer = ReactionDebouncer::new(
+        db.clone(),
+        ws_manager.clone(),
+        std::time::Duration::from_millis(config.reaction_debounce_ms),
+    );
+
+    // Initialize compose-time link previews (not cached or limited without Redis)
+    let unfurl = UnfurlService::new(redis.clone(), UnfurlConfig::from(&config));
+
+    // Initialize queueing of messages for offline users (requires Redis)
+    let offline_queue = OfflineQueue::new(redis.clone(), OfflineQueueConfig::from(&config));
+
+    // Initialize web push (disabled without a VAPID key pair)
+    let push = PushService::new(PushConfig::from(&config));
+
+    // Initialize login rate limiter (falls back to the database without Redis)
+    let login_rate_limiter =
+        LoginRateLimiter::new(redis.clone(), LoginRateLimitConfig::from(&config));
+
+    // Encrypt stored webhook secrets (plaintext without SECRET_ENCRYPTION_KEY)
+    services::crypto::install(
+        services::crypto::SecretKeys::from_config(&config).map_err(anyhow::Error::msg)?,
+    );
+
     // Initialize bot dispatcher
-    let bot_dispatcher = Arc::new(BotDispatcher::new(ws_manager.clone()));
+    let bot_dispatcher = Arc::new(BotDispatcher::new(ws_manager.clone(), &config));
 
     // Initialize connection manager (shared between QUIC and WebSocket)
-    let connection_manager = Arc::new(ConnectionManager::new());
+    let quic_config = QuicServerConfig::from_env()?;
+    let ws_sender = ws_manager.clone();
+    let ws_closer = ws_manager.clone();
+    let connection_manager = Arc::new(
+        ConnectionManager::new()
+            .with_idle_warning_window(quic_config.idle_warning_window())
+            .with_preferred_transport(quic_config.preferred_transport)
+            .with_connection_limit(config.max_connections_per_user, config.connection_limit_policy)
+            .with_websocket_sender(Arc::new(move |user_id, connection_id, data| {
+                let ws_manager = ws_sender.clone();
+                Box::pin(async move {
+                    let event: ServerEvent =
+                        serde_json::from_slice(&data).map_err(|e| e.to_string())?;
+                    if ws_manager
+                        .send_to_client(user_id, connection_id.as_uuid(), event)
+                        .await
+                    {
+                        Ok(())
+                    } else {
+                        Err("WebSocket client is gone".to_string())
+                    }
+                }) as WebSocketSendFuture
+            }))
+            .with_websocket_closer(Arc::new(move |user_id, connection_id| {
+                let ws_manager = ws_closer.clone();
+                tokio::spawn(async move {
+                    ws_manager
+                        .disconnect_client(user_id, connection_id.as_uuid(), "Too many connections")
+                        .await;
+                });
+            })),
+    );
+
+    // Initialize QUIC resumption tokens (full auth on every reconnect without Redis)
+    let quic_resumption = Arc::new(ResumptionStore::new(redis, quic_config.resumption_ttl_secs));
+
+    // Initialize QUIC metrics collector over the shared connection manager
+    let quic_metrics = Arc::new(QuicMetrics::new(connection_manager.clone()));
 
     // Initialize stream allocator (for QUIC stream management)
     let stream_allocator = Arc::new(StreamAllocator::new());
 
+    // Initialize dead-letter log for unroutable QUIC messages
+    let dead_letters = Arc::new(DeadLetterLog::new(
+        config.dead_letter_enabled,
+        config.dead_letter_capacity,
+    ));
+
+    if config.admin_ip_allowlist.is_empty() {
+        tracing::warn!(
+            "ADMIN_IP_ALLOWLIST is not set; admin routes are reachable from any address (admin auth still required)"
+        );
+    } else {
+        tracing::info!(
+            "Admin routes restricted to {} network(s)",
+            config.admin_ip_allowlist.len()
+        );
+    }
+
+    if config.maintenance_mode {
+        tracing::warn!("Starting in maintenance mode; writes are rejected");
+    }
+    let maintenance_mode = AtomicBool::new(config.maintenance_mode);
+
     let state = Arc::new(AppState {
         db,
         config,
         ws_manager: ws_manager.clone(),
         rate_limiter,
+        login_rate_limiter,
+        slow_mode,
+        upload_quota,
+        flood_guard,
+        reaction_limiter,
+        reaction_debouncer,
+        unfurl,
+        offline_queue,
+        push,
         bot_dispatcher,
         connection_manager,
+        quic_config,
+        quic_resumption,
         stream_allocator,
+        dead_letters,
+        quic_metrics,
+        entity_counts: EntityCountsCache::new(ENTITY_COUNTS_TTL),
+        maintenance_mode,
     });
 
+    // Delete expired disappearing messages in the background
+    services::DisappearingMessageService::spawn_reaper(
+        state.clone(),
+        std::time::Duration::from_secs(state.config.disappearing_reaper_interval_seconds.max(1)),
+    );
+
+    // Prune messages past their retention period in the background
+    services::MessageRetentionService::spawn_pruner(
+        state.clone(),
+        std::time::Duration::from_secs(state.config.retention_prune_interval_seconds.max(1)),
+    );
+
+    // Purge deleted chats past their recovery window in the background
+    services::ChatPurgeService::spawn_purger(
+        state.clone(),
+        std::time::Duration::from_secs(state.config.chat_purge_interval_seconds.max(1)),
+    );
+
+    // Deliver new-message events whose sender didn't finish delivering them
+    services::OutboxService::spawn_relay(
+        state.clone(),
+        std::time::Duration::from_secs(state.config.outbox_relay_interval_seconds.max(1)),
+    );
+
+    // Remove chat export archives past their download window
+    services::ExportService::spawn_sweeper(
+        state.clone(),
+        services::export::EXPORT_SWEEP_INTERVAL,
+    );
+
+    // Write buffered bot usage counters in the background
+    services::bot_engine::BotStatsBuffer::spawn_flusher(
+        state.clone(),
+        services::bot_engine::STATS_FLUSH_INTERVAL,
+    );
+
+    // Drop callback queries bots can no longer answer
+    services::bot_engine::CallbackQueryService::spawn_pruner(
+        state.clone(),
+        services::bot_engine::CALLBACK_PRUNE_INTERVAL,
+    );
+
     let app = Router::new()
         .route("/health", get(health_check))
         .route("/ws", get(ws::ws_handler))
         .route("/bot/ws", get(ws::bot_ws_handler))
-        .nest("/api/v1", routes::api_routes())
-        .merge(routes::bot_api_routes()) // Bot API routes at root level (/bot:token/*)
+        .nest("/api/v1", routes::api_routes(state.clone()))
+        .merge(routes::bot_api_routes(state.clone())) // Bot API routes at root level (/bot:token/*)
         // Serve static files from uploads directory
         .nest_service("/uploads", ServeDir::new("uploads"))
-        .layer(DefaultBodyLimit::max(500 * 1024 * 1024)) // 500MB body limit
-        .layer(
-            CorsLayer::new()
-                .allow_origin(Any)
-                .allow_methods(Any)
-                .allow_headers(Any)
-                .expose_headers(Any)
-                .allow_credentials(false),
-        )
+        .layer(DefaultBodyLimit::max(
+            state.config.max_upload_bytes + routes::upload::MULTIPART_OVERHEAD_BYTES,
+        ))
+        .layer(middleware::from_fn(services::request_id::middleware))
+        .layer(cors_layer(&state.config)?)
         .layer(TraceLayer::new_for_http())
         .with_state(state.clone());
 
     Ok((app, state))
 }
 
+/// Build the CORS layer: an explicit credentialed allowlist when
+/// `CORS_ALLOWED_ORIGINS` is set, otherwise permissive for development.
+fn cors_layer(config: &Config) -> Result<CorsLayer> {
+    if config.cors_allowed_origins.is_empty() {
+        tracing::warn!(
+            "CORS_ALLOWED_ORIGINS is not set; allowing any origin. Do not use this in production."
+        );
+        return Ok(CorsLayer::new()
+            .allow_origin(Any)
+            .allow_methods(Any)
+            .allow_headers(Any)
+            .expose_headers(Any)
+            .allow_credentials(false));
+    }
+
+    let origins = config
+        .cors_allowed_origins
+        .iter()
+        .map(|origin| HeaderValue::from_str(origin))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    tracing::info!("CORS restricted to {} origin(s)", origins.len());
+
+    // Wildcards are not allowed together with credentials
+    Ok(CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+            Method::OPTIONS,
+        ])
+        .allow_headers(AllowHeaders::mirror_request())
+        .expose_headers([
+            HeaderName::from_static(services::request_id::REQUEST_ID_HEADER),
+            HeaderName::from_static(routes::upload::QUOTA_REMAINING_HEADER),
+        ])
+        .allow_credentials(true))
+}
+
 async fn health_check() -> &'static str {
     "OK"
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// State over `db` and `connection_manager` with Redis, push and the QUIC
+    /// listener left out, for tests elsewhere in the crate
+    pub(crate) fn test_state(db: Database, connection_manager: Arc<ConnectionManager>) -> AppState {
+        let config = config::tests::valid_config();
+        let ws_manager = WsManager::new();
+        AppState {
+            rate_limiter: None,
+            login_rate_limiter: LoginRateLimiter::new(None, LoginRateLimitConfig::from(&config)),
+            slow_mode: SlowModeLimiter::new(None),
+            upload_quota: UploadQuota::new(None, UploadQuotaConfig::from(&config)),
+            flood_guard: FloodGuard::new(None, FloodGuardConfig::from(&config)),
+            reaction_limiter: ReactionLimiter::new(None, ReactionLimitConfig::from(&config)),
+            reaction_debouncer: ReactionDebouncer::new(
+                db.clone(),
+                ws_manager.clone(),
+                std::time::Duration::ZERO,
+            ),
+            unfurl: UnfurlService::new(None, UnfurlConfig::from(&config)),
+            offline_queue: OfflineQueue::new(None, OfflineQueueConfig::from(&config)),
+            push: PushService::new(PushConfig::from(&config)),
+            bot_dispatcher: Arc::new(BotDispatcher::new(ws_manager.clone(), &config)),
+            quic_metrics: Arc::new(QuicMetrics::new(connection_manager.clone())),
+            connection_manager,
+            quic_config: QuicServerConfig::default(),
+            quic_resumption: Arc::new(ResumptionStore::new(None, 0)),
+            stream_allocator: Arc::new(StreamAllocator::new()),
+            dead_letters: Arc::new(DeadLetterLog::new(false, 0)),
+            entity_counts: EntityCountsCache::new(ENTITY_COUNTS_TTL),
+            maintenance_mode: AtomicBool::new(false),
+            db,
+            config,
+            ws_manager,
+        }
+    }
+}
@@ -1,6 +1,12 @@
 use anyhow::Result;
-use chat_backend::{config::Config, create_app, quic};
+use chat_backend::{
+    config::{Config, LogFormat, DEFAULT_LOG_FILTER},
+    create_app, quic,
+    services::{AuthService, WebSocketService},
+};
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
@@ -10,16 +16,11 @@ async fn main() -> Result<()> {
         .install_default()
         .expect("Failed to install rustls crypto provider");
 
-    // Initialize tracing
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::try_from_default_env()
-            .unwrap_or_else(|_| "chat_backend=debug,tower_http=debug".into()))
-        .with(tracing_subscriber::fmt::layer())
-        .init();
-
-    // Load configuration
+    // Load configuration (before tracing, which reads LOG_FORMAT and RUST_LOG)
     dotenvy::dotenv().ok();
     let config = Config::from_env()?;
+    init_tracing(config.log_format);
+    config.validate()?;
     
     // Create uploads directory if it doesn't exist
     tokio::fs::create_dir_all("uploads").await?;
@@ -32,7 +33,7 @@ async fn main() -> Result<()> {
     let (app, app_state) = create_app(config.clone()).await?;
 
     // Initialize QUIC server if enabled
-    let quic_config = quic::QuicServerConfig::from_env()?;
+    let quic_config = app_state.quic_config.clone();
     let quic_server_handle = if quic_config.enabled {
         tracing::info!("QUIC is enabled, initializing QUIC server...");
         
@@ -45,10 +46,25 @@ async fn main() -> Result<()> {
         // Share connection manager between transports
         let connection_manager = Arc::clone(&app_state.connection_manager);
         quic_server.set_connection_manager(connection_manager);
+
+        // Let dropped clients reconnect with a resumption token
+        quic_server.set_resumption_store(Arc::clone(&app_state.quic_resumption));
         
         // Set stream allocator
         let stream_allocator = Arc::clone(&app_state.stream_allocator);
-        quic_server.set_stream_allocator(stream_allocator);
+        quic_server.set_stream_allocator(Arc::clone(&stream_allocator));
+
+        // Report rejected handshakes through the shared QUIC metrics
+        quic_server.set_handshake_counters(app_state.quic_metrics.handshake_counters());
+
+        // Turn per-message-type traffic counters into per-second rates
+        tokio::spawn(stream_allocator.traffic().run_sampler(Duration::from_secs(10)));
+
+        // Close streams left idle beyond their per-message-type timeout
+        tokio::spawn(stream_allocator.run_idle_sweeper(
+            quic_config.stream_idle_timeouts,
+            Duration::from_secs(5),
+        ));
         
         // Initialize QUIC server
         quic_server.initialize().await?;
@@ -76,7 +92,9 @@ async fn main() -> Result<()> {
     // Start HTTP/WebSocket server
     let listener = tokio::net::TcpListener::bind(&addr).await?;
     let http_handle = tokio::spawn(async move {
-        if let Err(e) = axum::serve(listener, app).await {
+        // Peer addresses are needed to identify clients for login rate limiting
+        let service = app.into_make_service_with_connect_info::<SocketAddr>();
+        if let Err(e) = axum::serve(listener, service).await {
             tracing::error!("HTTP server error: {}", e);
         }
     });
@@ -105,6 +123,35 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Install the global subscriber: `RUST_LOG` (or `DEFAULT_LOG_FILTER`) picks
+/// the levels, `format` the output
+///
+/// JSON lines carry the fields of the current span, so the `request_id` of
+/// the request being handled shows up under `span`.
+fn init_tracing(format: LogFormat) {
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| DEFAULT_LOG_FILTER.into());
+    let (pretty, json) = match format {
+        LogFormat::Pretty => (Some(tracing_subscriber::fmt::layer()), None),
+        LogFormat::Json => (
+            None,
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .flatten_event(true)
+                    .with_current_span(true)
+                    .with_span_list(false),
+            ),
+        ),
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(pretty)
+        .with(json)
+        .init();
+}
+
 /// Run the QUIC server connection acceptance loop
 async fn run_quic_server(
     quic_server: Arc<quic::QuicServer>,
@@ -117,12 +164,58 @@ async fn run_quic_server(
         async move {
             // Authenticate and register the connection
             match server.authenticate_and_register_connection(connection.clone()).await {
-                Ok((connection_id, user_id, user_name)) => {
+                Ok((connection_id, client)) => {
+                    let (user_id, user_name) = (client.user_id, client.user_name);
                     tracing::info!(
                         "QUIC connection authenticated: connection_id={}, user_id={}, user_name={}",
                         connection_id, user_id, user_name
                     );
+
+                    // Checked after registering so an admin disconnect or a terminated
+                    // session racing this handshake either closes the connection or
+                    // is seen here. A resumed session is checked against its original
+                    // JWT auth
+                    let revoked = AuthService::is_realtime_access_revoked(
+                        &state.db,
+                        user_id,
+                        client.session_id,
+                        client.authenticated_at,
+                        state.config.realtime_require_session,
+                    )
+                    .await
+                    .unwrap_or_else(|e| {
+                        tracing::error!("Failed to check token revocation: {}", e);
+                        true
+                    });
+                    if revoked {
+                        connection.close(quic::ADMIN_DISCONNECT_CLOSE_CODE.into(), b"token revoked");
+                        if let Err(e) = state.connection_manager.unregister_connection(connection_id).await {
+                            tracing::error!(
+                                "Failed to unregister connection {}: {}",
+                                connection_id, e
+                            );
+                        }
+                        return Ok(());
+                    }
                     
+                    // Messages that arrived while the user had no connection
+                    for event in WebSocketService::offline_events(&state, user_id).await {
+                        let sent = match serde_json::to_vec(&event) {
+                            Ok(data) => state
+                                .connection_manager
+                                .send_message(connection_id, &data)
+                                .await
+                                .map_err(|e| e.to_string()),
+                            Err(e) => Err(e.to_string()),
+                        };
+                        if let Err(e) = sent {
+                            tracing::warn!(
+                                "Failed to send queued event to connection {}: {}",
+                                connection_id, e
+                            );
+                        }
+                    }
+
                     // Create message router for this connection
                     let message_router = quic::MessageRouter::new(
                         Arc::clone(&state),
@@ -136,6 +229,7 @@ async fn run_quic_server(
                         user_id,
                         user_name,
                         message_router,
+                        &state,
                     ).await {
                         tracing::error!(
                             "Error handling QUIC connection {}: {}",
@@ -144,12 +238,16 @@ async fn run_quic_server(
                     }
                     
                     // Unregister connection when done
-                    if let Err(e) = state.connection_manager.unregister_connection(connection_id).await {
-                        tracing::error!(
+                    match state.connection_manager.unregister_connection(connection_id).await {
+                        // Already dropped to make room for a newer connection of the user
+                        Ok(()) | Err(quic::ConnectionManagerError::ConnectionNotFound(_)) => {}
+                        Err(e) => tracing::error!(
                             "Failed to unregister connection {}: {}",
                             connection_id, e
-                        );
+                        ),
                     }
+                    // The dropped connection can no longer send StopTyping itself
+                    state.ws_manager.clear_typing(user_id).await;
                     
                     Ok(())
                 }
@@ -171,11 +269,18 @@ async fn handle_quic_connection(
     user_id: uuid::Uuid,
     user_name: String,
     message_router: quic::MessageRouter,
+    state: &chat_backend::AppState,
 ) -> Result<()> {
     tracing::info!(
         "Handling QUIC connection: connection_id={}, user_id={}",
         connection_id, user_id
     );
+    let stream_allocator = &state.stream_allocator;
+    let dead_letters = &state.dead_letters;
+    let stream_priorities = &state.quic_config.stream_priorities;
+
+    // Track this connection's streams so idle ones can be swept
+    stream_allocator.register_connection(connection_id).await;
     
     // Accept and handle bidirectional streams
     loop {
@@ -186,9 +291,50 @@ async fn handle_quic_connection(
                     connection_id
                 );
                 
+                // Clients open streams in the per-type id ranges; anything
+                // outside them is treated as chat traffic
+                let msg_type = quic::MessageType::from_stream_id(recv_stream.id().index())
+                    .unwrap_or(quic::MessageType::ChatMessage);
+                let stream_id = match stream_allocator.allocate_stream(connection_id, msg_type).await {
+                    Ok(stream_id) => stream_id,
+                    Err(e) => {
+                        tracing::warn!(
+                            "Refusing stream on connection {}: {}",
+                            connection_id, e
+                        );
+                        let _ = recv_stream.stop(0u32.into());
+                        let _ = send_stream.reset(0u32.into());
+                        continue;
+                    }
+                };
+                // Responses to control traffic go out ahead of file transfers
+                if let Err(e) = send_stream.set_priority(msg_type.priority(stream_priorities)) {
+                    tracing::debug!(
+                        "Failed to set priority of stream {} on connection {}: {}",
+                        stream_id, connection_id, e
+                    );
+                }
+
                 // Read message from stream
-                match recv_stream.read_to_end(1024 * 1024).await {
-                    Ok(data) => {
+                let read = read_stream_tracked(
+                    &mut recv_stream,
+                    stream_allocator,
+                    connection_id,
+                    stream_id,
+                    quic::MAX_STREAM_MESSAGE_BYTES,
+                ).await;
+                match read {
+                    Ok(None) => {
+                        // Released by the idle sweeper
+                        tracing::info!(
+                            "Closing idle stream {} on connection {}",
+                            stream_id, connection_id
+                        );
+                        let _ = recv_stream.stop(quic::STREAM_IDLE_CLOSE_CODE.into());
+                        let _ = send_stream.reset(quic::STREAM_IDLE_CLOSE_CODE.into());
+                        continue;
+                    }
+                    Ok(Some(data)) => {
                         tracing::debug!(
                             "Received {} bytes from connection {}",
                             data.len(), connection_id
@@ -197,6 +343,7 @@ async fn handle_quic_connection(
                         // Route the message
                         match message_router.route_message(
                             &data,
+                            msg_type,
                             connection_id,
                             user_id,
                             &user_name,
@@ -230,8 +377,15 @@ async fn handle_quic_connection(
                                     "Failed to route message from connection {}: {}",
                                     connection_id, e
                                 );
+                                e.record_dead_letter(
+                                    dead_letters,
+                                    &data,
+                                    connection_id,
+                                    user_id,
+                                );
                                 // Send error response
                                 let error_msg = format!("{{\"error\":\"{}\"}}", e);
+                                stream_allocator.traffic().record_sent(msg_type, error_msg.len());
                                 if let Err(e) = send_stream.write_all(error_msg.as_bytes()).await {
                                     tracing::error!(
                                         "Failed to send error response on connection {}: {}",
@@ -254,6 +408,8 @@ async fn handle_quic_connection(
                         );
                     }
                 }
+
+                let _ = stream_allocator.release_stream(connection_id, stream_id).await;
             }
             Err(e) => {
                 // Connection closed or error
@@ -266,6 +422,38 @@ async fn handle_quic_connection(
         }
     }
     
+    let _ = stream_allocator.unregister_connection(connection_id).await;
+
     tracing::info!("Finished handling QUIC connection: connection_id={}", connection_id);
     Ok(())
+}
+
+/// Read a client stream to the end, resetting its idle timer on every chunk
+///
+/// Returns `Ok(None)` if the idle sweeper released the stream first.
+async fn read_stream_tracked(
+    recv_stream: &mut quic::RecvStream,
+    stream_allocator: &quic::StreamAllocator,
+    connection_id: quic::ConnectionId,
+    stream_id: u64,
+    max_size: usize,
+) -> Result<Option<Vec<u8>>> {
+    let idle_close = stream_allocator.idle_close_signal(connection_id, stream_id).await?;
+    let mut data = Vec::new();
+
+    loop {
+        tokio::select! {
+            _ = idle_close.notified() => return Ok(None),
+            chunk = recv_stream.read_chunk(max_size, true) => match chunk? {
+                Some(chunk) => {
+                    data.extend_from_slice(&chunk.bytes);
+                    if data.len() > max_size {
+                        anyhow::bail!("stream exceeded {} bytes", max_size);
+                    }
+                    let _ = stream_allocator.touch_stream(connection_id, stream_id).await;
+                }
+                None => return Ok(Some(data)),
+            }
+        }
+    }
 }
\ No newline at end of file
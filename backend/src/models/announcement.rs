@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+/// Severity of a system announcement, so clients can style it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnouncementLevel {
+    #[default]
+    Info,
+    Warning,
+    Critical,
+}
+
+impl AnnouncementLevel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AnnouncementLevel::Info => "info",
+            AnnouncementLevel::Warning => "warning",
+            AnnouncementLevel::Critical => "critical",
+        }
+    }
+}
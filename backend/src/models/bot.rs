@@ -3,7 +3,7 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 
-use super::InlineButton;
+use super::{InlineButton, ParseMode};
 
 /// Bot entity stored in the database
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -17,8 +17,70 @@ pub struct Bot {
     pub is_active: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Sent as X-Giano-Webhook-Secret on every webhook delivery
+    #[serde(skip_serializing)]
+    pub webhook_secret: Option<String>,
+    /// Maximum number of concurrent webhook deliveries
+    pub webhook_max_connections: i32,
+    /// Update types delivered to the bot (None = all)
+    pub webhook_allowed_updates: Option<Vec<String>>,
+    /// Exempt from the bot API rate limit; set by server admins only
+    pub privileged: bool,
+    /// Webhook payload schema version the bot expects (None = latest)
+    pub webhook_schema_version: Option<i32>,
 }
 
+impl Bot {
+    /// Check if the bot wants to receive updates of the given type
+    pub fn accepts_update(&self, update_type: &str) -> bool {
+        match &self.webhook_allowed_updates {
+            Some(allowed) => allowed.iter().any(|u| u == update_type),
+            None => true,
+        }
+    }
+}
+
+/// Update types a bot can subscribe to via allowed_updates
+pub const UPDATE_MESSAGE: &str = "message";
+pub const UPDATE_REACTION: &str = "reaction";
+pub const UPDATE_MEMBER_JOINED: &str = "member_joined";
+pub const UPDATE_CALLBACK_QUERY: &str = "callback_query";
+pub const UPDATE_TYPES: &[&str] = &[
+    UPDATE_MESSAGE,
+    UPDATE_REACTION,
+    UPDATE_MEMBER_JOINED,
+    UPDATE_CALLBACK_QUERY,
+];
+
+/// A command a bot advertises in its menu (setMyCommands)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BotMenuCommand {
+    /// Command name without the leading slash
+    pub command: String,
+    pub description: String,
+}
+
+/// setMyCommands request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetMyCommandsRequest {
+    pub commands: Vec<BotMenuCommand>,
+}
+
+/// A menu command of a bot in a chat, offered while the user types `/`
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatCommandSuggestion {
+    #[serde(rename = "botId")]
+    pub bot_id: Uuid,
+    #[serde(rename = "botUsername")]
+    pub bot_username: Option<String>,
+    pub command: String,
+    pub description: String,
+}
+
+/// Default and maximum for webhook max_connections
+pub const DEFAULT_WEBHOOK_MAX_CONNECTIONS: i32 = 40;
+pub const MAX_WEBHOOK_MAX_CONNECTIONS: i32 = 100;
+
 /// Bot permission record linking a bot to a scope
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct BotPermission {
@@ -43,8 +105,18 @@ pub struct BotResponse {
     pub token: String,
     #[serde(rename = "webhookUrl")]
     pub webhook_url: Option<String>,
+    #[serde(rename = "hasWebhookSecret")]
+    pub has_webhook_secret: bool,
+    #[serde(rename = "webhookMaxConnections")]
+    pub webhook_max_connections: i32,
+    #[serde(rename = "webhookAllowedUpdates")]
+    pub webhook_allowed_updates: Option<Vec<String>>,
+    #[serde(rename = "webhookSchemaVersion")]
+    pub webhook_schema_version: Option<i32>,
     #[serde(rename = "isActive")]
     pub is_active: bool,
+    #[serde(rename = "isPrivileged")]
+    pub is_privileged: bool,
     #[serde(rename = "createdAt")]
     pub created_at: DateTime<Utc>,
 }
@@ -57,7 +129,12 @@ impl From<Bot> for BotResponse {
             username: bot.username,
             token: bot.token,
             webhook_url: bot.webhook_url,
+            has_webhook_secret: bot.webhook_secret.is_some(),
+            webhook_max_connections: bot.webhook_max_connections,
+            webhook_allowed_updates: bot.webhook_allowed_updates,
+            webhook_schema_version: bot.webhook_schema_version,
             is_active: bot.is_active,
+            is_privileged: bot.privileged,
             created_at: bot.created_at,
         }
     }
@@ -103,6 +180,37 @@ pub struct UpdateBotRequest {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SetWebhookRequest {
     pub url: Option<String>,
+    #[serde(flatten)]
+    pub options: WebhookOptions,
+}
+
+/// Optional webhook delivery settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WebhookOptions {
+    #[serde(rename = "secretToken")]
+    pub secret_token: Option<String>,
+    #[serde(rename = "maxConnections")]
+    pub max_connections: Option<i32>,
+    #[serde(rename = "allowedUpdates")]
+    pub allowed_updates: Option<Vec<String>>,
+    /// Payload schema version to deliver (None = always the latest)
+    #[serde(rename = "schemaVersion")]
+    pub schema_version: Option<i32>,
+}
+
+/// Outcome of a test delivery to a bot's webhook; nothing about it is stored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookTestResult {
+    pub url: String,
+    /// HTTP status of the response, `None` when none arrived
+    pub status: Option<u16>,
+    #[serde(rename = "latencyMs")]
+    pub latency_ms: u64,
+    /// Start of the response body
+    #[serde(rename = "bodySnippet")]
+    pub body_snippet: Option<String>,
+    /// Why no response arrived (timeout, connection refused, ...)
+    pub error: Option<String>,
 }
 
 /// Request for bot to send a message
@@ -114,6 +222,97 @@ pub struct BotSendMessageRequest {
     pub reply_to_id: Option<Uuid>,
     #[serde(rename = "inlineKeyboard")]
     pub inline_keyboard: Option<Vec<Vec<InlineButton>>>,
+    /// `markdown` or `html` to send formatted text (default: plain)
+    #[serde(rename = "parseMode", alias = "parse_mode", default)]
+    pub parse_mode: ParseMode,
+}
+
+/// sendBulk request: one message delivered to several chats
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BotSendBulkRequest {
+    #[serde(alias = "chatIds")]
+    pub chat_ids: Vec<Uuid>,
+    pub text: String,
+    #[serde(rename = "inlineKeyboard")]
+    pub inline_keyboard: Option<Vec<Vec<InlineButton>>>,
+    #[serde(rename = "parseMode", alias = "parse_mode", default)]
+    pub parse_mode: ParseMode,
+}
+
+/// A press of an inline keyboard button, forwarded to the bot that sent
+/// the message
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct CallbackQuery {
+    pub id: Uuid,
+    pub bot_id: Uuid,
+    /// The user who pressed the button
+    pub user_id: Uuid,
+    pub chat_id: Uuid,
+    pub message_id: Uuid,
+    pub data: String,
+    pub created_at: DateTime<Utc>,
+    pub answered_at: Option<DateTime<Utc>>,
+}
+
+/// answerCallbackQuery request: acknowledge a button press, optionally with
+/// a notice shown to the user who pressed it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnswerCallbackQueryRequest {
+    #[serde(rename = "callbackQueryId", alias = "callback_query_id")]
+    pub callback_query_id: Uuid,
+    pub text: Option<String>,
+    /// Show the text as an alert instead of a toast
+    #[serde(rename = "showAlert", alias = "show_alert", default)]
+    pub show_alert: bool,
+}
+
+/// editMessageText request: replace the text (and keyboard) of one of the
+/// bot's own messages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BotEditMessageTextRequest {
+    #[serde(alias = "chatId")]
+    pub chat_id: Uuid,
+    #[serde(alias = "messageId")]
+    pub message_id: Uuid,
+    pub text: String,
+    /// Replaces the keyboard when given; an empty keyboard removes it
+    #[serde(rename = "inlineKeyboard")]
+    pub inline_keyboard: Option<Vec<Vec<InlineButton>>>,
+    #[serde(rename = "parseMode", alias = "parse_mode", default)]
+    pub parse_mode: ParseMode,
+}
+
+/// Per-chat outcome of a sendBulk call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BotBulkSendResult {
+    #[serde(rename = "chatId")]
+    pub chat_id: Uuid,
+    /// "sent" or "failed"
+    pub status: String,
+    #[serde(rename = "messageId", skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl BotBulkSendResult {
+    pub fn sent(chat_id: Uuid, message_id: Uuid) -> Self {
+        Self {
+            chat_id,
+            status: "sent".to_string(),
+            message_id: Some(message_id),
+            error: None,
+        }
+    }
+
+    pub fn failed(chat_id: Uuid, error: impl Into<String>) -> Self {
+        Self {
+            chat_id,
+            status: "failed".to_string(),
+            message_id: None,
+            error: Some(error.into()),
+        }
+    }
 }
 
 /// Webhook payload sent to bot's webhook URL
@@ -0,0 +1,103 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// How a call turned out (`call_logs.outcome`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CallOutcome {
+    /// Still waiting for the callee
+    Ringing,
+    Accepted,
+    Declined,
+    /// Not answered before the ring timeout or cancelled by the caller
+    Missed,
+    /// Callee was already in another call
+    Busy,
+}
+
+impl CallOutcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CallOutcome::Ringing => "ringing",
+            CallOutcome::Accepted => "accepted",
+            CallOutcome::Declined => "declined",
+            CallOutcome::Missed => "missed",
+            CallOutcome::Busy => "busy",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "ringing" => Some(CallOutcome::Ringing),
+            "accepted" => Some(CallOutcome::Accepted),
+            "declined" => Some(CallOutcome::Declined),
+            "missed" => Some(CallOutcome::Missed),
+            "busy" => Some(CallOutcome::Busy),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct CallLog {
+    pub id: Uuid,
+    pub chat_id: Uuid,
+    pub caller_id: Uuid,
+    pub callee_id: Uuid,
+    pub call_type: String,
+    pub outcome: String,
+    pub started_at: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
+/// A call history entry as seen by one of its participants
+#[derive(Debug, Clone, Serialize)]
+pub struct CallLogResponse {
+    pub id: Uuid,
+    #[serde(rename = "chatId")]
+    pub chat_id: Uuid,
+    #[serde(rename = "callerId")]
+    pub caller_id: Uuid,
+    #[serde(rename = "calleeId")]
+    pub callee_id: Uuid,
+    #[serde(rename = "callType")]
+    pub call_type: String,
+    pub outcome: CallOutcome,
+    /// "outgoing" for the caller, "incoming" for the callee
+    pub direction: &'static str,
+    #[serde(rename = "startedAt")]
+    pub started_at: DateTime<Utc>,
+    #[serde(rename = "acceptedAt")]
+    pub accepted_at: Option<DateTime<Utc>>,
+    #[serde(rename = "endedAt")]
+    pub ended_at: Option<DateTime<Utc>>,
+    /// Talk time from accept to end, for finished accepted calls
+    #[serde(rename = "durationSeconds")]
+    pub duration_seconds: Option<i64>,
+}
+
+impl CallLogResponse {
+    pub fn for_user(log: CallLog, user_id: Uuid) -> Self {
+        let duration_seconds = match (log.accepted_at, log.ended_at) {
+            (Some(accepted), Some(ended)) => Some((ended - accepted).num_seconds().max(0)),
+            _ => None,
+        };
+
+        Self {
+            id: log.id,
+            chat_id: log.chat_id,
+            caller_id: log.caller_id,
+            callee_id: log.callee_id,
+            call_type: log.call_type,
+            outcome: CallOutcome::parse(&log.outcome).unwrap_or(CallOutcome::Missed),
+            direction: if log.caller_id == user_id { "outgoing" } else { "incoming" },
+            started_at: log.started_at,
+            accepted_at: log.accepted_at,
+            ended_at: log.ended_at,
+            duration_seconds,
+        }
+    }
+}
@@ -12,9 +12,70 @@ pub struct Chat {
     pub chat_type: String,
     pub name: Option<String>,
     pub avatar: Option<String>,
+    pub description: Option<String>,
     pub created_by: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub slow_mode_seconds: i32,
+    pub disappear_after_seconds: i32,
+    pub link_previews_enabled: bool,
+    /// Message text is end-to-end encrypted by the clients
+    pub e2ee: bool,
+    /// Set while the chat is deleted but can still be restored
+    #[sqlx(default)]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+impl Chat {
+    /// The chat's kind, `None` for types this server doesn't know
+    pub fn kind(&self) -> Option<ChatType> {
+        ChatType::parse(&self.chat_type)
+    }
+
+    /// Whether the chat has admins and members (groups and channels)
+    pub fn has_admins(&self) -> bool {
+        self.kind().is_some_and(ChatType::has_admins)
+    }
+}
+
+/// Kind of chat, stored in `chats.type`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatType {
+    /// Direct messages between two users
+    Private,
+    Group,
+    /// Broadcast-only group: every member reads, only admins post
+    Channel,
+    /// Direct messages with a bot
+    Bot,
+}
+
+impl ChatType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChatType::Private => "private",
+            ChatType::Group => "group",
+            ChatType::Channel => "channel",
+            ChatType::Bot => "bot",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            // Direct chats opened through invite links
+            "private" | "direct" => Some(ChatType::Private),
+            "group" => Some(ChatType::Group),
+            "channel" => Some(ChatType::Channel),
+            "bot" => Some(ChatType::Bot),
+            _ => None,
+        }
+    }
+
+    /// Groups and channels have admins and members; private and bot chats
+    /// are between two equal parties
+    pub fn has_admins(self) -> bool {
+        matches!(self, ChatType::Group | ChatType::Channel)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -49,6 +110,22 @@ pub struct ChatResponse {
     pub is_bot: bool,
     #[serde(rename = "isPinned")]
     pub is_pinned: bool,
+    #[serde(rename = "isArchived")]
+    pub is_archived: bool,
+    pub folder: Option<String>,
+    #[serde(rename = "isMuted")]
+    pub is_muted: bool,
+}
+
+/// A user's private view state of a chat (archived flag, folder label, mute)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, FromRow)]
+pub struct ChatUserState {
+    #[serde(rename = "isArchived")]
+    pub archived: bool,
+    pub folder: Option<String>,
+    /// No push notifications for this chat
+    #[serde(rename = "isMuted", default)]
+    pub muted: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -58,6 +135,7 @@ pub struct ChatDetailResponse {
     pub chat_type: String,
     pub name: String,
     pub avatar: Option<String>,
+    pub description: Option<String>,
     pub participants: Vec<Uuid>,
     #[serde(rename = "unreadCount")]
     pub unread_count: i32,
@@ -65,4 +143,10 @@ pub struct ChatDetailResponse {
     pub is_typing: bool,
     #[serde(rename = "isBot")]
     pub is_bot: bool,
+    #[serde(rename = "slowModeSeconds")]
+    pub slow_mode_seconds: i32,
+    #[serde(rename = "disappearAfterSeconds")]
+    pub disappear_after_seconds: i32,
+    /// Clients must encrypt message text (see `POST /chats/:id/keys`)
+    pub e2ee: bool,
 }
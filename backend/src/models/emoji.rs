@@ -0,0 +1,52 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// Uploaded emoji image referenced as `:shortcode:`
+#[derive(Debug, Clone, FromRow)]
+pub struct CustomEmoji {
+    pub id: Uuid,
+    /// Chat the emoji belongs to; `None` for global emoji
+    pub chat_id: Option<Uuid>,
+    pub shortcode: String,
+    /// File name under the uploads directory
+    pub stored_name: String,
+    pub url: String,
+    pub mime_type: String,
+    pub size_bytes: i32,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Custom emoji metadata clients need to render it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomEmojiResponse {
+    pub id: Uuid,
+    pub shortcode: String,
+    pub url: String,
+    #[serde(rename = "mimeType")]
+    pub mime_type: String,
+    #[serde(rename = "chatId", skip_serializing_if = "Option::is_none")]
+    pub chat_id: Option<Uuid>,
+    #[serde(rename = "createdBy", skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<Uuid>,
+}
+
+impl CustomEmoji {
+    /// How the emoji is written in text and stored on reactions
+    pub fn reference(&self) -> String {
+        format!(":{}:", self.shortcode)
+    }
+
+    pub fn to_response(&self) -> CustomEmojiResponse {
+        CustomEmojiResponse {
+            id: self.id,
+            shortcode: self.shortcode.clone(),
+            url: self.url.clone(),
+            mime_type: self.mime_type.clone(),
+            chat_id: self.chat_id,
+            created_by: self.created_by,
+        }
+    }
+}
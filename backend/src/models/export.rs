@@ -0,0 +1,121 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// Progress of a chat export (`chat_exports.status`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportStatus {
+    /// Waiting for the background job to start
+    Pending,
+    Running,
+    /// Archive written and downloadable until it expires
+    Ready,
+    Failed,
+    /// Archive removed after its download window
+    Expired,
+}
+
+impl ExportStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExportStatus::Pending => "pending",
+            ExportStatus::Running => "running",
+            ExportStatus::Ready => "ready",
+            ExportStatus::Failed => "failed",
+            ExportStatus::Expired => "expired",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(ExportStatus::Pending),
+            "running" => Some(ExportStatus::Running),
+            "ready" => Some(ExportStatus::Ready),
+            "failed" => Some(ExportStatus::Failed),
+            "expired" => Some(ExportStatus::Expired),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct ChatExport {
+    pub id: Uuid,
+    pub chat_id: Uuid,
+    pub user_id: Uuid,
+    pub status: String,
+    /// Bundle attachment files into a zip instead of listing their URLs
+    pub include_media: bool,
+    /// File name under the exports directory, once ready
+    pub file_name: Option<String>,
+    pub size_bytes: Option<i64>,
+    pub message_count: Option<i32>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// An export as reported to the user who requested it
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatExportResponse {
+    pub id: Uuid,
+    #[serde(rename = "chatId")]
+    pub chat_id: Uuid,
+    pub status: ExportStatus,
+    #[serde(rename = "includeMedia")]
+    pub include_media: bool,
+    /// `application/zip` with media, `application/x-ndjson` without
+    pub format: &'static str,
+    #[serde(rename = "sizeBytes", skip_serializing_if = "Option::is_none")]
+    pub size_bytes: Option<i64>,
+    #[serde(rename = "messageCount", skip_serializing_if = "Option::is_none")]
+    pub message_count: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Relative download URL, once the archive is ready
+    #[serde(rename = "downloadUrl", skip_serializing_if = "Option::is_none")]
+    pub download_url: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename = "completedAt", skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+    #[serde(rename = "expiresAt", skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl ChatExport {
+    pub fn status(&self) -> ExportStatus {
+        ExportStatus::parse(&self.status).unwrap_or(ExportStatus::Failed)
+    }
+
+    /// Content type of the archive
+    pub fn format(&self) -> &'static str {
+        if self.include_media {
+            "application/zip"
+        } else {
+            "application/x-ndjson"
+        }
+    }
+
+    pub fn to_response(&self) -> ChatExportResponse {
+        let status = self.status();
+        ChatExportResponse {
+            id: self.id,
+            chat_id: self.chat_id,
+            status,
+            include_media: self.include_media,
+            format: self.format(),
+            size_bytes: self.size_bytes,
+            message_count: self.message_count,
+            error: self.error.clone(),
+            download_url: (status == ExportStatus::Ready)
+                .then(|| format!("/api/v1/exports/{}/download", self.id)),
+            created_at: self.created_at,
+            completed_at: self.completed_at,
+            expires_at: self.expires_at,
+        }
+    }
+}
@@ -14,6 +14,8 @@ pub struct InviteLink {
     pub max_uses: Option<i32>,
     pub current_uses: i32,
     pub is_active: bool,
+    #[sqlx(default)]
+    pub revoked_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -3,6 +3,8 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 
+use super::CustomEmojiResponse;
+
 /// Represents the sender of a message - either a User or a Bot
 /// 
 /// This enum is used to distinguish between messages sent by human users
@@ -61,7 +63,7 @@ pub struct Message {
     pub id: Uuid,
     pub chat_id: Uuid,
     pub sender_id: Uuid,
-    /// The type of sender: "user" or "bot"
+    /// The type of sender: "user", "bot" or "system"
     #[sqlx(default)]
     pub sender_type: Option<String>,
     pub text: Option<String>,
@@ -72,6 +74,44 @@ pub struct Message {
     pub delivery_status: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// When the message disappears (set from the chat's timer at send time)
+    #[sqlx(default)]
+    pub delete_at: Option<DateTime<Utc>>,
+    /// Root message of the thread this message replies in
+    #[sqlx(default)]
+    pub thread_root_id: Option<Uuid>,
+    /// Number of thread replies (on root messages)
+    #[sqlx(default)]
+    pub reply_count: i32,
+    /// Set when a thread root is deleted; the root stays as a tombstone
+    #[sqlx(default)]
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Author of the replied-to message, copied at send time
+    #[sqlx(default)]
+    pub reply_author_id: Option<Uuid>,
+    #[sqlx(default)]
+    pub reply_author_name: Option<String>,
+    /// Truncated text of the replied-to message, copied at send time
+    #[sqlx(default)]
+    pub reply_snippet: Option<String>,
+    /// Text is client-side ciphertext (sent in an e2ee chat)
+    #[sqlx(default)]
+    pub encrypted: bool,
+    /// Message this one is a forwarded copy of
+    #[sqlx(default)]
+    pub forwarded_from_id: Option<Uuid>,
+    /// Original author, copied at forward time; unset when their privacy
+    /// settings don't link forwards back to them
+    #[sqlx(default)]
+    pub forward_author_id: Option<Uuid>,
+    #[sqlx(default)]
+    pub forward_author_name: Option<String>,
+    /// Event a system message records (`SystemEventType`)
+    #[sqlx(default)]
+    pub system_event: Option<String>,
+    /// JSON object with the event's details
+    #[sqlx(default)]
+    pub system_metadata: Option<String>,
 }
 
 impl Message {
@@ -79,6 +119,11 @@ impl Message {
     pub fn sender(&self) -> MessageSender {
         MessageSender::from_parts(self.sender_id, self.sender_type.as_deref())
     }
+
+    /// Whether the server posted this message (announcements, chat events)
+    pub fn is_system(&self) -> bool {
+        self.sender_type.as_deref() == Some("system")
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -93,6 +138,32 @@ pub struct Attachment {
     pub mime_type: Option<String>,
     pub duration: Option<i32>,
     pub created_at: DateTime<Utc>,
+    /// Upload this attachment was created from
+    #[sqlx(default)]
+    pub upload_id: Option<Uuid>,
+    #[sqlx(default)]
+    pub width: Option<i32>,
+    #[sqlx(default)]
+    pub height: Option<i32>,
+}
+
+/// A stored file from `POST /upload`, attachable by its uploader
+#[derive(Debug, Clone, FromRow)]
+pub struct Upload {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    #[sqlx(rename = "type")]
+    pub attachment_type: String,
+    pub name: String,
+    pub size: i64,
+    pub url: String,
+    /// File name under the uploads directory
+    pub stored_name: String,
+    pub mime_type: Option<String>,
+    /// Pixel dimensions for images
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -102,6 +173,92 @@ pub struct Reaction {
     pub user_id: Uuid,
     pub emoji: String,
     pub created_at: DateTime<Utc>,
+    /// Set when `emoji` is a custom emoji's `:shortcode:`
+    pub custom_emoji_id: Option<Uuid>,
+}
+
+/// What happened in a chat, recorded as a system message
+/// (`messages.system_event`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SystemEventType {
+    MessagePinned,
+    MessageUnpinned,
+    MemberJoined,
+    MemberLeft,
+}
+
+impl SystemEventType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SystemEventType::MessagePinned => "message_pinned",
+            SystemEventType::MessageUnpinned => "message_unpinned",
+            SystemEventType::MemberJoined => "member_joined",
+            SystemEventType::MemberLeft => "member_left",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "message_pinned" => Some(SystemEventType::MessagePinned),
+            "message_unpinned" => Some(SystemEventType::MessageUnpinned),
+            "member_joined" => Some(SystemEventType::MemberJoined),
+            "member_left" => Some(SystemEventType::MemberLeft),
+            _ => None,
+        }
+    }
+
+    /// Plain-text version of the event, stored as the message text for
+    /// clients that don't render system events
+    pub fn describe(self, actor_name: &str) -> String {
+        match self {
+            SystemEventType::MessagePinned => format!("{} pinned a message", actor_name),
+            SystemEventType::MessageUnpinned => format!("{} unpinned a message", actor_name),
+            SystemEventType::MemberJoined => format!("{} joined the chat", actor_name),
+            SystemEventType::MemberLeft => format!("{} left the chat", actor_name),
+        }
+    }
+}
+
+/// Structured part of a system message, for clients to render
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SystemEventResponse {
+    #[serde(rename = "type")]
+    pub event_type: SystemEventType,
+    /// Event details: `messageId` for pins, `userId` for members
+    pub metadata: serde_json::Value,
+}
+
+/// Per-recipient delivery status ("ticks")
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// Persisted on the server
+    Sent,
+    /// The recipient's client has the message
+    Delivered,
+    /// The recipient read it and shares read receipts
+    Read,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeliveryStatus::Sent => "sent",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Read => "read",
+        }
+    }
+
+    /// Combine the recipient's signals; a read without receipts enabled only
+    /// counts as delivered
+    pub fn resolve(delivered: bool, read: bool, read_receipts_enabled: bool) -> Self {
+        match (read, delivered) {
+            (true, _) if read_receipts_enabled => DeliveryStatus::Read,
+            (true, _) | (false, true) => DeliveryStatus::Delivered,
+            (false, false) => DeliveryStatus::Sent,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -122,6 +279,10 @@ pub struct AttachmentResponse {
     pub url: String,
     #[serde(rename = "mimeType")]
     pub mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<i32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -129,6 +290,19 @@ pub struct ReactionResponse {
     pub emoji: String,
     #[serde(rename = "userId")]
     pub user_id: Uuid,
+    #[serde(rename = "customEmojiId", skip_serializing_if = "Option::is_none", default)]
+    pub custom_emoji_id: Option<Uuid>,
+}
+
+/// Reactions on a message grouped by emoji
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReactionSummary {
+    pub emoji: String,
+    #[serde(rename = "customEmojiId", skip_serializing_if = "Option::is_none", default)]
+    pub custom_emoji_id: Option<Uuid>,
+    pub count: i64,
+    #[serde(rename = "reactedByMe")]
+    pub reacted_by_me: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -139,6 +313,41 @@ pub struct ReplyToResponse {
     pub sender_id: Uuid,
     #[serde(rename = "senderName")]
     pub sender_name: String,
+    /// One-line preview of the replied-to message
+    pub snippet: String,
+    /// The replied-to message was deleted; `text` is empty and `snippet`
+    /// is a placeholder
+    #[serde(rename = "isDeleted")]
+    pub is_deleted: bool,
+}
+
+/// Original author of a forwarded message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForwardedFromResponse {
+    /// Absent when the author doesn't allow linking forwards to their account
+    #[serde(rename = "senderId", skip_serializing_if = "Option::is_none")]
+    pub sender_id: Option<Uuid>,
+    #[serde(rename = "senderName")]
+    pub sender_name: String,
+}
+
+/// Outcome of forwarding a message to one target chat
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForwardResult {
+    #[serde(rename = "chatId")]
+    pub chat_id: Uuid,
+    /// The copy posted in the chat, when forwarding succeeded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<MessageResponse>,
+    /// Why forwarding to this chat failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ForwardError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForwardError {
+    pub code: String,
+    pub message: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -149,6 +358,191 @@ pub struct ReadByResponse {
     pub read_at: DateTime<Utc>,
 }
 
+/// A group participant whose read pointer is at or past a message
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SeenByUser {
+    #[serde(rename = "userId")]
+    pub user_id: Uuid,
+    pub name: String,
+    pub username: Option<String>,
+    pub avatar: Option<String>,
+}
+
+/// Who has seen a message; `users` is capped, `total` is not
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeenByResponse {
+    pub users: Vec<SeenByUser>,
+    pub total: i64,
+}
+
+/// A participant mentioned by `@username`
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MentionResponse {
+    #[serde(rename = "userId")]
+    pub user_id: Uuid,
+    pub username: Option<String>,
+}
+
+/// How bot message text is interpreted before it is stored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParseMode {
+    /// Text is sent verbatim
+    #[default]
+    None,
+    Markdown,
+    Html,
+}
+
+/// Kind of a formatting entity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityKind {
+    Bold,
+    Italic,
+    Code,
+    Pre,
+    TextLink,
+}
+
+impl EntityKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EntityKind::Bold => "bold",
+            EntityKind::Italic => "italic",
+            EntityKind::Code => "code",
+            EntityKind::Pre => "pre",
+            EntityKind::TextLink => "text_link",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "bold" => Some(EntityKind::Bold),
+            "italic" => Some(EntityKind::Italic),
+            "code" => Some(EntityKind::Code),
+            "pre" => Some(EntityKind::Pre),
+            "text_link" => Some(EntityKind::TextLink),
+            _ => None,
+        }
+    }
+}
+
+/// A formatted span of message text; clients render from these instead of markup
+///
+/// `offset` and `length` count UTF-16 code units of the plain text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageEntity {
+    #[serde(rename = "type")]
+    pub kind: EntityKind,
+    pub offset: usize,
+    pub length: usize,
+    /// Target of a `text_link`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Language of a `pre` block
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+/// Stored form of a `MessageEntity`
+#[derive(Debug, Clone, FromRow)]
+pub struct MessageEntityRow {
+    #[sqlx(rename = "type")]
+    pub entity_type: String,
+    pub start_offset: i32,
+    pub length: i32,
+    pub url: Option<String>,
+    pub language: Option<String>,
+}
+
+impl MessageEntityRow {
+    pub fn into_entity(self) -> Option<MessageEntity> {
+        Some(MessageEntity {
+            kind: EntityKind::parse(&self.entity_type)?,
+            offset: usize::try_from(self.start_offset).ok()?,
+            length: usize::try_from(self.length).ok()?,
+            url: self.url,
+            language: self.language,
+        })
+    }
+}
+
+/// Preview generated server-side for the first link in a message
+#[derive(Debug, Clone, FromRow)]
+pub struct LinkPreview {
+    pub id: Uuid,
+    pub message_id: Uuid,
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    pub site_name: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkPreviewResponse {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    #[serde(rename = "imageUrl")]
+    pub image_url: Option<String>,
+    #[serde(rename = "siteName")]
+    pub site_name: Option<String>,
+}
+
+impl From<LinkPreview> for LinkPreviewResponse {
+    fn from(preview: LinkPreview) -> Self {
+        Self {
+            url: preview.url,
+            title: preview.title,
+            description: preview.description,
+            image_url: preview.image_url,
+            site_name: preview.site_name,
+        }
+    }
+}
+
+/// A poll posted in a chat; its message carries the question as text
+#[derive(Debug, Clone, FromRow)]
+pub struct Poll {
+    pub id: Uuid,
+    pub message_id: Uuid,
+    pub chat_id: Uuid,
+    pub creator_id: Uuid,
+    pub question: String,
+    pub multiple_choice: bool,
+    pub anonymous: bool,
+    pub closed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Vote count of one poll option
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PollTally {
+    #[serde(rename = "optionId")]
+    pub option_id: Uuid,
+    pub text: String,
+    pub votes: i64,
+    /// Who voted for the option, oldest vote first; absent for anonymous polls
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub voters: Option<Vec<Uuid>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PollResponse {
+    pub id: Uuid,
+    pub question: String,
+    #[serde(rename = "multipleChoice")]
+    pub multiple_choice: bool,
+    pub anonymous: bool,
+    #[serde(rename = "isClosed")]
+    pub is_closed: bool,
+    /// Options in the order they were given, with their tallies
+    pub options: Vec<PollTally>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageResponse {
     pub id: Uuid,
@@ -156,7 +550,7 @@ pub struct MessageResponse {
     pub chat_id: Uuid,
     #[serde(rename = "senderId")]
     pub sender_id: Uuid,
-    /// The type of sender: "user" or "bot"
+    /// The type of sender: "user", "bot" or "system" (server notices)
     #[serde(rename = "senderType")]
     pub sender_type: String,
     pub text: Option<String>,
@@ -168,6 +562,10 @@ pub struct MessageResponse {
     #[serde(rename = "isPinned")]
     pub is_pinned: bool,
     pub reactions: Vec<ReactionResponse>,
+    /// Reaction counts per emoji as seen by the requesting user, most used
+    /// first; filled when fetching history, empty elsewhere
+    #[serde(rename = "reactionSummary", default)]
+    pub reaction_summary: Vec<ReactionSummary>,
     pub attachments: Vec<AttachmentResponse>,
     #[serde(rename = "replyTo")]
     pub reply_to: Option<ReplyToResponse>,
@@ -177,6 +575,116 @@ pub struct MessageResponse {
     pub read_by: Vec<ReadByResponse>,
     #[serde(rename = "inlineKeyboard", skip_serializing_if = "Option::is_none")]
     pub inline_keyboard: Option<Vec<Vec<InlineButton>>>,
+    #[serde(rename = "deleteAt", skip_serializing_if = "Option::is_none")]
+    pub delete_at: Option<DateTime<Utc>>,
+    #[serde(rename = "threadRootId", skip_serializing_if = "Option::is_none")]
+    pub thread_root_id: Option<Uuid>,
+    #[serde(rename = "replyCount", default)]
+    pub reply_count: i32,
+    #[serde(rename = "isDeleted", default)]
+    pub is_deleted: bool,
+    /// `text` is end-to-end encrypted; only clients can read it
+    #[serde(default)]
+    pub encrypted: bool,
+    #[serde(rename = "linkPreview", skip_serializing_if = "Option::is_none", default)]
+    pub link_preview: Option<LinkPreviewResponse>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub entities: Vec<MessageEntity>,
+    /// Participants mentioned by `@username`, so clients can highlight them
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub mentions: Vec<MentionResponse>,
+    /// `everyone` or `here` when a chat admin mentioned the whole chat
+    #[serde(rename = "mentionAll", skip_serializing_if = "Option::is_none", default)]
+    pub mention_all: Option<String>,
+    /// Set when the message is a poll
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub poll: Option<PollResponse>,
+    /// Set when the message was forwarded from another chat
+    #[serde(rename = "forwardedFrom", skip_serializing_if = "Option::is_none", default)]
+    pub forwarded_from: Option<ForwardedFromResponse>,
+    /// Custom emoji used in reactions or as `:shortcode:` in the text
+    #[serde(rename = "customEmoji", skip_serializing_if = "Vec::is_empty", default)]
+    pub custom_emoji: Vec<CustomEmojiResponse>,
+    /// Set on system messages (`senderType` "system") that record a chat event
+    #[serde(rename = "systemEvent", skip_serializing_if = "Option::is_none", default)]
+    pub system_event: Option<SystemEventResponse>,
+}
+
+/// Where a search term matched in a message's text, as UTF-16 code unit
+/// offsets (the way JavaScript strings index)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HighlightRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A message matching an in-chat search, with the messages around it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageSearchHit {
+    pub message: MessageResponse,
+    pub highlights: Vec<HighlightRange>,
+    /// Number of newer messages in the chat timeline (0 = the latest), for
+    /// scrolling to the hit
+    pub position: i64,
+    /// Messages just before and after the hit, in chronological order
+    pub before: Vec<MessageResponse>,
+    pub after: Vec<MessageResponse>,
+}
+
+/// A page of in-chat search hits, newest first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageSearchResponse {
+    pub hits: Vec<MessageSearchHit>,
+    #[serde(rename = "hasMore")]
+    pub has_more: bool,
+}
+
+/// A message a user saved for themselves, with the chat it came from
+#[derive(Debug, Clone, FromRow)]
+pub struct SavedMessage {
+    pub user_id: Uuid,
+    pub message_id: Uuid,
+    pub chat_id: Uuid,
+    pub saved_at: DateTime<Utc>,
+    /// Chat name and type; `None` once the chat is deleted
+    pub chat_name: Option<String>,
+    pub chat_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedMessageResponse {
+    #[serde(rename = "messageId")]
+    pub message_id: Uuid,
+    #[serde(rename = "chatId")]
+    pub chat_id: Uuid,
+    #[serde(rename = "chatName")]
+    pub chat_name: Option<String>,
+    #[serde(rename = "chatType")]
+    pub chat_type: Option<String>,
+    #[serde(rename = "savedAt")]
+    pub saved_at: DateTime<Utc>,
+    /// The message as it is now; absent once it was removed for good
+    pub message: Option<MessageResponse>,
+    /// The original was deleted; `message` is a tombstone or absent
+    #[serde(rename = "isDeleted")]
+    pub is_deleted: bool,
+}
+
+/// A page of saved messages, most recently saved first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedMessagesResponse {
+    pub saved: Vec<SavedMessageResponse>,
+    #[serde(rename = "hasMore")]
+    pub has_more: bool,
+}
+
+/// A thread: its root message and a page of replies in chronological order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreadResponse {
+    pub root: MessageResponse,
+    pub replies: Vec<MessageResponse>,
+    #[serde(rename = "hasMore")]
+    pub has_more: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -6,6 +6,11 @@ pub mod message;
 pub mod session;
 pub mod settings;
 pub mod invite_link;
+pub mod call_log;
+pub mod push;
+pub mod announcement;
+pub mod export;
+pub mod emoji;
 
 pub use bot::*;
 pub use botfather_message::*;
@@ -15,3 +20,8 @@ pub use message::*;
 pub use session::*;
 pub use settings::*;
 pub use invite_link::*;
+pub use call_log::*;
+pub use push::*;
+pub use announcement::*;
+pub use export::*;
+pub use emoji::*;
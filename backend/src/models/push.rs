@@ -0,0 +1,25 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// A browser's web push subscription
+#[derive(Debug, Clone, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct PushSubscription {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub user_id: Uuid,
+    pub endpoint: String,
+    #[serde(skip)]
+    pub p256dh: String,
+    #[serde(skip)]
+    pub auth: String,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// Login session the subscription was registered from
+    #[serde(skip)]
+    #[sqlx(default)]
+    pub session_id: Option<Uuid>,
+}
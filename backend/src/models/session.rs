@@ -17,6 +17,13 @@ pub struct Session {
     pub expires_at: DateTime<Utc>,
     pub created_at: DateTime<Utc>,
     pub last_active: DateTime<Utc>,
+    /// Per-device notification toggles; `None` follows the user's settings
+    #[sqlx(default)]
+    pub message_notifications: Option<bool>,
+    #[sqlx(default)]
+    pub group_notifications: Option<bool>,
+    #[sqlx(default)]
+    pub channel_notifications: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -30,4 +37,28 @@ pub struct DeviceResponse {
     pub last_active: DateTime<Utc>,
     #[serde(rename = "isCurrent")]
     pub is_current: bool,
+    /// "web" for login sessions, "quic" for live realtime connections
+    #[serde(rename = "sessionType")]
+    pub session_type: String,
+}
+
+/// A live realtime (QUIC or WebSocket) connection of the current user
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RealtimeConnectionResponse {
+    pub id: Uuid,
+    /// "quic" or "websocket"
+    pub transport: String,
+    #[serde(rename = "connectedAt")]
+    pub connected_at: DateTime<Utc>,
+    #[serde(rename = "lastActivity")]
+    pub last_activity: DateTime<Utc>,
+    /// Coarse location and device, when the transport captured them
+    pub location: Option<String>,
+    #[serde(rename = "deviceName")]
+    pub device_name: Option<String>,
+    #[serde(rename = "deviceType")]
+    pub device_type: Option<String>,
+    /// The connection the request came from (per `X-Connection-Id`)
+    #[serde(rename = "isCurrent")]
+    pub is_current: bool,
 }
@@ -15,6 +15,7 @@ pub struct UserSettings {
     pub forwards_enabled: bool,
     pub read_receipts_enabled: bool,
     pub two_factor_enabled: bool,
+    pub link_previews_enabled: bool,
     // Notifications
     pub message_notifications: bool,
     pub group_notifications: bool,
@@ -45,6 +46,15 @@ pub struct UserSettings {
     pub animations_enabled: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Bumped on every privacy update (optimistic concurrency)
+    #[sqlx(default)]
+    pub privacy_version: i32,
+    /// Mentions still notify in chats the user has muted
+    #[sqlx(default)]
+    pub mention_notify_when_muted: bool,
+    /// IANA timezone for server-rendered timestamps (UTC when unset)
+    #[sqlx(default)]
+    pub timezone: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -56,6 +66,8 @@ pub struct ProfileResponse {
     pub phone: Option<String>,
     pub email: String,
     pub avatar: Option<String>,
+    /// Send back on update; a stale version is rejected with 409
+    pub version: i32,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -71,6 +83,11 @@ pub struct PrivacySettings {
     pub read_receipts: bool,
     #[serde(rename = "twoFactorAuth")]
     pub two_factor_auth: bool,
+    /// Whether the server generates previews for links this user sends
+    #[serde(rename = "linkPreviews")]
+    pub link_previews: bool,
+    /// Send back on update; a stale version is rejected with 409
+    pub version: i32,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -89,6 +106,35 @@ pub struct NotificationSettings {
     pub in_app_preview: bool,
     #[serde(rename = "contactJoined")]
     pub contact_joined: bool,
+    #[serde(rename = "mentionsWhenMuted")]
+    pub mentions_when_muted: bool,
+}
+
+/// Push notification toggles of a single device
+///
+/// Values are effective ones: a toggle the device never set reports the
+/// user's global setting.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeviceNotificationSettings {
+    #[serde(rename = "deviceId")]
+    pub device_id: Uuid,
+    #[serde(rename = "messageNotifications")]
+    pub message_notifications: bool,
+    #[serde(rename = "groupNotifications")]
+    pub group_notifications: bool,
+    #[serde(rename = "channelNotifications")]
+    pub channel_notifications: bool,
+}
+
+/// Changes to a device's notification toggles; `None` leaves one as is
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+pub struct DeviceNotificationPrefs {
+    #[serde(rename = "messageNotifications")]
+    pub message_notifications: Option<bool>,
+    #[serde(rename = "groupNotifications")]
+    pub group_notifications: Option<bool>,
+    #[serde(rename = "channelNotifications")]
+    pub channel_notifications: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -138,4 +184,5 @@ pub struct AppearanceSettings {
     pub bubble_style: String,
     #[serde(rename = "animationsEnabled")]
     pub animations_enabled: bool,
+    pub timezone: Option<String>,
 }
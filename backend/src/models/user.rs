@@ -19,6 +19,9 @@ pub struct User {
     pub is_bot: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Bumped on every profile update (optimistic concurrency)
+    #[sqlx(default)]
+    pub profile_version: i32,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -28,9 +31,95 @@ pub struct UserPublic {
     pub avatar: Option<String>,
     pub status: String,
     pub last_seen: Option<DateTime<Utc>>,
+    /// Coarse last-seen shown instead of `last_seen` when privacy settings hide the exact time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_seen_approx: Option<LastSeenApprox>,
     pub is_bot: bool,
 }
 
+/// Presence state of a user
+///
+/// `Invisible` is only ever seen by the user themselves; everyone else sees
+/// `Offline` (see `visible`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PresenceState {
+    #[default]
+    Online,
+    Away,
+    Busy,
+    Invisible,
+    Offline,
+}
+
+impl PresenceState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PresenceState::Online => "online",
+            PresenceState::Away => "away",
+            PresenceState::Busy => "busy",
+            PresenceState::Invisible => "invisible",
+            PresenceState::Offline => "offline",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "online" => Some(PresenceState::Online),
+            "away" => Some(PresenceState::Away),
+            "busy" => Some(PresenceState::Busy),
+            "invisible" => Some(PresenceState::Invisible),
+            "offline" => Some(PresenceState::Offline),
+            _ => None,
+        }
+    }
+
+    /// The state other users are shown
+    pub fn visible(self) -> Self {
+        match self {
+            PresenceState::Invisible => PresenceState::Offline,
+            state => state,
+        }
+    }
+}
+
+/// Approximate last-seen bucket shown when the exact time is hidden
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LastSeenApprox {
+    /// Within the last 3 days
+    Recently,
+    /// Within the last week
+    LastWeek,
+    /// Within the last month
+    LastMonth,
+    /// More than a month ago
+    LongAgo,
+}
+
+/// A user's last-seen time as a particular viewer is allowed to see it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisibleLastSeen {
+    Exact(DateTime<Utc>),
+    Approximate(LastSeenApprox),
+}
+
+impl VisibleLastSeen {
+    pub fn exact(self) -> Option<DateTime<Utc>> {
+        match self {
+            VisibleLastSeen::Exact(at) => Some(at),
+            VisibleLastSeen::Approximate(_) => None,
+        }
+    }
+
+    pub fn approx(self) -> Option<LastSeenApprox> {
+        match self {
+            VisibleLastSeen::Exact(_) => None,
+            VisibleLastSeen::Approximate(approx) => Some(approx),
+        }
+    }
+}
+
 impl From<User> for UserPublic {
     fn from(user: User) -> Self {
         Self {
@@ -39,11 +128,25 @@ impl From<User> for UserPublic {
             avatar: user.avatar,
             status: user.status,
             last_seen: user.last_seen,
+            last_seen_approx: None,
             is_bot: user.is_bot,
         }
     }
 }
 
+/// A user in someone's contact list
+#[derive(Debug, Clone, Serialize, FromRow)]
+#[serde(rename_all = "camelCase")]
+pub struct Contact {
+    pub id: Uuid,
+    pub name: String,
+    pub username: Option<String>,
+    pub avatar: Option<String>,
+    /// The contact has added the owner back
+    pub is_mutual: bool,
+    pub added_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserSession {
     pub user: UserPublic,
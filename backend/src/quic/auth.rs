@@ -1,3 +1,8 @@
+use crate::quic::capabilities::{
+    CapabilityMismatch, ClientCapabilities, NegotiatedCapabilities, ServerCapabilities,
+};
+use crate::quic::connection_manager::ConnectionId;
+use crate::quic::resumption::{ResumableSession, ResumptionStore};
 use crate::services::AuthService;
 use quinn::{RecvStream, SendStream};
 use serde::{Deserialize, Serialize};
@@ -28,13 +33,71 @@ pub enum QuicAuthError {
 
     #[error("Invalid user ID")]
     InvalidUserId,
+
+    #[error("Resumption token rejected")]
+    ResumptionRejected,
+
+    #[error("Authentication timed out")]
+    Timeout,
+
+    #[error("Auth request too large: {size} bytes (max {max})")]
+    PayloadTooLarge { size: usize, max: usize },
+
+    #[error("Incompatible client: {0}")]
+    Incompatible(#[from] CapabilityMismatch),
 }
 
 /// Authentication request message sent by client
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AuthRequest {
-    /// JWT token
+    /// JWT token; may be empty when resuming
+    #[serde(default)]
     pub token: String,
+    /// Optional client-supplied device descriptor, shown in the devices list
+    #[serde(default)]
+    pub device: Option<DeviceDescriptor>,
+    /// Token from a previous connection's auth response; when valid the JWT
+    /// is not checked again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resumption_token: Option<String>,
+    /// Features the client supports; older clients send none
+    #[serde(default)]
+    pub capabilities: ClientCapabilities,
+}
+
+/// Default cap on the size of an auth request
+pub const DEFAULT_MAX_AUTH_PAYLOAD_BYTES: usize = 4096;
+
+/// Longest device name/type accepted from a client
+const MAX_DEVICE_FIELD_LEN: usize = 64;
+
+/// Device label sent by the client alongside its auth token
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceDescriptor {
+    pub name: Option<String>,
+    #[serde(rename = "type")]
+    pub device_type: Option<String>,
+}
+
+impl DeviceDescriptor {
+    /// Trim fields, drop empty ones and cap their length
+    pub fn sanitized(self) -> Self {
+        fn clean(value: Option<String>) -> Option<String> {
+            value
+                .map(|v| {
+                    v.trim()
+                        .chars()
+                        .filter(|c| !c.is_control())
+                        .take(MAX_DEVICE_FIELD_LEN)
+                        .collect::<String>()
+                })
+                .filter(|v| !v.is_empty())
+        }
+        Self {
+            name: clean(self.name),
+            device_type: clean(self.device_type),
+        }
+    }
 }
 
 /// Authentication response message sent by server
@@ -46,12 +109,41 @@ pub enum AuthResponse {
     Success {
         user_id: String,
         user_name: String,
+        /// Single-use token for resuming this session on a new connection
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        resumption_token: Option<String>,
+        /// Whether the client's resumption token was accepted
+        #[serde(default)]
+        resumed: bool,
+        /// Id of this connection, as listed under the user's connections
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        connection_id: Option<String>,
+        /// Features agreed for this connection and the server's limits
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        capabilities: Option<NegotiatedCapabilities>,
     },
     /// Authentication failed
     #[serde(rename = "error")]
     Error { code: String, message: String },
 }
 
+/// A client that completed the auth exchange
+#[derive(Debug, Clone)]
+pub struct AuthenticatedClient {
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub device: DeviceDescriptor,
+    /// When the JWT was last verified (unix seconds); token revocation is
+    /// checked against this
+    pub authenticated_at: i64,
+    /// Whether the JWT check was skipped with a resumption token
+    pub resumed: bool,
+    /// Login session the JWT was bound to
+    pub session_id: Option<Uuid>,
+    /// Features agreed during the auth exchange
+    pub capabilities: NegotiatedCapabilities,
+}
+
 /// QUIC authentication handler
 ///
 /// # Requirements
@@ -60,16 +152,36 @@ pub enum AuthResponse {
 pub struct QuicAuthenticator {
     /// JWT secret for token verification
     jwt_secret: String,
+    /// Largest auth request read from a client, in bytes
+    max_payload_bytes: usize,
 }
 
 impl QuicAuthenticator {
     /// Create a new QUIC authenticator
     pub fn new(jwt_secret: String) -> Self {
-        Self { jwt_secret }
+        Self {
+            jwt_secret,
+            max_payload_bytes: DEFAULT_MAX_AUTH_PAYLOAD_BYTES,
+        }
+    }
+
+    /// Cap the size of auth requests read from clients
+    pub fn with_max_payload_bytes(mut self, max_payload_bytes: usize) -> Self {
+        self.set_max_payload_bytes(max_payload_bytes);
+        self
+    }
+
+    /// Change the auth request size cap
+    pub fn set_max_payload_bytes(&mut self, max_payload_bytes: usize) {
+        self.max_payload_bytes = max_payload_bytes;
     }
 
     /// Authenticate a QUIC connection by reading auth request from stream
     ///
+    /// A request carrying a valid resumption token skips JWT verification and
+    /// restores the session it was issued for. An expired, reused or foreign
+    /// token falls back to the JWT in the same request, if any.
+    ///
     /// # Requirements
     /// - 1.3: Authenticate QUIC connections on establishment
     /// - 7.3: Reuse existing JWT validation logic
@@ -77,67 +189,145 @@ impl QuicAuthenticator {
     /// # Arguments
     /// * `recv_stream` - Stream to read authentication request from
     /// * `send_stream` - Stream to send authentication response to
+    /// * `accepted_at` - When the connection was accepted (unix seconds); the
+    ///   JWT was issued no later than this, so it stands in for `iat`
+    /// * `resumption` - Store for issuing and redeeming resumption tokens
+    /// * `connection_id` - Id the connection will be registered under
+    /// * `offer` - Features and limits the server offers this connection;
+    ///   a client that cannot work with them is rejected before its
+    ///   credentials are checked
     ///
     /// # Returns
-    /// * `Ok((user_id, user_name))` - Authentication successful
+    /// * `Ok(AuthenticatedClient)` - Authentication successful
     /// * `Err(QuicAuthError)` - Authentication failed
     pub async fn authenticate_connection(
         &self,
         mut recv_stream: RecvStream,
         mut send_stream: SendStream,
-    ) -> Result<(Uuid, String), QuicAuthError> {
+        accepted_at: i64,
+        resumption: Option<&ResumptionStore>,
+        connection_id: ConnectionId,
+        offer: &ServerCapabilities,
+    ) -> Result<AuthenticatedClient, QuicAuthError> {
         // Read authentication request from stream
         let auth_request = self.read_auth_request(&mut recv_stream).await?;
+        let device = auth_request.device.map(DeviceDescriptor::sanitized);
 
-        // Verify JWT token using existing AuthService
-        match AuthService::verify_token(&auth_request.token, &self.jwt_secret) {
-            Ok(claims) => {
-                // Parse user ID from claims
-                let user_id = Uuid::parse_str(&claims.sub)
-                    .map_err(|_| QuicAuthError::InvalidUserId)?;
-
-                let user_name = claims.name.clone();
-
-                // Send success response
-                let response = AuthResponse::Success {
-                    user_id: user_id.to_string(),
-                    user_name: user_name.clone(),
+        // Turn away clients the server cannot talk to before spending a
+        // resumption token on them
+        let capabilities = match offer.negotiate(&auth_request.capabilities) {
+            Ok(capabilities) => capabilities,
+            Err(mismatch) => {
+                let response = AuthResponse::Error {
+                    code: mismatch.code().to_string(),
+                    message: mismatch.to_string(),
                 };
-
                 self.send_auth_response(&mut send_stream, &response).await?;
-
-                tracing::info!(
-                    "QUIC authentication successful: user_id={}, user_name={}",
-                    user_id,
-                    user_name
-                );
-
-                Ok((user_id, user_name))
+                tracing::warn!("QUIC client rejected: {}", mismatch);
+                return Err(mismatch.into());
             }
-            Err(e) => {
-                // Send error response
-                let (code, message) = match e {
-                    crate::error::AppError::TokenExpired => {
-                        ("TOKEN_EXPIRED", "Token has expired")
-                    }
-                    crate::error::AppError::InvalidToken => {
-                        ("INVALID_TOKEN", "Invalid or malformed token")
+        };
+
+        // Try the resumption token first
+        let mut resumed = None;
+        if let (Some(store), Some(token)) = (resumption, auth_request.resumption_token.as_deref()) {
+            match store.redeem(token).await {
+                Some(session) if session.matches_device(device.as_ref()) => {
+                    resumed = Some(session);
+                }
+                _ => {
+                    tracing::debug!("QUIC resumption token rejected, falling back to full auth");
+                    if auth_request.token.is_empty() {
+                        let response = AuthResponse::Error {
+                            code: "RESUMPTION_REJECTED".to_string(),
+                            message: "Resumption token is invalid or expired".to_string(),
+                        };
+                        self.send_auth_response(&mut send_stream, &response).await?;
+                        return Err(QuicAuthError::ResumptionRejected);
                     }
-                    _ => ("AUTH_ERROR", "Authentication failed"),
-                };
+                }
+            }
+        }
 
-                let response = AuthResponse::Error {
-                    code: code.to_string(),
-                    message: message.to_string(),
-                };
+        let was_resumed = resumed.is_some();
+        let session = match resumed {
+            Some(session) => session,
+            // Verify JWT token using existing AuthService
+            None => match AuthService::verify_token(&auth_request.token, &self.jwt_secret) {
+                Ok(claims) => {
+                    // Parse user ID from claims
+                    let user_id = Uuid::parse_str(&claims.sub)
+                        .map_err(|_| QuicAuthError::InvalidUserId)?;
+
+                    ResumableSession {
+                        user_id,
+                        user_name: claims.name,
+                        device: device.unwrap_or_default(),
+                        authenticated_at: accepted_at,
+                        expires_at: claims.exp,
+                        session_id: claims.sid,
+                    }
+                }
+                Err(e) => {
+                    // Send error response
+                    let (code, message, error) = match e {
+                        crate::error::AppError::TokenExpired => {
+                            ("TOKEN_EXPIRED", "Token has expired", QuicAuthError::TokenExpired)
+                        }
+                        crate::error::AppError::InvalidToken => {
+                            ("INVALID_TOKEN", "Invalid or malformed token", QuicAuthError::InvalidToken)
+                        }
+                        _ => ("AUTH_ERROR", "Authentication failed", QuicAuthError::InvalidToken),
+                    };
+
+                    let response = AuthResponse::Error {
+                        code: code.to_string(),
+                        message: message.to_string(),
+                    };
+
+                    self.send_auth_response(&mut send_stream, &response).await?;
+
+                    tracing::warn!("QUIC authentication failed: {}", message);
+
+                    return Err(error);
+                }
+            },
+        };
 
-                self.send_auth_response(&mut send_stream, &response).await?;
+        // Hand out a fresh token for the next reconnect
+        let resumption_token = match resumption {
+            Some(store) => store.issue(&session).await,
+            None => None,
+        };
 
-                tracing::warn!("QUIC authentication failed: {}", message);
+        // Send success response
+        let response = AuthResponse::Success {
+            user_id: session.user_id.to_string(),
+            user_name: session.user_name.clone(),
+            resumption_token,
+            resumed: was_resumed,
+            connection_id: Some(connection_id.to_string()),
+            capabilities: Some(capabilities.clone()),
+        };
 
-                Err(QuicAuthError::InvalidToken)
-            }
-        }
+        self.send_auth_response(&mut send_stream, &response).await?;
+
+        tracing::info!(
+            "QUIC authentication successful: user_id={}, user_name={}, resumed={}",
+            session.user_id,
+            session.user_name,
+            was_resumed
+        );
+
+        Ok(AuthenticatedClient {
+            user_id: session.user_id,
+            user_name: session.user_name,
+            device: session.device,
+            authenticated_at: session.authenticated_at,
+            resumed: was_resumed,
+            session_id: session.session_id,
+            capabilities,
+        })
     }
 
     /// Authenticate using a token string directly (for testing or alternative flows)
@@ -173,12 +363,12 @@ impl QuicAuthenticator {
 
         let len = u32::from_be_bytes(len_buf) as usize;
 
-        // Validate length (max 4KB for auth request)
-        if len > 4096 {
-            return Err(QuicAuthError::Stream(format!(
-                "Auth request too large: {} bytes",
-                len
-            )));
+        // Validate length before allocating the buffer
+        if len > self.max_payload_bytes {
+            return Err(QuicAuthError::PayloadTooLarge {
+                size: len,
+                max: self.max_payload_bytes,
+            });
         }
 
         // Read JSON data
@@ -233,6 +423,9 @@ mod tests {
     fn test_auth_request_serialization() {
         let request = AuthRequest {
             token: "test_token".to_string(),
+            device: None,
+            resumption_token: None,
+            capabilities: ClientCapabilities::default(),
         };
 
         let json = serde_json::to_string(&request).unwrap();
@@ -247,6 +440,10 @@ mod tests {
         let response = AuthResponse::Success {
             user_id: "123e4567-e89b-12d3-a456-426614174000".to_string(),
             user_name: "Test User".to_string(),
+            resumption_token: None,
+            resumed: false,
+            connection_id: None,
+            capabilities: None,
         };
 
         let json = serde_json::to_string(&response).unwrap();
@@ -255,7 +452,7 @@ mod tests {
 
         let deserialized: AuthResponse = serde_json::from_str(&json).unwrap();
         match deserialized {
-            AuthResponse::Success { user_id, user_name } => {
+            AuthResponse::Success { user_id, user_name, .. } => {
                 assert_eq!(user_id, "123e4567-e89b-12d3-a456-426614174000");
                 assert_eq!(user_name, "Test User");
             }
@@ -284,9 +481,92 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_auth_messages_with_resumption_token() {
+        let request: AuthRequest =
+            serde_json::from_str(r#"{"resumption_token":"abc"}"#).unwrap();
+        assert_eq!(request.token, "");
+        assert_eq!(request.resumption_token.as_deref(), Some("abc"));
+
+        let response = AuthResponse::Success {
+            user_id: "123e4567-e89b-12d3-a456-426614174000".to_string(),
+            user_name: "Test User".to_string(),
+            resumption_token: Some("next".to_string()),
+            resumed: true,
+            connection_id: Some("conn".to_string()),
+            capabilities: None,
+        };
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["resumption_token"], "next");
+        assert_eq!(json["resumed"], true);
+        assert_eq!(json["connection_id"], "conn");
+
+        // Older servers send neither field
+        let json = r#"{"type":"success","user_id":"u","user_name":"n"}"#;
+        match serde_json::from_str(json).unwrap() {
+            AuthResponse::Success { resumption_token, resumed, .. } => {
+                assert!(resumption_token.is_none());
+                assert!(!resumed);
+            }
+            _ => panic!("Expected Success variant"),
+        }
+    }
+
+    #[test]
+    fn test_auth_messages_with_capabilities() {
+        let json = r#"{"token":"t","capabilities":{"batching":true,"schema_version":1,"required":["batching"]}}"#;
+        let request: AuthRequest = serde_json::from_str(json).unwrap();
+        assert!(request.capabilities.batching);
+        assert_eq!(request.capabilities.required, vec!["batching".to_string()]);
+
+        let offer = ServerCapabilities::from_config(&crate::quic::QuicServerConfig::default());
+        let response = AuthResponse::Success {
+            user_id: "u".to_string(),
+            user_name: "n".to_string(),
+            resumption_token: None,
+            resumed: false,
+            connection_id: None,
+            capabilities: Some(offer.negotiate(&request.capabilities).unwrap()),
+        };
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["capabilities"]["batching"], true);
+        assert_eq!(json["capabilities"]["datagrams"], false);
+        assert_eq!(
+            json["capabilities"]["limits"]["max_message_size"],
+            crate::quic::capabilities::MAX_STREAM_MESSAGE_BYTES
+        );
+    }
+
     #[test]
     fn test_authenticator_new() {
         let authenticator = QuicAuthenticator::new("test_secret".to_string());
         assert_eq!(authenticator.jwt_secret, "test_secret");
+        assert_eq!(authenticator.max_payload_bytes, DEFAULT_MAX_AUTH_PAYLOAD_BYTES);
+
+        let authenticator = authenticator.with_max_payload_bytes(512);
+        assert_eq!(authenticator.max_payload_bytes, 512);
+    }
+
+    #[test]
+    fn test_auth_request_with_device() {
+        let json = r#"{"token":"t","device":{"name":"  Pixel 8 ","type":"android"}}"#;
+        let request: AuthRequest = serde_json::from_str(json).unwrap();
+        let device = request.device.unwrap().sanitized();
+        assert_eq!(device.name.as_deref(), Some("Pixel 8"));
+        assert_eq!(device.device_type.as_deref(), Some("android"));
+
+        let request: AuthRequest = serde_json::from_str(r#"{"token":"t"}"#).unwrap();
+        assert!(request.device.is_none());
+    }
+
+    #[test]
+    fn test_device_descriptor_sanitized() {
+        let device = DeviceDescriptor {
+            name: Some("x".repeat(MAX_DEVICE_FIELD_LEN + 10)),
+            device_type: Some("   ".to_string()),
+        }
+        .sanitized();
+        assert_eq!(device.name.unwrap().len(), MAX_DEVICE_FIELD_LEN);
+        assert_eq!(device.device_type, None);
     }
 }
@@ -0,0 +1,279 @@
+/// QUIC capability negotiation
+///
+/// Clients list the features they support in their auth request; the server
+/// answers with the features both sides support and its own limits, or
+/// rejects the client before authenticating it when the two cannot talk:
+/// - Schema version: the client's message schema must be in the server's
+///   supported range
+/// - Features (`datagrams`, `compression`, `batching`): negotiated to the
+///   intersection; one the client marks as required but does not get
+///   rejects the connection
+/// - Limits: largest message the server reads from a stream and its
+///   stream limits, so clients size their traffic up front
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+use thiserror::Error;
+
+use crate::quic::config::QuicServerConfig;
+
+/// Message schema versions this server speaks
+pub const SUPPORTED_SCHEMA_VERSIONS: RangeInclusive<u32> = 1..=1;
+
+/// Schema version assumed for clients that do not send one
+pub const DEFAULT_SCHEMA_VERSION: u32 = 1;
+
+/// Largest message read from a client stream, in bytes
+pub const MAX_STREAM_MESSAGE_BYTES: usize = 1024 * 1024;
+
+/// Stream compression algorithms the server can decode
+pub const SUPPORTED_COMPRESSION: &[&str] = &[];
+
+fn default_schema_version() -> u32 {
+    DEFAULT_SCHEMA_VERSION
+}
+
+/// Features advertised by the client in its auth request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientCapabilities {
+    /// Whether the client can receive QUIC datagrams
+    #[serde(default)]
+    pub datagrams: bool,
+    /// Compression algorithms, most preferred first
+    #[serde(default)]
+    pub compression: Vec<String>,
+    /// Whether the client sends several events in one frame as a JSON array
+    #[serde(default)]
+    pub batching: bool,
+    /// Message schema version the client speaks
+    #[serde(default = "default_schema_version")]
+    pub schema_version: u32,
+    /// Features the client cannot work without
+    #[serde(default)]
+    pub required: Vec<String>,
+}
+
+impl Default for ClientCapabilities {
+    fn default() -> Self {
+        Self {
+            datagrams: false,
+            compression: Vec::new(),
+            batching: false,
+            schema_version: DEFAULT_SCHEMA_VERSION,
+            required: Vec::new(),
+        }
+    }
+}
+
+/// Server-side limits sent back to the client
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerLimits {
+    /// Largest message read from a stream, in bytes
+    pub max_message_size: usize,
+    /// Bidirectional streams the client may have open at once
+    pub max_bidi_streams: u64,
+    /// Unidirectional streams the client may have open at once
+    pub max_uni_streams: u64,
+}
+
+/// Features agreed for one connection
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NegotiatedCapabilities {
+    pub datagrams: bool,
+    /// Compression algorithm both sides use, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<String>,
+    pub batching: bool,
+    pub schema_version: u32,
+    pub limits: ServerLimits,
+}
+
+/// Why a client cannot be served
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum CapabilityMismatch {
+    #[error("Schema version {requested} is not supported (server supports {min}-{max})")]
+    UnsupportedSchemaVersion { requested: u32, min: u32, max: u32 },
+
+    #[error("Required feature '{0}' is not supported by the server")]
+    MissingFeature(String),
+}
+
+impl CapabilityMismatch {
+    /// Error code sent in the auth response
+    pub fn code(&self) -> &'static str {
+        match self {
+            CapabilityMismatch::UnsupportedSchemaVersion { .. } => "UNSUPPORTED_SCHEMA_VERSION",
+            CapabilityMismatch::MissingFeature(_) => "INCOMPATIBLE_CLIENT",
+        }
+    }
+}
+
+/// What the server offers a connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerCapabilities {
+    /// Whether datagrams can be sent on the connection
+    pub datagrams: bool,
+    pub compression: Vec<String>,
+    pub batching: bool,
+    pub schema_versions: RangeInclusive<u32>,
+    pub limits: ServerLimits,
+}
+
+impl ServerCapabilities {
+    /// Offer built from the server configuration; datagrams are off until
+    /// the connection is known to carry them
+    pub fn from_config(config: &QuicServerConfig) -> Self {
+        Self {
+            datagrams: false,
+            compression: SUPPORTED_COMPRESSION.iter().map(|c| c.to_string()).collect(),
+            batching: true,
+            schema_versions: SUPPORTED_SCHEMA_VERSIONS,
+            limits: ServerLimits {
+                max_message_size: MAX_STREAM_MESSAGE_BYTES,
+                max_bidi_streams: config.max_streams_per_connection,
+                max_uni_streams: config.max_streams_per_connection,
+            },
+        }
+    }
+
+    /// Set whether the connection can carry datagrams
+    pub fn with_datagrams(mut self, datagrams: bool) -> Self {
+        self.datagrams = datagrams;
+        self
+    }
+
+    /// Agree on the features both sides support
+    pub fn negotiate(
+        &self,
+        client: &ClientCapabilities,
+    ) -> Result<NegotiatedCapabilities, CapabilityMismatch> {
+        if !self.schema_versions.contains(&client.schema_version) {
+            return Err(CapabilityMismatch::UnsupportedSchemaVersion {
+                requested: client.schema_version,
+                min: *self.schema_versions.start(),
+                max: *self.schema_versions.end(),
+            });
+        }
+
+        let negotiated = NegotiatedCapabilities {
+            datagrams: client.datagrams && self.datagrams,
+            compression: client
+                .compression
+                .iter()
+                .find(|c| self.compression.contains(c))
+                .cloned(),
+            batching: client.batching && self.batching,
+            schema_version: client.schema_version,
+            limits: self.limits,
+        };
+
+        for feature in &client.required {
+            let granted = match feature.as_str() {
+                "datagrams" => negotiated.datagrams,
+                "compression" => negotiated.compression.is_some(),
+                "batching" => negotiated.batching,
+                _ => false,
+            };
+            if !granted {
+                return Err(CapabilityMismatch::MissingFeature(feature.clone()));
+            }
+        }
+
+        Ok(negotiated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server() -> ServerCapabilities {
+        ServerCapabilities {
+            datagrams: true,
+            compression: vec!["zstd".to_string(), "deflate".to_string()],
+            batching: true,
+            schema_versions: 1..=2,
+            limits: ServerLimits {
+                max_message_size: 1024,
+                max_bidi_streams: 10,
+                max_uni_streams: 10,
+            },
+        }
+    }
+
+    #[test]
+    fn test_client_without_capabilities_gets_baseline() {
+        let client: ClientCapabilities = serde_json::from_str("{}").unwrap();
+        assert_eq!(client, ClientCapabilities::default());
+
+        let negotiated = server().negotiate(&client).unwrap();
+        assert!(!negotiated.datagrams);
+        assert!(!negotiated.batching);
+        assert_eq!(negotiated.compression, None);
+        assert_eq!(negotiated.schema_version, DEFAULT_SCHEMA_VERSION);
+        assert_eq!(negotiated.limits.max_message_size, 1024);
+    }
+
+    #[test]
+    fn test_features_negotiate_to_the_intersection() {
+        let client = ClientCapabilities {
+            datagrams: true,
+            compression: vec!["brotli".to_string(), "deflate".to_string(), "zstd".to_string()],
+            batching: true,
+            schema_version: 2,
+            required: Vec::new(),
+        };
+        let negotiated = server().with_datagrams(false).negotiate(&client).unwrap();
+        assert!(!negotiated.datagrams);
+        assert!(negotiated.batching);
+        // The client's preference order wins among shared algorithms
+        assert_eq!(negotiated.compression.as_deref(), Some("deflate"));
+        assert_eq!(negotiated.schema_version, 2);
+
+        let client = ClientCapabilities {
+            compression: vec!["brotli".to_string()],
+            ..ClientCapabilities::default()
+        };
+        assert_eq!(server().negotiate(&client).unwrap().compression, None);
+    }
+
+    #[test]
+    fn test_unsupported_schema_version_is_rejected() {
+        let client = ClientCapabilities {
+            schema_version: 3,
+            ..ClientCapabilities::default()
+        };
+        let err = server().negotiate(&client).unwrap_err();
+        assert_eq!(
+            err,
+            CapabilityMismatch::UnsupportedSchemaVersion { requested: 3, min: 1, max: 2 }
+        );
+        assert_eq!(err.code(), "UNSUPPORTED_SCHEMA_VERSION");
+    }
+
+    #[test]
+    fn test_missing_required_feature_is_rejected() {
+        let client = ClientCapabilities {
+            datagrams: true,
+            required: vec!["datagrams".to_string()],
+            ..ClientCapabilities::default()
+        };
+        assert!(server().negotiate(&client).unwrap().datagrams);
+
+        let err = server().with_datagrams(false).negotiate(&client).unwrap_err();
+        assert_eq!(err, CapabilityMismatch::MissingFeature("datagrams".to_string()));
+        assert_eq!(err.code(), "INCOMPATIBLE_CLIENT");
+
+        // Requiring a feature the client did not advertise, or one the
+        // server has never heard of, fails the same way
+        let client = ClientCapabilities {
+            required: vec!["batching".to_string()],
+            ..ClientCapabilities::default()
+        };
+        assert!(server().negotiate(&client).is_err());
+        let client = ClientCapabilities {
+            required: vec!["telepathy".to_string()],
+            ..ClientCapabilities::default()
+        };
+        assert!(server().negotiate(&client).is_err());
+    }
+}
@@ -4,6 +4,9 @@ use std::path::PathBuf;
 use std::time::Duration;
 use thiserror::Error;
 
+use super::auth::DEFAULT_MAX_AUTH_PAYLOAD_BYTES;
+use super::connection_manager::TransportType;
+
 /// Configuration errors
 #[derive(Debug, Error)]
 pub enum ConfigError {
@@ -49,6 +52,198 @@ pub struct QuicServerConfig {
 
     /// Keep-alive interval in milliseconds
     pub keep_alive_interval_ms: u64,
+
+    /// Milliseconds before the idle timeout that idle connections get an
+    /// `idle_warning` event (0 disconnects without warning; at most half
+    /// the idle timeout is used)
+    #[serde(default = "default_idle_warning_ms")]
+    pub idle_warning_ms: u64,
+
+    /// Send priority per message type (higher is sent first)
+    #[serde(default)]
+    pub stream_priorities: StreamPriorities,
+
+    /// Application-level idle timeout per message type stream
+    #[serde(default)]
+    pub stream_idle_timeouts: StreamIdleTimeouts,
+
+    /// ALPN protocol ids offered during the TLS handshake, in order of
+    /// preference. `giano/1` is the native messaging protocol; `h3` stays in
+    /// the default list for browser WebTransport clients.
+    #[serde(default = "default_alpn_protocols")]
+    pub alpn_protocols: Vec<String>,
+
+    /// Seconds a new connection has to open its auth stream and authenticate
+    #[serde(default = "default_auth_timeout_secs")]
+    pub auth_timeout_secs: u64,
+
+    /// Largest auth request a client may send, in bytes
+    #[serde(default = "default_max_auth_payload_bytes")]
+    pub max_auth_payload_bytes: usize,
+
+    /// Answer handshakes from unvalidated addresses with a Retry packet, so
+    /// spoofed sources cannot use the server for amplification
+    #[serde(default = "default_require_address_validation")]
+    pub require_address_validation: bool,
+
+    /// New connections allowed per second from one IP (0 disables the limit)
+    #[serde(default = "default_connection_rate_per_ip")]
+    pub connection_rate_per_ip: u32,
+
+    /// Connections one IP may open in a burst before the rate applies
+    #[serde(default = "default_connection_burst_per_ip")]
+    pub connection_burst_per_ip: u32,
+
+    /// Handshakes allowed in progress at once (0 disables the limit)
+    #[serde(default = "default_max_concurrent_handshakes")]
+    pub max_concurrent_handshakes: usize,
+
+    /// Milliseconds a new connection waits for a handshake slot before it is
+    /// refused
+    #[serde(default = "default_handshake_queue_timeout_ms")]
+    pub handshake_queue_timeout_ms: u64,
+
+    /// Bytes a peer may send on one stream before we read them
+    ///
+    /// Received but unread data is buffered in memory, so the worst case is
+    /// roughly `max_connections * receive_window` (each connection is also
+    /// capped by its own `receive_window`, however many streams it opens).
+    /// Larger windows speed up file transfers on high-latency links.
+    #[serde(default = "default_stream_receive_window")]
+    pub stream_receive_window: u64,
+
+    /// Bytes a peer may send across all streams of one connection before
+    /// we read them
+    #[serde(default = "default_receive_window")]
+    pub receive_window: u64,
+
+    /// Bytes buffered for sending per connection before writes wait for
+    /// acknowledgements
+    #[serde(default = "default_send_window")]
+    pub send_window: u64,
+
+    /// Transport deliveries go out on first for users connected over both
+    /// QUIC and WebSocket; the other one is used if sending fails
+    #[serde(default = "default_preferred_transport")]
+    pub preferred_transport: TransportType,
+
+    /// Seconds a resumption token stays valid after it is issued
+    /// (0 disables resumption, so every reconnect runs the full JWT auth)
+    #[serde(default = "default_resumption_ttl_secs")]
+    pub resumption_ttl_secs: u64,
+}
+
+/// Smallest accepted flow-control window (16 KiB)
+pub const MIN_FLOW_CONTROL_WINDOW: u64 = 16 * 1024;
+
+/// Largest accepted `max_auth_payload_bytes` (64 KiB)
+pub const MAX_AUTH_PAYLOAD_BYTES: usize = 64 * 1024;
+
+/// Largest accepted flow-control window (256 MiB)
+pub const MAX_FLOW_CONTROL_WINDOW: u64 = 256 * 1024 * 1024;
+
+/// Default `QUIC_ALPN_PROTOCOLS`
+pub fn default_alpn_protocols() -> Vec<String> {
+    vec!["giano/1".to_string(), "h3".to_string()]
+}
+
+fn default_auth_timeout_secs() -> u64 {
+    10
+}
+
+fn default_max_auth_payload_bytes() -> usize {
+    DEFAULT_MAX_AUTH_PAYLOAD_BYTES
+}
+
+fn default_idle_warning_ms() -> u64 {
+    10_000
+}
+
+fn default_resumption_ttl_secs() -> u64 {
+    60
+}
+
+fn default_require_address_validation() -> bool {
+    true
+}
+
+fn default_connection_rate_per_ip() -> u32 {
+    5
+}
+
+fn default_connection_burst_per_ip() -> u32 {
+    20
+}
+
+fn default_max_concurrent_handshakes() -> usize {
+    256
+}
+
+fn default_handshake_queue_timeout_ms() -> u64 {
+    1000
+}
+
+fn default_stream_receive_window() -> u64 {
+    1_250_000
+}
+
+fn default_receive_window() -> u64 {
+    12_500_000
+}
+
+fn default_send_window() -> u64 {
+    10_000_000
+}
+
+fn default_preferred_transport() -> TransportType {
+    TransportType::Quic
+}
+
+/// Per-message-type stream send priorities
+///
+/// Quinn sends data from higher-priority streams first, so a large file
+/// transfer cannot starve control traffic on the same connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamPriorities {
+    pub control: i32,
+    pub chat_message: i32,
+    pub bot_command: i32,
+    pub file_transfer: i32,
+}
+
+impl Default for StreamPriorities {
+    fn default() -> Self {
+        Self {
+            control: 3,
+            chat_message: 2,
+            bot_command: 1,
+            file_transfer: 0,
+        }
+    }
+}
+
+/// Per-message-type stream idle timeouts in milliseconds
+///
+/// A stream with no reads for longer than its type's timeout is closed and
+/// its id released, so abandoned streams cannot exhaust a stream range.
+/// File transfers legitimately stay open much longer than chat streams.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StreamIdleTimeouts {
+    pub control_ms: u64,
+    pub chat_message_ms: u64,
+    pub bot_command_ms: u64,
+    pub file_transfer_ms: u64,
+}
+
+impl Default for StreamIdleTimeouts {
+    fn default() -> Self {
+        Self {
+            control_ms: 30_000,
+            chat_message_ms: 60_000,
+            bot_command_ms: 60_000,
+            file_transfer_ms: 600_000,
+        }
+    }
 }
 
 impl Default for QuicServerConfig {
@@ -63,6 +258,22 @@ impl Default for QuicServerConfig {
             max_streams_per_connection: 100,
             idle_timeout_ms: 30000,
             keep_alive_interval_ms: 5000,
+            idle_warning_ms: default_idle_warning_ms(),
+            stream_priorities: StreamPriorities::default(),
+            stream_idle_timeouts: StreamIdleTimeouts::default(),
+            alpn_protocols: default_alpn_protocols(),
+            auth_timeout_secs: default_auth_timeout_secs(),
+            max_auth_payload_bytes: default_max_auth_payload_bytes(),
+            require_address_validation: default_require_address_validation(),
+            connection_rate_per_ip: default_connection_rate_per_ip(),
+            connection_burst_per_ip: default_connection_burst_per_ip(),
+            max_concurrent_handshakes: default_max_concurrent_handshakes(),
+            handshake_queue_timeout_ms: default_handshake_queue_timeout_ms(),
+            stream_receive_window: default_stream_receive_window(),
+            receive_window: default_receive_window(),
+            send_window: default_send_window(),
+            preferred_transport: default_preferred_transport(),
+            resumption_ttl_secs: default_resumption_ttl_secs(),
         }
     }
 }
@@ -119,9 +330,114 @@ impl QuicServerConfig {
             config.keep_alive_interval_ms = keep_alive_str.parse()?;
         }
 
+        // QUIC_IDLE_WARNING_MS (optional, 0 disables the warning)
+        if let Ok(warning) = std::env::var("QUIC_IDLE_WARNING_MS") {
+            config.idle_warning_ms = warning.parse()?;
+        }
+
+        // QUIC_PRIORITY_* (optional)
+        if let Ok(priority) = std::env::var("QUIC_PRIORITY_CONTROL") {
+            config.stream_priorities.control = priority.parse()?;
+        }
+        if let Ok(priority) = std::env::var("QUIC_PRIORITY_CHAT_MESSAGE") {
+            config.stream_priorities.chat_message = priority.parse()?;
+        }
+        if let Ok(priority) = std::env::var("QUIC_PRIORITY_BOT_COMMAND") {
+            config.stream_priorities.bot_command = priority.parse()?;
+        }
+        if let Ok(priority) = std::env::var("QUIC_PRIORITY_FILE_TRANSFER") {
+            config.stream_priorities.file_transfer = priority.parse()?;
+        }
+
+        // QUIC_STREAM_IDLE_*_MS (optional)
+        if let Ok(timeout) = std::env::var("QUIC_STREAM_IDLE_CONTROL_MS") {
+            config.stream_idle_timeouts.control_ms = timeout.parse()?;
+        }
+        if let Ok(timeout) = std::env::var("QUIC_STREAM_IDLE_CHAT_MESSAGE_MS") {
+            config.stream_idle_timeouts.chat_message_ms = timeout.parse()?;
+        }
+        if let Ok(timeout) = std::env::var("QUIC_STREAM_IDLE_BOT_COMMAND_MS") {
+            config.stream_idle_timeouts.bot_command_ms = timeout.parse()?;
+        }
+        if let Ok(timeout) = std::env::var("QUIC_STREAM_IDLE_FILE_TRANSFER_MS") {
+            config.stream_idle_timeouts.file_transfer_ms = timeout.parse()?;
+        }
+
+        // QUIC_ALPN_PROTOCOLS (optional, comma-separated)
+        if let Ok(protocols) = std::env::var("QUIC_ALPN_PROTOCOLS") {
+            config.alpn_protocols = protocols
+                .split(',')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(String::from)
+                .collect();
+        }
+
+        // QUIC_AUTH_TIMEOUT_SECS (optional)
+        if let Ok(timeout) = std::env::var("QUIC_AUTH_TIMEOUT_SECS") {
+            config.auth_timeout_secs = timeout.parse()?;
+        }
+
+        // QUIC_MAX_AUTH_PAYLOAD_BYTES (optional)
+        if let Ok(max) = std::env::var("QUIC_MAX_AUTH_PAYLOAD_BYTES") {
+            config.max_auth_payload_bytes = max.parse()?;
+        }
+
+        // QUIC_REQUIRE_RETRY (optional, defaults to true)
+        if let Ok(require) = std::env::var("QUIC_REQUIRE_RETRY") {
+            config.require_address_validation = require.to_lowercase() == "true" || require == "1";
+        }
+
+        // QUIC_CONNECTION_RATE_PER_IP / QUIC_CONNECTION_BURST_PER_IP (optional)
+        if let Ok(rate) = std::env::var("QUIC_CONNECTION_RATE_PER_IP") {
+            config.connection_rate_per_ip = rate.parse()?;
+        }
+        if let Ok(burst) = std::env::var("QUIC_CONNECTION_BURST_PER_IP") {
+            config.connection_burst_per_ip = burst.parse()?;
+        }
+
+        // QUIC_MAX_CONCURRENT_HANDSHAKES / QUIC_HANDSHAKE_QUEUE_TIMEOUT_MS (optional)
+        if let Ok(max) = std::env::var("QUIC_MAX_CONCURRENT_HANDSHAKES") {
+            config.max_concurrent_handshakes = max.parse()?;
+        }
+        if let Ok(timeout) = std::env::var("QUIC_HANDSHAKE_QUEUE_TIMEOUT_MS") {
+            config.handshake_queue_timeout_ms = timeout.parse()?;
+        }
+
+        // QUIC_STREAM_RECEIVE_WINDOW / QUIC_RECEIVE_WINDOW / QUIC_SEND_WINDOW (optional, bytes)
+        if let Ok(window) = std::env::var("QUIC_STREAM_RECEIVE_WINDOW") {
+            config.stream_receive_window = window.parse()?;
+        }
+        if let Ok(window) = std::env::var("QUIC_RECEIVE_WINDOW") {
+            config.receive_window = window.parse()?;
+        }
+        if let Ok(window) = std::env::var("QUIC_SEND_WINDOW") {
+            config.send_window = window.parse()?;
+        }
+
+        // QUIC_PREFERRED_TRANSPORT (optional, quic or websocket)
+        if let Ok(transport) = std::env::var("QUIC_PREFERRED_TRANSPORT") {
+            config.preferred_transport = transport.parse().map_err(|e| {
+                ConfigError::InvalidValue("QUIC_PREFERRED_TRANSPORT".to_string(), e)
+            })?;
+        }
+
+        // QUIC_RESUMPTION_TTL_SECS (optional, 0 disables resumption)
+        if let Ok(ttl) = std::env::var("QUIC_RESUMPTION_TTL_SECS") {
+            config.resumption_ttl_secs = ttl.parse()?;
+        }
+
         Ok(config)
     }
 
+    /// ALPN protocol ids as passed to rustls
+    pub fn alpn_protocol_ids(&self) -> Vec<Vec<u8>> {
+        self.alpn_protocols
+            .iter()
+            .map(|p| p.as_bytes().to_vec())
+            .collect()
+    }
+
     /// Get the socket address for binding
     pub fn socket_addr(&self) -> Result<SocketAddr, ConfigError> {
         let addr_str = format!("{}:{}", self.bind_address, self.port);
@@ -133,11 +449,26 @@ impl QuicServerConfig {
         Duration::from_millis(self.idle_timeout_ms)
     }
 
+    /// Get the idle warning window as Duration
+    pub fn idle_warning_window(&self) -> Duration {
+        Duration::from_millis(self.idle_warning_ms)
+    }
+
     /// Get keep-alive interval as Duration
     pub fn keep_alive_interval(&self) -> Duration {
         Duration::from_millis(self.keep_alive_interval_ms)
     }
 
+    /// Get authentication timeout as Duration
+    pub fn auth_timeout(&self) -> Duration {
+        Duration::from_secs(self.auth_timeout_secs)
+    }
+
+    /// How long a new connection waits for a handshake slot
+    pub fn handshake_queue_timeout(&self) -> Duration {
+        Duration::from_millis(self.handshake_queue_timeout_ms)
+    }
+
     /// Validate configuration
     pub fn validate(&self) -> Result<(), ConfigError> {
         // Validate port range
@@ -179,6 +510,83 @@ impl QuicServerConfig {
             ));
         }
 
+        if self.auth_timeout_secs == 0 {
+            return Err(ConfigError::InvalidValue(
+                "QUIC_AUTH_TIMEOUT_SECS".to_string(),
+                "Must be greater than 0".to_string(),
+            ));
+        }
+
+        if self.max_auth_payload_bytes == 0 || self.max_auth_payload_bytes > MAX_AUTH_PAYLOAD_BYTES {
+            return Err(ConfigError::InvalidValue(
+                "QUIC_MAX_AUTH_PAYLOAD_BYTES".to_string(),
+                format!("Must be between 1 and {}", MAX_AUTH_PAYLOAD_BYTES),
+            ));
+        }
+
+        if self.connection_rate_per_ip > 0 && self.connection_burst_per_ip == 0 {
+            return Err(ConfigError::InvalidValue(
+                "QUIC_CONNECTION_BURST_PER_IP".to_string(),
+                "Must be greater than 0 when QUIC_CONNECTION_RATE_PER_IP is set".to_string(),
+            ));
+        }
+
+        let stream_timeouts = [
+            ("QUIC_STREAM_IDLE_CONTROL_MS", self.stream_idle_timeouts.control_ms),
+            ("QUIC_STREAM_IDLE_CHAT_MESSAGE_MS", self.stream_idle_timeouts.chat_message_ms),
+            ("QUIC_STREAM_IDLE_BOT_COMMAND_MS", self.stream_idle_timeouts.bot_command_ms),
+            ("QUIC_STREAM_IDLE_FILE_TRANSFER_MS", self.stream_idle_timeouts.file_transfer_ms),
+        ];
+        for (name, timeout_ms) in stream_timeouts {
+            if timeout_ms == 0 {
+                return Err(ConfigError::InvalidValue(
+                    name.to_string(),
+                    "Must be greater than 0".to_string(),
+                ));
+            }
+        }
+
+        let windows = [
+            ("QUIC_STREAM_RECEIVE_WINDOW", self.stream_receive_window),
+            ("QUIC_RECEIVE_WINDOW", self.receive_window),
+            ("QUIC_SEND_WINDOW", self.send_window),
+        ];
+        for (name, window) in windows {
+            if !(MIN_FLOW_CONTROL_WINDOW..=MAX_FLOW_CONTROL_WINDOW).contains(&window) {
+                return Err(ConfigError::InvalidValue(
+                    name.to_string(),
+                    format!(
+                        "Must be between {} and {} bytes",
+                        MIN_FLOW_CONTROL_WINDOW, MAX_FLOW_CONTROL_WINDOW
+                    ),
+                ));
+            }
+        }
+        if self.receive_window < self.stream_receive_window {
+            return Err(ConfigError::InvalidValue(
+                "QUIC_RECEIVE_WINDOW".to_string(),
+                "Must not be smaller than QUIC_STREAM_RECEIVE_WINDOW".to_string(),
+            ));
+        }
+
+        // Validate ALPN protocols (each id is 1-255 bytes on the wire)
+        if self.alpn_protocols.is_empty() {
+            return Err(ConfigError::InvalidValue(
+                "QUIC_ALPN_PROTOCOLS".to_string(),
+                "At least one protocol is required".to_string(),
+            ));
+        }
+        if let Some(bad) = self
+            .alpn_protocols
+            .iter()
+            .find(|p| p.is_empty() || p.len() > 255)
+        {
+            return Err(ConfigError::InvalidValue(
+                "QUIC_ALPN_PROTOCOLS".to_string(),
+                format!("Invalid protocol id '{}'", bad),
+            ));
+        }
+
         // Validate that keep-alive is less than idle timeout
         if self.keep_alive_interval_ms >= self.idle_timeout_ms {
             return Err(ConfigError::InvalidValue(
@@ -208,6 +616,94 @@ mod tests {
         assert_eq!(config.max_streams_per_connection, 100);
         assert_eq!(config.idle_timeout_ms, 30000);
         assert_eq!(config.keep_alive_interval_ms, 5000);
+        assert_eq!(config.idle_warning_ms, 10000);
+        assert_eq!(config.stream_priorities, StreamPriorities::default());
+        assert_eq!(config.stream_idle_timeouts, StreamIdleTimeouts::default());
+        assert_eq!(config.auth_timeout(), Duration::from_secs(10));
+        assert_eq!(config.max_auth_payload_bytes, 4096);
+        assert!(config.require_address_validation);
+        assert_eq!(config.preferred_transport, TransportType::Quic);
+        assert_eq!(config.resumption_ttl_secs, 60);
+    }
+
+    #[test]
+    fn test_validate_zero_auth_timeout() {
+        let mut config = QuicServerConfig::default();
+        config.auth_timeout_secs = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_max_auth_payload_bytes() {
+        let mut config = QuicServerConfig {
+            max_auth_payload_bytes: 0,
+            ..QuicServerConfig::default()
+        };
+        assert!(config.validate().is_err());
+
+        config.max_auth_payload_bytes = MAX_AUTH_PAYLOAD_BYTES + 1;
+        assert!(config.validate().is_err());
+
+        config.max_auth_payload_bytes = MAX_AUTH_PAYLOAD_BYTES;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_connection_rate_needs_burst() {
+        let mut config = QuicServerConfig::default();
+        config.connection_burst_per_ip = 0;
+        assert!(config.validate().is_err());
+
+        // No burst is fine when the limit is off
+        config.connection_rate_per_ip = 0;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_alpn_protocols() {
+        let mut config = QuicServerConfig::default();
+        assert_eq!(config.alpn_protocols, vec!["giano/1", "h3"]);
+        assert_eq!(config.alpn_protocol_ids()[0], b"giano/1".to_vec());
+
+        config.alpn_protocols.clear();
+        assert!(config.validate().is_err());
+
+        config.alpn_protocols = vec!["x".repeat(256)];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_zero_stream_idle_timeout() {
+        let mut config = QuicServerConfig::default();
+        config.stream_idle_timeouts.file_transfer_ms = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_flow_control_windows() {
+        let mut config = QuicServerConfig::default();
+        config.send_window = MIN_FLOW_CONTROL_WINDOW - 1;
+        assert!(config.validate().is_err());
+
+        config.send_window = default_send_window();
+        config.stream_receive_window = MAX_FLOW_CONTROL_WINDOW + 1;
+        assert!(config.validate().is_err());
+
+        // The connection window must fit at least one full stream window
+        config.stream_receive_window = 4 * 1024 * 1024;
+        config.receive_window = 2 * 1024 * 1024;
+        assert!(config.validate().is_err());
+
+        config.receive_window = 4 * 1024 * 1024;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_default_stream_priorities_order() {
+        let p = StreamPriorities::default();
+        assert!(p.control > p.chat_message);
+        assert!(p.chat_message > p.bot_command);
+        assert!(p.bot_command > p.file_transfer);
     }
 
     #[test]
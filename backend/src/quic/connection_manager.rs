@@ -1,4 +1,9 @@
+use chrono::{DateTime, Utc};
 use std::collections::HashMap;
+use std::future::Future;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
@@ -6,9 +11,47 @@ use uuid::Uuid;
 use quinn::Connection as QuinnConnection;
 use thiserror::Error;
 
+use crate::config::ConnectionLimitPolicy;
+use crate::quic::capabilities::NegotiatedCapabilities;
+use crate::ws::events::ServerEvent;
+
+/// Application close code sent when a user terminates a session from the devices list
+pub const SESSION_TERMINATED_CLOSE_CODE: u32 = 0x11;
+
+/// Application close code sent when an admin forcibly disconnects a user
+pub const ADMIN_DISCONNECT_CLOSE_CODE: u32 = 0x13;
+
+/// Application close code sent when a connection is refused or closed because
+/// its user holds `MAX_CONNECTIONS_PER_USER` connections
+pub const USER_CONNECTION_LIMIT_CLOSE_CODE: u32 = 0x15;
+
+/// Close reason sent alongside `USER_CONNECTION_LIMIT_CLOSE_CODE`
+pub const USER_CONNECTION_LIMIT_CLOSE_REASON: &[u8] = b"too many connections";
+
+/// Longest a single send may take before it counts as failed
+pub const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Connections found idle by `ConnectionManager::check_idle`
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct IdleCheck {
+    /// Idle for `idle_timeout - idle_warning_window`: send them a warning
+    pub warn: Vec<ConnectionId>,
+    /// Still idle at `idle_timeout` after being warned: disconnect them
+    pub disconnect: Vec<ConnectionId>,
+}
+
+/// Future returned by `WebSocketSendCallback`
+pub type WebSocketSendFuture = Pin<Box<dyn Future<Output = Result<(), String>> + Send>>;
+
 /// Callback for sending messages via WebSocket
-/// This allows the ConnectionManager to delegate WebSocket sends to WsManager
-pub type WebSocketSendCallback = Arc<dyn Fn(Uuid, Vec<u8>) -> Result<(), String> + Send + Sync>;
+/// This allows the ConnectionManager to delegate WebSocket sends to WsManager,
+/// which holds the socket of each connection
+pub type WebSocketSendCallback =
+    Arc<dyn Fn(Uuid, ConnectionId, Vec<u8>) -> WebSocketSendFuture + Send + Sync>;
+
+/// Callback for closing a WebSocket connection of a user
+/// The socket itself lives in WsManager, so closing is delegated to it
+pub type WebSocketCloseCallback = Arc<dyn Fn(Uuid, ConnectionId) + Send + Sync>;
 
 /// Connection manager errors
 #[derive(Debug, Error)]
@@ -27,6 +70,9 @@ pub enum ConnectionManagerError {
 
     #[error("Invalid connection type")]
     InvalidConnectionType,
+
+    #[error("Too many connections for user {0}")]
+    ConnectionLimitReached(Uuid),
 }
 
 /// Unique identifier for a connection
@@ -39,6 +85,11 @@ impl ConnectionId {
         Self(Uuid::new_v4())
     }
 
+    /// Wrap an existing UUID (e.g. a device id from the devices list)
+    pub fn from_uuid(id: Uuid) -> Self {
+        Self(id)
+    }
+
     /// Get the inner UUID
     pub fn as_uuid(&self) -> Uuid {
         self.0
@@ -58,7 +109,8 @@ impl std::fmt::Display for ConnectionId {
 }
 
 /// Type of transport connection
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum TransportType {
     /// QUIC connection
     Quic,
@@ -66,6 +118,35 @@ pub enum TransportType {
     WebSocket,
 }
 
+impl TransportType {
+    /// The transport to fall back to when this one fails
+    pub fn other(self) -> Self {
+        match self {
+            TransportType::Quic => TransportType::WebSocket,
+            TransportType::WebSocket => TransportType::Quic,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            TransportType::Quic => "QUIC",
+            TransportType::WebSocket => "WebSocket",
+        }
+    }
+}
+
+impl std::str::FromStr for TransportType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "quic" => Ok(TransportType::Quic),
+            "websocket" | "ws" => Ok(TransportType::WebSocket),
+            other => Err(format!("unknown transport '{}' (expected quic or websocket)", other)),
+        }
+    }
+}
+
 /// Represents a QUIC connection with its state
 #[derive(Debug)]
 pub struct QuicConnection {
@@ -87,6 +168,32 @@ pub struct QuicConnection {
     pub last_migration: Option<Instant>,
     /// Migration start timestamp (for timeout detection)
     pub migration_started_at: Option<Instant>,
+    /// Remote address, location and device label captured at authentication
+    pub info: Option<ConnectionInfo>,
+    /// Login session the connection authenticated with
+    pub session_id: Option<Uuid>,
+    /// Features agreed during the auth exchange
+    pub capabilities: Option<Arc<NegotiatedCapabilities>>,
+}
+
+/// Where a realtime session connects from, shown in the devices list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionInfo {
+    pub remote_ip: IpAddr,
+    /// Coarse location, "Unknown" for private/LAN addresses
+    pub location: String,
+    pub device_name: Option<String>,
+    pub device_type: Option<String>,
+}
+
+/// Snapshot of an authenticated realtime session for the devices list
+#[derive(Debug, Clone)]
+pub struct RealtimeSession {
+    pub connection_id: ConnectionId,
+    pub transport: TransportType,
+    pub info: Option<ConnectionInfo>,
+    pub connected_at: DateTime<Utc>,
+    pub last_activity: DateTime<Utc>,
 }
 
 /// Connection migration state
@@ -116,6 +223,9 @@ impl QuicConnection {
             migration_count: 0,
             last_migration: None,
             migration_started_at: None,
+            info: None,
+            session_id: None,
+            capabilities: None,
         }
     }
 
@@ -129,6 +239,21 @@ impl QuicConnection {
         self.user_id = Some(user_id);
     }
 
+    /// Bind the connection to the login session it authenticated with
+    pub fn set_session_id(&mut self, session_id: Option<Uuid>) {
+        self.session_id = session_id;
+    }
+
+    /// Record the features agreed with the client
+    pub fn set_capabilities(&mut self, capabilities: NegotiatedCapabilities) {
+        self.capabilities = Some(Arc::new(capabilities));
+    }
+
+    /// Record where the connection comes from and which device it is
+    pub fn set_info(&mut self, info: ConnectionInfo) {
+        self.info = Some(info);
+    }
+
     /// Check if the connection is authenticated
     pub fn is_authenticated(&self) -> bool {
         self.user_id.is_some()
@@ -239,6 +364,8 @@ pub struct WebSocketConnection {
     pub last_activity: Instant,
     /// Connection established timestamp
     pub connected_at: Instant,
+    /// Login session the connection authenticated with
+    pub session_id: Option<Uuid>,
 }
 
 impl WebSocketConnection {
@@ -250,9 +377,16 @@ impl WebSocketConnection {
             user_id,
             last_activity: now,
             connected_at: now,
+            session_id: None,
         }
     }
 
+    /// Record the login session the connection authenticated with
+    pub fn with_session(mut self, session_id: Option<Uuid>) -> Self {
+        self.session_id = session_id;
+        self
+    }
+
     /// Update the last activity timestamp
     pub fn update_activity(&mut self) {
         self.last_activity = Instant::now();
@@ -329,6 +463,41 @@ impl Connection {
             Connection::WebSocket(conn) => conn.connected_at,
         }
     }
+
+    /// Login session the connection authenticated with, if any
+    pub fn session_id(&self) -> Option<Uuid> {
+        match self {
+            Connection::Quic(conn) => conn.session_id,
+            Connection::WebSocket(conn) => conn.session_id,
+        }
+    }
+
+    /// Remote address/location/device captured at authentication, if any
+    pub fn info(&self) -> Option<&ConnectionInfo> {
+        match self {
+            Connection::Quic(conn) => conn.info.as_ref(),
+            Connection::WebSocket(_) => None,
+        }
+    }
+}
+
+/// Convert a monotonic timestamp into wall-clock time for display
+fn instant_to_utc(instant: Instant) -> DateTime<Utc> {
+    let elapsed = chrono::Duration::from_std(instant.elapsed()).unwrap_or_default();
+    Utc::now() - elapsed
+}
+
+/// What `ConnectionManager::send_message` sends over, taken out of the map
+enum SendTarget {
+    Quic(QuinnConnection),
+    WebSocket(Uuid),
+}
+
+/// One device's connections, split by the preferred transport
+#[derive(Default)]
+struct DeviceConnections {
+    preferred: Vec<ConnectionId>,
+    fallback: Vec<ConnectionId>,
 }
 
 /// Manages all active connections (QUIC and WebSocket)
@@ -341,8 +510,22 @@ pub struct ConnectionManager {
     keep_alive_interval: Duration,
     /// Idle timeout duration
     idle_timeout: Duration,
+    /// How long before `idle_timeout` idle connections are warned (zero = never)
+    idle_warning_window: Duration,
+    /// Warned connections, with their last activity when the warning went out
+    idle_warnings: Arc<RwLock<HashMap<ConnectionId, Instant>>>,
     /// Optional callback for sending WebSocket messages
     websocket_send_callback: Option<WebSocketSendCallback>,
+    /// Optional callback for closing WebSocket connections evicted by the connection limit
+    websocket_close_callback: Option<WebSocketCloseCallback>,
+    /// Connections one user may hold across both transports (0 = unlimited)
+    max_connections_per_user: usize,
+    /// How a connection beyond `max_connections_per_user` is handled
+    connection_limit_policy: ConnectionLimitPolicy,
+    /// Transport tried first for users connected over both
+    preferred_transport: TransportType,
+    /// Deliveries that failed on the preferred transport and went over the other
+    transport_fallbacks: AtomicU64,
 }
 
 impl ConnectionManager {
@@ -365,10 +548,62 @@ impl ConnectionManager {
             user_connections: Arc::new(RwLock::new(HashMap::new())),
             keep_alive_interval,
             idle_timeout,
+            idle_warning_window: Duration::ZERO,
+            idle_warnings: Arc::new(RwLock::new(HashMap::new())),
             websocket_send_callback: None,
+            websocket_close_callback: None,
+            max_connections_per_user: 0,
+            connection_limit_policy: ConnectionLimitPolicy::default(),
+            preferred_transport: TransportType::Quic,
+            transport_fallbacks: AtomicU64::new(0),
         }
     }
 
+    /// Set the transport tried first by `send_to_user_preferred`
+    pub fn with_preferred_transport(mut self, transport: TransportType) -> Self {
+        self.preferred_transport = transport;
+        self
+    }
+
+    /// Warn idle connections this long before disconnecting them
+    ///
+    /// Clamped to half the idle timeout, so connections are never warned as
+    /// soon as they go quiet; zero disconnects without a warning.
+    pub fn with_idle_warning_window(mut self, window: Duration) -> Self {
+        self.idle_warning_window = window.min(self.idle_timeout / 2);
+        self
+    }
+
+    /// Limit each user to `max` connections (0 = unlimited), handling one
+    /// more according to `policy`
+    pub fn with_connection_limit(mut self, max: usize, policy: ConnectionLimitPolicy) -> Self {
+        self.max_connections_per_user = max;
+        self.connection_limit_policy = policy;
+        self
+    }
+
+    /// Send to WebSocket connections through `callback`
+    pub fn with_websocket_sender(mut self, callback: WebSocketSendCallback) -> Self {
+        self.websocket_send_callback = Some(callback);
+        self
+    }
+
+    /// Close evicted WebSocket connections through `callback`
+    pub fn with_websocket_closer(mut self, callback: WebSocketCloseCallback) -> Self {
+        self.websocket_close_callback = Some(callback);
+        self
+    }
+
+    /// Transport tried first for users connected over both
+    pub fn preferred_transport(&self) -> TransportType {
+        self.preferred_transport
+    }
+
+    /// Deliveries that fell back from the preferred transport so far
+    pub fn transport_fallback_count(&self) -> u64 {
+        self.transport_fallbacks.load(Ordering::Relaxed)
+    }
+
     /// Set the WebSocket send callback
     ///
     /// # Requirements
@@ -387,8 +622,17 @@ impl ConnectionManager {
         self.idle_timeout
     }
 
+    /// Get the idle warning window
+    pub fn idle_warning_window(&self) -> Duration {
+        self.idle_warning_window
+    }
+
     /// Register a new connection
     ///
+    /// A user already holding `max_connections_per_user` connections either
+    /// gets `ConnectionLimitReached` (reject-new) or has their oldest
+    /// connections closed to make room (close-oldest).
+    ///
     /// # Requirements
     /// - 1.3: Track connections with user mappings
     /// - 1.4: Maintain connection state
@@ -396,19 +640,40 @@ impl ConnectionManager {
         let connection_id = connection.connection_id();
         let user_id = connection.user_id();
 
-        // Add to connections map
         let mut connections = self.connections.write().await;
-        connections.insert(connection_id, connection);
 
         // Add to user_connections map if authenticated
         if let Some(user_id) = user_id {
             let mut user_connections = self.user_connections.write().await;
-            user_connections
-                .entry(user_id)
-                .or_insert_with(Vec::new)
-                .push(connection_id);
+            let conn_ids = user_connections.entry(user_id).or_default();
+
+            let max = self.max_connections_per_user;
+            if max > 0 && conn_ids.len() >= max {
+                if self.connection_limit_policy == ConnectionLimitPolicy::RejectNew {
+                    tracing::warn!(
+                        "Connection refused: user_id={} already holds {} connections",
+                        user_id,
+                        max
+                    );
+                    return Err(ConnectionManagerError::ConnectionLimitReached(user_id));
+                }
+
+                // Connection IDs are kept in registration order
+                let excess = conn_ids.len() + 1 - max;
+                for evicted_id in conn_ids.drain(..excess) {
+                    if let Some(evicted) = connections.remove(&evicted_id) {
+                        self.close_evicted(evicted);
+                    }
+                    self.idle_warnings.write().await.remove(&evicted_id);
+                }
+            }
+
+            conn_ids.push(connection_id);
         }
 
+        // Add to connections map
+        connections.insert(connection_id, connection);
+
         tracing::info!(
             "Connection registered: connection_id={}, user_id={:?}",
             connection_id,
@@ -418,6 +683,28 @@ impl ConnectionManager {
         Ok(connection_id)
     }
 
+    /// Close a connection evicted to make room for a newer one of its user
+    ///
+    /// Already unregistered; its handler's own unregister finds nothing.
+    fn close_evicted(&self, connection: Connection) {
+        let connection_id = connection.connection_id();
+        match connection {
+            Connection::Quic(conn) => conn.quinn_connection.close(
+                USER_CONNECTION_LIMIT_CLOSE_CODE.into(),
+                USER_CONNECTION_LIMIT_CLOSE_REASON,
+            ),
+            Connection::WebSocket(conn) => {
+                if let Some(callback) = &self.websocket_close_callback {
+                    callback(conn.user_id, connection_id);
+                }
+            }
+        }
+        tracing::info!(
+            "Closed oldest connection {} to stay within the per-user connection limit",
+            connection_id
+        );
+    }
+
     /// Unregister a connection
     ///
     /// # Requirements
@@ -430,6 +717,7 @@ impl ConnectionManager {
         let connection = connections
             .remove(&connection_id)
             .ok_or(ConnectionManagerError::ConnectionNotFound(connection_id))?;
+        self.idle_warnings.write().await.remove(&connection_id);
 
         // Remove from user_connections map if authenticated
         if let Some(user_id) = connection.user_id() {
@@ -457,6 +745,16 @@ impl ConnectionManager {
         connections.get(&connection_id).map(|_| connection_id)
     }
 
+    /// Features negotiated by a QUIC connection; `None` for WebSocket or
+    /// unknown connections
+    pub async fn capabilities(&self, connection_id: ConnectionId) -> Option<Arc<NegotiatedCapabilities>> {
+        let connections = self.connections.read().await;
+        match connections.get(&connection_id) {
+            Some(Connection::Quic(conn)) => conn.capabilities.clone(),
+            _ => None,
+        }
+    }
+
     /// Get all connection IDs for a user
     ///
     /// # Requirements
@@ -478,6 +776,106 @@ impl ConnectionManager {
             .unwrap_or(false)
     }
 
+    /// List a user's authenticated realtime sessions, most recently active first
+    pub async fn get_user_sessions(&self, user_id: Uuid) -> Vec<RealtimeSession> {
+        let connections = self.connections.read().await;
+        let user_connections = self.user_connections.read().await;
+
+        let mut sessions: Vec<RealtimeSession> = user_connections
+            .get(&user_id)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| connections.get(id))
+            .map(|conn| RealtimeSession {
+                connection_id: conn.connection_id(),
+                transport: conn.transport_type(),
+                info: conn.info().cloned(),
+                connected_at: instant_to_utc(conn.connected_at()),
+                last_activity: instant_to_utc(conn.last_activity()),
+            })
+            .collect();
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.last_activity));
+        sessions
+    }
+
+    /// Close one of the user's QUIC connections (terminating a realtime session)
+    ///
+    /// Returns `false` if the connection does not exist, belongs to someone
+    /// else, or is not a QUIC connection. The acceptance loop unregisters the
+    /// connection once its handler notices the close.
+    pub async fn close_user_connection(&self, user_id: Uuid, connection_id: ConnectionId) -> bool {
+        let connections = self.connections.read().await;
+        match connections.get(&connection_id) {
+            Some(Connection::Quic(conn)) if conn.user_id == Some(user_id) => {
+                conn.quinn_connection
+                    .close(SESSION_TERMINATED_CLOSE_CODE.into(), b"session terminated");
+                tracing::info!(
+                    "Realtime session terminated by user: connection_id={}, user_id={}",
+                    connection_id,
+                    user_id
+                );
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Close the user's QUIC connections authenticated with a login session,
+    /// returning how many were closed
+    pub async fn close_session_connections(&self, user_id: Uuid, session_id: Uuid) -> usize {
+        let connection_ids = self.get_user_connections(user_id).await;
+        let connections = self.connections.read().await;
+
+        let mut closed = 0;
+        for connection_id in connection_ids {
+            if let Some(Connection::Quic(conn)) = connections.get(&connection_id) {
+                if conn.session_id == Some(session_id) {
+                    conn.quinn_connection
+                        .close(SESSION_TERMINATED_CLOSE_CODE.into(), b"session terminated");
+                    closed += 1;
+                }
+            }
+        }
+
+        if closed > 0 {
+            tracing::info!(
+                "Closed {} QUIC connection(s) of terminated session {} for user_id={}",
+                closed,
+                session_id,
+                user_id
+            );
+        }
+        closed
+    }
+
+    /// Close every QUIC connection belonging to a user, returning how many were closed
+    ///
+    /// Connections registered after the snapshot is taken are not affected;
+    /// callers that need to keep the user out must revoke their tokens first.
+    pub async fn close_all_user_connections(&self, user_id: Uuid, reason: &str) -> usize {
+        let connection_ids = self.get_user_connections(user_id).await;
+        let connections = self.connections.read().await;
+
+        let mut closed = 0;
+        for connection_id in connection_ids {
+            if let Some(Connection::Quic(conn)) = connections.get(&connection_id) {
+                conn.quinn_connection
+                    .close(ADMIN_DISCONNECT_CLOSE_CODE.into(), reason.as_bytes());
+                closed += 1;
+            }
+        }
+
+        if closed > 0 {
+            tracing::info!(
+                "Closed {} QUIC connection(s) for user_id={}: {}",
+                closed,
+                user_id,
+                reason
+            );
+        }
+        closed
+    }
+
     /// Get the number of active connections
     pub async fn connection_count(&self) -> usize {
         let connections = self.connections.read().await;
@@ -593,6 +991,51 @@ impl ConnectionManager {
         self.get_inactive_connections(self.idle_timeout).await
     }
 
+    /// Two-phase idle check: which connections to warn and which to disconnect
+    ///
+    /// A connection is warned once it has been idle for `idle_timeout -
+    /// idle_warning_window`, and only disconnected once it is idle past
+    /// `idle_timeout` after that warning. Any activity since the warning
+    /// (which moves `last_activity`) cancels it. A connection first seen
+    /// past the timeout is warned now and disconnected on a later check.
+    pub async fn check_idle(&self) -> IdleCheck {
+        let connections = self.connections.read().await;
+        let mut warnings = self.idle_warnings.write().await;
+        warnings.retain(|id, warned_activity| {
+            connections
+                .get(id)
+                .is_some_and(|conn| conn.last_activity() == *warned_activity)
+        });
+
+        let warn_after = self.idle_timeout.saturating_sub(self.idle_warning_window);
+        let mut check = IdleCheck::default();
+        for (id, conn) in connections.iter() {
+            let idle = conn.time_since_activity();
+            if self.idle_warning_window.is_zero() || warnings.contains_key(id) {
+                if idle > self.idle_timeout {
+                    check.disconnect.push(*id);
+                }
+            } else if idle > warn_after {
+                warnings.insert(*id, conn.last_activity());
+                check.warn.push(*id);
+            }
+        }
+
+        check
+    }
+
+    /// Tell an idle connection it will be disconnected unless it sends something
+    pub async fn send_idle_warning(&self, connection_id: ConnectionId) -> Result<(), ConnectionManagerError> {
+        let event = ServerEvent::IdleWarning {
+            disconnect_in_seconds: self.idle_warning_window.as_secs().max(1),
+        };
+        let data = serde_json::to_vec(&event)
+            .map_err(|e| ConnectionManagerError::SendError(e.to_string()))?;
+
+        // Not activity: only the client's answer keeps the connection
+        self.send_message(connection_id, &data).await
+    }
+
     /// Run the keep-alive loop for all connections
     /// This should be spawned as a background task
     ///
@@ -618,6 +1061,9 @@ impl ConnectionManager {
     /// Run the timeout check loop
     /// This should be spawned as a background task
     ///
+    /// Idle connections get an `idle_warning` event before they are
+    /// unregistered (see `check_idle`).
+    ///
     /// # Requirements
     /// - 1.4: Handle connection timeout detection
     pub async fn run_timeout_loop(self: Arc<Self>) -> Vec<ConnectionId> {
@@ -627,8 +1073,15 @@ impl ConnectionManager {
         loop {
             interval.tick().await;
 
-            // Check for timed-out connections
-            let timeout_ids = self.check_timeouts().await;
+            let IdleCheck { warn, disconnect: timeout_ids } = self.check_idle().await;
+
+            // Give idle clients a chance to wake up before they are dropped
+            for conn_id in warn {
+                tracing::debug!("Warning idle connection: {}", conn_id);
+                if let Err(e) = self.send_idle_warning(conn_id).await {
+                    tracing::warn!("Failed to send idle warning to {}: {}", conn_id, e);
+                }
+            }
 
             if !timeout_ids.is_empty() {
                 tracing::info!("Found {} timed-out connections", timeout_ids.len());
@@ -649,6 +1102,10 @@ impl ConnectionManager {
 
     /// Send a message via a specific connection
     ///
+    /// The connection is looked up and the map released before sending, so a
+    /// stalled peer holds up neither other sends nor (un)registration. A send
+    /// not done within `SEND_TIMEOUT` fails.
+    ///
     /// # Requirements
     /// - 5.4: Unified send interface for both transports
     /// - 5.4: Handle send errors gracefully
@@ -657,47 +1114,61 @@ impl ConnectionManager {
         connection_id: ConnectionId,
         data: &[u8],
     ) -> Result<(), ConnectionManagerError> {
-        let connections = self.connections.read().await;
-        let connection = connections
-            .get(&connection_id)
-            .ok_or(ConnectionManagerError::ConnectionNotFound(connection_id))?;
-
-        match connection {
-            Connection::Quic(quic_conn) => {
-                // Open a new unidirectional stream for sending
-                let mut send_stream = quic_conn
-                    .quinn_connection
-                    .open_uni()
-                    .await
-                    .map_err(|e| ConnectionManagerError::SendError(e.to_string()))?;
-
-                // Send the data
-                send_stream
-                    .write_all(data)
-                    .await
-                    .map_err(|e| ConnectionManagerError::SendError(e.to_string()))?;
-
-                // Finish the stream
-                send_stream
-                    .finish()
-                    .map_err(|e| ConnectionManagerError::SendError(e.to_string()))?;
-
-                Ok(())
+        let target = {
+            let connections = self.connections.read().await;
+            match connections
+                .get(&connection_id)
+                .ok_or(ConnectionManagerError::ConnectionNotFound(connection_id))?
+            {
+                Connection::Quic(quic_conn) => SendTarget::Quic(quic_conn.quinn_connection.clone()),
+                Connection::WebSocket(ws_conn) => SendTarget::WebSocket(ws_conn.user_id),
             }
-            Connection::WebSocket(ws_conn) => {
-                // Use the callback to send via WebSocket
-                if let Some(callback) = &self.websocket_send_callback {
-                    callback(ws_conn.user_id, data.to_vec())
-                        .map_err(|e| ConnectionManagerError::SendError(e))?;
+        };
+
+        let send = async {
+            match target {
+                SendTarget::Quic(quinn_connection) => {
+                    // Open a new unidirectional stream for sending
+                    let mut send_stream = quinn_connection
+                        .open_uni()
+                        .await
+                        .map_err(|e| ConnectionManagerError::SendError(e.to_string()))?;
+
+                    // Send the data
+                    send_stream
+                        .write_all(data)
+                        .await
+                        .map_err(|e| ConnectionManagerError::SendError(e.to_string()))?;
+
+                    // Finish the stream
+                    send_stream
+                        .finish()
+                        .map_err(|e| ConnectionManagerError::SendError(e.to_string()))?;
+
                     Ok(())
-                } else {
-                    // If no callback is set, return an error
-                    Err(ConnectionManagerError::SendError(
-                        "WebSocket send callback not configured".to_string(),
-                    ))
+                }
+                SendTarget::WebSocket(user_id) => {
+                    // Use the callback to send via WebSocket
+                    let callback = self.websocket_send_callback.as_ref().ok_or_else(|| {
+                        ConnectionManagerError::SendError(
+                            "WebSocket send callback not configured".to_string(),
+                        )
+                    })?;
+                    callback(user_id, connection_id, data.to_vec())
+                        .await
+                        .map_err(ConnectionManagerError::SendError)
                 }
             }
-        }
+        };
+
+        tokio::time::timeout(SEND_TIMEOUT, send)
+            .await
+            .unwrap_or_else(|_| {
+                Err(ConnectionManagerError::SendError(format!(
+                    "timed out after {:?}",
+                    SEND_TIMEOUT
+                )))
+            })
     }
 
     /// Broadcast a message to all connections of a user
@@ -780,14 +1251,146 @@ impl ConnectionManager {
             Ok(sent_count)
         } else {
             Err(ConnectionManagerError::SendError(
-                format!("No {} connections found for user", match transport_type {
-                    TransportType::Quic => "QUIC",
-                    TransportType::WebSocket => "WebSocket",
-                })
+                format!("No {} connections found for user", transport_type.label())
             ))
         }
     }
 
+    /// Deliver to every device of a user, each over the preferred transport
+    /// with a fallback to the other one
+    ///
+    /// A user's connections are grouped into devices by the login session
+    /// they authenticated with; a connection without one is a device of its
+    /// own. Each device gets the message on all its connections over the
+    /// preferred transport, or, if none of those sends succeed, on its
+    /// connections over the other transport, which counts as a fallback.
+    /// Devices are served concurrently.
+    ///
+    /// Returns how many devices were reached.
+    pub async fn send_to_user_preferred(
+        &self,
+        user_id: Uuid,
+        data: &[u8],
+    ) -> Result<usize, ConnectionManagerError> {
+        let preferred = self.preferred_transport;
+
+        let devices = {
+            let connections = self.connections.read().await;
+            let user_connections = self.user_connections.read().await;
+            let mut devices: HashMap<Uuid, DeviceConnections> = HashMap::new();
+            for conn in user_connections
+                .get(&user_id)
+                .into_iter()
+                .flatten()
+                .filter_map(|id| connections.get(id))
+            {
+                let id = conn.connection_id();
+                let device = devices
+                    .entry(conn.session_id().unwrap_or(id.as_uuid()))
+                    .or_default();
+                if conn.transport_type() == preferred {
+                    device.preferred.push(id);
+                } else {
+                    device.fallback.push(id);
+                }
+            }
+            devices
+        };
+
+        if devices.is_empty() {
+            return Err(ConnectionManagerError::UserNotFound(user_id));
+        }
+
+        let results = futures::future::join_all(
+            devices
+                .values()
+                .map(|device| self.send_to_device(user_id, device, data)),
+        )
+        .await;
+        let reached = results.iter().filter(|result| result.is_ok()).count();
+        if reached > 0 {
+            return Ok(reached);
+        }
+        Err(results
+            .into_iter()
+            .find_map(Result::err)
+            .unwrap_or_else(|| ConnectionManagerError::SendError("All sends failed".to_string())))
+    }
+
+    /// Send to one device's preferred connections, then its fallback ones
+    async fn send_to_device(
+        &self,
+        user_id: Uuid,
+        device: &DeviceConnections,
+        data: &[u8],
+    ) -> Result<(), ConnectionManagerError> {
+        let preferred = self.preferred_transport;
+
+        if !device.preferred.is_empty() {
+            match self.send_to_all(&device.preferred, data).await {
+                Ok(()) => return Ok(()),
+                Err(e) if !device.fallback.is_empty() => {
+                    tracing::warn!(
+                        "{} send to user {} failed, falling back to {}: {}",
+                        preferred.label(),
+                        user_id,
+                        preferred.other().label(),
+                        e
+                    );
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.send_to_all(&device.fallback, data).await?;
+        if !device.preferred.is_empty() {
+            self.transport_fallbacks.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// Send to `connection_ids` concurrently; succeeds if any send does
+    async fn send_to_all(
+        &self,
+        connection_ids: &[ConnectionId],
+        data: &[u8],
+    ) -> Result<(), ConnectionManagerError> {
+        let results = futures::future::join_all(
+            connection_ids
+                .iter()
+                .map(|&connection_id| self.send_message(connection_id, data)),
+        )
+        .await;
+
+        let mut first_error = None;
+        for (connection_id, result) in connection_ids.iter().zip(results) {
+            match result {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    tracing::warn!("Failed to send to connection {}: {}", connection_id, e);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        Err(first_error
+            .unwrap_or_else(|| ConnectionManagerError::SendError("All sends failed".to_string())))
+    }
+
+    /// Users with at least one connection over `transport`
+    pub async fn users_on_transport(&self, transport: TransportType) -> Vec<Uuid> {
+        let connections = self.connections.read().await;
+        let user_connections = self.user_connections.read().await;
+        user_connections
+            .iter()
+            .filter(|(_, ids)| {
+                ids.iter()
+                    .filter_map(|id| connections.get(id))
+                    .any(|conn| conn.transport_type() == transport)
+            })
+            .map(|(user_id, _)| *user_id)
+            .collect()
+    }
+
     /// Get all connection IDs
     pub async fn get_all_connection_ids(&self) -> Vec<ConnectionId> {
         let connections = self.connections.read().await;
@@ -820,6 +1423,7 @@ impl ConnectionManager {
             websocket_connections: websocket,
             authenticated_connections: authenticated,
             unique_users,
+            transport_fallbacks: self.transport_fallback_count(),
         }
     }
 
@@ -1131,6 +1735,8 @@ pub struct ConnectionStats {
     pub authenticated_connections: usize,
     /// Number of unique users
     pub unique_users: usize,
+    /// Deliveries that fell back from the preferred transport
+    pub transport_fallbacks: u64,
 }
 
 /// Migration statistics
@@ -1148,6 +1754,16 @@ pub struct MigrationStats {
 mod tests {
     use super::*;
 
+    /// WebSocket send callback answering synchronously
+    fn ws_sender(
+        send: impl Fn(Uuid, Vec<u8>) -> Result<(), String> + Send + Sync + 'static,
+    ) -> WebSocketSendCallback {
+        Arc::new(move |user_id, _, data| {
+            let result = send(user_id, data);
+            Box::pin(async move { result })
+        })
+    }
+
     #[test]
     fn test_connection_id_new() {
         let id1 = ConnectionId::new();
@@ -1155,6 +1771,91 @@ mod tests {
         assert_ne!(id1, id2);
     }
 
+    #[tokio::test]
+    async fn test_user_sessions_and_close() {
+        let manager = ConnectionManager::new();
+        let user_id = Uuid::new_v4();
+        let conn_id = ConnectionId::new();
+        manager
+            .register_connection(Connection::WebSocket(WebSocketConnection::new(conn_id, user_id)))
+            .await
+            .unwrap();
+
+        let sessions = manager.get_user_sessions(user_id).await;
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].connection_id, conn_id);
+        assert_eq!(sessions[0].transport, TransportType::WebSocket);
+        assert!(sessions[0].info.is_none());
+        assert!(manager.get_user_sessions(Uuid::new_v4()).await.is_empty());
+
+        // Only QUIC connections owned by the caller can be closed
+        assert!(!manager.close_user_connection(user_id, conn_id).await);
+        assert!(!manager.close_user_connection(Uuid::new_v4(), conn_id).await);
+        assert_eq!(ConnectionId::from_uuid(conn_id.as_uuid()), conn_id);
+    }
+
+    #[tokio::test]
+    async fn test_connection_limit_rejects_new_connections() {
+        let manager = ConnectionManager::new().with_connection_limit(2, ConnectionLimitPolicy::RejectNew);
+        let (user_id, other_user) = (Uuid::new_v4(), Uuid::new_v4());
+        let register = |user_id| {
+            let conn_id = ConnectionId::new();
+            let connection = Connection::WebSocket(WebSocketConnection::new(conn_id, user_id));
+            (conn_id, manager.register_connection(connection))
+        };
+
+        let (first, result) = register(user_id);
+        result.await.unwrap();
+        register(user_id).1.await.unwrap();
+        let (refused, result) = register(user_id);
+        assert!(matches!(
+            result.await,
+            Err(ConnectionManagerError::ConnectionLimitReached(id)) if id == user_id
+        ));
+        assert!(!manager.get_user_connections(user_id).await.contains(&refused));
+        assert_eq!(manager.connection_count().await, 2);
+
+        // The limit is per user, and freed slots can be used again
+        register(other_user).1.await.unwrap();
+        manager.unregister_connection(first).await.unwrap();
+        register(user_id).1.await.unwrap();
+        assert_eq!(manager.get_user_connections(user_id).await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_connection_limit_closes_oldest_connections() {
+        let closed = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorder = closed.clone();
+        let manager = ConnectionManager::new()
+            .with_connection_limit(2, ConnectionLimitPolicy::CloseOldest)
+            .with_websocket_closer(Arc::new(move |user_id, conn_id| {
+                recorder.lock().unwrap().push((user_id, conn_id));
+            }));
+        let user_id = Uuid::new_v4();
+
+        let mut conn_ids = Vec::new();
+        for _ in 0..4 {
+            let conn_id = ConnectionId::new();
+            manager
+                .register_connection(Connection::WebSocket(WebSocketConnection::new(conn_id, user_id)))
+                .await
+                .unwrap();
+            conn_ids.push(conn_id);
+        }
+
+        // Each connection beyond the limit pushed out the oldest one
+        assert_eq!(manager.get_user_connections(user_id).await, conn_ids[2..].to_vec());
+        assert_eq!(manager.connection_count().await, 2);
+        assert_eq!(
+            *closed.lock().unwrap(),
+            vec![(user_id, conn_ids[0]), (user_id, conn_ids[1])]
+        );
+        assert!(matches!(
+            manager.unregister_connection(conn_ids[0]).await,
+            Err(ConnectionManagerError::ConnectionNotFound(_))
+        ));
+    }
+
     #[test]
     fn test_connection_id_display() {
         let id = ConnectionId::new();
@@ -1323,6 +2024,44 @@ mod tests {
         assert_eq!(timed_out[0], conn_id);
     }
 
+    #[tokio::test]
+    async fn test_idle_warning_then_recover() {
+        let sent: Arc<std::sync::Mutex<Vec<Vec<u8>>>> = Arc::default();
+        let mut manager = ConnectionManager::with_timeouts(
+            Duration::from_secs(30),
+            Duration::from_millis(100),
+        )
+        .with_idle_warning_window(Duration::from_millis(50));
+        let sink = Arc::clone(&sent);
+        manager.set_websocket_callback(ws_sender(move |_, data| {
+            sink.lock().unwrap().push(data);
+            Ok(())
+        }));
+
+        let conn_id = ConnectionId::new();
+        let ws_conn = WebSocketConnection::new(conn_id, Uuid::new_v4());
+        manager.register_connection(Connection::WebSocket(ws_conn)).await.unwrap();
+
+        // Inside the warning window: warned once, not disconnected
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(manager.check_idle().await, IdleCheck { warn: vec![conn_id], disconnect: vec![] });
+        assert_eq!(manager.check_idle().await, IdleCheck::default());
+
+        manager.send_idle_warning(conn_id).await.unwrap();
+        let warning: serde_json::Value =
+            serde_json::from_slice(&sent.lock().unwrap()[0]).unwrap();
+        assert_eq!(warning["event"], "idle_warning");
+
+        // The client answers: the pending disconnect is cancelled
+        manager.update_activity(conn_id).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(manager.check_idle().await, IdleCheck { warn: vec![conn_id], disconnect: vec![] });
+
+        // Warned again and still idle past the timeout: now it is dropped
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(manager.check_idle().await, IdleCheck { warn: vec![], disconnect: vec![conn_id] });
+    }
+
     #[tokio::test]
     async fn test_get_connections_needing_keepalive() {
         let manager = ConnectionManager::with_timeouts(
@@ -1347,13 +2086,13 @@ mod tests {
         let mut manager = ConnectionManager::new();
         
         // Set up a callback that tracks calls
-        let callback = Arc::new(|user_id: Uuid, data: Vec<u8>| -> Result<(), String> {
+        let callback = |user_id: Uuid, data: Vec<u8>| -> Result<(), String> {
             assert!(!data.is_empty());
             assert_ne!(user_id, Uuid::nil());
             Ok(())
-        });
+        };
 
-        manager.set_websocket_callback(callback);
+        manager.set_websocket_callback(ws_sender(callback));
 
         let conn_id = ConnectionId::new();
         let user_id = Uuid::new_v4();
@@ -1372,10 +2111,10 @@ mod tests {
         let mut manager = ConnectionManager::new();
         
         // Set up a callback
-        let callback = Arc::new(|_user_id: Uuid, _data: Vec<u8>| -> Result<(), String> {
+        let callback = |_user_id: Uuid, _data: Vec<u8>| -> Result<(), String> {
             Ok(())
-        });
-        manager.set_websocket_callback(callback);
+        };
+        manager.set_websocket_callback(ws_sender(callback));
 
         let user_id = Uuid::new_v4();
 
@@ -1405,10 +2144,10 @@ mod tests {
         let mut manager = ConnectionManager::new();
         
         // Set up a callback
-        let callback = Arc::new(|_user_id: Uuid, _data: Vec<u8>| -> Result<(), String> {
+        let callback = |_user_id: Uuid, _data: Vec<u8>| -> Result<(), String> {
             Ok(())
-        });
-        manager.set_websocket_callback(callback);
+        };
+        manager.set_websocket_callback(ws_sender(callback));
 
         let user_id = Uuid::new_v4();
 
@@ -1433,4 +2172,65 @@ mod tests {
             .await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_send_to_user_preferred_uses_available_transport() {
+        let user_id = Uuid::new_v4();
+        let mut manager = ConnectionManager::new();
+        manager.set_websocket_callback(ws_sender(
+            |_user_id: Uuid, _data: Vec<u8>| -> Result<(), String> { Ok(()) },
+        ));
+        assert_eq!(manager.preferred_transport(), TransportType::Quic);
+
+        assert!(matches!(
+            manager.send_to_user_preferred(user_id, b"test").await,
+            Err(ConnectionManagerError::UserNotFound(_))
+        ));
+
+        manager
+            .register_connection(Connection::WebSocket(WebSocketConnection::new(
+                ConnectionId::new(),
+                user_id,
+            )))
+            .await
+            .unwrap();
+
+        // Only connected over WebSocket: served there, not a fallback
+        let devices = manager.send_to_user_preferred(user_id, b"test").await.unwrap();
+        assert_eq!(devices, 1);
+        assert_eq!(manager.transport_fallback_count(), 0);
+        assert_eq!(manager.get_stats().await.transport_fallbacks, 0);
+    }
+
+    #[tokio::test]
+    async fn test_send_to_user_preferred_reports_failure_without_fallback() {
+        let user_id = Uuid::new_v4();
+        let mut manager =
+            ConnectionManager::new().with_preferred_transport(TransportType::WebSocket);
+        manager.set_websocket_callback(ws_sender(
+            |_user_id: Uuid, _data: Vec<u8>| -> Result<(), String> {
+                Err("socket closed".to_string())
+            },
+        ));
+        manager
+            .register_connection(Connection::WebSocket(WebSocketConnection::new(
+                ConnectionId::new(),
+                user_id,
+            )))
+            .await
+            .unwrap();
+
+        assert!(manager.send_to_user_preferred(user_id, b"test").await.is_err());
+        assert_eq!(manager.transport_fallback_count(), 0);
+    }
+
+    #[test]
+    fn test_transport_type_parse_and_other() {
+        assert_eq!("quic".parse::<TransportType>(), Ok(TransportType::Quic));
+        assert_eq!(" WebSocket ".parse::<TransportType>(), Ok(TransportType::WebSocket));
+        assert_eq!("ws".parse::<TransportType>(), Ok(TransportType::WebSocket));
+        assert!("tcp".parse::<TransportType>().is_err());
+        assert_eq!(TransportType::Quic.other(), TransportType::WebSocket);
+        assert_eq!(TransportType::WebSocket.other(), TransportType::Quic);
+    }
 }
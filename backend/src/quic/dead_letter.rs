@@ -0,0 +1,215 @@
+/// Dead-letter log module - keeps a record of QUIC messages that could not be routed
+///
+/// This module provides:
+/// - A bounded in-memory ring of recent routing failures
+/// - Redaction of payloads before they are stored (string values are never kept)
+/// - A payload fingerprint so repeated failures from the same client can be correlated
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use super::connection_manager::ConnectionId;
+
+/// Maximum length of the redacted payload preview kept per entry
+const MAX_PREVIEW_LEN: usize = 512;
+
+/// A single unroutable message
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadLetter {
+    pub id: Uuid,
+    #[serde(rename = "connectionId")]
+    pub connection_id: Uuid,
+    #[serde(rename = "userId")]
+    pub user_id: Uuid,
+    pub error: String,
+    /// Fingerprint of the raw payload
+    #[serde(rename = "payloadHash")]
+    pub payload_hash: String,
+    #[serde(rename = "payloadSize")]
+    pub payload_size: usize,
+    /// Payload structure with all string values redacted, truncated
+    #[serde(rename = "redactedPayload")]
+    pub redacted_payload: Option<String>,
+    #[serde(rename = "recordedAt")]
+    pub recorded_at: DateTime<Utc>,
+}
+
+/// Bounded ring of recent dead letters
+pub struct DeadLetterLog {
+    enabled: bool,
+    capacity: usize,
+    entries: Mutex<VecDeque<DeadLetter>>,
+}
+
+impl DeadLetterLog {
+    /// Create a new dead-letter log
+    ///
+    /// # Arguments
+    /// * `enabled` - Whether failures should be recorded at all
+    /// * `capacity` - Maximum number of entries kept; oldest entries are evicted first
+    pub fn new(enabled: bool, capacity: usize) -> Self {
+        Self {
+            enabled: enabled && capacity > 0,
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity.min(1024))),
+        }
+    }
+
+    /// Whether the log records anything
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Record an unroutable payload
+    pub fn record(
+        &self,
+        data: &[u8],
+        connection_id: ConnectionId,
+        user_id: Uuid,
+        error: &str,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        let entry = DeadLetter {
+            id: Uuid::new_v4(),
+            connection_id: connection_id.as_uuid(),
+            user_id,
+            error: error.to_string(),
+            payload_hash: fingerprint(data),
+            payload_size: data.len(),
+            redacted_payload: redact_payload(data),
+            recorded_at: Utc::now(),
+        };
+
+        let mut entries = self.entries.lock().unwrap();
+        while entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// Get the most recent entries, newest first
+    pub fn recent(&self, limit: usize) -> Vec<DeadLetter> {
+        let entries = self.entries.lock().unwrap();
+        entries.iter().rev().take(limit).cloned().collect()
+    }
+
+    /// Number of entries currently held
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Whether the log is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove all entries
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+/// Compute a hex fingerprint of a payload
+fn fingerprint(data: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Produce a PII-free view of a payload.
+///
+/// JSON payloads keep their structure (keys, numbers, booleans) but every string
+/// value is replaced by its length, since message text, emails and tokens all
+/// travel as strings. Non-JSON payloads are not stored at all.
+fn redact_payload(data: &[u8]) -> Option<String> {
+    let mut value: serde_json::Value = serde_json::from_slice(data).ok()?;
+    redact_value(&mut value);
+
+    let mut preview = value.to_string();
+    if preview.len() > MAX_PREVIEW_LEN {
+        let mut end = MAX_PREVIEW_LEN;
+        while !preview.is_char_boundary(end) {
+            end -= 1;
+        }
+        preview.truncate(end);
+        preview.push('…');
+    }
+    Some(preview)
+}
+
+fn redact_value(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::String(s) => {
+            *s = format!("<redacted:{}>", s.chars().count());
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact_value),
+        serde_json::Value::Object(map) => map.values_mut().for_each(redact_value),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redacts_string_values() {
+        let data = br#"{"event":"send_message","data":{"text":"call me at alice@example.com","count":3}}"#;
+        let redacted = redact_payload(data).unwrap();
+        assert!(!redacted.contains("alice@example.com"));
+        assert!(!redacted.contains("send_message"));
+        assert!(redacted.contains("\"text\""));
+        assert!(redacted.contains("\"count\":3"));
+    }
+
+    #[test]
+    fn test_non_json_payload_is_not_stored() {
+        assert!(redact_payload(b"\xff\xfe garbage").is_none());
+        assert!(redact_payload(b"not json").is_none());
+    }
+
+    #[test]
+    fn test_preview_is_truncated() {
+        let keys: Vec<String> = (0..200).map(|i| format!("\"key{}\":1", i)).collect();
+        let data = format!("{{{}}}", keys.join(","));
+        let redacted = redact_payload(data.as_bytes()).unwrap();
+        assert!(redacted.len() <= MAX_PREVIEW_LEN + '…'.len_utf8());
+    }
+
+    #[test]
+    fn test_ring_is_bounded() {
+        let log = DeadLetterLog::new(true, 3);
+        for i in 0..5 {
+            log.record(b"{}", ConnectionId::new(), Uuid::new_v4(), &format!("error {}", i));
+        }
+        assert_eq!(log.len(), 3);
+
+        let recent = log.recent(10);
+        assert_eq!(recent[0].error, "error 4");
+        assert_eq!(recent[2].error, "error 2");
+    }
+
+    #[test]
+    fn test_disabled_log_records_nothing() {
+        let log = DeadLetterLog::new(false, 10);
+        log.record(b"{}", ConnectionId::new(), Uuid::new_v4(), "error");
+        assert!(log.is_empty());
+
+        let log = DeadLetterLog::new(true, 0);
+        log.record(b"{}", ConnectionId::new(), Uuid::new_v4(), "error");
+        assert!(log.is_empty());
+    }
+
+    #[test]
+    fn test_fingerprint_is_stable() {
+        assert_eq!(fingerprint(b"payload"), fingerprint(b"payload"));
+        assert_ne!(fingerprint(b"payload"), fingerprint(b"other"));
+    }
+}
@@ -0,0 +1,362 @@
+/// Flood guard module - protects the QUIC accept loop from connection floods
+///
+/// This module provides:
+/// - A per-source-IP token bucket for new connection attempts
+/// - A cap on handshakes in progress at once, so a connection storm cannot
+///   spawn unbounded handshake tasks
+/// - Counters for handshakes the accept loop retried, refused or timed out
+///
+/// The rate limit is applied only to validated addresses (after a Retry
+/// round trip), so spoofed source addresses cannot exhaust a real client's
+/// bucket.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Tracked addresses above which full buckets are pruned
+const MAX_TRACKED_ADDRESSES: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token bucket per source IP for new connection attempts
+///
+/// Each address starts with `burst` tokens and regains `rate_per_sec` per
+/// second; every attempt takes one. A rate of 0 disables the limit.
+pub struct ConnectionRateLimiter {
+    rate_per_sec: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl ConnectionRateLimiter {
+    pub fn new(rate_per_sec: u32, burst: u32) -> Self {
+        Self {
+            rate_per_sec: rate_per_sec as f64,
+            burst: burst.max(1) as f64,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a token for a connection attempt from `ip`
+    ///
+    /// Returns false when the address is over its rate.
+    pub fn try_acquire(&self, ip: IpAddr) -> bool {
+        self.try_acquire_at(ip, Instant::now())
+    }
+
+    fn try_acquire_at(&self, ip: IpAddr, now: Instant) -> bool {
+        if self.rate_per_sec <= 0.0 {
+            return true;
+        }
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED_ADDRESSES {
+            // A full bucket behaves exactly like an untracked address
+            buckets.retain(|_, bucket| self.refill(bucket, now) < self.burst);
+        }
+
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        if self.refill(bucket, now) >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Add the tokens earned since the last update; returns the new level
+    fn refill(&self, bucket: &mut Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate_per_sec).min(self.burst);
+        bucket.updated = now;
+        bucket.tokens
+    }
+
+    /// Number of addresses currently tracked
+    pub fn tracked_addresses(&self) -> usize {
+        self.buckets.lock().unwrap().len()
+    }
+}
+
+/// Counters for handshakes stopped by the accept loop
+#[derive(Debug, Default)]
+pub struct HandshakeCounters {
+    retries_sent: AtomicU64,
+    rate_limited: AtomicU64,
+    failed: AtomicU64,
+    auth_timeouts: AtomicU64,
+    in_flight: AtomicU64,
+    queue_waits: AtomicU64,
+    queue_rejected: AtomicU64,
+}
+
+/// Point-in-time copy of `HandshakeCounters`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HandshakeStats {
+    /// Retry packets sent to validate a client address
+    pub retries_sent: u64,
+    /// Connections refused by the per-IP rate limit
+    pub rate_limited: u64,
+    /// Handshakes that did not complete
+    pub failed: u64,
+    /// Connections closed for not authenticating in time
+    pub auth_timeouts: u64,
+    /// Handshakes currently in progress
+    pub in_flight: u64,
+    /// Handshakes that had to wait for a free slot
+    pub queue_waits: u64,
+    /// Handshakes refused because no slot freed up in time
+    pub queue_rejected: u64,
+}
+
+impl HandshakeCounters {
+    pub fn record_retry(&self) {
+        self.retries_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_rate_limited(&self) {
+        self.rate_limited.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_failed(&self) {
+        self.failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_auth_timeout(&self) {
+        self.auth_timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_queue_wait(&self) {
+        self.queue_waits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_queue_rejected(&self) {
+        self.queue_rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> HandshakeStats {
+        HandshakeStats {
+            retries_sent: self.retries_sent.load(Ordering::Relaxed),
+            rate_limited: self.rate_limited.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            auth_timeouts: self.auth_timeouts.load(Ordering::Relaxed),
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            queue_waits: self.queue_waits.load(Ordering::Relaxed),
+            queue_rejected: self.queue_rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Caps the handshakes in progress at once
+///
+/// The accept loop takes a permit before spawning a handshake task. When
+/// none is free it waits up to `queue_timeout` for one (which also stops it
+/// from accepting more connections meanwhile), then refuses the connection.
+/// A limit of 0 disables the cap.
+pub struct HandshakeLimiter {
+    semaphore: Option<Arc<Semaphore>>,
+    queue_timeout: Duration,
+}
+
+/// A handshake slot, released when dropped
+#[derive(Debug)]
+pub struct HandshakePermit {
+    _permit: Option<OwnedSemaphorePermit>,
+    counters: Arc<HandshakeCounters>,
+}
+
+impl Drop for HandshakePermit {
+    fn drop(&mut self) {
+        self.counters.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl HandshakeLimiter {
+    pub fn new(max_concurrent: usize, queue_timeout: Duration) -> Self {
+        Self {
+            semaphore: (max_concurrent > 0).then(|| Arc::new(Semaphore::new(max_concurrent))),
+            queue_timeout,
+        }
+    }
+
+    /// Take a handshake slot, waiting up to the queue timeout for one
+    ///
+    /// Returns `None` (and records the refusal) when none freed up in time.
+    pub async fn acquire(&self, counters: &Arc<HandshakeCounters>) -> Option<HandshakePermit> {
+        let permit = match &self.semaphore {
+            None => None,
+            Some(semaphore) => match Arc::clone(semaphore).try_acquire_owned() {
+                Ok(permit) => Some(permit),
+                Err(_) => {
+                    counters.record_queue_wait();
+                    let acquire = Arc::clone(semaphore).acquire_owned();
+                    match tokio::time::timeout(self.queue_timeout, acquire).await {
+                        Ok(Ok(permit)) => Some(permit),
+                        _ => {
+                            counters.record_queue_rejected();
+                            return None;
+                        }
+                    }
+                }
+            },
+        };
+
+        Some(Self::issue(permit, counters))
+    }
+
+    /// Take a handshake slot only if one is free right now
+    pub fn try_acquire(&self, counters: &Arc<HandshakeCounters>) -> Option<HandshakePermit> {
+        let permit = match &self.semaphore {
+            None => None,
+            Some(semaphore) => Some(Arc::clone(semaphore).try_acquire_owned().ok()?),
+        };
+
+        Some(Self::issue(permit, counters))
+    }
+
+    fn issue(permit: Option<OwnedSemaphorePermit>, counters: &Arc<HandshakeCounters>) -> HandshakePermit {
+        counters.in_flight.fetch_add(1, Ordering::Relaxed);
+        HandshakePermit {
+            _permit: permit,
+            counters: Arc::clone(counters),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(192, 0, 2, last))
+    }
+
+    #[test]
+    fn test_burst_then_refused() {
+        let limiter = ConnectionRateLimiter::new(1, 3);
+        let now = Instant::now();
+
+        assert!(limiter.try_acquire_at(ip(1), now));
+        assert!(limiter.try_acquire_at(ip(1), now));
+        assert!(limiter.try_acquire_at(ip(1), now));
+        assert!(!limiter.try_acquire_at(ip(1), now));
+
+        // Other addresses have their own bucket
+        assert!(limiter.try_acquire_at(ip(2), now));
+    }
+
+    #[test]
+    fn test_tokens_refill_over_time() {
+        let limiter = ConnectionRateLimiter::new(2, 1);
+        let now = Instant::now();
+
+        assert!(limiter.try_acquire_at(ip(1), now));
+        assert!(!limiter.try_acquire_at(ip(1), now));
+        assert!(!limiter.try_acquire_at(ip(1), now + Duration::from_millis(100)));
+        assert!(limiter.try_acquire_at(ip(1), now + Duration::from_millis(600)));
+    }
+
+    #[test]
+    fn test_zero_rate_disables_limit() {
+        let limiter = ConnectionRateLimiter::new(0, 1);
+        let now = Instant::now();
+
+        for _ in 0..100 {
+            assert!(limiter.try_acquire_at(ip(1), now));
+        }
+        assert_eq!(limiter.tracked_addresses(), 0);
+    }
+
+    #[test]
+    fn test_handshake_counters_snapshot() {
+        let counters = HandshakeCounters::default();
+        counters.record_retry();
+        counters.record_retry();
+        counters.record_rate_limited();
+        counters.record_auth_timeout();
+
+        assert_eq!(
+            counters.snapshot(),
+            HandshakeStats {
+                retries_sent: 2,
+                rate_limited: 1,
+                failed: 0,
+                auth_timeouts: 1,
+                ..HandshakeStats::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handshake_limiter_caps_concurrent_handshakes() {
+        let counters = Arc::new(HandshakeCounters::default());
+        let limiter = HandshakeLimiter::new(2, Duration::from_millis(20));
+
+        let first = limiter.acquire(&counters).await.unwrap();
+        let _second = limiter.acquire(&counters).await.unwrap();
+        assert_eq!(counters.snapshot().in_flight, 2);
+
+        // No slot frees up within the queue timeout
+        assert!(limiter.acquire(&counters).await.is_none());
+        let stats = counters.snapshot();
+        assert_eq!((stats.in_flight, stats.queue_waits, stats.queue_rejected), (2, 1, 1));
+
+        // A waiting handshake gets the slot as soon as one finishes
+        let waiting = tokio::spawn({
+            let counters = Arc::clone(&counters);
+            async move {
+                let limiter = HandshakeLimiter::new(1, Duration::from_secs(5));
+                let held = limiter.acquire(&counters).await.unwrap();
+                let waiter = async { limiter.acquire(&counters).await.is_some() };
+                let release = async {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    drop(held);
+                };
+                tokio::join!(waiter, release).0
+            }
+        });
+        assert!(waiting.await.unwrap());
+
+        drop(first);
+        let stats = counters.snapshot();
+        assert_eq!((stats.in_flight, stats.queue_waits, stats.queue_rejected), (1, 2, 1));
+    }
+
+    #[tokio::test]
+    async fn test_try_acquire_never_waits() {
+        let counters = Arc::new(HandshakeCounters::default());
+        let limiter = HandshakeLimiter::new(1, Duration::from_secs(5));
+
+        let held = limiter.try_acquire(&counters).unwrap();
+        assert!(limiter.try_acquire(&counters).is_none());
+        assert_eq!(counters.snapshot().queue_waits, 0);
+
+        drop(held);
+        assert!(limiter.try_acquire(&counters).is_some());
+        assert_eq!(counters.snapshot().in_flight, 0);
+    }
+
+    #[tokio::test]
+    async fn test_zero_limit_disables_handshake_cap() {
+        let counters = Arc::new(HandshakeCounters::default());
+        let limiter = HandshakeLimiter::new(0, Duration::ZERO);
+
+        let permits: Vec<_> = futures::future::join_all((0..100).map(|_| limiter.acquire(&counters))).await;
+        assert!(permits.iter().all(Option::is_some));
+        assert_eq!(counters.snapshot().in_flight, 100);
+        drop(permits);
+        assert_eq!(counters.snapshot().in_flight, 0);
+    }
+}
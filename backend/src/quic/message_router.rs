@@ -1,14 +1,22 @@
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
 use uuid::Uuid;
 use thiserror::Error;
 
 use crate::{
     AppState,
+    models::CallOutcome,
+    services::{request_id, CallLogService, WebSocketService},
     ws::events::{ClientEvent, ServerEvent},
     ws::manager::WsManager,
 };
 
 use super::connection_manager::ConnectionId;
+use super::dead_letter::DeadLetterLog;
+use super::stream_allocator::MessageType;
 
 /// Message router errors
 #[derive(Debug, Error)]
@@ -27,6 +35,134 @@ pub enum MessageRouterError {
 
     #[error("Invalid message format")]
     InvalidFormat,
+
+    /// Routing the message panicked; details are only logged
+    #[error("Internal error while handling message")]
+    Panicked,
+}
+
+impl MessageRouterError {
+    /// Record the payload that caused this error in the dead-letter log
+    ///
+    /// The payload is fingerprinted and redacted before storage; nothing is
+    /// recorded when the log is disabled.
+    pub fn record_dead_letter(
+        &self,
+        log: &DeadLetterLog,
+        data: &[u8],
+        connection_id: ConnectionId,
+        user_id: Uuid,
+    ) {
+        log.record(data, connection_id, user_id, &self.to_string());
+    }
+}
+
+/// Application-level ping from a native client:
+/// `{"type":"ping","nonce":"...","client_ts":...}`
+///
+/// Separate from `ClientEvent::Ping` so clients can measure round trips
+/// through the router itself.
+#[derive(Debug, Deserialize)]
+struct DiagnosticPing {
+    #[serde(rename = "type")]
+    kind: String,
+    nonce: String,
+    #[serde(default)]
+    client_ts: serde_json::Value,
+}
+
+/// Reply to a `DiagnosticPing`; `nonce` and `client_ts` are echoed back
+#[derive(Debug, Serialize)]
+struct DiagnosticPong<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    nonce: &'a str,
+    client_ts: &'a serde_json::Value,
+    /// Server time in milliseconds since the Unix epoch
+    server_ts: i64,
+}
+
+/// Build the pong for a diagnostic ping, or `None` if `text` is not one
+fn diagnostic_pong(text: &str, server_ts: i64) -> Option<Vec<u8>> {
+    // Cheap pre-check: ClientEvent frames are tagged with "event", not "type"
+    if !text.contains("\"type\"") {
+        return None;
+    }
+    let ping: DiagnosticPing = serde_json::from_str(text).ok()?;
+    if ping.kind != "ping" {
+        return None;
+    }
+
+    serde_json::to_vec(&DiagnosticPong {
+        kind: "pong",
+        nonce: &ping.nonce,
+        client_ts: &ping.client_ts,
+        server_ts,
+    })
+    .ok()
+}
+
+/// Whether a frame is a JSON array of events
+fn is_batch(text: &str) -> bool {
+    text.trim_start().starts_with('[')
+}
+
+/// Parse the events in a frame, each with the request id to trace it under
+///
+/// A JSON array carries several events and is only accepted from clients
+/// that negotiated batching; anything else is a single event.
+fn parse_events(
+    text: &str,
+    batching: bool,
+) -> Result<Vec<(String, ClientEvent)>, MessageRouterError> {
+    let invalid = |e: serde_json::Error| MessageRouterError::ParseError(format!("Invalid JSON: {}", e));
+
+    if batching && is_batch(text) {
+        let frames: Vec<serde_json::Value> = serde_json::from_str(text).map_err(invalid)?;
+        return frames
+            .into_iter()
+            .map(|frame| {
+                let request_id = request_id::from_frame(&frame.to_string());
+                let event = serde_json::from_value(frame).map_err(invalid)?;
+                Ok((request_id, event))
+            })
+            .collect();
+    }
+
+    let event = serde_json::from_str(text).map_err(invalid)?;
+    Ok(vec![(request_id::from_frame(text), event)])
+}
+
+/// Route one message, turning a panic into `MessageRouterError::Panicked`
+///
+/// A panic would otherwise unwind through the connection's handler task and
+/// drop the connection, so one malformed message from a client could cut it
+/// off. The panic is logged with the connection it came from.
+async fn route_isolated<F>(
+    routing: F,
+    connection_id: ConnectionId,
+    user_id: Uuid,
+) -> Result<Option<Vec<u8>>, MessageRouterError>
+where
+    F: Future<Output = Result<Option<Vec<u8>>, MessageRouterError>>,
+{
+    match AssertUnwindSafe(routing).catch_unwind().await {
+        Ok(routed) => routed,
+        Err(panic) => {
+            let reason = panic
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown panic");
+            tracing::error!(
+                "Routing a message from connection {} (user {}) panicked: {}",
+                connection_id,
+                user_id,
+                reason
+            );
+            Err(MessageRouterError::Panicked)
+        }
+    }
 }
 
 /// Message router that handles incoming messages from QUIC streams
@@ -56,17 +192,49 @@ impl MessageRouter {
     ///
     /// # Arguments
     /// * `data` - Raw message data from QUIC stream
+    /// * `msg_type` - Message type of the stream the data arrived on
     /// * `connection_id` - ID of the connection that sent the message
     /// * `user_id` - Authenticated user ID
     /// * `user_name` - Authenticated user name
     ///
+    /// Bytes in, bytes out and routed messages are counted per `msg_type`
+    /// in the stream allocator's traffic counters. A panic while routing is
+    /// caught and returned as `MessageRouterError::Panicked`, so the
+    /// connection keeps being served.
+    ///
     /// # Returns
     /// * `Ok(Option<Vec<u8>>)` - Optional response data to send back
     /// * `Err(MessageRouterError)` - Error if routing fails
     pub async fn route_message(
         &self,
         data: &[u8],
-        _connection_id: ConnectionId,
+        msg_type: MessageType,
+        connection_id: ConnectionId,
+        user_id: Uuid,
+        user_name: &str,
+    ) -> Result<Option<Vec<u8>>, MessageRouterError> {
+        let traffic = self.state.stream_allocator.traffic();
+        traffic.record_received(msg_type, data.len());
+
+        let routed = route_isolated(
+            self.route(data, connection_id, user_id, user_name),
+            connection_id,
+            user_id,
+        )
+        .await;
+        if let Ok(response) = &routed {
+            traffic.record_routed(msg_type);
+            if let Some(response) = response {
+                traffic.record_sent(msg_type, response.len());
+            }
+        }
+        routed
+    }
+
+    async fn route(
+        &self,
+        data: &[u8],
+        connection_id: ConnectionId,
         user_id: Uuid,
         user_name: &str,
     ) -> Result<Option<Vec<u8>>, MessageRouterError> {
@@ -74,22 +242,59 @@ impl MessageRouter {
         let text = std::str::from_utf8(data)
             .map_err(|e| MessageRouterError::ParseError(format!("Invalid UTF-8: {}", e)))?;
 
+        // Any frame counts as activity, cancelling a pending idle disconnect
+        let _ = self
+            .state
+            .connection_manager
+            .update_activity(connection_id)
+            .await;
+
+        // Diagnostic ping: answer immediately on the same stream, no DB access
+        if let Some(pong) = diagnostic_pong(text, chrono::Utc::now().timestamp_millis()) {
+            return Ok(Some(pong));
+        }
+
         tracing::debug!("Routing QUIC message from user {}: {}", user_id, text);
 
-        // Parse as ClientEvent (same as WebSocket)
-        let event: ClientEvent = serde_json::from_str(text)
-            .map_err(|e| MessageRouterError::ParseError(format!("Invalid JSON: {}", e)))?;
+        // Only look up the connection's capabilities for frames that need them
+        let batching = is_batch(text)
+            && self
+                .state
+                .connection_manager
+                .capabilities(connection_id)
+                .await
+                .is_some_and(|capabilities| capabilities.batching);
 
-        // Handle the event using the same logic as WebSocket
-        self.handle_client_event(event, user_id, user_name).await?;
+        // Parse as ClientEvent (same as WebSocket)
+        let events = parse_events(text, batching)?;
+
+        // Handle each event using the same logic as WebSocket, traced under
+        // its `traceId` (or a fresh id)
+        let mut replies = Vec::new();
+        for (request_id, event) in events {
+            let reply =
+                request_id::scope(request_id, self.handle_client_event(event, user_id, user_name))
+                    .await?;
+            replies.extend(reply);
+        }
 
-        // Most events don't require a direct response (they broadcast to other clients)
-        // Return None to indicate no response needed
-        Ok(None)
+        // Most events don't require a direct response (they broadcast to other
+        // clients); replies such as message acks go back on this stream, as
+        // an array when a batch produced several
+        let response = match replies.as_slice() {
+            [] => return Ok(None),
+            [reply] => serde_json::to_vec(reply),
+            replies => serde_json::to_vec(replies),
+        };
+        response
+            .map(Some)
+            .map_err(|e| MessageRouterError::SerializeError(e.to_string()))
     }
 
     /// Handle a client event (same logic as WebSocket handler)
     ///
+    /// Returns the reply for the sending connection, if the event has one.
+    ///
     /// # Requirements
     /// - 6.2: Route messages to existing handlers
     /// - 6.3: Support all existing message types
@@ -98,8 +303,8 @@ impl MessageRouter {
         event: ClientEvent,
         user_id: Uuid,
         user_name: &str,
-    ) -> Result<(), MessageRouterError> {
-        match event {
+    ) -> Result<Option<ServerEvent>, MessageRouterError> {
+        let handled = match event {
             ClientEvent::StartTyping { chat_id } => {
                 self.handle_start_typing(chat_id, user_id, user_name).await
             }
@@ -112,9 +317,39 @@ impl MessageRouter {
             ClientEvent::LeaveChat { chat_id } => {
                 self.handle_leave_chat(chat_id, user_id).await
             }
+            ClientEvent::SendMessage {
+                chat_id,
+                content,
+                client_msg_id,
+                reply_to,
+            } => {
+                let ack = self
+                    .handle_send_message(user_id, chat_id, content, client_msg_id, reply_to)
+                    .await;
+                return Ok(Some(ack));
+            }
+            ClientEvent::MessagesDelivered { message_ids } => {
+                WebSocketService::mark_delivered(&self.state, user_id, &message_ids).await;
+                Ok(())
+            }
             ClientEvent::Ping => {
                 self.handle_ping(user_id).await
             }
+            ClientEvent::SetPresence { status } => {
+                if let Err(e) = WebSocketService::set_presence(&self.state, user_id, status).await {
+                    let error = ServerEvent::Error {
+                        code: e.status_and_code().1.to_string(),
+                        message: e.to_string(),
+                    };
+                    self.ws_manager.send_to_user(user_id, error).await;
+                }
+                Ok(())
+            }
+            ClientEvent::SetIdle { idle } => {
+                WebSocketService::set_idle(&self.state, user_id, idle)
+                    .await
+                    .map_err(|e| MessageRouterError::HandlerError(e.to_string()))
+            }
             ClientEvent::InitiateCall {
                 target_user_id,
                 chat_id,
@@ -132,7 +367,8 @@ impl MessageRouter {
             ClientEvent::EndCall { call_id } => {
                 self.handle_end_call(user_id, call_id).await
             }
-        }
+        };
+        handled.map(|()| None)
     }
 
     /// Handle StartTyping event
@@ -147,15 +383,8 @@ impl MessageRouter {
             return Ok(());
         }
 
-        let typing_event = ServerEvent::Typing {
-            chat_id,
-            user_id,
-            user_name: user_name.to_string(),
-            is_typing: true,
-        };
-
         self.ws_manager
-            .broadcast_to_room(chat_id, typing_event, Some(user_id))
+            .start_typing(chat_id, user_id, user_name.to_string())
             .await;
 
         Ok(())
@@ -173,15 +402,8 @@ impl MessageRouter {
             return Ok(());
         }
 
-        let typing_event = ServerEvent::Typing {
-            chat_id,
-            user_id,
-            user_name: user_name.to_string(),
-            is_typing: false,
-        };
-
         self.ws_manager
-            .broadcast_to_room(chat_id, typing_event, Some(user_id))
+            .stop_typing(chat_id, user_id, user_name.to_string())
             .await;
 
         Ok(())
@@ -204,6 +426,26 @@ impl MessageRouter {
         Ok(())
     }
 
+    /// Handle SendMessage event, returning its ack or failure
+    async fn handle_send_message(
+        &self,
+        user_id: Uuid,
+        chat_id: Uuid,
+        content: String,
+        client_msg_id: Option<String>,
+        reply_to: Option<Uuid>,
+    ) -> ServerEvent {
+        WebSocketService::send_client_message(
+            &self.state,
+            user_id,
+            chat_id,
+            content,
+            client_msg_id,
+            reply_to,
+        )
+        .await
+    }
+
     /// Handle Ping event
     async fn handle_ping(&self, user_id: Uuid) -> Result<(), MessageRouterError> {
         tracing::debug!("Received ping from user {} via QUIC", user_id);
@@ -290,6 +532,7 @@ impl MessageRouter {
                 call_id: session.call_id,
             };
             self.ws_manager.send_to_user(caller_id, busy_event).await;
+            CallLogService::record_started(&self.state.db, &session, CallOutcome::Busy).await;
 
             // Clean up the temporary session
             self.ws_manager.end_call(session.call_id).await;
@@ -302,6 +545,9 @@ impl MessageRouter {
             .create_call_session(caller_id, target_user_id, chat_id, call_type.clone())
             .await;
 
+        CallLogService::record_started(&self.state.db, &session, CallOutcome::Ringing).await;
+        CallLogService::spawn_ring_timeout(self.state.clone(), session.call_id);
+
         // Get caller avatar
         let caller_avatar = self.ws_manager.get_user_avatar(caller_id).await;
 
@@ -371,6 +617,7 @@ impl MessageRouter {
                 return Ok(());
             }
         };
+        CallLogService::record_accepted(&self.state.db, call_id).await;
 
         // Get mediasoup URL from config
         let mediasoup_url = self.state.config.mediasoup_url.clone();
@@ -425,6 +672,7 @@ impl MessageRouter {
 
         // End the call
         self.ws_manager.end_call(call_id).await;
+        CallLogService::record_ended(&self.state.db, call_id, CallOutcome::Declined).await;
 
         // Send CallDeclined to caller
         let declined_event = ServerEvent::CallDeclined { call_id };
@@ -464,6 +712,8 @@ impl MessageRouter {
 
         // End the call
         self.ws_manager.end_call(call_id).await;
+        let outcome = CallLogService::outcome_on_hangup(&session);
+        CallLogService::record_ended(&self.state.db, call_id, outcome).await;
 
         // Send CallEnded to both parties
         let ended_event = ServerEvent::CallEnded {
@@ -477,6 +727,9 @@ impl MessageRouter {
         self.ws_manager
             .send_to_user(session.callee_id, ended_event)
             .await;
+        if outcome == CallOutcome::Missed {
+            CallLogService::notify_missed(&self.state, &session).await;
+        }
 
         tracing::info!("Call ended via QUIC: call_id={}", call_id);
 
@@ -492,7 +745,11 @@ impl MessageRouter {
         );
 
         let query_result = sqlx::query_as::<_, (i32,)>(
-            "SELECT 1 FROM chat_participants WHERE chat_id = $1 AND user_id = $2",
+            r#"
+            SELECT 1 FROM chat_participants cp
+            JOIN chats c ON c.id = cp.chat_id
+            WHERE cp.chat_id = $1 AND cp.user_id = $2 AND c.deleted_at IS NULL
+            "#,
         )
         .bind(chat_id)
         .bind(user_id)
@@ -520,6 +777,69 @@ impl MessageRouter {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_diagnostic_pong_echoes_nonce_and_client_ts() {
+        let ping = r#"{"type":"ping","nonce":"a\"b\u00e9-42","client_ts":1718000000123}"#;
+        let pong = diagnostic_pong(ping, 1718000000456).expect("ping should get a pong");
+        let pong: serde_json::Value = serde_json::from_slice(&pong).unwrap();
+
+        assert_eq!(pong["type"], "pong");
+        assert_eq!(pong["nonce"], "a\"b\u{e9}-42");
+        assert_eq!(pong["client_ts"], 1718000000123i64);
+        assert_eq!(pong["server_ts"], 1718000000456i64);
+    }
+
+    #[test]
+    fn test_diagnostic_pong_ignores_other_messages() {
+        assert!(diagnostic_pong(r#"{"event":"ping"}"#, 0).is_none());
+        assert!(diagnostic_pong(r#"{"type":"pong","nonce":"x"}"#, 0).is_none());
+        // A ping without a nonce is not a valid diagnostic ping
+        assert!(diagnostic_pong(r#"{"type":"ping"}"#, 0).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_panicking_message_returns_error_and_connection_survives() {
+        use super::super::stream_allocator::StreamAllocator;
+
+        let allocator = Arc::new(StreamAllocator::new());
+        let connection_id = ConnectionId::new();
+        let user_id = Uuid::new_v4();
+        allocator.register_connection(connection_id).await;
+
+        // The connection's handler task routes a message whose handler
+        // panics, then keeps serving the next one
+        let handler = tokio::spawn({
+            let allocator = allocator.clone();
+            async move {
+                let bad = route_isolated(
+                    async {
+                        let fields: Vec<&str> = Vec::new();
+                        Ok(Some(fields[1].as_bytes().to_vec()))
+                    },
+                    connection_id,
+                    user_id,
+                )
+                .await;
+                let good =
+                    route_isolated(async { Ok(Some(b"ok".to_vec())) }, connection_id, user_id)
+                        .await;
+                let registered = allocator.active_stream_count(connection_id).await.is_ok();
+                (bad, good, registered)
+            }
+        });
+
+        let (bad, good, registered) = handler.await.expect("handler task should not die");
+        assert!(matches!(bad, Err(MessageRouterError::Panicked)));
+        // The client only learns that something went wrong
+        assert_eq!(
+            bad.unwrap_err().to_string(),
+            "Internal error while handling message"
+        );
+        assert_eq!(good.unwrap(), Some(b"ok".to_vec()));
+        assert!(registered);
+        assert_eq!(allocator.get_stats().await.total_connections, 1);
+    }
+
     #[test]
     fn test_message_router_error_display() {
         let err = MessageRouterError::ParseError("test".to_string());
@@ -542,6 +862,65 @@ mod tests {
         assert!(event.is_ok());
     }
 
+    #[test]
+    fn test_parse_send_message_event() {
+        let json = r#"{"event":"send_message","data":{"chatId":"550e8400-e29b-41d4-a716-446655440000","content":"hi","clientMsgId":"tmp-1"}}"#;
+        let event: ClientEvent = serde_json::from_str(json).unwrap();
+        match event {
+            ClientEvent::SendMessage { content, client_msg_id, reply_to, .. } => {
+                assert_eq!(content, "hi");
+                assert_eq!(client_msg_id.as_deref(), Some("tmp-1"));
+                assert!(reply_to.is_none());
+            }
+            _ => panic!("Expected SendMessage"),
+        }
+    }
+
+    #[test]
+    fn test_parse_presence_events() {
+        let json = r#"{"event":"set_presence","data":{"status":"invisible"}}"#;
+        let event: ClientEvent = serde_json::from_str(json).unwrap();
+        assert!(matches!(
+            event,
+            ClientEvent::SetPresence { status: crate::models::PresenceState::Invisible }
+        ));
+
+        let json = r#"{"event":"set_idle","data":{"idle":true}}"#;
+        let event: ClientEvent = serde_json::from_str(json).unwrap();
+        assert!(matches!(event, ClientEvent::SetIdle { idle: true }));
+
+        let json = r#"{"event":"set_presence","data":{"status":"sleeping"}}"#;
+        assert!(serde_json::from_str::<ClientEvent>(json).is_err());
+    }
+
+    #[test]
+    fn test_parse_messages_delivered() {
+        let id = Uuid::new_v4();
+        let json = format!(r#"{{"event":"messages_delivered","data":{{"messageIds":["{}"]}}}}"#, id);
+        let event: ClientEvent = serde_json::from_str(&json).unwrap();
+        assert!(matches!(event, ClientEvent::MessagesDelivered { message_ids } if message_ids == vec![id]));
+    }
+
+    #[test]
+    fn test_batched_frames_need_negotiated_batching() {
+        let batch = r#"[{"event":"ping","traceId":"first"},{"event":"ping"}]"#;
+
+        let events = parse_events(batch, true).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].0, "first");
+        assert!(matches!(events[1].1, ClientEvent::Ping));
+
+        // Without batching an array is just an invalid event
+        assert!(matches!(
+            parse_events(batch, false),
+            Err(MessageRouterError::ParseError(_))
+        ));
+
+        // Single events parse either way
+        assert_eq!(parse_events(r#"{"event":"ping"}"#, true).unwrap().len(), 1);
+        assert_eq!(parse_events(r#"{"event":"ping"}"#, false).unwrap().len(), 1);
+    }
+
     #[test]
     fn test_invalid_json() {
         let json = r#"{"invalid": "json"}"#;
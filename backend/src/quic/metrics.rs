@@ -4,6 +4,7 @@ use tokio::sync::RwLock;
 use serde::{Deserialize, Serialize};
 
 use crate::quic::connection_manager::{ConnectionManager, ConnectionStats, MigrationStats};
+use crate::quic::flood_guard::{HandshakeCounters, HandshakeStats};
 
 /// QUIC metrics collector
 ///
@@ -18,6 +19,8 @@ pub struct QuicMetrics {
     performance: Arc<RwLock<PerformanceMetrics>>,
     /// Start time for uptime calculation
     start_time: Instant,
+    /// Handshakes stopped by the accept loop, shared with the server
+    handshakes: Arc<HandshakeCounters>,
 }
 
 /// Performance metrics for QUIC connections
@@ -152,6 +155,8 @@ pub struct MetricsSnapshot {
     pub migrations: MigrationStats,
     /// Performance metrics
     pub performance: PerformanceMetrics,
+    /// Handshakes retried, rate limited, failed or timed out
+    pub handshakes: HandshakeStats,
     /// QUIC to WebSocket ratio
     ///
     /// # Requirements
@@ -173,9 +178,15 @@ impl QuicMetrics {
             connection_manager,
             performance: Arc::new(RwLock::new(PerformanceMetrics::default())),
             start_time: Instant::now(),
+            handshakes: Arc::new(HandshakeCounters::default()),
         }
     }
 
+    /// Counters the QUIC server records rejected handshakes in
+    pub fn handshake_counters(&self) -> Arc<HandshakeCounters> {
+        Arc::clone(&self.handshakes)
+    }
+
     /// Record bytes sent
     ///
     /// # Requirements
@@ -246,6 +257,7 @@ impl QuicMetrics {
             connections,
             migrations,
             performance,
+            handshakes: self.handshakes.snapshot(),
             quic_to_websocket_ratio,
             uptime_seconds: uptime.as_secs(),
             timestamp: chrono::Utc::now().to_rfc3339(),
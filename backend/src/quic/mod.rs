@@ -2,29 +2,52 @@
 // Provides QUIC protocol support using Quinn library
 
 pub mod auth;
+pub mod capabilities;
 pub mod config;
 pub mod connection_manager;
+pub mod dead_letter;
 pub mod diagnostics;
+pub mod flood_guard;
 pub mod message_router;
 pub mod metrics;
+pub mod resumption;
 pub mod server;
 pub mod stream_allocator;
+pub mod stream_traffic;
 
-pub use auth::{AuthRequest, AuthResponse, QuicAuthError, QuicAuthenticator};
-pub use config::{QuicConfig, QuicServerConfig};
+pub use auth::{
+    AuthRequest, AuthResponse, AuthenticatedClient, DeviceDescriptor, QuicAuthError,
+    QuicAuthenticator,
+};
+pub use capabilities::{
+    CapabilityMismatch, ClientCapabilities, NegotiatedCapabilities, ServerCapabilities,
+    ServerLimits, MAX_STREAM_MESSAGE_BYTES,
+};
+pub use config::{QuicConfig, QuicServerConfig, StreamIdleTimeouts, StreamPriorities};
 pub use connection_manager::{
-    Connection as ManagedConnection, ConnectionId, ConnectionManager, ConnectionManagerError,
-    ConnectionStats, MigrationState, MigrationStats, QuicConnection, TransportType,
-    WebSocketConnection,
+    Connection as ManagedConnection, ConnectionId, ConnectionInfo, ConnectionManager,
+    ConnectionManagerError, ConnectionStats, MigrationState, MigrationStats, QuicConnection,
+    RealtimeSession, TransportType, WebSocketConnection, ADMIN_DISCONNECT_CLOSE_CODE,
+    SESSION_TERMINATED_CLOSE_CODE, USER_CONNECTION_LIMIT_CLOSE_CODE,
+    USER_CONNECTION_LIMIT_CLOSE_REASON, WebSocketSendCallback, WebSocketSendFuture,
 };
+pub use dead_letter::{DeadLetter, DeadLetterLog};
 pub use diagnostics::{DiagnosticLogger, PerformanceMonitor};
+pub use flood_guard::{
+    ConnectionRateLimiter, HandshakeCounters, HandshakeLimiter, HandshakePermit, HandshakeStats,
+};
 pub use message_router::{MessageRouter, MessageRouterError};
 pub use metrics::{MetricsSnapshot, PerformanceMetrics, QuicMetrics};
-pub use server::{QuicServer, QuicServerError, ServerState};
+pub use resumption::{ResumableSession, ResumptionStore};
+pub use server::{
+    QuicServer, QuicServerError, ServerState, AUTH_TIMEOUT_CLOSE_CODE, AUTH_TIMEOUT_CLOSE_REASON,
+    CONNECTION_LIMIT_CLOSE_CODE, CONNECTION_LIMIT_CLOSE_REASON,
+};
 pub use stream_allocator::{
-    MessageType, StreamAllocator, StreamAllocatorError, StreamAllocatorStats, StreamRange,
-    StreamType,
+    IdleStream, MessageType, StreamAllocator, StreamAllocatorError, StreamAllocatorStats,
+    StreamRange, StreamType, STREAM_IDLE_CLOSE_CODE,
 };
+pub use stream_traffic::{MessageTypeTraffic, StreamTraffic, TrafficRates};
 
 // Re-export commonly used types
 pub use quinn::{Connection, Endpoint, RecvStream, SendStream};
@@ -0,0 +1,203 @@
+/// Resumption module - single-use tokens for fast QUIC re-authentication
+///
+/// Every successful auth exchange hands the client a fresh resumption token.
+/// A client reconnecting after a drop or failed migration can present it
+/// instead of its JWT and skip token verification. Tokens:
+/// - live in Redis under `quic:resume:{token}` for `QUIC_RESUMPTION_TTL_SECS`
+/// - are bound to the user and device they were issued to; a client must
+///   send the same device descriptor to redeem one
+/// - are deleted when redeemed, so a replayed token is rejected
+/// - never outlive the JWT the session was first authenticated with
+///
+/// Resumption only skips JWT verification. The new connection is registered
+/// like any other and starts with no state from the old one: the server keeps
+/// no per-connection pending acks or sequence numbers, so acks that were lost
+/// with the old connection are not sent again. Messages that arrived while the
+/// user had no connection are replayed from the offline queue, as after a
+/// full auth.
+///
+/// Without Redis no tokens are issued and clients always run the full auth.
+use crate::quic::auth::DeviceDescriptor;
+use rand::Rng;
+use redis::aio::ConnectionManager;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Characters in a resumption token
+const TOKEN_LEN: usize = 48;
+
+/// The authenticated identity a resumption token stands for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResumableSession {
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub device: DeviceDescriptor,
+    /// When the JWT was last verified (unix seconds); revocation is checked
+    /// against this rather than the reconnect time
+    pub authenticated_at: i64,
+    /// Expiry of the JWT the session was authenticated with (unix seconds)
+    pub expires_at: i64,
+    /// Login session the JWT was bound to; checked again on every reconnect
+    #[serde(default)]
+    pub session_id: Option<Uuid>,
+}
+
+impl ResumableSession {
+    /// Whether a reconnecting client describing itself as `device` may take
+    /// over this session; clients that omit the descriptor must run the full
+    /// auth instead
+    pub fn matches_device(&self, device: Option<&DeviceDescriptor>) -> bool {
+        device.is_some_and(|device| *device == self.device)
+    }
+}
+
+/// Issues and redeems resumption tokens
+pub struct ResumptionStore {
+    redis: Option<ConnectionManager>,
+    ttl_secs: u64,
+}
+
+impl ResumptionStore {
+    pub fn new(redis: Option<ConnectionManager>, ttl_secs: u64) -> Self {
+        Self { redis, ttl_secs }
+    }
+
+    /// Whether tokens are issued at all
+    pub fn is_enabled(&self) -> bool {
+        self.redis.is_some() && self.ttl_secs > 0
+    }
+
+    /// Store `session` under a new token
+    ///
+    /// Returns `None` when resumption is disabled, the JWT is about to expire
+    /// or Redis fails; the client then simply has no token to resume with.
+    pub async fn issue(&self, session: &ResumableSession) -> Option<String> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let ttl = token_ttl(self.ttl_secs, session.expires_at, chrono::Utc::now().timestamp());
+        if ttl == 0 {
+            return None;
+        }
+        let mut conn = self.redis.clone()?;
+
+        let token = generate_token();
+        let value = serde_json::to_string(session).ok()?;
+        let result: redis::RedisResult<()> = redis::cmd("SET")
+            .arg(key(&token))
+            .arg(value)
+            .arg("EX")
+            .arg(ttl)
+            .query_async(&mut conn)
+            .await;
+        match result {
+            Ok(()) => Some(token),
+            Err(e) => {
+                tracing::warn!("Failed to store QUIC resumption token: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Take the session stored under `token`, invalidating the token
+    ///
+    /// Returns `None` for unknown, expired, reused or malformed tokens.
+    pub async fn redeem(&self, token: &str) -> Option<ResumableSession> {
+        if !self.is_enabled() || !is_well_formed(token) {
+            return None;
+        }
+        let mut conn = self.redis.clone()?;
+
+        let value: Option<String> = match redis::cmd("GETDEL")
+            .arg(key(token))
+            .query_async(&mut conn)
+            .await
+        {
+            Ok(value) => value,
+            Err(e) => {
+                tracing::warn!("Failed to redeem QUIC resumption token: {}", e);
+                return None;
+            }
+        };
+
+        let session: ResumableSession = serde_json::from_str(&value?).ok()?;
+        (session.expires_at > chrono::Utc::now().timestamp()).then_some(session)
+    }
+}
+
+fn key(token: &str) -> String {
+    format!("quic:resume:{}", token)
+}
+
+fn generate_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&rand::distributions::Alphanumeric)
+        .take(TOKEN_LEN)
+        .map(char::from)
+        .collect()
+}
+
+/// Reject anything we could not have issued before it reaches Redis
+fn is_well_formed(token: &str) -> bool {
+    token.len() == TOKEN_LEN && token.bytes().all(|b| b.is_ascii_alphanumeric())
+}
+
+/// Seconds to keep a token: the configured TTL, cut short by JWT expiry
+fn token_ttl(ttl_secs: u64, expires_at: i64, now: i64) -> u64 {
+    let remaining = expires_at.saturating_sub(now).max(0) as u64;
+    ttl_secs.min(remaining)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(device: DeviceDescriptor) -> ResumableSession {
+        ResumableSession {
+            user_id: Uuid::new_v4(),
+            user_name: "Alice".to_string(),
+            device,
+            authenticated_at: 1_000,
+            expires_at: 2_000,
+            session_id: Some(Uuid::new_v4()),
+        }
+    }
+
+    #[test]
+    fn test_generated_tokens_are_well_formed() {
+        let token = generate_token();
+        assert!(is_well_formed(&token));
+        assert_ne!(token, generate_token());
+
+        assert!(!is_well_formed("short"));
+        assert!(!is_well_formed(&format!("{}*", &token[1..])));
+    }
+
+    #[test]
+    fn test_token_ttl_capped_by_jwt_expiry() {
+        assert_eq!(token_ttl(60, 10_000, 1_000), 60);
+        assert_eq!(token_ttl(60, 1_030, 1_000), 30);
+        assert_eq!(token_ttl(60, 900, 1_000), 0);
+    }
+
+    #[test]
+    fn test_session_bound_to_device() {
+        let phone = DeviceDescriptor {
+            name: Some("Pixel 8".to_string()),
+            device_type: Some("android".to_string()),
+        };
+        let session = session(phone.clone());
+
+        assert!(!session.matches_device(None));
+        assert!(session.matches_device(Some(&phone)));
+        assert!(!session.matches_device(Some(&DeviceDescriptor::default())));
+    }
+
+    #[tokio::test]
+    async fn test_disabled_without_redis() {
+        let store = ResumptionStore::new(None, 60);
+        assert!(!store.is_enabled());
+        assert!(store.issue(&session(DeviceDescriptor::default())).await.is_none());
+        assert!(store.redeem(&generate_token()).await.is_none());
+    }
+}
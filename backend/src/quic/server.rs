@@ -1,17 +1,51 @@
-use crate::quic::auth::QuicAuthenticator;
+use crate::quic::auth::{AuthenticatedClient, QuicAuthError, QuicAuthenticator};
+use crate::quic::capabilities::ServerCapabilities;
 use crate::quic::config::QuicServerConfig;
-use crate::quic::connection_manager::{ConnectionId, ConnectionManager, QuicConnection, Connection as ManagedConnection};
+use crate::quic::connection_manager::{
+    ConnectionId, ConnectionInfo, ConnectionManager, ConnectionManagerError, QuicConnection,
+    Connection as ManagedConnection, USER_CONNECTION_LIMIT_CLOSE_CODE,
+    USER_CONNECTION_LIMIT_CLOSE_REASON,
+};
+use crate::quic::flood_guard::{
+    ConnectionRateLimiter, HandshakeCounters, HandshakeLimiter, HandshakeStats,
+};
+use crate::quic::resumption::ResumptionStore;
 use crate::quic::stream_allocator::{MessageType, StreamAllocator};
-use quinn::{Connection, Endpoint, RecvStream, SendStream, ServerConfig};
+use crate::services::geoip::{self, GeoIpLookup, NoGeoIp};
+use quinn::{Connection, Endpoint, RecvStream, SendStream, ServerConfig, VarInt};
 use rustls::pki_types::{CertificateDer, PrivateKeyDer};
 use std::fs;
 use std::io;
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use thiserror::Error;
 use tokio::sync::RwLock;
 use tracing::{error, info, warn};
-use uuid::Uuid;
+
+/// Application close code sent when a connection is refused because the
+/// server is at `max_connections`
+pub const CONNECTION_LIMIT_CLOSE_CODE: u32 = 0x10;
+
+/// Close reason sent alongside `CONNECTION_LIMIT_CLOSE_CODE`
+pub const CONNECTION_LIMIT_CLOSE_REASON: &[u8] = b"server at capacity";
+
+/// Application close code sent when a connection does not authenticate
+/// within `QUIC_AUTH_TIMEOUT_SECS`
+pub const AUTH_TIMEOUT_CLOSE_CODE: u32 = 0x14;
+
+/// Close reason sent alongside `AUTH_TIMEOUT_CLOSE_CODE`
+pub const AUTH_TIMEOUT_CLOSE_REASON: &[u8] = b"authentication timed out";
+
+/// ALPN protocol agreed on during the handshake, if any
+pub fn negotiated_alpn(connection: &Connection) -> Option<String> {
+    connection
+        .handshake_data()?
+        .downcast::<quinn::crypto::rustls::HandshakeData>()
+        .ok()?
+        .protocol
+        .map(|p| String::from_utf8_lossy(&p).into_owned())
+}
 
 /// QUIC server errors
 #[derive(Debug, Error)]
@@ -36,6 +70,9 @@ pub enum QuicServerError {
 
     #[error("Server already running")]
     AlreadyRunning,
+
+    #[error("Authentication failed: {0}")]
+    Auth(#[from] QuicAuthError),
 }
 
 /// QUIC server state
@@ -67,21 +104,60 @@ pub struct QuicServer {
     authenticator: Option<QuicAuthenticator>,
     /// Connection manager
     connection_manager: Option<Arc<ConnectionManager>>,
+    /// Resumption tokens for reconnecting without the full JWT auth
+    resumption: Option<Arc<ResumptionStore>>,
     /// Stream allocator
     stream_allocator: Option<Arc<StreamAllocator>>,
+    /// Soft cap on concurrent connections, adjustable at runtime
+    max_connections: Arc<AtomicUsize>,
+    /// Connections currently being handled by the acceptance loop
+    active_connections: Arc<AtomicUsize>,
+    /// Connections refused because the server was at capacity
+    rejected_at_capacity: Arc<AtomicU64>,
+    /// Per-IP limit on new connection attempts
+    connection_rate_limiter: Arc<ConnectionRateLimiter>,
+    /// Handshakes retried, refused or timed out (shared with `QuicMetrics`)
+    handshakes: Arc<HandshakeCounters>,
+    /// Cap on handshakes in progress at once
+    handshake_limiter: Arc<HandshakeLimiter>,
+    /// Resolves a coarse location for each authenticated connection
+    geoip: Arc<dyn GeoIpLookup>,
+}
+
+/// Decrements the active connection count when a handler finishes
+struct ActiveConnectionGuard(Arc<AtomicUsize>);
+
+impl Drop for ActiveConnectionGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
 }
 
 impl QuicServer {
     /// Create a new QUIC server with the given configuration
     pub fn new(config: QuicServerConfig) -> Self {
         Self {
+            max_connections: Arc::new(AtomicUsize::new(config.max_connections)),
+            active_connections: Arc::new(AtomicUsize::new(0)),
+            rejected_at_capacity: Arc::new(AtomicU64::new(0)),
+            connection_rate_limiter: Arc::new(ConnectionRateLimiter::new(
+                config.connection_rate_per_ip,
+                config.connection_burst_per_ip,
+            )),
+            handshakes: Arc::new(HandshakeCounters::default()),
+            handshake_limiter: Arc::new(HandshakeLimiter::new(
+                config.max_concurrent_handshakes,
+                config.handshake_queue_timeout(),
+            )),
             config,
             endpoint: None,
             state: Arc::new(RwLock::new(ServerState::NotInitialized)),
             bound_addr: None,
             authenticator: None,
             connection_manager: None,
+            resumption: None,
             stream_allocator: None,
+            geoip: Arc::new(NoGeoIp),
         }
     }
 
@@ -146,7 +222,27 @@ impl QuicServer {
             }
         }
 
-        // Apply the new configuration
+        // Apply the new configuration; the connection cap takes effect immediately
+        self.set_max_connections(new_config.max_connections);
+        if let Some(authenticator) = self.authenticator.as_mut() {
+            authenticator.set_max_payload_bytes(new_config.max_auth_payload_bytes);
+        }
+        if self.config.connection_rate_per_ip != new_config.connection_rate_per_ip
+            || self.config.connection_burst_per_ip != new_config.connection_burst_per_ip
+        {
+            self.connection_rate_limiter = Arc::new(ConnectionRateLimiter::new(
+                new_config.connection_rate_per_ip,
+                new_config.connection_burst_per_ip,
+            ));
+        }
+        if self.config.max_concurrent_handshakes != new_config.max_concurrent_handshakes
+            || self.config.handshake_queue_timeout_ms != new_config.handshake_queue_timeout_ms
+        {
+            self.handshake_limiter = Arc::new(HandshakeLimiter::new(
+                new_config.max_concurrent_handshakes,
+                new_config.handshake_queue_timeout(),
+            ));
+        }
         self.config = new_config;
 
         info!("QUIC server configuration updated successfully");
@@ -177,13 +273,49 @@ impl QuicServer {
         Ok(())
     }
 
+    /// Update the concurrent connection cap without restarting the server
+    ///
+    /// The cap is soft: existing connections above a lowered limit are kept,
+    /// only new connections are refused until the count drops below it.
+    pub fn set_max_connections(&self, max_connections: usize) {
+        self.max_connections.store(max_connections, Ordering::SeqCst);
+    }
+
+    /// Current concurrent connection cap
+    pub fn max_connections(&self) -> usize {
+        self.max_connections.load(Ordering::SeqCst)
+    }
+
+    /// Number of connections currently handled by the acceptance loop
+    pub fn active_connections(&self) -> usize {
+        self.active_connections.load(Ordering::SeqCst)
+    }
+
+    /// Total connections refused because the server was at capacity
+    pub fn rejected_at_capacity(&self) -> u64 {
+        self.rejected_at_capacity.load(Ordering::SeqCst)
+    }
+
+    /// Share handshake counters with the metrics collector
+    pub fn set_handshake_counters(&mut self, handshakes: Arc<HandshakeCounters>) {
+        self.handshakes = handshakes;
+    }
+
+    /// Handshakes retried, refused or timed out so far
+    pub fn handshake_stats(&self) -> HandshakeStats {
+        self.handshakes.snapshot()
+    }
+
     /// Set the JWT secret for authentication
     ///
     /// # Requirements
     /// - 1.3: Authenticate QUIC connections on establishment
     /// - 7.3: Support the same authentication tokens as WebSocket connections
     pub fn set_jwt_secret(&mut self, jwt_secret: String) {
-        self.authenticator = Some(QuicAuthenticator::new(jwt_secret));
+        self.authenticator = Some(
+            QuicAuthenticator::new(jwt_secret)
+                .with_max_payload_bytes(self.config.max_auth_payload_bytes),
+        );
     }
 
     /// Set the connection manager
@@ -194,6 +326,16 @@ impl QuicServer {
         self.connection_manager = Some(connection_manager);
     }
 
+    /// Set the store used to issue and redeem resumption tokens
+    pub fn set_resumption_store(&mut self, resumption: Arc<ResumptionStore>) {
+        self.resumption = Some(resumption);
+    }
+
+    /// Set the GeoIP lookup used to label connections in the devices list
+    pub fn set_geoip_lookup(&mut self, geoip: Arc<dyn GeoIpLookup>) {
+        self.geoip = geoip;
+    }
+
     /// Set the stream allocator
     ///
     /// # Requirements
@@ -223,13 +365,7 @@ impl QuicServer {
         let (certs, key) = self.load_tls_config()?;
 
         // Create rustls server configuration
-        let mut server_crypto = rustls::ServerConfig::builder()
-            .with_no_client_auth()
-            .with_single_cert(certs, key)
-            .map_err(|e| QuicServerError::Tls(e.to_string()))?;
-
-        // Configure ALPN protocols
-        server_crypto.alpn_protocols = vec![b"h3".to_vec()];
+        let server_crypto = self.build_crypto_config(certs, key)?;
 
         // Create Quinn server configuration
         let mut server_config = ServerConfig::with_crypto(Arc::new(
@@ -238,22 +374,7 @@ impl QuicServer {
         ));
 
         // Configure transport parameters
-        let mut transport_config = quinn::TransportConfig::default();
-        transport_config.max_concurrent_bidi_streams(
-            self.config.max_streams_per_connection.try_into().unwrap(),
-        );
-        transport_config.max_concurrent_uni_streams(
-            self.config.max_streams_per_connection.try_into().unwrap(),
-        );
-        transport_config.max_idle_timeout(Some(
-            self.config
-                .idle_timeout()
-                .try_into()
-                .map_err(|e| QuicServerError::Config(format!("Invalid idle timeout: {}", e)))?,
-        ));
-        transport_config.keep_alive_interval(Some(self.config.keep_alive_interval()));
-
-        server_config.transport_config(Arc::new(transport_config));
+        server_config.transport_config(Arc::new(Self::transport_config(&self.config)?));
 
         // Get bind address
         let bind_addr = self
@@ -280,6 +401,56 @@ impl QuicServer {
         Ok(())
     }
 
+    /// Quinn transport parameters: stream limits, timeouts and flow-control windows
+    fn transport_config(
+        config: &QuicServerConfig,
+    ) -> Result<quinn::TransportConfig, QuicServerError> {
+        let window = |name: &str, bytes: u64| {
+            quinn::VarInt::from_u64(bytes)
+                .map_err(|_| QuicServerError::Config(format!("Invalid {}: {}", name, bytes)))
+        };
+
+        let mut transport_config = quinn::TransportConfig::default();
+        transport_config.max_concurrent_bidi_streams(
+            config.max_streams_per_connection.try_into().unwrap(),
+        );
+        transport_config.max_concurrent_uni_streams(
+            config.max_streams_per_connection.try_into().unwrap(),
+        );
+        transport_config.max_idle_timeout(Some(
+            config
+                .idle_timeout()
+                .try_into()
+                .map_err(|e| QuicServerError::Config(format!("Invalid idle timeout: {}", e)))?,
+        ));
+        transport_config.keep_alive_interval(Some(config.keep_alive_interval()));
+        transport_config.stream_receive_window(window(
+            "stream receive window",
+            config.stream_receive_window,
+        )?);
+        transport_config.receive_window(window("receive window", config.receive_window)?);
+        transport_config.send_window(config.send_window);
+
+        Ok(transport_config)
+    }
+
+    /// Build the rustls server configuration, including the configured ALPN list
+    fn build_crypto_config(
+        &self,
+        certs: Vec<CertificateDer<'static>>,
+        key: PrivateKeyDer<'static>,
+    ) -> Result<rustls::ServerConfig, QuicServerError> {
+        let mut server_crypto = rustls::ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .map_err(|e| QuicServerError::Tls(e.to_string()))?;
+
+        server_crypto.alpn_protocols = self.config.alpn_protocol_ids();
+        info!("QUIC ALPN protocols: {}", self.config.alpn_protocols.join(", "));
+
+        Ok(server_crypto)
+    }
+
     /// Load TLS certificate and private key from files
     fn load_tls_config(
         &self,
@@ -427,8 +598,9 @@ impl QuicServer {
         let connection = incoming.await?;
 
         info!(
-            "Accepted QUIC connection from {}",
-            connection.remote_address()
+            "Accepted QUIC connection from {} (alpn: {})",
+            connection.remote_address(),
+            negotiated_alpn(&connection).as_deref().unwrap_or("none")
         );
 
         Ok(connection)
@@ -480,14 +652,61 @@ impl QuicServer {
             // Accept incoming connection
             match endpoint.accept().await {
                 Some(incoming) => {
+                    // Validate the source address first: a Retry costs no state,
+                    // and rate limiting spoofed addresses would lock out real clients
+                    if self.config.require_address_validation && !incoming.remote_address_validated() {
+                        self.handshakes.record_retry();
+                        if let Err(e) = incoming.retry() {
+                            e.into_incoming().ignore();
+                        }
+                        continue;
+                    }
+
+                    if !self.connection_rate_limiter.try_acquire(incoming.remote_address().ip()) {
+                        self.handshakes.record_rate_limited();
+                        warn!(
+                            "Refusing QUIC connection from {}: connection rate exceeded",
+                            incoming.remote_address()
+                        );
+                        incoming.refuse();
+                        continue;
+                    }
+
+                    // Only this loop increments the count, so check-then-add can't overshoot
+                    if self.active_connections.load(Ordering::SeqCst) >= self.max_connections() {
+                        self.reject_at_capacity(incoming);
+                        continue;
+                    }
+
+                    // Waiting here also holds back accepting further connections
+                    let Some(permit) = self.handshake_limiter.acquire(&self.handshakes).await else {
+                        warn!(
+                            "Refusing QUIC connection from {}: too many handshakes in progress",
+                            incoming.remote_address()
+                        );
+                        incoming.refuse();
+                        continue;
+                    };
+                    self.active_connections.fetch_add(1, Ordering::SeqCst);
+                    let guard = ActiveConnectionGuard(Arc::clone(&self.active_connections));
+
                     let handler = Arc::clone(&handler);
+                    let handshakes = Arc::clone(&self.handshakes);
                     
                     // Spawn a task to handle the connection
                     tokio::spawn(async move {
-                        match incoming.await {
+                        let _guard = guard;
+                        // The slot is freed once the handshake completes or fails
+                        let handshake = incoming.await;
+                        drop(permit);
+                        match handshake {
                             Ok(connection) => {
                                 let remote_addr = connection.remote_address();
-                                info!("Accepted QUIC connection from {}", remote_addr);
+                                info!(
+                                    "Accepted QUIC connection from {} (alpn: {})",
+                                    remote_addr,
+                                    negotiated_alpn(&connection).as_deref().unwrap_or("none")
+                                );
 
                                 // Handle the connection
                                 if let Err(e) = handler(connection).await {
@@ -498,6 +717,7 @@ impl QuicServer {
                                 }
                             }
                             Err(e) => {
+                                handshakes.record_failed();
                                 error!("Failed to establish connection: {}", e);
                             }
                         }
@@ -515,6 +735,36 @@ impl QuicServer {
         Ok(())
     }
 
+    /// Refuse an incoming connection because the server is at capacity
+    ///
+    /// The handshake is completed so the client receives an application close
+    /// code and reason instead of a bare refusal. It takes a handshake slot like
+    /// any other; when none is free the connection is refused outright.
+    fn reject_at_capacity(&self, incoming: quinn::Incoming) {
+        let rejected = self.rejected_at_capacity.fetch_add(1, Ordering::SeqCst) + 1;
+        warn!(
+            "Refusing QUIC connection from {}: at capacity ({} connections, {} rejected so far)",
+            incoming.remote_address(),
+            self.max_connections(),
+            rejected
+        );
+
+        let Some(permit) = self.handshake_limiter.try_acquire(&self.handshakes) else {
+            incoming.refuse();
+            return;
+        };
+        tokio::spawn(async move {
+            let handshake = incoming.await;
+            drop(permit);
+            if let Ok(connection) = handshake {
+                connection.close(
+                    VarInt::from_u32(CONNECTION_LIMIT_CLOSE_CODE),
+                    CONNECTION_LIMIT_CLOSE_REASON,
+                );
+            }
+        });
+    }
+
     /// Handle a single connection (placeholder for now)
     /// This will be expanded in later tasks to handle streams and messages
     pub async fn handle_connection(&self, connection: Connection) -> Result<(), QuicServerError> {
@@ -561,8 +811,8 @@ impl QuicServer {
     /// 
     /// // Accept and authenticate connections
     /// let connection = server.accept().await?;
-    /// let (conn_id, user_id, user_name) = server.authenticate_and_register_connection(connection).await?;
-    /// println!("Authenticated user: {} ({})", user_name, user_id);
+    /// let (conn_id, client) = server.authenticate_and_register_connection(connection).await?;
+    /// println!("Authenticated user: {} ({})", client.user_name, client.user_id);
     /// # Ok(())
     /// # }
     /// ```
@@ -571,13 +821,14 @@ impl QuicServer {
     /// * `connection` - The Quinn connection to authenticate
     ///
     /// # Returns
-    /// * `Ok((connection_id, user_id, user_name))` - Authentication successful
+    /// * `Ok((connection_id, client))` - Authentication successful
     /// * `Err(QuicServerError)` - Authentication failed
     pub async fn authenticate_and_register_connection(
         &self,
         connection: Connection,
-    ) -> Result<(ConnectionId, Uuid, String), QuicServerError> {
+    ) -> Result<(ConnectionId, AuthenticatedClient), QuicServerError> {
         let remote_addr = connection.remote_address();
+        let accepted_at = chrono::Utc::now().timestamp();
         
         // Check if authenticator is configured
         let authenticator = self.authenticator.as_ref().ok_or_else(|| {
@@ -591,52 +842,109 @@ impl QuicServer {
 
         info!("Authenticating QUIC connection from {}", remote_addr);
 
+        // Opening the auth stream and authenticating share one deadline
+        let deadline = tokio::time::Instant::now() + self.config.auth_timeout();
+
         // Accept the first bidirectional stream for authentication
-        let (send_stream, recv_stream) = match connection.accept_bi().await {
-            Ok(streams) => streams,
-            Err(e) => {
+        let (send_stream, recv_stream) = match tokio::time::timeout_at(deadline, connection.accept_bi()).await {
+            Ok(Ok(streams)) => streams,
+            Ok(Err(e)) => {
                 error!("Failed to accept authentication stream from {}: {}", remote_addr, e);
                 return Err(QuicServerError::Connection(e));
             }
+            Err(_) => return Err(self.auth_timed_out(&connection)),
         };
 
+        // Datagrams are only offered when the client's transport accepts them
+        let offer = ServerCapabilities::from_config(&self.config)
+            .with_datagrams(connection.max_datagram_size().is_some());
+
         // Authenticate the connection
-        let (user_id, user_name) = match authenticator.authenticate_connection(recv_stream, send_stream).await {
-            Ok((user_id, user_name)) => {
+        let connection_id = ConnectionId::new();
+        let auth = tokio::time::timeout_at(
+            deadline,
+            authenticator.authenticate_connection(
+                recv_stream,
+                send_stream,
+                accepted_at,
+                self.resumption.as_deref(),
+                connection_id,
+                &offer,
+            ),
+        );
+        let client = match auth.await {
+            Err(_) => return Err(self.auth_timed_out(&connection)),
+            Ok(Ok(client)) => {
                 info!(
-                    "QUIC authentication successful from {}: user_id={}, user_name={}",
-                    remote_addr, user_id, user_name
+                    "QUIC authentication successful from {}: user_id={}, user_name={}, resumed={}",
+                    remote_addr, client.user_id, client.user_name, client.resumed
                 );
-                (user_id, user_name)
+                client
+            }
+            Ok(Err(e @ QuicAuthError::Incompatible(_))) => {
+                warn!("QUIC client from {} is incompatible: {}", remote_addr, e);
+                connection.close(0u32.into(), b"Incompatible client");
+                return Err(e.into());
             }
-            Err(e) => {
+            Ok(Err(e)) => {
                 error!("QUIC authentication failed from {}: {}", remote_addr, e);
                 // Close the connection
                 connection.close(0u32.into(), b"Authentication failed");
-                return Err(QuicServerError::Config(format!("Authentication failed: {}", e)));
+                return Err(e.into());
             }
         };
 
         // Create a QuicConnection and register it
-        let connection_id = ConnectionId::new();
-        let mut quic_connection = QuicConnection::new(connection_id, connection);
+        let mut quic_connection = QuicConnection::new(connection_id, connection.clone());
         
         // Set the authenticated user ID
-        quic_connection.set_user_id(user_id);
+        quic_connection.set_user_id(client.user_id);
+        quic_connection.set_session_id(client.session_id);
+        quic_connection.set_capabilities(client.capabilities.clone());
+
+        // Label the session for the devices list
+        let remote_ip = remote_addr.ip();
+        quic_connection.set_info(ConnectionInfo {
+            remote_ip,
+            location: geoip::resolve_location(self.geoip.as_ref(), remote_ip),
+            device_name: client.device.name.clone(),
+            device_type: client.device.device_type.clone(),
+        });
 
         // Register the connection with the connection manager
         let managed_connection = ManagedConnection::Quic(quic_connection);
-        connection_manager
-            .register_connection(managed_connection)
-            .await
-            .map_err(|e| QuicServerError::Config(format!("Failed to register connection: {}", e)))?;
+        if let Err(e) = connection_manager.register_connection(managed_connection).await {
+            if matches!(e, ConnectionManagerError::ConnectionLimitReached(_)) {
+                warn!("QUIC connection from {} refused: {}", remote_addr, e);
+                connection.close(
+                    VarInt::from_u32(USER_CONNECTION_LIMIT_CLOSE_CODE),
+                    USER_CONNECTION_LIMIT_CLOSE_REASON,
+                );
+            }
+            return Err(QuicServerError::Config(format!("Failed to register connection: {}", e)));
+        }
 
         info!(
             "QUIC connection registered: connection_id={}, user_id={}, user_name={}",
-            connection_id, user_id, user_name
+            connection_id, client.user_id, client.user_name
         );
 
-        Ok((connection_id, user_id, user_name))
+        Ok((connection_id, client))
+    }
+
+    /// Close a connection that did not authenticate in time
+    fn auth_timed_out(&self, connection: &Connection) -> QuicServerError {
+        self.handshakes.record_auth_timeout();
+        warn!(
+            "QUIC connection from {} did not authenticate within {}s",
+            connection.remote_address(),
+            self.config.auth_timeout_secs
+        );
+        connection.close(
+            VarInt::from_u32(AUTH_TIMEOUT_CLOSE_CODE),
+            AUTH_TIMEOUT_CLOSE_REASON,
+        );
+        QuicAuthError::Timeout.into()
     }
 
     /// Accept an incoming bidirectional stream from a client
@@ -697,6 +1005,40 @@ impl QuicServer {
         Ok((send_stream, recv_stream))
     }
 
+    /// Set a send stream's priority from its message type
+    ///
+    /// # Returns
+    /// * `Ok(priority)` - The priority that was applied
+    /// * `Err(QuicServerError)` - If the stream is already closed
+    pub fn apply_stream_priority(
+        &self,
+        send_stream: &SendStream,
+        msg_type: MessageType,
+    ) -> Result<i32, QuicServerError> {
+        let priority = msg_type.priority(&self.config.stream_priorities);
+        send_stream
+            .set_priority(priority)
+            .map_err(|e| QuicServerError::Endpoint(format!("Failed to set stream priority: {}", e)))?;
+
+        Ok(priority)
+    }
+
+    /// Open an outgoing bidirectional stream for a message type, prioritized accordingly
+    ///
+    /// # Arguments
+    /// * `connection` - The QUIC connection to open a stream on
+    /// * `msg_type` - The kind of traffic the stream will carry
+    pub async fn open_prioritized_stream(
+        &self,
+        connection: &Connection,
+        msg_type: MessageType,
+    ) -> Result<(SendStream, RecvStream), QuicServerError> {
+        let (send_stream, recv_stream) = self.open_bidirectional_stream(connection).await?;
+        self.apply_stream_priority(&send_stream, msg_type)?;
+
+        Ok((send_stream, recv_stream))
+    }
+
     /// Open an outgoing unidirectional stream to a client
     ///
     /// # Requirements
@@ -811,47 +1153,6 @@ impl QuicServer {
         Ok(data)
     }
 
-    /// Handle a bidirectional stream with stream allocator integration
-    ///
-    /// # Requirements
-    /// - 3.1: Accept incoming streams from clients
-    /// - 3.1: Track active streams per connection
-    /// - 3.3: Handle stream lifecycle (open, close)
-    ///
-    /// # Arguments
-    /// * `connection_id` - The connection ID
-    /// * `_send_stream` - The send stream (unused in this method, but kept for API consistency)
-    /// * `_recv_stream` - The receive stream (unused in this method, but kept for API consistency)
-    /// * `msg_type` - The message type for this stream
-    ///
-    /// # Returns
-    /// * `Ok(stream_id)` - The allocated stream ID
-    pub async fn handle_stream_with_allocator(
-        &self,
-        connection_id: ConnectionId,
-        _send_stream: SendStream,
-        _recv_stream: RecvStream,
-        msg_type: MessageType,
-    ) -> Result<u64, QuicServerError> {
-        let stream_allocator = self
-            .stream_allocator
-            .as_ref()
-            .ok_or_else(|| QuicServerError::Config("Stream allocator not configured".to_string()))?;
-
-        // Allocate a stream ID
-        let stream_id = stream_allocator
-            .allocate_stream(connection_id, msg_type)
-            .await
-            .map_err(|e| QuicServerError::Config(format!("Failed to allocate stream: {}", e)))?;
-
-        info!(
-            "Allocated stream {} for connection {} (type: {:?})",
-            stream_id, connection_id, msg_type
-        );
-
-        Ok(stream_id)
-    }
-
     /// Release a stream from the stream allocator
     ///
     /// # Requirements
@@ -1069,6 +1370,26 @@ mod tests {
         assert!(!server.is_running().await);
     }
 
+    #[test]
+    fn test_flow_control_windows_reach_transport_config() {
+        let mut config = QuicServerConfig::default();
+        config.stream_receive_window = 1_111_111;
+        config.receive_window = 22_222_222;
+        config.send_window = 3_333_333;
+
+        // Quinn exposes no getters; its Debug output lists every parameter
+        let transport = QuicServer::transport_config(&config).unwrap();
+        let debug = format!("{:?}", transport);
+        // Window values print bare or wrapped in `VarInt(..)` depending on the quinn version
+        let has = |field: &str, value: &str| {
+            debug.contains(&format!("{}: {}", field, value))
+                || debug.contains(&format!("{}: VarInt({})", field, value))
+        };
+        assert!(has("stream_receive_window", "1111111"), "{}", debug);
+        assert!(has(" receive_window", "22222222"), "{}", debug);
+        assert!(has("send_window", "3333333"), "{}", debug);
+    }
+
     #[test]
     fn test_local_addr_before_init() {
         let config = QuicServerConfig::default();
@@ -1120,6 +1441,24 @@ mod tests {
         assert!(result.unwrap_err().to_string().contains("disabled"));
     }
 
+    #[test]
+    fn test_custom_alpn_round_trips_into_rustls_config() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+
+        let mut config = QuicServerConfig::default();
+        config.cert_path = concat!(env!("CARGO_MANIFEST_DIR"), "/certs/server.crt").into();
+        config.key_path = concat!(env!("CARGO_MANIFEST_DIR"), "/certs/server.key").into();
+        config.alpn_protocols = vec!["giano/2".to_string(), "giano/1".to_string()];
+        let server = QuicServer::new(config);
+
+        let (certs, key) = server.load_tls_config().unwrap();
+        let crypto = server.build_crypto_config(certs, key).unwrap();
+        assert_eq!(
+            crypto.alpn_protocols,
+            vec![b"giano/2".to_vec(), b"giano/1".to_vec()]
+        );
+    }
+
     #[test]
     fn test_config_getter() {
         let config = QuicServerConfig::default();
@@ -1147,6 +1486,22 @@ mod tests {
         assert_eq!(server.config().max_connections, 5000);
     }
 
+    #[tokio::test]
+    async fn test_update_config_applies_connection_cap() {
+        let mut server = QuicServer::new(QuicServerConfig::default());
+        assert_eq!(server.max_connections(), 10000);
+
+        let mut new_config = QuicServerConfig::default();
+        new_config.max_connections = 2;
+        server.update_config(new_config).await.unwrap();
+        assert_eq!(server.max_connections(), 2);
+
+        server.set_max_connections(3);
+        assert_eq!(server.max_connections(), 3);
+        assert_eq!(server.active_connections(), 0);
+        assert_eq!(server.rejected_at_capacity(), 0);
+    }
+
     #[tokio::test]
     async fn test_update_config_invalid() {
         let config = QuicServerConfig::default();
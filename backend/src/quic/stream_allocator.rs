@@ -1,10 +1,16 @@
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::{Notify, RwLock};
 use thiserror::Error;
 
+use crate::quic::config::{StreamIdleTimeouts, StreamPriorities};
+use crate::quic::stream_traffic::{MessageTypeTraffic, StreamTraffic};
 use crate::quic::ConnectionId;
 
+/// Application error code used to stop/reset a stream closed for inactivity
+pub const STREAM_IDLE_CLOSE_CODE: u32 = 0x12;
+
 /// Stream allocator errors
 #[derive(Debug, Error)]
 pub enum StreamAllocatorError {
@@ -38,6 +44,24 @@ pub enum MessageType {
 }
 
 impl MessageType {
+    /// Every message type, in stream range order
+    pub const ALL: [MessageType; 4] = [
+        MessageType::Control,
+        MessageType::ChatMessage,
+        MessageType::FileTransfer,
+        MessageType::BotCommand,
+    ];
+
+    /// Label used for this message type in metrics
+    pub fn label(&self) -> &'static str {
+        match self {
+            MessageType::Control => "control",
+            MessageType::ChatMessage => "chat_message",
+            MessageType::FileTransfer => "file_transfer",
+            MessageType::BotCommand => "bot_command",
+        }
+    }
+
     /// Get the stream range for this message type
     ///
     /// Stream allocation strategy from design:
@@ -54,6 +78,30 @@ impl MessageType {
         }
     }
 
+    /// Get the send priority for streams of this message type
+    ///
+    /// Control > ChatMessage > BotCommand > FileTransfer with the default
+    /// priorities; the values come from `QUIC_PRIORITY_*` configuration.
+    pub fn priority(&self, priorities: &StreamPriorities) -> i32 {
+        match self {
+            MessageType::Control => priorities.control,
+            MessageType::ChatMessage => priorities.chat_message,
+            MessageType::BotCommand => priorities.bot_command,
+            MessageType::FileTransfer => priorities.file_transfer,
+        }
+    }
+
+    /// Get how long a stream of this message type may stay idle
+    pub fn idle_timeout(&self, timeouts: &StreamIdleTimeouts) -> Duration {
+        let ms = match self {
+            MessageType::Control => timeouts.control_ms,
+            MessageType::ChatMessage => timeouts.chat_message_ms,
+            MessageType::BotCommand => timeouts.bot_command_ms,
+            MessageType::FileTransfer => timeouts.file_transfer_ms,
+        };
+        Duration::from_millis(ms)
+    }
+
     /// Determine message type from stream ID
     pub fn from_stream_id(stream_id: u64) -> Result<Self, StreamAllocatorError> {
         match stream_id {
@@ -111,6 +159,31 @@ impl From<MessageType> for StreamType {
     }
 }
 
+/// Last activity of an allocated stream and the signal used to close it
+#[derive(Debug)]
+struct StreamActivity {
+    last_activity: Instant,
+    idle_close: Arc<Notify>,
+}
+
+impl StreamActivity {
+    fn new() -> Self {
+        Self {
+            last_activity: Instant::now(),
+            idle_close: Arc::new(Notify::new()),
+        }
+    }
+}
+
+/// A stream released by the idle sweeper
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdleStream {
+    pub connection_id: ConnectionId,
+    pub stream_id: u64,
+    pub msg_type: MessageType,
+    pub idle_for: Duration,
+}
+
 /// Tracks active streams per connection
 #[derive(Debug)]
 struct ConnectionStreams {
@@ -118,6 +191,8 @@ struct ConnectionStreams {
     active_streams: HashMap<u64, MessageType>,
     /// Next stream ID to allocate for each message type
     next_stream_id: HashMap<MessageType, u64>,
+    /// Map of stream ID to its activity tracking
+    activity: HashMap<u64, StreamActivity>,
 }
 
 impl ConnectionStreams {
@@ -132,6 +207,7 @@ impl ConnectionStreams {
         Self {
             active_streams: HashMap::new(),
             next_stream_id,
+            activity: HashMap::new(),
         }
     }
 
@@ -149,6 +225,7 @@ impl ConnectionStreams {
             if !self.active_streams.contains_key(&stream_id) {
                 // Found an available stream
                 self.active_streams.insert(stream_id, msg_type);
+                self.activity.insert(stream_id, StreamActivity::new());
                 
                 // Update next stream ID (wrap around within range)
                 let next = if stream_id >= range.end {
@@ -179,9 +256,37 @@ impl ConnectionStreams {
         self.active_streams
             .remove(&stream_id)
             .ok_or(StreamAllocatorError::InvalidStreamId(stream_id))?;
+        self.activity.remove(&stream_id);
+        Ok(())
+    }
+
+    /// Record activity on a stream
+    fn touch_stream(&mut self, stream_id: u64) -> Result<(), StreamAllocatorError> {
+        let activity = self
+            .activity
+            .get_mut(&stream_id)
+            .ok_or(StreamAllocatorError::InvalidStreamId(stream_id))?;
+        activity.last_activity = Instant::now();
         Ok(())
     }
 
+    /// Streams idle for longer than their type's timeout, with their idle time
+    fn idle_streams(
+        &self,
+        now: Instant,
+        timeouts: &StreamIdleTimeouts,
+    ) -> Vec<(u64, MessageType, Duration)> {
+        self.active_streams
+            .iter()
+            .filter_map(|(&stream_id, &msg_type)| {
+                let activity = self.activity.get(&stream_id)?;
+                let idle_for = now.saturating_duration_since(activity.last_activity);
+                (idle_for > msg_type.idle_timeout(timeouts))
+                    .then_some((stream_id, msg_type, idle_for))
+            })
+            .collect()
+    }
+
     /// Get the message type for a stream
     fn get_stream_type(&self, stream_id: u64) -> Option<MessageType> {
         self.active_streams.get(&stream_id).copied()
@@ -215,6 +320,8 @@ impl ConnectionStreams {
 pub struct StreamAllocator {
     /// Map of connection ID to its active streams
     connections: Arc<RwLock<HashMap<ConnectionId, ConnectionStreams>>>,
+    /// Bytes and messages carried per message type
+    traffic: Arc<StreamTraffic>,
 }
 
 impl StreamAllocator {
@@ -222,9 +329,15 @@ impl StreamAllocator {
     pub fn new() -> Self {
         Self {
             connections: Arc::new(RwLock::new(HashMap::new())),
+            traffic: Arc::new(StreamTraffic::new()),
         }
     }
 
+    /// Per-message-type traffic counters shared with the message router
+    pub fn traffic(&self) -> Arc<StreamTraffic> {
+        Arc::clone(&self.traffic)
+    }
+
     /// Register a new connection
     ///
     /// # Requirements
@@ -292,6 +405,90 @@ impl StreamAllocator {
         conn_streams.release_stream(stream_id)
     }
 
+    /// Record activity on a stream, resetting its idle timer
+    pub async fn touch_stream(
+        &self,
+        connection_id: ConnectionId,
+        stream_id: u64,
+    ) -> Result<(), StreamAllocatorError> {
+        let mut connections = self.connections.write().await;
+        let conn_streams = connections
+            .get_mut(&connection_id)
+            .ok_or(StreamAllocatorError::ConnectionNotFound(connection_id))?;
+
+        conn_streams.touch_stream(stream_id)
+    }
+
+    /// Get the signal fired when the idle sweeper releases a stream
+    ///
+    /// The owner of the Quinn stream waits on it and stops/resets the stream
+    /// with `STREAM_IDLE_CLOSE_CODE`. The permit is stored, so a stream swept
+    /// before its owner starts waiting is still closed.
+    pub async fn idle_close_signal(
+        &self,
+        connection_id: ConnectionId,
+        stream_id: u64,
+    ) -> Result<Arc<Notify>, StreamAllocatorError> {
+        let connections = self.connections.read().await;
+        let conn_streams = connections
+            .get(&connection_id)
+            .ok_or(StreamAllocatorError::ConnectionNotFound(connection_id))?;
+
+        conn_streams
+            .activity
+            .get(&stream_id)
+            .map(|activity| Arc::clone(&activity.idle_close))
+            .ok_or(StreamAllocatorError::InvalidStreamId(stream_id))
+    }
+
+    /// Release every stream idle beyond its message type's timeout
+    ///
+    /// Each released stream's idle-close signal is fired so its owner closes
+    /// the underlying Quinn stream.
+    pub async fn release_idle_streams(&self, timeouts: &StreamIdleTimeouts) -> Vec<IdleStream> {
+        let now = Instant::now();
+        let mut connections = self.connections.write().await;
+        let mut released = Vec::new();
+
+        for (&connection_id, conn_streams) in connections.iter_mut() {
+            for (stream_id, msg_type, idle_for) in conn_streams.idle_streams(now, timeouts) {
+                if let Some(activity) = conn_streams.activity.get(&stream_id) {
+                    activity.idle_close.notify_one();
+                }
+                if conn_streams.release_stream(stream_id).is_ok() {
+                    released.push(IdleStream {
+                        connection_id,
+                        stream_id,
+                        msg_type,
+                        idle_for,
+                    });
+                }
+            }
+        }
+
+        released
+    }
+
+    /// Run the idle stream sweeper
+    /// This should be spawned as a background task
+    pub async fn run_idle_sweeper(self: Arc<Self>, timeouts: StreamIdleTimeouts, every: Duration) {
+        let mut interval = tokio::time::interval(every);
+
+        loop {
+            interval.tick().await;
+
+            for idle in self.release_idle_streams(&timeouts).await {
+                tracing::info!(
+                    "Closed idle stream {} on connection {} (type: {:?}, idle for {:?})",
+                    idle.stream_id,
+                    idle.connection_id,
+                    idle.msg_type,
+                    idle.idle_for
+                );
+            }
+        }
+    }
+
     /// Get the message type for a stream
     ///
     /// # Requirements
@@ -356,12 +553,7 @@ impl StreamAllocator {
         for conn_streams in connections.values() {
             total_streams += conn_streams.active_stream_count();
             
-            for msg_type in &[
-                MessageType::Control,
-                MessageType::ChatMessage,
-                MessageType::FileTransfer,
-                MessageType::BotCommand,
-            ] {
+            for msg_type in &MessageType::ALL {
                 let count = conn_streams.active_stream_count_for_type(*msg_type);
                 *streams_by_type.entry(*msg_type).or_insert(0) += count;
             }
@@ -374,6 +566,7 @@ impl StreamAllocator {
             chat_streams: *streams_by_type.get(&MessageType::ChatMessage).unwrap_or(&0),
             file_streams: *streams_by_type.get(&MessageType::FileTransfer).unwrap_or(&0),
             bot_streams: *streams_by_type.get(&MessageType::BotCommand).unwrap_or(&0),
+            traffic: self.traffic.snapshot(),
         }
     }
 }
@@ -385,7 +578,7 @@ impl Default for StreamAllocator {
 }
 
 /// Statistics about stream allocation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize)]
 pub struct StreamAllocatorStats {
     /// Total number of connections
     pub total_connections: usize,
@@ -399,12 +592,63 @@ pub struct StreamAllocatorStats {
     pub file_streams: usize,
     /// Number of bot command streams
     pub bot_streams: usize,
+    /// Bytes and messages per message type, with per-second rates
+    pub traffic: Vec<MessageTypeTraffic>,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_message_type_priority() {
+        let priorities = StreamPriorities::default();
+        assert!(MessageType::Control.priority(&priorities) > MessageType::ChatMessage.priority(&priorities));
+        assert!(MessageType::ChatMessage.priority(&priorities) > MessageType::BotCommand.priority(&priorities));
+        assert!(MessageType::BotCommand.priority(&priorities) > MessageType::FileTransfer.priority(&priorities));
+
+        let custom = StreamPriorities { file_transfer: 10, ..StreamPriorities::default() };
+        assert_eq!(MessageType::FileTransfer.priority(&custom), 10);
+    }
+
+    #[test]
+    fn test_message_type_idle_timeout() {
+        let timeouts = StreamIdleTimeouts::default();
+        assert_eq!(MessageType::Control.idle_timeout(&timeouts), Duration::from_secs(30));
+        assert_eq!(MessageType::ChatMessage.idle_timeout(&timeouts), Duration::from_secs(60));
+        assert_eq!(MessageType::FileTransfer.idle_timeout(&timeouts), Duration::from_secs(600));
+    }
+
+    #[tokio::test]
+    async fn test_release_idle_streams() {
+        let allocator = StreamAllocator::new();
+        let conn_id = ConnectionId::new();
+        allocator.register_connection(conn_id).await;
+
+        let chat = allocator.allocate_stream(conn_id, MessageType::ChatMessage).await.unwrap();
+        let file = allocator.allocate_stream(conn_id, MessageType::FileTransfer).await.unwrap();
+        let signal = allocator.idle_close_signal(conn_id, chat).await.unwrap();
+
+        let timeouts = StreamIdleTimeouts {
+            chat_message_ms: 10,
+            ..StreamIdleTimeouts::default()
+        };
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        allocator.touch_stream(conn_id, file).await.unwrap();
+
+        let released = allocator.release_idle_streams(&timeouts).await;
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].stream_id, chat);
+        assert_eq!(released[0].msg_type, MessageType::ChatMessage);
+
+        // The owner is told to close the Quinn stream, and the id is free again
+        tokio::time::timeout(Duration::from_millis(100), signal.notified())
+            .await
+            .expect("idle close signal");
+        assert_eq!(allocator.get_active_streams(conn_id).await.unwrap(), vec![file]);
+        assert!(allocator.touch_stream(conn_id, chat).await.is_err());
+    }
+
     #[test]
     fn test_message_type_stream_range() {
         assert_eq!(MessageType::Control.stream_range(), StreamRange { start: 0, end: 0 });
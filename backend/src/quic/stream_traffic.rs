@@ -0,0 +1,240 @@
+/// Stream traffic module - per-message-type byte and message counters
+///
+/// This module provides:
+/// - Lock-free counters of bytes received, bytes sent and messages routed
+///   for each `MessageType`, updated by the message router
+/// - Per-second rates derived from those counters by a periodic sampler
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::quic::stream_allocator::MessageType;
+
+/// Raw counters for one message type
+#[derive(Debug, Default)]
+struct TypeCounters {
+    bytes_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    messages_routed: AtomicU64,
+}
+
+impl TypeCounters {
+    fn totals(&self) -> Totals {
+        Totals {
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            messages_routed: self.messages_routed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Totals {
+    bytes_received: u64,
+    bytes_sent: u64,
+    messages_routed: u64,
+}
+
+/// Per-second rates for one message type over the last sample interval
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct TrafficRates {
+    pub bytes_received_per_sec: f64,
+    pub bytes_sent_per_sec: f64,
+    pub messages_routed_per_sec: f64,
+}
+
+/// Last sample taken by the rate sampler
+#[derive(Debug)]
+struct RateSample {
+    taken_at: Instant,
+    totals: [Totals; 4],
+    rates: [TrafficRates; 4],
+}
+
+/// Traffic carried by streams of one message type
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageTypeTraffic {
+    /// Message type label (`control`, `chat_message`, ...)
+    pub message_type: &'static str,
+    pub bytes_received: u64,
+    pub bytes_sent: u64,
+    pub messages_routed: u64,
+    pub rates: TrafficRates,
+}
+
+/// Byte and message counters per `MessageType`
+#[derive(Debug)]
+pub struct StreamTraffic {
+    counters: [TypeCounters; 4],
+    sample: Mutex<RateSample>,
+}
+
+fn index(msg_type: MessageType) -> usize {
+    match msg_type {
+        MessageType::Control => 0,
+        MessageType::ChatMessage => 1,
+        MessageType::FileTransfer => 2,
+        MessageType::BotCommand => 3,
+    }
+}
+
+fn per_sec(delta: u64, elapsed: f64) -> f64 {
+    if elapsed > 0.0 {
+        delta as f64 / elapsed
+    } else {
+        0.0
+    }
+}
+
+impl StreamTraffic {
+    /// Create zeroed counters
+    pub fn new() -> Self {
+        Self {
+            counters: Default::default(),
+            sample: Mutex::new(RateSample {
+                taken_at: Instant::now(),
+                totals: [Totals::default(); 4],
+                rates: [TrafficRates::default(); 4],
+            }),
+        }
+    }
+
+    /// Count bytes read from a stream of this type
+    pub fn record_received(&self, msg_type: MessageType, bytes: usize) {
+        self.counters[index(msg_type)]
+            .bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Count bytes written back on a stream of this type
+    pub fn record_sent(&self, msg_type: MessageType, bytes: usize) {
+        self.counters[index(msg_type)]
+            .bytes_sent
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Count a message successfully routed from a stream of this type
+    pub fn record_routed(&self, msg_type: MessageType) {
+        self.counters[index(msg_type)]
+            .messages_routed
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Recompute per-second rates from the counters since the last sample
+    pub fn sample(&self) {
+        self.sample_at(Instant::now());
+    }
+
+    fn sample_at(&self, now: Instant) {
+        let mut sample = self.sample.lock().unwrap_or_else(|e| e.into_inner());
+        let elapsed = now.saturating_duration_since(sample.taken_at).as_secs_f64();
+
+        for (i, counters) in self.counters.iter().enumerate() {
+            let totals = counters.totals();
+            let previous = sample.totals[i];
+            sample.rates[i] = TrafficRates {
+                bytes_received_per_sec: per_sec(
+                    totals.bytes_received.saturating_sub(previous.bytes_received),
+                    elapsed,
+                ),
+                bytes_sent_per_sec: per_sec(
+                    totals.bytes_sent.saturating_sub(previous.bytes_sent),
+                    elapsed,
+                ),
+                messages_routed_per_sec: per_sec(
+                    totals.messages_routed.saturating_sub(previous.messages_routed),
+                    elapsed,
+                ),
+            };
+            sample.totals[i] = totals;
+        }
+        sample.taken_at = now;
+    }
+
+    /// Current totals and the rates from the most recent sample
+    pub fn snapshot(&self) -> Vec<MessageTypeTraffic> {
+        let rates = self.sample.lock().unwrap_or_else(|e| e.into_inner()).rates;
+
+        MessageType::ALL
+            .iter()
+            .map(|msg_type| {
+                let i = index(*msg_type);
+                let totals = self.counters[i].totals();
+                MessageTypeTraffic {
+                    message_type: msg_type.label(),
+                    bytes_received: totals.bytes_received,
+                    bytes_sent: totals.bytes_sent,
+                    messages_routed: totals.messages_routed,
+                    rates: rates[i],
+                }
+            })
+            .collect()
+    }
+
+    /// Run the rate sampler
+    /// This should be spawned as a background task
+    pub async fn run_sampler(self: Arc<Self>, every: Duration) {
+        let mut interval = tokio::time::interval(every);
+
+        loop {
+            interval.tick().await;
+            self.sample();
+        }
+    }
+}
+
+impl Default for StreamTraffic {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_are_kept_per_message_type() {
+        let traffic = StreamTraffic::new();
+        traffic.record_received(MessageType::FileTransfer, 4096);
+        traffic.record_received(MessageType::FileTransfer, 1024);
+        traffic.record_sent(MessageType::ChatMessage, 20);
+        traffic.record_routed(MessageType::BotCommand);
+
+        let snapshot = traffic.snapshot();
+        assert_eq!(snapshot.len(), 4);
+
+        let file = snapshot.iter().find(|t| t.message_type == "file_transfer").unwrap();
+        assert_eq!(file.bytes_received, 5120);
+        assert_eq!(file.bytes_sent, 0);
+
+        let chat = snapshot.iter().find(|t| t.message_type == "chat_message").unwrap();
+        assert_eq!(chat.bytes_sent, 20);
+
+        let bot = snapshot.iter().find(|t| t.message_type == "bot_command").unwrap();
+        assert_eq!(bot.messages_routed, 1);
+    }
+
+    #[test]
+    fn test_rates_cover_the_last_sample_interval() {
+        let traffic = StreamTraffic::new();
+        let start = traffic.sample.lock().unwrap().taken_at;
+
+        traffic.record_received(MessageType::ChatMessage, 1000);
+        traffic.record_routed(MessageType::ChatMessage);
+        traffic.record_routed(MessageType::ChatMessage);
+        traffic.sample_at(start + Duration::from_secs(2));
+
+        let chat = traffic.snapshot().into_iter().find(|t| t.message_type == "chat_message").unwrap();
+        assert_eq!(chat.rates.bytes_received_per_sec, 500.0);
+        assert_eq!(chat.rates.messages_routed_per_sec, 1.0);
+
+        // Nothing new in the next interval: rates drop back to zero, totals stay
+        traffic.sample_at(start + Duration::from_secs(4));
+        let chat = traffic.snapshot().into_iter().find(|t| t.message_type == "chat_message").unwrap();
+        assert_eq!(chat.rates, TrafficRates::default());
+        assert_eq!(chat.bytes_received, 1000);
+    }
+}
@@ -0,0 +1,576 @@
+/// Admin API Routes - Server operator endpoints.
+///
+/// This module provides:
+/// - GET /api/v1/admin/dead-letters - List recent unroutable QUIC messages
+/// - DELETE /api/v1/admin/dead-letters - Clear the dead-letter log
+/// - GET /api/v1/admin/maintenance - Get maintenance mode status
+/// - POST /api/v1/admin/maintenance - Enable or disable maintenance mode
+/// - POST /api/v1/admin/users/:user_id/disconnect - Force a user offline on all transports
+/// - DELETE /api/v1/admin/messages/:message_id - Purge a message without leaving a tombstone
+/// - GET /api/v1/admin/stats - System health summary for the internal dashboard
+/// - POST /api/v1/admin/announce - Broadcast a system announcement
+/// - POST /api/v1/admin/bots/:bot_id/privilege - Exempt a bot from rate limits (or not)
+/// - GET /api/v1/admin/emoji - List global custom emoji
+/// - POST /api/v1/admin/emoji - Upload a global custom emoji
+/// - DELETE /api/v1/admin/emoji/:emoji_id - Delete a global custom emoji
+///
+/// All routes require the caller to be listed in `ADMIN_USER_IDS`. When
+/// `ADMIN_IP_ALLOWLIST` is set, requests from other addresses are refused
+/// before authentication.
+use axum::{
+    extract::{ConnectInfo, Multipart, Path, Query, Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, AppResult},
+    models::{AnnouncementLevel, CustomEmojiResponse, MessageResponse},
+    quic::{ConnectionStats, DeadLetter, MetricsSnapshot, MigrationStats, StreamAllocatorStats},
+    routes::{auth::get_current_user_id, upload::read_custom_emoji_form},
+    services::{
+        admin_stats::EntityCounts,
+        announcement::AnnouncementTarget,
+        bot_engine::BotEngineService,
+        login_rate_limiter::client_ip,
+        AnnouncementService, AttachmentService, AuthService, ChatService, CustomEmojiService,
+        MessageService, WebSocketService,
+    },
+    AppState,
+};
+
+pub fn routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route(
+            "/dead-letters",
+            get(list_dead_letters).delete(clear_dead_letters),
+        )
+        .route(
+            "/maintenance",
+            get(get_maintenance).post(set_maintenance),
+        )
+        .route("/users/:user_id/disconnect", post(disconnect_user))
+        .route("/messages/:message_id", delete(purge_message))
+        .route("/stats", get(get_stats))
+        .route("/announce", post(announce))
+        .route("/bots/:bot_id/privilege", post(set_bot_privilege))
+        .route("/emoji", get(list_global_emoji).post(add_global_emoji))
+        .route("/emoji/:emoji_id", delete(delete_global_emoji))
+        .route_layer(middleware::from_fn_with_state(state, ip_allowlist))
+}
+
+/// Refuse requests from outside `ADMIN_IP_ALLOWLIST`, whatever their credentials
+async fn ip_allowlist(
+    State(state): State<Arc<AppState>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let allowlist = &state.config.admin_ip_allowlist;
+    if allowlist.is_empty() {
+        return next.run(request).await;
+    }
+
+    let ip = client_ip(
+        request.headers(),
+        state.config.trusted_proxy_header.as_deref(),
+        peer.map(|ConnectInfo(addr)| addr),
+    );
+    if !ip_allowed(allowlist, ip.as_deref()) {
+        tracing::warn!(ip = ?ip, "Admin request from outside ADMIN_IP_ALLOWLIST refused");
+        return AppError::Forbidden("Admin access is not allowed from this address".to_string())
+            .into_response();
+    }
+
+    next.run(request).await
+}
+
+/// Whether `ip` falls in one of the allowed networks; an unknown or
+/// unparsable address is never allowed
+fn ip_allowed(allowlist: &[IpNet], ip: Option<&str>) -> bool {
+    let Some(ip) = ip.and_then(|ip| ip.parse::<IpAddr>().ok()) else {
+        return false;
+    };
+    // IPv4 clients of a dual-stack listener show up as ::ffff:a.b.c.d
+    let ip = ip.to_canonical();
+    allowlist.iter().any(|network| network.contains(&ip))
+}
+
+/// Authenticate the request and verify the user is a server admin.
+pub async fn require_admin(state: &AppState, headers: &HeaderMap) -> AppResult<Uuid> {
+    let user_id = get_current_user_id(state, headers).await?;
+
+    if !state.config.admin_user_ids.contains(&user_id) {
+        return Err(AppError::Forbidden("Admin access required".to_string()));
+    }
+
+    Ok(user_id)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeadLettersQuery {
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeadLettersResponse {
+    enabled: bool,
+    #[serde(rename = "deadLetters")]
+    dead_letters: Vec<DeadLetter>,
+}
+
+/// List recent dead letters, newest first.
+///
+/// GET /api/v1/admin/dead-letters?limit=100
+async fn list_dead_letters(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<DeadLettersQuery>,
+) -> AppResult<Json<DeadLettersResponse>> {
+    require_admin(&state, &headers).await?;
+
+    let limit = query.limit.unwrap_or(100).min(1000);
+
+    Ok(Json(DeadLettersResponse {
+        enabled: state.dead_letters.is_enabled(),
+        dead_letters: state.dead_letters.recent(limit),
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimpleMessage {
+    message: String,
+}
+
+/// Clear the dead-letter log.
+///
+/// DELETE /api/v1/admin/dead-letters
+async fn clear_dead_letters(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> AppResult<Json<SimpleMessage>> {
+    require_admin(&state, &headers).await?;
+
+    state.dead_letters.clear();
+
+    Ok(Json(SimpleMessage {
+        message: "Dead-letter log cleared".to_string(),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetMaintenanceRequest {
+    enabled: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MaintenanceResponse {
+    enabled: bool,
+}
+
+/// Get whether maintenance mode is enabled.
+///
+/// GET /api/v1/admin/maintenance
+async fn get_maintenance(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> AppResult<Json<MaintenanceResponse>> {
+    require_admin(&state, &headers).await?;
+
+    Ok(Json(MaintenanceResponse {
+        enabled: state.is_maintenance_mode(),
+    }))
+}
+
+/// Enable or disable maintenance mode without a restart.
+///
+/// POST /api/v1/admin/maintenance
+/// Body: { "enabled": true }
+async fn set_maintenance(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<SetMaintenanceRequest>,
+) -> AppResult<Json<MaintenanceResponse>> {
+    let admin_id = require_admin(&state, &headers).await?;
+
+    state.set_maintenance_mode(req.enabled);
+    tracing::warn!(
+        "Maintenance mode {} by admin {}",
+        if req.enabled { "enabled" } else { "disabled" },
+        admin_id
+    );
+
+    Ok(Json(MaintenanceResponse {
+        enabled: req.enabled,
+    }))
+}
+
+/// Close reason used when the request does not supply one
+const DEFAULT_DISCONNECT_REASON: &str = "disconnected by administrator";
+
+/// Close reasons travel in a single QUIC frame / WebSocket close frame
+const MAX_DISCONNECT_REASON_LENGTH: usize = 120;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DisconnectUserRequest {
+    /// Also revoke every issued token and session so the user cannot reconnect
+    #[serde(default, rename = "revokeSessions")]
+    revoke_sessions: bool,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DisconnectUserResponse {
+    #[serde(rename = "quicClosed")]
+    quic_closed: usize,
+    #[serde(rename = "websocketClosed")]
+    websocket_closed: usize,
+    total: usize,
+    #[serde(rename = "sessionsRevoked")]
+    sessions_revoked: u64,
+}
+
+/// Forcibly disconnect every realtime connection of a user.
+///
+/// Tokens are revoked before any connection is closed, so a client that
+/// reconnects while this runs is rejected once it registers.
+///
+/// POST /api/v1/admin/users/:user_id/disconnect
+/// Body (optional): { "revokeSessions": true, "reason": "..." }
+async fn disconnect_user(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+    req: Option<Json<DisconnectUserRequest>>,
+) -> AppResult<Json<DisconnectUserResponse>> {
+    let admin_id = require_admin(&state, &headers).await?;
+    let req = req.map(|Json(req)| req).unwrap_or_default();
+
+    let reason = req
+        .reason
+        .as_deref()
+        .map(str::trim)
+        .filter(|reason| !reason.is_empty())
+        .unwrap_or(DEFAULT_DISCONNECT_REASON);
+    if reason.len() > MAX_DISCONNECT_REASON_LENGTH {
+        return Err(AppError::BadRequest(format!(
+            "Reason must be at most {} bytes",
+            MAX_DISCONNECT_REASON_LENGTH
+        )));
+    }
+
+    let sessions_revoked = if req.revoke_sessions {
+        AuthService::revoke_user_tokens(&state.db, user_id).await?
+    } else {
+        0
+    };
+
+    let quic_closed = state
+        .connection_manager
+        .close_all_user_connections(user_id, reason)
+        .await;
+    let websocket_closed = state.ws_manager.disconnect_user(user_id, reason).await;
+
+    tracing::warn!(
+        "User {} disconnected by admin {}: quic={}, websocket={}, sessions_revoked={}",
+        user_id,
+        admin_id,
+        quic_closed,
+        websocket_closed,
+        sessions_revoked
+    );
+
+    Ok(Json(DisconnectUserResponse {
+        quic_closed,
+        websocket_closed,
+        total: quic_closed + websocket_closed,
+        sessions_revoked,
+    }))
+}
+
+/// Permanently remove a message, its attachments (including stored files)
+/// and its thread replies
+///
+/// DELETE /api/v1/admin/messages/:message_id
+async fn purge_message(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(message_id): Path<Uuid>,
+) -> AppResult<Json<SimpleMessage>> {
+    let admin_id = require_admin(&state, &headers).await?;
+
+    let (chat_id, files) = MessageService::purge_message(&state.db, message_id).await?;
+    AttachmentService::spawn_remove_files(files);
+
+    tracing::warn!("Message {} in chat {} purged by admin {}", message_id, chat_id, admin_id);
+
+    let participant_ids = ChatService::get_participant_ids(&state.db, chat_id).await?;
+    WebSocketService::broadcast_message_deleted(
+        &state.ws_manager,
+        chat_id,
+        message_id,
+        &participant_ids,
+        admin_id,
+    )
+    .await;
+
+    Ok(Json(SimpleMessage {
+        message: "Message purged".to_string(),
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct DbPoolStats {
+    /// Open connections, idle or in use
+    size: u32,
+    idle: usize,
+    #[serde(rename = "inUse")]
+    in_use: usize,
+    #[serde(rename = "maxConnections")]
+    max_connections: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AdminStatsResponse {
+    connections: ConnectionStats,
+    migrations: MigrationStats,
+    streams: StreamAllocatorStats,
+    metrics: MetricsSnapshot,
+    database: DbPoolStats,
+    #[serde(rename = "activeCalls")]
+    active_calls: usize,
+    /// Cached for a short TTL; see `countedAt`
+    counts: EntityCounts,
+    #[serde(rename = "maintenanceMode")]
+    maintenance_mode: bool,
+}
+
+/// Summarize system health in one call.
+///
+/// GET /api/v1/admin/stats
+async fn get_stats(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> AppResult<Json<AdminStatsResponse>> {
+    require_admin(&state, &headers).await?;
+
+    let size = state.db.pool.size();
+    let idle = state.db.pool.num_idle();
+
+    Ok(Json(AdminStatsResponse {
+        connections: state.connection_manager.get_stats().await,
+        migrations: state.connection_manager.get_migration_stats().await,
+        streams: state.stream_allocator.get_stats().await,
+        metrics: state.quic_metrics.snapshot().await,
+        database: DbPoolStats {
+            size,
+            idle,
+            in_use: (size as usize).saturating_sub(idle),
+            max_connections: state.config.db_max_connections,
+        },
+        active_calls: state.ws_manager.active_call_count().await,
+        counts: state.entity_counts.get(&state.db).await?,
+        maintenance_mode: state.is_maintenance_mode(),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnnounceRequest {
+    text: String,
+    #[serde(default)]
+    level: AnnouncementLevel,
+    /// Only announce to this chat's participants; everyone when absent
+    #[serde(rename = "chatId")]
+    chat_id: Option<Uuid>,
+    /// Also keep it in the chat's history as a system message (chat only)
+    #[serde(default)]
+    persist: bool,
+    /// Also notify offline targets by web push
+    #[serde(default)]
+    push: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AnnounceResponse {
+    #[serde(rename = "websocketUsers")]
+    websocket_users: usize,
+    #[serde(rename = "quicUsers")]
+    quic_users: usize,
+    #[serde(rename = "pushUsers")]
+    push_users: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<MessageResponse>,
+}
+
+/// Broadcast a system announcement to all connected users, or to one chat.
+///
+/// Works in maintenance mode, where it is needed most.
+///
+/// POST /api/v1/admin/announce
+/// Body: { "text": "...", "level": "warning", "chatId": null, "persist": false, "push": false }
+async fn announce(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<AnnounceRequest>,
+) -> AppResult<Json<AnnounceResponse>> {
+    let admin_id = require_admin(&state, &headers).await?;
+
+    let target = match req.chat_id {
+        Some(chat_id) => AnnouncementTarget::Chat(chat_id),
+        None => AnnouncementTarget::AllUsers,
+    };
+    let report = AnnouncementService::announce(
+        &state,
+        admin_id,
+        &req.text,
+        req.level,
+        target,
+        req.persist,
+        req.push,
+    )
+    .await?;
+
+    Ok(Json(AnnounceResponse {
+        websocket_users: report.websocket_users,
+        quic_users: report.quic_users,
+        push_users: report.push_users,
+        message: report.message,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BotPrivilegeRequest {
+    privileged: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BotPrivilegeResponse {
+    #[serde(rename = "botId")]
+    bot_id: Uuid,
+    privileged: bool,
+}
+
+/// Grant or revoke a bot's exemption from the bot API rate limit.
+///
+/// Every request a privileged bot makes is written to the audit log.
+///
+/// POST /api/v1/admin/bots/:bot_id/privilege
+/// Body: { "privileged": true }
+async fn set_bot_privilege(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(bot_id): Path<Uuid>,
+    Json(req): Json<BotPrivilegeRequest>,
+) -> AppResult<Json<BotPrivilegeResponse>> {
+    let admin_id = require_admin(&state, &headers).await?;
+
+    let bot = BotEngineService::set_privileged(&state.db, bot_id, admin_id, req.privileged).await?;
+    tracing::warn!(
+        "Bot {} ({}) privileged={} set by admin {}",
+        bot.name,
+        bot.id,
+        bot.privileged,
+        admin_id
+    );
+
+    Ok(Json(BotPrivilegeResponse {
+        bot_id: bot.id,
+        privileged: bot.privileged,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct GlobalEmojiListResponse {
+    emoji: Vec<CustomEmojiResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GlobalEmojiResponse {
+    emoji: CustomEmojiResponse,
+}
+
+/// List global custom emoji.
+///
+/// GET /api/v1/admin/emoji
+async fn list_global_emoji(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> AppResult<Json<GlobalEmojiListResponse>> {
+    require_admin(&state, &headers).await?;
+
+    let emoji = CustomEmojiService::list_global(&state.db).await?;
+    Ok(Json(GlobalEmojiListResponse {
+        emoji: emoji.iter().map(|e| e.to_response()).collect(),
+    }))
+}
+
+/// Upload a custom emoji usable in every chat.
+///
+/// The shortcode must not be used by any chat's emoji.
+///
+/// POST /api/v1/admin/emoji
+/// Body: multipart form with `shortcode` and `file`
+async fn add_global_emoji(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    multipart: Multipart,
+) -> AppResult<(StatusCode, Json<GlobalEmojiResponse>)> {
+    let admin_id = require_admin(&state, &headers).await?;
+
+    let (shortcode, data) = read_custom_emoji_form(multipart).await?;
+    let emoji = CustomEmojiService::create(&state, None, admin_id, &shortcode, &data).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(GlobalEmojiResponse {
+            emoji: emoji.to_response(),
+        }),
+    ))
+}
+
+/// Delete a global custom emoji and every reaction using it.
+///
+/// DELETE /api/v1/admin/emoji/:emoji_id
+async fn delete_global_emoji(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(emoji_id): Path<Uuid>,
+) -> AppResult<StatusCode> {
+    require_admin(&state, &headers).await?;
+
+    CustomEmojiService::delete_global(&state.db, emoji_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowlist() -> Vec<IpNet> {
+        vec!["10.0.0.0/8".parse().unwrap(), "fd00::/8".parse().unwrap()]
+    }
+
+    #[test]
+    fn test_addresses_in_range_are_allowed() {
+        assert!(ip_allowed(&allowlist(), Some("10.1.2.3")));
+        assert!(ip_allowed(&allowlist(), Some("::ffff:10.0.0.1")));
+        assert!(ip_allowed(&allowlist(), Some("fd12::1")));
+    }
+
+    #[test]
+    fn test_addresses_out_of_range_are_refused() {
+        assert!(!ip_allowed(&allowlist(), Some("11.0.0.1")));
+        assert!(!ip_allowed(&allowlist(), Some("2001:db8::1")));
+        assert!(!ip_allowed(&allowlist(), Some("not-an-ip")));
+        assert!(!ip_allowed(&allowlist(), None));
+    }
+}
@@ -1,17 +1,18 @@
 use axum::{
-    extract::State,
+    extract::{ConnectInfo, State},
     http::{header, HeaderMap},
     routing::{get, post},
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::{
     error::{AppError, AppResult},
     models::UserSession,
-    services::{AuthService, LoginRateLimiter},
+    services::{login_rate_limiter::client_ip, AuthService, LoginRateLimiter},
     AppState,
 };
 
@@ -40,6 +41,7 @@ async fn register(
     State(state): State<Arc<AppState>>,
     Json(req): Json<RegisterRequest>,
 ) -> AppResult<Json<SessionResponse>> {
+    state.ensure_writable()?;
     let session = AuthService::register(
         &state.db,
         &req.email,
@@ -61,41 +63,30 @@ pub struct LoginRequest {
 
 async fn login(
     State(state): State<Arc<AppState>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
     headers: HeaderMap,
     Json(req): Json<LoginRequest>,
 ) -> AppResult<Json<SessionResponse>> {
     // Extract IP address and User-Agent for rate limiting
-    let ip_address = headers
-        .get("x-forwarded-for")
-        .or_else(|| headers.get("x-real-ip"))
-        .and_then(|v| v.to_str().ok())
-        .map(|s| s.to_string());
-    
+    let ip_address = client_ip(
+        &headers,
+        state.config.trusted_proxy_header.as_deref(),
+        peer.map(|ConnectInfo(addr)| addr),
+    );
+
     let user_agent = headers
         .get(header::USER_AGENT)
         .and_then(|v| v.to_str().ok())
         .map(|s| s.to_string());
 
-    // Check rate limit
-    let (allowed, retry_after) = LoginRateLimiter::check_rate_limit(
-        &state.db,
-        &req.email,
-        ip_address.as_deref(),
-    )
-    .await?;
-
-    if !allowed {
-        // Record failed attempt
-        LoginRateLimiter::record_attempt(
-            &state.db,
-            &req.email,
-            ip_address.as_deref(),
-            false,
-            user_agent.as_deref(),
-        )
-        .await?;
-
-        return Err(AppError::LoginRateLimitExceeded(retry_after.unwrap_or(60)));
+    // Check rate limit. Rejected attempts are not counted as failures so a
+    // locked account is released once the (capped) lockout expires.
+    if let Some(retry_after) = state
+        .login_rate_limiter
+        .check(&state.db, &req.email, ip_address.as_deref())
+        .await?
+    {
+        return Err(AppError::LoginRateLimitExceeded(retry_after));
     }
 
     // Attempt login
@@ -111,30 +102,26 @@ async fn login(
     // Record attempt
     match &result {
         Ok(_) => {
-            // Success - clear failed attempts
-            LoginRateLimiter::clear_failed_attempts(&state.db, &req.email).await?;
-            LoginRateLimiter::record_attempt(
-                &state.db,
-                &req.email,
-                ip_address.as_deref(),
-                true,
-                user_agent.as_deref(),
-            )
-            .await?;
+            // Success - reset failed attempt counters
+            state.login_rate_limiter.reset(&state.db, &req.email).await?;
         }
         Err(_) => {
-            // Failed - record attempt
-            LoginRateLimiter::record_attempt(
-                &state.db,
-                &req.email,
-                ip_address.as_deref(),
-                false,
-                user_agent.as_deref(),
-            )
-            .await?;
+            state
+                .login_rate_limiter
+                .record_failure(&req.email, ip_address.as_deref())
+                .await?;
         }
     }
 
+    LoginRateLimiter::record_attempt(
+        &state.db,
+        &req.email,
+        ip_address.as_deref(),
+        result.is_ok(),
+        user_agent.as_deref(),
+    )
+    .await?;
+
     let session = result?;
     Ok(Json(SessionResponse { session }))
 }
@@ -209,5 +196,8 @@ pub async fn get_current_user_id(
     let token = extract_token(headers)?;
     let claims = AuthService::verify_token(&token, &state.config.jwt_secret)?;
     let user_id: Uuid = claims.sub.parse().map_err(|_| AppError::InvalidToken)?;
+    if AuthService::is_token_revoked(&state.db, user_id, claims.iat).await? {
+        return Err(AppError::InvalidToken);
+    }
     Ok(user_id)
 }
@@ -2,8 +2,19 @@
 ///
 /// This module provides:
 /// - POST /bot:token/sendMessage - Send a message to a chat
+/// - POST /bot:token/sendBulk - Send one message to several chats
+/// - POST /bot:token/editMessageText - Edit one of the bot's messages
+/// - POST /bot:token/answerCallbackQuery - Answer an inline keyboard button press
 /// - POST /bot:token/setWebhook - Set webhook URL for updates
 /// - GET /bot:token/getMe - Get bot information
+/// - GET /bot:token/getMyCommands - Commands advertised to users
+/// - POST /bot:token/setMyCommands - Replace the advertised commands
+///
+/// Every call counts against the bot's rate limit, and every response from
+/// an authenticated bot carries `X-RateLimit-Limit`, `X-RateLimit-Remaining`
+/// and `X-RateLimit-Reset` (seconds until the window resets). Rejected
+/// calls get a 429 with `Retry-After`. Privileged bots are never limited;
+/// each of their calls is written to `audit_log` instead.
 ///
 /// # Requirements
 /// - 7.1: Create message from bot via sendMessage
@@ -14,37 +25,136 @@
 /// - 7.6: Return forbidden error if not subscribed
 /// - 2.1, 2.2, 2.3, 2.4: Webhook management
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
     routing::{get, post},
-    Json, Router,
+    Extension, Json, Router,
 };
 use std::sync::Arc;
+use uuid::Uuid;
 
 use crate::{
     error::{AppError, AppResult},
     models::{
-        Bot, BotApiResponse, BotMeResponse, BotSendMessageRequest, MessageResponse,
+        AnswerCallbackQueryRequest, Bot, BotApiResponse, BotBulkSendResult,
+        BotEditMessageTextRequest, BotMeResponse, BotMenuCommand, BotSendBulkRequest,
+        BotSendMessageRequest, InlineButton, MessageResponse, SetMyCommandsRequest,
         SetWebhookRequest,
     },
     services::{
         bot_engine::{
-            dispatcher::{BotDispatcher, CommandContext},
-            BotEngineService, PermissionChecker, RateLimitResult, SCOPE_SEND_MESSAGE,
+            dispatcher::CommandContext,
+            formatting::{self, FormattedText, FormattingError},
+            inline_keyboard, BotEngineService, CallbackQueryService, InlineKeyboardStore,
+            KeyboardError, PermissionChecker, RateLimitResult, MAX_CALLBACK_ANSWER_CHARS,
+            SCOPE_SEND_MESSAGE,
         },
-        ChatService, MessageService, WebSocketService,
+        request_id, ChatService, MessageProcessor, MessageService, WebSocketService,
     },
     AppState,
 };
 
 /// Create the bot API router.
 /// Routes are mounted at /bot:token/* pattern.
-pub fn bot_api_routes() -> Router<Arc<AppState>> {
+pub fn bot_api_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
     Router::new()
         .route("/bot:token/sendMessage", post(send_message))
+        .route("/bot:token/sendBulk", post(send_bulk))
+        .route("/bot:token/editMessageText", post(edit_message_text))
+        .route("/bot:token/answerCallbackQuery", post(answer_callback_query))
         .route("/bot:token/setWebhook", post(set_webhook))
         .route("/bot:token/getMe", get(get_me))
+        .route("/bot:token/getMyCommands", get(get_my_commands))
+        .route("/bot:token/setMyCommands", post(set_my_commands))
+        .route_layer(middleware::from_fn_with_state(state.clone(), rate_limit))
+        .route_layer(middleware::from_fn_with_state(state, super::reject_writes_in_maintenance))
+}
+
+const RATE_LIMIT_LIMIT: &str = "x-ratelimit-limit";
+const RATE_LIMIT_REMAINING: &str = "x-ratelimit-remaining";
+const RATE_LIMIT_RESET: &str = "x-ratelimit-reset";
+
+/// Charge one request to the bot's rate limit and report the budget in the
+/// response headers (Requirements 8.1-8.4)
+///
+/// The check result is put in the request extensions. A handler that charges
+/// more (sendBulk) puts its own result in the response extensions, which then
+/// takes precedence. Unknown tokens are left to the handler to reject.
+async fn rate_limit(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(ref rate_limiter) = state.rate_limiter else {
+        return next.run(request).await;
+    };
+    let Ok(bot) = extract_bot_from_token(&state, &token).await else {
+        return next.run(request).await;
+    };
+
+    if bot.privileged {
+        let endpoint = request.uri().path().rsplit('/').next().unwrap_or_default().to_string();
+        let db = state.db.clone();
+        tokio::spawn(async move {
+            if let Err(e) = BotEngineService::audit_privileged_request(&db, bot.id, &endpoint).await {
+                tracing::warn!("Failed to audit privileged bot {}: {}", bot.id, e);
+            }
+        });
+    }
+
+    let result = match rate_limiter.check_bot(&bot, 1).await {
+        Ok(result) => result,
+        Err(e) => {
+            tracing::warn!("Rate limit check failed: {}. Allowing request.", e);
+            return next.run(request).await;
+        }
+    };
+
+    let mut response = if let RateLimitResult::Exceeded { retry_after } = result {
+        tracing::debug!(
+            "Bot {} rate limited, retry after {} seconds",
+            bot.id,
+            retry_after
+        );
+        Json(BotApiResponse::<()>::rate_limited(retry_after)).into_response()
+    } else {
+        request.extensions_mut().insert(result.clone());
+        next.run(request).await
+    };
+
+    let result = response
+        .extensions_mut()
+        .remove::<RateLimitResult>()
+        .unwrap_or(result);
+    if matches!(result, RateLimitResult::Exceeded { .. }) {
+        *response.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+    }
+    response.headers_mut().extend(rate_limit_headers(
+        rate_limiter.requests_per_minute(),
+        &result,
+    ));
+    response
+}
+
+/// `X-RateLimit-*` headers for a check result, plus `Retry-After` if it was
+/// rejected
+fn rate_limit_headers(limit: u32, result: &RateLimitResult) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(RATE_LIMIT_LIMIT, HeaderValue::from(limit));
+    headers.insert(RATE_LIMIT_REMAINING, HeaderValue::from(result.remaining()));
+    headers.insert(RATE_LIMIT_RESET, HeaderValue::from(result.reset_after()));
+    if let RateLimitResult::Exceeded { retry_after } = result {
+        headers.insert(header::RETRY_AFTER, HeaderValue::from(*retry_after));
+    }
+    headers
 }
 
+/// Most chats a single sendBulk call may target
+pub const MAX_BULK_CHATS: usize = 50;
+
 /// Extract and validate bot token from URL path.
 ///
 /// # Arguments
@@ -85,16 +195,20 @@ async fn extract_bot_from_token(state: &AppState, token: &str) -> AppResult<Bot>
 ///   "chat_id": "uuid",
 ///   "text": "message text",
 ///   "replyToId": "uuid" (optional),
-///   "inlineKeyboard": [[{"text": "btn", "callbackData": "data"}]] (optional)
+///   "inlineKeyboard": [[{"text": "btn", "callbackData": "data"}]] (optional),
+///   "parseMode": "markdown" | "html" (optional)
 /// }
 /// ```
 ///
+/// With a parse mode the markup is converted to plain text plus `entities`;
+/// markup that does not parse is rejected with 400.
+///
 /// # Requirements
 /// - 7.1: Create message from bot
 /// - 7.3: Mark sender as Bot(bot_id)
 /// - 7.4: Push message via WebSocket
 /// - 7.6: Return forbidden error if not subscribed
-/// - 8.1-8.4: Rate limiting
+/// - 8.1-8.4: Rate limiting (see `rate_limit`)
 async fn send_message(
     State(state): State<Arc<AppState>>,
     Path(token): Path<String>,
@@ -115,27 +229,6 @@ async fn send_message(
         tracing::debug!("Bot {} is not active", bot.id);
         return Ok(Json(BotApiResponse::error(403, "Bot is not active")));
     }
-    tracing::debug!("Bot {} is active, checking rate limit...", bot.id);
-
-    // 3. Check rate limit (Requirements 8.1-8.4)
-    if let Some(ref rate_limiter) = state.rate_limiter {
-        match rate_limiter.check_rate_limit(bot.id).await {
-            Ok(RateLimitResult::Exceeded { retry_after }) => {
-                tracing::debug!(
-                    "Bot {} rate limited, retry after {} seconds",
-                    bot.id,
-                    retry_after
-                );
-                return Ok(Json(BotApiResponse::rate_limited(retry_after)));
-            }
-            Ok(RateLimitResult::Allowed { remaining: _ }) => {
-                tracing::debug!("Bot {} rate limit OK", bot.id);
-            }
-            Err(e) => {
-                tracing::warn!("Rate limit check failed: {}. Allowing request.", e);
-            }
-        }
-    }
 
     tracing::debug!(
         "Checking chat subscription for bot {} in chat {}...",
@@ -178,13 +271,71 @@ async fn send_message(
         bot.id
     );
 
-    // 6. Create message with Bot sender using MessageService
+    let formatted = match formatting::parse(&body.text, body.parse_mode) {
+        Ok(formatted) => formatted,
+        Err(e) => return Ok(Json(parse_error_response(&e))),
+    };
+    check_message_length(&state, &formatted)?;
+    if let Err(e) = check_keyboard(body.inline_keyboard.as_deref()) {
+        return Ok(Json(keyboard_error_response(&e)));
+    }
+
+    // 6-8. Create, broadcast and dispatch the message
+    let message = deliver_bot_message(
+        &state,
+        &bot,
+        body.chat_id,
+        &formatted,
+        body.reply_to_id,
+        body.inline_keyboard.clone(),
+    )
+    .await?;
+
+    Ok(Json(BotApiResponse::success(message)))
+}
+
+/// Reject markup that does not parse for the requested parse mode
+fn parse_error_response<T>(e: &FormattingError) -> BotApiResponse<T> {
+    BotApiResponse::error(400, &format!("Can't parse entities: {}", e))
+}
+
+/// Check an inline keyboard against the size limits before anything is sent
+fn check_keyboard(keyboard: Option<&[Vec<InlineButton>]>) -> Result<(), KeyboardError> {
+    keyboard.map_or(Ok(()), inline_keyboard::validate)
+}
+
+/// Reject keyboards over the limits or with malformed buttons
+fn keyboard_error_response<T>(e: &KeyboardError) -> BotApiResponse<T> {
+    BotApiResponse::error(400, &format!("Invalid inline keyboard: {}", e))
+}
+
+/// Bot messages are held to the same `MAX_MESSAGE_BYTES` as user messages
+fn check_message_length(state: &AppState, formatted: &FormattedText) -> AppResult<()> {
+    let max_bytes = state.config.max_message_bytes;
+    if formatted.text.len() > max_bytes {
+        return Err(AppError::MessageTooLong(max_bytes));
+    }
+    Ok(())
+}
+
+/// Create a bot message in a chat, push it to users and dispatch it to
+/// other bots in the chat. Callers check subscription and permissions.
+async fn deliver_bot_message(
+    state: &AppState,
+    bot: &Bot,
+    chat_id: Uuid,
+    formatted: &FormattedText,
+    reply_to_id: Option<Uuid>,
+    inline_keyboard: Option<Vec<Vec<InlineButton>>>,
+) -> AppResult<MessageResponse> {
+    // Create message with Bot sender using MessageService
     let mut message = MessageService::send_bot_message(
         &state.db,
-        body.chat_id,
+        chat_id,
         bot.id,
-        body.text.clone(),
-        body.reply_to_id,
+        formatted.text.clone(),
+        reply_to_id,
+        &formatted.entities,
     )
     .await
     .map_err(|e| {
@@ -193,11 +344,14 @@ async fn send_message(
     })?;
     tracing::debug!("Bot message created with id {}", message.id);
 
-    // Add inline keyboard if provided
-    message.inline_keyboard = body.inline_keyboard.clone();
+    // Store the inline keyboard with the message; callers validated it
+    if let Some(keyboard) = inline_keyboard.filter(|k| !k.is_empty()) {
+        InlineKeyboardStore::save(&state.db, message.id, &keyboard).await?;
+        message.inline_keyboard = Some(keyboard);
+    }
 
-    // 7. Broadcast via WebSocket to users
-    let participant_ids = ChatService::get_participant_ids(&state.db, body.chat_id).await?;
+    // Broadcast via WebSocket to users
+    let participant_ids = ChatService::get_participant_ids(&state.db, chat_id).await?;
     WebSocketService::broadcast_new_message(
         &state.ws_manager,
         message.clone(),
@@ -206,32 +360,265 @@ async fn send_message(
     )
     .await;
 
-    // 8. Dispatch to other bots in the chat (bot-to-bot messaging)
-    // Get all bots subscribed to this chat, excluding the sender bot to prevent loops
-    let other_bots = BotEngineService::get_chat_bots(&state.db, body.chat_id)
-        .await?
-        .into_iter()
-        .filter(|b| b.id != bot.id) // Anti-loop: exclude sender bot
-        .collect::<Vec<_>>();
-
-    if !other_bots.is_empty() {
-        let ctx = CommandContext {
-            user_id: bot.id, // Sender is a bot
-            sender_username: bot.username.clone().or_else(|| Some(bot.name.clone())),
-            chat_id: body.chat_id,
-            message_id: message.id,
-            text: body.text.clone(),
-        };
+    // Dispatch to other bots in the chat (bot-to-bot messaging)
+    // Only opted-in bots receive it; repeated bounces are suppressed to break loops
+    let ctx = CommandContext {
+        user_id: bot.id, // Sender is a bot
+        sender_username: bot.username.clone().or_else(|| Some(bot.name.clone())),
+        chat_id,
+        message_id: message.id,
+        text: formatted.text.clone(),
+        sender_bot_id: Some(bot.id),
+        request_id: request_id::current(),
+        command_aliases: Default::default(),
+    };
+
+    if let Err(e) =
+        MessageProcessor::dispatch_bot_message(&state.db, &state.bot_dispatcher, &ctx).await
+    {
+        tracing::warn!("Failed to dispatch bot message to other bots: {}", e);
+    }
+
+    Ok(message)
+}
+
+/// Send one message to several chats.
+///
+/// POST /bot:token/sendBulk
+///
+/// # Request Body
+/// ```json
+/// {
+///   "chat_ids": ["uuid", "uuid"],
+///   "text": "announcement",
+///   "inlineKeyboard": [[{"text": "btn", "callbackData": "data"}]] (optional),
+///   "parseMode": "markdown" | "html" (optional)
+/// }
+/// ```
+///
+/// The whole batch costs one rate-limit request per chat and is rejected
+/// up front if it does not fit in the remaining budget. Chats the bot is
+/// not subscribed to are reported as failed without stopping the batch.
+async fn send_bulk(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+    Json(body): Json<BotSendBulkRequest>,
+) -> AppResult<Response> {
+    let bot = extract_bot_from_token(&state, &token).await?;
+
+    if !bot.is_active {
+        return Ok(Json(BotApiResponse::<()>::error(403, "Bot is not active")).into_response());
+    }
+
+    let chat_ids = dedup_chat_ids(body.chat_ids);
+    if chat_ids.is_empty() {
+        return Ok(Json(BotApiResponse::<()>::error(
+            400,
+            "chat_ids must not be empty",
+        ))
+        .into_response());
+    }
+    if chat_ids.len() > MAX_BULK_CHATS {
+        return Ok(Json(BotApiResponse::<()>::error(
+            400,
+            &format!("At most {} chats per sendBulk call", MAX_BULK_CHATS),
+        ))
+        .into_response());
+    }
 
-        let dispatcher = BotDispatcher::new(state.ws_manager.clone());
-        if let Err(e) = dispatcher.dispatch(&ctx, other_bots).await {
-            tracing::warn!("Failed to dispatch bot message to other bots: {}", e);
+    let has_permission =
+        PermissionChecker::check_scope(&state.db, bot.id, SCOPE_SEND_MESSAGE).await?;
+    if !has_permission {
+        return Ok(Json(BotApiResponse::<()>::error(
+            403,
+            "Permission denied: missing send_message scope",
+        ))
+        .into_response());
+    }
+
+    let formatted = match formatting::parse(&body.text, body.parse_mode) {
+        Ok(formatted) => formatted,
+        Err(e) => return Ok(Json(parse_error_response::<()>(&e)).into_response()),
+    };
+    check_message_length(&state, &formatted)?;
+    if let Err(e) = check_keyboard(body.inline_keyboard.as_deref()) {
+        return Ok(Json(keyboard_error_response::<()>(&e)).into_response());
+    }
+
+    // Consume one request per chat, all or nothing; the middleware already
+    // charged the first one
+    let mut charged = None;
+    if let Some(ref rate_limiter) = state.rate_limiter {
+        let extra = chat_ids.len() as u32 - 1;
+        if extra > 0 {
+            match rate_limiter.check_bot(&bot, extra).await {
+                Ok(RateLimitResult::Exceeded { retry_after }) => {
+                    return Ok((
+                        Extension(RateLimitResult::Exceeded { retry_after }),
+                        Json(BotApiResponse::<()>::rate_limited(retry_after)),
+                    )
+                        .into_response());
+                }
+                Ok(result) => charged = Some(Extension(result)),
+                Err(e) => {
+                    tracing::warn!("Rate limit check failed: {}. Allowing request.", e);
+                }
+            }
         }
     }
 
+    tracing::info!(
+        "Bot {} ({}) sending bulk message to {} chats",
+        bot.name,
+        bot.id,
+        chat_ids.len()
+    );
+
+    let mut results = Vec::with_capacity(chat_ids.len());
+    for chat_id in chat_ids {
+        let is_subscribed =
+            PermissionChecker::check_chat_subscription(&state.db, bot.id, chat_id).await?;
+        if !is_subscribed {
+            results.push(BotBulkSendResult::failed(chat_id, "Bot not subscribed to chat"));
+            continue;
+        }
+
+        let result = deliver_bot_message(
+            &state,
+            &bot,
+            chat_id,
+            &formatted,
+            None,
+            body.inline_keyboard.clone(),
+        )
+        .await;
+        results.push(match result {
+            Ok(message) => BotBulkSendResult::sent(chat_id, message.id),
+            Err(e) => BotBulkSendResult::failed(chat_id, e.to_string()),
+        });
+    }
+
+    Ok((charged, Json(BotApiResponse::success(results))).into_response())
+}
+
+/// Edit the text of one of the bot's own messages.
+///
+/// POST /bot:token/editMessageText
+///
+/// # Request Body
+/// ```json
+/// {
+///   "chat_id": "uuid",
+///   "message_id": "uuid",
+///   "text": "new text",
+///   "inlineKeyboard": [[{"text": "btn", "callbackData": "data"}]] (optional),
+///   "parseMode": "markdown" | "html" (optional)
+/// }
+/// ```
+///
+/// A given keyboard replaces the message's keyboard, an empty one removes
+/// it; without one the keyboard is kept. Chat participants get the edited
+/// message as `message_updated`.
+async fn edit_message_text(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+    Json(body): Json<BotEditMessageTextRequest>,
+) -> AppResult<Json<BotApiResponse<MessageResponse>>> {
+    let bot = extract_bot_from_token(&state, &token).await?;
+
+    if !bot.is_active {
+        return Ok(Json(BotApiResponse::error(403, "Bot is not active")));
+    }
+
+    let formatted = match formatting::parse(&body.text, body.parse_mode) {
+        Ok(formatted) => formatted,
+        Err(e) => return Ok(Json(parse_error_response(&e))),
+    };
+    check_message_length(&state, &formatted)?;
+    if let Err(e) = check_keyboard(body.inline_keyboard.as_deref()) {
+        return Ok(Json(keyboard_error_response(&e)));
+    }
+
+    let mut message = MessageService::edit_bot_message(
+        &state.db,
+        body.chat_id,
+        body.message_id,
+        bot.id,
+        &formatted.text,
+        &formatted.entities,
+    )
+    .await?;
+
+    if let Some(keyboard) = body.inline_keyboard {
+        InlineKeyboardStore::save(&state.db, message.id, &keyboard).await?;
+        message.inline_keyboard = (!keyboard.is_empty()).then_some(keyboard);
+    }
+
+    let participant_ids = ChatService::get_participant_ids(&state.db, body.chat_id).await?;
+    WebSocketService::broadcast_message_updated(
+        &state.ws_manager,
+        message.clone(),
+        &participant_ids,
+        bot.id,
+    )
+    .await;
+
     Ok(Json(BotApiResponse::success(message)))
 }
 
+/// Answer a press of one of the bot's inline keyboard buttons.
+///
+/// POST /bot:token/answerCallbackQuery
+///
+/// # Request Body
+/// ```json
+/// {
+///   "callbackQueryId": "uuid",
+///   "text": "Vote counted" (optional),
+///   "showAlert": false (optional)
+/// }
+/// ```
+///
+/// Each query can be answered once, within `CALLBACK_ANSWER_WINDOW_SECONDS`
+/// of the press. The user who pressed the button gets a `callback_answer`
+/// event; clients show `text` as a toast, or as an alert with `showAlert`.
+async fn answer_callback_query(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+    Json(body): Json<AnswerCallbackQueryRequest>,
+) -> AppResult<Json<BotApiResponse<bool>>> {
+    let bot = extract_bot_from_token(&state, &token).await?;
+
+    let text = body.text.filter(|t| !t.trim().is_empty());
+    if text
+        .as_ref()
+        .is_some_and(|t| t.chars().count() > MAX_CALLBACK_ANSWER_CHARS)
+    {
+        return Ok(Json(BotApiResponse::error(
+            400,
+            &format!("text must be at most {} characters", MAX_CALLBACK_ANSWER_CHARS),
+        )));
+    }
+
+    let query =
+        match CallbackQueryService::answer(&state.db, bot.id, body.callback_query_id).await {
+            Ok(query) => query,
+            Err(AppError::NotFound(msg)) => return Ok(Json(BotApiResponse::error(404, &msg))),
+            Err(e) => return Err(e),
+        };
+
+    WebSocketService::send_callback_answer(&state.ws_manager, &query, text, body.show_alert)
+        .await;
+
+    Ok(Json(BotApiResponse::success(true)))
+}
+
+/// Drop repeated chat ids, keeping the first occurrence's position
+fn dedup_chat_ids(chat_ids: Vec<Uuid>) -> Vec<Uuid> {
+    let mut seen = std::collections::HashSet::new();
+    chat_ids.into_iter().filter(|id| seen.insert(*id)).collect()
+}
+
 /// Set webhook URL for the bot.
 ///
 /// POST /bot:token/setWebhook
@@ -239,7 +626,10 @@ async fn send_message(
 /// # Request Body
 /// ```json
 /// {
-///   "url": "https://example.com/webhook" (or null to clear)
+///   "url": "https://example.com/webhook" (or null to clear),
+///   "secretToken": "sent back as X-Giano-Webhook-Secret" (optional),
+///   "maxConnections": 40 (optional, 1-100),
+///   "allowedUpdates": ["message", "reaction", "member_joined"] (optional)
 /// }
 /// ```
 ///
@@ -280,14 +670,21 @@ async fn set_webhook(
         }
     }
 
-    // 3. Update webhook URL
-    let webhook_url = body.url.filter(|u| !u.is_empty());
-
-    sqlx::query("UPDATE bots SET webhook_url = $1, updated_at = NOW() WHERE id = $2")
-        .bind(&webhook_url)
-        .bind(bot.id)
-        .execute(&state.db.pool)
-        .await?;
+    // 3. Update webhook URL and delivery options
+    match body.url.as_deref().filter(|u| !u.is_empty()) {
+        Some(url) => {
+            match BotEngineService::save_webhook(&state.db, bot.id, url, &body.options).await {
+                Ok(_) => {}
+                Err(AppError::BadRequest(msg)) => {
+                    return Ok(Json(BotApiResponse::error(400, &msg)));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        None => {
+            BotEngineService::clear_webhook(&state.db, bot.id).await?;
+        }
+    }
 
     Ok(Json(BotApiResponse::success(true)))
 }
@@ -311,8 +708,84 @@ async fn get_me(
     Ok(Json(BotApiResponse::success(BotMeResponse::from(bot))))
 }
 
+/// Get the commands the bot advertises to users.
+///
+/// GET /bot:token/getMyCommands
+async fn get_my_commands(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> AppResult<Json<BotApiResponse<Vec<BotMenuCommand>>>> {
+    let bot = extract_bot_from_token(&state, &token).await?;
+
+    let commands = BotEngineService::get_menu_commands(&state.db, bot.id).await?;
+    Ok(Json(BotApiResponse::success(commands)))
+}
+
+/// Replace the commands the bot advertises to users. They show up in the
+/// command menu and autocomplete of every chat the bot is in.
+///
+/// POST /bot:token/setMyCommands
+///
+/// # Request Body
+/// ```json
+/// {
+///   "commands": [{ "command": "start", "description": "Start the bot" }]
+/// }
+/// ```
+///
+/// Command names are 1-32 lowercase letters, digits or underscores;
+/// descriptions 1-256 characters. An empty list clears the menu. Returns
+/// the commands as stored.
+async fn set_my_commands(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+    Json(body): Json<SetMyCommandsRequest>,
+) -> AppResult<Json<BotApiResponse<Vec<BotMenuCommand>>>> {
+    let bot = extract_bot_from_token(&state, &token).await?;
+
+    match BotEngineService::set_menu_commands(&state.db, bot.id, body.commands).await {
+        Ok(commands) => Ok(Json(BotApiResponse::success(commands))),
+        Err(AppError::BadRequest(msg)) => Ok(Json(BotApiResponse::error(400, &msg))),
+        Err(e) => Err(e),
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dedup_chat_ids_keeps_order() {
+        let a = Uuid::new_v4();
+        let b = Uuid::new_v4();
+        assert_eq!(dedup_chat_ids(vec![a, b, a, b, a]), vec![a, b]);
+        assert!(dedup_chat_ids(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn test_rate_limit_headers_when_allowed() {
+        let headers = rate_limit_headers(
+            60,
+            &RateLimitResult::Allowed {
+                remaining: 59,
+                reset_after: 45,
+            },
+        );
+        assert_eq!(headers[RATE_LIMIT_LIMIT], "60");
+        assert_eq!(headers[RATE_LIMIT_REMAINING], "59");
+        assert_eq!(headers[RATE_LIMIT_RESET], "45");
+        assert!(!headers.contains_key(header::RETRY_AFTER));
+    }
+
+    #[test]
+    fn test_rate_limit_headers_when_exceeded() {
+        let headers = rate_limit_headers(60, &RateLimitResult::Exceeded { retry_after: 12 });
+        assert_eq!(headers[RATE_LIMIT_LIMIT], "60");
+        assert_eq!(headers[RATE_LIMIT_REMAINING], "0");
+        assert_eq!(headers[RATE_LIMIT_RESET], "12");
+        assert_eq!(headers[header::RETRY_AFTER], "12");
+    }
+
     #[test]
     fn test_token_extraction_removes_colon() {
         // Test that colon prefix is properly handled
@@ -2,10 +2,14 @@
 ///
 /// This module provides:
 /// - POST /api/v1/bots - Create a new bot
-/// - GET /api/v1/bots - List user's bots
+/// - GET /api/v1/bots - List user's bots (paginated, filterable)
 /// - GET /api/v1/bots/search - Search bot by username
 /// - GET /api/v1/bots/:id - Get bot details
 /// - DELETE /api/v1/bots/:id - Delete a bot
+/// - PUT /api/v1/bots/:bot_id/permissions - Replace a bot's permission scopes
+/// - GET /api/v1/bots/:bot_id/stats - Usage stats for the bot's owner
+/// - GET /api/v1/bots/:bot_id/status - Whether the bot is connected over WebSocket
+/// - POST /api/v1/bots/:bot_id/webhook/test - Send a sample update to the bot's webhook
 /// - POST /api/v1/bots/:bot_id/callback - Handle inline button callback
 ///
 /// # Requirements
@@ -14,18 +18,24 @@
 use axum::{
     extract::{Path, Query, State},
     http::HeaderMap,
-    routing::{get, post},
+    routing::{get, post, put},
     Json, Router,
 };
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::{
-    error::AppResult,
-    models::{BotPublicResponse, BotResponse, CreateBotRequest, MessageResponse},
+    error::{AppError, AppResult},
+    models::{
+        BotPublicResponse, BotResponse, CreateBotRequest, MessageResponse, WebhookTestResult,
+    },
     routes::auth::get_current_user_id,
-    services::{bot_engine::BotEngineService, BotService, ChatService, WebSocketService},
+    services::{
+        bot_engine::{stats, BotEngineService, BotListFilter, BotStats, MAX_BOT_LIST_LIMIT},
+        BotService, ChatService, WebSocketService,
+    },
     AppState,
 };
 
@@ -38,7 +48,11 @@ pub fn routes() -> Router<Arc<AppState>> {
     // Parameterized routes
     let param_routes = Router::new()
         .route("/:bot_id", get(get_bot).delete(delete_bot))
-        .route("/:bot_id/callback", post(handle_callback));
+        .route("/:bot_id/callback", post(handle_callback))
+        .route("/:bot_id/permissions", put(set_bot_permissions))
+        .route("/:bot_id/stats", get(get_bot_stats))
+        .route("/:bot_id/status", get(get_bot_status))
+        .route("/:bot_id/webhook/test", post(test_webhook));
 
     // Root route + merge static first, then parameterized
     Router::new()
@@ -47,6 +61,14 @@ pub fn routes() -> Router<Arc<AppState>> {
         .merge(param_routes)
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ListBotsQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    active: Option<bool>,
+    q: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct AvailableBotsQuery {
     limit: Option<i64>,
@@ -76,10 +98,14 @@ pub struct BotResponseWrapper {
     bot: BotResponse,
 }
 
-/// Response wrapper for a list of bots
+/// Response wrapper for a page of bots
 #[derive(Debug, Serialize)]
 pub struct BotsListResponse {
     bots: Vec<BotResponse>,
+    /// Bots matching the filters across all pages
+    total: i64,
+    limit: i64,
+    offset: i64,
 }
 
 /// Simple message response
@@ -127,23 +153,39 @@ async fn create_bot(
     Ok(Json(BotResponseWrapper { bot }))
 }
 
-/// List all bots owned by the current user.
+/// List bots owned by the current user.
+///
+/// GET /api/v1/bots?limit=50&offset=0&active=true&q=weather
 ///
-/// GET /api/v1/bots
+/// # Query Parameters
+/// - `limit`: Optional, page size (default: 50, max: 100)
+/// - `offset`: Optional, number of bots to skip (default: 0)
+/// - `active`: Optional, only active (`true`) or inactive (`false`) bots
+/// - `q`: Optional, case-insensitive substring of the bot name
 ///
 /// # Returns
-/// List of bots owned by the authenticated user.
+/// One page of the user's bots, newest first, with the total match count.
 async fn list_bots(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
+    Query(query): Query<ListBotsQuery>,
 ) -> AppResult<Json<BotsListResponse>> {
     let user_id = get_current_user_id(&state, &headers).await?;
 
-    let bots = BotEngineService::get_bots_by_owner(&state.db, user_id).await?;
+    let filter = BotListFilter {
+        active: query.active,
+        query: query.q,
+        limit: query.limit.unwrap_or(50).clamp(1, MAX_BOT_LIST_LIMIT),
+        offset: query.offset.unwrap_or(0).max(0),
+    };
+    let (bots, total) = BotEngineService::list_bots_by_owner(&state.db, user_id, &filter).await?;
     let bot_responses: Vec<BotResponse> = bots.into_iter().map(BotResponse::from).collect();
 
     Ok(Json(BotsListResponse {
         bots: bot_responses,
+        total,
+        limit: filter.limit,
+        offset: filter.offset,
     }))
 }
 
@@ -225,6 +267,154 @@ async fn delete_bot(
     }))
 }
 
+/// Request to replace a bot's permissions: either a template or explicit scopes
+#[derive(Debug, Deserialize)]
+pub struct SetPermissionsRequest {
+    template: Option<String>,
+    scopes: Option<Vec<String>>,
+}
+
+/// Effective scopes after a permission change
+#[derive(Debug, Serialize)]
+pub struct PermissionsResponse {
+    scopes: Vec<String>,
+}
+
+/// Replace a bot's permission scopes.
+///
+/// PUT /api/v1/bots/:bot_id/permissions
+///
+/// # Request Body
+/// ```json
+/// { "template": "moderator" }
+/// ```
+/// or
+/// ```json
+/// { "scopes": ["send_message", "read_message"] }
+/// ```
+async fn set_bot_permissions(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(bot_id): Path<Uuid>,
+    Json(req): Json<SetPermissionsRequest>,
+) -> AppResult<Json<PermissionsResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let bot = BotEngineService::get_bot_by_id(&state.db, bot_id).await?;
+    if bot.owner_id != user_id {
+        return Err(AppError::AccessDenied);
+    }
+
+    let scopes = match (req.template, req.scopes) {
+        (Some(template), None) => {
+            BotEngineService::apply_permission_template(&state.db, bot_id, &template).await?
+        }
+        (None, Some(scopes)) => BotEngineService::set_permissions(&state.db, bot_id, &scopes).await?,
+        _ => {
+            return Err(AppError::BadRequest(
+                "Provide exactly one of 'template' or 'scopes'".to_string(),
+            ))
+        }
+    };
+
+    Ok(Json(PermissionsResponse { scopes }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BotStatsQuery {
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BotStatsResponse {
+    stats: BotStats,
+}
+
+/// Usage stats of a bot, bucketed per hour (ranges up to 48h) or per day.
+///
+/// GET /api/v1/bots/:bot_id/stats?from=&to=
+///
+/// `from` and `to` are RFC 3339 timestamps; the default range is the last
+/// 7 days. Owner only.
+async fn get_bot_stats(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(bot_id): Path<Uuid>,
+    Query(query): Query<BotStatsQuery>,
+) -> AppResult<Json<BotStatsResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let bot = BotEngineService::get_bot_by_id(&state.db, bot_id).await?;
+    if bot.owner_id != user_id {
+        return Err(AppError::AccessDenied);
+    }
+
+    let stats = stats::query(&state.db, bot_id, query.from, query.to).await?;
+    Ok(Json(BotStatsResponse { stats }))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BotStatusResponse {
+    connected: bool,
+    last_seen: Option<DateTime<Utc>>,
+}
+
+/// Whether the bot has an active WebSocket connection and when it was last
+/// seen; live changes arrive as `bot_status` events.
+///
+/// GET /api/v1/bots/:bot_id/status
+///
+/// Owner only. `lastSeen` is null if the bot hasn't connected since the
+/// server started.
+async fn get_bot_status(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(bot_id): Path<Uuid>,
+) -> AppResult<Json<BotStatusResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let bot = BotEngineService::get_bot_by_id(&state.db, bot_id).await?;
+    if bot.owner_id != user_id {
+        return Err(AppError::AccessDenied);
+    }
+
+    let status = state.ws_manager.bot_status(bot_id).await;
+    Ok(Json(BotStatusResponse {
+        connected: status.connected,
+        last_seen: status.last_seen,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookTestResponse {
+    result: WebhookTestResult,
+}
+
+/// Send a sample update, flagged `is_test`, to the bot's webhook and report
+/// how the receiver answered.
+///
+/// POST /api/v1/bots/:bot_id/webhook/test
+///
+/// Owner only. Nothing is stored; an unreachable receiver is reported in
+/// `result.error` rather than as a failed request.
+async fn test_webhook(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(bot_id): Path<Uuid>,
+) -> AppResult<Json<WebhookTestResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let bot = BotEngineService::get_bot_by_id(&state.db, bot_id).await?;
+    if bot.owner_id != user_id {
+        return Err(AppError::AccessDenied);
+    }
+
+    let result = BotEngineService::test_webhook_delivery(&bot, user_id).await?;
+    Ok(Json(WebhookTestResponse { result }))
+}
+
 /// Handle inline button callback from bot
 /// POST /bots/:botId/callback
 async fn handle_callback(
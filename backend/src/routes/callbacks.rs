@@ -0,0 +1,55 @@
+/// Callback Routes - inline keyboard button presses.
+///
+/// This module provides:
+/// - POST /api/v1/callback - Press a `callbackData` button on a bot message
+///
+/// The press is forwarded to the bot as a `callback_query` update. The bot's
+/// answer (answerCallbackQuery) reaches the user as a `callback_answer`
+/// WebSocket event; an edit of the message arrives as `message_updated`.
+use axum::{extract::State, http::HeaderMap, routing::post, Json, Router};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    error::AppResult, routes::auth::get_current_user_id, services::MessageProcessor, AppState,
+};
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route("/", post(press_button))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CallbackRequest {
+    #[serde(rename = "messageId")]
+    message_id: Uuid,
+    #[serde(rename = "callbackData")]
+    callback_data: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CallbackResponse {
+    #[serde(rename = "callbackQueryId")]
+    callback_query_id: Uuid,
+}
+
+async fn press_button(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<CallbackRequest>,
+) -> AppResult<Json<CallbackResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let query = MessageProcessor::process_callback(
+        &state.db,
+        &state.bot_dispatcher,
+        user_id,
+        req.message_id,
+        &req.callback_data,
+    )
+    .await?;
+
+    Ok(Json(CallbackResponse {
+        callback_query_id: query.id,
+    }))
+}
@@ -0,0 +1,76 @@
+use crate::error::{AppError, AppResult};
+use crate::models::CallLogResponse;
+use crate::routes::auth::get_current_user_id;
+use crate::services::turn::{TurnConfig, TurnCredentials, TurnService};
+use crate::services::CallLogService;
+use crate::AppState;
+use axum::{
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    response::Json,
+    routing::{get, post},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/history", get(get_call_history))
+        .route("/:call_id/ice", post(issue_ice_credentials))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CallHistoryQuery {
+    limit: Option<i64>,
+    before: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CallHistoryResponse {
+    calls: Vec<CallLogResponse>,
+    #[serde(rename = "hasMore")]
+    has_more: bool,
+}
+
+/// Calls the current user placed or received, newest first
+///
+/// GET /api/v1/calls/history?limit=50&before=call_id
+async fn get_call_history(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<CallHistoryQuery>,
+) -> AppResult<Json<CallHistoryResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+    let limit = query.limit.unwrap_or(50).clamp(1, 100);
+
+    let (calls, has_more) =
+        CallLogService::get_history(&state.db, user_id, limit, query.before).await?;
+
+    Ok(Json(CallHistoryResponse { calls, has_more }))
+}
+
+/// Issue short-lived TURN credentials for an ongoing call
+///
+/// Only the caller and callee of `call_id` may fetch credentials.
+async fn issue_ice_credentials(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(call_id): Path<Uuid>,
+) -> AppResult<Json<TurnCredentials>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let session = state
+        .ws_manager
+        .get_call_session(call_id)
+        .await
+        .ok_or_else(|| AppError::NotFound("Call not found".to_string()))?;
+    if session.caller_id != user_id && session.callee_id != user_id {
+        return Err(AppError::AccessDenied);
+    }
+
+    let config = TurnConfig::from(&state.config);
+    let credentials = TurnService::issue_credentials(&config, user_id, chrono::Utc::now())?;
+    Ok(Json(credentials))
+}
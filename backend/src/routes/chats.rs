@@ -1,6 +1,6 @@
 use axum::{
-    extract::{Path, Query, State},
-    http::HeaderMap,
+    extract::{Multipart, Path, Query, State},
+    http::{HeaderMap, StatusCode},
     routing::{get, post},
     Json, Router,
 };
@@ -9,13 +9,24 @@ use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::{
-    error::AppResult,
-    models::{BotPublicResponse, ChatDetailResponse, ChatResponse, MessageResponse},
-    routes::auth::get_current_user_id,
+    error::{AppError, AppResult},
+    models::{
+        BotPublicResponse, ChatCommandSuggestion, ChatDetailResponse, ChatResponse, ChatType,
+        ChatUserState, CustomEmojiResponse, DeliveryStatus, MessageResponse,
+        MessageSearchResponse, SeenByResponse, ThreadResponse,
+    },
+    routes::{
+        auth::get_current_user_id, exports::ExportResponseWrapper,
+        upload::read_custom_emoji_form,
+    },
     services::{
-        bot_engine::BotEngineService,
+        bot_engine::{BotEngineService, MessageProcessor},
+        content::normalize_text,
+        e2ee::{FetchedKeyBundle, KeyBundleInput},
         message::{AttachmentInput, ReplyToInput},
-        ChatService, MessageProcessor, MessageService, WebSocketService,
+        poll::normalize_poll,
+        AttachmentService, ChatService, CustomEmojiService, E2eeKeyService, ExportService,
+        MessageService, WebSocketService,
     },
     AppState,
 };
@@ -24,20 +35,49 @@ pub fn routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/", get(get_chats))
         .route("/group", post(create_group))
+        .route("/channel", post(create_channel))
         .route("/private", post(create_private_chat))
         .route("/bot", post(create_bot_chat))
-        .route("/:chat_id", get(get_chat).delete(delete_chat))
+        .route(
+            "/:chat_id",
+            get(get_chat).put(update_chat).delete(delete_chat),
+        )
+        .route("/:chat_id/restore", post(restore_chat))
         .route("/:chat_id/pin", post(pin_chat))
         .route("/:chat_id/unpin", post(unpin_chat))
+        .route("/:chat_id/state", get(get_chat_state).put(update_chat_state))
         .route("/:chat_id/read", post(mark_as_read))
+        .route("/:chat_id/slowmode", axum::routing::put(set_slow_mode))
+        .route("/:chat_id/disappearing", axum::routing::put(set_disappearing_timer))
+        .route("/:chat_id/retention", axum::routing::put(set_retention))
+        .route("/:chat_id/link-previews", axum::routing::put(set_link_previews))
+        .route("/:chat_id/e2ee", axum::routing::put(enable_e2ee))
+        .route("/:chat_id/emoji", get(list_custom_emoji).post(add_custom_emoji))
+        .route(
+            "/:chat_id/emoji/:emoji_id",
+            axum::routing::delete(delete_custom_emoji),
+        )
+        .route(
+            "/:chat_id/emoji-policy",
+            axum::routing::put(set_custom_emoji_policy),
+        )
+        .route("/:chat_id/keys", post(publish_key_bundle))
+        .route("/:chat_id/keys/:user_id", get(get_key_bundle))
         .route(
             "/:chat_id/messages",
             get(get_messages).post(send_message).delete(clear_messages),
         )
+        .route("/:chat_id/messages/search", get(search_messages))
+        .route("/:chat_id/polls", post(create_poll))
+        .route("/:chat_id/export", post(export_chat))
         .route(
             "/:chat_id/messages/:message_id",
             axum::routing::put(edit_message).delete(delete_message),
         )
+        .route(
+            "/:chat_id/messages/:message_id/thread",
+            get(get_thread).post(reply_in_thread),
+        )
         .route(
             "/:chat_id/messages/:message_id/reactions",
             post(toggle_reaction),
@@ -46,8 +86,10 @@ pub fn routes() -> Router<Arc<AppState>> {
             "/:chat_id/messages/:message_id/pin",
             post(pin_message).delete(unpin_message),
         )
+        .route("/:chat_id/messages/:message_id/seen-by", get(get_seen_by))
         // Bot-Chat management routes (Requirements 4.1, 4.2)
         .route("/:chat_id/bots", get(list_chat_bots).post(add_bot_to_chat))
+        .route("/:chat_id/commands", get(list_chat_commands))
         .route(
             "/:chat_id/bots/:bot_id",
             axum::routing::delete(remove_bot_from_chat),
@@ -57,6 +99,10 @@ pub fn routes() -> Router<Arc<AppState>> {
 #[derive(Debug, Deserialize)]
 pub struct ChatsQuery {
     search: Option<String>,
+    /// List archived chats instead of the main list
+    #[serde(default)]
+    archived: bool,
+    folder: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -71,11 +117,77 @@ async fn get_chats(
 ) -> AppResult<Json<ChatsResponse>> {
     let user_id = get_current_user_id(&state, &headers).await?;
 
-    let chats = ChatService::get_user_chats(&state.db, user_id, query.search.as_deref()).await?;
+    let chats = ChatService::get_user_chats(
+        &state.db,
+        user_id,
+        query.search.as_deref(),
+        query.archived,
+        query.folder.as_deref(),
+    )
+    .await?;
 
     Ok(Json(ChatsResponse { chats }))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct UpdateChatStateRequest {
+    #[serde(rename = "isArchived")]
+    is_archived: Option<bool>,
+    /// New folder label; an empty string removes the chat from its folder
+    folder: Option<String>,
+    /// Stop (or resume) push notifications for this chat
+    #[serde(rename = "isMuted")]
+    is_muted: Option<bool>,
+}
+
+/// GET /api/v1/chats/:chat_id/state - The caller's archive/folder/mute state of a chat
+async fn get_chat_state(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(chat_id): Path<Uuid>,
+) -> AppResult<Json<ChatUserState>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    if !ChatService::is_participant(&state.db, chat_id, user_id).await? {
+        return Err(AppError::AccessDenied);
+    }
+    let chat_state = ChatService::get_chat_state(&state.db, chat_id, user_id).await?;
+
+    Ok(Json(chat_state))
+}
+
+/// PUT /api/v1/chats/:chat_id/state - Archive, mute or file a chat under a folder
+///
+/// Private to the caller: other participants are not affected or notified.
+async fn update_chat_state(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(chat_id): Path<Uuid>,
+    Json(req): Json<UpdateChatStateRequest>,
+) -> AppResult<Json<ChatUserState>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    if !ChatService::is_participant(&state.db, chat_id, user_id).await? {
+        return Err(AppError::AccessDenied);
+    }
+    let mut chat_state = ChatService::get_chat_state(&state.db, chat_id, user_id).await?;
+    if let Some(folder) = req.folder.as_deref() {
+        chat_state = ChatService::set_chat_folder(&state.db, chat_id, user_id, Some(folder)).await?;
+    }
+    if let Some(archived) = req.is_archived {
+        chat_state = ChatService::archive_chat(&state.db, chat_id, user_id, archived).await?;
+    }
+    if let Some(muted) = req.is_muted {
+        chat_state = ChatService::mute_chat(&state.db, chat_id, user_id, muted).await?;
+    }
+
+    // Keep the user's other devices in sync
+    WebSocketService::send_chat_state_updated(&state.ws_manager, user_id, chat_id, &chat_state)
+        .await;
+
+    Ok(Json(chat_state))
+}
+
 #[derive(Debug, Serialize)]
 pub struct ChatDetailResponseWrapper {
     chat: ChatDetailResponse,
@@ -93,6 +205,43 @@ async fn get_chat(
     Ok(Json(ChatDetailResponseWrapper { chat }))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct UpdateChatRequest {
+    name: Option<String>,
+    /// Empty string clears the description
+    description: Option<String>,
+    /// Empty string clears the avatar
+    avatar: Option<String>,
+}
+
+/// PUT /api/v1/chats/:chat_id - Edit a group chat's name, description and
+/// avatar (chat admins only)
+async fn update_chat(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(chat_id): Path<Uuid>,
+    Json(req): Json<UpdateChatRequest>,
+) -> AppResult<Json<ChatDetailResponseWrapper>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let updated = ChatService::update_chat(
+        &state.db,
+        chat_id,
+        user_id,
+        req.name.as_deref(),
+        req.description.as_deref(),
+        req.avatar.as_deref(),
+    )
+    .await?;
+
+    let participant_ids = ChatService::get_participant_ids(&state.db, chat_id).await?;
+    WebSocketService::broadcast_chat_updated(&state.ws_manager, &updated, &participant_ids).await;
+
+    let chat = ChatService::get_chat_by_id(&state.db, chat_id, user_id).await?;
+
+    Ok(Json(ChatDetailResponseWrapper { chat }))
+}
+
 async fn delete_chat(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
@@ -107,10 +256,25 @@ async fn delete_chat(
     }))
 }
 
+async fn restore_chat(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(chat_id): Path<Uuid>,
+) -> AppResult<Json<ChatDetailResponseWrapper>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    ChatService::restore_chat(&state.db, chat_id, user_id, state.config.chat_recovery_days)
+        .await?;
+
+    let chat = ChatService::get_chat_by_id(&state.db, chat_id, user_id).await?;
+
+    Ok(Json(ChatDetailResponseWrapper { chat }))
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CreateGroupRequest {
     name: String,
-    #[serde(rename = "participantIds")]
+    #[serde(rename = "participantIds", default)]
     participant_ids: Vec<Uuid>,
 }
 
@@ -121,8 +285,37 @@ async fn create_group(
 ) -> AppResult<Json<ChatDetailResponseWrapper>> {
     let user_id = get_current_user_id(&state, &headers).await?;
 
-    let chat =
-        ChatService::create_group(&state.db, user_id, &req.name, req.participant_ids).await?;
+    let chat = ChatService::create_group(
+        &state.db,
+        user_id,
+        &req.name,
+        req.participant_ids,
+        ChatType::Group,
+        state.config.max_chat_participants,
+    )
+    .await?;
+
+    Ok(Json(ChatDetailResponseWrapper { chat }))
+}
+
+/// POST /api/v1/chats/channel - Create a broadcast-only channel; members
+/// read, only admins post
+async fn create_channel(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<CreateGroupRequest>,
+) -> AppResult<Json<ChatDetailResponseWrapper>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let chat = ChatService::create_group(
+        &state.db,
+        user_id,
+        &req.name,
+        req.participant_ids,
+        ChatType::Channel,
+        state.config.max_chat_participants,
+    )
+    .await?;
 
     Ok(Json(ChatDetailResponseWrapper { chat }))
 }
@@ -192,18 +385,30 @@ async fn mark_as_read(
 
     ChatService::mark_as_read(&state.db, chat_id, user_id).await?;
 
-    // Broadcast message read status to senders
-    let read_at = chrono::Utc::now();
-    for (message_id, sender_id) in unread_messages {
-        WebSocketService::broadcast_message_read(
-            &state.ws_manager,
-            chat_id,
-            message_id,
-            user_id,
-            read_at,
-            sender_id,
-        )
-        .await;
+    // Broadcast message read status to senders, unless the reader keeps
+    // read receipts private
+    if MessageService::read_receipts_enabled(&state.db, user_id).await? {
+        let read_at = chrono::Utc::now();
+        for (message_id, sender_id) in unread_messages {
+            WebSocketService::broadcast_message_read(
+                &state.ws_manager,
+                chat_id,
+                message_id,
+                user_id,
+                read_at,
+                sender_id,
+            )
+            .await;
+            WebSocketService::broadcast_message_status(
+                &state.ws_manager,
+                chat_id,
+                message_id,
+                DeliveryStatus::Read,
+                user_id,
+                sender_id,
+            )
+            .await;
+        }
     }
 
     Ok(Json(SimpleMessage {
@@ -239,7 +444,334 @@ async fn unpin_chat(
     }))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct SetSlowModeRequest {
+    seconds: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SlowModeResponse {
+    #[serde(rename = "chatId")]
+    chat_id: Uuid,
+    #[serde(rename = "slowModeSeconds")]
+    slow_mode_seconds: i32,
+}
+
+/// PUT /api/v1/chats/:chat_id/slowmode - Configure slow mode (chat admins only)
+async fn set_slow_mode(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(chat_id): Path<Uuid>,
+    Json(req): Json<SetSlowModeRequest>,
+) -> AppResult<Json<SlowModeResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    ChatService::set_slow_mode(
+        &state.db,
+        chat_id,
+        user_id,
+        req.seconds,
+        state.config.max_slow_mode_seconds,
+    )
+    .await?;
+
+    // Let clients show (or clear) the countdown
+    let participant_ids = ChatService::get_participant_ids(&state.db, chat_id).await?;
+    WebSocketService::broadcast_slow_mode_changed(
+        &state.ws_manager,
+        chat_id,
+        req.seconds,
+        &participant_ids,
+    )
+    .await;
+
+    Ok(Json(SlowModeResponse {
+        chat_id,
+        slow_mode_seconds: req.seconds,
+    }))
+}
+
 // Message routes
+#[derive(Debug, Deserialize)]
+pub struct SetDisappearingTimerRequest {
+    seconds: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DisappearingTimerResponse {
+    #[serde(rename = "chatId")]
+    chat_id: Uuid,
+    #[serde(rename = "disappearAfterSeconds")]
+    disappear_after_seconds: i32,
+}
+
+/// PUT /api/v1/chats/:chat_id/disappearing - Set the disappearing-message timer
+///
+/// Group admins only; either party in private chats. Applies to new messages.
+async fn set_disappearing_timer(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(chat_id): Path<Uuid>,
+    Json(req): Json<SetDisappearingTimerRequest>,
+) -> AppResult<Json<DisappearingTimerResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    ChatService::set_disappearing_timer(&state.db, chat_id, user_id, req.seconds).await?;
+
+    Ok(Json(DisappearingTimerResponse {
+        chat_id,
+        disappear_after_seconds: req.seconds,
+    }))
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RetentionRequest {
+    /// Days to keep messages; `null` uses the server default, 0 keeps forever
+    #[serde(rename = "retentionDays")]
+    retention_days: Option<i32>,
+}
+
+/// PUT /api/v1/chats/:chat_id/retention - Override the message retention period
+///
+/// Same permissions as the disappearing timer.
+async fn set_retention(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(chat_id): Path<Uuid>,
+    Json(req): Json<RetentionRequest>,
+) -> AppResult<Json<RetentionRequest>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    ChatService::set_retention_days(&state.db, chat_id, user_id, req.retention_days).await?;
+
+    Ok(Json(req))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetLinkPreviewsRequest {
+    enabled: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LinkPreviewsResponse {
+    #[serde(rename = "chatId")]
+    chat_id: Uuid,
+    #[serde(rename = "linkPreviewsEnabled")]
+    link_previews_enabled: bool,
+}
+
+/// PUT /api/v1/chats/:chat_id/link-previews - Enable or disable link previews
+///
+/// Group admins only; either party in private chats. Applies to new messages.
+async fn set_link_previews(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(chat_id): Path<Uuid>,
+    Json(req): Json<SetLinkPreviewsRequest>,
+) -> AppResult<Json<LinkPreviewsResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    ChatService::set_link_previews_enabled(&state.db, chat_id, user_id, req.enabled).await?;
+
+    Ok(Json(LinkPreviewsResponse {
+        chat_id,
+        link_previews_enabled: req.enabled,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct CustomEmojiListResponse {
+    emoji: Vec<CustomEmojiResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CustomEmojiResponseWrapper {
+    emoji: CustomEmojiResponse,
+}
+
+/// GET /api/v1/chats/:chat_id/emoji - Custom emoji usable in the chat
+///
+/// The chat's own emoji first, then the global ones.
+async fn list_custom_emoji(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(chat_id): Path<Uuid>,
+) -> AppResult<Json<CustomEmojiListResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let emoji = CustomEmojiService::list_for_chat(&state.db, chat_id, user_id).await?;
+    Ok(Json(CustomEmojiListResponse {
+        emoji: emoji.iter().map(|e| e.to_response()).collect(),
+    }))
+}
+
+/// POST /api/v1/chats/:chat_id/emoji - Upload a custom emoji for the chat
+///
+/// Multipart form with `shortcode` and `file` (PNG, GIF, WebP or JPEG up to
+/// `CUSTOM_EMOJI_MAX_BYTES`). In groups, only admins unless the chat's
+/// emoji policy is `members`.
+async fn add_custom_emoji(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(chat_id): Path<Uuid>,
+    multipart: Multipart,
+) -> AppResult<(StatusCode, Json<CustomEmojiResponseWrapper>)> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let (shortcode, data) = read_custom_emoji_form(multipart).await?;
+    let emoji =
+        CustomEmojiService::create(&state, Some(chat_id), user_id, &shortcode, &data).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CustomEmojiResponseWrapper {
+            emoji: emoji.to_response(),
+        }),
+    ))
+}
+
+/// DELETE /api/v1/chats/:chat_id/emoji/:emoji_id - Delete a custom emoji
+///
+/// The uploader or a chat admin. Reactions using the emoji are removed.
+async fn delete_custom_emoji(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((chat_id, emoji_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<StatusCode> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    CustomEmojiService::delete_from_chat(&state.db, chat_id, emoji_id, user_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CustomEmojiPolicyRequest {
+    policy: String,
+}
+
+/// PUT /api/v1/chats/:chat_id/emoji-policy - Who may add custom emoji
+///
+/// `admins` (the default) or `members`. Group admins only; either party in
+/// private chats.
+async fn set_custom_emoji_policy(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(chat_id): Path<Uuid>,
+    Json(req): Json<CustomEmojiPolicyRequest>,
+) -> AppResult<Json<CustomEmojiPolicyRequest>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    ChatService::set_custom_emoji_policy(&state.db, chat_id, user_id, &req.policy).await?;
+    Ok(Json(req))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EnableE2eeRequest {
+    enabled: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct E2eeResponse {
+    #[serde(rename = "chatId")]
+    chat_id: Uuid,
+    e2ee: bool,
+}
+
+/// PUT /api/v1/chats/:chat_id/e2ee - Turn on end-to-end encryption
+///
+/// Group admins only; either party in private chats. Once on it stays on:
+/// new messages are stored as the ciphertext clients send.
+async fn enable_e2ee(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(chat_id): Path<Uuid>,
+    Json(req): Json<EnableE2eeRequest>,
+) -> AppResult<Json<E2eeResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+    if !req.enabled {
+        return Err(AppError::BadRequest(
+            "End-to-end encryption cannot be turned off".to_string(),
+        ));
+    }
+
+    ChatService::enable_e2ee(&state.db, chat_id, user_id).await?;
+
+    Ok(Json(E2eeResponse { chat_id, e2ee: true }))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishKeyBundleRequest {
+    identity_key: String,
+    signed_prekey: String,
+    prekey_signature: String,
+    #[serde(default)]
+    one_time_prekeys: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishKeyBundleResponse {
+    one_time_prekeys: usize,
+}
+
+/// POST /api/v1/chats/:chat_id/keys - Publish the caller's public prekey
+/// bundle for the chat, replacing the previous one
+async fn publish_key_bundle(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(chat_id): Path<Uuid>,
+    Json(req): Json<PublishKeyBundleRequest>,
+) -> AppResult<Json<PublishKeyBundleResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let bundle = KeyBundleInput {
+        identity_key: req.identity_key,
+        signed_prekey: req.signed_prekey,
+        prekey_signature: req.prekey_signature,
+        one_time_prekeys: req.one_time_prekeys,
+    };
+    let one_time_prekeys = E2eeKeyService::publish(&state.db, chat_id, user_id, bundle).await?;
+
+    Ok(Json(PublishKeyBundleResponse { one_time_prekeys }))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyBundleResponse {
+    user_id: Uuid,
+    identity_key: String,
+    signed_prekey: String,
+    prekey_signature: String,
+    one_time_prekey: Option<String>,
+}
+
+/// GET /api/v1/chats/:chat_id/keys/:user_id - Another participant's bundle,
+/// to open an E2EE session with them; each call uses up one of their
+/// one-time prekeys
+async fn get_key_bundle(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((chat_id, owner_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<Json<KeyBundleResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let FetchedKeyBundle {
+        identity_key,
+        signed_prekey,
+        prekey_signature,
+        one_time_prekey,
+    } = E2eeKeyService::fetch(&state.db, chat_id, user_id, owner_id).await?;
+
+    Ok(Json(KeyBundleResponse {
+        user_id: owner_id,
+        identity_key,
+        signed_prekey,
+        prekey_signature,
+        one_time_prekey,
+    }))
+}
+
 #[derive(Debug, Deserialize)]
 pub struct MessagesQuery {
     limit: Option<i64>,
@@ -273,6 +805,39 @@ async fn get_messages(
     Ok(Json(MessagesResponse { messages, has_more }))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct SearchMessagesQuery {
+    q: String,
+    limit: Option<i64>,
+    /// Return hits older than this hit (the last one of the previous page)
+    before: Option<Uuid>,
+    /// Messages of context on each side of a hit
+    context: Option<i64>,
+}
+
+/// GET /api/v1/chats/:chat_id/messages/search?q= - Search within one chat
+async fn search_messages(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(chat_id): Path<Uuid>,
+    Query(query): Query<SearchMessagesQuery>,
+) -> AppResult<Json<MessageSearchResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let results = MessageService::search_in_chat(
+        &state.db,
+        chat_id,
+        user_id,
+        &query.q,
+        query.limit.unwrap_or(20),
+        query.before,
+        query.context.unwrap_or(2),
+    )
+    .await?;
+
+    Ok(Json(results))
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SendMessageRequest {
     text: Option<String>,
@@ -282,15 +847,11 @@ pub struct SendMessageRequest {
     reply_to: Option<ReplyToRequest>,
 }
 
+/// An uploaded file to attach, by the `id` from `POST /upload`; other
+/// fields of the upload response may be echoed back and are ignored
 #[derive(Debug, Deserialize)]
 pub struct AttachmentRequest {
-    #[serde(rename = "type")]
-    attachment_type: String,
-    name: String,
-    size: i64,
-    url: String,
-    #[serde(rename = "mimeType")]
-    mime_type: Option<String>,
+    id: Uuid,
 }
 
 #[derive(Debug, Deserialize)]
@@ -314,38 +875,159 @@ async fn send_message(
     let attachments: Vec<AttachmentInput> = req
         .attachments
         .into_iter()
-        .map(|a| AttachmentInput {
-            attachment_type: a.attachment_type,
-            name: a.name,
-            size: a.size,
-            url: a.url,
-            mime_type: a.mime_type,
-        })
+        .map(|a| AttachmentInput { upload_id: a.id })
         .collect();
 
     let reply_to = req.reply_to.map(|r| ReplyToInput { id: r.id });
 
     let message =
-        MessageService::send_message(&state.db, chat_id, user_id, req.text, attachments, reply_to)
+        MessageService::send_and_deliver(&state, chat_id, user_id, req.text, attachments, reply_to)
             .await?;
 
-    // Broadcast new message to all chat participants via WebSocket
-    let participant_ids = ChatService::get_participant_ids(&state.db, chat_id).await?;
-    WebSocketService::broadcast_new_message(
-        &state.ws_manager,
-        message.clone(),
-        &participant_ids,
+    Ok(Json(MessageResponseWrapper { message }))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatePollRequest {
+    question: String,
+    options: Vec<String>,
+    #[serde(default)]
+    multiple_choice: bool,
+    #[serde(default)]
+    anonymous: bool,
+}
+
+/// POST /api/v1/chats/:chat_id/polls - Post a poll; it arrives as a
+/// `new_message` whose `poll` holds the options
+async fn create_poll(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(chat_id): Path<Uuid>,
+    Json(req): Json<CreatePollRequest>,
+) -> AppResult<Json<MessageResponseWrapper>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+    // Reject a malformed poll before it uses up the slow-mode window
+    normalize_poll(&req.question, &req.options)?;
+    state.flood_guard.check_muted(chat_id, user_id).await?;
+    state.slow_mode.check_and_mark(&state.db, chat_id, user_id).await?;
+
+    let message = MessageService::create_poll(
+        &state.db,
+        chat_id,
         user_id,
+        &req.question,
+        &req.options,
+        req.multiple_choice,
+        req.anonymous,
     )
-    .await;
+    .await?;
+    MessageService::deliver_or_defer(&state, &message).await;
 
-    // Process message for bot commands (Requirements 6.1, 6.2)
-    // This will parse commands and dispatch to subscribed bots
-    if let Err(e) =
-        MessageProcessor::process_message(&state.db, state.ws_manager.clone(), &message).await
-    {
-        tracing::error!("Failed to process message for bots: {}", e);
-    }
+    Ok(Json(MessageResponseWrapper { message }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ThreadQuery {
+    limit: Option<i64>,
+    after: Option<Uuid>,
+}
+
+/// GET /api/v1/chats/:chat_id/messages/:message_id/thread - Root and replies
+async fn get_thread(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((chat_id, message_id)): Path<(Uuid, Uuid)>,
+    Query(query): Query<ThreadQuery>,
+) -> AppResult<Json<ThreadResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let thread = MessageService::get_thread(
+        &state.db,
+        chat_id,
+        message_id,
+        user_id,
+        query.limit.unwrap_or(50),
+        query.after,
+    )
+    .await?;
+
+    Ok(Json(thread))
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportChatRequest {
+    /// Bundle attachment files into a zip instead of listing their URLs
+    #[serde(default)]
+    include_media: bool,
+}
+
+/// POST /api/v1/chats/:chat_id/export - Start exporting the chat's messages
+///
+/// The archive is written in the background; poll
+/// `GET /api/v1/exports/:export_id` until it is ready to download.
+async fn export_chat(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(chat_id): Path<Uuid>,
+    req: Option<Json<ExportChatRequest>>,
+) -> AppResult<(StatusCode, Json<ExportResponseWrapper>)> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+    let Json(req) = req.unwrap_or_default();
+
+    let export = ExportService::request_export(&state, chat_id, user_id, req.include_media).await?;
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(ExportResponseWrapper {
+            export: export.to_response(),
+        }),
+    ))
+}
+
+/// GET /api/v1/chats/:chat_id/messages/:message_id/seen-by - Group members who read a message
+async fn get_seen_by(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((chat_id, message_id)): Path<(Uuid, Uuid)>,
+) -> AppResult<Json<SeenByResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+    let seen_by = MessageService::seen_by(&state.db, chat_id, message_id, user_id).await?;
+    Ok(Json(seen_by))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ThreadReplyRequest {
+    text: Option<String>,
+    #[serde(default)]
+    attachments: Vec<AttachmentRequest>,
+}
+
+/// POST /api/v1/chats/:chat_id/messages/:message_id/thread - Reply in a thread
+async fn reply_in_thread(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((chat_id, message_id)): Path<(Uuid, Uuid)>,
+    Json(req): Json<ThreadReplyRequest>,
+) -> AppResult<Json<MessageResponseWrapper>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let attachments: Vec<AttachmentInput> = req
+        .attachments
+        .into_iter()
+        .map(|a| AttachmentInput { upload_id: a.id })
+        .collect();
+
+    let message = MessageService::reply_and_deliver(
+        &state,
+        chat_id,
+        message_id,
+        user_id,
+        req.text,
+        attachments,
+    )
+    .await?;
 
     Ok(Json(MessageResponseWrapper { message }))
 }
@@ -362,9 +1044,18 @@ async fn edit_message(
     Json(req): Json<EditMessageRequest>,
 ) -> AppResult<Json<MessageResponseWrapper>> {
     let user_id = get_current_user_id(&state, &headers).await?;
+    let text = normalize_text(&req.text, state.config.max_message_bytes)?;
 
     let message =
-        MessageService::edit_message(&state.db, chat_id, message_id, user_id, &req.text).await?;
+        MessageService::edit_message(
+            &state.db,
+            chat_id,
+            message_id,
+            user_id,
+            &text,
+            state.config.message_edit_window_seconds,
+        )
+        .await?;
 
     // Broadcast message updated to all chat participants via WebSocket
     let participant_ids = ChatService::get_participant_ids(&state.db, chat_id).await?;
@@ -386,9 +1077,20 @@ async fn delete_message(
 ) -> AppResult<Json<SimpleMessage>> {
     let user_id = get_current_user_id(&state, &headers).await?;
 
-    MessageService::delete_message(&state.db, chat_id, message_id, user_id).await?;
+    let files = AttachmentService::stored_files(&state.db, message_id).await?;
+    MessageService::delete_message(
+        &state.db,
+        chat_id,
+        message_id,
+        user_id,
+        state.config.message_delete_window_seconds,
+    )
+    .await?;
+    if state.config.delete_attachment_files {
+        AttachmentService::spawn_remove_files(files);
+    }
 
-    // Broadcast message deleted to all chat participants via WebSocket
+    // Clients replace the message with a tombstone
     let participant_ids = ChatService::get_participant_ids(&state.db, chat_id).await?;
     WebSocketService::broadcast_message_deleted(
         &state.ws_manager,
@@ -418,9 +1120,12 @@ async fn clear_messages(
     }))
 }
 
+/// Either a standard emoji / custom `:shortcode:` or a custom emoji id
 #[derive(Debug, Deserialize)]
 pub struct ReactionRequest {
-    emoji: String,
+    emoji: Option<String>,
+    #[serde(rename = "customEmojiId")]
+    custom_emoji_id: Option<Uuid>,
 }
 
 async fn toggle_reaction(
@@ -431,21 +1136,46 @@ async fn toggle_reaction(
 ) -> AppResult<Json<MessageResponseWrapper>> {
     let user_id = get_current_user_id(&state, &headers).await?;
 
-    let message =
-        MessageService::toggle_reaction(&state.db, chat_id, message_id, user_id, &req.emoji)
+    let emoji = match (req.emoji, req.custom_emoji_id) {
+        (Some(emoji), None) => emoji,
+        (None, Some(emoji_id)) => CustomEmojiService::usable(&state.db, chat_id, emoji_id)
+            .await?
+            .reference(),
+        _ => {
+            return Err(AppError::BadRequest(
+                "Provide either emoji or customEmojiId".to_string(),
+            ))
+        }
+    };
+
+    state.reaction_limiter.check(user_id).await?;
+
+    let change =
+        MessageService::toggle_reaction(&state.db, chat_id, message_id, user_id, &emoji)
             .await?;
 
-    // Broadcast reaction updated to all chat participants via WebSocket
-    let participant_ids = ChatService::get_participant_ids(&state.db, chat_id).await?;
-    WebSocketService::broadcast_reaction_updated(
-        &state.ws_manager,
-        message.clone(),
-        &participant_ids,
-        user_id,
-    )
-    .await;
+    // Broadcast the final reaction counts once the debounce window closes
+    state
+        .reaction_debouncer
+        .schedule(chat_id, message_id, user_id)
+        .await;
 
-    Ok(Json(MessageResponseWrapper { message }))
+    // Let the chat's bots react to new reactions
+    if change.added {
+        if let Err(e) = MessageProcessor::process_reaction(
+            &state.db,
+            &state.bot_dispatcher,
+            &change.message,
+            user_id,
+            &emoji,
+        )
+        .await
+        {
+            tracing::warn!("Failed to dispatch reaction to bots: {}", e);
+        }
+    }
+
+    Ok(Json(MessageResponseWrapper { message: change.message }))
 }
 
 async fn pin_message(
@@ -455,7 +1185,7 @@ async fn pin_message(
 ) -> AppResult<Json<MessageResponseWrapper>> {
     let user_id = get_current_user_id(&state, &headers).await?;
 
-    let message = MessageService::pin_message(&state.db, chat_id, message_id, user_id).await?;
+    let change = MessageService::pin_message(&state.db, chat_id, message_id, user_id).await?;
 
     // Broadcast message pinned to all chat participants via WebSocket
     let participant_ids = ChatService::get_participant_ids(&state.db, chat_id).await?;
@@ -468,8 +1198,16 @@ async fn pin_message(
         user_id,
     )
     .await;
+    if let Some(system_message) = change.system_message {
+        WebSocketService::broadcast_system_message(
+            &state.ws_manager,
+            system_message,
+            &participant_ids,
+        )
+        .await;
+    }
 
-    Ok(Json(MessageResponseWrapper { message }))
+    Ok(Json(MessageResponseWrapper { message: change.message }))
 }
 
 async fn unpin_message(
@@ -479,7 +1217,7 @@ async fn unpin_message(
 ) -> AppResult<Json<MessageResponseWrapper>> {
     let user_id = get_current_user_id(&state, &headers).await?;
 
-    let message = MessageService::unpin_message(&state.db, chat_id, message_id, user_id).await?;
+    let change = MessageService::unpin_message(&state.db, chat_id, message_id, user_id).await?;
 
     // Broadcast message unpinned to all chat participants via WebSocket
     let participant_ids = ChatService::get_participant_ids(&state.db, chat_id).await?;
@@ -492,8 +1230,16 @@ async fn unpin_message(
         user_id,
     )
     .await;
+    if let Some(system_message) = change.system_message {
+        WebSocketService::broadcast_system_message(
+            &state.ws_manager,
+            system_message,
+            &participant_ids,
+        )
+        .await;
+    }
 
-    Ok(Json(MessageResponseWrapper { message }))
+    Ok(Json(MessageResponseWrapper { message: change.message }))
 }
 
 // ==================== Bot-Chat Management Routes ====================
@@ -618,3 +1364,39 @@ async fn list_chat_bots(
         bots: bot_responses,
     }))
 }
+
+#[derive(Debug, Deserialize)]
+pub struct ChatCommandsQuery {
+    /// What the user typed after `/` so far
+    #[serde(default)]
+    prefix: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChatCommandsResponse {
+    commands: Vec<ChatCommandSuggestion>,
+}
+
+/// Command autocomplete: the menu commands of the bots in a chat.
+///
+/// GET /api/v1/chats/:chat_id/commands?prefix=st
+///
+/// # Returns
+/// Commands set with setMyCommands by the chat's active bots, filtered by
+/// `prefix` when given.
+async fn list_chat_commands(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(chat_id): Path<Uuid>,
+    Query(query): Query<ChatCommandsQuery>,
+) -> AppResult<Json<ChatCommandsResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    if !ChatService::is_participant(&state.db, chat_id, user_id).await? {
+        return Err(AppError::AccessDenied);
+    }
+
+    let commands =
+        BotEngineService::get_chat_menu_commands(&state.db, chat_id, &query.prefix).await?;
+    Ok(Json(ChatCommandsResponse { commands }))
+}
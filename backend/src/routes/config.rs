@@ -0,0 +1,89 @@
+use axum::{extract::State, routing::get, Json, Router};
+use serde::Serialize;
+use std::sync::Arc;
+
+use crate::{config::Config, AppState};
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route("/limits", get(get_limits))
+}
+
+/// The limits the server enforces, straight from `Config`
+#[derive(Debug, Serialize)]
+pub struct LimitsResponse {
+    /// Longest message text, in bytes after normalization (user and bot messages)
+    #[serde(rename = "maxMessageBytes")]
+    max_message_bytes: usize,
+    /// Largest single uploaded file
+    #[serde(rename = "maxUploadBytes")]
+    max_upload_bytes: usize,
+    #[serde(rename = "maxAttachmentsPerMessage")]
+    max_attachments_per_message: usize,
+    /// Combined size of the attachments on one message
+    #[serde(rename = "maxAttachmentBytesPerMessage")]
+    max_attachment_bytes_per_message: i64,
+    /// Uploads per user per minute (0 = unlimited)
+    #[serde(rename = "uploadsPerMinute")]
+    uploads_per_minute: u32,
+    /// Bytes per user in any 24 hours (0 = unlimited)
+    #[serde(rename = "uploadDailyQuotaBytes")]
+    upload_daily_quota_bytes: u64,
+    /// Slow mode can be set from 0 (off) up to this
+    #[serde(rename = "maxSlowModeSeconds")]
+    max_slow_mode_seconds: i32,
+    /// Most members of a group or channel
+    #[serde(rename = "maxChatParticipants")]
+    max_chat_participants: i64,
+    /// Largest text frame accepted over the WebSocket
+    #[serde(rename = "wsMaxFrameBytes")]
+    ws_max_frame_bytes: usize,
+}
+
+impl From<&Config> for LimitsResponse {
+    fn from(config: &Config) -> Self {
+        Self {
+            max_message_bytes: config.max_message_bytes,
+            max_upload_bytes: config.max_upload_bytes,
+            max_attachments_per_message: config.max_attachments_per_message,
+            max_attachment_bytes_per_message: config.max_attachment_bytes_per_message,
+            uploads_per_minute: config.upload_max_per_minute,
+            upload_daily_quota_bytes: config.upload_daily_quota_bytes,
+            max_slow_mode_seconds: config.max_slow_mode_seconds,
+            max_chat_participants: config.max_chat_participants,
+            ws_max_frame_bytes: config.ws_max_frame_bytes,
+        }
+    }
+}
+
+/// GET /api/v1/config/limits - Effective server limits, so clients can
+/// check input before sending it
+///
+/// Public: the values are the same for every user.
+async fn get_limits(State(state): State<Arc<AppState>>) -> Json<LimitsResponse> {
+    Json(LimitsResponse::from(&state.config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits_follow_config() {
+        let config = Config {
+            max_message_bytes: 4096,
+            max_upload_bytes: 10 * 1024 * 1024,
+            max_attachments_per_message: 4,
+            max_slow_mode_seconds: 600,
+            ..crate::config::tests::valid_config()
+        };
+        let json = serde_json::to_value(LimitsResponse::from(&config)).unwrap();
+        assert_eq!(json["maxMessageBytes"], 4096);
+        assert_eq!(json["maxUploadBytes"], 10 * 1024 * 1024);
+        assert_eq!(json["maxAttachmentsPerMessage"], 4);
+        assert_eq!(json["maxAttachmentBytesPerMessage"], 1073741824);
+        assert_eq!(json["uploadsPerMinute"], 20);
+        assert_eq!(json["maxSlowModeSeconds"], 600);
+        assert_eq!(json["maxChatParticipants"], 1000);
+        assert_eq!(json["wsMaxFrameBytes"], 262144);
+    }
+}
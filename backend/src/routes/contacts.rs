@@ -0,0 +1,117 @@
+use axum::{
+    extract::{Path, State},
+    http::HeaderMap,
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    error::AppResult,
+    models::Contact,
+    routes::auth::get_current_user_id,
+    services::{user::normalize_contact_import, UserService},
+    AppState,
+};
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_contacts).post(add_contact))
+        .route("/import", post(import_contacts))
+        .route("/:user_id", delete(remove_contact))
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContactsResponse {
+    contacts: Vec<Contact>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContactResponse {
+    contact: Contact,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimpleMessage {
+    message: String,
+}
+
+async fn list_contacts(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> AppResult<Json<ContactsResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+    let contacts = UserService::list_contacts(&state.db, user_id).await?;
+    Ok(Json(ContactsResponse { contacts }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddContactRequest {
+    #[serde(rename = "userId")]
+    user_id: Uuid,
+}
+
+async fn add_contact(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<AddContactRequest>,
+) -> AppResult<Json<ContactResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+    let contact =
+        UserService::add_contact(&state.db, user_id, req.user_id, state.config.contacts_mutual)
+            .await?;
+    Ok(Json(ContactResponse { contact }))
+}
+
+async fn remove_contact(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(contact_id): Path<Uuid>,
+) -> AppResult<Json<SimpleMessage>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+    UserService::remove_contact(&state.db, user_id, contact_id, state.config.contacts_mutual)
+        .await?;
+    Ok(Json(SimpleMessage {
+        message: "Contact removed".to_string(),
+    }))
+}
+
+/// Address book import
+///
+/// Phone numbers are never sent in the clear: clients strip everything but
+/// digits and a leading `+`, then send the hex SHA-256 of the result.
+#[derive(Debug, Deserialize)]
+pub struct ImportContactsRequest {
+    #[serde(default)]
+    usernames: Vec<String>,
+    #[serde(default, rename = "phoneHashes")]
+    phone_hashes: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportContactsResponse {
+    contacts: Vec<Contact>,
+    /// Identifiers that matched nobody yet; their owner is added once a
+    /// matching user shows up
+    pending: usize,
+}
+
+async fn import_contacts(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<ImportContactsRequest>,
+) -> AppResult<Json<ImportContactsResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+    let (usernames, phone_hashes) = normalize_contact_import(&req.usernames, &req.phone_hashes)?;
+    let (contacts, pending) = UserService::import_contacts(
+        &state.db,
+        user_id,
+        &usernames,
+        &phone_hashes,
+        state.config.contacts_mutual,
+    )
+    .await?;
+    Ok(Json(ImportContactsResponse { contacts, pending }))
+}
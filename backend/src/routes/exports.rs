@@ -0,0 +1,88 @@
+/// Chat Export Routes - status and download of message history exports.
+///
+/// Exports are started with `POST /api/v1/chats/:chat_id/export`. This
+/// module provides:
+/// - GET /api/v1/exports/:export_id - Progress of an export
+/// - GET /api/v1/exports/:export_id/download - Stream the finished archive
+///
+/// Only the user who requested an export can see or download it.
+use axum::{
+    body::Body,
+    extract::{Path, Request, State},
+    http::{header, HeaderMap, HeaderValue},
+    response::Response,
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
+use std::sync::Arc;
+use tower_http::services::ServeFile;
+use uuid::Uuid;
+
+use crate::{
+    error::AppResult,
+    models::ChatExportResponse,
+    routes::auth::get_current_user_id,
+    services::ExportService,
+    AppState,
+};
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/:export_id", get(get_export))
+        .route("/:export_id/download", get(download_export))
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportResponseWrapper {
+    pub export: ChatExportResponse,
+}
+
+/// GET /api/v1/exports/:export_id - Progress of one of the caller's exports
+async fn get_export(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(export_id): Path<Uuid>,
+) -> AppResult<Json<ExportResponseWrapper>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let export = ExportService::get_export(&state.db, export_id, user_id).await?;
+    Ok(Json(ExportResponseWrapper {
+        export: export.to_response(),
+    }))
+}
+
+/// GET /api/v1/exports/:export_id/download - Stream a finished archive
+///
+/// The file is streamed from disk (with range support) rather than read
+/// into memory.
+async fn download_export(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(export_id): Path<Uuid>,
+    request: Request,
+) -> AppResult<Response> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let export = ExportService::get_export(&state.db, export_id, user_id).await?;
+    let path = ExportService::archive_path(&export)?;
+
+    let mut file = ServeFile::new(path)
+        .try_call(request)
+        .await
+        .map_err(anyhow::Error::from)?;
+    if file.status().is_success() {
+        let extension = if export.include_media { "zip" } else { "jsonl" };
+        let disposition = format!(
+            "attachment; filename=\"chat-export-{}.{}\"",
+            export.chat_id, extension
+        );
+        let file_headers = file.headers_mut();
+        file_headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(export.format()));
+        if let Ok(value) = HeaderValue::from_str(&disposition) {
+            file_headers.insert(header::CONTENT_DISPOSITION, value);
+        }
+    }
+
+    Ok(file.map(Body::new))
+}
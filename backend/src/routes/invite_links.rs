@@ -1,7 +1,7 @@
 use crate::error::AppResult;
-use crate::models::CreateInviteLinkRequest;
+use crate::models::{ChatType, CreateInviteLinkRequest, SystemEventType};
 use crate::routes::auth::get_current_user_id;
-use crate::services::invite_link;
+use crate::services::{invite_link, ChatService, MessageService, WebSocketService};
 use crate::AppState;
 use axum::{
     extract::{Path, State},
@@ -18,7 +18,8 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/", post(create_invite_link))
         .route("/my", get(get_my_invite_links))
         .route("/:code", get(get_invite_link))
-        .route("/:code/use", post(use_invite_link))
+        .route("/:code/use", post(redeem_invite_link))
+        .route("/:code/redeem", post(redeem_invite_link))
         .route("/:id/revoke", post(revoke_invite_link))
 }
 
@@ -42,17 +43,45 @@ async fn get_invite_link(
     Ok(Json(serde_json::json!(link)))
 }
 
-/// Use an invite link
-async fn use_invite_link(
+/// Redeem an invite link and join its chat
+async fn redeem_invite_link(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Path(code): Path<String>,
 ) -> AppResult<Json<serde_json::Value>> {
     let user_id = get_current_user_id(&state, &headers).await?;
-    let result = invite_link::use_invite_link(&state.db.pool, user_id, &code).await?;
+    let result = invite_link::redeem_invite_link(
+        &state.db.pool,
+        user_id,
+        &code,
+        state.config.max_chat_participants,
+    )
+    .await?;
+
+    // Let the group see who joined; the join itself already went through
+    if result.joined && ChatType::parse(&result.chat_type) == Some(ChatType::Group) {
+        if let Err(e) = announce_member_joined(&state, result.chat_id, user_id).await {
+            tracing::warn!("Failed to record join of {} in chat {}: {}", user_id, result.chat_id, e);
+        }
+    }
+
     Ok(Json(serde_json::json!(result)))
 }
 
+async fn announce_member_joined(state: &AppState, chat_id: Uuid, user_id: Uuid) -> AppResult<()> {
+    let message = MessageService::create_system_event(
+        &state.db,
+        chat_id,
+        user_id,
+        SystemEventType::MemberJoined,
+        serde_json::json!({ "userId": user_id }),
+    )
+    .await?;
+    let participant_ids = ChatService::get_participant_ids(&state.db, chat_id).await?;
+    WebSocketService::broadcast_system_message(&state.ws_manager, message, &participant_ids).await;
+    Ok(())
+}
+
 /// Get all invite links created by the current user
 async fn get_my_invite_links(
     State(state): State<Arc<AppState>>,
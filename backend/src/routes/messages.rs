@@ -0,0 +1,85 @@
+/// Message API Routes - actions on a single message across chats.
+///
+/// Messages are sent and listed under `/api/v1/chats/:chat_id/messages`.
+/// This module provides:
+/// - POST /api/v1/messages/:message_id/forward - Forward a message to up to
+///   `MAX_FORWARD_TARGETS` chats at once
+/// - POST /api/v1/messages/:message_id/hide - Hide a message from the caller's history
+/// - DELETE /api/v1/messages/:message_id/hide - Show it again
+///
+/// Each forwarded copy is delivered as a `new_message` in its chat. Hiding
+/// only affects the caller and is never broadcast.
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    routing::post,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    error::AppResult,
+    models::ForwardResult,
+    routes::auth::get_current_user_id,
+    services::MessageService,
+    AppState,
+};
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/:message_id/forward", post(forward_message))
+        .route("/:message_id/hide", post(hide_message).delete(unhide_message))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ForwardRequest {
+    #[serde(rename = "targetChatIds")]
+    target_chat_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ForwardResponse {
+    /// One result per distinct target chat, in request order
+    results: Vec<ForwardResult>,
+}
+
+/// Targets the sender can't post in are reported in their result; the
+/// request only fails as a whole when the source message can't be forwarded
+async fn forward_message(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(message_id): Path<Uuid>,
+    Json(req): Json<ForwardRequest>,
+) -> AppResult<Json<ForwardResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let results =
+        MessageService::forward_and_deliver(&state, message_id, user_id, &req.target_chat_ids)
+            .await?;
+
+    Ok(Json(ForwardResponse { results }))
+}
+
+async fn hide_message(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(message_id): Path<Uuid>,
+) -> AppResult<StatusCode> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    MessageService::hide_for_user(&state.db, message_id, user_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn unhide_message(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(message_id): Path<Uuid>,
+) -> AppResult<StatusCode> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    MessageService::unhide_for_user(&state.db, message_id, user_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,6 +1,6 @@
 use axum::{
     extract::State,
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Json},
     routing::get,
     Router,
@@ -8,7 +8,7 @@ use axum::{
 use std::sync::Arc;
 
 use crate::AppState;
-use crate::quic::MetricsSnapshot;
+use crate::quic::{MessageTypeTraffic, MetricsSnapshot, StreamAllocatorStats};
 
 /// Get QUIC metrics
 ///
@@ -58,6 +58,107 @@ async fn quic_health(
     )
 }
 
+/// Stream metrics in the Prometheus text exposition format
+///
+/// Active streams plus per-message-type byte and message counters, with the
+/// per-second rates from the traffic sampler as gauges, and the deliveries
+/// that fell back from the preferred transport.
+async fn prometheus_metrics(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let stats = state.stream_allocator.get_stats().await;
+    let fallbacks = state.connection_manager.transport_fallback_count();
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        render_prometheus(&stats, fallbacks),
+    )
+}
+
+fn write_family(out: &mut String, name: &str, kind: &str, help: &str) {
+    out.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n", name, help, name, kind));
+}
+
+/// Render stream allocator stats and the transport fallback count as
+/// Prometheus metric families
+fn render_prometheus(stats: &StreamAllocatorStats, transport_fallbacks: u64) -> String {
+    let mut out = String::new();
+
+    write_family(
+        &mut out,
+        "realtime_transport_fallbacks_total",
+        "counter",
+        "Deliveries that fell back from the preferred transport",
+    );
+    out.push_str(&format!("realtime_transport_fallbacks_total {}\n", transport_fallbacks));
+
+    write_family(&mut out, "quic_connections", "gauge", "Connections with registered streams");
+    out.push_str(&format!("quic_connections {}\n", stats.total_connections));
+
+    write_family(&mut out, "quic_active_streams", "gauge", "Active streams by message type");
+    for (label, count) in [
+        ("control", stats.control_streams),
+        ("chat_message", stats.chat_streams),
+        ("file_transfer", stats.file_streams),
+        ("bot_command", stats.bot_streams),
+    ] {
+        out.push_str(&format!(
+            "quic_active_streams{{message_type=\"{}\"}} {}\n",
+            label, count
+        ));
+    }
+
+    type Value = fn(&MessageTypeTraffic) -> String;
+    let families: [(&str, &str, &str, Value); 6] = [
+        (
+            "quic_stream_bytes_received_total",
+            "counter",
+            "Bytes received on streams by message type",
+            |t| t.bytes_received.to_string(),
+        ),
+        (
+            "quic_stream_bytes_sent_total",
+            "counter",
+            "Bytes sent on streams by message type",
+            |t| t.bytes_sent.to_string(),
+        ),
+        (
+            "quic_stream_messages_routed_total",
+            "counter",
+            "Messages routed by message type",
+            |t| t.messages_routed.to_string(),
+        ),
+        (
+            "quic_stream_bytes_received_per_second",
+            "gauge",
+            "Recent receive rate by message type",
+            |t| t.rates.bytes_received_per_sec.to_string(),
+        ),
+        (
+            "quic_stream_bytes_sent_per_second",
+            "gauge",
+            "Recent send rate by message type",
+            |t| t.rates.bytes_sent_per_sec.to_string(),
+        ),
+        (
+            "quic_stream_messages_routed_per_second",
+            "gauge",
+            "Recent routing rate by message type",
+            |t| t.rates.messages_routed_per_sec.to_string(),
+        ),
+    ];
+    for (name, kind, help, value) in families {
+        write_family(&mut out, name, kind, help);
+        for traffic in &stats.traffic {
+            out.push_str(&format!(
+                "{}{{message_type=\"{}\"}} {}\n",
+                name,
+                traffic.message_type,
+                value(traffic)
+            ));
+        }
+    }
+
+    out
+}
+
 /// Metrics routes
 ///
 /// # Requirements
@@ -66,11 +167,13 @@ pub fn routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/quic", get(get_metrics))
         .route("/quic/health", get(quic_health))
+        .route("/prometheus", get(prometheus_metrics))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::quic::{MessageType, StreamTraffic};
 
     #[test]
     fn test_routes_compile() {
@@ -78,4 +181,30 @@ mod tests {
         // Actual integration testing will be done when QUIC is fully integrated
         let _routes = routes();
     }
+
+    #[test]
+    fn test_render_prometheus_includes_traffic_per_message_type() {
+        let traffic = StreamTraffic::new();
+        traffic.record_received(MessageType::FileTransfer, 2048);
+        traffic.record_routed(MessageType::BotCommand);
+
+        let stats = StreamAllocatorStats {
+            total_connections: 1,
+            total_streams: 2,
+            control_streams: 1,
+            chat_streams: 1,
+            file_streams: 0,
+            bot_streams: 0,
+            traffic: traffic.snapshot(),
+        };
+        let text = render_prometheus(&stats, 3);
+
+        assert!(text.contains("# TYPE quic_stream_bytes_received_total counter\n"));
+        assert!(text.contains("quic_stream_bytes_received_total{message_type=\"file_transfer\"} 2048\n"));
+        assert!(text.contains("quic_stream_messages_routed_total{message_type=\"bot_command\"} 1\n"));
+        assert!(text.contains("# TYPE quic_stream_bytes_sent_per_second gauge\n"));
+        assert!(text.contains("quic_active_streams{message_type=\"control\"} 1\n"));
+        assert!(text.contains("# TYPE realtime_transport_fallbacks_total counter\n"));
+        assert!(text.contains("realtime_transport_fallbacks_total 3\n"));
+    }
 }
@@ -9,23 +9,75 @@ pub mod bot_api;
 pub mod botfather;
 pub mod metrics;
 pub mod invite_links;
+pub mod admin;
+pub mod calls;
+pub mod contacts;
+pub mod push;
+pub mod transport;
+pub mod config;
+pub mod polls;
+pub mod messages;
+pub mod exports;
+pub mod saved;
+pub mod callbacks;
+pub mod unfurl;
 
-use axum::Router;
+use axum::{
+    extract::{Request, State},
+    http::Method,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
 use std::sync::Arc;
 use crate::AppState;
 
-pub fn api_routes() -> Router<Arc<AppState>> {
-    Router::new()
-        .nest("/auth", auth::routes())
+pub fn api_routes(state: Arc<AppState>) -> Router<Arc<AppState>> {
+    // Everything here but GET requests is a write
+    let writes = Router::new()
         .nest("/users", users::routes())
         .nest("/chats", chats::routes())
         .nest("/settings", settings::routes())
         .nest("/upload", upload::routes())
-        .nest("/ws", ws::routes())
         .nest("/bots", bots::routes())
         .nest("/botfather", botfather::routes())
-        .nest("/metrics", metrics::routes())
         .nest("/invite-links", invite_links::routes())
+        .nest("/contacts", contacts::routes())
+        .nest("/push", push::routes())
+        .nest("/polls", polls::routes())
+        .nest("/messages", messages::routes())
+        .nest("/exports", exports::routes())
+        .nest("/saved", saved::routes())
+        .nest("/callback", callbacks::routes())
+        .nest("/unfurl", unfurl::routes())
+        .route_layer(middleware::from_fn_with_state(state.clone(), reject_writes_in_maintenance));
+
+    // Signing in, typing and call setup keep working in maintenance mode,
+    // and admins must be able to turn it off
+    Router::new()
+        .nest("/auth", auth::routes())
+        .nest("/ws", ws::routes())
+        .nest("/metrics", metrics::routes())
+        .nest("/admin", admin::routes(state))
+        .nest("/calls", calls::routes())
+        .nest("/transport", transport::routes())
+        .nest("/config", config::routes())
+        .merge(writes)
+}
+
+/// Refuse anything but reads with 503 while the service is in maintenance mode
+pub(crate) async fn reject_writes_in_maintenance(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let read = matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    if !read {
+        if let Err(e) = state.ensure_writable() {
+            return e.into_response();
+        }
+    }
+    next.run(request).await
 }
 
 /// Bot API routes (Telegram-style /bot:token/* endpoints)
@@ -0,0 +1,95 @@
+/// Poll API Routes - voting on polls posted in chats.
+///
+/// Polls are created with `POST /api/v1/chats/:chat_id/polls`. This module
+/// provides:
+/// - GET /api/v1/polls/:poll_id - Current tallies
+/// - POST /api/v1/polls/:poll_id/vote - Replace the caller's votes
+/// - POST /api/v1/polls/:poll_id/close - Stop accepting votes (creator or chat admin)
+///
+/// Every change is broadcast to the chat as `poll_updated`.
+use axum::{
+    extract::{Path, State},
+    http::HeaderMap,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    error::AppResult,
+    models::{Poll, PollResponse},
+    routes::auth::get_current_user_id,
+    services::{ChatService, PollService, WebSocketService},
+    AppState,
+};
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/:poll_id", get(get_poll))
+        .route("/:poll_id/vote", post(vote))
+        .route("/:poll_id/close", post(close_poll))
+}
+
+#[derive(Debug, Serialize)]
+pub struct PollResponseWrapper {
+    poll: PollResponse,
+}
+
+async fn get_poll(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(poll_id): Path<Uuid>,
+) -> AppResult<Json<PollResponseWrapper>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let poll = PollService::get(&state.db, poll_id, user_id).await?;
+    Ok(Json(PollResponseWrapper { poll }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VoteRequest {
+    /// The options picked; an empty list retracts the vote
+    #[serde(rename = "optionIds")]
+    option_ids: Vec<Uuid>,
+}
+
+async fn vote(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(poll_id): Path<Uuid>,
+    Json(req): Json<VoteRequest>,
+) -> AppResult<Json<PollResponseWrapper>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let (poll, response) = PollService::vote(&state.db, poll_id, user_id, req.option_ids).await?;
+    broadcast(&state, &poll, &response).await?;
+
+    Ok(Json(PollResponseWrapper { poll: response }))
+}
+
+async fn close_poll(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(poll_id): Path<Uuid>,
+) -> AppResult<Json<PollResponseWrapper>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let (poll, response) = PollService::close(&state.db, poll_id, user_id).await?;
+    broadcast(&state, &poll, &response).await?;
+
+    Ok(Json(PollResponseWrapper { poll: response }))
+}
+
+async fn broadcast(state: &AppState, poll: &Poll, response: &PollResponse) -> AppResult<()> {
+    let participant_ids = ChatService::get_participant_ids(&state.db, poll.chat_id).await?;
+    WebSocketService::broadcast_poll_updated(
+        &state.ws_manager,
+        poll,
+        response.clone(),
+        &participant_ids,
+    )
+    .await;
+    Ok(())
+}
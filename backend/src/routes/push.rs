@@ -0,0 +1,119 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::{
+    error::{AppError, AppResult},
+    models::PushSubscription,
+    routes::auth::{extract_token, get_current_user_id},
+    services::PushService,
+    AppState,
+};
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/vapid-public-key", get(get_vapid_public_key))
+        .route(
+            "/subscriptions",
+            axum::routing::post(subscribe).delete(unsubscribe),
+        )
+}
+
+#[derive(Debug, Serialize)]
+pub struct VapidPublicKeyResponse {
+    #[serde(rename = "publicKey")]
+    public_key: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubscriptionResponse {
+    subscription: PushSubscription,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimpleMessage {
+    message: String,
+}
+
+/// GET /api/v1/push/vapid-public-key - Application server key for `pushManager.subscribe`
+async fn get_vapid_public_key(
+    State(state): State<Arc<AppState>>,
+) -> AppResult<Json<VapidPublicKeyResponse>> {
+    let public_key = state
+        .push
+        .public_key()
+        .filter(|_| state.push.is_enabled())
+        .ok_or_else(|| AppError::NotFound("Push notifications are not enabled".to_string()))?;
+
+    Ok(Json(VapidPublicKeyResponse {
+        public_key: public_key.to_string(),
+    }))
+}
+
+/// Keys of a browser `PushSubscription` (as in `subscription.toJSON()`)
+#[derive(Debug, Deserialize)]
+pub struct SubscriptionKeys {
+    p256dh: String,
+    auth: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubscribeRequest {
+    endpoint: String,
+    keys: SubscriptionKeys,
+}
+
+/// POST /api/v1/push/subscriptions - Register this browser for push notifications
+async fn subscribe(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<SubscribeRequest>,
+) -> AppResult<Json<SubscriptionResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+    let token = extract_token(&headers)?;
+    if !state.push.is_enabled() {
+        return Err(AppError::BadRequest(
+            "Push notifications are not enabled".to_string(),
+        ));
+    }
+
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok());
+    let subscription = PushService::subscribe(
+        &state.db,
+        user_id,
+        &req.endpoint,
+        &req.keys.p256dh,
+        &req.keys.auth,
+        user_agent,
+        &token,
+    )
+    .await?;
+
+    Ok(Json(SubscriptionResponse { subscription }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnsubscribeRequest {
+    endpoint: String,
+}
+
+/// DELETE /api/v1/push/subscriptions - Stop push notifications to this browser
+async fn unsubscribe(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<UnsubscribeRequest>,
+) -> AppResult<Json<SimpleMessage>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+    PushService::unsubscribe(&state.db, user_id, &req.endpoint).await?;
+
+    Ok(Json(SimpleMessage {
+        message: "Push subscription removed".to_string(),
+    }))
+}
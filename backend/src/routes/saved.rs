@@ -0,0 +1,84 @@
+/// Saved Messages Routes - the caller's private bookmarks of messages.
+///
+/// This module provides:
+/// - GET /api/v1/saved - List saved messages, most recently saved first
+/// - POST /api/v1/saved/:message_id - Save a message from one of the caller's chats
+/// - DELETE /api/v1/saved/:message_id - Remove a message from the list
+///
+/// Saving is never broadcast. A saved message stays listed after its
+/// original is deleted, as a tombstone.
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    error::AppResult,
+    models::{SavedMessageResponse, SavedMessagesResponse},
+    routes::auth::get_current_user_id,
+    services::MessageService,
+    AppState,
+};
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_saved))
+        .route("/:message_id", post(save_message).delete(unsave_message))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SavedQuery {
+    limit: Option<i64>,
+    /// Return entries saved before this message (the last one of the previous page)
+    before: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SavedMessageWrapper {
+    saved: SavedMessageResponse,
+}
+
+async fn list_saved(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<SavedQuery>,
+) -> AppResult<Json<SavedMessagesResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let (saved, has_more) = MessageService::list_saved(
+        &state.db,
+        user_id,
+        query.limit.unwrap_or(50),
+        query.before,
+    )
+    .await?;
+
+    Ok(Json(SavedMessagesResponse { saved, has_more }))
+}
+
+async fn save_message(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(message_id): Path<Uuid>,
+) -> AppResult<Json<SavedMessageWrapper>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let saved = MessageService::save_message(&state.db, message_id, user_id).await?;
+    Ok(Json(SavedMessageWrapper { saved }))
+}
+
+async fn unsave_message(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(message_id): Path<Uuid>,
+) -> AppResult<StatusCode> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    MessageService::unsave_message(&state.db, message_id, user_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
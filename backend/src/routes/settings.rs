@@ -11,11 +11,11 @@ use uuid::Uuid;
 use crate::{
     error::AppResult,
     models::{
-        AppearanceSettings, ChatSettings, DataStorageSettings, DeviceResponse,
-        NotificationSettings, PrivacySettings, ProfileResponse,
+        AppearanceSettings, ChatSettings, DataStorageSettings, DeviceNotificationPrefs,
+        DeviceNotificationSettings, DeviceResponse, NotificationSettings, PrivacySettings, ProfileResponse,
     },
     routes::auth::{extract_token, get_current_user_id},
-    services::SettingsService,
+    services::{SettingsService, WebSocketService},
     AppState,
 };
 
@@ -30,6 +30,10 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/appearance", get(get_appearance).put(update_appearance))
         .route("/devices", get(get_devices).delete(terminate_all_devices))
         .route("/devices/:device_id", delete(terminate_device))
+        .route(
+            "/devices/:device_id/notifications",
+            get(get_device_notifications).put(update_device_notifications),
+        )
 }
 
 // Profile
@@ -54,6 +58,8 @@ pub struct UpdateProfileRequest {
     bio: Option<String>,
     phone: Option<String>,
     email: Option<String>,
+    /// Profile version the client last saw; omit to overwrite unconditionally
+    version: Option<i32>,
 }
 
 async fn update_profile(
@@ -62,6 +68,7 @@ async fn update_profile(
     Json(req): Json<UpdateProfileRequest>,
 ) -> AppResult<Json<ProfileResponseWrapper>> {
     let user_id = get_current_user_id(&state, &headers).await?;
+    let identifiers_changed = req.username.is_some() || req.phone.is_some();
     let profile = SettingsService::update_profile(
         &state.db,
         user_id,
@@ -70,8 +77,15 @@ async fn update_profile(
         req.bio,
         req.phone,
         req.email,
+        req.version,
     )
     .await?;
+
+    if identifiers_changed {
+        if let Err(e) = WebSocketService::announce_contact_joined(&state, user_id).await {
+            tracing::warn!("Failed to match contact imports for user {}: {}", user_id, e);
+        }
+    }
     Ok(Json(ProfileResponseWrapper { profile }))
 }
 
@@ -103,6 +117,10 @@ pub struct UpdatePrivacyRequest {
     read_receipts: Option<bool>,
     #[serde(rename = "twoFactorAuth")]
     two_factor_auth: Option<bool>,
+    #[serde(rename = "linkPreviews")]
+    link_previews: Option<bool>,
+    /// Privacy version the client last saw; omit to overwrite unconditionally
+    version: Option<i32>,
 }
 
 async fn update_privacy(
@@ -121,6 +139,8 @@ async fn update_privacy(
         req.forwards,
         req.read_receipts,
         req.two_factor_auth,
+        req.link_previews,
+        req.version,
     )
     .await?;
     Ok(Json(PrivacyResponseWrapper { privacy }))
@@ -157,6 +177,8 @@ pub struct UpdateNotificationsRequest {
     in_app_preview: Option<bool>,
     #[serde(rename = "contactJoined")]
     contact_joined: Option<bool>,
+    #[serde(rename = "mentionsWhenMuted")]
+    mentions_when_muted: Option<bool>,
 }
 
 async fn update_notifications(
@@ -175,6 +197,7 @@ async fn update_notifications(
         req.in_app_vibrate,
         req.in_app_preview,
         req.contact_joined,
+        req.mentions_when_muted,
     )
     .await?;
     Ok(Json(NotificationsResponseWrapper { notifications }))
@@ -318,6 +341,8 @@ pub struct UpdateAppearanceRequest {
     bubble_style: Option<String>,
     #[serde(rename = "animationsEnabled")]
     animations_enabled: Option<bool>,
+    /// IANA timezone name; an empty string resets to UTC
+    timezone: Option<String>,
 }
 
 async fn update_appearance(
@@ -335,6 +360,7 @@ async fn update_appearance(
         req.chat_background,
         req.bubble_style,
         req.animations_enabled,
+        req.timezone,
     )
     .await?;
     Ok(Json(AppearanceResponseWrapper { appearance }))
@@ -352,10 +378,41 @@ async fn get_devices(
 ) -> AppResult<Json<DevicesResponseWrapper>> {
     let user_id = get_current_user_id(&state, &headers).await?;
     let token = extract_token(&headers)?;
-    let devices = SettingsService::get_devices(&state.db, user_id, &token).await?;
+    let mut devices = SettingsService::get_devices(&state.db, user_id, &token).await?;
+    devices.extend(
+        SettingsService::get_realtime_devices(&state.connection_manager, user_id).await,
+    );
     Ok(Json(DevicesResponseWrapper { devices }))
 }
 
+#[derive(Debug, Serialize)]
+pub struct DeviceNotificationsResponseWrapper {
+    notifications: DeviceNotificationSettings,
+}
+
+async fn get_device_notifications(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(device_id): Path<Uuid>,
+) -> AppResult<Json<DeviceNotificationsResponseWrapper>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+    let notifications =
+        SettingsService::get_device_notifications(&state.db, user_id, device_id).await?;
+    Ok(Json(DeviceNotificationsResponseWrapper { notifications }))
+}
+
+async fn update_device_notifications(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(device_id): Path<Uuid>,
+    Json(req): Json<DeviceNotificationPrefs>,
+) -> AppResult<Json<DeviceNotificationsResponseWrapper>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+    let notifications =
+        SettingsService::update_device_notifications(&state.db, user_id, device_id, req).await?;
+    Ok(Json(DeviceNotificationsResponseWrapper { notifications }))
+}
+
 #[derive(Debug, Serialize)]
 pub struct SimpleMessage {
     message: String,
@@ -368,7 +425,19 @@ async fn terminate_device(
 ) -> AppResult<Json<SimpleMessage>> {
     let user_id = get_current_user_id(&state, &headers).await?;
     let token = extract_token(&headers)?;
-    SettingsService::terminate_device(&state.db, user_id, device_id, &token).await?;
+    // Device ids of live QUIC sessions are their connection ids
+    if !SettingsService::terminate_realtime_device(&state.connection_manager, user_id, device_id)
+        .await
+    {
+        SettingsService::terminate_device(&state.db, user_id, device_id, &token).await?;
+        SettingsService::disconnect_session(
+            &state.connection_manager,
+            &state.ws_manager,
+            user_id,
+            device_id,
+        )
+        .await;
+    }
     Ok(Json(SimpleMessage {
         message: "Device session terminated".to_string(),
     }))
@@ -380,7 +449,17 @@ async fn terminate_all_devices(
 ) -> AppResult<Json<SimpleMessage>> {
     let user_id = get_current_user_id(&state, &headers).await?;
     let token = extract_token(&headers)?;
-    SettingsService::terminate_all_other_devices(&state.db, user_id, &token).await?;
+    let terminated =
+        SettingsService::terminate_all_other_devices(&state.db, user_id, &token).await?;
+    for session_id in terminated {
+        SettingsService::disconnect_session(
+            &state.connection_manager,
+            &state.ws_manager,
+            user_id,
+            session_id,
+        )
+        .await;
+    }
     Ok(Json(SimpleMessage {
         message: "All other sessions terminated".to_string(),
     }))
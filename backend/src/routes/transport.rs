@@ -0,0 +1,93 @@
+use axum::{extract::State, routing::get, Json, Router};
+use serde::Serialize;
+use std::sync::Arc;
+
+use crate::{
+    quic::{QuicServerConfig, TransportType},
+    AppState,
+};
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route("/capabilities", get(get_capabilities))
+}
+
+#[derive(Debug, Serialize)]
+pub struct TransportCapabilitiesResponse {
+    #[serde(rename = "quicEnabled")]
+    quic_enabled: bool,
+    /// UDP port of the QUIC server, `None` when QUIC is disabled
+    #[serde(rename = "quicPort")]
+    quic_port: Option<u16>,
+    /// ALPN protocol ids accepted by the QUIC server, in order of preference
+    alpn: Vec<String>,
+    /// Transport the server delivers events on first when both are connected
+    #[serde(rename = "preferredTransport")]
+    preferred_transport: TransportType,
+    /// Path to fall back to when QUIC is disabled or blocked
+    #[serde(rename = "websocketPath")]
+    websocket_path: &'static str,
+}
+
+impl TransportCapabilitiesResponse {
+    fn from_config(config: &QuicServerConfig) -> Self {
+        Self {
+            quic_enabled: config.enabled,
+            quic_port: config.enabled.then_some(config.port),
+            alpn: if config.enabled {
+                config.alpn_protocols.clone()
+            } else {
+                Vec::new()
+            },
+            preferred_transport: if config.enabled {
+                config.preferred_transport
+            } else {
+                TransportType::WebSocket
+            },
+            websocket_path: "/ws",
+        }
+    }
+}
+
+/// GET /api/v1/transport/capabilities - Which realtime transports a client
+/// can use, so it knows whether to try QUIC before WebSocket
+///
+/// Public: clients ask before they have a session to connect with.
+async fn get_capabilities(
+    State(state): State<Arc<AppState>>,
+) -> Json<TransportCapabilitiesResponse> {
+    Json(TransportCapabilitiesResponse::from_config(
+        &state.quic_config,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities_with_quic_enabled() {
+        let config = QuicServerConfig {
+            enabled: true,
+            port: 5000,
+            ..QuicServerConfig::default()
+        };
+        let json =
+            serde_json::to_value(TransportCapabilitiesResponse::from_config(&config)).unwrap();
+        assert_eq!(json["quicEnabled"], true);
+        assert_eq!(json["quicPort"], 5000);
+        assert_eq!(json["alpn"], serde_json::json!(["giano/1", "h3"]));
+        assert_eq!(json["preferredTransport"], "quic");
+        assert_eq!(json["websocketPath"], "/ws");
+    }
+
+    #[test]
+    fn test_capabilities_with_quic_disabled() {
+        let config = QuicServerConfig::default();
+        let json =
+            serde_json::to_value(TransportCapabilitiesResponse::from_config(&config)).unwrap();
+        assert_eq!(json["quicEnabled"], false);
+        assert!(json["quicPort"].is_null());
+        assert_eq!(json["alpn"], serde_json::json!([]));
+        assert_eq!(json["preferredTransport"], "websocket");
+    }
+}
@@ -0,0 +1,42 @@
+/// Unfurl Routes - link previews while composing.
+///
+/// This module provides:
+/// - POST /api/v1/unfurl - Preview a link before sending it
+///
+/// The response carries the OpenGraph metadata of the page and `cached`,
+/// which is true when the preview came from the cache instead of a fetch.
+use axum::{extract::State, http::HeaderMap, routing::post, Json, Router};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::{
+    error::AppResult, models::LinkPreviewResponse, routes::auth::get_current_user_id, AppState,
+};
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route("/", post(unfurl))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnfurlRequest {
+    url: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UnfurlResponse {
+    #[serde(flatten)]
+    preview: LinkPreviewResponse,
+    cached: bool,
+}
+
+async fn unfurl(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<UnfurlRequest>,
+) -> AppResult<Json<UnfurlResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+
+    let (preview, cached) = state.unfurl.unfurl(user_id, &req.url).await?;
+
+    Ok(Json(UnfurlResponse { preview, cached }))
+}
@@ -1,6 +1,6 @@
 use axum::{
     extract::{Multipart, State},
-    http::HeaderMap,
+    http::{HeaderMap, HeaderValue},
     routing::post,
     Json, Router,
 };
@@ -12,8 +12,9 @@ use tokio::io::AsyncWriteExt;
 
 use crate::{
     error::{AppError, AppResult},
-    models::AttachmentResponse,
+    models::{AttachmentResponse, Upload},
     routes::auth::get_current_user_id,
+    services::attachment::{image_dimensions, AttachmentService, UPLOAD_DIR},
     AppState,
 };
 
@@ -21,7 +22,12 @@ pub fn routes() -> Router<Arc<AppState>> {
     Router::new().route("/", post(upload_file))
 }
 
-const MAX_FILE_SIZE: usize = 500 * 1024 * 1024; // 500MB
+/// Room for multipart framing and the other form fields on top of
+/// `MAX_UPLOAD_BYTES` in the request body limit
+pub const MULTIPART_OVERHEAD_BYTES: usize = 64 * 1024;
+
+/// Bytes left in the uploader's rolling daily quota (absent when unlimited)
+pub const QUOTA_REMAINING_HEADER: &str = "x-upload-quota-remaining";
 
 #[derive(Debug, Serialize)]
 pub struct UploadResponse {
@@ -84,8 +90,9 @@ async fn upload_file(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     mut multipart: Multipart,
-) -> AppResult<Json<UploadResponse>> {
-    let _ = get_current_user_id(&state, &headers).await?;
+) -> AppResult<(HeaderMap, Json<UploadResponse>)> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+    state.upload_quota.check_rate(user_id).await?;
 
     let mut file_data: Option<Vec<u8>> = None;
     let mut file_name: Option<String> = None;
@@ -106,7 +113,7 @@ async fn upload_file(
                     AppError::Internal(anyhow::anyhow!("Failed to read file: {}", e))
                 })?;
 
-                if data.len() > MAX_FILE_SIZE {
+                if data.len() > state.config.max_upload_bytes {
                     return Err(AppError::FileTooLarge);
                 }
 
@@ -163,6 +170,8 @@ async fn upload_file(
         return Err(AppError::InvalidFileType);
     }
 
+    state.upload_quota.check_quota(user_id, data.len() as u64).await?;
+
     // Generate unique filename
     let file_id = Uuid::new_v4();
     let stored_name = format!("{}.{}", file_id, extension);
@@ -174,18 +183,80 @@ async fn upload_file(
     let base_url = state.config.base_url.as_deref().unwrap_or("http://localhost:3000");
     let url = format!("{}/uploads/{}", base_url, stored_name);
 
-    tracing::info!("File uploaded successfully: {} ({})", name, stored_name);
+    let (width, height) = match image_dimensions(&data) {
+        Some((width, height)) => (Some(width), Some(height)),
+        None => (None, None),
+    };
+    let upload = Upload {
+        id: file_id,
+        user_id,
+        attachment_type,
+        name,
+        size: data.len() as i64,
+        url,
+        stored_name,
+        mime_type: content_type,
+        width,
+        height,
+        created_at: chrono::Utc::now(),
+    };
+    AttachmentService::record_upload(&state.db, &upload).await?;
+
+    tracing::info!("File uploaded successfully: {} ({})", upload.name, upload.stored_name);
+
+    let mut response_headers = HeaderMap::new();
+    match state.upload_quota.record(user_id, data.len() as u64).await {
+        Ok(Some(remaining)) => {
+            response_headers.insert(QUOTA_REMAINING_HEADER, HeaderValue::from(remaining));
+        }
+        Ok(None) => {}
+        // The file is already stored; don't fail the upload over accounting
+        Err(e) => tracing::warn!("Failed to record upload quota for {}: {}", user_id, e),
+    }
 
-    Ok(Json(UploadResponse {
+    Ok((response_headers, Json(UploadResponse {
         attachment: AttachmentResponse {
-            id: file_id,
-            attachment_type,
-            name,
-            size: data.len() as i64,
-            url,
-            mime_type: content_type,
+            id: upload.id,
+            attachment_type: upload.attachment_type,
+            name: upload.name,
+            size: upload.size,
+            url: upload.url,
+            mime_type: upload.mime_type,
+            width: upload.width,
+            height: upload.height,
         },
-    }))
+    })))
+}
+
+/// Read the `shortcode` and `file` fields of a custom emoji upload form
+pub async fn read_custom_emoji_form(mut multipart: Multipart) -> AppResult<(String, Vec<u8>)> {
+    let mut shortcode: Option<String> = None;
+    let mut file_data: Option<Vec<u8>> = None;
+
+    while let Some(field) = multipart.next_field().await.map_err(|e| {
+        AppError::BadRequest(format!("Failed to read multipart: {}", e))
+    })? {
+        match field.name().unwrap_or("") {
+            "shortcode" => {
+                let text = field.text().await.map_err(|e| {
+                    AppError::BadRequest(format!("Failed to read shortcode: {}", e))
+                })?;
+                // Accept `:name:` as well as `name`
+                shortcode = Some(text.trim().trim_matches(':').to_string());
+            }
+            "file" => {
+                let data = field.bytes().await.map_err(|e| {
+                    AppError::BadRequest(format!("Failed to read file: {}", e))
+                })?;
+                file_data = Some(data.to_vec());
+            }
+            _ => {}
+        }
+    }
+
+    let shortcode = shortcode.ok_or_else(|| AppError::BadRequest("No shortcode provided".to_string()))?;
+    let data = file_data.ok_or_else(|| AppError::BadRequest("No file provided".to_string()))?;
+    Ok((shortcode, data))
 }
 
 fn detect_file_type(data: &[u8]) -> Option<&'static MagicBytes> {
@@ -217,7 +288,7 @@ fn is_text_file(extension: &str) -> bool {
 
 async fn store_file_locally(filename: &str, data: &[u8]) -> AppResult<()> {
     // Create uploads directory if it doesn't exist
-    let upload_dir = "uploads";
+    let upload_dir = UPLOAD_DIR;
     fs::create_dir_all(upload_dir).await.map_err(|e| {
         AppError::Internal(anyhow::anyhow!("Failed to create upload directory: {}", e))
     })?;
@@ -1,7 +1,7 @@
 use axum::{
     extract::{Path, Query, State},
     http::HeaderMap,
-    routing::get,
+    routing::{delete, get},
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
@@ -10,16 +10,22 @@ use uuid::Uuid;
 
 use crate::{
     error::AppResult,
-    models::UserPublic,
+    models::{RealtimeConnectionResponse, UserPublic},
     routes::auth::get_current_user_id,
-    services::UserService,
+    services::{SettingsService, UserService},
     AppState,
 };
 
+/// Header naming the realtime connection a client is making requests from
+/// (the id it got in `connected` or the QUIC auth response)
+pub const CONNECTION_ID_HEADER: &str = "x-connection-id";
+
 pub fn routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/", get(get_users))
         .route("/search", get(search_user_by_email))
+        .route("/me/connections", get(get_my_connections))
+        .route("/me/connections/:connection_id", delete(terminate_my_connection))
         .route("/:user_id", get(get_user))
 }
 
@@ -70,9 +76,9 @@ async fn get_user(
     Path(user_id): Path<Uuid>,
 ) -> AppResult<Json<UserResponse>> {
     // Verify authentication
-    let _ = get_current_user_id(&state, &headers).await?;
+    let current_user_id = get_current_user_id(&state, &headers).await?;
 
-    let user = UserService::get_user_by_id(&state.db, user_id).await?;
+    let user = UserService::get_user_for_viewer(&state.db, current_user_id, user_id).await?;
 
     Ok(Json(UserResponse { user }))
 }
@@ -102,3 +108,53 @@ async fn search_user_by_email(
 
     Ok(Json(UserResponse { user }))
 }
+
+#[derive(Debug, Serialize)]
+pub struct ConnectionsResponse {
+    connections: Vec<RealtimeConnectionResponse>,
+}
+
+/// GET /api/v1/users/me/connections - The caller's live QUIC and WebSocket connections
+async fn get_my_connections(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> AppResult<Json<ConnectionsResponse>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+    let current = headers
+        .get(CONNECTION_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| Uuid::parse_str(v.trim()).ok());
+
+    let connections = SettingsService::get_realtime_connections(
+        &state.connection_manager,
+        &state.ws_manager,
+        user_id,
+        current,
+    )
+    .await;
+    Ok(Json(ConnectionsResponse { connections }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimpleMessage {
+    message: String,
+}
+
+/// DELETE /api/v1/users/me/connections/:connection_id - Close one of the caller's connections
+async fn terminate_my_connection(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(connection_id): Path<Uuid>,
+) -> AppResult<Json<SimpleMessage>> {
+    let user_id = get_current_user_id(&state, &headers).await?;
+    SettingsService::terminate_realtime_connection(
+        &state.connection_manager,
+        &state.ws_manager,
+        user_id,
+        connection_id,
+    )
+    .await?;
+    Ok(Json(SimpleMessage {
+        message: "Connection terminated".to_string(),
+    }))
+}
@@ -0,0 +1,132 @@
+/// Admin Stats Service
+///
+/// Row counts for the admin dashboard. Counting whole tables is not free, so
+/// results are cached for a short TTL; concurrent callers wait on the same
+/// refresh instead of each running the queries.
+
+use crate::{db::Database, error::AppResult};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// How long entity counts are served from cache
+pub const ENTITY_COUNTS_TTL: Duration = Duration::from_secs(30);
+
+/// Row counts of the main tables
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityCounts {
+    pub users: i64,
+    pub chats: i64,
+    pub bots: i64,
+    pub messages: i64,
+    /// When these counts were taken
+    pub counted_at: DateTime<Utc>,
+}
+
+pub struct EntityCountsCache {
+    ttl: Duration,
+    cached: Mutex<Option<(Instant, EntityCounts)>>,
+}
+
+impl EntityCountsCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Current counts, recounted at most once per TTL
+    pub async fn get(&self, db: &Database) -> AppResult<EntityCounts> {
+        self.get_with(|| count_entities(db)).await
+    }
+
+    async fn get_with<F, Fut>(&self, load: F) -> AppResult<EntityCounts>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = AppResult<EntityCounts>>,
+    {
+        // Held across the reload so concurrent callers share one refresh
+        let mut cached = self.cached.lock().await;
+        if let Some((at, counts)) = cached.as_ref() {
+            if at.elapsed() < self.ttl {
+                return Ok(counts.clone());
+            }
+        }
+
+        let counts = load().await?;
+        *cached = Some((Instant::now(), counts.clone()));
+        Ok(counts)
+    }
+}
+
+async fn count_entities(db: &Database) -> AppResult<EntityCounts> {
+    let (users, chats, bots, messages): (i64, i64, i64, i64) = sqlx::query_as(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM users),
+            (SELECT COUNT(*) FROM chats),
+            (SELECT COUNT(*) FROM bots),
+            (SELECT COUNT(*) FROM messages)
+        "#,
+    )
+    .fetch_one(&db.pool)
+    .await?;
+
+    Ok(EntityCounts {
+        users,
+        chats,
+        bots,
+        messages,
+        counted_at: Utc::now(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn counts(users: i64) -> EntityCounts {
+        EntityCounts {
+            users,
+            chats: 0,
+            bots: 0,
+            messages: 0,
+            counted_at: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_counts_are_cached_within_ttl() {
+        let cache = EntityCountsCache::new(Duration::from_secs(60));
+        let loads = AtomicUsize::new(0);
+        let loads_ref = &loads;
+        let load = move || async move {
+            let n = loads_ref.fetch_add(1, Ordering::SeqCst) as i64;
+            Ok(counts(n))
+        };
+
+        assert_eq!(cache.get_with(load).await.unwrap().users, 0);
+        assert_eq!(cache.get_with(load).await.unwrap().users, 0);
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_counts_reload_after_ttl() {
+        let cache = EntityCountsCache::new(Duration::ZERO);
+        let loads = AtomicUsize::new(0);
+        let loads_ref = &loads;
+        let load = move || async move {
+            let n = loads_ref.fetch_add(1, Ordering::SeqCst) as i64;
+            Ok(counts(n))
+        };
+
+        cache.get_with(load).await.unwrap();
+        assert_eq!(cache.get_with(load).await.unwrap().users, 1);
+        assert_eq!(loads.load(Ordering::SeqCst), 2);
+    }
+}
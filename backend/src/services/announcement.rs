@@ -0,0 +1,273 @@
+/// System Announcement Service
+///
+/// Admins broadcast `system_announcement` events to every connected user or
+/// to the participants of one chat, over both WebSocket and QUIC. A chat
+/// announcement can also be kept in the chat history as a system message,
+/// and offline targets can be reached by web push.
+///
+/// Announcements are spaced at least `ANNOUNCEMENT_MIN_INTERVAL_SECONDS`
+/// apart and every one is written to `audit_log`.
+use uuid::Uuid;
+
+use crate::{
+    error::{AppError, AppResult},
+    models::{AnnouncementLevel, MessageResponse},
+    quic::TransportType,
+    services::{content::normalize_text, ChatService, MessageService, WebSocketService},
+    ws::events::ServerEvent,
+    AppState,
+};
+
+/// `audit_log.action` for a system announcement
+pub const AUDIT_ANNOUNCEMENT: &str = "announcement";
+
+/// Longest announcement text, in bytes after normalization
+pub const MAX_ANNOUNCEMENT_BYTES: usize = 4096;
+
+/// Who an announcement goes to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnouncementTarget {
+    AllUsers,
+    Chat(Uuid),
+}
+
+/// What an announcement reached
+#[derive(Debug, Clone, Default)]
+pub struct AnnouncementReport {
+    /// Users reached on at least one WebSocket connection
+    pub websocket_users: usize,
+    /// Users reached on at least one QUIC connection
+    pub quic_users: usize,
+    /// Offline users handed to web push
+    pub push_users: usize,
+    /// The system message, when the announcement was persisted
+    pub message: Option<MessageResponse>,
+}
+
+pub struct AnnouncementService;
+
+impl AnnouncementService {
+    /// Validate, rate-limit, audit and broadcast an announcement
+    pub async fn announce(
+        state: &AppState,
+        admin_id: Uuid,
+        text: &str,
+        level: AnnouncementLevel,
+        target: AnnouncementTarget,
+        persist: bool,
+        push: bool,
+    ) -> AppResult<AnnouncementReport> {
+        let text = normalize_text(text, MAX_ANNOUNCEMENT_BYTES)?;
+
+        let recipients = match target {
+            AnnouncementTarget::AllUsers => {
+                if persist {
+                    return Err(AppError::BadRequest(
+                        "Only chat announcements can be persisted".to_string(),
+                    ));
+                }
+                None
+            }
+            AnnouncementTarget::Chat(chat_id) => {
+                let participant_ids = ChatService::get_participant_ids(&state.db, chat_id).await?;
+                if participant_ids.is_empty() {
+                    return Err(AppError::ChatNotFound);
+                }
+                Some(participant_ids)
+            }
+        };
+
+        Self::record(state, admin_id, &text, level, target).await?;
+
+        let mut report = AnnouncementReport::default();
+        if let (AnnouncementTarget::Chat(chat_id), true, Some(participant_ids)) =
+            (target, persist, &recipients)
+        {
+            let message =
+                MessageService::create_system_message(&state.db, chat_id, admin_id, &text).await?;
+            WebSocketService::broadcast_new_message(
+                &state.ws_manager,
+                message.clone(),
+                participant_ids,
+                admin_id,
+            )
+            .await;
+            report.message = Some(message);
+        }
+
+        let event = ServerEvent::SystemAnnouncement {
+            text: text.clone(),
+            level,
+        };
+        report.websocket_users = match &recipients {
+            None => state.ws_manager.broadcast_to_all(event.clone()).await,
+            Some(participant_ids) => {
+                let mut reached = 0;
+                for user_id in participant_ids {
+                    if state.ws_manager.is_user_online(*user_id).await {
+                        state.ws_manager.send_to_user(*user_id, event.clone()).await;
+                        reached += 1;
+                    }
+                }
+                reached
+            }
+        };
+        report.quic_users = Self::send_over_quic(state, &event, recipients.as_deref()).await;
+
+        if push && state.push.is_enabled() {
+            let offline = Self::offline_targets(state, recipients.as_deref()).await?;
+            report.push_users = offline.len();
+            state
+                .push
+                .spawn_for_announcement(state.db.clone(), &text, level, offline);
+        }
+
+        tracing::warn!(
+            "Announcement ({}) by admin {} to {:?}: websocket={}, quic={}, push={}",
+            level.as_str(),
+            admin_id,
+            target,
+            report.websocket_users,
+            report.quic_users,
+            report.push_users
+        );
+
+        Ok(report)
+    }
+
+    /// Write the audit entry, refusing if the previous announcement is more
+    /// recent than the configured interval
+    async fn record(
+        state: &AppState,
+        admin_id: Uuid,
+        text: &str,
+        level: AnnouncementLevel,
+        target: AnnouncementTarget,
+    ) -> AppResult<()> {
+        let interval = state.config.announcement_min_interval_seconds as f64;
+        let chat_id = match target {
+            AnnouncementTarget::Chat(chat_id) => Some(chat_id),
+            AnnouncementTarget::AllUsers => None,
+        };
+
+        let inserted: Option<Uuid> = sqlx::query_scalar(
+            r#"
+            INSERT INTO audit_log (chat_id, actor_id, action, details)
+            SELECT $1, $2, $3, $4
+            WHERE NOT EXISTS (
+                SELECT 1 FROM audit_log
+                WHERE action = $3 AND created_at > NOW() - make_interval(secs => $5)
+            )
+            RETURNING id
+            "#,
+        )
+        .bind(chat_id)
+        .bind(admin_id)
+        .bind(AUDIT_ANNOUNCEMENT)
+        .bind(serde_json::json!({
+            "text": text,
+            "level": level.as_str(),
+        }))
+        .bind(interval)
+        .fetch_optional(&state.db.pool)
+        .await?;
+
+        if inserted.is_some() {
+            return Ok(());
+        }
+
+        let retry_after: Option<f64> = sqlx::query_scalar(
+            r#"
+            SELECT EXTRACT(EPOCH FROM (MAX(created_at) + make_interval(secs => $2) - NOW()))::float8
+            FROM audit_log WHERE action = $1
+            "#,
+        )
+        .bind(AUDIT_ANNOUNCEMENT)
+        .bind(interval)
+        .fetch_one(&state.db.pool)
+        .await?;
+
+        Err(AppError::AnnouncementRateLimited(
+            retry_after.unwrap_or(0.0).ceil().max(1.0) as u32,
+        ))
+    }
+
+    /// Deliver to QUIC-connected targets (all of them when `recipients` is
+    /// `None`), returning how many users were reached
+    async fn send_over_quic(
+        state: &AppState,
+        event: &ServerEvent,
+        recipients: Option<&[Uuid]>,
+    ) -> usize {
+        let data = match serde_json::to_vec(event) {
+            Ok(data) => data,
+            Err(e) => {
+                tracing::error!("Failed to serialize announcement: {}", e);
+                return 0;
+            }
+        };
+
+        let mut reached = 0;
+        for user_id in state
+            .connection_manager
+            .users_on_transport(TransportType::Quic)
+            .await
+        {
+            if recipients.is_some_and(|ids| !ids.contains(&user_id)) {
+                continue;
+            }
+            match state
+                .connection_manager
+                .send_to_transport_type(user_id, TransportType::Quic, &data)
+                .await
+            {
+                Ok(_) => reached += 1,
+                Err(e) => tracing::warn!("Announcement to user {} over QUIC failed: {}", user_id, e),
+            }
+        }
+        reached
+    }
+
+    /// Targets with no live connection and at least one push subscription
+    async fn offline_targets(
+        state: &AppState,
+        recipients: Option<&[Uuid]>,
+    ) -> AppResult<Vec<Uuid>> {
+        let subscribed: Vec<Uuid> = sqlx::query_scalar(
+            r#"
+            SELECT DISTINCT user_id FROM push_subscriptions
+            WHERE $1::uuid[] IS NULL OR user_id = ANY($1)
+            "#,
+        )
+        .bind(recipients)
+        .fetch_all(&state.db.pool)
+        .await?;
+
+        let mut offline = Vec::new();
+        for user_id in subscribed {
+            if !state.ws_manager.is_user_online(user_id).await
+                && !state.connection_manager.is_user_connected(user_id).await
+            {
+                offline.push(user_id);
+            }
+        }
+        Ok(offline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_announcement_event_shape() {
+        let event = ServerEvent::SystemAnnouncement {
+            text: "Restarting soon".to_string(),
+            level: AnnouncementLevel::Warning,
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], "system_announcement");
+        assert_eq!(json["data"]["level"], "warning");
+        assert_eq!(json["data"]["text"], "Restarting soon");
+    }
+}
@@ -0,0 +1,244 @@
+/// Attachment Service
+///
+/// Every file accepted by `POST /upload` is recorded in `uploads` with its
+/// uploader. Messages reference uploads by id; `resolve` turns those ids into
+/// attachment rows, accepting only the sender's own recent, not yet attached
+/// uploads within the per-message limits.
+
+use crate::{
+    config::Config,
+    db::Database,
+    error::{AppError, AppResult},
+    models::Upload,
+};
+use uuid::Uuid;
+
+/// Per-message attachment limits taken from `Config`
+#[derive(Debug, Clone, Copy)]
+pub struct AttachmentLimits {
+    /// Most attachments on a single message
+    pub max_count: usize,
+    /// Largest combined attachment size on a single message
+    pub max_total_bytes: i64,
+}
+
+impl From<&Config> for AttachmentLimits {
+    fn from(config: &Config) -> Self {
+        Self {
+            max_count: config.max_attachments_per_message,
+            max_total_bytes: config.max_attachment_bytes_per_message,
+        }
+    }
+}
+
+/// Uploads older than this can no longer be attached
+pub const ATTACHABLE_UPLOAD_HOURS: i32 = 24;
+
+/// Directory uploaded files are stored in (served under `/uploads`)
+pub const UPLOAD_DIR: &str = "uploads";
+
+pub struct AttachmentService;
+
+impl AttachmentService {
+    /// Record a stored upload so it can be attached to a message
+    pub async fn record_upload(db: &Database, upload: &Upload) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO uploads (id, user_id, type, name, size, url, stored_name, mime_type, width, height)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            "#,
+        )
+        .bind(upload.id)
+        .bind(upload.user_id)
+        .bind(&upload.attachment_type)
+        .bind(&upload.name)
+        .bind(upload.size)
+        .bind(&upload.url)
+        .bind(&upload.stored_name)
+        .bind(&upload.mime_type)
+        .bind(upload.width)
+        .bind(upload.height)
+        .execute(&db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Look up the uploads `sender_id` wants to attach, in request order
+    ///
+    /// Rejects unknown ids, other users' uploads, uploads older than
+    /// `ATTACHABLE_UPLOAD_HOURS`, uploads already attached elsewhere, and
+    /// messages over the count or size limits.
+    pub async fn resolve(
+        db: &Database,
+        sender_id: Uuid,
+        upload_ids: &[Uuid],
+        limits: AttachmentLimits,
+    ) -> AppResult<Vec<Upload>> {
+        let mut ids: Vec<Uuid> = Vec::with_capacity(upload_ids.len());
+        for id in upload_ids {
+            if !ids.contains(id) {
+                ids.push(*id);
+            }
+        }
+
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        if ids.len() > limits.max_count {
+            return Err(AppError::BadRequest(format!(
+                "A message can have at most {} attachments",
+                limits.max_count
+            )));
+        }
+
+        let mut uploads: Vec<Upload> = sqlx::query_as(
+            r#"
+            SELECT * FROM uploads u
+            WHERE u.id = ANY($1) AND u.user_id = $2
+              AND u.created_at > NOW() - make_interval(hours => $3)
+              AND NOT EXISTS (SELECT 1 FROM attachments a WHERE a.upload_id = u.id)
+            "#,
+        )
+        .bind(&ids)
+        .bind(sender_id)
+        .bind(ATTACHABLE_UPLOAD_HOURS)
+        .fetch_all(&db.pool)
+        .await?;
+
+        if uploads.len() != ids.len() {
+            return Err(AppError::BadRequest(
+                "Attachments must be your own recent uploads".to_string(),
+            ));
+        }
+
+        let total: i64 = uploads.iter().map(|u| u.size).sum();
+        if total > limits.max_total_bytes {
+            return Err(AppError::FileTooLarge);
+        }
+
+        uploads.sort_by_key(|u| ids.iter().position(|id| *id == u.id));
+        Ok(uploads)
+    }
+
+    /// Stored file names of the uploads attached to `message_id`
+    pub async fn stored_files(db: &Database, message_id: Uuid) -> AppResult<Vec<String>> {
+        let files = sqlx::query_scalar(
+            r#"
+            SELECT u.stored_name FROM attachments a
+            JOIN uploads u ON u.id = a.upload_id
+            WHERE a.message_id = $1
+            "#,
+        )
+        .bind(message_id)
+        .fetch_all(&db.pool)
+        .await?;
+
+        Ok(files)
+    }
+
+    /// Delete stored files in the background; missing files are ignored
+    pub fn spawn_remove_files(stored_names: Vec<String>) {
+        if stored_names.is_empty() {
+            return;
+        }
+
+        tokio::spawn(async move {
+            for name in stored_names {
+                // Stored names are `{uuid}.{ext}`; never follow anything else
+                if name.contains('/') || name.contains('\\') || name.starts_with('.') {
+                    continue;
+                }
+                let path = format!("{}/{}", UPLOAD_DIR, name);
+                match tokio::fs::remove_file(&path).await {
+                    Ok(()) => tracing::debug!("Removed attachment file {}", path),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => tracing::warn!("Failed to remove attachment file {}: {}", path, e),
+                }
+            }
+        });
+    }
+}
+
+/// Pixel dimensions of PNG, GIF, BMP and JPEG images, read from the header
+pub fn image_dimensions(data: &[u8]) -> Option<(i32, i32)> {
+    let be16 = |i: usize| data.get(i..i + 2).map(|b| u16::from_be_bytes([b[0], b[1]]) as i32);
+    let le16 = |i: usize| data.get(i..i + 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as i32);
+    let be32 = |i: usize| {
+        data.get(i..i + 4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    let le32 = |i: usize| {
+        data.get(i..i + 4)
+            .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+
+    if data.starts_with(&[0x89, b'P', b'N', b'G']) {
+        let (width, height) = (be32(16)?, be32(20)?);
+        return Some((i32::try_from(width).ok()?, i32::try_from(height).ok()?));
+    }
+    if data.starts_with(b"GIF8") {
+        return Some((le16(6)?, le16(8)?));
+    }
+    if data.starts_with(b"BM") {
+        // Negative height marks a top-down bitmap
+        return Some((le32(18)?.checked_abs()?, le32(22)?.checked_abs()?));
+    }
+    if data.starts_with(&[0xFF, 0xD8]) {
+        // Walk the segments up to the first start-of-frame marker
+        let mut i = 2;
+        while data.get(i) == Some(&0xFF) {
+            let marker = *data.get(i + 1)?;
+            let length = be16(i + 2)? as usize;
+            let is_sof = matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+            if is_sof {
+                return Some((be16(i + 7)?, be16(i + 5)?));
+            }
+            i += 2 + length;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_png_dimensions() {
+        let mut png = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        png.extend_from_slice(&[0, 0, 0, 13]);
+        png.extend_from_slice(b"IHDR");
+        png.extend_from_slice(&640u32.to_be_bytes());
+        png.extend_from_slice(&480u32.to_be_bytes());
+        assert_eq!(image_dimensions(&png), Some((640, 480)));
+    }
+
+    #[test]
+    fn test_gif_dimensions() {
+        let mut gif = b"GIF89a".to_vec();
+        gif.extend_from_slice(&320u16.to_le_bytes());
+        gif.extend_from_slice(&200u16.to_le_bytes());
+        assert_eq!(image_dimensions(&gif), Some((320, 200)));
+    }
+
+    #[test]
+    fn test_jpeg_dimensions_skip_leading_segments() {
+        let mut jpeg = vec![0xFF, 0xD8];
+        // APP0 segment with 14 bytes of payload
+        jpeg.extend_from_slice(&[0xFF, 0xE0, 0x00, 0x10]);
+        jpeg.extend_from_slice(&[0; 14]);
+        // SOF0: length, precision, height, width
+        jpeg.extend_from_slice(&[0xFF, 0xC0, 0x00, 0x11, 0x08]);
+        jpeg.extend_from_slice(&768u16.to_be_bytes());
+        jpeg.extend_from_slice(&1024u16.to_be_bytes());
+        assert_eq!(image_dimensions(&jpeg), Some((1024, 768)));
+    }
+
+    #[test]
+    fn test_unknown_or_truncated_images() {
+        assert_eq!(image_dimensions(b"%PDF-1.7"), None);
+        assert_eq!(image_dimensions(&[0x89, b'P', b'N', b'G']), None);
+        assert_eq!(image_dimensions(&[0xFF, 0xD8, 0xFF, 0xE0]), None);
+    }
+}
@@ -4,7 +4,7 @@ use crate::{
     models::{Session, User, UserSession},
 };
 use argon2::{password_hash::SaltString, Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use rand::rngs::OsRng;
 use serde::{Deserialize, Serialize};
@@ -18,6 +18,11 @@ pub struct Claims {
     pub exp: i64,
     pub iat: i64,
     pub jti: String,
+    /// Session (device) the token was issued to; kept across refreshes so a
+    /// reconnecting client stays bound to it. Absent in tokens issued before
+    /// tokens were bound to sessions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<Uuid>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -49,12 +54,13 @@ impl AuthService {
             .is_ok())
     }
 
+    /// Issue an access token bound to `session_id`
     pub fn generate_token(
         user: &User,
+        session_id: Uuid,
         secret: &str,
         expiration_hours: i64,
-    ) -> AppResult<(String, i64, Uuid)> {
-        let session_id = Uuid::new_v4();
+    ) -> AppResult<(String, i64)> {
         let now = Utc::now();
         let exp = now + Duration::hours(expiration_hours);
 
@@ -64,7 +70,8 @@ impl AuthService {
             name: user.name.clone(),
             exp: exp.timestamp(),
             iat: now.timestamp(),
-            jti: session_id.to_string(),
+            jti: Uuid::new_v4().to_string(),
+            sid: Some(session_id),
         };
 
         let token = encode(
@@ -74,7 +81,7 @@ impl AuthService {
         )
         .map_err(|e| AppError::Internal(anyhow::anyhow!("Token generation failed: {}", e)))?;
 
-        Ok((token, exp.timestamp_millis(), session_id))
+        Ok((token, exp.timestamp_millis()))
     }
 
     /// Generate refresh token (long-lived, 7 days default)
@@ -199,8 +206,9 @@ impl AuthService {
         .await?;
 
         // Generate access token (short-lived, 15 minutes)
-        let (token, expires_at, session_id) =
-            Self::generate_token(&user, jwt_secret, 1)?; // 1 hour for now
+        let session_id = Uuid::new_v4();
+        let (token, expires_at) =
+            Self::generate_token(&user, session_id, jwt_secret, 1)?; // 1 hour for now
         
         // Generate refresh token (long-lived, 7 days)
         let (refresh_token, refresh_expires_at) =
@@ -254,15 +262,18 @@ impl AuthService {
             return Err(AppError::InvalidCredentials);
         }
 
-        // Update status to online
-        sqlx::query("UPDATE users SET status = 'online', last_seen = NULL WHERE id = $1")
+        // Update status to online (invisible users keep appearing offline)
+        sqlx::query(
+            "UPDATE users SET status = 'online', last_seen = NULL WHERE id = $1 AND presence <> 'invisible'",
+        )
             .bind(user.id)
             .execute(&db.pool)
             .await?;
 
         // Generate access token (short-lived, 1 hour)
-        let (token, expires_at, session_id) =
-            Self::generate_token(&user, jwt_secret, 1)?; // 1 hour
+        let session_id = Uuid::new_v4();
+        let (token, expires_at) =
+            Self::generate_token(&user, session_id, jwt_secret, 1)?; // 1 hour
         
         // Generate refresh token (long-lived, 7 days)
         let (refresh_token, refresh_expires_at) =
@@ -327,9 +338,9 @@ impl AuthService {
             .fetch_one(&db.pool)
             .await?;
 
-        // Generate new access token (1 hour)
-        let (new_token, new_expires_at, _) =
-            Self::generate_token(&user, jwt_secret, 1)?;
+        // Generate new access token (1 hour), still bound to the same session
+        let (new_token, new_expires_at) =
+            Self::generate_token(&user, session_id, jwt_secret, 1)?;
 
         // Update session with new access token
         sqlx::query(
@@ -364,6 +375,79 @@ impl AuthService {
         Ok(())
     }
 
+    /// Invalidate every token issued to a user so far and drop their sessions
+    ///
+    /// Returns the number of sessions removed. Tokens issued after this call
+    /// (a fresh login) are unaffected.
+    pub async fn revoke_user_tokens(db: &Database, user_id: Uuid) -> AppResult<u64> {
+        let mut tx = db.pool.begin().await?;
+
+        let updated = sqlx::query("UPDATE users SET tokens_revoked_at = NOW() WHERE id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        if updated.rows_affected() == 0 {
+            return Err(AppError::UserNotFound);
+        }
+
+        let deleted = sqlx::query("DELETE FROM sessions WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(deleted.rows_affected())
+    }
+
+    /// Whether a token issued at `issued_at` (unix seconds) was revoked
+    pub async fn is_token_revoked(db: &Database, user_id: Uuid, issued_at: i64) -> AppResult<bool> {
+        let revoked_at: Option<Option<DateTime<Utc>>> =
+            sqlx::query_scalar("SELECT tokens_revoked_at FROM users WHERE id = $1")
+                .bind(user_id)
+                .fetch_optional(&db.pool)
+                .await?;
+
+        Ok(is_revoked_at(revoked_at.flatten(), issued_at))
+    }
+
+    /// Whether a realtime connection authenticated with a token may stay open
+    ///
+    /// Refused when the user's tokens were revoked or the token's session was
+    /// terminated. Tokens without a session are refused when `require_session`
+    /// is set.
+    pub async fn is_realtime_access_revoked(
+        db: &Database,
+        user_id: Uuid,
+        session_id: Option<Uuid>,
+        issued_at: i64,
+        require_session: bool,
+    ) -> AppResult<bool> {
+        if Self::is_token_revoked(db, user_id, issued_at).await? {
+            return Ok(true);
+        }
+        match session_id {
+            Some(session_id) => Ok(!Self::is_session_active(db, user_id, session_id).await?),
+            None => Ok(require_session),
+        }
+    }
+
+    /// Whether the session still exists (it was not logged out or terminated)
+    pub async fn is_session_active(
+        db: &Database,
+        user_id: Uuid,
+        session_id: Uuid,
+    ) -> AppResult<bool> {
+        let active: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM sessions WHERE id = $1 AND user_id = $2)",
+        )
+        .bind(session_id)
+        .bind(user_id)
+        .fetch_one(&db.pool)
+        .await?;
+
+        Ok(active)
+    }
+
     pub async fn get_session(
         db: &Database,
         user_id: Uuid,
@@ -394,3 +478,64 @@ impl AuthService {
         })
     }
 }
+
+/// Tokens only carry whole seconds, so one issued in the same second as the
+/// revocation is treated as revoked
+fn is_revoked_at(revoked_at: Option<DateTime<Utc>>, issued_at: i64) -> bool {
+    revoked_at.is_some_and(|revoked_at| issued_at <= revoked_at.timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user() -> User {
+        User {
+            id: Uuid::new_v4(),
+            email: "alice@example.com".to_string(),
+            password_hash: String::new(),
+            name: "Alice".to_string(),
+            username: None,
+            avatar: None,
+            bio: None,
+            phone: None,
+            status: "online".to_string(),
+            last_seen: None,
+            is_bot: false,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            profile_version: 0,
+        }
+    }
+
+    #[test]
+    fn test_tokens_are_bound_to_their_session() {
+        let secret = "s".repeat(32);
+        let session_id = Uuid::new_v4();
+
+        let (token, _) = AuthService::generate_token(&user(), session_id, &secret, 1).unwrap();
+        let claims = AuthService::verify_token(&token, &secret).unwrap();
+        assert_eq!(claims.sid, Some(session_id));
+
+        // Tokens issued before sessions were embedded still verify, unbound
+        let legacy = Claims { sid: None, ..claims };
+        let token = encode(
+            &Header::default(),
+            &legacy,
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap();
+        assert_eq!(AuthService::verify_token(&token, &secret).unwrap().sid, None);
+    }
+
+    #[test]
+    fn test_is_revoked_at() {
+        let revoked_at = Utc::now();
+        let ts = revoked_at.timestamp();
+
+        assert!(!is_revoked_at(None, ts));
+        assert!(is_revoked_at(Some(revoked_at), ts - 60));
+        assert!(is_revoked_at(Some(revoked_at), ts));
+        assert!(!is_revoked_at(Some(revoked_at), ts + 1));
+    }
+}
@@ -2,7 +2,7 @@ use crate::{
     db::Database,
     error::{AppError, AppResult},
     models::{InlineButton, Message, MessageResponse},
-    services::{ChatService, MessageService},
+    services::{timezone, ChatService, MessageService},
 };
 use chrono::Utc;
 use uuid::Uuid;
@@ -27,7 +27,9 @@ impl BotService {
         }
 
         // Generate bot response based on message content
-        let (response_text, inline_keyboard) = Self::generate_bot_response(message_text);
+        let tz = timezone::user_timezone(db, user_id).await?;
+        let (response_text, inline_keyboard) =
+            Self::generate_bot_response(message_text, tz.as_deref());
 
         // Create the bot's response message
         let message = Self::create_bot_message(db, chat_id, bot_id, &response_text, inline_keyboard).await?;
@@ -70,7 +72,9 @@ impl BotService {
         }
 
         // Generate response based on callback data
-        let (response_text, inline_keyboard) = Self::generate_callback_response(callback_data);
+        let tz = timezone::user_timezone(db, user_id).await?;
+        let (response_text, inline_keyboard) =
+            Self::generate_callback_response(callback_data, tz.as_deref());
 
         // Create the bot's response message
         let message = Self::create_bot_message(db, chat_id, bot_id, &response_text, inline_keyboard).await?;
@@ -104,8 +108,11 @@ impl BotService {
         // Create the message
         let message: Message = sqlx::query_as(
             r#"
-            INSERT INTO messages (chat_id, sender_id, text, delivery_status)
-            VALUES ($1, $2, $3, 'sent')
+            INSERT INTO messages (chat_id, sender_id, text, delivery_status, delete_at)
+            VALUES ($1, $2, $3, 'sent', (
+                SELECT NOW() + make_interval(secs => disappear_after_seconds)
+                FROM chats WHERE id = $1 AND disappear_after_seconds > 0
+            ))
             RETURNING *
             "#,
         )
@@ -133,7 +140,10 @@ impl BotService {
     }
 
     /// Generate a bot response based on the input message
-    fn generate_bot_response(message: &str) -> (String, Option<Vec<Vec<InlineButton>>>) {
+    fn generate_bot_response(
+        message: &str,
+        tz: Option<&str>,
+    ) -> (String, Option<Vec<Vec<InlineButton>>>) {
         let message_lower = message.to_lowercase();
 
         if message_lower.contains("help") || message_lower == "/help" {
@@ -190,9 +200,9 @@ impl BotService {
             
             (text.to_string(), Some(keyboard))
         } else if message_lower.contains("stats") || message_lower == "/stats" {
-            Self::generate_stats_response()
+            Self::generate_stats_response(tz)
         } else if message_lower.contains("settings") || message_lower == "/settings" {
-            Self::generate_settings_response()
+            Self::generate_settings_response(tz)
         } else {
             // Default response
             let text = "I received your message. How can I help you?\n\n\
@@ -214,17 +224,20 @@ impl BotService {
 
 
     /// Generate a response for inline keyboard callback
-    fn generate_callback_response(callback_data: &str) -> (String, Option<Vec<Vec<InlineButton>>>) {
+    fn generate_callback_response(
+        callback_data: &str,
+        tz: Option<&str>,
+    ) -> (String, Option<Vec<Vec<InlineButton>>>) {
         match callback_data {
-            "stats" => Self::generate_stats_response(),
+            "stats" => Self::generate_stats_response(tz),
             "daily" => Self::generate_daily_stats_response(),
             "weekly" => Self::generate_weekly_stats_response(),
-            "settings" => Self::generate_settings_response(),
+            "settings" => Self::generate_settings_response(tz),
             "notifications_on" | "notifications_off" => {
                 Self::generate_notification_toggle_response(callback_data == "notifications_on")
             }
-            "help" => Self::generate_bot_response("/help"),
-            "refresh" => Self::generate_stats_response(),
+            "help" => Self::generate_bot_response("/help", tz),
+            "refresh" => Self::generate_stats_response(tz),
             "back" => Self::generate_main_menu_response(),
             _ => {
                 let text = format!("Received callback: {}\n\nThis action is not yet implemented.", callback_data);
@@ -242,7 +255,7 @@ impl BotService {
         }
     }
 
-    fn generate_stats_response() -> (String, Option<Vec<Vec<InlineButton>>>) {
+    fn generate_stats_response(tz: Option<&str>) -> (String, Option<Vec<Vec<InlineButton>>>) {
         let now = Utc::now();
         let text = format!(
             "📊 *Your Statistics*\n\n\
@@ -251,7 +264,7 @@ impl BotService {
             👥 Active chats: 8\n\
             ⏱️ Time active: 24h 30m\n\n\
             Last updated: {}",
-            now.format("%Y-%m-%d %H:%M UTC")
+            timezone::format_timestamp(now, tz)
         );
         
         let keyboard = vec![
@@ -351,12 +364,15 @@ impl BotService {
         (text.to_string(), Some(keyboard))
     }
 
-    fn generate_settings_response() -> (String, Option<Vec<Vec<InlineButton>>>) {
-        let text = "⚙️ *Bot Settings*\n\n\
+    fn generate_settings_response(tz: Option<&str>) -> (String, Option<Vec<Vec<InlineButton>>>) {
+        let text = format!(
+            "⚙️ *Bot Settings*\n\n\
             Configure your bot preferences:\n\n\
             🔔 Notifications: Enabled\n\
             🌐 Language: English\n\
-            🕐 Timezone: UTC";
+            🕐 Timezone: {}",
+            tz.unwrap_or("UTC")
+        );
         
         let keyboard = vec![
             vec![
@@ -375,7 +391,7 @@ impl BotService {
             ],
         ];
         
-        (text.to_string(), Some(keyboard))
+        (text, Some(keyboard))
     }
 
     fn generate_notification_toggle_response(enabled: bool) -> (String, Option<Vec<Vec<InlineButton>>>) {
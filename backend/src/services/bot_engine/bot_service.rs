@@ -9,13 +9,55 @@
 ///
 /// Requirements covered: 1.1, 1.2, 1.3, 1.4, 1.5, 3.1
 use rand::Rng;
+use reqwest::redirect;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
 use crate::db::Database;
 use crate::error::{AppError, AppResult};
-use crate::models::{Bot, BotChat, BotPermission, BotResponse, CreateBotRequest, UpdateBotRequest};
-
-use super::permission::SCOPE_SEND_MESSAGE;
+use crate::models::{
+    Bot, BotChat, BotMenuCommand, BotPermission, BotResponse, ChatCommandSuggestion,
+    CreateBotRequest, UpdateBotRequest, WebhookOptions,
+    WebhookTestResult, DEFAULT_WEBHOOK_MAX_CONNECTIONS, MAX_WEBHOOK_MAX_CONNECTIONS, UPDATE_TYPES,
+};
+use crate::services::{
+    crypto::{decrypt_secret, encrypt_secret},
+    link_preview::resolve_public_url,
+    request_id,
+};
+
+use super::command_parser::{normalize_menu_commands, validate_alias};
+use super::dispatcher::{WebhookPayload, WEBHOOK_REQUEST_ID_HEADER, WEBHOOK_SECRET_HEADER};
+use super::permission::{
+    is_known_scope, template_scopes, PERMISSION_TEMPLATES, SCOPE_SEND_MESSAGE,
+};
+
+/// Largest page `list_bots_by_owner` returns
+pub const MAX_BOT_LIST_LIMIT: i64 = 100;
+
+/// How long a test webhook delivery may take
+pub const WEBHOOK_TEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Most bytes of the receiver's response returned from a test delivery
+pub const WEBHOOK_TEST_BODY_BYTES: usize = 1024;
+
+/// `audit_log.action` for an admin granting or revoking a bot's privilege
+pub const AUDIT_BOT_PRIVILEGE: &str = "bot_privilege";
+
+/// `audit_log.action` for a request a privileged bot made unlimited
+pub const AUDIT_PRIVILEGED_BOT_REQUEST: &str = "privileged_bot_request";
+
+/// Filters and paging for an owner's bot list
+#[derive(Debug, Clone, Default)]
+pub struct BotListFilter {
+    /// Only active (`Some(true)`) or inactive (`Some(false)`) bots
+    pub active: Option<bool>,
+    /// Case-insensitive substring of the bot name
+    pub query: Option<String>,
+    pub limit: i64,
+    pub offset: i64,
+}
 
 /// BotEngineService handles all bot CRUD operations.
 pub struct BotEngineService;
@@ -145,6 +187,59 @@ impl BotEngineService {
         Ok(bots)
     }
 
+    /// Get one page of a user's bots, newest first, plus the total number of
+    /// bots matching the filter.
+    ///
+    /// `limit` is clamped to `1..=MAX_BOT_LIST_LIMIT` and `offset` to `>= 0`.
+    pub async fn list_bots_by_owner(
+        db: &Database,
+        owner_id: Uuid,
+        filter: &BotListFilter,
+    ) -> AppResult<(Vec<Bot>, i64)> {
+        let limit = filter.limit.clamp(1, MAX_BOT_LIST_LIMIT);
+        let offset = filter.offset.max(0);
+        let pattern = filter
+            .query
+            .as_deref()
+            .map(str::trim)
+            .filter(|q| !q.is_empty())
+            .map(like_pattern);
+
+        let (total,): (i64,) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*) FROM bots
+            WHERE owner_id = $1
+              AND ($2::boolean IS NULL OR is_active = $2)
+              AND ($3::text IS NULL OR name ILIKE $3 ESCAPE '\')
+            "#,
+        )
+        .bind(owner_id)
+        .bind(filter.active)
+        .bind(pattern.as_deref())
+        .fetch_one(&db.pool)
+        .await?;
+
+        let bots: Vec<Bot> = sqlx::query_as(
+            r#"
+            SELECT * FROM bots
+            WHERE owner_id = $1
+              AND ($2::boolean IS NULL OR is_active = $2)
+              AND ($3::text IS NULL OR name ILIKE $3 ESCAPE '\')
+            ORDER BY created_at DESC, id
+            LIMIT $4 OFFSET $5
+            "#,
+        )
+        .bind(owner_id)
+        .bind(filter.active)
+        .bind(pattern.as_deref())
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&db.pool)
+        .await?;
+
+        Ok((bots, total))
+    }
+
     /// Get all active bots that can be added to chats.
     ///
     /// # Arguments
@@ -349,6 +444,55 @@ impl BotEngineService {
         Ok(bot)
     }
 
+    /// Grant or revoke a bot's exemption from the bot API rate limit.
+    ///
+    /// Callers must check that `admin_id` is a server admin; bot owners
+    /// cannot change this. The change is written to `audit_log`.
+    pub async fn set_privileged(
+        db: &Database,
+        bot_id: Uuid,
+        admin_id: Uuid,
+        privileged: bool,
+    ) -> AppResult<Bot> {
+        let mut tx = db.pool.begin().await?;
+
+        let bot: Bot = sqlx::query_as(
+            "UPDATE bots SET privileged = $2, updated_at = NOW() WHERE id = $1 RETURNING *",
+        )
+        .bind(bot_id)
+        .bind(privileged)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(AppError::BotNotFound)?;
+
+        sqlx::query("INSERT INTO audit_log (actor_id, action, details) VALUES ($1, $2, $3)")
+            .bind(admin_id)
+            .bind(AUDIT_BOT_PRIVILEGE)
+            .bind(serde_json::json!({
+                "botId": bot_id,
+                "privileged": privileged,
+            }))
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(bot)
+    }
+
+    /// Record a bot API call a privileged bot made without being rate limited
+    pub async fn audit_privileged_request(db: &Database, bot_id: Uuid, endpoint: &str) -> AppResult<()> {
+        sqlx::query("INSERT INTO audit_log (action, details) VALUES ($1, $2)")
+            .bind(AUDIT_PRIVILEGED_BOT_REQUEST)
+            .bind(serde_json::json!({
+                "botId": bot_id,
+                "endpoint": endpoint,
+            }))
+            .execute(&db.pool)
+            .await?;
+
+        Ok(())
+    }
+
     /// Get bot permissions.
     ///
     /// # Arguments
@@ -452,6 +596,80 @@ impl BotEngineService {
         Ok(result.rows_affected() > 0)
     }
 
+    /// Replace a bot's scopes with those of a named permission template.
+    ///
+    /// # Arguments
+    /// * `db` - Database connection
+    /// * `bot_id` - The bot's UUID
+    /// * `template` - Template name ("moderator", "poster" or "full")
+    ///
+    /// # Returns
+    /// * `AppResult<Vec<String>>` - The bot's effective scopes after the change
+    pub async fn apply_permission_template(
+        db: &Database,
+        bot_id: Uuid,
+        template: &str,
+    ) -> AppResult<Vec<String>> {
+        let scopes = template_scopes(template).ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "Unknown permission template '{}'. Expected one of: {}",
+                template,
+                PERMISSION_TEMPLATES.join(", ")
+            ))
+        })?;
+
+        let scopes: Vec<String> = scopes.iter().map(|s| s.to_string()).collect();
+        Self::set_permissions(db, bot_id, &scopes).await
+    }
+
+    /// Atomically replace a bot's scopes with an explicit set.
+    ///
+    /// Unknown scopes are rejected before anything is changed.
+    ///
+    /// # Returns
+    /// * `AppResult<Vec<String>>` - The bot's effective scopes after the change, sorted
+    pub async fn set_permissions(
+        db: &Database,
+        bot_id: Uuid,
+        scopes: &[String],
+    ) -> AppResult<Vec<String>> {
+        if let Some(unknown) = scopes.iter().find(|scope| !is_known_scope(scope)) {
+            return Err(AppError::BadRequest(format!("Unknown scope '{}'", unknown)));
+        }
+
+        // Verify bot exists
+        Self::get_bot_by_id(db, bot_id).await?;
+
+        let mut tx = db.pool.begin().await?;
+
+        sqlx::query("DELETE FROM bot_permissions WHERE bot_id = $1")
+            .bind(bot_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO bot_permissions (bot_id, scope)
+            SELECT $1, scope FROM UNNEST($2::text[]) AS scope
+            ON CONFLICT (bot_id, scope) DO NOTHING
+            "#,
+        )
+        .bind(bot_id)
+        .bind(scopes)
+        .execute(&mut *tx)
+        .await?;
+
+        let effective: Vec<(String,)> =
+            sqlx::query_as("SELECT scope FROM bot_permissions WHERE bot_id = $1 ORDER BY scope")
+                .bind(bot_id)
+                .fetch_all(&mut *tx)
+                .await?;
+
+        tx.commit().await?;
+
+        Ok(effective.into_iter().map(|(scope,)| scope).collect())
+    }
+
     // ==================== Chat Subscription Management ====================
     // Requirements: 4.1, 4.2
 
@@ -576,6 +794,182 @@ impl BotEngineService {
         Ok(result.is_some())
     }
 
+    // ==================== Command Aliases ====================
+
+    /// A bot's command aliases (alias -> canonical command).
+    pub async fn get_command_aliases(
+        db: &Database,
+        bot_id: Uuid,
+    ) -> AppResult<HashMap<String, String>> {
+        let rows: Vec<(String, String)> =
+            sqlx::query_as("SELECT alias, command FROM bot_command_aliases WHERE bot_id = $1")
+                .bind(bot_id)
+                .fetch_all(&db.pool)
+                .await?;
+
+        Ok(rows.into_iter().collect())
+    }
+
+    /// Add or re-point a command alias.
+    ///
+    /// Returns `AppError::BadRequest` when the alias would point at another
+    /// alias, shadow a command other aliases use, or form a cycle.
+    pub async fn set_command_alias(
+        db: &Database,
+        bot_id: Uuid,
+        alias: &str,
+        command: &str,
+    ) -> AppResult<()> {
+        let alias = alias.trim_start_matches('/').to_lowercase();
+        let command = command.trim_start_matches('/').to_lowercase();
+
+        let mut tx = db.pool.begin().await?;
+
+        // Serialize alias changes per bot so two edits cannot form a cycle
+        sqlx::query("SELECT id FROM bots WHERE id = $1 FOR UPDATE")
+            .bind(bot_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(AppError::BotNotFound)?;
+
+        let rows: Vec<(String, String)> =
+            sqlx::query_as("SELECT alias, command FROM bot_command_aliases WHERE bot_id = $1")
+                .bind(bot_id)
+                .fetch_all(&mut *tx)
+                .await?;
+        let aliases: HashMap<String, String> = rows.into_iter().collect();
+        validate_alias(&aliases, &alias, &command)
+            .map_err(|e| AppError::BadRequest(e.to_string()))?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO bot_command_aliases (bot_id, alias, command)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (bot_id, alias) DO UPDATE SET command = EXCLUDED.command
+            "#,
+        )
+        .bind(bot_id)
+        .bind(&alias)
+        .bind(&command)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Remove a command alias; returns whether it existed.
+    pub async fn remove_command_alias(db: &Database, bot_id: Uuid, alias: &str) -> AppResult<bool> {
+        let result = sqlx::query("DELETE FROM bot_command_aliases WHERE bot_id = $1 AND alias = $2")
+            .bind(bot_id)
+            .bind(alias.trim_start_matches('/').to_lowercase())
+            .execute(&db.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Canonical command per bot, for the bots among `bot_ids` that have
+    /// `command` as an alias.
+    pub async fn resolve_command_aliases(
+        db: &Database,
+        bot_ids: &[Uuid],
+        command: &str,
+    ) -> AppResult<HashMap<Uuid, String>> {
+        if bot_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let rows: Vec<(Uuid, String)> = sqlx::query_as(
+            "SELECT bot_id, command FROM bot_command_aliases WHERE bot_id = ANY($1) AND alias = $2",
+        )
+        .bind(bot_ids)
+        .bind(command)
+        .fetch_all(&db.pool)
+        .await?;
+
+        Ok(rows.into_iter().collect())
+    }
+
+    // ==================== Menu Commands ====================
+
+    /// The commands a bot advertises to users.
+    pub async fn get_menu_commands(db: &Database, bot_id: Uuid) -> AppResult<Vec<BotMenuCommand>> {
+        let json: Option<String> =
+            sqlx::query_scalar("SELECT menu_commands::text FROM bots WHERE id = $1")
+                .bind(bot_id)
+                .fetch_optional(&db.pool)
+                .await?;
+
+        parse_menu_commands(&json.ok_or(AppError::BotNotFound)?)
+    }
+
+    /// Replace the commands a bot advertises to users, returning them as
+    /// stored.
+    ///
+    /// Returns `AppError::BadRequest` for invalid names or descriptions,
+    /// duplicates, or more than `MAX_MENU_COMMANDS` commands.
+    pub async fn set_menu_commands(
+        db: &Database,
+        bot_id: Uuid,
+        commands: Vec<BotMenuCommand>,
+    ) -> AppResult<Vec<BotMenuCommand>> {
+        let commands = normalize_menu_commands(commands)
+            .map_err(|e| AppError::BadRequest(e.to_string()))?;
+        let json = serde_json::to_string(&commands)
+            .map_err(|e| AppError::Internal(anyhow::anyhow!(e)))?;
+
+        let stored: Option<String> = sqlx::query_scalar(
+            r#"
+            UPDATE bots SET menu_commands = $2::jsonb, updated_at = NOW()
+            WHERE id = $1
+            RETURNING menu_commands::text
+            "#,
+        )
+        .bind(bot_id)
+        .bind(json)
+        .fetch_optional(&db.pool)
+        .await?;
+
+        parse_menu_commands(&stored.ok_or(AppError::BotNotFound)?)
+    }
+
+    /// Menu commands of the active bots in a chat whose name starts with
+    /// `prefix`, for command autocomplete.
+    pub async fn get_chat_menu_commands(
+        db: &Database,
+        chat_id: Uuid,
+        prefix: &str,
+    ) -> AppResult<Vec<ChatCommandSuggestion>> {
+        let rows: Vec<(Uuid, Option<String>, String)> = sqlx::query_as(
+            r#"
+            SELECT b.id, b.username, b.menu_commands::text FROM bots b
+            INNER JOIN bot_chats bc ON b.id = bc.bot_id
+            WHERE bc.chat_id = $1 AND b.is_active = true
+            ORDER BY bc.added_at
+            "#,
+        )
+        .bind(chat_id)
+        .fetch_all(&db.pool)
+        .await?;
+
+        let prefix = prefix.trim_start_matches('/').to_lowercase();
+        let mut suggestions = Vec::new();
+        for (bot_id, bot_username, json) in rows {
+            for entry in parse_menu_commands(&json)? {
+                if entry.command.starts_with(&prefix) {
+                    suggestions.push(ChatCommandSuggestion {
+                        bot_id,
+                        bot_username: bot_username.clone(),
+                        command: entry.command,
+                        description: entry.description,
+                    });
+                }
+            }
+        }
+        Ok(suggestions)
+    }
+
     // ==================== Username Management ====================
 
     /// Check if a username is already taken.
@@ -679,12 +1073,223 @@ impl BotEngineService {
         Ok(())
     }
 
+    /// Send a sample update to the bot's configured webhook.
+    ///
+    /// A diagnostic for bot authors: the payload has the shape of a real
+    /// update, flagged `is_test`, with the bot's secret header. The URL gets
+    /// the same checks as link-preview fetches (HTTPS, public address, no
+    /// redirects). Delivery failures are reported in the result rather than
+    /// as errors, and nothing is recorded in the bot's stats.
+    ///
+    /// # Arguments
+    /// * `bot` - The bot whose webhook to test
+    /// * `from` - User the sample message appears to come from
+    ///
+    /// # Returns
+    /// * `AppResult<WebhookTestResult>` - Status, latency and body snippet
+    pub async fn test_webhook_delivery(bot: &Bot, from: Uuid) -> AppResult<WebhookTestResult> {
+        let url = match &bot.webhook_url {
+            Some(url) if !url.is_empty() => url.clone(),
+            _ => {
+                return Err(AppError::BadRequest(
+                    "Bot has no webhook configured".to_string(),
+                ))
+            }
+        };
+        Self::validate_webhook_url(&url)?;
+        let parsed = url::Url::parse(&url).map_err(|_| AppError::InvalidWebhookUrl)?;
+        let (domain, addr) = resolve_public_url(&parsed)
+            .await
+            .map_err(|e| AppError::BadRequest(format!("Webhook URL is not allowed: {}", e)))?;
+
+        let mut builder = reqwest::Client::builder()
+            .redirect(redirect::Policy::none())
+            .no_proxy()
+            .timeout(WEBHOOK_TEST_TIMEOUT);
+        if let Some(domain) = &domain {
+            builder = builder.resolve(domain, addr);
+        }
+        let client = builder
+            .build()
+            .map_err(|e| AppError::WebhookError(e.to_string()))?;
+
+        let mut request = WebhookPayload::sample(from)
+            .attach(client.post(parsed), bot.webhook_schema_version)
+            .header(WEBHOOK_REQUEST_ID_HEADER, request_id::generate());
+        if let Some(secret) = &bot.webhook_secret {
+            request = request.header(WEBHOOK_SECRET_HEADER, decrypt_secret(secret)?);
+        }
+
+        let started = Instant::now();
+        let elapsed_ms = || started.elapsed().as_millis() as u64;
+        let mut response = match request.send().await {
+            Ok(response) => response,
+            Err(e) => {
+                return Ok(WebhookTestResult {
+                    url,
+                    status: None,
+                    latency_ms: elapsed_ms(),
+                    body_snippet: None,
+                    error: Some(e.to_string()),
+                })
+            }
+        };
+        let status = response.status().as_u16();
+        let latency_ms = elapsed_ms();
+
+        let mut body = Vec::new();
+        let mut error = None;
+        while body.len() < WEBHOOK_TEST_BODY_BYTES {
+            match response.chunk().await {
+                Ok(Some(chunk)) => body.extend_from_slice(&chunk),
+                Ok(None) => break,
+                Err(e) => {
+                    error = Some(e.to_string());
+                    break;
+                }
+            }
+        }
+
+        tracing::info!("Test webhook delivery for bot {} returned {}", bot.id, status);
+        Ok(WebhookTestResult {
+            url,
+            status: Some(status),
+            latency_ms,
+            body_snippet: Some(body_snippet(&body, WEBHOOK_TEST_BODY_BYTES)),
+            error,
+        })
+    }
+
+    /// Validate optional webhook delivery settings.
+    ///
+    /// # Arguments
+    /// * `options` - The webhook options to validate
+    ///
+    /// # Returns
+    /// * `AppResult<()>` - Ok if valid, `AppError::BadRequest` describing the problem if not
+    pub fn validate_webhook_options(options: &WebhookOptions) -> AppResult<()> {
+        if let Some(secret) = &options.secret_token {
+            if secret.is_empty() || secret.len() > 256 {
+                return Err(AppError::BadRequest(
+                    "Webhook secret token must be 1-256 characters".to_string(),
+                ));
+            }
+            if !secret
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                return Err(AppError::BadRequest(
+                    "Webhook secret token may only contain letters, numbers, '_' and '-'"
+                        .to_string(),
+                ));
+            }
+        }
+
+        if let Some(max) = options.max_connections {
+            if !(1..=MAX_WEBHOOK_MAX_CONNECTIONS).contains(&max) {
+                return Err(AppError::BadRequest(format!(
+                    "Webhook max connections must be between 1 and {}",
+                    MAX_WEBHOOK_MAX_CONNECTIONS
+                )));
+            }
+        }
+
+        // Versions newer than this server are accepted: such bots receive the
+        // latest payload flagged with a schema mismatch header
+        if let Some(version) = options.schema_version {
+            if version < 1 {
+                return Err(AppError::BadRequest(
+                    "Webhook schema version must be at least 1".to_string(),
+                ));
+            }
+        }
+
+        if let Some(updates) = &options.allowed_updates {
+            for update in updates {
+                if !UPDATE_TYPES.contains(&update.as_str()) {
+                    return Err(AppError::BadRequest(format!(
+                        "Unknown update type '{}'. Allowed types: {}",
+                        update,
+                        UPDATE_TYPES.join(", ")
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Store a webhook URL together with its delivery options.
+    ///
+    /// The URL is stored as given; callers are responsible for validating it.
+    /// Options that are not provided are reset to their defaults.
+    ///
+    /// # Arguments
+    /// * `db` - Database connection
+    /// * `bot_id` - The bot's UUID
+    /// * `url` - The webhook URL to store
+    /// * `options` - Secret token, max connections and allowed update types
+    ///
+    /// # Returns
+    /// * `AppResult<Bot>` - The updated bot
+    pub async fn save_webhook(
+        db: &Database,
+        bot_id: Uuid,
+        url: &str,
+        options: &WebhookOptions,
+    ) -> AppResult<Bot> {
+        Self::validate_webhook_options(options)?;
+
+        // Deduplicate allowed updates while keeping order
+        let allowed_updates = options.allowed_updates.as_ref().map(|updates| {
+            let mut unique: Vec<String> = Vec::with_capacity(updates.len());
+            for update in updates {
+                if !unique.contains(update) {
+                    unique.push(update.clone());
+                }
+            }
+            unique
+        });
+
+        // Always rewritten, so legacy plaintext or a retired key is replaced
+        let secret = options
+            .secret_token
+            .as_deref()
+            .map(encrypt_secret)
+            .transpose()?;
+
+        let bot: Bot = sqlx::query_as(
+            r#"
+            UPDATE bots 
+            SET webhook_url = $1,
+                webhook_secret = $2,
+                webhook_max_connections = $3,
+                webhook_allowed_updates = $4,
+                webhook_schema_version = $5,
+                updated_at = NOW()
+            WHERE id = $6
+            RETURNING *
+            "#,
+        )
+        .bind(url)
+        .bind(secret)
+        .bind(options.max_connections.unwrap_or(DEFAULT_WEBHOOK_MAX_CONNECTIONS))
+        .bind(allowed_updates)
+        .bind(options.schema_version)
+        .bind(bot_id)
+        .fetch_one(&db.pool)
+        .await?;
+
+        Ok(bot)
+    }
+
     /// Set webhook URL for a bot.
     ///
     /// # Arguments
     /// * `db` - Database connection
     /// * `bot_id` - The bot's UUID
     /// * `url` - The webhook URL to set
+    /// * `options` - Optional secret token, max connections and allowed update types
     ///
     /// # Returns
     /// * `AppResult<Bot>` - The updated bot
@@ -693,29 +1298,24 @@ impl BotEngineService {
     /// - 2.1: Store the webhook_url for the bot
     /// - 2.3: Validate URL format
     /// - 2.4: Test connectivity before storing
-    pub async fn set_webhook(db: &Database, bot_id: Uuid, url: &str) -> AppResult<Bot> {
+    pub async fn set_webhook(
+        db: &Database,
+        bot_id: Uuid,
+        url: &str,
+        options: &WebhookOptions,
+    ) -> AppResult<Bot> {
         // Verify bot exists
         Self::get_bot_by_id(db, bot_id).await?;
 
-        // Validate URL format
+        // Validate URL format and options
         Self::validate_webhook_url(url)?;
+        Self::validate_webhook_options(options)?;
 
         // Test connectivity
         Self::test_webhook_connectivity(url).await?;
 
         // Update webhook URL
-        let bot: Bot = sqlx::query_as(
-            r#"
-            UPDATE bots 
-            SET webhook_url = $1, updated_at = NOW()
-            WHERE id = $2
-            RETURNING *
-            "#,
-        )
-        .bind(url)
-        .bind(bot_id)
-        .fetch_one(&db.pool)
-        .await?;
+        let bot = Self::save_webhook(db, bot_id, url, options).await?;
 
         tracing::info!("Set webhook for bot {}: {}", bot_id, url);
         Ok(bot)
@@ -723,6 +1323,8 @@ impl BotEngineService {
 
     /// Clear webhook URL for a bot.
     ///
+    /// Also clears the secret token and resets delivery options.
+    ///
     /// # Arguments
     /// * `db` - Database connection
     /// * `bot_id` - The bot's UUID
@@ -740,12 +1342,18 @@ impl BotEngineService {
         let bot: Bot = sqlx::query_as(
             r#"
             UPDATE bots 
-            SET webhook_url = NULL, updated_at = NOW()
+            SET webhook_url = NULL,
+                webhook_secret = NULL,
+                webhook_max_connections = $2,
+                webhook_allowed_updates = NULL,
+                webhook_schema_version = NULL,
+                updated_at = NOW()
             WHERE id = $1
             RETURNING *
             "#,
         )
         .bind(bot_id)
+        .bind(DEFAULT_WEBHOOK_MAX_CONNECTIONS)
         .fetch_one(&db.pool)
         .await?;
 
@@ -759,6 +1367,7 @@ impl BotEngineService {
     /// * `db` - Database connection
     /// * `bot_id` - The bot's UUID
     /// * `url` - Optional webhook URL (None or empty string to clear)
+    /// * `options` - Delivery options, ignored when clearing
     ///
     /// # Returns
     /// * `AppResult<Bot>` - The updated bot
@@ -766,18 +1375,72 @@ impl BotEngineService {
         db: &Database,
         bot_id: Uuid,
         url: Option<&str>,
+        options: &WebhookOptions,
     ) -> AppResult<Bot> {
         match url {
-            Some(u) if !u.trim().is_empty() => Self::set_webhook(db, bot_id, u.trim()).await,
+            Some(u) if !u.trim().is_empty() => {
+                Self::set_webhook(db, bot_id, u.trim(), options).await
+            }
             _ => Self::clear_webhook(db, bot_id).await,
         }
     }
 }
 
+/// The first `max_bytes` of a response body as text, cut on a character
+/// boundary
+fn body_snippet(body: &[u8], max_bytes: usize) -> String {
+    let body = &body[..body.len().min(max_bytes)];
+    match std::str::from_utf8(body) {
+        Ok(text) => text.to_string(),
+        // Only the last character is incomplete: drop it
+        Err(e) if e.error_len().is_none() => {
+            String::from_utf8_lossy(&body[..e.valid_up_to()]).into_owned()
+        }
+        Err(_) => String::from_utf8_lossy(body).into_owned(),
+    }
+}
+
+/// Decode a `bots.menu_commands` value
+fn parse_menu_commands(json: &str) -> AppResult<Vec<BotMenuCommand>> {
+    serde_json::from_str(json).map_err(|e| {
+        AppError::Internal(anyhow::anyhow!("Invalid bots.menu_commands: {}", e))
+    })
+}
+
+/// `ILIKE` pattern matching `query` as a literal substring
+fn like_pattern(query: &str) -> String {
+    let mut pattern = String::with_capacity(query.len() + 2);
+    pattern.push('%');
+    for c in query.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_body_snippet_cuts_on_char_boundary() {
+        assert_eq!(body_snippet(b"ok", 16), "ok");
+        assert_eq!(body_snippet(b"hello world", 5), "hello");
+        // "é" is two bytes; cutting after the first drops it
+        assert_eq!(body_snippet("caf\u{e9}".as_bytes(), 4), "caf");
+        assert_eq!(body_snippet(&[b'a', 0xFF, b'b'], 16), "a\u{FFFD}b");
+    }
+
+    #[test]
+    fn test_like_pattern_escapes_wildcards() {
+        assert_eq!(like_pattern("weather"), "%weather%");
+        assert_eq!(like_pattern("100%_bot"), "%100\\%\\_bot%");
+        assert_eq!(like_pattern("a\\b"), "%a\\\\b%");
+    }
+
     #[test]
     fn test_generate_token_format() {
         let bot_id = Uuid::new_v4();
@@ -802,4 +1465,50 @@ mod tests {
         // Even for the same bot_id, tokens should be different
         assert_ne!(token1, token2);
     }
+
+    #[test]
+    fn test_validate_webhook_options() {
+        assert!(BotEngineService::validate_webhook_options(&WebhookOptions::default()).is_ok());
+
+        let options = WebhookOptions {
+            secret_token: Some("my_secret-123".to_string()),
+            max_connections: Some(10),
+            allowed_updates: Some(vec!["message".to_string(), "reaction".to_string()]),
+            schema_version: Some(1),
+        };
+        assert!(BotEngineService::validate_webhook_options(&options).is_ok());
+
+        // Invalid secret characters
+        let options = WebhookOptions {
+            secret_token: Some("has spaces".to_string()),
+            ..Default::default()
+        };
+        assert!(BotEngineService::validate_webhook_options(&options).is_err());
+
+        // Max connections out of range
+        let options = WebhookOptions {
+            max_connections: Some(0),
+            ..Default::default()
+        };
+        assert!(BotEngineService::validate_webhook_options(&options).is_err());
+
+        // Schema versions start at 1
+        let options = WebhookOptions {
+            schema_version: Some(0),
+            ..Default::default()
+        };
+        assert!(BotEngineService::validate_webhook_options(&options).is_err());
+    }
+
+    #[test]
+    fn test_validate_webhook_options_unknown_update_type() {
+        let options = WebhookOptions {
+            allowed_updates: Some(vec!["message".to_string(), "edited_message".to_string()]),
+            ..Default::default()
+        };
+        match BotEngineService::validate_webhook_options(&options) {
+            Err(AppError::BadRequest(msg)) => assert!(msg.contains("edited_message")),
+            other => panic!("expected BadRequest, got {:?}", other.map(|_| ())),
+        }
+    }
 }
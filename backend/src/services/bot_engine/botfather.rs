@@ -6,6 +6,8 @@
 /// - /deletebot - Delete a bot
 /// - /setwebhook - Set webhook URL for a bot
 /// - /token - Get or regenerate bot token
+/// - /setperms - Apply a permission template to a bot
+/// - /setalias - Add or remove a command alias (e.g. /s for /status)
 /// - /help - Show available commands
 
 use std::collections::HashMap;
@@ -14,10 +16,12 @@ use uuid::Uuid;
 
 use crate::db::Database;
 use crate::error::{AppError, AppResult};
-use crate::models::CreateBotRequest;
+use crate::models::{CreateBotRequest, WebhookOptions};
+use crate::services::timezone;
 
 use super::bot_service::BotEngineService;
 use super::command_parser::ParsedCommand;
+use super::permission::{template_scopes, PERMISSION_TEMPLATES};
 
 /// Conversation state for multi-step commands
 #[derive(Debug, Clone)]
@@ -87,7 +91,8 @@ impl BotFather {
         matches!(
             cmd.command.as_str(),
             "newbot" | "mybots" | "deletebot" | "setwebhook" | "clearwebhook" 
-            | "token" | "bothelp" | "addbot" | "removebot" | "botinfo" | "cancel"
+            | "token" | "bothelp" | "addbot" | "removebot" | "botinfo" | "setperms" | "setalias"
+            | "cancel"
         )
     }
 
@@ -252,6 +257,8 @@ impl BotFather {
             "addbot" => Some(Self::cmd_addbot(db, user_id, chat_id, cmd).await?),
             "removebot" => Some(Self::cmd_removebot(db, user_id, chat_id, cmd).await?),
             "botinfo" => Some(Self::cmd_botinfo(db, user_id, cmd).await?),
+            "setperms" => Some(Self::cmd_setperms(db, user_id, cmd).await?),
+            "setalias" => Some(Self::cmd_setalias(db, user_id, cmd).await?),
             _ => None,
         };
 
@@ -399,12 +406,12 @@ impl BotFather {
         }
     }
 
-    /// /setwebhook <bot_id> <url> - Set webhook URL
+    /// /setwebhook <bot_id> <url> [options] - Set webhook URL and optional delivery settings
     async fn cmd_setwebhook(db: &Database, user_id: Uuid, cmd: &ParsedCommand) -> AppResult<BotFatherResponse> {
         if cmd.args.len() < 2 {
             return Ok(BotFatherResponse::error(
-                "❌ Usage: /setwebhook <bot_id> <url>\n\n\
-                Example: /setwebhook 123e4567-e89b-12d3-a456-426614174000 https://myserver.com/webhook"
+                "❌ Usage: /setwebhook <bot_id> <url> [secret=<token>] [max_connections=<1-100>] [allowed_updates=message,reaction,member_joined] [schema_version=<n>]\n\n\
+                Example: /setwebhook 123e4567-e89b-12d3-a456-426614174000 https://myserver.com/webhook secret=s3cr3t"
             ));
         }
 
@@ -417,22 +424,40 @@ impl BotFather {
 
         let url = &cmd.args[1];
 
+        let options = match Self::parse_webhook_options(&cmd.args[2..]) {
+            Ok(options) => options,
+            Err(msg) => return Ok(BotFatherResponse::error(msg)),
+        };
+
         // Verify ownership
         let bot = BotEngineService::get_bot_by_id(db, bot_id).await?;
         if bot.owner_id != user_id {
             return Ok(BotFatherResponse::error("❌ You don't own this bot."));
         }
 
-        match BotEngineService::set_webhook(db, bot_id, url).await {
-            Ok(_) => Ok(BotFatherResponse::success(format!(
-                "✅ Webhook set successfully!\n\nURL: {}",
-                url
-            ))),
+        match BotEngineService::set_webhook(db, bot_id, url, &options).await {
+            Ok(bot) => {
+                let mut text = format!("✅ Webhook set successfully!\n\nURL: {}", url);
+                if bot.webhook_secret.is_some() {
+                    text.push_str("\nSecret token: configured");
+                }
+                text.push_str(&format!("\nMax connections: {}", bot.webhook_max_connections));
+                if let Some(updates) = &bot.webhook_allowed_updates {
+                    text.push_str(&format!("\nAllowed updates: {}", updates.join(", ")));
+                }
+                if let Some(version) = bot.webhook_schema_version {
+                    text.push_str(&format!("\nPayload schema version: {}", version));
+                }
+                Ok(BotFatherResponse::success(text))
+            }
             Err(AppError::InvalidWebhookUrl) => {
                 Ok(BotFatherResponse::error(
                     "❌ Invalid webhook URL. Must be a valid HTTPS URL."
                 ))
             }
+            Err(AppError::BadRequest(msg)) => {
+                Ok(BotFatherResponse::error(format!("❌ {}", msg)))
+            }
             Err(AppError::WebhookError(msg)) => {
                 Ok(BotFatherResponse::error(format!(
                     "❌ Webhook error: {}\n\nMake sure the URL is reachable.",
@@ -443,6 +468,50 @@ impl BotFather {
         }
     }
 
+    /// Parse optional `key=value` webhook settings from /setwebhook arguments
+    fn parse_webhook_options(args: &[String]) -> Result<WebhookOptions, String> {
+        let mut options = WebhookOptions::default();
+
+        for arg in args {
+            let (key, value) = arg
+                .split_once('=')
+                .ok_or_else(|| format!("❌ Invalid option '{}'. Use key=value.", arg))?;
+
+            match key.to_lowercase().as_str() {
+                "secret" | "secret_token" => options.secret_token = Some(value.to_string()),
+                "max_connections" => {
+                    let max = value
+                        .parse()
+                        .map_err(|_| format!("❌ max_connections must be a number, got '{}'.", value))?;
+                    options.max_connections = Some(max);
+                }
+                "schema_version" => {
+                    let version = value
+                        .parse()
+                        .map_err(|_| format!("❌ schema_version must be a number, got '{}'.", value))?;
+                    options.schema_version = Some(version);
+                }
+                "allowed_updates" => {
+                    options.allowed_updates = Some(
+                        value
+                            .split(',')
+                            .map(|u| u.trim().to_lowercase())
+                            .filter(|u| !u.is_empty())
+                            .collect(),
+                    );
+                }
+                _ => {
+                    return Err(format!(
+                        "❌ Unknown option '{}'. Supported: secret, max_connections, allowed_updates, schema_version.",
+                        key
+                    ))
+                }
+            }
+        }
+
+        Ok(options)
+    }
+
     /// /clearwebhook <bot_id> - Clear webhook URL
     async fn cmd_clearwebhook(db: &Database, user_id: Uuid, cmd: &ParsedCommand) -> AppResult<BotFatherResponse> {
         let bot_id_str = match cmd.first_arg() {
@@ -471,6 +540,59 @@ impl BotFather {
         Ok(BotFatherResponse::success("✅ Webhook cleared."))
     }
 
+    /// /setalias <bot_id> <alias> [command] - Add, re-point or remove (no command) an alias
+    async fn cmd_setalias(db: &Database, user_id: Uuid, cmd: &ParsedCommand) -> AppResult<BotFatherResponse> {
+        if cmd.args.len() < 2 || cmd.args.len() > 3 {
+            return Ok(BotFatherResponse::error(
+                "❌ Usage: /setalias <bot_id> <alias> [command]\n\n\
+                Example: /setalias 123e4567-e89b-12d3-a456-426614174000 s status\n\
+                Leave out the command to remove the alias."
+            ));
+        }
+
+        let bot_id = match Uuid::parse_str(&cmd.args[0]) {
+            Ok(id) => id,
+            Err(_) => {
+                return Ok(BotFatherResponse::error("❌ Invalid bot ID format."));
+            }
+        };
+
+        // Verify ownership
+        let bot = BotEngineService::get_bot_by_id(db, bot_id).await?;
+        if bot.owner_id != user_id {
+            return Ok(BotFatherResponse::error("❌ You don't own this bot."));
+        }
+
+        let alias = cmd.args[1].trim_start_matches('/').to_lowercase();
+        let Some(command) = cmd.args.get(2) else {
+            return Ok(if BotEngineService::remove_command_alias(db, bot_id, &alias).await? {
+                BotFatherResponse::success(format!("✅ Alias /{} removed.", alias))
+            } else {
+                BotFatherResponse::error(format!("❌ /{} is not an alias of this bot.", alias))
+            });
+        };
+
+        // BotFather answers its own commands before any bot sees them
+        if let Some(reserved) = ParsedCommand::parse(&format!("/{}", alias))
+            .filter(Self::is_botfather_command)
+        {
+            return Ok(BotFatherResponse::error(format!(
+                "❌ /{} is a BotFather command and cannot be an alias.",
+                reserved.command
+            )));
+        }
+
+        match BotEngineService::set_command_alias(db, bot_id, &alias, command).await {
+            Ok(()) => Ok(BotFatherResponse::success(format!(
+                "✅ /{} now runs /{}.",
+                alias,
+                command.trim_start_matches('/').to_lowercase()
+            ))),
+            Err(AppError::BadRequest(msg)) => Ok(BotFatherResponse::error(format!("❌ {}", msg))),
+            Err(e) => Err(e),
+        }
+    }
+
     /// /token <bot_id> [regenerate] - Get or regenerate token
     async fn cmd_token(db: &Database, user_id: Uuid, cmd: &ParsedCommand) -> AppResult<BotFatherResponse> {
         let bot_id_str = match cmd.first_arg() {
@@ -627,17 +749,60 @@ impl BotFather {
         );
 
         if is_owner {
+            let tz = timezone::user_timezone(db, user_id).await?;
             text.push_str(&format!(
                 "Webhook: {}\n\
                 Created: {}\n",
                 webhook,
-                bot.created_at.format("%Y-%m-%d %H:%M UTC")
+                timezone::format_timestamp(bot.created_at, tz.as_deref())
             ));
         }
 
         Ok(BotFatherResponse::success(text))
     }
 
+    /// /setperms <bot_id> <template> - Replace a bot's permissions with a template
+    async fn cmd_setperms(db: &Database, user_id: Uuid, cmd: &ParsedCommand) -> AppResult<BotFatherResponse> {
+        let usage = format!(
+            "❌ Usage: /setperms <bot_id> <template>\n\nTemplates: {}",
+            PERMISSION_TEMPLATES.join(", ")
+        );
+
+        let (bot_id_str, template) = match (cmd.first_arg(), cmd.args.get(1)) {
+            (Some(id), Some(template)) => (id, template.to_lowercase()),
+            _ => return Ok(BotFatherResponse::error(usage)),
+        };
+
+        let bot_id = match Uuid::parse_str(bot_id_str) {
+            Ok(id) => id,
+            Err(_) => {
+                return Ok(BotFatherResponse::error("❌ Invalid bot ID format."));
+            }
+        };
+
+        if template_scopes(&template).is_none() {
+            return Ok(BotFatherResponse::error(format!(
+                "❌ Unknown template '{}'. Templates: {}",
+                template,
+                PERMISSION_TEMPLATES.join(", ")
+            )));
+        }
+
+        // Verify ownership
+        let bot = BotEngineService::get_bot_by_id(db, bot_id).await?;
+        if bot.owner_id != user_id {
+            return Ok(BotFatherResponse::error("❌ You don't own this bot."));
+        }
+
+        let scopes = BotEngineService::apply_permission_template(db, bot_id, &template).await?;
+        Ok(BotFatherResponse::success(format!(
+            "✅ Applied '{}' permissions to {}.\n\nScopes: {}",
+            template,
+            bot.name,
+            scopes.join(", ")
+        )))
+    }
+
     /// /bothelp - Show available commands
     fn cmd_help() -> BotFatherResponse {
         BotFatherResponse::success(
@@ -648,9 +813,11 @@ impl BotFather {
             /deletebot <bot_id> - Delete a bot\n\
             /botinfo <bot_id> - Get bot info\n\n\
             🔧 Configuration:\n\
-            /setwebhook <bot_id> <url> - Set webhook URL\n\
+            /setwebhook <bot_id> <url> [secret=..] [max_connections=..] [allowed_updates=..] - Set webhook\n\
             /clearwebhook <bot_id> - Clear webhook\n\
-            /token <bot_id> [regenerate] - Get/regenerate token\n\n\
+            /token <bot_id> [regenerate] - Get/regenerate token\n\
+            /setperms <bot_id> <moderator|poster|full> - Set bot permissions\n\
+            /setalias <bot_id> <alias> [command] - Add or remove a command alias\n\n\
             💬 Chat Integration:\n\
             /addbot <bot_id> - Add bot to this chat\n\
             /removebot <bot_id> - Remove bot from this chat\n\n\
@@ -675,6 +842,8 @@ mod tests {
         assert!(BotFather::is_botfather_command(&ParsedCommand::parse("/mybots").unwrap()));
         assert!(BotFather::is_botfather_command(&ParsedCommand::parse("/bothelp").unwrap()));
         assert!(BotFather::is_botfather_command(&ParsedCommand::parse("/cancel").unwrap()));
+        assert!(BotFather::is_botfather_command(&ParsedCommand::parse("/setperms").unwrap()));
+        assert!(BotFather::is_botfather_command(&ParsedCommand::parse("/setalias").unwrap()));
         assert!(!BotFather::is_botfather_command(&ParsedCommand::parse("/help").unwrap()));
         assert!(!BotFather::is_botfather_command(&ParsedCommand::parse("/start").unwrap()));
     }
@@ -709,4 +878,27 @@ mod tests {
         // Invalid - starts with number
         assert!(BotFather::validate_username("123bot").is_err());
     }
+
+    #[test]
+    fn test_parse_webhook_options() {
+        let args = vec![
+            "secret=abc_123".to_string(),
+            "max_connections=5".to_string(),
+            "allowed_updates=message, Reaction".to_string(),
+            "schema_version=1".to_string(),
+        ];
+        let options = BotFather::parse_webhook_options(&args).unwrap();
+        assert_eq!(options.secret_token.as_deref(), Some("abc_123"));
+        assert_eq!(options.max_connections, Some(5));
+        assert_eq!(options.schema_version, Some(1));
+        assert_eq!(
+            options.allowed_updates,
+            Some(vec!["message".to_string(), "reaction".to_string()])
+        );
+
+        assert!(BotFather::parse_webhook_options(&[]).unwrap().secret_token.is_none());
+        assert!(BotFather::parse_webhook_options(&["secret".to_string()]).is_err());
+        assert!(BotFather::parse_webhook_options(&["max_connections=lots".to_string()]).is_err());
+        assert!(BotFather::parse_webhook_options(&["colour=blue".to_string()]).is_err());
+    }
 }
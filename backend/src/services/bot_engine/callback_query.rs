@@ -0,0 +1,138 @@
+/// Callback Query module - presses of inline keyboard buttons.
+///
+/// A user pressing a button with `callback_data` creates a callback query
+/// that is forwarded to the bot which sent the message. The press is only
+/// accepted when the message really shows a button with that data, so
+/// clients cannot feed bots arbitrary payloads. The bot answers a query once,
+/// within `CALLBACK_ANSWER_WINDOW_SECONDS`, optionally with a notice for the
+/// user.
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::db::Database;
+use crate::error::{AppError, AppResult};
+use crate::models::{Bot, CallbackQuery, Message};
+use crate::services::ChatService;
+use crate::AppState;
+
+use super::inline_keyboard::{InlineKeyboardStore, MAX_CALLBACK_DATA_BYTES};
+use super::BotEngineService;
+
+/// How long a bot has to answer a callback query
+pub const CALLBACK_ANSWER_WINDOW_SECONDS: i64 = 300;
+
+/// Longest notice a bot may show when answering, in characters
+pub const MAX_CALLBACK_ANSWER_CHARS: usize = 200;
+
+/// How often queries past the answer window are deleted
+pub const CALLBACK_PRUNE_INTERVAL: Duration = Duration::from_secs(600);
+
+pub struct CallbackQueryService;
+
+impl CallbackQueryService {
+    /// Spawn the background pruner of expired queries. Runs until the process exits.
+    pub fn spawn_pruner(state: Arc<AppState>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = Self::prune_expired(&state.db).await {
+                    tracing::error!("Failed to prune callback queries: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Record a press of the button carrying `data` on `message_id`
+    ///
+    /// The user must be in the message's chat, and the message must come
+    /// from an active bot and show a button with exactly this data. Returns
+    /// the query and the bot to forward it to.
+    pub async fn create(
+        db: &Database,
+        user_id: Uuid,
+        message_id: Uuid,
+        data: &str,
+    ) -> AppResult<(CallbackQuery, Bot)> {
+        if data.is_empty() || data.len() > MAX_CALLBACK_DATA_BYTES {
+            return Err(AppError::BadRequest(format!(
+                "callbackData must be 1-{} bytes",
+                MAX_CALLBACK_DATA_BYTES
+            )));
+        }
+
+        let message: Message =
+            sqlx::query_as("SELECT * FROM messages WHERE id = $1 AND deleted_at IS NULL")
+                .bind(message_id)
+                .fetch_optional(&db.pool)
+                .await?
+                .ok_or(AppError::MessageNotFound)?;
+
+        if !ChatService::is_participant(db, message.chat_id, user_id).await? {
+            return Err(AppError::AccessDenied);
+        }
+
+        if message.sender_type.as_deref() != Some("bot")
+            || !InlineKeyboardStore::has_callback(db, message.id, data).await?
+        {
+            return Err(AppError::BadRequest(
+                "Message has no button with this callback data".to_string(),
+            ));
+        }
+
+        let bot = BotEngineService::get_bot_by_id(db, message.sender_id).await?;
+        if !bot.is_active {
+            return Err(AppError::BotInactive);
+        }
+
+        let query: CallbackQuery = sqlx::query_as(
+            r#"
+            INSERT INTO callback_queries (bot_id, user_id, chat_id, message_id, data)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *
+            "#,
+        )
+        .bind(bot.id)
+        .bind(user_id)
+        .bind(message.chat_id)
+        .bind(message.id)
+        .bind(data)
+        .fetch_one(&db.pool)
+        .await?;
+
+        Ok((query, bot))
+    }
+
+    /// Mark a query of `bot_id` as answered
+    ///
+    /// Queries of other bots, already answered ones and ones older than the
+    /// answer window are reported as not found.
+    pub async fn answer(db: &Database, bot_id: Uuid, query_id: Uuid) -> AppResult<CallbackQuery> {
+        sqlx::query_as(
+            r#"
+            UPDATE callback_queries SET answered_at = NOW()
+            WHERE id = $1 AND bot_id = $2 AND answered_at IS NULL
+              AND created_at > NOW() - make_interval(secs => $3)
+            RETURNING *
+            "#,
+        )
+        .bind(query_id)
+        .bind(bot_id)
+        .bind(CALLBACK_ANSWER_WINDOW_SECONDS as f64)
+        .fetch_optional(&db.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Callback query not found or expired".to_string()))
+    }
+
+    /// Delete queries past the answer window
+    pub async fn prune_expired(db: &Database) -> AppResult<u64> {
+        let result = sqlx::query(
+            "DELETE FROM callback_queries WHERE created_at <= NOW() - make_interval(secs => $1)",
+        )
+        .bind(CALLBACK_ANSWER_WINDOW_SECONDS as f64)
+        .execute(&db.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
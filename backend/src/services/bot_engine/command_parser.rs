@@ -1,7 +1,22 @@
 /// Command Parser module for parsing bot commands from messages.
 /// 
 /// Parses messages starting with "/" into commands and arguments,
-/// supporting shell-style quoting for arguments with spaces.
+/// supporting shell-style quoting for arguments with spaces. Also checks
+/// per-bot command aliases (e.g. `/s` for `/status`).
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::models::BotMenuCommand;
+
+/// Longest alias or aliased command name
+pub const MAX_ALIAS_LENGTH: usize = 32;
+
+/// Most commands a bot can advertise in its menu
+pub const MAX_MENU_COMMANDS: usize = 100;
+
+/// Longest menu command description, in characters
+pub const MAX_COMMAND_DESCRIPTION_LENGTH: usize = 256;
 
 /// Represents a parsed command with its name and arguments.
 #[derive(Debug, Clone, PartialEq)]
@@ -10,6 +25,8 @@ pub struct ParsedCommand {
     pub command: String,
     /// The arguments passed to the command
     pub args: Vec<String>,
+    /// The alias the user typed, when `command` was resolved from one
+    pub alias: Option<String>,
 }
 
 impl ParsedCommand {
@@ -72,7 +89,26 @@ impl ParsedCommand {
         // Remaining parts are arguments
         let args = parts[1..].to_vec();
 
-        Some(ParsedCommand { command, args })
+        Some(ParsedCommand {
+            command,
+            args,
+            alias: None,
+        })
+    }
+
+    /// Replace an alias with the canonical `command`, keeping the arguments.
+    ///
+    /// The typed alias stays available in `alias` for analytics.
+    pub fn resolve_alias(&mut self, command: &str) {
+        let command = command.to_lowercase();
+        if command != self.command {
+            self.alias = Some(std::mem::replace(&mut self.command, command));
+        }
+    }
+
+    /// The command as the user typed it (the alias, if one was resolved).
+    pub fn original_command(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.command)
     }
 
     /// Check if this command matches a given command name (case-insensitive).
@@ -97,6 +133,174 @@ impl ParsedCommand {
     }
 }
 
+/// Replace the leading `/command` of a message with `/{command}`.
+///
+/// Everything after the command is kept exactly as typed, quoting included.
+pub fn rewrite_command(text: &str, command: &str) -> String {
+    let text = text.trim_start();
+    let end = text.find(char::is_whitespace).unwrap_or(text.len());
+    format!("/{}{}", command, &text[end..])
+}
+
+/// Why a command alias was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AliasError {
+    /// Not 1-32 lowercase letters, digits or underscores
+    InvalidName(String),
+    /// Following the aliases leads back to the alias; holds the loop
+    Cycle(Vec<String>),
+    /// The target is itself an alias
+    TargetIsAlias { target: String, command: String },
+    /// The alias is a real command other aliases point to
+    ShadowsCommand(String),
+}
+
+impl fmt::Display for AliasError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AliasError::InvalidName(name) => write!(
+                f,
+                "/{} is not a valid command name (use up to {} letters, digits or underscores)",
+                name, MAX_ALIAS_LENGTH
+            ),
+            AliasError::Cycle(path) => write!(f, "Alias cycle: /{}", path.join(" -> /")),
+            AliasError::TargetIsAlias { target, command } => write!(
+                f,
+                "/{} is itself an alias of /{}; point the alias at /{} instead",
+                target, command, command
+            ),
+            AliasError::ShadowsCommand(name) => {
+                write!(f, "/{} is a command other aliases point to", name)
+            }
+        }
+    }
+}
+
+/// Why a bot's menu commands were refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MenuCommandError {
+    /// More than `MAX_MENU_COMMANDS` commands
+    TooMany(usize),
+    /// Not 1-32 lowercase letters, digits or underscores
+    InvalidName(String),
+    /// Empty or longer than `MAX_COMMAND_DESCRIPTION_LENGTH`
+    InvalidDescription(String),
+    /// The same command listed twice
+    Duplicate(String),
+}
+
+impl fmt::Display for MenuCommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MenuCommandError::TooMany(count) => write!(
+                f,
+                "A bot can list at most {} commands (got {})",
+                MAX_MENU_COMMANDS, count
+            ),
+            MenuCommandError::InvalidName(name) => write!(
+                f,
+                "/{} is not a valid command name (use up to {} lowercase letters, digits or underscores)",
+                name, MAX_ALIAS_LENGTH
+            ),
+            MenuCommandError::InvalidDescription(name) => write!(
+                f,
+                "The description of /{} must be 1-{} characters",
+                name, MAX_COMMAND_DESCRIPTION_LENGTH
+            ),
+            MenuCommandError::Duplicate(name) => write!(f, "/{} is listed more than once", name),
+        }
+    }
+}
+
+/// Validate a bot's menu commands, returning them with the leading slash
+/// dropped and descriptions trimmed
+pub fn normalize_menu_commands(
+    commands: Vec<BotMenuCommand>,
+) -> Result<Vec<BotMenuCommand>, MenuCommandError> {
+    if commands.len() > MAX_MENU_COMMANDS {
+        return Err(MenuCommandError::TooMany(commands.len()));
+    }
+
+    let mut normalized: Vec<BotMenuCommand> = Vec::with_capacity(commands.len());
+    for entry in commands {
+        let command = entry.command.trim();
+        let command = command.strip_prefix('/').unwrap_or(command).to_string();
+        if !is_valid_command_name(&command) {
+            return Err(MenuCommandError::InvalidName(command));
+        }
+
+        let description = entry.description.trim().to_string();
+        let length = description.chars().count();
+        if length == 0 || length > MAX_COMMAND_DESCRIPTION_LENGTH {
+            return Err(MenuCommandError::InvalidDescription(command));
+        }
+
+        if normalized.iter().any(|c| c.command == command) {
+            return Err(MenuCommandError::Duplicate(command));
+        }
+        normalized.push(BotMenuCommand {
+            command,
+            description,
+        });
+    }
+    Ok(normalized)
+}
+
+fn is_valid_command_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_ALIAS_LENGTH
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Check that `alias -> command` can be added to a bot's `aliases`
+/// (alias -> canonical command).
+///
+/// Aliases always point straight at a real command: an alias of an alias
+/// is refused, and so is anything that would make the aliases loop.
+/// Re-pointing an existing alias is allowed.
+pub fn validate_alias(
+    aliases: &HashMap<String, String>,
+    alias: &str,
+    command: &str,
+) -> Result<(), AliasError> {
+    for name in [alias, command] {
+        if !is_valid_command_name(name) {
+            return Err(AliasError::InvalidName(name.to_string()));
+        }
+    }
+
+    // Walk the chain the new alias would start
+    let mut path = vec![alias.to_string(), command.to_string()];
+    let mut current = command;
+    while current != alias {
+        let Some(next) = aliases.get(current) else {
+            break;
+        };
+        let looped = path.contains(next);
+        path.push(next.clone());
+        if looped {
+            return Err(AliasError::Cycle(path));
+        }
+        current = next;
+    }
+    if current == alias {
+        return Err(AliasError::Cycle(path));
+    }
+    if path.len() > 2 {
+        return Err(AliasError::TargetIsAlias {
+            target: command.to_string(),
+            command: path[2].clone(),
+        });
+    }
+
+    if aliases.iter().any(|(other, target)| target == alias && other != alias) {
+        return Err(AliasError::ShadowsCommand(alias.to_string()));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -185,6 +389,106 @@ mod tests {
         assert_eq!(cmd.first_arg(), None);
     }
 
+    #[test]
+    fn test_resolve_alias_keeps_args_and_original() {
+        let mut cmd = ParsedCommand::parse("/s now").unwrap();
+        assert_eq!(cmd.original_command(), "s");
+
+        cmd.resolve_alias("status");
+        assert_eq!(cmd.command, "status");
+        assert_eq!(cmd.alias.as_deref(), Some("s"));
+        assert_eq!(cmd.original_command(), "s");
+        assert_eq!(cmd.args, vec!["now"]);
+    }
+
+    #[test]
+    fn test_rewrite_command_keeps_args_as_typed() {
+        assert_eq!(rewrite_command(r#"  /s "a b"  c"#, "status"), r#"/status "a b"  c"#);
+        assert_eq!(rewrite_command("/s", "status"), "/status");
+    }
+
+    fn aliases(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(a, c)| (a.to_string(), c.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_alias_accepts_new_and_repointed_aliases() {
+        let existing = aliases(&[("s", "status")]);
+        assert_eq!(validate_alias(&existing, "h", "help"), Ok(()));
+        assert_eq!(validate_alias(&existing, "s", "stats"), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_alias_detects_self_cycle() {
+        assert_eq!(
+            validate_alias(&HashMap::new(), "status", "status"),
+            Err(AliasError::Cycle(vec!["status".into(), "status".into()]))
+        );
+    }
+
+    #[test]
+    fn test_validate_alias_detects_cycles_through_existing_aliases() {
+        // b -> c and c -> a already exist, so a -> b closes a loop
+        let existing = aliases(&[("b", "c"), ("c", "a")]);
+        assert_eq!(
+            validate_alias(&existing, "a", "b"),
+            Err(AliasError::Cycle(vec![
+                "a".into(),
+                "b".into(),
+                "c".into(),
+                "a".into()
+            ]))
+        );
+
+        // A loop already stored (e.g. by hand) does not hang the check
+        let looping = aliases(&[("x", "y"), ("y", "x")]);
+        assert!(matches!(
+            validate_alias(&looping, "a", "x"),
+            Err(AliasError::Cycle(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate_alias_refuses_alias_of_alias() {
+        let existing = aliases(&[("s", "status")]);
+        assert_eq!(
+            validate_alias(&existing, "st", "s"),
+            Err(AliasError::TargetIsAlias {
+                target: "s".into(),
+                command: "status".into()
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_alias_refuses_shadowing_a_real_command() {
+        let existing = aliases(&[("s", "status")]);
+        assert_eq!(
+            validate_alias(&existing, "status", "stats"),
+            Err(AliasError::ShadowsCommand("status".into()))
+        );
+    }
+
+    #[test]
+    fn test_validate_alias_names() {
+        let none = HashMap::new();
+        assert!(matches!(
+            validate_alias(&none, "", "status"),
+            Err(AliasError::InvalidName(_))
+        ));
+        assert!(matches!(
+            validate_alias(&none, "s", "Status"),
+            Err(AliasError::InvalidName(_))
+        ));
+        assert!(matches!(
+            validate_alias(&none, &"a".repeat(33), "status"),
+            Err(AliasError::InvalidName(_))
+        ));
+    }
+
     #[test]
     fn test_args_text() {
         let cmd = ParsedCommand::parse("/echo hello world").unwrap();
@@ -193,4 +497,56 @@ mod tests {
         let cmd = ParsedCommand::parse("/help").unwrap();
         assert_eq!(cmd.args_text(), "");
     }
+
+    fn menu(command: &str, description: &str) -> BotMenuCommand {
+        BotMenuCommand {
+            command: command.to_string(),
+            description: description.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_menu_commands_are_normalized() {
+        let commands = normalize_menu_commands(vec![
+            menu("/start", "  Start the bot "),
+            menu("status_2", "Show status"),
+        ])
+        .unwrap();
+        assert_eq!(
+            commands,
+            vec![menu("start", "Start the bot"), menu("status_2", "Show status")]
+        );
+        assert!(normalize_menu_commands(Vec::new()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_menu_commands_are_validated() {
+        for name in ["Start", "two words", "", "/", &"a".repeat(33)] {
+            assert!(matches!(
+                normalize_menu_commands(vec![menu(name, "ok")]),
+                Err(MenuCommandError::InvalidName(_))
+            ));
+        }
+        assert_eq!(
+            normalize_menu_commands(vec![menu("help", "   ")]),
+            Err(MenuCommandError::InvalidDescription("help".to_string()))
+        );
+        assert!(normalize_menu_commands(vec![menu("help", &"é".repeat(256))]).is_ok());
+        assert!(matches!(
+            normalize_menu_commands(vec![menu("help", &"é".repeat(257))]),
+            Err(MenuCommandError::InvalidDescription(_))
+        ));
+        assert_eq!(
+            normalize_menu_commands(vec![menu("help", "a"), menu("/help", "b")]),
+            Err(MenuCommandError::Duplicate("help".to_string()))
+        );
+
+        let too_many = (0..=MAX_MENU_COMMANDS)
+            .map(|i| menu(&format!("c{}", i), "x"))
+            .collect();
+        assert_eq!(
+            normalize_menu_commands(too_many),
+            Err(MenuCommandError::TooMany(MAX_MENU_COMMANDS + 1))
+        );
+    }
 }
@@ -4,15 +4,26 @@
 /// - WebSocket delivery preference (Requirement 9.4)
 /// - Webhook fallback on disconnect (Requirement 9.5)
 /// - Consistent payload format (Requirement 9.6)
+/// - In-order webhook delivery per bot, see `webhook_queue`
 ///
 /// Requirements covered: 6.2, 6.3, 6.4, 6.5, 9.2, 9.4, 9.5, 9.6
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 use uuid::Uuid;
 
+use crate::config::Config;
 use crate::error::{AppError, AppResult};
-use crate::models::Bot;
-use crate::ws::{BotServerEvent, BotUpdateChat, BotUpdateMessage, BotUpdateUser, WsManager};
+use crate::models::{Bot, UPDATE_CALLBACK_QUERY, UPDATE_MESSAGE, UPDATE_REACTION};
+use crate::services::crypto::decrypt_secret;
+use super::command_parser::rewrite_command;
+use super::loop_guard::LoopGuard;
+use super::stats::BotStatsBuffer;
+use super::webhook_queue::WebhookQueues;
+use crate::ws::{
+    BotServerEvent, BotUpdateCallbackQuery, BotUpdateChat, BotUpdateMessage, BotUpdateReaction,
+    BotUpdateUser, WsManager,
+};
 
 /// Context for a command/message being dispatched to bots
 #[derive(Debug, Clone)]
@@ -23,12 +34,141 @@ pub struct CommandContext {
     pub chat_id: Uuid,
     pub message_id: Uuid,
     pub text: String,
+    /// Set when the message was authored by a bot
+    pub sender_bot_id: Option<Uuid>,
+    /// Correlation id of the user action that produced this message
+    pub request_id: Option<String>,
+    /// Canonical command per bot, for bots that have the message's command
+    /// as an alias; those bots receive the text with the command rewritten
+    pub command_aliases: HashMap<Uuid, String>,
 }
 
+impl CommandContext {
+    /// The context as `bot_id` should see it, with its alias resolved
+    fn for_bot(&self, bot_id: Uuid) -> std::borrow::Cow<'_, CommandContext> {
+        match self.command_aliases.get(&bot_id) {
+            Some(command) => std::borrow::Cow::Owned(CommandContext {
+                text: rewrite_command(&self.text, command),
+                ..self.clone()
+            }),
+            None => std::borrow::Cow::Borrowed(self),
+        }
+    }
+}
+
+/// Context for a reaction being dispatched to bots
+#[derive(Debug, Clone)]
+pub struct ReactionContext {
+    pub update_id: Uuid,
+    /// The user who reacted
+    pub user_id: Uuid,
+    pub username: Option<String>,
+    pub chat_id: Uuid,
+    /// The message reacted to
+    pub message_id: Uuid,
+    pub emoji: String,
+    /// Correlation id of the user action that produced this reaction
+    pub request_id: Option<String>,
+}
+
+/// Context for an inline keyboard button press forwarded to a bot
+#[derive(Debug, Clone)]
+pub struct CallbackContext {
+    /// Id of the callback query, used to answer it
+    pub query_id: Uuid,
+    /// The user who pressed the button
+    pub user_id: Uuid,
+    pub username: Option<String>,
+    pub chat_id: Uuid,
+    /// The message carrying the button
+    pub message_id: Uuid,
+    pub data: String,
+    /// Correlation id of the user action that produced this press
+    pub request_id: Option<String>,
+}
+
+/// An update on its way to one bot
+#[derive(Debug, Clone)]
+enum BotUpdate {
+    Message(CommandContext),
+    Reaction(ReactionContext),
+    Callback(CallbackContext),
+}
+
+impl BotUpdate {
+    fn request_id(&self) -> Option<&str> {
+        match self {
+            BotUpdate::Message(ctx) => ctx.request_id.as_deref(),
+            BotUpdate::Reaction(ctx) => ctx.request_id.as_deref(),
+            BotUpdate::Callback(ctx) => ctx.request_id.as_deref(),
+        }
+    }
+
+    /// The update as pushed to a bot's WebSocket (matches webhook payload format per Requirement 9.6)
+    fn event(&self) -> BotServerEvent {
+        match self {
+            BotUpdate::Message(ctx) => BotServerEvent::BotUpdate {
+                update_id: ctx.message_id,
+                message: BotUpdateMessage {
+                    message_id: ctx.message_id,
+                    chat: BotUpdateChat { id: ctx.chat_id },
+                    from: BotUpdateUser {
+                        id: ctx.user_id,
+                        username: ctx.sender_username.clone(),
+                    },
+                    text: ctx.text.clone(),
+                },
+            },
+            BotUpdate::Reaction(ctx) => BotServerEvent::ReactionAdded {
+                update_id: ctx.update_id,
+                reaction: BotUpdateReaction {
+                    message_id: ctx.message_id,
+                    chat: BotUpdateChat { id: ctx.chat_id },
+                    from: BotUpdateUser {
+                        id: ctx.user_id,
+                        username: ctx.username.clone(),
+                    },
+                    emoji: ctx.emoji.clone(),
+                },
+            },
+            BotUpdate::Callback(ctx) => BotServerEvent::CallbackQuery {
+                update_id: ctx.query_id,
+                callback_query: BotUpdateCallbackQuery {
+                    id: ctx.query_id,
+                    message_id: ctx.message_id,
+                    chat: BotUpdateChat { id: ctx.chat_id },
+                    from: BotUpdateUser {
+                        id: ctx.user_id,
+                        username: ctx.username.clone(),
+                    },
+                    data: ctx.data.clone(),
+                },
+            },
+        }
+    }
+}
+
+/// Header carrying the bot's webhook secret token on every delivery
+pub const WEBHOOK_SECRET_HEADER: &str = "X-Giano-Webhook-Secret";
+
+/// Header carrying the originating request's correlation id on webhook deliveries
+pub const WEBHOOK_REQUEST_ID_HEADER: &str = "X-Giano-Request-Id";
+
+/// Header set when the bot asked for a payload schema version this server
+/// does not support; carries the requested version, the body is the latest
+pub const WEBHOOK_SCHEMA_MISMATCH_HEADER: &str = "X-Giano-Webhook-Schema-Mismatch";
+
 /// Bot Dispatcher handles delivering updates to bots
 pub struct BotDispatcher {
     ws_manager: Arc<WsManager>,
-    http_client: reqwest::Client,
+    /// Per-bot webhook queues, delivering each bot's updates in order
+    webhooks: WebhookQueues<(Bot, BotUpdate)>,
+    /// Webhook deliveries awaiting a response per bot (BOT_WEBHOOK_MAX_IN_FLIGHT)
+    webhook_max_in_flight: usize,
+    /// Detects bot-to-bot loops per chat
+    loop_guard: LoopGuard,
+    /// Usage counters, flushed to the database in the background
+    stats: Arc<BotStatsBuffer>,
 }
 
 impl BotDispatcher {
@@ -36,18 +176,56 @@ impl BotDispatcher {
     ///
     /// # Arguments
     /// * `ws_manager` - WebSocket manager for checking bot connections and sending events
-    pub fn new(ws_manager: Arc<WsManager>) -> Self {
+    /// * `config` - Webhook queue size and in-flight limit
+    pub fn new(ws_manager: Arc<WsManager>, config: &Config) -> Self {
         let http_client = reqwest::Client::builder()
             .timeout(Duration::from_secs(5))
             .build()
             .expect("Failed to create HTTP client");
+        let stats = Arc::new(BotStatsBuffer::default());
+
+        let sender = WebhookSender {
+            http_client,
+            stats: stats.clone(),
+        };
+        let webhooks = WebhookQueues::new(
+            config.bot_webhook_queue_size,
+            Arc::new(move |(bot, update): (Bot, BotUpdate)| {
+                let sender = sender.clone();
+                Box::pin(async move {
+                    if let Err(e) = sender.send(&bot, &update).await {
+                        tracing::warn!(
+                            "Failed to deliver update to bot {} via webhook: {}",
+                            bot.id,
+                            e
+                        );
+                    }
+                })
+            }),
+        );
 
         Self {
             ws_manager,
-            http_client,
+            webhooks,
+            webhook_max_in_flight: config.bot_webhook_max_in_flight,
+            loop_guard: LoopGuard::default(),
+            stats,
         }
     }
 
+    /// Buffered usage counters of all bots
+    pub fn stats(&self) -> &BotStatsBuffer {
+        &self.stats
+    }
+
+    /// Record a bot-authored message and check it is not part of a loop.
+    ///
+    /// Returns false if the same command has bounced between bots too often
+    /// in this chat recently; the message must then not be dispatched.
+    pub fn allow_bot_message(&self, chat_id: Uuid, text: &str) -> bool {
+        self.loop_guard.allow(chat_id, text)
+    }
+
     /// Dispatch message to all active bots subscribed to the chat
     ///
     /// # Arguments
@@ -66,26 +244,71 @@ impl BotDispatcher {
                 continue;
             }
 
+            // Never echo a bot's message back to itself
+            if ctx.sender_bot_id == Some(bot.id) {
+                continue;
+            }
+
+            // Skip bots that filtered out message updates
+            if !bot.accepts_update(UPDATE_MESSAGE) {
+                tracing::debug!("Bot {} does not accept message updates", bot.id);
+                continue;
+            }
+
+            let ctx = ctx.for_bot(bot.id);
+            self.stats.record_message(bot.id, &ctx.text);
+
             // Try WebSocket first, fallback to webhook (Requirement 9.4)
-            if !self.send_via_websocket(&bot, ctx).await {
+            let update = BotUpdate::Message(ctx.into_owned());
+            if !self.send_via_websocket(&bot, &update).await {
                 // WebSocket delivery failed, try webhook (Requirement 9.5)
-                if let Err(e) = self.send_via_webhook(&bot, ctx).await {
-                    tracing::warn!(
-                        "Failed to deliver update to bot {} via webhook: {}",
-                        bot.id,
-                        e
-                    );
-                }
+                self.send_via_webhook(&bot, update);
             }
         }
         Ok(())
     }
 
+    /// Dispatch a reaction to the given bots
+    ///
+    /// Callers pick the bots allowed to see it (`read_reactions`); bots that
+    /// filtered out reaction updates or reacted themselves are skipped here.
+    pub async fn dispatch_reaction(&self, ctx: &ReactionContext, bots: Vec<Bot>) -> AppResult<()> {
+        for bot in bots {
+            if !bot.is_active || bot.id == ctx.user_id {
+                continue;
+            }
+            if !bot.accepts_update(UPDATE_REACTION) {
+                tracing::debug!("Bot {} does not accept reaction updates", bot.id);
+                continue;
+            }
+
+            let update = BotUpdate::Reaction(ctx.clone());
+            if !self.send_via_websocket(&bot, &update).await {
+                self.send_via_webhook(&bot, update);
+            }
+        }
+        Ok(())
+    }
+
+    /// Forward a button press to the bot that sent the message
+    ///
+    /// Returns false when the bot filtered out callback queries or its
+    /// webhook queue is full, so the press will not reach it.
+    pub async fn dispatch_callback(&self, ctx: &CallbackContext, bot: &Bot) -> bool {
+        if !bot.accepts_update(UPDATE_CALLBACK_QUERY) {
+            tracing::debug!("Bot {} does not accept callback queries", bot.id);
+            return false;
+        }
+
+        let update = BotUpdate::Callback(ctx.clone());
+        self.send_via_websocket(bot, &update).await || self.send_via_webhook(bot, update)
+    }
+
     /// Send update to bot via WebSocket
     ///
     /// # Arguments
     /// * `bot` - The bot to send to
-    /// * `ctx` - Command context
+    /// * `update` - The update to send
     ///
     /// # Returns
     /// * `bool` - True if sent successfully via WebSocket
@@ -93,73 +316,138 @@ impl BotDispatcher {
     /// # Requirements
     /// - 9.2: Push message updates directly to bot's WebSocket
     /// - 9.4: Prefer WebSocket delivery
-    async fn send_via_websocket(&self, bot: &Bot, ctx: &CommandContext) -> bool {
+    async fn send_via_websocket(&self, bot: &Bot, update: &BotUpdate) -> bool {
         // Check if bot has active WebSocket connection
         if !self.ws_manager.is_bot_connected(bot.id).await {
             return false;
         }
 
-        // Create BotUpdate event (matches webhook payload format per Requirement 9.6)
-        let event = BotServerEvent::BotUpdate {
-            update_id: ctx.message_id,
-            message: BotUpdateMessage {
-                message_id: ctx.message_id,
-                chat: BotUpdateChat { id: ctx.chat_id },
-                from: BotUpdateUser {
-                    id: ctx.user_id,
-                    username: ctx.sender_username.clone(),
-                },
-                text: ctx.text.clone(),
-            },
-        };
-
         // Send via WebSocket
-        let sent = self.ws_manager.send_to_bot(bot.id, event).await;
+        let sent = self.ws_manager.send_to_bot(bot.id, update.event()).await;
         if sent {
             tracing::debug!("Sent update to bot {} via WebSocket", bot.id);
         }
         sent
     }
 
-    /// Send update to bot via webhook
+    /// Queue an update for delivery to the bot's webhook
+    ///
+    /// Updates to one bot are sent in order, at most `webhook_max_in_flight`
+    /// (and the bot's `webhook_max_connections`) awaiting a response at once.
+    /// Returns false when the bot's queue is full and the update was dropped.
+    ///
+    /// # Requirements
+    /// - 9.5: Fallback to webhook on WebSocket disconnect
+    fn send_via_webhook(&self, bot: &Bot, update: BotUpdate) -> bool {
+        if bot.webhook_url.as_deref().is_none_or(str::is_empty) {
+            tracing::debug!("Bot {} has no webhook configured", bot.id);
+            return true;
+        }
+
+        let max_in_flight = self
+            .webhook_max_in_flight
+            .min(bot.webhook_max_connections.max(1) as usize);
+        self.webhooks
+            .enqueue(bot.id, max_in_flight, (bot.clone(), update))
+    }
+
+    /// Send update to a single bot (convenience method)
     ///
     /// # Arguments
     /// * `bot` - The bot to send to
     /// * `ctx` - Command context
     ///
+    /// # Returns
+    /// * `AppResult<bool>` - True if delivered via WebSocket, false if queued for the webhook
+    pub async fn send_to_bot(&self, bot: &Bot, ctx: &CommandContext) -> AppResult<bool> {
+        if !bot.is_active {
+            return Err(AppError::BotInactive);
+        }
+
+        // Filtered out by the bot's allowed_updates - nothing to deliver
+        if !bot.accepts_update(UPDATE_MESSAGE) {
+            return Ok(false);
+        }
+
+        self.stats.record_message(bot.id, &ctx.text);
+
+        // Try WebSocket first
+        let update = BotUpdate::Message(ctx.clone());
+        if self.send_via_websocket(bot, &update).await {
+            return Ok(true);
+        }
+
+        // Fallback to webhook
+        if !self.send_via_webhook(bot, update) {
+            return Err(AppError::WebhookError("Webhook queue is full".to_string()));
+        }
+        Ok(false)
+    }
+}
+
+/// Sends webhook requests; shared by the per-bot queue workers
+#[derive(Clone)]
+struct WebhookSender {
+    http_client: reqwest::Client,
+    stats: Arc<BotStatsBuffer>,
+}
+
+impl WebhookSender {
+    /// Send update to bot via webhook, returning once the bot answered
+    ///
     /// # Requirements
     /// - 6.4: Call webhook with message payload
     /// - 6.5: Webhook payload includes update_id, message.chat.id, message.from.id, message.text
-    /// - 9.5: Fallback to webhook on WebSocket disconnect
-    async fn send_via_webhook(&self, bot: &Bot, ctx: &CommandContext) -> AppResult<()> {
+    async fn send(&self, bot: &Bot, update: &BotUpdate) -> AppResult<()> {
         let webhook_url = match &bot.webhook_url {
             Some(url) if !url.is_empty() => url,
-            _ => {
-                tracing::debug!("Bot {} has no webhook configured", bot.id);
-                return Ok(());
-            }
+            _ => return Ok(()),
         };
 
-        // Create webhook payload (Requirement 6.5)
-        let payload = WebhookPayload {
-            update_id: ctx.message_id,
-            message: WebhookMessage {
-                message_id: ctx.message_id,
-                chat: WebhookChat { id: ctx.chat_id },
-                from: WebhookUser { id: ctx.user_id },
-                text: ctx.text.clone(),
-            },
+        let mut request = match update {
+            BotUpdate::Message(ctx) => {
+                // Create webhook payload (Requirement 6.5)
+                let payload = WebhookPayload {
+                    schema_version: WEBHOOK_SCHEMA_VERSION,
+                    update_id: ctx.message_id,
+                    message: WebhookMessage {
+                        message_id: ctx.message_id,
+                        chat: WebhookChat { id: ctx.chat_id },
+                        from: WebhookUser {
+                            id: ctx.user_id,
+                            username: ctx.sender_username.clone(),
+                        },
+                        text: ctx.text.clone(),
+                    },
+                    is_test: false,
+                };
+                payload.attach(self.http_client.post(webhook_url), bot.webhook_schema_version)
+            }
+            BotUpdate::Reaction(ctx) => self
+                .http_client
+                .post(webhook_url)
+                .json(&WebhookReactionPayload::from(ctx)),
+            BotUpdate::Callback(ctx) => self
+                .http_client
+                .post(webhook_url)
+                .json(&WebhookCallbackPayload::from(ctx)),
         };
+        if let Some(secret) = &bot.webhook_secret {
+            request = request.header(WEBHOOK_SECRET_HEADER, decrypt_secret(secret)?);
+        }
+        if let Some(request_id) = update.request_id() {
+            request = request.header(WEBHOOK_REQUEST_ID_HEADER, request_id);
+        }
 
-        // Send webhook request
-        let response = self
-            .http_client
-            .post(webhook_url)
-            .json(&payload)
-            .send()
-            .await
-            .map_err(|e| AppError::WebhookError(e.to_string()))?;
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(e) => {
+                self.stats.record_webhook(bot.id, false);
+                return Err(AppError::WebhookError(e.to_string()));
+            }
+        };
 
+        self.stats.record_webhook(bot.id, response.status().is_success());
         if !response.status().is_success() {
             tracing::warn!(
                 "Webhook to bot {} returned status {}: {}",
@@ -173,37 +461,74 @@ impl BotDispatcher {
 
         Ok(())
     }
-
-    /// Send update to a single bot (convenience method)
-    ///
-    /// # Arguments
-    /// * `bot` - The bot to send to
-    /// * `ctx` - Command context
-    ///
-    /// # Returns
-    /// * `AppResult<bool>` - True if delivered via WebSocket, false if via webhook
-    pub async fn send_to_bot(&self, bot: &Bot, ctx: &CommandContext) -> AppResult<bool> {
-        if !bot.is_active {
-            return Err(AppError::BotInactive);
-        }
-
-        // Try WebSocket first
-        if self.send_via_websocket(bot, ctx).await {
-            return Ok(true);
-        }
-
-        // Fallback to webhook
-        self.send_via_webhook(bot, ctx).await?;
-        Ok(false)
-    }
 }
 
-/// Webhook payload structure (matches Requirement 6.5)
+/// Latest webhook payload schema version
+///
+/// Versions:
+/// - 1: original shape; `from` carries only the sender id and test
+///   deliveries are flagged with `is_test`
+/// - 2: `from.username` added (as in WebSocket updates), `is_test` renamed
+///   to `isTest`
+pub const WEBHOOK_SCHEMA_VERSION: i32 = 2;
+
+/// Webhook payload structure (matches Requirement 6.5), in the latest schema
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct WebhookPayload {
+    #[serde(rename = "schemaVersion")]
+    pub schema_version: i32,
     #[serde(rename = "updateId")]
     pub update_id: Uuid,
     pub message: WebhookMessage,
+    /// Set only on test deliveries requested by the bot's owner
+    #[serde(rename = "isTest", default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_test: bool,
+}
+
+impl WebhookPayload {
+    /// A representative update for test deliveries, sent as if `from` had
+    /// typed `/start` in a chat with the bot
+    pub fn sample(from: Uuid) -> Self {
+        let message_id = Uuid::new_v4();
+        Self {
+            schema_version: WEBHOOK_SCHEMA_VERSION,
+            update_id: message_id,
+            message: WebhookMessage {
+                message_id,
+                chat: WebhookChat { id: Uuid::new_v4() },
+                from: WebhookUser { id: from, username: None },
+                text: "/start".to_string(),
+            },
+            is_test: true,
+        }
+    }
+
+    /// Render the payload in the schema version a bot asked for.
+    ///
+    /// `None` means the latest version. Unsupported versions also get the
+    /// latest; the requested version is then returned alongside so the
+    /// delivery can flag the mismatch.
+    pub fn render(&self, requested: Option<i32>) -> (VersionedWebhookPayload, Option<i32>) {
+        match requested.unwrap_or(WEBHOOK_SCHEMA_VERSION) {
+            1 => (VersionedWebhookPayload::V1(WebhookPayloadV1::from(self)), None),
+            WEBHOOK_SCHEMA_VERSION => (VersionedWebhookPayload::Latest(self.clone()), None),
+            version => (VersionedWebhookPayload::Latest(self.clone()), Some(version)),
+        }
+    }
+
+    /// Set the payload, rendered for `requested`, as the body of a webhook request
+    pub fn attach(
+        &self,
+        request: reqwest::RequestBuilder,
+        requested: Option<i32>,
+    ) -> reqwest::RequestBuilder {
+        let (body, mismatch) = self.render(requested);
+        let request = request.json(&body);
+        match mismatch {
+            Some(version) => request.header(WEBHOOK_SCHEMA_MISMATCH_HEADER, version.to_string()),
+            None => request,
+        }
+    }
 }
 
 /// Message data in webhook payload
@@ -226,6 +551,140 @@ pub struct WebhookChat {
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct WebhookUser {
     pub id: Uuid,
+    pub username: Option<String>,
+}
+
+/// Webhook payload of a reaction update; the same in every schema version
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WebhookReactionPayload {
+    #[serde(rename = "schemaVersion")]
+    pub schema_version: i32,
+    #[serde(rename = "updateId")]
+    pub update_id: Uuid,
+    pub reaction: WebhookReaction,
+}
+
+/// Reaction data in webhook payload
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WebhookReaction {
+    #[serde(rename = "messageId")]
+    pub message_id: Uuid,
+    pub chat: WebhookChat,
+    pub from: WebhookUser,
+    pub emoji: String,
+}
+
+impl From<&ReactionContext> for WebhookReactionPayload {
+    fn from(ctx: &ReactionContext) -> Self {
+        Self {
+            schema_version: WEBHOOK_SCHEMA_VERSION,
+            update_id: ctx.update_id,
+            reaction: WebhookReaction {
+                message_id: ctx.message_id,
+                chat: WebhookChat { id: ctx.chat_id },
+                from: WebhookUser {
+                    id: ctx.user_id,
+                    username: ctx.username.clone(),
+                },
+                emoji: ctx.emoji.clone(),
+            },
+        }
+    }
+}
+
+/// Webhook payload of a button press; the same in every schema version
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WebhookCallbackPayload {
+    #[serde(rename = "schemaVersion")]
+    pub schema_version: i32,
+    #[serde(rename = "updateId")]
+    pub update_id: Uuid,
+    #[serde(rename = "callbackQuery")]
+    pub callback_query: WebhookCallbackQuery,
+}
+
+/// Button press data in webhook payload
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WebhookCallbackQuery {
+    pub id: Uuid,
+    #[serde(rename = "messageId")]
+    pub message_id: Uuid,
+    pub chat: WebhookChat,
+    pub from: WebhookUser,
+    pub data: String,
+}
+
+impl From<&CallbackContext> for WebhookCallbackPayload {
+    fn from(ctx: &CallbackContext) -> Self {
+        Self {
+            schema_version: WEBHOOK_SCHEMA_VERSION,
+            update_id: ctx.query_id,
+            callback_query: WebhookCallbackQuery {
+                id: ctx.query_id,
+                message_id: ctx.message_id,
+                chat: WebhookChat { id: ctx.chat_id },
+                from: WebhookUser {
+                    id: ctx.user_id,
+                    username: ctx.username.clone(),
+                },
+                data: ctx.data.clone(),
+            },
+        }
+    }
+}
+
+/// A webhook payload rendered in a specific schema version
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(untagged)]
+pub enum VersionedWebhookPayload {
+    V1(WebhookPayloadV1),
+    Latest(WebhookPayload),
+}
+
+/// Webhook payload in schema version 1
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WebhookPayloadV1 {
+    #[serde(rename = "schemaVersion")]
+    pub schema_version: i32,
+    #[serde(rename = "updateId")]
+    pub update_id: Uuid,
+    pub message: WebhookMessageV1,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_test: bool,
+}
+
+/// Message data in a version 1 webhook payload
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WebhookMessageV1 {
+    #[serde(rename = "messageId")]
+    pub message_id: Uuid,
+    pub chat: WebhookChat,
+    pub from: WebhookUserV1,
+    pub text: String,
+}
+
+/// User info in a version 1 webhook payload
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct WebhookUserV1 {
+    pub id: Uuid,
+}
+
+impl From<&WebhookPayload> for WebhookPayloadV1 {
+    fn from(payload: &WebhookPayload) -> Self {
+        Self {
+            schema_version: 1,
+            update_id: payload.update_id,
+            message: WebhookMessageV1 {
+                message_id: payload.message.message_id,
+                chat: payload.message.chat.clone(),
+                from: WebhookUserV1 {
+                    id: payload.message.from.id,
+                },
+                text: payload.message.text.clone(),
+            },
+            is_test: payload.is_test,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -235,21 +694,127 @@ mod tests {
     #[test]
     fn test_webhook_payload_serialization() {
         let payload = WebhookPayload {
+            schema_version: WEBHOOK_SCHEMA_VERSION,
             update_id: Uuid::new_v4(),
             message: WebhookMessage {
                 message_id: Uuid::new_v4(),
                 chat: WebhookChat { id: Uuid::new_v4() },
-                from: WebhookUser { id: Uuid::new_v4() },
+                from: WebhookUser {
+                    id: Uuid::new_v4(),
+                    username: Some("alice".to_string()),
+                },
                 text: "Hello, bot!".to_string(),
             },
+            is_test: false,
         };
 
         let json = serde_json::to_string(&payload).unwrap();
+        assert!(json.contains("\"schemaVersion\":2"));
         assert!(json.contains("updateId"));
         assert!(json.contains("messageId"));
         assert!(json.contains("chat"));
         assert!(json.contains("from"));
         assert!(json.contains("text"));
+        assert!(!json.contains("isTest"));
+    }
+
+    #[test]
+    fn test_sample_webhook_payload_is_flagged() {
+        let owner = Uuid::new_v4();
+        let payload = WebhookPayload::sample(owner);
+        assert!(payload.is_test);
+        assert_eq!(payload.message.from.id, owner);
+
+        let json: serde_json::Value = serde_json::to_value(&payload).unwrap();
+        assert_eq!(json["isTest"], true);
+        assert_eq!(json["message"]["text"], "/start");
+    }
+
+    #[test]
+    fn test_webhook_payload_down_translates_to_v1() {
+        let mut payload = WebhookPayload::sample(Uuid::new_v4());
+        payload.message.from.username = Some("alice".to_string());
+
+        let (rendered, mismatch) = payload.render(Some(1));
+        assert_eq!(mismatch, None);
+        let json = serde_json::to_value(&rendered).unwrap();
+        assert_eq!(json["schemaVersion"], 1);
+        assert_eq!(json["is_test"], true);
+        assert!(json.get("isTest").is_none());
+        assert_eq!(json["message"]["from"]["id"], payload.message.from.id.to_string());
+        assert!(json["message"]["from"].get("username").is_none());
+        assert_eq!(json["message"]["text"], "/start");
+    }
+
+    #[test]
+    fn test_webhook_payload_renders_latest_by_default() {
+        let payload = WebhookPayload::sample(Uuid::new_v4());
+
+        for requested in [None, Some(WEBHOOK_SCHEMA_VERSION)] {
+            let (rendered, mismatch) = payload.render(requested);
+            assert_eq!(mismatch, None);
+            let json = serde_json::to_value(&rendered).unwrap();
+            assert_eq!(json["schemaVersion"], WEBHOOK_SCHEMA_VERSION);
+            assert_eq!(json["isTest"], true);
+        }
+    }
+
+    #[test]
+    fn test_unsupported_schema_version_gets_latest_with_mismatch() {
+        let payload = WebhookPayload::sample(Uuid::new_v4());
+
+        let (rendered, mismatch) = payload.render(Some(WEBHOOK_SCHEMA_VERSION + 1));
+        assert_eq!(mismatch, Some(WEBHOOK_SCHEMA_VERSION + 1));
+        let json = serde_json::to_value(&rendered).unwrap();
+        assert_eq!(json["schemaVersion"], WEBHOOK_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_reaction_update_payloads() {
+        let ctx = ReactionContext {
+            update_id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            username: Some("alice".to_string()),
+            chat_id: Uuid::new_v4(),
+            message_id: Uuid::new_v4(),
+            emoji: "👍".to_string(),
+            request_id: None,
+        };
+
+        let json = serde_json::to_value(WebhookReactionPayload::from(&ctx)).unwrap();
+        assert_eq!(json["schemaVersion"], WEBHOOK_SCHEMA_VERSION);
+        assert_eq!(json["reaction"]["messageId"], ctx.message_id.to_string());
+        assert_eq!(json["reaction"]["from"]["username"], "alice");
+        assert_eq!(json["reaction"]["emoji"], "👍");
+
+        // WebSocket bots get the same shape
+        let event = serde_json::to_value(BotUpdate::Reaction(ctx.clone()).event()).unwrap();
+        assert_eq!(event["event"], "reaction_added");
+        assert_eq!(event["data"]["updateId"], ctx.update_id.to_string());
+        assert_eq!(event["data"]["reaction"], json["reaction"]);
+    }
+
+    #[test]
+    fn test_callback_update_payloads() {
+        let ctx = CallbackContext {
+            query_id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            username: Some("alice".to_string()),
+            chat_id: Uuid::new_v4(),
+            message_id: Uuid::new_v4(),
+            data: "vote:yes".to_string(),
+            request_id: None,
+        };
+
+        let json = serde_json::to_value(WebhookCallbackPayload::from(&ctx)).unwrap();
+        assert_eq!(json["updateId"], ctx.query_id.to_string());
+        assert_eq!(json["callbackQuery"]["id"], ctx.query_id.to_string());
+        assert_eq!(json["callbackQuery"]["messageId"], ctx.message_id.to_string());
+        assert_eq!(json["callbackQuery"]["data"], "vote:yes");
+
+        let event = serde_json::to_value(BotUpdate::Callback(ctx).event()).unwrap();
+        assert_eq!(event["event"], "callback_query");
+        assert_eq!(event["data"]["callbackQuery"], json["callbackQuery"]);
     }
 
     #[test]
@@ -260,8 +825,29 @@ mod tests {
             chat_id: Uuid::new_v4(),
             message_id: Uuid::new_v4(),
             text: "/help".to_string(),
+            sender_bot_id: None,
+            request_id: None,
+            command_aliases: HashMap::new(),
         };
 
         assert!(!ctx.text.is_empty());
     }
+
+    #[test]
+    fn test_command_context_resolves_alias_per_bot() {
+        let (aliased, plain) = (Uuid::new_v4(), Uuid::new_v4());
+        let ctx = CommandContext {
+            user_id: Uuid::new_v4(),
+            sender_username: None,
+            chat_id: Uuid::new_v4(),
+            message_id: Uuid::new_v4(),
+            text: "/s \"all servers\"".to_string(),
+            sender_bot_id: None,
+            request_id: None,
+            command_aliases: HashMap::from([(aliased, "status".to_string())]),
+        };
+
+        assert_eq!(ctx.for_bot(aliased).text, "/status \"all servers\"");
+        assert_eq!(ctx.for_bot(plain).text, "/s \"all servers\"");
+    }
 }
@@ -0,0 +1,730 @@
+/// Formatting module for bot messages sent with a parse mode.
+///
+/// Converts Markdown or HTML markup into plain text plus a list of
+/// formatting entities (bold, italic, code, pre, links). Clients render from
+/// the entities, so markup itself is never stored or trusted. Malformed
+/// markup is rejected instead of being sent half-formatted.
+///
+/// Markdown syntax: `*bold*`, `_italic_`, `` `code` ``, ```` ```lang\npre``` ````
+/// and `[text](url)`. Any character can be escaped with `\`; literal `*`, `_`,
+/// `` ` ``, `[` and `]` must be. Inside code only `` \` `` and `\\` are escapes.
+///
+/// HTML syntax: `<b>`/`<strong>`, `<i>`/`<em>`, `<code>`, `<pre>` (optionally
+/// wrapping `<code class="language-x">`) and `<a href="...">`, with the
+/// `&lt;` `&gt;` `&amp;` `&quot;` `&apos;` and numeric character entities.
+///
+/// Entity offsets and lengths count UTF-16 code units of the plain text.
+
+use thiserror::Error;
+use url::Url;
+
+use crate::models::{EntityKind, MessageEntity, ParseMode};
+use crate::services::link_preview::parse_attributes;
+
+/// Longest language name accepted on a pre block
+const MAX_LANGUAGE_LENGTH: usize = 32;
+
+/// Why markup could not be parsed; positions are character offsets in the input
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum FormattingError {
+    #[error("unclosed {0}")]
+    Unclosed(String),
+    #[error("unexpected '{token}' at position {position}")]
+    Unexpected { token: String, position: usize },
+    #[error("'{found}' at position {position} closes '{expected}' out of order")]
+    Misnested {
+        expected: String,
+        found: String,
+        position: usize,
+    },
+    #[error("unsupported tag <{0}>")]
+    UnsupportedTag(String),
+    #[error("tags are not allowed inside code (position {0})")]
+    TagInCode(usize),
+    #[error("invalid link URL '{0}'")]
+    InvalidUrl(String),
+    #[error("invalid code block language '{0}'")]
+    InvalidLanguage(String),
+    #[error("unknown character entity '&{0};'")]
+    UnknownCharEntity(String),
+}
+
+/// Plain text with the entities describing its formatting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormattedText {
+    pub text: String,
+    pub entities: Vec<MessageEntity>,
+}
+
+/// Parse `input` according to `mode`
+pub fn parse(input: &str, mode: ParseMode) -> Result<FormattedText, FormattingError> {
+    match mode {
+        ParseMode::None => Ok(FormattedText {
+            text: input.to_string(),
+            entities: Vec::new(),
+        }),
+        ParseMode::Markdown => parse_markdown(input),
+        ParseMode::Html => parse_html(input),
+    }
+}
+
+/// Accumulates plain text, tracking its UTF-16 length for entity offsets
+#[derive(Default)]
+struct Builder {
+    text: String,
+    utf16_len: usize,
+    entities: Vec<MessageEntity>,
+}
+
+impl Builder {
+    fn push(&mut self, c: char) {
+        self.text.push(c);
+        self.utf16_len += c.len_utf16();
+    }
+
+    /// Record an entity from `start` to the current end; empty spans are dropped
+    fn close(
+        &mut self,
+        kind: EntityKind,
+        start: usize,
+        url: Option<String>,
+        language: Option<String>,
+    ) {
+        if self.utf16_len > start {
+            self.entities.push(MessageEntity {
+                kind,
+                offset: start,
+                length: self.utf16_len - start,
+                url,
+                language,
+            });
+        }
+    }
+
+    fn finish(mut self) -> FormattedText {
+        // Outer entities before the ones nested in them; entities are recorded
+        // as they close (inner first), so reverse to keep that for equal spans
+        self.entities.reverse();
+        self.entities
+            .sort_by(|a, b| a.offset.cmp(&b.offset).then(b.length.cmp(&a.length)));
+        FormattedText {
+            text: self.text,
+            entities: self.entities,
+        }
+    }
+}
+
+fn unexpected(token: &str, position: usize) -> FormattingError {
+    FormattingError::Unexpected {
+        token: token.to_string(),
+        position,
+    }
+}
+
+fn validate_url(raw: &str) -> Result<String, FormattingError> {
+    let invalid = || FormattingError::InvalidUrl(raw.to_string());
+    let url = Url::parse(raw.trim()).map_err(|_| invalid())?;
+    if !matches!(url.scheme(), "http" | "https" | "mailto") {
+        return Err(invalid());
+    }
+    Ok(url.to_string())
+}
+
+fn validate_language(language: &str) -> Result<String, FormattingError> {
+    let valid = !language.is_empty()
+        && language.len() <= MAX_LANGUAGE_LENGTH
+        && language
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '+' | '-' | '#' | '.'));
+    if !valid {
+        return Err(FormattingError::InvalidLanguage(language.to_string()));
+    }
+    Ok(language.to_string())
+}
+
+// ---------------------------------------------------------------------------
+// Markdown
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MdSpan {
+    Bold,
+    Italic,
+    LinkText,
+}
+
+impl MdSpan {
+    fn token(self) -> &'static str {
+        match self {
+            MdSpan::Bold => "*",
+            MdSpan::Italic => "_",
+            MdSpan::LinkText => "[",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            MdSpan::Bold => "bold",
+            MdSpan::Italic => "italic",
+            MdSpan::LinkText => "link",
+        }
+    }
+}
+
+fn parse_markdown(input: &str) -> Result<FormattedText, FormattingError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut out = Builder::default();
+    let mut stack: Vec<(MdSpan, usize)> = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => {
+                let escaped = chars.get(i + 1).ok_or_else(|| unexpected("\\", i))?;
+                out.push(*escaped);
+                i += 2;
+                continue;
+            }
+            '*' => toggle_span(&mut out, &mut stack, MdSpan::Bold, i)?,
+            '_' => toggle_span(&mut out, &mut stack, MdSpan::Italic, i)?,
+            '`' => {
+                i = if chars[i..].starts_with(&['`', '`', '`']) {
+                    parse_md_pre(&chars, i + 3, &mut out)?
+                } else {
+                    parse_md_code(&chars, i + 1, &mut out)?
+                };
+                continue;
+            }
+            '[' => stack.push((MdSpan::LinkText, out.utf16_len)),
+            ']' => match stack.last().copied() {
+                Some((MdSpan::LinkText, start)) => {
+                    stack.pop();
+                    if chars.get(i + 1) != Some(&'(') {
+                        return Err(unexpected("]", i));
+                    }
+                    let (url, next) = read_md_url(&chars, i + 2)?;
+                    out.close(EntityKind::TextLink, start, Some(url), None);
+                    i = next;
+                    continue;
+                }
+                Some((open, _)) if stack.iter().any(|(span, _)| *span == MdSpan::LinkText) => {
+                    return Err(FormattingError::Misnested {
+                        expected: open.token().to_string(),
+                        found: "]".to_string(),
+                        position: i,
+                    });
+                }
+                _ => return Err(unexpected("]", i)),
+            },
+            c => out.push(c),
+        }
+        i += 1;
+    }
+
+    if let Some((span, _)) = stack.last() {
+        return Err(FormattingError::Unclosed(span.name().to_string()));
+    }
+    Ok(out.finish())
+}
+
+/// Open `span`, or close it if it is the innermost open span
+fn toggle_span(
+    out: &mut Builder,
+    stack: &mut Vec<(MdSpan, usize)>,
+    span: MdSpan,
+    position: usize,
+) -> Result<(), FormattingError> {
+    match stack.last().copied() {
+        Some((top, start)) if top == span => {
+            stack.pop();
+            let kind = match span {
+                MdSpan::Bold => EntityKind::Bold,
+                _ => EntityKind::Italic,
+            };
+            out.close(kind, start, None, None);
+            Ok(())
+        }
+        Some((top, _)) if stack.iter().any(|(open, _)| *open == span) => {
+            Err(FormattingError::Misnested {
+                expected: top.token().to_string(),
+                found: span.token().to_string(),
+                position,
+            })
+        }
+        _ => {
+            stack.push((span, out.utf16_len));
+            Ok(())
+        }
+    }
+}
+
+/// Read a link target up to the closing `)`; returns it and the index after `)`
+fn read_md_url(chars: &[char], mut i: usize) -> Result<(String, usize), FormattingError> {
+    let mut url = String::new();
+    loop {
+        match chars.get(i) {
+            None => return Err(FormattingError::Unclosed("link URL".to_string())),
+            Some('\\') => {
+                let escaped = chars.get(i + 1).ok_or_else(|| unexpected("\\", i))?;
+                url.push(*escaped);
+                i += 2;
+            }
+            Some(')') => return Ok((validate_url(&url)?, i + 1)),
+            Some(c) => {
+                url.push(*c);
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Copy code content verbatim until `closing`, honouring only `` \` `` and `\\`
+fn read_md_code_body(
+    chars: &[char],
+    mut i: usize,
+    closing: &[char],
+    out: &mut Builder,
+    name: &str,
+) -> Result<usize, FormattingError> {
+    loop {
+        match chars.get(i) {
+            None => return Err(FormattingError::Unclosed(name.to_string())),
+            Some('\\') if matches!(chars.get(i + 1), Some('`' | '\\')) => {
+                out.push(chars[i + 1]);
+                i += 2;
+            }
+            Some(_) if chars[i..].starts_with(closing) => return Ok(i + closing.len()),
+            Some(c) => {
+                out.push(*c);
+                i += 1;
+            }
+        }
+    }
+}
+
+fn parse_md_code(chars: &[char], i: usize, out: &mut Builder) -> Result<usize, FormattingError> {
+    let start = out.utf16_len;
+    let next = read_md_code_body(chars, i, &['`'], out, "code")?;
+    out.close(EntityKind::Code, start, None, None);
+    Ok(next)
+}
+
+fn parse_md_pre(chars: &[char], mut i: usize, out: &mut Builder) -> Result<usize, FormattingError> {
+    // ```lang\n...``` - the first line names the language when it is a single word
+    let mut language = None;
+    if let Some(newline) = chars[i..].iter().position(|c| *c == '\n') {
+        let first_line: String = chars[i..i + newline].iter().collect();
+        if !first_line.is_empty() && !first_line.contains(|c: char| c.is_whitespace() || c == '`')
+        {
+            language = Some(validate_language(&first_line)?);
+            i += newline + 1;
+        } else if first_line.is_empty() {
+            i += 1;
+        }
+    }
+
+    let start = out.utf16_len;
+    let next = read_md_code_body(chars, i, &['`', '`', '`'], out, "pre")?;
+    out.close(EntityKind::Pre, start, None, language);
+    Ok(next)
+}
+
+// ---------------------------------------------------------------------------
+// HTML
+// ---------------------------------------------------------------------------
+
+struct OpenTag {
+    name: String,
+    /// `None` for the `<code>` wrapper inside `<pre>`, which adds no entity
+    kind: Option<EntityKind>,
+    start: usize,
+    url: Option<String>,
+    language: Option<String>,
+}
+
+fn parse_html(input: &str) -> Result<FormattedText, FormattingError> {
+    let mut out = Builder::default();
+    let mut stack: Vec<OpenTag> = Vec::new();
+    let mut rest = input;
+    let position = |rest: &str| input[..input.len() - rest.len()].chars().count();
+
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '<' => {
+                let end = rest.find('>').ok_or_else(|| unexpected("<", position(rest)))?;
+                let tag = &rest[1..end];
+                let tag_position = position(rest);
+                rest = &rest[end + 1..];
+
+                if let Some(name) = tag.strip_prefix('/') {
+                    let name = name.trim().to_ascii_lowercase();
+                    let found = format!("</{}>", name);
+                    let open = stack
+                        .pop()
+                        .ok_or_else(|| unexpected(&found, tag_position))?;
+                    if open.name != name {
+                        return Err(FormattingError::Misnested {
+                            expected: format!("</{}>", open.name),
+                            found,
+                            position: tag_position,
+                        });
+                    }
+                    if let Some(kind) = open.kind {
+                        out.close(kind, open.start, open.url, open.language);
+                    }
+                } else {
+                    let open = open_html_tag(tag, &mut stack, out.utf16_len, tag_position)?;
+                    stack.push(open);
+                }
+            }
+            '&' => match take_char_entity(rest)? {
+                Some((decoded, len)) => {
+                    out.push(decoded);
+                    rest = &rest[len..];
+                }
+                None => {
+                    out.push('&');
+                    rest = &rest[1..];
+                }
+            },
+            c => {
+                out.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+
+    if let Some(open) = stack.last() {
+        return Err(FormattingError::Unclosed(format!("<{}>", open.name)));
+    }
+    Ok(out.finish())
+}
+
+fn open_html_tag(
+    tag: &str,
+    stack: &mut [OpenTag],
+    start: usize,
+    position: usize,
+) -> Result<OpenTag, FormattingError> {
+    let name_len = tag
+        .find(|c: char| c.is_whitespace() || c == '/')
+        .unwrap_or(tag.len());
+    let name = tag[..name_len].to_ascii_lowercase();
+    let attrs = parse_attributes(&tag[name_len..]);
+    let attr = |wanted: &str| {
+        attrs
+            .iter()
+            .find(|(name, _)| name == wanted)
+            .map(|(_, value)| unescape(value))
+            .transpose()
+    };
+
+    let open = |kind: Option<EntityKind>| OpenTag {
+        name: name.clone(),
+        kind,
+        start,
+        url: None,
+        language: None,
+    };
+
+    match stack.last_mut() {
+        // <pre><code class="language-x"> names the block's language
+        Some(pre) if pre.name == "pre" && name == "code" && pre.start == start => {
+            if let Some(class) = attr("class")? {
+                if let Some(language) = class.strip_prefix("language-") {
+                    pre.language = Some(validate_language(language)?);
+                }
+            }
+            return Ok(open(None));
+        }
+        Some(code) if code.name == "code" || code.name == "pre" => {
+            return Err(FormattingError::TagInCode(position));
+        }
+        _ => {}
+    }
+
+    match name.as_str() {
+        "b" | "strong" => Ok(open(Some(EntityKind::Bold))),
+        "i" | "em" => Ok(open(Some(EntityKind::Italic))),
+        "code" => Ok(open(Some(EntityKind::Code))),
+        "pre" => Ok(open(Some(EntityKind::Pre))),
+        "a" => {
+            let href = attr("href")?.ok_or_else(|| FormattingError::InvalidUrl(String::new()))?;
+            Ok(OpenTag {
+                url: Some(validate_url(&href)?),
+                ..open(Some(EntityKind::TextLink))
+            })
+        }
+        _ => Err(FormattingError::UnsupportedTag(name.clone())),
+    }
+}
+
+/// Decode a character entity at the start of `s` (which begins with `&`)
+///
+/// Returns the character and the bytes consumed, or `None` when the `&` does
+/// not start an entity and should be kept literally.
+fn take_char_entity(s: &str) -> Result<Option<(char, usize)>, FormattingError> {
+    let Some(end) = s.find(';').filter(|&end| end > 1 && end <= 10) else {
+        return Ok(None);
+    };
+    let name = &s[1..end];
+    if !name
+        .strip_prefix('#')
+        .unwrap_or(name)
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric())
+    {
+        return Ok(None);
+    }
+
+    let decoded = match name {
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "amp" => Some('&'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some('\u{a0}'),
+        _ => name
+            .strip_prefix("#x")
+            .or_else(|| name.strip_prefix("#X"))
+            .map(|hex| u32::from_str_radix(hex, 16))
+            .or_else(|| name.strip_prefix('#').map(str::parse::<u32>))
+            .and_then(Result::ok)
+            .and_then(char::from_u32)
+            .filter(|c| !c.is_control() || matches!(c, '\n' | '\t')),
+    };
+
+    decoded
+        .map(|c| Some((c, end + 1)))
+        .ok_or_else(|| FormattingError::UnknownCharEntity(name.to_string()))
+}
+
+/// Decode character entities in an attribute value
+fn unescape(value: &str) -> Result<String, FormattingError> {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        match take_char_entity(rest)? {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(kind: EntityKind, offset: usize, length: usize) -> MessageEntity {
+        MessageEntity {
+            kind,
+            offset,
+            length,
+            url: None,
+            language: None,
+        }
+    }
+
+    fn link(offset: usize, length: usize, url: &str) -> MessageEntity {
+        MessageEntity {
+            url: Some(url.to_string()),
+            ..entity(EntityKind::TextLink, offset, length)
+        }
+    }
+
+    #[test]
+    fn test_plain_mode_is_verbatim() {
+        let formatted = parse("*not* <b>parsed</b>", ParseMode::None).unwrap();
+        assert_eq!(formatted.text, "*not* <b>parsed</b>");
+        assert!(formatted.entities.is_empty());
+    }
+
+    #[test]
+    fn test_markdown_basic() {
+        let formatted = parse(
+            "*bold* _it_ `code` [link](https://example.com/a)",
+            ParseMode::Markdown,
+        )
+        .unwrap();
+        assert_eq!(formatted.text, "bold it code link");
+        assert_eq!(
+            formatted.entities,
+            vec![
+                entity(EntityKind::Bold, 0, 4),
+                entity(EntityKind::Italic, 5, 2),
+                entity(EntityKind::Code, 8, 4),
+                link(13, 4, "https://example.com/a"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_markdown_nested_and_utf16_offsets() {
+        let formatted = parse("😀 *bold _both_*", ParseMode::Markdown).unwrap();
+        assert_eq!(formatted.text, "😀 bold both");
+        // The emoji is two UTF-16 code units
+        assert_eq!(
+            formatted.entities,
+            vec![
+                entity(EntityKind::Bold, 3, 9),
+                entity(EntityKind::Italic, 8, 4),
+            ]
+        );
+
+        let formatted = parse("[*bold link*](https://example.com)", ParseMode::Markdown).unwrap();
+        assert_eq!(
+            formatted.entities,
+            vec![
+                link(0, 9, "https://example.com/"),
+                entity(EntityKind::Bold, 0, 9),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_markdown_escapes_and_code() {
+        let formatted = parse(r"\*not bold\* 2\_000", ParseMode::Markdown).unwrap();
+        assert_eq!(formatted.text, "*not bold* 2_000");
+        assert!(formatted.entities.is_empty());
+
+        // Markup inside code is literal
+        let formatted = parse(r"`*x* _y_ [z] \` \\`", ParseMode::Markdown).unwrap();
+        assert_eq!(formatted.text, r"*x* _y_ [z] ` \");
+        assert_eq!(formatted.entities, vec![entity(EntityKind::Code, 0, 15)]);
+
+        let formatted = parse("```rust\nlet x = *y*;\n```", ParseMode::Markdown).unwrap();
+        assert_eq!(formatted.text, "let x = *y*;\n");
+        assert_eq!(
+            formatted.entities,
+            vec![MessageEntity {
+                language: Some("rust".to_string()),
+                ..entity(EntityKind::Pre, 0, 13)
+            }]
+        );
+
+        let formatted = parse("```a _b_ c```", ParseMode::Markdown).unwrap();
+        assert_eq!(formatted.text, "a _b_ c");
+        assert_eq!(formatted.entities[0].language, None);
+    }
+
+    #[test]
+    fn test_markdown_malformed() {
+        assert_eq!(
+            parse("*bold", ParseMode::Markdown),
+            Err(FormattingError::Unclosed("bold".to_string()))
+        );
+        assert!(matches!(
+            parse("*a _b* c_", ParseMode::Markdown),
+            Err(FormattingError::Misnested { .. })
+        ));
+        assert!(matches!(
+            parse("a ] b", ParseMode::Markdown),
+            Err(FormattingError::Unexpected { position: 2, .. })
+        ));
+        assert!(matches!(
+            parse("[text] no url", ParseMode::Markdown),
+            Err(FormattingError::Unexpected { .. })
+        ));
+        assert!(matches!(
+            parse("[x](javascript:alert(1))", ParseMode::Markdown),
+            Err(FormattingError::InvalidUrl(_))
+        ));
+        assert_eq!(
+            parse("`code", ParseMode::Markdown),
+            Err(FormattingError::Unclosed("code".to_string()))
+        );
+        assert_eq!(
+            parse("```pre", ParseMode::Markdown),
+            Err(FormattingError::Unclosed("pre".to_string()))
+        );
+        assert!(matches!(
+            parse("trailing \\", ParseMode::Markdown),
+            Err(FormattingError::Unexpected { .. })
+        ));
+    }
+
+    #[test]
+    fn test_html_basic() {
+        let formatted = parse(
+            r#"<b>bold <i>both</i></b> &lt;tag&gt; <a href="https://e.com/?a=1&amp;b=2">l</a> Tom & Jerry"#,
+            ParseMode::Html,
+        )
+        .unwrap();
+        assert_eq!(formatted.text, "bold both <tag> l Tom & Jerry");
+        assert_eq!(
+            formatted.entities,
+            vec![
+                entity(EntityKind::Bold, 0, 9),
+                entity(EntityKind::Italic, 5, 4),
+                link(16, 1, "https://e.com/?a=1&b=2"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_html_code_blocks() {
+        let formatted = parse(
+            r#"<pre><code class="language-python">print(1 &lt; 2)</code></pre>"#,
+            ParseMode::Html,
+        )
+        .unwrap();
+        assert_eq!(formatted.text, "print(1 < 2)");
+        assert_eq!(
+            formatted.entities,
+            vec![MessageEntity {
+                language: Some("python".to_string()),
+                ..entity(EntityKind::Pre, 0, 12)
+            }]
+        );
+
+        // Markup inside code has to be escaped
+        assert_eq!(
+            parse("<code><b>x</b></code>", ParseMode::Html),
+            Err(FormattingError::TagInCode(6))
+        );
+        let formatted = parse("<code>&lt;b&gt;x</code>", ParseMode::Html).unwrap();
+        assert_eq!(formatted.text, "<b>x");
+    }
+
+    #[test]
+    fn test_html_malformed() {
+        assert_eq!(
+            parse("<b>x", ParseMode::Html),
+            Err(FormattingError::Unclosed("<b>".to_string()))
+        );
+        assert!(matches!(
+            parse("<b><i>x</b></i>", ParseMode::Html),
+            Err(FormattingError::Misnested { .. })
+        ));
+        assert!(matches!(
+            parse("x</b>", ParseMode::Html),
+            Err(FormattingError::Unexpected { position: 1, .. })
+        ));
+        assert_eq!(
+            parse("<script>x</script>", ParseMode::Html),
+            Err(FormattingError::UnsupportedTag("script".to_string()))
+        );
+        assert!(matches!(
+            parse("<a>x</a>", ParseMode::Html),
+            Err(FormattingError::InvalidUrl(_))
+        ));
+        assert!(matches!(
+            parse("<b", ParseMode::Html),
+            Err(FormattingError::Unexpected { .. })
+        ));
+        assert_eq!(
+            parse("&bogus;", ParseMode::Html),
+            Err(FormattingError::UnknownCharEntity("bogus".to_string()))
+        );
+    }
+}
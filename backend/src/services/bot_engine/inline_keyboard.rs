@@ -0,0 +1,244 @@
+/// Inline Keyboard module - buttons attached to bot messages.
+///
+/// This module provides:
+/// - Validation of keyboards sent through the bot API: bounded rows,
+///   buttons and label lengths, and each button either a URL or a
+///   `callback_data` of at most `MAX_CALLBACK_DATA_BYTES`
+/// - Storage in `inline_keyboards`, one row per button, so keyboards are
+///   returned with the message in history and callbacks can be checked
+///   against the buttons actually shown
+use thiserror::Error;
+use url::Url;
+use uuid::Uuid;
+
+use crate::db::Database;
+use crate::error::AppResult;
+use crate::models::InlineButton;
+
+/// Most rows in one keyboard
+pub const MAX_KEYBOARD_ROWS: usize = 10;
+
+/// Most buttons in one row
+pub const MAX_BUTTONS_PER_ROW: usize = 8;
+
+/// Longest button label, in characters
+pub const MAX_BUTTON_TEXT_CHARS: usize = 64;
+
+/// Largest `callback_data`, in bytes
+pub const MAX_CALLBACK_DATA_BYTES: usize = 64;
+
+/// Why a keyboard was refused
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum KeyboardError {
+    #[error("inline keyboard has more than {MAX_KEYBOARD_ROWS} rows")]
+    TooManyRows,
+    #[error("row {0} is empty or has more than {MAX_BUTTONS_PER_ROW} buttons")]
+    RowSize(usize),
+    #[error("button {row}.{button} needs a label of 1-{MAX_BUTTON_TEXT_CHARS} characters")]
+    Label { row: usize, button: usize },
+    #[error("button {row}.{button} needs exactly one of callbackData or url")]
+    Action { row: usize, button: usize },
+    #[error("callbackData of button {row}.{button} must be 1-{MAX_CALLBACK_DATA_BYTES} bytes")]
+    CallbackData { row: usize, button: usize },
+    #[error("url of button {row}.{button} must be an http(s) URL")]
+    Url { row: usize, button: usize },
+}
+
+/// Check a keyboard before it is stored; rows and buttons count from 1
+pub fn validate(keyboard: &[Vec<InlineButton>]) -> Result<(), KeyboardError> {
+    if keyboard.len() > MAX_KEYBOARD_ROWS {
+        return Err(KeyboardError::TooManyRows);
+    }
+
+    for (r, row) in keyboard.iter().enumerate() {
+        let row_number = r + 1;
+        if row.is_empty() || row.len() > MAX_BUTTONS_PER_ROW {
+            return Err(KeyboardError::RowSize(row_number));
+        }
+
+        for (b, button) in row.iter().enumerate() {
+            let (row, button_number) = (row_number, b + 1);
+            let label = button.text.trim().chars().count();
+            if label == 0 || label > MAX_BUTTON_TEXT_CHARS {
+                return Err(KeyboardError::Label { row, button: button_number });
+            }
+
+            match (&button.callback_data, &button.url) {
+                (Some(data), None) => {
+                    if data.is_empty() || data.len() > MAX_CALLBACK_DATA_BYTES {
+                        return Err(KeyboardError::CallbackData { row, button: button_number });
+                    }
+                }
+                (None, Some(url)) => {
+                    let valid = Url::parse(url)
+                        .is_ok_and(|url| matches!(url.scheme(), "http" | "https"));
+                    if !valid {
+                        return Err(KeyboardError::Url { row, button: button_number });
+                    }
+                }
+                _ => return Err(KeyboardError::Action { row, button: button_number }),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Stored inline keyboards
+pub struct InlineKeyboardStore;
+
+impl InlineKeyboardStore {
+    /// Replace the keyboard of a message; an empty keyboard removes it
+    pub async fn save(
+        db: &Database,
+        message_id: Uuid,
+        keyboard: &[Vec<InlineButton>],
+    ) -> AppResult<()> {
+        let mut tx = db.pool.begin().await?;
+
+        sqlx::query("DELETE FROM inline_keyboards WHERE message_id = $1")
+            .bind(message_id)
+            .execute(&mut *tx)
+            .await?;
+
+        for (row_index, row) in keyboard.iter().enumerate() {
+            for (button_index, button) in row.iter().enumerate() {
+                sqlx::query(
+                    r#"
+                    INSERT INTO inline_keyboards
+                        (message_id, row_index, button_index, text, callback_data, url)
+                    VALUES ($1, $2, $3, $4, $5, $6)
+                    "#,
+                )
+                .bind(message_id)
+                .bind(row_index as i32)
+                .bind(button_index as i32)
+                .bind(button.text.trim())
+                .bind(&button.callback_data)
+                .bind(&button.url)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// The keyboard of a message, if it has one
+    pub async fn load(db: &Database, message_id: Uuid) -> AppResult<Option<Vec<Vec<InlineButton>>>> {
+        let buttons: Vec<(i32, String, Option<String>, Option<String>)> = sqlx::query_as(
+            r#"
+            SELECT row_index, text, callback_data, url FROM inline_keyboards
+            WHERE message_id = $1
+            ORDER BY row_index, button_index
+            "#,
+        )
+        .bind(message_id)
+        .fetch_all(&db.pool)
+        .await?;
+
+        if buttons.is_empty() {
+            return Ok(None);
+        }
+
+        let mut keyboard: Vec<Vec<InlineButton>> = Vec::new();
+        let mut current_row = None;
+        for (row_index, text, callback_data, url) in buttons {
+            if current_row != Some(row_index) {
+                keyboard.push(Vec::new());
+                current_row = Some(row_index);
+            }
+            if let Some(row) = keyboard.last_mut() {
+                row.push(InlineButton { text, callback_data, url });
+            }
+        }
+
+        Ok(Some(keyboard))
+    }
+
+    /// Whether the message shows a button carrying `callback_data`
+    pub async fn has_callback(db: &Database, message_id: Uuid, callback_data: &str) -> AppResult<bool> {
+        let found: Option<(i32,)> = sqlx::query_as(
+            "SELECT 1 FROM inline_keyboards WHERE message_id = $1 AND callback_data = $2 LIMIT 1",
+        )
+        .bind(message_id)
+        .bind(callback_data)
+        .fetch_optional(&db.pool)
+        .await?;
+
+        Ok(found.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn callback(text: &str, data: &str) -> InlineButton {
+        InlineButton {
+            text: text.to_string(),
+            callback_data: Some(data.to_string()),
+            url: None,
+        }
+    }
+
+    #[test]
+    fn test_valid_keyboard() {
+        let keyboard = vec![
+            vec![callback("Yes", "vote:yes"), callback("No", "vote:no")],
+            vec![InlineButton {
+                text: "Results".to_string(),
+                callback_data: None,
+                url: Some("https://example.com/results".to_string()),
+            }],
+        ];
+        assert_eq!(validate(&keyboard), Ok(()));
+        assert_eq!(validate(&[]), Ok(()));
+    }
+
+    #[test]
+    fn test_keyboard_size_is_bounded() {
+        let rows = vec![vec![callback("a", "a")]; MAX_KEYBOARD_ROWS + 1];
+        assert_eq!(validate(&rows), Err(KeyboardError::TooManyRows));
+
+        let wide = vec![vec![callback("a", "a"); MAX_BUTTONS_PER_ROW + 1]];
+        assert_eq!(validate(&wide), Err(KeyboardError::RowSize(1)));
+        assert_eq!(
+            validate(&[vec![callback("a", "a")], vec![]]),
+            Err(KeyboardError::RowSize(2))
+        );
+    }
+
+    #[test]
+    fn test_buttons_are_checked() {
+        let long_label = "x".repeat(MAX_BUTTON_TEXT_CHARS + 1);
+        assert_eq!(
+            validate(&[vec![callback(&long_label, "a")]]),
+            Err(KeyboardError::Label { row: 1, button: 1 })
+        );
+        assert_eq!(
+            validate(&[vec![callback("ok", "a"), callback(" ", "b")]]),
+            Err(KeyboardError::Label { row: 1, button: 2 })
+        );
+
+        // Callback data is limited in bytes, not characters
+        let data = "é".repeat(MAX_CALLBACK_DATA_BYTES / 2 + 1);
+        assert_eq!(
+            validate(&[vec![callback("ok", &data)]]),
+            Err(KeyboardError::CallbackData { row: 1, button: 1 })
+        );
+
+        let neither = InlineButton { text: "ok".to_string(), callback_data: None, url: None };
+        assert_eq!(
+            validate(&[vec![neither]]),
+            Err(KeyboardError::Action { row: 1, button: 1 })
+        );
+        let bad_url = InlineButton {
+            text: "ok".to_string(),
+            callback_data: None,
+            url: Some("javascript:alert(1)".to_string()),
+        };
+        assert_eq!(validate(&[vec![bad_url]]), Err(KeyboardError::Url { row: 1, button: 1 }));
+    }
+}
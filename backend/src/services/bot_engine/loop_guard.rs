@@ -0,0 +1,137 @@
+/// Loop Guard module - breaks bot-to-bot message loops.
+///
+/// This module provides:
+/// - Per-chat tracking of bot-authored commands/messages
+/// - Suppression once the same command bounces too often within a short window
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use super::command_parser::ParsedCommand;
+
+/// Bot-authored repeats of the same command allowed per chat within the window
+pub const DEFAULT_MAX_BOUNCES: usize = 5;
+
+/// Window over which repeats are counted
+pub const DEFAULT_BOUNCE_WINDOW: Duration = Duration::from_secs(10);
+
+/// Number of tracked keys above which stale entries are pruned
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Tracks recent bot-authored messages per chat to detect loops
+pub struct LoopGuard {
+    max_bounces: usize,
+    window: Duration,
+    seen: Mutex<HashMap<(Uuid, String), VecDeque<Instant>>>,
+}
+
+impl LoopGuard {
+    pub fn new(max_bounces: usize, window: Duration) -> Self {
+        Self {
+            max_bounces,
+            window,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record a bot-authored message in a chat.
+    ///
+    /// Returns false when the same command has already bounced `max_bounces`
+    /// times within the window, in which case it must not be dispatched.
+    pub fn allow(&self, chat_id: Uuid, text: &str) -> bool {
+        self.allow_at(chat_id, text, Instant::now())
+    }
+
+    fn allow_at(&self, chat_id: Uuid, text: &str, now: Instant) -> bool {
+        let mut seen = self.seen.lock().unwrap();
+
+        if seen.len() > PRUNE_THRESHOLD {
+            seen.retain(|_, hits| {
+                hits.back()
+                    .is_some_and(|last| now.duration_since(*last) < self.window)
+            });
+        }
+
+        let hits = seen.entry((chat_id, bounce_key(text))).or_default();
+        while hits
+            .front()
+            .is_some_and(|first| now.duration_since(*first) >= self.window)
+        {
+            hits.pop_front();
+        }
+
+        if hits.len() >= self.max_bounces {
+            return false;
+        }
+
+        hits.push_back(now);
+        true
+    }
+}
+
+impl Default for LoopGuard {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_BOUNCES, DEFAULT_BOUNCE_WINDOW)
+    }
+}
+
+/// Commands are keyed by name so `/ping 1` and `/ping 2` count as the same
+/// bounce; plain text is keyed by its normalized content.
+fn bounce_key(text: &str) -> String {
+    match ParsedCommand::parse(text) {
+        Some(cmd) => format!("/{}", cmd.command),
+        None => text.trim().to_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounce_key() {
+        assert_eq!(bounce_key("/ping 1"), bounce_key("/ping 2"));
+        assert_eq!(bounce_key(" Hello "), bounce_key("hello"));
+        assert_ne!(bounce_key("/ping"), bounce_key("/pong"));
+    }
+
+    #[test]
+    fn test_two_echo_bots_loop_is_broken() {
+        let guard = LoopGuard::new(5, Duration::from_secs(10));
+        let chat_id = Uuid::new_v4();
+        let now = Instant::now();
+
+        // Bot A and bot B echo each other's "/echo" forever; every message
+        // that gets through triggers the other bot's reply.
+        let mut delivered = 0;
+        for i in 0..100 {
+            let text = if i % 2 == 0 { "/echo from a" } else { "/echo from b" };
+            if !guard.allow_at(chat_id, text, now + Duration::from_millis(i * 10)) {
+                break;
+            }
+            delivered += 1;
+        }
+
+        assert_eq!(delivered, 5);
+    }
+
+    #[test]
+    fn test_window_expiry_allows_again() {
+        let guard = LoopGuard::new(2, Duration::from_secs(10));
+        let chat_id = Uuid::new_v4();
+        let now = Instant::now();
+
+        assert!(guard.allow_at(chat_id, "/ping", now));
+        assert!(guard.allow_at(chat_id, "/ping", now));
+        assert!(!guard.allow_at(chat_id, "/ping", now));
+        assert!(guard.allow_at(chat_id, "/ping", now + Duration::from_secs(11)));
+    }
+
+    #[test]
+    fn test_chats_are_tracked_separately() {
+        let guard = LoopGuard::new(1, Duration::from_secs(10));
+        assert!(guard.allow(Uuid::new_v4(), "/ping"));
+        assert!(guard.allow(Uuid::new_v4(), "/ping"));
+    }
+}
@@ -5,19 +5,27 @@
 /// - Command parsing on new messages
 /// - BotFather command handling
 /// - Dispatch to bots when command detected
+/// - Bot-to-bot dispatch with opt-in and loop detection
+/// - Reaction dispatch to bots holding `read_reactions`
+/// - Inline keyboard button presses forwarded to the message's bot
 ///
 /// Requirements covered: 6.1, 6.2
-use std::sync::Arc;
+use std::collections::HashMap;
+use uuid::Uuid;
 
 use crate::db::Database;
 use crate::error::AppResult;
-use crate::models::MessageResponse;
-use crate::ws::WsManager;
+use crate::models::{Bot, CallbackQuery, MessageResponse};
+use crate::services::request_id;
 
 use super::bot_service::BotEngineService;
 use super::botfather::{BotFather, BotFatherResponse};
+use super::callback_query::CallbackQueryService;
 use super::command_parser::ParsedCommand;
-use super::dispatcher::{BotDispatcher, CommandContext};
+use super::dispatcher::{BotDispatcher, CallbackContext, CommandContext, ReactionContext};
+use super::permission::{
+    PermissionChecker, SCOPE_READ_MESSAGE, SCOPE_READ_REACTIONS, SCOPE_RECEIVE_BOT_MESSAGES,
+};
 
 /// Result of processing a message
 #[derive(Debug)]
@@ -42,7 +50,7 @@ impl MessageProcessor {
     ///
     /// # Arguments
     /// * `db` - Database connection
-    /// * `ws_manager` - WebSocket manager for bot delivery
+    /// * `dispatcher` - Shared bot dispatcher for delivery
     /// * `message` - The newly created message
     ///
     /// # Returns
@@ -53,7 +61,7 @@ impl MessageProcessor {
     /// - 6.2: Find all active bots subscribed to the chat
     pub async fn process_message(
         db: &Database,
-        ws_manager: Arc<WsManager>,
+        dispatcher: &BotDispatcher,
         message: &MessageResponse,
     ) -> AppResult<ProcessResult> {
         // Only process messages with text
@@ -68,7 +76,7 @@ impl MessageProcessor {
         };
 
         // Check if message is a command (Requirement 6.1)
-        let parsed_command = ParsedCommand::parse(text);
+        let mut parsed_command = ParsedCommand::parse(text);
 
         if let Some(ref cmd) = parsed_command {
             tracing::debug!(
@@ -90,6 +98,30 @@ impl MessageProcessor {
             }
         }
 
+        // Create command context
+        // Note: sender_username is not available in MessageResponse, set to None for now
+        // A full implementation would query the user/bot name from the database
+        let mut ctx = CommandContext {
+            user_id: message.sender_id,
+            sender_username: None, // TODO: Query username from users/bots table based on sender_type
+            chat_id: message.chat_id,
+            message_id: message.id,
+            text: text.clone(),
+            sender_bot_id: sender_bot_id(message),
+            request_id: request_id::current(),
+            command_aliases: HashMap::new(),
+        };
+
+        if ctx.sender_bot_id.is_some() {
+            if let Err(e) = Self::dispatch_bot_message(db, dispatcher, &ctx).await {
+                tracing::error!("Failed to dispatch bot message to bots: {}", e);
+            }
+            return Ok(ProcessResult {
+                command: parsed_command,
+                botfather_response: None,
+            });
+        }
+
         // Find all active bots subscribed to this chat (Requirement 6.2)
         let bots = BotEngineService::get_chat_bots(db, message.chat_id).await?;
 
@@ -101,19 +133,16 @@ impl MessageProcessor {
             });
         }
 
-        // Create command context
-        // Note: sender_username is not available in MessageResponse, set to None for now
-        // A full implementation would query the user/bot name from the database
-        let ctx = CommandContext {
-            user_id: message.sender_id,
-            sender_username: None, // TODO: Query username from users/bots table based on sender_type
-            chat_id: message.chat_id,
-            message_id: message.id,
-            text: text.clone(),
-        };
+        ctx.command_aliases = Self::command_aliases(db, &bots, parsed_command.as_ref()).await?;
+        // Report the canonical command when every bot agrees on it
+        let mut targets = ctx.command_aliases.values();
+        if let (Some(cmd), Some(first)) = (parsed_command.as_mut(), targets.next()) {
+            if targets.all(|target| target == first) {
+                cmd.resolve_alias(first);
+            }
+        }
 
         // Dispatch to bots
-        let dispatcher = BotDispatcher::new(ws_manager);
         if let Err(e) = dispatcher.dispatch(&ctx, bots).await {
             tracing::error!("Failed to dispatch message to bots: {}", e);
         }
@@ -131,14 +160,14 @@ impl MessageProcessor {
     ///
     /// # Arguments
     /// * `db` - Database connection
-    /// * `ws_manager` - WebSocket manager for bot delivery
+    /// * `dispatcher` - Shared bot dispatcher for delivery
     /// * `message` - The message to dispatch
     ///
     /// # Returns
     /// * `AppResult<()>` - Success if dispatched
     pub async fn dispatch_to_bots(
         db: &Database,
-        ws_manager: Arc<WsManager>,
+        dispatcher: &BotDispatcher,
         message: &MessageResponse,
     ) -> AppResult<()> {
         // Only process messages with text
@@ -147,27 +176,195 @@ impl MessageProcessor {
             _ => return Ok(()),
         };
 
-        // Find all active bots subscribed to this chat
-        let bots = BotEngineService::get_chat_bots(db, message.chat_id).await?;
-
-        if bots.is_empty() {
-            return Ok(());
-        }
-
         // Create command context
         // Note: sender_username is not available in MessageResponse, set to None for now
-        let ctx = CommandContext {
+        let mut ctx = CommandContext {
             user_id: message.sender_id,
             sender_username: None, // TODO: Query username from users/bots table
             chat_id: message.chat_id,
             message_id: message.id,
             text: text.clone(),
+            sender_bot_id: sender_bot_id(message),
+            request_id: request_id::current(),
+            command_aliases: HashMap::new(),
         };
 
+        if ctx.sender_bot_id.is_some() {
+            return Self::dispatch_bot_message(db, dispatcher, &ctx).await;
+        }
+
+        // Find all active bots subscribed to this chat
+        let bots = BotEngineService::get_chat_bots(db, message.chat_id).await?;
+
+        if bots.is_empty() {
+            return Ok(());
+        }
+
+        let parsed_command = ParsedCommand::parse(text);
+        ctx.command_aliases = Self::command_aliases(db, &bots, parsed_command.as_ref()).await?;
+
         // Dispatch to bots
-        let dispatcher = BotDispatcher::new(ws_manager);
         dispatcher.dispatch(&ctx, bots).await
     }
+
+    /// Canonical command per bot for bots that have `command` as an alias
+    async fn command_aliases(
+        db: &Database,
+        bots: &[Bot],
+        command: Option<&ParsedCommand>,
+    ) -> AppResult<HashMap<Uuid, String>> {
+        let Some(command) = command else {
+            return Ok(HashMap::new());
+        };
+        let bot_ids: Vec<Uuid> = bots.iter().map(|bot| bot.id).collect();
+        BotEngineService::resolve_command_aliases(db, &bot_ids, &command.command).await
+    }
+
+    /// Dispatch a bot-authored message to the other bots in the chat.
+    ///
+    /// Only bots holding the `receive_bot_messages` scope receive it, the
+    /// sender never receives its own message, and once the same command has
+    /// bounced too often in the chat further dispatch is suppressed.
+    ///
+    /// # Arguments
+    /// * `db` - Database connection
+    /// * `dispatcher` - Shared bot dispatcher for delivery
+    /// * `ctx` - Context of the bot message; `sender_bot_id` must be set
+    pub async fn dispatch_bot_message(
+        db: &Database,
+        dispatcher: &BotDispatcher,
+        ctx: &CommandContext,
+    ) -> AppResult<()> {
+        let Some(sender_bot_id) = ctx.sender_bot_id else {
+            return Ok(());
+        };
+
+        let mut targets = Vec::new();
+        for bot in BotEngineService::get_chat_bots(db, ctx.chat_id).await? {
+            if bot.id == sender_bot_id {
+                continue;
+            }
+            if PermissionChecker::check_scope(db, bot.id, SCOPE_RECEIVE_BOT_MESSAGES).await? {
+                targets.push(bot);
+            }
+        }
+
+        if targets.is_empty() {
+            return Ok(());
+        }
+
+        if !dispatcher.allow_bot_message(ctx.chat_id, &ctx.text) {
+            tracing::warn!(
+                "Suppressing bot-to-bot dispatch in chat {}: loop detected (sender bot {})",
+                ctx.chat_id,
+                sender_bot_id
+            );
+            return Ok(());
+        }
+
+        dispatcher.dispatch(ctx, targets).await
+    }
+
+    /// Dispatch a reaction `user_id` added to `message` to the chat's bots.
+    ///
+    /// # Arguments
+    /// * `db` - Database connection
+    /// * `dispatcher` - Shared bot dispatcher for delivery
+    /// * `message` - The message reacted to
+    /// * `user_id` - The user who reacted
+    /// * `emoji` - The reaction
+    pub async fn process_reaction(
+        db: &Database,
+        dispatcher: &BotDispatcher,
+        message: &MessageResponse,
+        user_id: Uuid,
+        emoji: &str,
+    ) -> AppResult<()> {
+        let bots = Self::reaction_recipients(db, message).await?;
+        if bots.is_empty() {
+            return Ok(());
+        }
+
+        let username: Option<String> = sqlx::query_scalar("SELECT username FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(&db.pool)
+            .await?;
+        let ctx = ReactionContext {
+            update_id: Uuid::new_v4(),
+            user_id,
+            username,
+            chat_id: message.chat_id,
+            message_id: message.id,
+            emoji: emoji.to_string(),
+            request_id: request_id::current(),
+        };
+
+        dispatcher.dispatch_reaction(&ctx, bots).await
+    }
+
+    /// Record a press of the button carrying `data` on a bot message and
+    /// forward it to that bot
+    ///
+    /// Presses on messages without such a button are rejected, see
+    /// `CallbackQueryService::create`.
+    pub async fn process_callback(
+        db: &Database,
+        dispatcher: &BotDispatcher,
+        user_id: Uuid,
+        message_id: Uuid,
+        data: &str,
+    ) -> AppResult<CallbackQuery> {
+        let (query, bot) = CallbackQueryService::create(db, user_id, message_id, data).await?;
+
+        let username: Option<String> = sqlx::query_scalar("SELECT username FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(&db.pool)
+            .await?;
+        let ctx = CallbackContext {
+            query_id: query.id,
+            user_id,
+            username,
+            chat_id: query.chat_id,
+            message_id: query.message_id,
+            data: query.data.clone(),
+            request_id: request_id::current(),
+        };
+
+        if !dispatcher.dispatch_callback(&ctx, &bot).await {
+            tracing::warn!("Callback query {} was not forwarded to bot {}", query.id, bot.id);
+        }
+        Ok(query)
+    }
+
+    /// Bots of the message's chat that may see reactions to it
+    ///
+    /// A bot needs `read_reactions`; that covers its own messages, reactions
+    /// to any other message also need `read_message`.
+    pub async fn reaction_recipients(
+        db: &Database,
+        message: &MessageResponse,
+    ) -> AppResult<Vec<Bot>> {
+        let author_bot = sender_bot_id(message);
+
+        let mut recipients = Vec::new();
+        for bot in BotEngineService::get_chat_bots(db, message.chat_id).await? {
+            if !PermissionChecker::check_scope(db, bot.id, SCOPE_READ_REACTIONS).await? {
+                continue;
+            }
+            if author_bot == Some(bot.id)
+                || PermissionChecker::check_scope(db, bot.id, SCOPE_READ_MESSAGE).await?
+            {
+                recipients.push(bot);
+            }
+        }
+
+        Ok(recipients)
+    }
+}
+
+/// The authoring bot's id, if the message was sent by a bot
+fn sender_bot_id(message: &MessageResponse) -> Option<uuid::Uuid> {
+    (message.sender_type == "bot").then_some(message.sender_id)
 }
 
 #[cfg(test)]
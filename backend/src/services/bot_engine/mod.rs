@@ -2,16 +2,38 @@
 
 pub mod bot_service;
 pub mod botfather;
+pub mod callback_query;
 pub mod command_parser;
 pub mod dispatcher;
+pub mod formatting;
+pub mod inline_keyboard;
+pub mod loop_guard;
 pub mod message_processor;
 pub mod permission;
 pub mod rate_limiter;
+pub mod stats;
+pub mod webhook_queue;
 
-pub use bot_service::BotEngineService;
+pub use bot_service::{BotEngineService, BotListFilter, MAX_BOT_LIST_LIMIT};
 pub use botfather::{BotFather, BotFatherResponse, BOTFATHER_ID};
-pub use command_parser::ParsedCommand;
-pub use dispatcher::{BotDispatcher, CommandContext, WebhookPayload};
+pub use callback_query::{
+    CallbackQueryService, CALLBACK_ANSWER_WINDOW_SECONDS, CALLBACK_PRUNE_INTERVAL,
+    MAX_CALLBACK_ANSWER_CHARS,
+};
+pub use command_parser::{AliasError, MenuCommandError, ParsedCommand};
+pub use dispatcher::{
+    BotDispatcher, CallbackContext, CommandContext, ReactionContext, VersionedWebhookPayload, WebhookPayload,
+    WEBHOOK_REQUEST_ID_HEADER, WEBHOOK_SCHEMA_MISMATCH_HEADER, WEBHOOK_SCHEMA_VERSION,
+    WEBHOOK_SECRET_HEADER,
+};
+pub use formatting::{FormattedText, FormattingError};
+pub use inline_keyboard::{InlineKeyboardStore, KeyboardError};
 pub use message_processor::{MessageProcessor, ProcessResult};
-pub use permission::{PermissionChecker, SCOPE_SEND_MESSAGE, SCOPE_READ_MESSAGE, SCOPE_BAN_USER};
-pub use rate_limiter::{RateLimiter, RateLimitResult, DEFAULT_REQUESTS_PER_MINUTE};
+pub use permission::{
+    is_known_scope, template_scopes, PermissionChecker, ALL_SCOPES, PERMISSION_TEMPLATES,
+    SCOPE_BAN_USER, SCOPE_READ_MESSAGE, SCOPE_READ_REACTIONS, SCOPE_RECEIVE_BOT_MESSAGES,
+    SCOPE_SEND_MESSAGE,
+};
+pub use rate_limiter::{FailMode, RateLimiter, RateLimitResult, RateLimitStore, DEFAULT_REQUESTS_PER_MINUTE};
+pub use stats::{BotStats, BotStatsBuffer, STATS_FLUSH_INTERVAL};
+pub use webhook_queue::WebhookQueues;
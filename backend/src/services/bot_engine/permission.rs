@@ -4,6 +4,7 @@
 /// - Scope constants for bot permissions
 /// - Functions to check if a bot has a specific scope
 /// - Functions to check if a bot is subscribed to a chat
+/// - Named permission templates for common bot roles
 use uuid::Uuid;
 
 use crate::db::Database;
@@ -13,6 +14,38 @@ use crate::error::AppError;
 pub const SCOPE_SEND_MESSAGE: &str = "send_message";
 pub const SCOPE_READ_MESSAGE: &str = "read_message";
 pub const SCOPE_BAN_USER: &str = "ban_user";
+/// Opt-in to receive messages authored by other bots
+pub const SCOPE_RECEIVE_BOT_MESSAGES: &str = "receive_bot_messages";
+/// Receive reactions to the bot's own messages; together with
+/// `read_message`, reactions to every message in its chats
+pub const SCOPE_READ_REACTIONS: &str = "read_reactions";
+
+/// Every scope a bot can be granted
+pub const ALL_SCOPES: &[&str] = &[
+    SCOPE_SEND_MESSAGE,
+    SCOPE_READ_MESSAGE,
+    SCOPE_BAN_USER,
+    SCOPE_RECEIVE_BOT_MESSAGES,
+    SCOPE_READ_REACTIONS,
+];
+
+/// Names of the permission templates accepted by `template_scopes`
+pub const PERMISSION_TEMPLATES: &[&str] = &["moderator", "poster", "full"];
+
+/// Check whether `scope` is a known permission scope
+pub fn is_known_scope(scope: &str) -> bool {
+    ALL_SCOPES.contains(&scope)
+}
+
+/// Scopes granted by a named permission template, or None for an unknown name
+pub fn template_scopes(template: &str) -> Option<&'static [&'static str]> {
+    match template {
+        "moderator" => Some(&[SCOPE_READ_MESSAGE, SCOPE_BAN_USER]),
+        "poster" => Some(&[SCOPE_SEND_MESSAGE]),
+        "full" => Some(ALL_SCOPES),
+        _ => None,
+    }
+}
 
 /// Permission Checker provides methods to verify bot permissions and chat subscriptions.
 pub struct PermissionChecker;
@@ -81,6 +114,30 @@ mod tests {
         assert_eq!(SCOPE_SEND_MESSAGE, "send_message");
         assert_eq!(SCOPE_READ_MESSAGE, "read_message");
         assert_eq!(SCOPE_BAN_USER, "ban_user");
+        assert_eq!(SCOPE_RECEIVE_BOT_MESSAGES, "receive_bot_messages");
+        assert_eq!(SCOPE_READ_REACTIONS, "read_reactions");
+    }
+
+    #[test]
+    fn test_template_scopes() {
+        assert_eq!(
+            template_scopes("moderator"),
+            Some(&[SCOPE_READ_MESSAGE, SCOPE_BAN_USER][..])
+        );
+        assert_eq!(template_scopes("poster"), Some(&[SCOPE_SEND_MESSAGE][..]));
+        assert_eq!(template_scopes("full"), Some(ALL_SCOPES));
+        assert_eq!(template_scopes("admin"), None);
+
+        for template in PERMISSION_TEMPLATES {
+            let scopes = template_scopes(template).unwrap();
+            assert!(scopes.iter().all(|scope| is_known_scope(scope)));
+        }
+    }
+
+    #[test]
+    fn test_is_known_scope() {
+        assert!(is_known_scope(SCOPE_BAN_USER));
+        assert!(!is_known_scope("delete_chat"));
     }
 }
 
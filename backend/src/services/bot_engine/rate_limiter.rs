@@ -3,7 +3,10 @@
 /// This module provides:
 /// - Rate limiting per bot using Redis counters
 /// - Configurable requests per minute limit
-/// - RateLimitResult with remaining requests or retry_after time
+/// - RateLimitResult with remaining requests and reset time, or retry_after time
+/// - A circuit breaker that stops calling Redis after repeated failures and
+///   answers according to the configured `FailMode` until a probe succeeds
+/// - Privileged bots, which are never counted or limited
 ///
 /// # Requirements
 /// - 8.1: Track API call counts per bot per time window
@@ -12,10 +15,15 @@
 /// - 8.4: Allow requests again when time window resets
 
 use redis::aio::ConnectionManager;
-use redis::AsyncCommands;
+use redis::{AsyncCommands, RedisError};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
 use crate::error::AppError;
+use crate::models::Bot;
 
 /// Default rate limit: 60 requests per minute
 pub const DEFAULT_REQUESTS_PER_MINUTE: u32 = 60;
@@ -23,15 +31,156 @@ pub const DEFAULT_REQUESTS_PER_MINUTE: u32 = 60;
 /// Time window in seconds (1 minute)
 const RATE_LIMIT_WINDOW_SECONDS: i64 = 60;
 
+/// Consecutive Redis failures that trip the circuit breaker
+const BREAKER_FAILURE_THRESHOLD: u32 = 5;
+
+/// How long the breaker stays open before letting a probe request through
+const BREAKER_PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// What to do with requests while Redis is unreachable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FailMode {
+    /// Let requests through unlimited
+    #[default]
+    Open,
+    /// Reject requests as rate limited
+    Closed,
+}
+
+type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, RedisError>> + Send + 'a>>;
+
+/// Counter storage behind the rate limiter; Redis outside of tests
+pub trait RateLimitStore: Send + Sync {
+    fn get(&self, key: String) -> StoreFuture<'_, Option<u32>>;
+    fn ttl(&self, key: String) -> StoreFuture<'_, i64>;
+    fn incr(&self, key: String, by: u32) -> StoreFuture<'_, u32>;
+    fn expire(&self, key: String, seconds: i64) -> StoreFuture<'_, ()>;
+}
+
+impl RateLimitStore for ConnectionManager {
+    fn get(&self, key: String) -> StoreFuture<'_, Option<u32>> {
+        let mut conn = self.clone();
+        Box::pin(async move { AsyncCommands::get(&mut conn, key).await })
+    }
+
+    fn ttl(&self, key: String) -> StoreFuture<'_, i64> {
+        let mut conn = self.clone();
+        Box::pin(async move { AsyncCommands::ttl(&mut conn, key).await })
+    }
+
+    fn incr(&self, key: String, by: u32) -> StoreFuture<'_, u32> {
+        let mut conn = self.clone();
+        Box::pin(async move { AsyncCommands::incr(&mut conn, key, by).await })
+    }
+
+    fn expire(&self, key: String, seconds: i64) -> StoreFuture<'_, ()> {
+        let mut conn = self.clone();
+        Box::pin(async move { AsyncCommands::expire(&mut conn, key, seconds).await })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BreakerState {
+    /// Redis is used; counts consecutive failures
+    Closed { failures: u32 },
+    /// Redis is skipped until the instant a probe may be sent
+    Open { until: Instant },
+    /// One probe request is in flight
+    HalfOpen { since: Instant },
+}
+
+/// Circuit breaker around the Redis calls
+struct CircuitBreaker {
+    threshold: u32,
+    probe_interval: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    fn new(threshold: u32, probe_interval: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            probe_interval,
+            state: Mutex::new(BreakerState::Closed { failures: 0 }),
+        }
+    }
+
+    /// Whether this request may call Redis
+    ///
+    /// Once the open period is over, a single caller is let through as the
+    /// probe. A probe that never reports back (e.g. its request was dropped)
+    /// is replaced after another `probe_interval`.
+    fn allow(&self, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            BreakerState::Closed { .. } => true,
+            BreakerState::Open { until } if now < until => false,
+            BreakerState::HalfOpen { since } if now < since + self.probe_interval => false,
+            BreakerState::Open { .. } | BreakerState::HalfOpen { .. } => {
+                *state = BreakerState::HalfOpen { since: now };
+                true
+            }
+        }
+    }
+
+    fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if !matches!(*state, BreakerState::Closed { .. }) {
+            tracing::info!("Redis reachable again; bot rate limiting resumed");
+        }
+        *state = BreakerState::Closed { failures: 0 };
+    }
+
+    fn record_failure(&self, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        let failures = match *state {
+            BreakerState::Closed { failures } => failures + 1,
+            // A failed probe reopens straight away
+            BreakerState::Open { .. } | BreakerState::HalfOpen { .. } => self.threshold,
+        };
+
+        *state = if failures >= self.threshold {
+            BreakerState::Open {
+                until: now + self.probe_interval,
+            }
+        } else {
+            BreakerState::Closed { failures }
+        };
+    }
+
+    fn is_open(&self) -> bool {
+        !matches!(*self.state.lock().unwrap(), BreakerState::Closed { .. })
+    }
+}
+
 /// Result of a rate limit check.
 #[derive(Debug, Clone, PartialEq)]
 pub enum RateLimitResult {
-    /// Request is allowed, includes remaining requests in the window
-    Allowed { remaining: u32 },
+    /// Request is allowed, includes remaining requests in the window and
+    /// seconds until the window resets
+    Allowed { remaining: u32, reset_after: u32 },
     /// Rate limit exceeded, includes seconds until the window resets
     Exceeded { retry_after: u32 },
 }
 
+impl RateLimitResult {
+    /// Requests left in the current window
+    pub fn remaining(&self) -> u32 {
+        match self {
+            RateLimitResult::Allowed { remaining, .. } => *remaining,
+            RateLimitResult::Exceeded { .. } => 0,
+        }
+    }
+
+    /// Seconds until the current window resets
+    pub fn reset_after(&self) -> u32 {
+        match self {
+            RateLimitResult::Allowed { reset_after, .. } => *reset_after,
+            RateLimitResult::Exceeded { retry_after } => *retry_after,
+        }
+    }
+}
+
 /// Rate Limiter using Redis for distributed rate limiting.
 ///
 /// Uses a simple counter with TTL approach:
@@ -39,10 +188,15 @@ pub enum RateLimitResult {
 /// - Counter increments on each request
 /// - TTL set to window duration on first request
 /// - When counter >= limit, requests are rejected
+///
+/// Redis errors never reach the caller: each one counts towards the circuit
+/// breaker and the request is answered according to the `FailMode`.
 #[derive(Clone)]
 pub struct RateLimiter {
-    redis: ConnectionManager,
+    store: Arc<dyn RateLimitStore>,
     requests_per_minute: u32,
+    fail_mode: FailMode,
+    breaker: Arc<CircuitBreaker>,
 }
 
 impl RateLimiter {
@@ -52,12 +206,28 @@ impl RateLimiter {
     /// * `redis` - Redis connection manager
     /// * `requests_per_minute` - Maximum requests allowed per minute per bot
     pub fn new(redis: ConnectionManager, requests_per_minute: u32) -> Self {
+        Self::with_store(Arc::new(redis), requests_per_minute)
+    }
+
+    /// Create a RateLimiter over any counter store
+    pub fn with_store(store: Arc<dyn RateLimitStore>, requests_per_minute: u32) -> Self {
         Self {
-            redis,
+            store,
             requests_per_minute,
+            fail_mode: FailMode::default(),
+            breaker: Arc::new(CircuitBreaker::new(
+                BREAKER_FAILURE_THRESHOLD,
+                BREAKER_PROBE_INTERVAL,
+            )),
         }
     }
 
+    /// Set how requests are answered while Redis is unreachable
+    pub fn with_fail_mode(mut self, fail_mode: FailMode) -> Self {
+        self.fail_mode = fail_mode;
+        self
+    }
+
     /// Create a new RateLimiter with default rate limit (60 requests/minute).
     pub fn with_defaults(redis: ConnectionManager) -> Self {
         Self::new(redis, DEFAULT_REQUESTS_PER_MINUTE)
@@ -69,9 +239,11 @@ impl RateLimiter {
     /// * `bot_id` - The bot's UUID
     ///
     /// # Returns
-    /// * `Ok(RateLimitResult::Allowed { remaining })` - Request allowed, with remaining count
+    /// * `Ok(RateLimitResult::Allowed { remaining, reset_after })` - Request allowed, with
+    ///   remaining count and seconds until the window resets
     /// * `Ok(RateLimitResult::Exceeded { retry_after })` - Rate limit exceeded, with retry time
-    /// * `Err(AppError)` - Redis error
+    ///
+    /// When Redis is unreachable the result depends on the `FailMode`.
     ///
     /// # Requirements
     /// - 8.1: Track API call counts per bot per time window
@@ -79,46 +251,110 @@ impl RateLimiter {
     /// - 8.3: Include retry_after in rate limit error
     /// - 8.4: Allow requests again when time window resets
     pub async fn check_rate_limit(&self, bot_id: Uuid) -> Result<RateLimitResult, AppError> {
+        self.check_rate_limit_n(bot_id, 1).await
+    }
+
+    /// Check and consume `cost` requests at once (e.g. a bulk send).
+    ///
+    /// Either the whole cost fits in the remaining budget and is consumed,
+    /// or nothing is consumed and `Exceeded` is returned.
+    pub async fn check_rate_limit_n(
+        &self,
+        bot_id: Uuid,
+        cost: u32,
+    ) -> Result<RateLimitResult, AppError> {
+        if !self.breaker.allow(Instant::now()) {
+            return Ok(self.unavailable_result());
+        }
+
+        match self.consume(bot_id, cost).await {
+            Ok(result) => {
+                self.breaker.record_success();
+                Ok(result)
+            }
+            Err(e) => {
+                self.breaker.record_failure(Instant::now());
+                if self.breaker.is_open() {
+                    tracing::warn!(
+                        "Redis error during rate limit check: {}. Circuit open, failing {:?} for {}s",
+                        e,
+                        self.fail_mode,
+                        BREAKER_PROBE_INTERVAL.as_secs()
+                    );
+                } else {
+                    tracing::warn!("Redis error during rate limit check: {}", e);
+                }
+                Ok(self.unavailable_result())
+            }
+        }
+    }
+
+    async fn consume(&self, bot_id: Uuid, cost: u32) -> Result<RateLimitResult, RedisError> {
         let key = format!("bot_rate_limit:{}", bot_id);
-        let mut conn = self.redis.clone();
 
         // Get current count
-        let count: Option<u32> = conn.get(&key).await.map_err(|e| {
-            AppError::Internal(anyhow::anyhow!("Redis error: {}", e))
-        })?;
-
-        let current_count = count.unwrap_or(0);
+        let current_count = self.store.get(key.clone()).await?.unwrap_or(0);
 
         // Check if rate limit exceeded
-        if current_count >= self.requests_per_minute {
+        if exceeds_limit(current_count, cost, self.requests_per_minute) {
             // Get TTL to determine retry_after
-            let ttl: i64 = conn.ttl(&key).await.map_err(|e| {
-                AppError::Internal(anyhow::anyhow!("Redis error: {}", e))
-            })?;
+            let ttl = self.store.ttl(key).await?;
 
-            // TTL can be -1 (no expiry) or -2 (key doesn't exist)
-            // In those cases, default to full window
-            let retry_after = if ttl > 0 { ttl as u32 } else { RATE_LIMIT_WINDOW_SECONDS as u32 };
-
-            return Ok(RateLimitResult::Exceeded { retry_after });
+            return Ok(RateLimitResult::Exceeded {
+                retry_after: window_reset(ttl),
+            });
         }
 
         // Increment counter
-        let new_count: u32 = conn.incr(&key, 1).await.map_err(|e| {
-            AppError::Internal(anyhow::anyhow!("Redis error: {}", e))
-        })?;
+        let new_count = self.store.incr(key.clone(), cost).await?;
 
         // Set expiry on first request (when count was 0)
-        if current_count == 0 {
-            let _: () = conn.expire(&key, RATE_LIMIT_WINDOW_SECONDS).await.map_err(|e| {
-                AppError::Internal(anyhow::anyhow!("Redis error: {}", e))
-            })?;
-        }
+        let reset_after = if current_count == 0 {
+            self.store.expire(key, RATE_LIMIT_WINDOW_SECONDS).await?;
+            RATE_LIMIT_WINDOW_SECONDS as u32
+        } else {
+            window_reset(self.store.ttl(key).await?)
+        };
 
         // Calculate remaining requests
         let remaining = self.requests_per_minute.saturating_sub(new_count);
 
-        Ok(RateLimitResult::Allowed { remaining })
+        Ok(RateLimitResult::Allowed {
+            remaining,
+            reset_after,
+        })
+    }
+
+    /// Check and consume `cost` requests for `bot`, unless it is privileged
+    ///
+    /// Privileged bots are always allowed with the full budget reported and
+    /// their counter is never touched; callers audit those requests.
+    pub async fn check_bot(&self, bot: &Bot, cost: u32) -> Result<RateLimitResult, AppError> {
+        if bot.privileged {
+            return Ok(RateLimitResult::Allowed {
+                remaining: self.requests_per_minute,
+                reset_after: RATE_LIMIT_WINDOW_SECONDS as u32,
+            });
+        }
+        self.check_rate_limit_n(bot.id, cost).await
+    }
+
+    /// Answer for a request that could not be checked against Redis
+    fn unavailable_result(&self) -> RateLimitResult {
+        match self.fail_mode {
+            FailMode::Open => RateLimitResult::Allowed {
+                remaining: self.requests_per_minute,
+                reset_after: RATE_LIMIT_WINDOW_SECONDS as u32,
+            },
+            FailMode::Closed => RateLimitResult::Exceeded {
+                retry_after: BREAKER_PROBE_INTERVAL.as_secs() as u32,
+            },
+        }
+    }
+
+    /// Whether Redis is currently being bypassed after repeated failures
+    pub fn is_degraded(&self) -> bool {
+        self.breaker.is_open()
     }
 
     /// Get the current rate limit configuration.
@@ -127,15 +363,41 @@ impl RateLimiter {
     }
 }
 
+/// Seconds until the window resets, from the counter's TTL
+///
+/// TTL can be -1 (no expiry) or -2 (key doesn't exist); in those cases,
+/// default to the full window.
+fn window_reset(ttl: i64) -> u32 {
+    if ttl > 0 {
+        ttl as u32
+    } else {
+        RATE_LIMIT_WINDOW_SECONDS as u32
+    }
+}
+
+/// Whether consuming `cost` more requests would go over `limit`
+fn exceeds_limit(current_count: u32, cost: u32, limit: u32) -> bool {
+    current_count.saturating_add(cost) > limit
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_rate_limit_result_equality() {
-        let allowed1 = RateLimitResult::Allowed { remaining: 10 };
-        let allowed2 = RateLimitResult::Allowed { remaining: 10 };
-        let allowed3 = RateLimitResult::Allowed { remaining: 5 };
+        let allowed1 = RateLimitResult::Allowed {
+            remaining: 10,
+            reset_after: 60,
+        };
+        let allowed2 = RateLimitResult::Allowed {
+            remaining: 10,
+            reset_after: 60,
+        };
+        let allowed3 = RateLimitResult::Allowed {
+            remaining: 5,
+            reset_after: 60,
+        };
         let exceeded1 = RateLimitResult::Exceeded { retry_after: 30 };
         let exceeded2 = RateLimitResult::Exceeded { retry_after: 30 };
 
@@ -145,8 +407,282 @@ mod tests {
         assert_ne!(allowed1, exceeded1);
     }
 
+    #[test]
+    fn test_remaining_and_reset_after() {
+        let allowed = RateLimitResult::Allowed {
+            remaining: 7,
+            reset_after: 12,
+        };
+        assert_eq!(allowed.remaining(), 7);
+        assert_eq!(allowed.reset_after(), 12);
+
+        let exceeded = RateLimitResult::Exceeded { retry_after: 30 };
+        assert_eq!(exceeded.remaining(), 0);
+        assert_eq!(exceeded.reset_after(), 30);
+
+        assert_eq!(window_reset(5), 5);
+        assert_eq!(window_reset(-1), RATE_LIMIT_WINDOW_SECONDS as u32);
+        assert_eq!(window_reset(-2), RATE_LIMIT_WINDOW_SECONDS as u32);
+    }
+
     #[test]
     fn test_default_requests_per_minute() {
         assert_eq!(DEFAULT_REQUESTS_PER_MINUTE, 60);
     }
+
+    #[test]
+    fn test_exceeds_limit() {
+        // Single requests: allowed until the counter reaches the limit
+        assert!(!exceeds_limit(59, 1, 60));
+        assert!(exceeds_limit(60, 1, 60));
+
+        // A batch must fit entirely in the remaining budget
+        assert!(!exceeds_limit(50, 10, 60));
+        assert!(exceeds_limit(51, 10, 60));
+        assert!(exceeds_limit(0, u32::MAX, 60));
+    }
+
+    /// In-memory store that can be switched to fail like a dead Redis
+    #[derive(Default)]
+    struct MockStore {
+        down: std::sync::atomic::AtomicBool,
+        calls: std::sync::atomic::AtomicU32,
+        counts: Mutex<std::collections::HashMap<String, u32>>,
+    }
+
+    impl MockStore {
+        fn set_down(&self, down: bool) {
+            self.down.store(down, std::sync::atomic::Ordering::SeqCst);
+        }
+
+        fn calls(&self) -> u32 {
+            self.calls.load(std::sync::atomic::Ordering::SeqCst)
+        }
+
+        fn check(&self) -> Result<(), RedisError> {
+            self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            if self.down.load(std::sync::atomic::Ordering::SeqCst) {
+                return Err((redis::ErrorKind::IoError, "connection refused").into());
+            }
+            Ok(())
+        }
+    }
+
+    impl RateLimitStore for MockStore {
+        fn get(&self, key: String) -> StoreFuture<'_, Option<u32>> {
+            Box::pin(async move {
+                self.check()?;
+                Ok(self.counts.lock().unwrap().get(&key).copied())
+            })
+        }
+
+        fn ttl(&self, _key: String) -> StoreFuture<'_, i64> {
+            Box::pin(async move {
+                self.check()?;
+                Ok(42)
+            })
+        }
+
+        fn incr(&self, key: String, by: u32) -> StoreFuture<'_, u32> {
+            Box::pin(async move {
+                self.check()?;
+                let mut counts = self.counts.lock().unwrap();
+                let count = counts.entry(key).or_insert(0);
+                *count += by;
+                Ok(*count)
+            })
+        }
+
+        fn expire(&self, _key: String, _seconds: i64) -> StoreFuture<'_, ()> {
+            Box::pin(async move { self.check() })
+        }
+    }
+
+    fn limiter(store: &Arc<MockStore>, fail_mode: FailMode) -> RateLimiter {
+        RateLimiter::with_store(store.clone(), 2).with_fail_mode(fail_mode)
+    }
+
+    #[test]
+    fn test_breaker_opens_after_threshold_and_probes() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(10));
+        let now = Instant::now();
+
+        breaker.record_failure(now);
+        breaker.record_failure(now);
+        assert!(!breaker.is_open());
+        assert!(breaker.allow(now));
+
+        breaker.record_failure(now);
+        assert!(breaker.is_open());
+        assert!(!breaker.allow(now + Duration::from_secs(9)));
+
+        // One probe after the interval, nobody else while it is in flight
+        assert!(breaker.allow(now + Duration::from_secs(10)));
+        assert!(!breaker.allow(now + Duration::from_secs(11)));
+
+        // A failed probe reopens the circuit
+        breaker.record_failure(now + Duration::from_secs(11));
+        assert!(!breaker.allow(now + Duration::from_secs(12)));
+
+        // A successful probe closes it
+        assert!(breaker.allow(now + Duration::from_secs(21)));
+        breaker.record_success();
+        assert!(!breaker.is_open());
+        assert!(breaker.allow(now + Duration::from_secs(21)));
+    }
+
+    #[test]
+    fn test_breaker_replaces_lost_probe() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(10));
+        let now = Instant::now();
+
+        breaker.record_failure(now);
+        assert!(breaker.allow(now + Duration::from_secs(10)));
+        // The probe never reported back
+        assert!(breaker.allow(now + Duration::from_secs(20)));
+    }
+
+    #[test]
+    fn test_success_resets_failure_count() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(10));
+        let now = Instant::now();
+
+        breaker.record_failure(now);
+        breaker.record_success();
+        breaker.record_failure(now);
+        assert!(!breaker.is_open());
+    }
+
+    #[tokio::test]
+    async fn test_fail_open_skips_redis_once_tripped() {
+        let store = Arc::new(MockStore::default());
+        let limiter = limiter(&store, FailMode::Open);
+        let bot_id = Uuid::new_v4();
+
+        store.set_down(true);
+        for _ in 0..BREAKER_FAILURE_THRESHOLD {
+            assert_eq!(
+                limiter.check_rate_limit(bot_id).await.unwrap(),
+                RateLimitResult::Allowed {
+                    remaining: 2,
+                    reset_after: 60
+                }
+            );
+        }
+        assert!(limiter.is_degraded());
+
+        // Open circuit: answered without touching Redis
+        let calls = store.calls();
+        assert_eq!(
+            limiter.check_rate_limit(bot_id).await.unwrap(),
+            RateLimitResult::Allowed {
+                remaining: 2,
+                reset_after: 60
+            }
+        );
+        assert_eq!(store.calls(), calls);
+    }
+
+    #[tokio::test]
+    async fn test_fail_closed_rejects_when_redis_is_down() {
+        let store = Arc::new(MockStore::default());
+        let limiter = limiter(&store, FailMode::Closed);
+        let bot_id = Uuid::new_v4();
+
+        store.set_down(true);
+        assert_eq!(
+            limiter.check_rate_limit(bot_id).await.unwrap(),
+            RateLimitResult::Exceeded {
+                retry_after: BREAKER_PROBE_INTERVAL.as_secs() as u32
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_limits_enforced_while_redis_is_up() {
+        let store = Arc::new(MockStore::default());
+        let limiter = limiter(&store, FailMode::Open);
+        let bot_id = Uuid::new_v4();
+
+        assert_eq!(
+            limiter.check_rate_limit(bot_id).await.unwrap(),
+            RateLimitResult::Allowed {
+                remaining: 1,
+                reset_after: 60
+            }
+        );
+        assert_eq!(
+            limiter.check_rate_limit(bot_id).await.unwrap(),
+            RateLimitResult::Allowed {
+                remaining: 0,
+                reset_after: 42
+            }
+        );
+        assert_eq!(
+            limiter.check_rate_limit(bot_id).await.unwrap(),
+            RateLimitResult::Exceeded { retry_after: 42 }
+        );
+        assert!(!limiter.is_degraded());
+    }
+
+    fn bot(privileged: bool) -> Bot {
+        Bot {
+            id: Uuid::new_v4(),
+            name: "Test Bot".to_string(),
+            username: None,
+            token: "token".to_string(),
+            owner_id: Uuid::new_v4(),
+            webhook_url: None,
+            is_active: true,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+            webhook_secret: None,
+            webhook_max_connections: 1,
+            webhook_allowed_updates: None,
+            privileged,
+            webhook_schema_version: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_privileged_bot_bypasses_limit() {
+        let store = Arc::new(MockStore::default());
+        let limiter = limiter(&store, FailMode::Closed);
+        let privileged = bot(true);
+
+        // Never counted, so never limited, even for bulk costs
+        for _ in 0..5 {
+            assert_eq!(
+                limiter.check_bot(&privileged, 10).await.unwrap(),
+                RateLimitResult::Allowed {
+                    remaining: 2,
+                    reset_after: 60
+                }
+            );
+        }
+        assert_eq!(store.calls(), 0);
+
+        // Nor while Redis is down and the limiter fails closed
+        store.set_down(true);
+        assert!(matches!(
+            limiter.check_bot(&privileged, 1).await.unwrap(),
+            RateLimitResult::Allowed { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_normal_bot_is_limited() {
+        let store = Arc::new(MockStore::default());
+        let limiter = limiter(&store, FailMode::Open);
+        let normal = bot(false);
+
+        assert!(matches!(
+            limiter.check_bot(&normal, 2).await.unwrap(),
+            RateLimitResult::Allowed { remaining: 0, .. }
+        ));
+        assert_eq!(
+            limiter.check_bot(&normal, 1).await.unwrap(),
+            RateLimitResult::Exceeded { retry_after: 42 }
+        );
+    }
 }
@@ -0,0 +1,452 @@
+/// Bot Stats module - usage analytics for bot owners.
+///
+/// This module provides:
+/// - An in-memory buffer of per-bot counters in hourly buckets (messages
+///   received, commands by name, webhook successes and failures)
+/// - Periodic flushing of the buffer into `bot_stats`, so the dispatch path
+///   never writes to the database
+/// - Time-bucketed reads with top commands and webhook error rate
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use super::command_parser::ParsedCommand;
+use crate::db::Database;
+use crate::error::{AppError, AppResult};
+use crate::AppState;
+
+pub const METRIC_MESSAGES: &str = "messages_received";
+pub const METRIC_COMMAND: &str = "command";
+pub const METRIC_WEBHOOK_SUCCESS: &str = "webhook_success";
+pub const METRIC_WEBHOOK_FAILURE: &str = "webhook_failure";
+
+/// How often buffered counters are written to the database
+pub const STATS_FLUSH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Widest range `query` accepts
+pub const MAX_STATS_RANGE_DAYS: i64 = 90;
+
+/// Range used when the caller gives no `from`
+const DEFAULT_STATS_RANGE_DAYS: i64 = 7;
+
+/// Ranges up to this long are reported per hour, longer ones per day
+const HOURLY_BUCKETS_MAX_HOURS: i64 = 48;
+
+/// Distinct command names counted per bot and hour; the rest go to `OTHER_COMMAND`
+const MAX_COMMANDS_PER_BUCKET: usize = 50;
+
+/// Longest command name counted by name
+const MAX_COMMAND_NAME_LEN: usize = 32;
+
+/// Bucket for commands not counted by name
+pub const OTHER_COMMAND: &str = "(other)";
+
+/// Commands listed in `BotStats::top_commands`
+const TOP_COMMANDS_LIMIT: i64 = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct StatKey {
+    bot_id: Uuid,
+    bucket: DateTime<Utc>,
+    metric: &'static str,
+    command: String,
+}
+
+/// Counters waiting to be flushed
+#[derive(Default)]
+pub struct BotStatsBuffer {
+    counts: Mutex<HashMap<StatKey, i64>>,
+}
+
+impl BotStatsBuffer {
+    /// Count an update delivered to a bot, and its command if it is one
+    pub fn record_message(&self, bot_id: Uuid, text: &str) {
+        self.record_message_at(bot_id, text, Utc::now());
+    }
+
+    fn record_message_at(&self, bot_id: Uuid, text: &str, now: DateTime<Utc>) {
+        let bucket = hour_bucket(now);
+        let mut counts = self.counts.lock().unwrap();
+        add(&mut counts, bot_id, bucket, METRIC_MESSAGES, String::new());
+
+        if let Some(cmd) = ParsedCommand::parse(text) {
+            let mut command = command_name(&cmd.command);
+            let known = |name: &str| {
+                counts.contains_key(&StatKey {
+                    bot_id,
+                    bucket,
+                    metric: METRIC_COMMAND,
+                    command: name.to_string(),
+                })
+            };
+            if !known(&command) {
+                let tracked = counts
+                    .keys()
+                    .filter(|k| k.bot_id == bot_id && k.bucket == bucket && k.metric == METRIC_COMMAND)
+                    .count();
+                if tracked >= MAX_COMMANDS_PER_BUCKET {
+                    command = OTHER_COMMAND.to_string();
+                }
+            }
+            add(&mut counts, bot_id, bucket, METRIC_COMMAND, command);
+        }
+    }
+
+    /// Count a webhook delivery attempt
+    pub fn record_webhook(&self, bot_id: Uuid, success: bool) {
+        let metric = if success {
+            METRIC_WEBHOOK_SUCCESS
+        } else {
+            METRIC_WEBHOOK_FAILURE
+        };
+        let mut counts = self.counts.lock().unwrap();
+        add(&mut counts, bot_id, hour_bucket(Utc::now()), metric, String::new());
+    }
+
+    /// Write buffered counters to `bot_stats`; returns how many rows were
+    /// upserted
+    ///
+    /// On failure the counters are put back to be retried on the next flush.
+    /// Counters of bots deleted in the meantime are dropped.
+    pub async fn flush(&self, db: &Database) -> AppResult<usize> {
+        let drained = std::mem::take(&mut *self.counts.lock().unwrap());
+        if drained.is_empty() {
+            return Ok(0);
+        }
+
+        let mut bot_ids = Vec::with_capacity(drained.len());
+        let mut buckets = Vec::with_capacity(drained.len());
+        let mut metrics = Vec::with_capacity(drained.len());
+        let mut commands = Vec::with_capacity(drained.len());
+        let mut values = Vec::with_capacity(drained.len());
+        for (key, count) in &drained {
+            bot_ids.push(key.bot_id);
+            buckets.push(key.bucket);
+            metrics.push(key.metric);
+            commands.push(key.command.as_str());
+            values.push(*count);
+        }
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO bot_stats (bot_id, bucket, metric, command, count)
+            SELECT s.bot_id, s.bucket, s.metric, s.command, s.count
+            FROM UNNEST($1::uuid[], $2::timestamptz[], $3::text[], $4::text[], $5::bigint[])
+                AS s(bot_id, bucket, metric, command, count)
+            JOIN bots b ON b.id = s.bot_id
+            ON CONFLICT (bot_id, bucket, metric, command)
+            DO UPDATE SET count = bot_stats.count + EXCLUDED.count
+            "#,
+        )
+        .bind(&bot_ids)
+        .bind(&buckets)
+        .bind(&metrics)
+        .bind(&commands)
+        .bind(&values)
+        .execute(&db.pool)
+        .await;
+
+        match result {
+            Ok(done) => Ok(done.rows_affected() as usize),
+            Err(e) => {
+                let mut counts = self.counts.lock().unwrap();
+                for (key, count) in drained {
+                    *counts.entry(key).or_insert(0) += count;
+                }
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Spawn the background flusher. Runs until the process exits.
+    pub fn spawn_flusher(state: Arc<AppState>, interval: std::time::Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = state.bot_dispatcher.stats().flush(&state.db).await {
+                    tracing::error!("Failed to flush bot stats: {}", e);
+                }
+            }
+        });
+    }
+}
+
+fn add(
+    counts: &mut HashMap<StatKey, i64>,
+    bot_id: Uuid,
+    bucket: DateTime<Utc>,
+    metric: &'static str,
+    command: String,
+) {
+    *counts
+        .entry(StatKey {
+            bot_id,
+            bucket,
+            metric,
+            command,
+        })
+        .or_insert(0) += 1;
+}
+
+fn hour_bucket(at: DateTime<Utc>) -> DateTime<Utc> {
+    at.duration_trunc(Duration::hours(1)).unwrap_or(at)
+}
+
+/// Name a command is counted under; odd or overlong names share `OTHER_COMMAND`
+fn command_name(command: &str) -> String {
+    let valid = !command.is_empty()
+        && command.len() <= MAX_COMMAND_NAME_LEN
+        && command
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid {
+        command.to_ascii_lowercase()
+    } else {
+        OTHER_COMMAND.to_string()
+    }
+}
+
+/// Counts for one time bucket
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BotStatsBucket {
+    pub bucket: DateTime<Utc>,
+    pub messages_received: i64,
+    pub commands: i64,
+    pub webhook_successes: i64,
+    pub webhook_failures: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandCount {
+    pub command: String,
+    pub count: i64,
+}
+
+/// Usage of a bot over a time range
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BotStats {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// "hour" or "day"
+    pub granularity: &'static str,
+    pub buckets: Vec<BotStatsBucket>,
+    pub messages_received: i64,
+    pub commands: i64,
+    pub webhook_successes: i64,
+    pub webhook_failures: i64,
+    /// Share of webhook deliveries that failed; null when there were none
+    pub webhook_error_rate: Option<f64>,
+    pub top_commands: Vec<CommandCount>,
+}
+
+/// Stats of `bot_id` between `from` (inclusive) and `to` (exclusive)
+///
+/// Counters are flushed every `STATS_FLUSH_INTERVAL`, so the most recent
+/// seconds may not be included yet.
+pub async fn query(
+    db: &Database,
+    bot_id: Uuid,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> AppResult<BotStats> {
+    let (from, to) = resolve_range(from, to, Utc::now())?;
+    let granularity = if to - from <= Duration::hours(HOURLY_BUCKETS_MAX_HOURS) {
+        "hour"
+    } else {
+        "day"
+    };
+
+    let rows: Vec<(DateTime<Utc>, i64, i64, i64, i64)> = sqlx::query_as(
+        r#"
+        SELECT date_trunc($4, bucket) AS period,
+            COALESCE(SUM(count) FILTER (WHERE metric = $5), 0)::bigint,
+            COALESCE(SUM(count) FILTER (WHERE metric = $6), 0)::bigint,
+            COALESCE(SUM(count) FILTER (WHERE metric = $7), 0)::bigint,
+            COALESCE(SUM(count) FILTER (WHERE metric = $8), 0)::bigint
+        FROM bot_stats
+        WHERE bot_id = $1 AND bucket >= $2 AND bucket < $3
+        GROUP BY period
+        ORDER BY period
+        "#,
+    )
+    .bind(bot_id)
+    .bind(from)
+    .bind(to)
+    .bind(granularity)
+    .bind(METRIC_MESSAGES)
+    .bind(METRIC_COMMAND)
+    .bind(METRIC_WEBHOOK_SUCCESS)
+    .bind(METRIC_WEBHOOK_FAILURE)
+    .fetch_all(&db.pool)
+    .await?;
+
+    let top_commands: Vec<(String, i64)> = sqlx::query_as(
+        r#"
+        SELECT command, SUM(count)::bigint AS total
+        FROM bot_stats
+        WHERE bot_id = $1 AND metric = $4 AND bucket >= $2 AND bucket < $3
+        GROUP BY command
+        ORDER BY total DESC, command
+        LIMIT $5
+        "#,
+    )
+    .bind(bot_id)
+    .bind(from)
+    .bind(to)
+    .bind(METRIC_COMMAND)
+    .bind(TOP_COMMANDS_LIMIT)
+    .fetch_all(&db.pool)
+    .await?;
+
+    let buckets: Vec<BotStatsBucket> = rows
+        .into_iter()
+        .map(
+            |(bucket, messages_received, commands, webhook_successes, webhook_failures)| {
+                BotStatsBucket {
+                    bucket,
+                    messages_received,
+                    commands,
+                    webhook_successes,
+                    webhook_failures,
+                }
+            },
+        )
+        .collect();
+
+    let messages_received = buckets.iter().map(|b| b.messages_received).sum();
+    let commands = buckets.iter().map(|b| b.commands).sum();
+    let webhook_successes = buckets.iter().map(|b| b.webhook_successes).sum();
+    let webhook_failures = buckets.iter().map(|b| b.webhook_failures).sum();
+
+    Ok(BotStats {
+        from,
+        to,
+        granularity,
+        buckets,
+        messages_received,
+        commands,
+        webhook_successes,
+        webhook_failures,
+        webhook_error_rate: error_rate(webhook_successes, webhook_failures),
+        top_commands: top_commands
+            .into_iter()
+            .map(|(command, count)| CommandCount { command, count })
+            .collect(),
+    })
+}
+
+/// Fill in defaults (the last 7 days) and check the range
+///
+/// `from` is rounded down to the hour so its bucket is included.
+fn resolve_range(
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> AppResult<(DateTime<Utc>, DateTime<Utc>)> {
+    let to = to.unwrap_or(now);
+    let from = from.unwrap_or(to - Duration::days(DEFAULT_STATS_RANGE_DAYS));
+
+    // Checked before bucketing, which would move `from` back before `to`
+    if from >= to {
+        return Err(AppError::BadRequest("'from' must be before 'to'".to_string()));
+    }
+    let from = hour_bucket(from);
+    if to - from > Duration::days(MAX_STATS_RANGE_DAYS) {
+        return Err(AppError::BadRequest(format!(
+            "Stats can cover at most {} days",
+            MAX_STATS_RANGE_DAYS
+        )));
+    }
+    Ok((from, to))
+}
+
+fn error_rate(successes: i64, failures: i64) -> Option<f64> {
+    let total = successes + failures;
+    (total > 0).then(|| failures as f64 / total as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn counts(buffer: &BotStatsBuffer) -> HashMap<StatKey, i64> {
+        buffer.counts.lock().unwrap().clone()
+    }
+
+    fn key(bot_id: Uuid, bucket: DateTime<Utc>, metric: &'static str, command: &str) -> StatKey {
+        StatKey {
+            bot_id,
+            bucket,
+            metric,
+            command: command.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_messages_and_commands_share_hour_bucket() {
+        let buffer = BotStatsBuffer::default();
+        let bot_id = Uuid::new_v4();
+        let at = Utc.with_ymd_and_hms(2026, 3, 1, 10, 42, 7).unwrap();
+        let bucket = Utc.with_ymd_and_hms(2026, 3, 1, 10, 0, 0).unwrap();
+
+        buffer.record_message_at(bot_id, "/Start now", at);
+        buffer.record_message_at(bot_id, "/start", at);
+        buffer.record_message_at(bot_id, "hello", at);
+
+        let counts = counts(&buffer);
+        assert_eq!(counts[&key(bot_id, bucket, METRIC_MESSAGES, "")], 3);
+        assert_eq!(counts[&key(bot_id, bucket, METRIC_COMMAND, "start")], 2);
+        assert_eq!(counts.len(), 2);
+    }
+
+    #[test]
+    fn test_command_names_are_capped_per_bucket() {
+        let buffer = BotStatsBuffer::default();
+        let bot_id = Uuid::new_v4();
+        let at = Utc.with_ymd_and_hms(2026, 3, 1, 10, 0, 0).unwrap();
+
+        for i in 0..MAX_COMMANDS_PER_BUCKET + 5 {
+            buffer.record_message_at(bot_id, &format!("/cmd{}", i), at);
+        }
+        // Already-tracked names keep counting by name
+        buffer.record_message_at(bot_id, "/cmd0", at);
+
+        let counts = counts(&buffer);
+        assert_eq!(counts[&key(bot_id, at, METRIC_COMMAND, OTHER_COMMAND)], 5);
+        assert_eq!(counts[&key(bot_id, at, METRIC_COMMAND, "cmd0")], 2);
+    }
+
+    #[test]
+    fn test_odd_command_names_are_grouped() {
+        assert_eq!(command_name("help"), "help");
+        assert_eq!(command_name("Help_2"), "help_2");
+        assert_eq!(command_name("héllo"), OTHER_COMMAND);
+        assert_eq!(command_name(&"x".repeat(MAX_COMMAND_NAME_LEN + 1)), OTHER_COMMAND);
+    }
+
+    #[test]
+    fn test_resolve_range() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 10, 12, 30, 0).unwrap();
+
+        let (from, to) = resolve_range(None, None, now).unwrap();
+        assert_eq!(to, now);
+        assert_eq!(from, Utc.with_ymd_and_hms(2026, 3, 3, 12, 0, 0).unwrap());
+
+        assert!(resolve_range(Some(now), Some(now), now).is_err());
+        assert!(resolve_range(Some(now - Duration::days(MAX_STATS_RANGE_DAYS + 1)), None, now).is_err());
+    }
+
+    #[test]
+    fn test_error_rate() {
+        assert_eq!(error_rate(0, 0), None);
+        assert_eq!(error_rate(3, 1), Some(0.25));
+        assert_eq!(error_rate(0, 2), Some(1.0));
+    }
+}
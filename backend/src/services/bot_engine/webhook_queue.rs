@@ -0,0 +1,274 @@
+/// Webhook Queue module - ordered per-bot webhook delivery.
+///
+/// Every bot gets its own bounded queue drained by a worker task:
+/// - Updates for one bot are sent in the order they were queued; the next
+///   one starts only once fewer than the bot's in-flight limit are still
+///   awaiting a response, so with a limit of 1 update N+1 is never sent
+///   before update N was answered
+/// - Different bots have separate workers and are delivered in parallel
+/// - A bot whose webhook stalls builds up at most `capacity` waiting
+///   updates; further ones are dropped with a warning
+/// - A worker with nothing to do for `WORKER_IDLE_TIMEOUT` exits and its
+///   queue is removed; the bot's next update starts a new one
+use futures::future::BoxFuture;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, Semaphore};
+use uuid::Uuid;
+
+/// How long a bot's worker waits for an update before exiting
+pub const WORKER_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Performs one delivery; the returned future completes when the bot answered
+pub type DeliverFn<J> = Arc<dyn Fn(J) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// A queued delivery and the in-flight limit of the bot at the time
+struct Job<J> {
+    max_in_flight: usize,
+    payload: J,
+}
+
+/// Per-bot delivery queues
+pub struct WebhookQueues<J> {
+    queues: Arc<Mutex<HashMap<Uuid, mpsc::Sender<Job<J>>>>>,
+    capacity: usize,
+    idle_timeout: Duration,
+    deliver: DeliverFn<J>,
+    /// Updates dropped because their bot's queue was full
+    shed: AtomicU64,
+}
+
+impl<J: Send + 'static> WebhookQueues<J> {
+    /// Create queues holding up to `capacity` waiting updates per bot
+    pub fn new(capacity: usize, deliver: DeliverFn<J>) -> Self {
+        Self {
+            queues: Arc::new(Mutex::new(HashMap::new())),
+            capacity: capacity.max(1),
+            idle_timeout: WORKER_IDLE_TIMEOUT,
+            deliver,
+            shed: AtomicU64::new(0),
+        }
+    }
+
+    /// Let workers exit after `idle_timeout` without updates instead of the default
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Queue an update for `bot_id`, starting the bot's worker if needed
+    ///
+    /// Returns false when the bot already has `capacity` updates waiting;
+    /// the update is then dropped.
+    pub fn enqueue(&self, bot_id: Uuid, max_in_flight: usize, payload: J) -> bool {
+        let mut queues = self.queues.lock().unwrap_or_else(|e| e.into_inner());
+        let sender = queues
+            .entry(bot_id)
+            .or_insert_with(|| self.spawn_worker(bot_id));
+
+        let job = Job { max_in_flight, payload };
+        match sender.try_send(job) {
+            Ok(()) => true,
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                self.shed.fetch_add(1, Ordering::Relaxed);
+                tracing::warn!(
+                    "Webhook queue of bot {} is full ({} updates waiting), dropping update",
+                    bot_id,
+                    self.capacity
+                );
+                false
+            }
+            Err(mpsc::error::TrySendError::Closed(job)) => {
+                // The worker died without removing its queue (runtime
+                // shutting down); start over
+                let sender = self.spawn_worker(bot_id);
+                let queued = sender.try_send(job).is_ok();
+                queues.insert(bot_id, sender);
+                queued
+            }
+        }
+    }
+
+    /// Updates dropped so far because a bot's queue was full
+    pub fn shed_count(&self) -> u64 {
+        self.shed.load(Ordering::Relaxed)
+    }
+
+    fn spawn_worker(&self, bot_id: Uuid) -> mpsc::Sender<Job<J>> {
+        let (sender, mut receiver) = mpsc::channel::<Job<J>>(self.capacity);
+        let deliver = self.deliver.clone();
+        let queues = self.queues.clone();
+        let idle_timeout = self.idle_timeout;
+
+        tokio::spawn(async move {
+            let mut limit = 0;
+            let mut slots = Arc::new(Semaphore::new(1));
+
+            loop {
+                let job = match tokio::time::timeout(idle_timeout, receiver.recv()).await {
+                    Ok(Some(job)) => job,
+                    Ok(None) => break,
+                    Err(_) => {
+                        // Updates are queued under this lock, so none can
+                        // slip in between the check and the removal
+                        let mut queues = queues.lock().unwrap_or_else(|e| e.into_inner());
+                        match receiver.try_recv() {
+                            Ok(job) => job,
+                            Err(_) => {
+                                queues.remove(&bot_id);
+                                break;
+                            }
+                        }
+                    }
+                };
+                let max_in_flight = job.max_in_flight.max(1);
+                if max_in_flight != limit {
+                    // Let deliveries under the old limit finish first so a
+                    // lowered limit holds from the next update on
+                    let _ = slots.acquire_many(limit as u32).await;
+                    slots = Arc::new(Semaphore::new(max_in_flight));
+                    limit = max_in_flight;
+                }
+
+                let Ok(permit) = slots.clone().acquire_owned().await else {
+                    break;
+                };
+                let delivery = deliver(job.payload);
+                tokio::spawn(async move {
+                    delivery.await;
+                    drop(permit);
+                });
+            }
+            tracing::debug!("Webhook queue of bot {} closed", bot_id);
+        });
+
+        sender
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::sync::Notify;
+
+    /// Delivery that records each update once it has been "answered", after
+    /// a delay that shrinks with the update number so later updates would
+    /// overtake earlier ones if they were sent concurrently
+    fn recording(log: Arc<Mutex<Vec<(Uuid, u32)>>>) -> DeliverFn<(Uuid, u32)> {
+        Arc::new(move |(bot_id, n): (Uuid, u32)| {
+            let log = log.clone();
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(u64::from(20 - n))).await;
+                log.lock().unwrap().push((bot_id, n));
+            })
+        })
+    }
+
+    async fn wait_for(log: &Mutex<Vec<(Uuid, u32)>>, count: usize) {
+        for _ in 0..200 {
+            if log.lock().unwrap().len() >= count {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("deliveries did not complete");
+    }
+
+    #[tokio::test]
+    async fn test_updates_to_one_bot_are_delivered_in_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let queues = WebhookQueues::new(100, recording(log.clone()));
+        let bot = Uuid::new_v4();
+
+        for n in 0..20 {
+            assert!(queues.enqueue(bot, 1, (bot, n)));
+        }
+        wait_for(&log, 20).await;
+
+        let delivered: Vec<u32> = log.lock().unwrap().iter().map(|(_, n)| *n).collect();
+        assert_eq!(delivered, (0..20).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_bots_are_delivered_in_parallel() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let queues = WebhookQueues::new(100, recording(log.clone()));
+        let (slow, fast) = (Uuid::new_v4(), Uuid::new_v4());
+
+        // The slow bot's single update takes longest; the fast bot's updates
+        // finish before it instead of waiting behind it
+        assert!(queues.enqueue(slow, 1, (slow, 0)));
+        for n in 17..20 {
+            assert!(queues.enqueue(fast, 1, (fast, n)));
+        }
+        wait_for(&log, 4).await;
+
+        let log = log.lock().unwrap();
+        assert_eq!(log.last(), Some(&(slow, 0)));
+        let fast_order: Vec<u32> = log.iter().filter(|(b, _)| *b == fast).map(|(_, n)| *n).collect();
+        assert_eq!(fast_order, vec![17, 18, 19]);
+    }
+
+    #[tokio::test]
+    async fn test_stalled_bot_sheds_updates_beyond_the_queue() {
+        let release = Arc::new(Notify::new());
+        let delivered = Arc::new(AtomicU64::new(0));
+        let deliver: DeliverFn<u32> = {
+            let (release, delivered) = (release.clone(), delivered.clone());
+            Arc::new(move |_| {
+                let (release, delivered) = (release.clone(), delivered.clone());
+                Box::pin(async move {
+                    release.notified().await;
+                    delivered.fetch_add(1, Ordering::Relaxed);
+                })
+            })
+        };
+        let queues = WebhookQueues::new(2, deliver);
+        let bot = Uuid::new_v4();
+
+        // The first update is taken by the worker and stalls; two more fill
+        // the queue and the rest are dropped
+        assert!(queues.enqueue(bot, 1, 0));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(queues.enqueue(bot, 1, 1));
+        assert!(queues.enqueue(bot, 1, 2));
+        assert!(!queues.enqueue(bot, 1, 3));
+        assert!(!queues.enqueue(bot, 1, 4));
+        assert_eq!(queues.shed_count(), 2);
+
+        for _ in 0..50 {
+            release.notify_waiters();
+            if delivered.load(Ordering::Relaxed) == 3 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(delivered.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn test_idle_worker_exits_and_removes_its_queue() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let queues = WebhookQueues::new(100, recording(log.clone()))
+            .with_idle_timeout(Duration::from_millis(50));
+        let bot = Uuid::new_v4();
+
+        assert!(queues.enqueue(bot, 1, (bot, 0)));
+        wait_for(&log, 1).await;
+        for _ in 0..50 {
+            if queues.queues.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(queues.queues.lock().unwrap().is_empty());
+
+        // The bot's next update starts a new worker
+        assert!(queues.enqueue(bot, 1, (bot, 1)));
+        wait_for(&log, 2).await;
+        assert_eq!(queues.queues.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,235 @@
+/// Call History Service
+///
+/// Live call state is kept in `WsManager`; this service mirrors each call
+/// into `call_logs` so it survives the session, and runs the ring timeout
+/// that turns unanswered calls into missed calls. Logging is best-effort:
+/// a database failure is traced but never interrupts call signaling.
+
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::{
+    db::Database,
+    error::AppResult,
+    models::{CallLog, CallLogResponse, CallOutcome},
+    ws::{events::ServerEvent, manager::{CallSession, CallState}},
+    AppState,
+};
+
+pub struct CallLogService;
+
+impl CallLogService {
+    /// Record a new call in the given starting outcome (`Ringing` or `Busy`)
+    pub async fn record_started(db: &Database, session: &CallSession, outcome: CallOutcome) {
+        let ended = outcome != CallOutcome::Ringing;
+        let result = sqlx::query(
+            r#"
+            INSERT INTO call_logs (id, chat_id, caller_id, callee_id, call_type, outcome, started_at, ended_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, CASE WHEN $8 THEN NOW() END)
+            ON CONFLICT (id) DO NOTHING
+            "#,
+        )
+        .bind(session.call_id)
+        .bind(session.chat_id)
+        .bind(session.caller_id)
+        .bind(session.callee_id)
+        .bind(&session.call_type)
+        .bind(outcome.as_str())
+        .bind(session.created_at)
+        .bind(ended)
+        .execute(&db.pool)
+        .await;
+
+        if let Err(e) = result {
+            tracing::warn!("Failed to log call {}: {}", session.call_id, e);
+        }
+    }
+
+    /// Mark a ringing call as accepted
+    pub async fn record_accepted(db: &Database, call_id: Uuid) {
+        let result = sqlx::query(
+            r#"
+            UPDATE call_logs
+            SET outcome = 'accepted', accepted_at = NOW()
+            WHERE id = $1 AND outcome = 'ringing'
+            "#,
+        )
+        .bind(call_id)
+        .execute(&db.pool)
+        .await;
+
+        if let Err(e) = result {
+            tracing::warn!("Failed to log accepted call {}: {}", call_id, e);
+        }
+    }
+
+    /// Close the log entry for a call that just ended
+    ///
+    /// `outcome` only applies to calls that were never accepted; an accepted
+    /// call keeps its outcome and just gets its end time.
+    pub async fn record_ended(db: &Database, call_id: Uuid, outcome: CallOutcome) {
+        let result = sqlx::query(
+            r#"
+            UPDATE call_logs
+            SET outcome = CASE WHEN outcome = 'ringing' THEN $2 ELSE outcome END,
+                ended_at = NOW()
+            WHERE id = $1 AND ended_at IS NULL
+            "#,
+        )
+        .bind(call_id)
+        .bind(outcome.as_str())
+        .execute(&db.pool)
+        .await;
+
+        if let Err(e) = result {
+            tracing::warn!("Failed to log ended call {}: {}", call_id, e);
+        }
+    }
+
+    /// Outcome to record when `session` is hung up (not declined)
+    pub fn outcome_on_hangup(session: &CallSession) -> CallOutcome {
+        match session.state {
+            CallState::Pending => CallOutcome::Missed,
+            CallState::Active | CallState::Ended => CallOutcome::Accepted,
+        }
+    }
+
+    /// Tell the callee about a call they did not answer
+    pub async fn notify_missed(state: &AppState, session: &CallSession) {
+        let event = ServerEvent::MissedCall {
+            call_id: session.call_id,
+            caller_id: session.caller_id,
+            chat_id: session.chat_id,
+            call_type: session.call_type.clone(),
+        };
+        state.ws_manager.send_to_user(session.callee_id, event).await;
+    }
+
+    /// End `call_id` as missed if it is still ringing after the configured timeout
+    pub fn spawn_ring_timeout(state: Arc<AppState>, call_id: Uuid) {
+        let timeout = Duration::from_secs(state.config.call_ring_timeout_seconds);
+
+        tokio::spawn(async move {
+            tokio::time::sleep(timeout).await;
+
+            let Some(session) = state.ws_manager.end_call_if_pending(call_id).await else {
+                return;
+            };
+
+            Self::record_ended(&state.db, call_id, CallOutcome::Missed).await;
+
+            let ended_event = ServerEvent::CallEnded {
+                call_id,
+                reason: "missed".to_string(),
+            };
+            state
+                .ws_manager
+                .send_to_user(session.caller_id, ended_event.clone())
+                .await;
+            state
+                .ws_manager
+                .send_to_user(session.callee_id, ended_event)
+                .await;
+            Self::notify_missed(&state, &session).await;
+
+            tracing::info!("Call not answered in time: call_id={}", call_id);
+        });
+    }
+
+    /// A user's calls, newest first, paginated by `before` (a call id)
+    ///
+    /// Returns the page and whether older entries exist.
+    pub async fn get_history(
+        db: &Database,
+        user_id: Uuid,
+        limit: i64,
+        before: Option<Uuid>,
+    ) -> AppResult<(Vec<CallLogResponse>, bool)> {
+        let logs: Vec<CallLog> = sqlx::query_as(
+            r#"
+            SELECT * FROM call_logs
+            WHERE (caller_id = $1 OR callee_id = $1)
+              AND ($2::uuid IS NULL OR started_at < (SELECT started_at FROM call_logs WHERE id = $2))
+            ORDER BY started_at DESC
+            LIMIT $3
+            "#,
+        )
+        .bind(user_id)
+        .bind(before)
+        .bind(limit + 1)
+        .fetch_all(&db.pool)
+        .await?;
+
+        let has_more = logs.len() > limit as usize;
+        let entries = logs
+            .into_iter()
+            .take(limit as usize)
+            .map(|log| CallLogResponse::for_user(log, user_id))
+            .collect();
+
+        Ok((entries, has_more))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration as ChronoDuration, Utc};
+
+    fn session(state: CallState) -> CallSession {
+        CallSession {
+            call_id: Uuid::new_v4(),
+            room_id: "call-test".to_string(),
+            caller_id: Uuid::new_v4(),
+            callee_id: Uuid::new_v4(),
+            chat_id: Uuid::new_v4(),
+            call_type: "voice".to_string(),
+            state,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_outcome_on_hangup() {
+        assert_eq!(
+            CallLogService::outcome_on_hangup(&session(CallState::Pending)),
+            CallOutcome::Missed
+        );
+        assert_eq!(
+            CallLogService::outcome_on_hangup(&session(CallState::Active)),
+            CallOutcome::Accepted
+        );
+    }
+
+    #[test]
+    fn test_history_entry_direction_and_duration() {
+        let caller = Uuid::new_v4();
+        let callee = Uuid::new_v4();
+        let started = Utc::now();
+        let log = CallLog {
+            id: Uuid::new_v4(),
+            chat_id: Uuid::new_v4(),
+            caller_id: caller,
+            callee_id: callee,
+            call_type: "video".to_string(),
+            outcome: "accepted".to_string(),
+            started_at: started,
+            accepted_at: Some(started + ChronoDuration::seconds(5)),
+            ended_at: Some(started + ChronoDuration::seconds(65)),
+        };
+
+        let outgoing = CallLogResponse::for_user(log.clone(), caller);
+        assert_eq!(outgoing.direction, "outgoing");
+        assert_eq!(outgoing.outcome, CallOutcome::Accepted);
+        assert_eq!(outgoing.duration_seconds, Some(60));
+
+        let incoming = CallLogResponse::for_user(
+            CallLog { outcome: "missed".to_string(), accepted_at: None, ..log },
+            callee,
+        );
+        assert_eq!(incoming.direction, "incoming");
+        assert_eq!(incoming.outcome, CallOutcome::Missed);
+        assert_eq!(incoming.duration_seconds, None);
+    }
+}
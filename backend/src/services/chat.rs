@@ -1,18 +1,147 @@
 use crate::{
     db::Database,
     error::{AppError, AppResult},
-    models::{Chat, ChatDetailResponse, ChatParticipant, ChatResponse, Message, MessageResponse},
-    services::MessageService,
+    models::{
+        Chat, ChatDetailResponse, ChatParticipant, ChatResponse, ChatType, ChatUserState, Message,
+        MessageResponse,
+    },
+    services::{custom_emoji, MessageService},
 };
+use sqlx::{Postgres, Transaction};
 use uuid::Uuid;
 
+/// Longest disappearing-message timer a chat can be configured with (1 week)
+pub const MAX_DISAPPEAR_AFTER_SECONDS: i32 = 7 * 24 * 60 * 60;
+
+/// Longest per-chat message retention period (10 years)
+pub const MAX_RETENTION_DAYS: i32 = 3650;
+
+/// Check a per-chat retention override (`None` falls back to the global policy)
+pub fn validate_retention_days(days: Option<i32>) -> AppResult<()> {
+    match days {
+        Some(days) if !(0..=MAX_RETENTION_DAYS).contains(&days) => Err(AppError::BadRequest(
+            format!("Retention must be between 0 and {} days", MAX_RETENTION_DAYS),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Longest folder label a user can file a chat under
+pub const MAX_FOLDER_NAME_LENGTH: usize = 64;
+
+/// Trim a folder label; an empty label means "no folder"
+pub fn normalize_folder(folder: Option<&str>) -> AppResult<Option<String>> {
+    let Some(folder) = folder.map(str::trim).filter(|f| !f.is_empty()) else {
+        return Ok(None);
+    };
+    if folder.chars().count() > MAX_FOLDER_NAME_LENGTH {
+        return Err(AppError::BadRequest(format!(
+            "Folder name must be at most {} characters",
+            MAX_FOLDER_NAME_LENGTH
+        )));
+    }
+    Ok(Some(folder.to_string()))
+}
+
+/// Longest group chat name
+pub const MAX_CHAT_NAME_LENGTH: usize = 128;
+
+/// Longest group chat description
+pub const MAX_CHAT_DESCRIPTION_LENGTH: usize = 1024;
+
+/// Longest chat avatar URL
+pub const MAX_CHAT_AVATAR_URL_LENGTH: usize = 512;
+
+/// Image types a chat avatar may point to, by file extension
+const CHAT_AVATAR_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "webp"];
+
+/// Trim a new chat name; it may not be empty
+pub fn normalize_chat_name(name: &str) -> AppResult<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::BadRequest(
+            "Chat name must not be empty".to_string(),
+        ));
+    }
+    if name.chars().count() > MAX_CHAT_NAME_LENGTH {
+        return Err(AppError::BadRequest(format!(
+            "Chat name must be at most {} characters",
+            MAX_CHAT_NAME_LENGTH
+        )));
+    }
+    Ok(name.to_string())
+}
+
+/// Trim a new chat description; an empty description clears it
+pub fn normalize_chat_description(description: &str) -> AppResult<Option<String>> {
+    let description = description.trim();
+    if description.chars().count() > MAX_CHAT_DESCRIPTION_LENGTH {
+        return Err(AppError::BadRequest(format!(
+            "Chat description must be at most {} characters",
+            MAX_CHAT_DESCRIPTION_LENGTH
+        )));
+    }
+    Ok(Some(description.to_string()).filter(|d| !d.is_empty()))
+}
+
+/// Check a new chat avatar; an empty avatar clears it
+///
+/// The avatar must be an uploaded file (`/uploads/...`) or an http(s) URL
+/// whose path ends in an image extension.
+pub fn normalize_chat_avatar(avatar: &str) -> AppResult<Option<String>> {
+    let avatar = avatar.trim();
+    if avatar.is_empty() {
+        return Ok(None);
+    }
+    if avatar.len() > MAX_CHAT_AVATAR_URL_LENGTH {
+        return Err(AppError::BadRequest(format!(
+            "Chat avatar URL must be at most {} characters",
+            MAX_CHAT_AVATAR_URL_LENGTH
+        )));
+    }
+
+    let path = if avatar.starts_with("/uploads/") {
+        avatar
+            .split(['?', '#'])
+            .next()
+            .unwrap_or_default()
+            .to_string()
+    } else {
+        match url::Url::parse(avatar) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => url.path().to_string(),
+            _ => {
+                return Err(AppError::BadRequest(
+                    "Chat avatar must be an uploaded file or an http(s) URL".to_string(),
+                ))
+            }
+        }
+    };
+
+    let is_image = path
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase())
+        .is_some_and(|ext| CHAT_AVATAR_EXTENSIONS.contains(&ext.as_str()));
+    if !is_image {
+        return Err(AppError::BadRequest(format!(
+            "Chat avatar must be an image ({})",
+            CHAT_AVATAR_EXTENSIONS.join(", ")
+        )));
+    }
+
+    Ok(Some(avatar.to_string()))
+}
+
 pub struct ChatService;
 
 impl ChatService {
+    /// List the user's chats. Archived chats are only listed when `archived`
+    /// is true; `folder` restricts the list to one folder label.
     pub async fn get_user_chats(
         db: &Database,
         user_id: Uuid,
         search: Option<&str>,
+        archived: bool,
+        folder: Option<&str>,
     ) -> AppResult<Vec<ChatResponse>> {
         let chats: Vec<Chat> = if let Some(query) = search {
             let pattern = format!("%{}%", query);
@@ -20,13 +149,20 @@ impl ChatService {
                 r#"
                 SELECT DISTINCT c.* FROM chats c
                 JOIN chat_participants cp ON c.id = cp.chat_id
+                LEFT JOIN chat_user_state s ON s.chat_id = c.id AND s.user_id = cp.user_id
                 LEFT JOIN messages m ON c.id = m.chat_id
-                WHERE cp.user_id = $1 AND (c.name ILIKE $2 OR m.text ILIKE $2)
+                WHERE cp.user_id = $1
+                  AND c.deleted_at IS NULL
+                  AND (c.name ILIKE $2 OR (m.text ILIKE $2 AND NOT m.encrypted))
+                  AND COALESCE(s.archived, FALSE) = $3
+                  AND ($4::text IS NULL OR s.folder = $4)
                 ORDER BY cp.is_pinned DESC NULLS LAST, c.updated_at DESC
                 "#,
             )
             .bind(user_id)
             .bind(&pattern)
+            .bind(archived)
+            .bind(folder)
             .fetch_all(&db.pool)
             .await?
         } else {
@@ -34,11 +170,17 @@ impl ChatService {
                 r#"
                 SELECT c.* FROM chats c
                 JOIN chat_participants cp ON c.id = cp.chat_id
+                LEFT JOIN chat_user_state s ON s.chat_id = c.id AND s.user_id = cp.user_id
                 WHERE cp.user_id = $1
+                  AND c.deleted_at IS NULL
+                  AND COALESCE(s.archived, FALSE) = $2
+                  AND ($3::text IS NULL OR s.folder = $3)
                 ORDER BY cp.is_pinned DESC NULLS LAST, c.updated_at DESC
                 "#,
             )
             .bind(user_id)
+            .bind(archived)
+            .bind(folder)
             .fetch_all(&db.pool)
             .await?
         };
@@ -58,16 +200,9 @@ impl ChatService {
         user_id: Uuid,
     ) -> AppResult<ChatDetailResponse> {
         // Check if user is participant
-        let participant: Option<ChatParticipant> =
-            sqlx::query_as("SELECT * FROM chat_participants WHERE chat_id = $1 AND user_id = $2")
-                .bind(chat_id)
-                .bind(user_id)
-                .fetch_optional(&db.pool)
-                .await?;
-
-        if participant.is_none() {
-            return Err(AppError::AccessDenied);
-        }
+        let participant = Self::participant(db, chat_id, user_id)
+            .await?
+            .ok_or(AppError::AccessDenied)?;
 
         let chat: Chat = sqlx::query_as("SELECT * FROM chats WHERE id = $1")
             .bind(chat_id)
@@ -89,27 +224,56 @@ impl ChatService {
             chat_type: chat.chat_type,
             name,
             avatar: chat.avatar,
+            description: chat.description,
             participants,
-            unread_count: participant.unwrap().unread_count,
+            unread_count: participant.unread_count,
             is_typing: false,
             is_bot,
+            slow_mode_seconds: chat.slow_mode_seconds,
+            disappear_after_seconds: chat.disappear_after_seconds,
+            e2ee: chat.e2ee,
         })
     }
 
+    /// Create a group or channel with the creator as its admin
+    ///
+    /// A channel may start without other members; they join through invite
+    /// links. Members beyond `max_participants` fail with `ChatFull`.
     pub async fn create_group(
         db: &Database,
         creator_id: Uuid,
         name: &str,
-        participant_ids: Vec<Uuid>,
+        mut participant_ids: Vec<Uuid>,
+        chat_type: ChatType,
+        max_participants: i64,
     ) -> AppResult<ChatDetailResponse> {
         if name.trim().is_empty() {
             return Err(AppError::Internal(anyhow::anyhow!("Missing name")));
         }
 
-        if participant_ids.is_empty() {
+        // The creator joins as admin; anyone listed twice joins once
+        let mut seen = std::collections::HashSet::from([creator_id]);
+        participant_ids.retain(|id| seen.insert(*id));
+
+        if !chat_type.has_admins() {
+            return Err(AppError::BadRequest(format!(
+                "Cannot create a {} chat with members",
+                chat_type.as_str()
+            )));
+        }
+        if participant_ids.is_empty() && chat_type == ChatType::Group {
             return Err(AppError::InvalidParticipants);
         }
 
+        // Bot ids in the list are subscribed rather than added as members
+        let other_users: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE id = ANY($1)")
+            .bind(&participant_ids)
+            .fetch_one(&db.pool)
+            .await?;
+        if other_users + 1 > max_participants {
+            return Err(AppError::ChatFull(max_participants));
+        }
+
         let avatar = format!(
             "https://api.dicebear.com/7.x/shapes/svg?seed={}",
             name.replace(' ', "")
@@ -119,10 +283,11 @@ impl ChatService {
         let chat: Chat = sqlx::query_as(
             r#"
             INSERT INTO chats (type, name, avatar, created_by)
-            VALUES ('group', $1, $2, $3)
+            VALUES ($1, $2, $3, $4)
             RETURNING *
             "#,
         )
+        .bind(chat_type.as_str())
         .bind(name)
         .bind(&avatar)
         .bind(creator_id)
@@ -186,13 +351,17 @@ impl ChatService {
 
         Ok(ChatDetailResponse {
             id: chat.id,
-            chat_type: "group".to_string(),
+            chat_type: chat.chat_type,
             name: name.to_string(),
             avatar: Some(avatar),
+            description: None,
             participants: user_participants,
             unread_count: 0,
             is_typing: false,
             is_bot: false,
+            slow_mode_seconds: 0,
+            disappear_after_seconds: 0,
+            e2ee: false,
         })
     }
 
@@ -205,6 +374,37 @@ impl ChatService {
         .execute(&db.pool)
         .await?;
 
+        // Move the read pointer to the newest message in the chat
+        sqlx::query(
+            r#"
+            UPDATE chat_participants cp
+            SET last_read_message_id = latest.id, last_read_message_at = latest.created_at
+            FROM (
+                SELECT id, created_at FROM messages WHERE chat_id = $1
+                ORDER BY created_at DESC, id DESC LIMIT 1
+            ) latest
+            WHERE cp.chat_id = $1 AND cp.user_id = $2
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .execute(&db.pool)
+        .await?;
+
+        // Record per-reader receipts before the unread flag is cleared
+        sqlx::query(
+            r#"
+            INSERT INTO read_receipts (message_id, user_id)
+            SELECT id, $2 FROM messages
+            WHERE chat_id = $1 AND sender_id != $2 AND is_read = false
+            ON CONFLICT (message_id, user_id) DO NOTHING
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .execute(&db.pool)
+        .await?;
+
         // Mark messages as read
         sqlx::query(
             r#"
@@ -231,6 +431,7 @@ impl ChatService {
             r#"
             SELECT c.* FROM chats c
             WHERE c.type = 'private'
+            AND c.deleted_at IS NULL
             AND EXISTS (SELECT 1 FROM chat_participants WHERE chat_id = c.id AND user_id = $1)
             AND EXISTS (SELECT 1 FROM chat_participants WHERE chat_id = c.id AND user_id = $2)
             LIMIT 1
@@ -288,10 +489,14 @@ impl ChatService {
             chat_type: "private".to_string(),
             name: other_user.0,
             avatar: other_user.1,
+            description: None,
             participants: vec![user_id, other_user_id],
             unread_count: 0,
             is_typing: false,
             is_bot: false,
+            slow_mode_seconds: 0,
+            disappear_after_seconds: 0,
+            e2ee: false,
         })
     }
 
@@ -318,6 +523,7 @@ impl ChatService {
             INNER JOIN chat_participants cp ON c.id = cp.chat_id
             INNER JOIN bot_chats bc ON c.id = bc.chat_id
             WHERE c.type = 'bot'
+            AND c.deleted_at IS NULL
             AND cp.user_id = $1
             AND bc.bot_id = $2
             LIMIT 1
@@ -379,31 +585,108 @@ impl ChatService {
             chat_type: "bot".to_string(),
             name: bot_name,
             avatar: Some(bot_avatar),
+            description: None,
             participants: vec![user_id],
             unread_count: 0,
             is_typing: false,
             is_bot: true,
+            slow_mode_seconds: 0,
+            disappear_after_seconds: 0,
+            e2ee: false,
         })
     }
 
-    pub async fn is_participant(db: &Database, chat_id: Uuid, user_id: Uuid) -> AppResult<bool> {
-        let exists: Option<(i32,)> =
-            sqlx::query_as("SELECT 1 FROM chat_participants WHERE chat_id = $1 AND user_id = $2")
+    /// Check one more member fits in a group or channel of at most
+    /// `max_participants`
+    ///
+    /// Locks the chat row until the transaction ends, so concurrent joins
+    /// can't both take the last place.
+    pub async fn ensure_room_for_member(
+        tx: &mut Transaction<'_, Postgres>,
+        chat_id: Uuid,
+        max_participants: i64,
+    ) -> AppResult<()> {
+        sqlx::query("SELECT 1 FROM chats WHERE id = $1 AND deleted_at IS NULL FOR UPDATE")
+            .bind(chat_id)
+            .fetch_optional(&mut **tx)
+            .await?
+            .ok_or(AppError::ChatNotFound)?;
+
+        let members: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM chat_participants WHERE chat_id = $1")
                 .bind(chat_id)
-                .bind(user_id)
-                .fetch_optional(&db.pool)
+                .fetch_one(&mut **tx)
                 .await?;
+        if members >= max_participants {
+            return Err(AppError::ChatFull(max_participants));
+        }
 
-        Ok(exists.is_some())
+        Ok(())
     }
 
-    /// Delete a chat (only for private chats or group admins)
-    pub async fn delete_chat(db: &Database, chat_id: Uuid, user_id: Uuid) -> AppResult<()> {
-        // Check if user is participant
-        if !Self::is_participant(db, chat_id, user_id).await? {
+    /// Check the user may post in the chat: any participant, except in
+    /// channels where only admins post
+    pub async fn ensure_can_post(db: &Database, chat_id: Uuid, user_id: Uuid) -> AppResult<()> {
+        let row: Option<(String, String)> = sqlx::query_as(
+            r#"
+            SELECT c.type, cp.role
+            FROM chats c
+            JOIN chat_participants cp ON cp.chat_id = c.id
+            WHERE c.id = $1 AND cp.user_id = $2 AND c.deleted_at IS NULL
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .fetch_optional(&db.pool)
+        .await?;
+
+        let Some((chat_type, role)) = row else {
             return Err(AppError::AccessDenied);
+        };
+        if ChatType::parse(&chat_type) == Some(ChatType::Channel) && role != "admin" {
+            return Err(AppError::Forbidden(
+                "Only channel admins can post".to_string(),
+            ));
         }
 
+        Ok(())
+    }
+
+    /// Whether the user is a participant of the chat; deleted chats have none
+    pub async fn is_participant(db: &Database, chat_id: Uuid, user_id: Uuid) -> AppResult<bool> {
+        Ok(Self::participant(db, chat_id, user_id).await?.is_some())
+    }
+
+    /// The user's membership of a chat that is not deleted
+    pub async fn participant(
+        db: &Database,
+        chat_id: Uuid,
+        user_id: Uuid,
+    ) -> AppResult<Option<ChatParticipant>> {
+        let participant = sqlx::query_as(
+            r#"
+            SELECT cp.* FROM chat_participants cp
+            JOIN chats c ON c.id = cp.chat_id
+            WHERE cp.chat_id = $1 AND cp.user_id = $2 AND c.deleted_at IS NULL
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .fetch_optional(&db.pool)
+        .await?;
+
+        Ok(participant)
+    }
+
+    /// Delete a chat (only for private chats or group and channel admins)
+    ///
+    /// The chat is hidden from its participants but kept until the purger
+    /// removes it; until then `restore_chat` brings it back.
+    pub async fn delete_chat(db: &Database, chat_id: Uuid, user_id: Uuid) -> AppResult<()> {
+        let participant = Self::participant(db, chat_id, user_id)
+            .await?
+            .ok_or(AppError::AccessDenied)?;
+
         // Get chat info
         let chat: Chat = sqlx::query_as("SELECT * FROM chats WHERE id = $1")
             .bind(chat_id)
@@ -411,23 +694,214 @@ impl ChatService {
             .await?
             .ok_or(AppError::ChatNotFound)?;
 
-        // For group chats, check if user is admin
-        if chat.chat_type == "group" {
-            let participant: ChatParticipant = sqlx::query_as(
-                "SELECT * FROM chat_participants WHERE chat_id = $1 AND user_id = $2",
-            )
+        // For group chats and channels, check if user is admin
+        if chat.has_admins() && participant.role != "admin" {
+            return Err(AppError::AccessDenied);
+        }
+
+        sqlx::query("UPDATE chats SET deleted_at = NOW() WHERE id = $1 AND deleted_at IS NULL")
             .bind(chat_id)
-            .bind(user_id)
-            .fetch_one(&db.pool)
+            .execute(&db.pool)
             .await?;
 
-            if participant.role != "admin" {
-                return Err(AppError::AccessDenied);
-            }
+        Ok(())
+    }
+
+    /// Restore a deleted chat within `recovery_days` of its deletion
+    ///
+    /// Allowed to the participants who could delete it. A chat that is not
+    /// deleted, or whose window has passed, is `ChatNotFound`.
+    pub async fn restore_chat(
+        db: &Database,
+        chat_id: Uuid,
+        user_id: Uuid,
+        recovery_days: u32,
+    ) -> AppResult<()> {
+        let row: Option<(String, String)> = sqlx::query_as(
+            r#"
+            SELECT c.type, cp.role
+            FROM chats c
+            JOIN chat_participants cp ON cp.chat_id = c.id
+            WHERE c.id = $1 AND cp.user_id = $2
+              AND c.deleted_at > NOW() - make_interval(days => $3)
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(recovery_days as i32)
+        .fetch_optional(&db.pool)
+        .await?;
+
+        let Some((chat_type, role)) = row else {
+            return Err(AppError::ChatNotFound);
+        };
+        if ChatType::parse(&chat_type).is_some_and(ChatType::has_admins) && role != "admin" {
+            return Err(AppError::AccessDenied);
+        }
+
+        sqlx::query("UPDATE chats SET deleted_at = NULL WHERE id = $1")
+            .bind(chat_id)
+            .execute(&db.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Configure slow mode for a group chat (admins only). 0 disables it;
+    /// `max_seconds` is `MAX_SLOW_MODE_SECONDS`.
+    pub async fn set_slow_mode(
+        db: &Database,
+        chat_id: Uuid,
+        user_id: Uuid,
+        seconds: i32,
+        max_seconds: i32,
+    ) -> AppResult<()> {
+        if !(0..=max_seconds).contains(&seconds) {
+            return Err(AppError::BadRequest(format!(
+                "Slow mode must be between 0 and {} seconds",
+                max_seconds
+            )));
+        }
+
+        let participant = Self::participant(db, chat_id, user_id)
+            .await?
+            .ok_or(AppError::AccessDenied)?;
+
+        if participant.role != "admin" {
+            return Err(AppError::Forbidden(
+                "Only chat admins can change slow mode".to_string(),
+            ));
+        }
+
+        let result = sqlx::query(
+            "UPDATE chats SET slow_mode_seconds = $1, updated_at = NOW() WHERE id = $2 AND type = 'group'",
+        )
+        .bind(seconds)
+        .bind(chat_id)
+        .execute(&db.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::BadRequest(
+                "Slow mode is only available in group chats".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Set the disappearing-message timer for a chat. 0 turns it off.
+    ///
+    /// Group chats require an admin; in private and bot chats either party may
+    /// change it. Only messages sent afterwards are affected.
+    pub async fn set_disappearing_timer(
+        db: &Database,
+        chat_id: Uuid,
+        user_id: Uuid,
+        seconds: i32,
+    ) -> AppResult<()> {
+        if !(0..=MAX_DISAPPEAR_AFTER_SECONDS).contains(&seconds) {
+            return Err(AppError::BadRequest(format!(
+                "Disappearing timer must be between 0 and {} seconds",
+                MAX_DISAPPEAR_AFTER_SECONDS
+            )));
+        }
+
+        Self::ensure_can_change_setting(db, chat_id, user_id, "the disappearing timer").await?;
+
+        sqlx::query(
+            "UPDATE chats SET disappear_after_seconds = $1, updated_at = NOW() WHERE id = $2",
+        )
+        .bind(seconds)
+        .bind(chat_id)
+        .execute(&db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Override the message retention period for a chat
+    ///
+    /// `None` restores the server default; `Some(0)` keeps messages forever.
+    /// Same permissions as the disappearing timer.
+    pub async fn set_retention_days(
+        db: &Database,
+        chat_id: Uuid,
+        user_id: Uuid,
+        days: Option<i32>,
+    ) -> AppResult<()> {
+        validate_retention_days(days)?;
+
+        Self::ensure_can_change_setting(db, chat_id, user_id, "message retention").await?;
+
+        sqlx::query("UPDATE chats SET retention_days = $1, updated_at = NOW() WHERE id = $2")
+            .bind(days)
+            .bind(chat_id)
+            .execute(&db.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Enable or disable server-generated link previews for a chat
+    ///
+    /// Same permissions as the disappearing timer: group admins, or either
+    /// party in private chats.
+    pub async fn set_link_previews_enabled(
+        db: &Database,
+        chat_id: Uuid,
+        user_id: Uuid,
+        enabled: bool,
+    ) -> AppResult<()> {
+        Self::ensure_can_change_setting(db, chat_id, user_id, "link previews").await?;
+
+        sqlx::query(
+            "UPDATE chats SET link_previews_enabled = $1, updated_at = NOW() WHERE id = $2",
+        )
+        .bind(enabled)
+        .bind(chat_id)
+        .execute(&db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Choose who may add custom emoji to a group: `admins` or `members`
+    ///
+    /// Same permissions as the disappearing timer.
+    pub async fn set_custom_emoji_policy(
+        db: &Database,
+        chat_id: Uuid,
+        user_id: Uuid,
+        policy: &str,
+    ) -> AppResult<()> {
+        if policy != custom_emoji::POLICY_ADMINS && policy != custom_emoji::POLICY_MEMBERS {
+            return Err(AppError::BadRequest(
+                "Custom emoji policy must be 'admins' or 'members'".to_string(),
+            ));
         }
 
-        // Delete chat (cascade will delete messages, participants, etc.)
-        sqlx::query("DELETE FROM chats WHERE id = $1")
+        Self::ensure_can_change_setting(db, chat_id, user_id, "the custom emoji policy").await?;
+
+        sqlx::query(
+            "UPDATE chats SET custom_emoji_policy = $1, updated_at = NOW() WHERE id = $2",
+        )
+        .bind(policy)
+        .bind(chat_id)
+        .execute(&db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Turn on end-to-end encryption for a chat; it cannot be turned off
+    ///
+    /// Same permissions as the disappearing timer. Messages sent afterwards
+    /// are stored as opaque ciphertext; earlier ones are left as they are.
+    pub async fn enable_e2ee(db: &Database, chat_id: Uuid, user_id: Uuid) -> AppResult<()> {
+        Self::ensure_can_change_setting(db, chat_id, user_id, "end-to-end encryption").await?;
+
+        sqlx::query("UPDATE chats SET e2ee = TRUE, updated_at = NOW() WHERE id = $1 AND NOT e2ee")
             .bind(chat_id)
             .execute(&db.pool)
             .await?;
@@ -435,6 +909,104 @@ impl ChatService {
         Ok(())
     }
 
+    /// Whether the chat's messages are end-to-end encrypted
+    pub async fn is_e2ee(db: &Database, chat_id: Uuid) -> AppResult<bool> {
+        let e2ee: Option<bool> = sqlx::query_scalar("SELECT e2ee FROM chats WHERE id = $1")
+            .bind(chat_id)
+            .fetch_optional(&db.pool)
+            .await?;
+        e2ee.ok_or(AppError::ChatNotFound)
+    }
+
+    /// Edit a group chat's name, description and avatar (admins only)
+    ///
+    /// `None` leaves a field unchanged; an empty description or avatar clears
+    /// it. Private and bot chats take their name and avatar from the other
+    /// side, so they cannot be edited.
+    pub async fn update_chat(
+        db: &Database,
+        chat_id: Uuid,
+        user_id: Uuid,
+        name: Option<&str>,
+        description: Option<&str>,
+        avatar: Option<&str>,
+    ) -> AppResult<Chat> {
+        let name = name.map(normalize_chat_name).transpose()?;
+        let description = description.map(normalize_chat_description).transpose()?;
+        let avatar = avatar.map(normalize_chat_avatar).transpose()?;
+
+        let participant = Self::participant(db, chat_id, user_id)
+            .await?
+            .ok_or(AppError::AccessDenied)?;
+
+        let chat: Chat = sqlx::query_as("SELECT * FROM chats WHERE id = $1")
+            .bind(chat_id)
+            .fetch_optional(&db.pool)
+            .await?
+            .ok_or(AppError::ChatNotFound)?;
+
+        if !chat.has_admins() {
+            return Err(AppError::BadRequest(
+                "Only group chats and channels have editable info".to_string(),
+            ));
+        }
+        if participant.role != "admin" {
+            return Err(AppError::Forbidden(
+                "Only chat admins can edit chat info".to_string(),
+            ));
+        }
+
+        let chat: Chat = sqlx::query_as(
+            r#"
+            UPDATE chats SET
+                name = COALESCE($2, name),
+                description = CASE WHEN $3 THEN $4 ELSE description END,
+                avatar = CASE WHEN $5 THEN $6 ELSE avatar END,
+                updated_at = NOW()
+            WHERE id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(chat_id)
+        .bind(name)
+        .bind(description.is_some())
+        .bind(description.flatten())
+        .bind(avatar.is_some())
+        .bind(avatar.flatten())
+        .fetch_one(&db.pool)
+        .await?;
+
+        Ok(chat)
+    }
+
+    /// Chat-wide settings may be changed by group and channel admins, or by
+    /// either party in a private chat
+    async fn ensure_can_change_setting(
+        db: &Database,
+        chat_id: Uuid,
+        user_id: Uuid,
+        setting: &str,
+    ) -> AppResult<()> {
+        let participant = Self::participant(db, chat_id, user_id)
+            .await?
+            .ok_or(AppError::AccessDenied)?;
+
+        let chat: Chat = sqlx::query_as("SELECT * FROM chats WHERE id = $1")
+            .bind(chat_id)
+            .fetch_optional(&db.pool)
+            .await?
+            .ok_or(AppError::ChatNotFound)?;
+
+        if chat.has_admins() && participant.role != "admin" {
+            return Err(AppError::Forbidden(format!(
+                "Only chat admins can change {}",
+                setting
+            )));
+        }
+
+        Ok(())
+    }
+
     /// Get all participant IDs for a chat
     pub async fn get_participant_ids(db: &Database, chat_id: Uuid) -> AppResult<Vec<Uuid>> {
         let participants: Vec<Uuid> =
@@ -466,11 +1038,12 @@ impl ChatService {
 
         let name = Self::get_chat_name(db, chat, user_id).await?;
         let is_bot = chat.chat_type == "bot";
+        let state = Self::get_chat_state(db, chat.id, user_id).await?;
 
         // Get last message
         let last_message: Option<MessageResponse> = {
             let msg: Option<Message> = sqlx::query_as(
-                "SELECT * FROM messages WHERE chat_id = $1 ORDER BY created_at DESC LIMIT 1",
+                "SELECT * FROM messages WHERE chat_id = $1 AND thread_root_id IS NULL ORDER BY created_at DESC LIMIT 1",
             )
             .bind(chat.id)
             .fetch_optional(&db.pool)
@@ -495,6 +1068,9 @@ impl ChatService {
             typing_user: None,
             is_bot,
             is_pinned: participant.is_pinned.unwrap_or(false),
+            is_archived: state.archived,
+            folder: state.folder,
+            is_muted: state.muted,
         })
     }
 
@@ -526,6 +1102,112 @@ impl ChatService {
         Ok("Unknown".to_string())
     }
 
+    /// The user's private view state of a chat (defaults if never set)
+    pub async fn get_chat_state(
+        db: &Database,
+        chat_id: Uuid,
+        user_id: Uuid,
+    ) -> AppResult<ChatUserState> {
+        let state: Option<ChatUserState> = sqlx::query_as(
+            "SELECT archived, folder, muted FROM chat_user_state WHERE chat_id = $1 AND user_id = $2",
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .fetch_optional(&db.pool)
+        .await?;
+
+        Ok(state.unwrap_or_default())
+    }
+
+    /// Archive or unarchive a chat for one user only
+    pub async fn archive_chat(
+        db: &Database,
+        chat_id: Uuid,
+        user_id: Uuid,
+        archived: bool,
+    ) -> AppResult<ChatUserState> {
+        if !Self::is_participant(db, chat_id, user_id).await? {
+            return Err(AppError::AccessDenied);
+        }
+
+        let state: ChatUserState = sqlx::query_as(
+            r#"
+            INSERT INTO chat_user_state (chat_id, user_id, archived)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (chat_id, user_id)
+            DO UPDATE SET archived = EXCLUDED.archived, updated_at = NOW()
+            RETURNING archived, folder, muted
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(archived)
+        .fetch_one(&db.pool)
+        .await?;
+
+        Ok(state)
+    }
+
+    /// Mute or unmute push notifications of a chat for one user only
+    pub async fn mute_chat(
+        db: &Database,
+        chat_id: Uuid,
+        user_id: Uuid,
+        muted: bool,
+    ) -> AppResult<ChatUserState> {
+        if !Self::is_participant(db, chat_id, user_id).await? {
+            return Err(AppError::AccessDenied);
+        }
+
+        let state: ChatUserState = sqlx::query_as(
+            r#"
+            INSERT INTO chat_user_state (chat_id, user_id, muted)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (chat_id, user_id)
+            DO UPDATE SET muted = EXCLUDED.muted, updated_at = NOW()
+            RETURNING archived, folder, muted
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(muted)
+        .fetch_one(&db.pool)
+        .await?;
+
+        Ok(state)
+    }
+
+    /// File a chat under a folder label for one user (`None` clears it)
+    pub async fn set_chat_folder(
+        db: &Database,
+        chat_id: Uuid,
+        user_id: Uuid,
+        folder: Option<&str>,
+    ) -> AppResult<ChatUserState> {
+        let folder = normalize_folder(folder)?;
+
+        if !Self::is_participant(db, chat_id, user_id).await? {
+            return Err(AppError::AccessDenied);
+        }
+
+        let state: ChatUserState = sqlx::query_as(
+            r#"
+            INSERT INTO chat_user_state (chat_id, user_id, folder)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (chat_id, user_id)
+            DO UPDATE SET folder = EXCLUDED.folder, updated_at = NOW()
+            RETURNING archived, folder, muted
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(&folder)
+        .fetch_one(&db.pool)
+        .await?;
+
+        Ok(state)
+    }
+
     /// Pin a chat for a user
     pub async fn pin_chat(db: &Database, chat_id: Uuid, user_id: Uuid) -> AppResult<()> {
         // Check if user is participant
@@ -580,3 +1262,61 @@ impl ChatService {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_folder() {
+        assert_eq!(normalize_folder(None).unwrap(), None);
+        assert_eq!(normalize_folder(Some("   ")).unwrap(), None);
+        assert_eq!(normalize_folder(Some(" Work ")).unwrap().as_deref(), Some("Work"));
+
+        let longest = "é".repeat(MAX_FOLDER_NAME_LENGTH);
+        assert_eq!(normalize_folder(Some(&longest)).unwrap(), Some(longest.clone()));
+        assert!(normalize_folder(Some(&format!("{longest}x"))).is_err());
+    }
+
+    #[test]
+    fn test_normalize_chat_name_and_description() {
+        assert_eq!(normalize_chat_name("  Team  ").unwrap(), "Team");
+        assert!(normalize_chat_name("   ").is_err());
+        assert!(normalize_chat_name(&"é".repeat(MAX_CHAT_NAME_LENGTH)).is_ok());
+        assert!(normalize_chat_name(&"é".repeat(MAX_CHAT_NAME_LENGTH + 1)).is_err());
+
+        assert_eq!(normalize_chat_description(" ").unwrap(), None);
+        assert_eq!(normalize_chat_description(" Hi ").unwrap().as_deref(), Some("Hi"));
+        assert!(normalize_chat_description(&"a".repeat(MAX_CHAT_DESCRIPTION_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn test_normalize_chat_avatar() {
+        assert_eq!(normalize_chat_avatar("").unwrap(), None);
+        assert!(normalize_chat_avatar("/uploads/abc.png").unwrap().is_some());
+        assert!(normalize_chat_avatar("/uploads/abc.JPG?v=2").unwrap().is_some());
+        assert!(normalize_chat_avatar("https://cdn.example.com/a/b.webp?size=64").is_ok());
+
+        // Not an image
+        assert!(normalize_chat_avatar("/uploads/report.pdf").is_err());
+        assert!(normalize_chat_avatar("https://example.com/avatar").is_err());
+        assert!(normalize_chat_avatar("https://example.com/x.svg").is_err());
+        // Not a URL we serve or fetch
+        assert!(normalize_chat_avatar("javascript:alert(1).png").is_err());
+        assert!(normalize_chat_avatar("ftp://example.com/a.png").is_err());
+        assert!(normalize_chat_avatar(&format!(
+            "https://example.com/{}.png",
+            "a".repeat(MAX_CHAT_AVATAR_URL_LENGTH)
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn test_validate_retention_days() {
+        assert!(validate_retention_days(None).is_ok());
+        assert!(validate_retention_days(Some(0)).is_ok());
+        assert!(validate_retention_days(Some(MAX_RETENTION_DAYS)).is_ok());
+        assert!(validate_retention_days(Some(-1)).is_err());
+        assert!(validate_retention_days(Some(MAX_RETENTION_DAYS + 1)).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+/// Deleted Chat Purge Service
+///
+/// Deleting a chat only sets `chats.deleted_at`; the chat stays restorable
+/// for `CHAT_RECOVERY_DAYS`. Afterwards this purger deletes it for good, its
+/// messages, participants and attachment rows cascading with it, and removes
+/// the stored attachment files no other message still uses.
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::{
+    db::Database,
+    error::AppResult,
+    services::DisappearingMessageService,
+    AppState,
+};
+
+/// Chats deleted per transaction
+const PURGE_BATCH_SIZE: i64 = 20;
+
+pub struct ChatPurgeService;
+
+impl ChatPurgeService {
+    /// Spawn the background purger. Runs until the process exits.
+    pub fn spawn_purger(state: Arc<AppState>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match Self::run_once(&state.db, state.config.chat_recovery_days).await {
+                    Ok(0) => {}
+                    Ok(purged) => tracing::info!("Purged {} deleted chats", purged),
+                    Err(e) => tracing::error!("Deleted chat purger failed: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Purge every chat deleted more than `recovery_days` ago, one batch at a time
+    pub async fn run_once(db: &Database, recovery_days: u32) -> AppResult<usize> {
+        let mut total = 0;
+
+        loop {
+            let (purged, urls) = Self::purge_batch(db, recovery_days, PURGE_BATCH_SIZE).await?;
+            total += purged;
+
+            // The chats are gone either way; a file left behind must not stop the run
+            for url in &urls {
+                if let Err(e) = DisappearingMessageService::remove_upload_if_unreferenced(db, url).await {
+                    tracing::warn!("Failed to clean up upload {} of a purged chat: {}", url, e);
+                }
+            }
+
+            if (purged as i64) < PURGE_BATCH_SIZE {
+                return Ok(total);
+            }
+            // Let other queries in between batches
+            tokio::task::yield_now().await;
+        }
+    }
+
+    /// Delete up to `limit` chats past their recovery window in one transaction
+    ///
+    /// Returns how many were deleted and the URLs of their attachments, so
+    /// the stored files can be removed once the transaction has committed.
+    async fn purge_batch(
+        db: &Database,
+        recovery_days: u32,
+        limit: i64,
+    ) -> AppResult<(usize, Vec<String>)> {
+        let mut tx = db.pool.begin().await?;
+
+        let chat_ids: Vec<Uuid> = sqlx::query_scalar(
+            r#"
+            SELECT id FROM chats
+            WHERE deleted_at <= NOW() - make_interval(days => $1)
+            ORDER BY deleted_at
+            LIMIT $2
+            FOR UPDATE SKIP LOCKED
+            "#,
+        )
+        .bind(recovery_days as i32)
+        .bind(limit)
+        .fetch_all(&mut *tx)
+        .await?;
+        if chat_ids.is_empty() {
+            return Ok((0, Vec::new()));
+        }
+
+        let urls: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT DISTINCT a.url FROM attachments a
+            JOIN messages m ON m.id = a.message_id
+            WHERE m.chat_id = ANY($1)
+            "#,
+        )
+        .bind(&chat_ids)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        sqlx::query("DELETE FROM chats WHERE id = ANY($1)")
+            .bind(&chat_ids)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok((chat_ids.len(), urls))
+    }
+}
@@ -0,0 +1,260 @@
+/// Message content validation and normalization
+///
+/// Every user-authored message text goes through `normalize_message_text`
+/// before it is stored, so the HTTP, WebSocket and QUIC paths share one set
+/// of rules and clients get back the canonical form:
+/// - line endings become `\n` and other control characters are rejected
+/// - text is Unicode NFC-normalized and trimmed
+/// - whitespace-only text counts as empty
+/// - the normalized text must fit in the configured byte limit
+
+use unicode_normalization::UnicodeNormalization;
+use url::Url;
+
+use crate::error::{AppError, AppResult};
+
+/// Default `MAX_MESSAGE_BYTES`
+pub const DEFAULT_MAX_MESSAGE_BYTES: usize = 64 * 1024;
+
+/// Most URLs reported for a single message
+pub const MAX_LINKS_PER_MESSAGE: usize = 5;
+
+/// Most distinct `@` mentions resolved for a single message
+pub const MAX_MENTIONS_PER_MESSAGE: usize = 50;
+
+/// Longest name an `@` mention can carry (`users.username` is VARCHAR(50))
+const MAX_MENTION_LENGTH: usize = 50;
+
+/// An `@` token found in message text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mention {
+    /// `@username`, lowercased
+    User(String),
+    /// `@everyone`: every participant
+    Everyone,
+    /// `@here`: participants who are currently connected
+    Here,
+}
+
+/// Validate and normalize optional message text
+///
+/// Returns `None` when there is no text but the message carries attachments;
+/// a message with neither is `AppError::EmptyMessage`.
+pub fn normalize_message_text(
+    text: Option<&str>,
+    has_attachments: bool,
+    max_bytes: usize,
+) -> AppResult<Option<String>> {
+    match text.map(|text| normalize_text(text, max_bytes)) {
+        Some(Ok(text)) => Ok(Some(text)),
+        Some(Err(AppError::EmptyMessage)) | None if has_attachments => Ok(None),
+        Some(Err(e)) => Err(e),
+        None => Err(AppError::EmptyMessage),
+    }
+}
+
+/// Validate and normalize non-optional message text (edits, bot messages)
+pub fn normalize_text(text: &str, max_bytes: usize) -> AppResult<String> {
+    let unified = text.replace("\r\n", "\n").replace('\r', "\n");
+
+    if unified
+        .chars()
+        .any(|c| c.is_control() && c != '\n' && c != '\t')
+    {
+        return Err(AppError::BadRequest(
+            "Message contains control characters".to_string(),
+        ));
+    }
+
+    let normalized: String = unified.nfc().collect();
+    let normalized = normalized.trim();
+
+    if normalized.is_empty() {
+        return Err(AppError::EmptyMessage);
+    }
+    if normalized.len() > max_bytes {
+        return Err(AppError::MessageTooLong(max_bytes));
+    }
+
+    Ok(normalized.to_string())
+}
+
+/// Collect the distinct http(s) URLs in a message, in order of appearance
+///
+/// Used to pick link-preview candidates; the text itself is left untouched.
+pub fn extract_urls(text: &str) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+
+    for word in text.split_whitespace() {
+        let candidate = word
+            .trim_start_matches(['(', '<', '[', '"', '\''])
+            .trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '>', ']', '"', '\'']);
+        if !(candidate.starts_with("http://") || candidate.starts_with("https://")) {
+            continue;
+        }
+
+        let Ok(url) = Url::parse(candidate) else {
+            continue;
+        };
+        if url.host_str().is_none() {
+            continue;
+        }
+
+        let url = url.to_string();
+        if !urls.contains(&url) {
+            urls.push(url);
+            if urls.len() == MAX_LINKS_PER_MESSAGE {
+                break;
+            }
+        }
+    }
+
+    urls
+}
+
+/// Collect the distinct `@` mentions in a message, in order of appearance
+///
+/// An `@` only starts a mention at the beginning of the text or after a
+/// character that can't be part of a username, so `mail@example.com` is left
+/// alone. Names are matched case-insensitively and returned lowercased;
+/// whether they resolve to anyone is up to the caller.
+pub fn extract_mentions(text: &str) -> Vec<Mention> {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut mentions: Vec<Mention> = Vec::new();
+    let mut prev: Option<char> = None;
+
+    for (i, c) in text.char_indices() {
+        let starts_mention = c == '@' && !prev.is_some_and(|p| is_name_char(p) || p == '@');
+        prev = Some(c);
+        if !starts_mention {
+            continue;
+        }
+
+        let rest = &text[i + 1..];
+        let end = rest.find(|c: char| !is_name_char(c)).unwrap_or(rest.len());
+        if end == 0 || end > MAX_MENTION_LENGTH {
+            continue;
+        }
+
+        let name = rest[..end].to_ascii_lowercase();
+        let mention = match name.as_str() {
+            "everyone" => Mention::Everyone,
+            "here" => Mention::Here,
+            _ => Mention::User(name),
+        };
+        if !mentions.contains(&mention) {
+            mentions.push(mention);
+            if mentions.len() == MAX_MENTIONS_PER_MESSAGE {
+                break;
+            }
+        }
+    }
+
+    mentions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_and_whitespace() {
+        assert!(matches!(
+            normalize_message_text(None, false, DEFAULT_MAX_MESSAGE_BYTES),
+            Err(AppError::EmptyMessage)
+        ));
+        assert!(matches!(
+            normalize_message_text(Some(" \n\t "), false, DEFAULT_MAX_MESSAGE_BYTES),
+            Err(AppError::EmptyMessage)
+        ));
+        assert_eq!(
+            normalize_message_text(Some("   "), true, DEFAULT_MAX_MESSAGE_BYTES).unwrap(),
+            None
+        );
+        assert_eq!(
+            normalize_message_text(None, true, DEFAULT_MAX_MESSAGE_BYTES).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_oversized() {
+        assert_eq!(normalize_text(&"a".repeat(16), 16).unwrap().len(), 16);
+        assert!(matches!(
+            normalize_text(&"a".repeat(17), 16),
+            Err(AppError::MessageTooLong(16))
+        ));
+        // Surrounding whitespace does not count against the limit
+        assert!(normalize_text(&format!("  {}  ", "a".repeat(16)), 16).is_ok());
+    }
+
+    #[test]
+    fn test_control_characters() {
+        assert!(matches!(
+            normalize_text("hi\u{0}there", DEFAULT_MAX_MESSAGE_BYTES),
+            Err(AppError::BadRequest(_))
+        ));
+        assert!(matches!(
+            normalize_text("bell\u{7}", DEFAULT_MAX_MESSAGE_BYTES),
+            Err(AppError::BadRequest(_))
+        ));
+        assert_eq!(
+            normalize_text("line1\r\nline2\rline3\tend", DEFAULT_MAX_MESSAGE_BYTES).unwrap(),
+            "line1\nline2\nline3\tend"
+        );
+    }
+
+    #[test]
+    fn test_mixed_normalization() {
+        // "é" as e + combining acute, next to a precomposed "é"
+        let text = "cafe\u{301} caf\u{e9}";
+        let normalized = normalize_text(text, DEFAULT_MAX_MESSAGE_BYTES).unwrap();
+        assert_eq!(normalized, "caf\u{e9} caf\u{e9}");
+
+        // Vietnamese with stacked diacritics composes to the same string
+        assert_eq!(
+            normalize_text("Vie\u{302}\u{323}t", DEFAULT_MAX_MESSAGE_BYTES).unwrap(),
+            normalize_text("Vi\u{1ec7}t", DEFAULT_MAX_MESSAGE_BYTES).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_extract_urls() {
+        let urls = extract_urls(
+            "see https://example.com/a, (http://foo.org/x?y=1) and https://example.com/a again; ftp://no",
+        );
+        assert_eq!(urls, vec!["https://example.com/a", "http://foo.org/x?y=1"]);
+        assert!(extract_urls("no links here http://").is_empty());
+    }
+
+    #[test]
+    fn test_extract_mentions() {
+        let mentions = extract_mentions("@Alice, ping @bob_2 and @alice again (@here)");
+        assert_eq!(
+            mentions,
+            vec![
+                Mention::User("alice".to_string()),
+                Mention::User("bob_2".to_string()),
+                Mention::Here,
+            ]
+        );
+        assert_eq!(extract_mentions("@EVERYONE look"), vec![Mention::Everyone]);
+    }
+
+    #[test]
+    fn test_extract_mentions_ignores_non_mentions() {
+        assert!(extract_mentions("mail me at bob@example.com").is_empty());
+        assert!(extract_mentions("@ alone, @@double, trailing @").is_empty());
+        assert!(extract_mentions(&format!("@{}", "a".repeat(51))).is_empty());
+        assert_eq!(
+            extract_mentions(&format!("@{}", "a".repeat(50))),
+            vec![Mention::User("a".repeat(50))]
+        );
+    }
+
+    #[test]
+    fn test_extract_mentions_is_capped() {
+        let text: Vec<String> = (0..60).map(|i| format!("@user{}", i)).collect();
+        assert_eq!(extract_mentions(&text.join(" ")).len(), MAX_MENTIONS_PER_MESSAGE);
+    }
+}
@@ -0,0 +1,277 @@
+/// Application-layer encryption of secrets stored in the database
+///
+/// Webhook secrets are sealed with AES-256-GCM under `SECRET_ENCRYPTION_KEY`
+/// before they are written and opened only when a delivery needs them.
+/// Stored values look like `enc:{key_id}:{base64url(nonce || ciphertext)}`;
+/// the key id picks the key to open them with, so keys can be rotated by
+/// moving the old key to `SECRET_DECRYPTION_KEYS`. Values without the
+/// `enc:` prefix are legacy plaintext and are read as they are; they get
+/// encrypted (or re-encrypted under the current key) on their next write.
+///
+/// Without a key, secrets are stored in plaintext and a warning is logged
+/// at startup. Bot tokens stay plaintext: they are looked up by value.
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::{
+    config::Config,
+    error::{AppError, AppResult},
+};
+
+/// Marks a stored value as ciphertext
+pub const ENCRYPTED_PREFIX: &str = "enc:";
+
+/// AES-256 key length
+const KEY_BYTES: usize = 32;
+
+/// Longest key id accepted
+const MAX_KEY_ID_LEN: usize = 16;
+
+/// Keys installed at startup; `None` when encryption is not configured
+static KEYS: OnceLock<Option<SecretKeys>> = OnceLock::new();
+
+/// The key new secrets are sealed with plus retired keys still accepted
+/// for opening
+pub struct SecretKeys {
+    current_id: String,
+    keys: HashMap<String, LessSafeKey>,
+}
+
+impl SecretKeys {
+    /// Parse keys given as `{key_id}:{base64 32-byte key}`
+    pub fn parse(current: &str, retired: &[String]) -> Result<Self, String> {
+        let (current_id, key) = parse_key(current)?;
+        let mut keys = HashMap::from([(current_id.clone(), key)]);
+        for entry in retired {
+            let (id, key) = parse_key(entry)?;
+            if keys.insert(id.clone(), key).is_some() {
+                return Err(format!("key id '{}' is used more than once", id));
+            }
+        }
+        Ok(Self { current_id, keys })
+    }
+
+    /// Keys from `SECRET_ENCRYPTION_KEY` and `SECRET_DECRYPTION_KEYS`, or
+    /// `None` when no encryption key is configured
+    pub fn from_config(config: &Config) -> Result<Option<Self>, String> {
+        config
+            .secret_encryption_key
+            .as_deref()
+            .map(|current| Self::parse(current, &config.secret_decryption_keys))
+            .transpose()
+    }
+
+    /// Id of the key new secrets are sealed with
+    pub fn current_key_id(&self) -> &str {
+        &self.current_id
+    }
+
+    /// Seal `plaintext` under the current key
+    pub fn encrypt(&self, plaintext: &str) -> AppResult<String> {
+        let key = &self.keys[&self.current_id];
+
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| AppError::Internal(anyhow::anyhow!("No randomness for secret nonce")))?;
+
+        let mut sealed = plaintext.as_bytes().to_vec();
+        key.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::empty(),
+            &mut sealed,
+        )
+        .map_err(|_| AppError::Internal(anyhow::anyhow!("Failed to encrypt secret")))?;
+
+        let mut payload = nonce.to_vec();
+        payload.extend_from_slice(&sealed);
+        Ok(format!(
+            "{}{}:{}",
+            ENCRYPTED_PREFIX,
+            self.current_id,
+            URL_SAFE_NO_PAD.encode(payload)
+        ))
+    }
+
+    /// Open a stored value; legacy plaintext is returned as it is
+    pub fn decrypt(&self, stored: &str) -> AppResult<String> {
+        let Some(rest) = stored.strip_prefix(ENCRYPTED_PREFIX) else {
+            return Ok(stored.to_string());
+        };
+        let invalid = |reason: &str| {
+            AppError::Internal(anyhow::anyhow!("Cannot decrypt stored secret: {}", reason))
+        };
+
+        let (key_id, encoded) = rest
+            .split_once(':')
+            .ok_or_else(|| invalid("malformed value"))?;
+        let key = self
+            .keys
+            .get(key_id)
+            .ok_or_else(|| invalid(&format!("unknown key id '{}'", key_id)))?;
+        let mut payload = URL_SAFE_NO_PAD
+            .decode(encoded)
+            .map_err(|_| invalid("malformed value"))?;
+        if payload.len() < NONCE_LEN {
+            return Err(invalid("malformed value"));
+        }
+
+        let (nonce, sealed) = payload.split_at_mut(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| invalid("bad nonce"))?;
+        let plaintext = key
+            .open_in_place(nonce, Aad::empty(), sealed)
+            .map_err(|_| invalid("authentication failed"))?;
+
+        String::from_utf8(plaintext.to_vec()).map_err(|_| invalid("not UTF-8"))
+    }
+}
+
+fn parse_key(entry: &str) -> Result<(String, LessSafeKey), String> {
+    let (id, encoded) = entry
+        .trim()
+        .split_once(':')
+        .ok_or("keys must look like {key_id}:{base64 key}")?;
+    if id.is_empty()
+        || id.len() > MAX_KEY_ID_LEN
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(format!(
+            "key id must be 1-{} letters, digits, '_' or '-'",
+            MAX_KEY_ID_LEN
+        ));
+    }
+
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|_| format!("key '{}' is not valid base64", id))?;
+    if bytes.len() != KEY_BYTES {
+        return Err(format!(
+            "key '{}' must be {} bytes (got {})",
+            id,
+            KEY_BYTES,
+            bytes.len()
+        ));
+    }
+    let key = UnboundKey::new(&AES_256_GCM, &bytes)
+        .map_err(|_| format!("key '{}' is not a valid AES-256 key", id))?;
+
+    Ok((id.to_string(), LessSafeKey::new(key)))
+}
+
+/// Install the process-wide keys; call once at startup
+pub fn install(keys: Option<SecretKeys>) {
+    match &keys {
+        Some(keys) => tracing::info!(
+            "Stored secrets are encrypted with key '{}'",
+            keys.current_key_id()
+        ),
+        None => tracing::warn!(
+            "SECRET_ENCRYPTION_KEY is not set; webhook secrets are stored in plaintext"
+        ),
+    }
+    if KEYS.set(keys).is_err() {
+        tracing::warn!("Secret encryption keys were already installed");
+    }
+}
+
+fn installed_keys() -> Option<&'static SecretKeys> {
+    KEYS.get().and_then(Option::as_ref)
+}
+
+/// Prepare a secret for storage: sealed when a key is configured,
+/// plaintext otherwise
+pub fn encrypt_secret(plaintext: &str) -> AppResult<String> {
+    match installed_keys() {
+        Some(keys) => keys.encrypt(plaintext),
+        None => Ok(plaintext.to_string()),
+    }
+}
+
+/// Recover a stored secret; encrypted values need the key they were
+/// sealed with
+pub fn decrypt_secret(stored: &str) -> AppResult<String> {
+    match installed_keys() {
+        Some(keys) => keys.decrypt(stored),
+        None if stored.starts_with(ENCRYPTED_PREFIX) => Err(AppError::Internal(anyhow::anyhow!(
+            "Stored secret is encrypted but SECRET_ENCRYPTION_KEY is not set"
+        ))),
+        None => Ok(stored.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: &str, byte: u8) -> String {
+        format!(
+            "{}:{}",
+            id,
+            base64::engine::general_purpose::STANDARD.encode([byte; KEY_BYTES])
+        )
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let keys = SecretKeys::parse(&key("k1", 1), &[]).unwrap();
+        let stored = keys.encrypt("hook-secret_123").unwrap();
+
+        assert!(stored.starts_with("enc:k1:"));
+        assert!(!stored.contains("hook-secret_123"));
+        assert_eq!(keys.decrypt(&stored).unwrap(), "hook-secret_123");
+
+        // Fresh nonce every time
+        assert_ne!(keys.encrypt("hook-secret_123").unwrap(), stored);
+    }
+
+    #[test]
+    fn test_plaintext_passes_through() {
+        let keys = SecretKeys::parse(&key("k1", 1), &[]).unwrap();
+        assert_eq!(keys.decrypt("legacy-secret").unwrap(), "legacy-secret");
+    }
+
+    #[test]
+    fn test_rotation_keeps_old_values_readable() {
+        let old = SecretKeys::parse(&key("k1", 1), &[]).unwrap();
+        let stored = old.encrypt("secret").unwrap();
+
+        let rotated = SecretKeys::parse(&key("k2", 2), &[key("k1", 1)]).unwrap();
+        assert_eq!(rotated.decrypt(&stored).unwrap(), "secret");
+        assert!(rotated.encrypt("secret").unwrap().starts_with("enc:k2:"));
+
+        // Once the old key is dropped its values can no longer be opened
+        let dropped = SecretKeys::parse(&key("k2", 2), &[]).unwrap();
+        assert!(dropped.decrypt(&stored).is_err());
+    }
+
+    #[test]
+    fn test_tampered_value_is_rejected() {
+        let keys = SecretKeys::parse(&key("k1", 1), &[]).unwrap();
+        let stored = keys.encrypt("secret").unwrap();
+        let mut tampered = stored.clone().into_bytes();
+        let last = tampered.len() - 1;
+        tampered[last] = if tampered[last] == b'A' { b'B' } else { b'A' };
+
+        assert!(keys.decrypt(&String::from_utf8(tampered).unwrap()).is_err());
+        assert!(keys.decrypt("enc:k1:").is_err());
+        assert!(keys.decrypt("enc:k1").is_err());
+
+        let other = SecretKeys::parse(&key("k1", 9), &[]).unwrap();
+        assert!(other.decrypt(&stored).is_err());
+    }
+
+    #[test]
+    fn test_invalid_keys_are_rejected() {
+        assert!(SecretKeys::parse("no-separator", &[]).is_err());
+        assert!(SecretKeys::parse(":AAAA", &[]).is_err());
+        assert!(SecretKeys::parse("k1:not base64!", &[]).is_err());
+        assert!(SecretKeys::parse("k1:AAAA", &[]).is_err());
+        assert!(SecretKeys::parse(&key("k:1", 1), &[]).is_err());
+        assert!(SecretKeys::parse(&key("k1", 1), &[key("k1", 2)]).is_err());
+    }
+}
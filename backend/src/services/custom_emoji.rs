@@ -0,0 +1,444 @@
+/// Custom Emoji Service - uploaded emoji images referenced as `:shortcode:`
+///
+/// This module provides:
+/// - Upload and deletion of emoji scoped to one chat, or global ones added by
+///   server admins
+/// - Resolution of `:shortcode:` and emoji ids for reactions, limited to the
+///   emoji usable in the reacting chat
+/// - Metadata for the custom emoji a message uses, so clients can render them
+///
+/// A shortcode resolves to at most one emoji in any chat: it is unique within
+/// a chat and may not shadow (or be shadowed by) a global emoji.
+use std::collections::HashSet;
+use uuid::Uuid;
+
+use crate::{
+    db::Database,
+    error::{AppError, AppResult},
+    models::{ChatParticipant, ChatType, CustomEmoji, CustomEmojiResponse, Reaction},
+    services::{attachment::UPLOAD_DIR, AttachmentService, ChatService},
+    AppState,
+};
+
+/// Shortest and longest shortcode, without the surrounding colons
+pub const MIN_SHORTCODE_LEN: usize = 2;
+pub const MAX_SHORTCODE_LEN: usize = 32;
+
+/// Longest standard emoji accepted as a reaction, in characters (covers
+/// skin tones and ZWJ sequences)
+const MAX_UNICODE_EMOJI_CHARS: usize = 10;
+
+/// Who may add custom emoji to a group chat (`chats.custom_emoji_policy`)
+pub const POLICY_ADMINS: &str = "admins";
+pub const POLICY_MEMBERS: &str = "members";
+
+pub struct CustomEmojiService;
+
+fn is_shortcode(name: &str) -> bool {
+    (MIN_SHORTCODE_LEN..=MAX_SHORTCODE_LEN).contains(&name.len())
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_')
+}
+
+/// Check a shortcode given without colons
+pub fn validate_shortcode(name: &str) -> AppResult<()> {
+    if is_shortcode(name) {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest(format!(
+            "Shortcode must be {}-{} lowercase letters, digits or underscores",
+            MIN_SHORTCODE_LEN, MAX_SHORTCODE_LEN
+        )))
+    }
+}
+
+/// The shortcode in a `:shortcode:` reaction, or `None` for other emoji
+pub fn shortcode_ref(emoji: &str) -> Option<&str> {
+    emoji
+        .strip_prefix(':')
+        .and_then(|rest| rest.strip_suffix(':'))
+        .filter(|name| is_shortcode(name))
+}
+
+/// Distinct `:shortcode:` references in message text, in order of appearance
+pub fn shortcodes_in_text(text: &str) -> Vec<&str> {
+    let mut seen = HashSet::new();
+    let mut found = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find(':') {
+        let after = &rest[start + 1..];
+        match after.find(':') {
+            Some(end) if is_shortcode(&after[..end]) => {
+                if seen.insert(&after[..end]) {
+                    found.push(&after[..end]);
+                }
+                rest = &after[end + 1..];
+            }
+            // The closing colon may open the next reference (`a :b:`)
+            Some(_) => rest = after,
+            None => break,
+        }
+    }
+
+    found
+}
+
+/// Check a standard emoji reaction
+fn validate_unicode_emoji(emoji: &str) -> AppResult<()> {
+    let chars = emoji.chars().count();
+    if chars == 0
+        || chars > MAX_UNICODE_EMOJI_CHARS
+        || emoji.chars().any(|c| c.is_ascii_alphanumeric() || c.is_whitespace())
+    {
+        return Err(AppError::BadRequest("Invalid reaction emoji".to_string()));
+    }
+    Ok(())
+}
+
+/// Extension and MIME type of a supported emoji image, from its magic bytes
+///
+/// SVG is deliberately not accepted: it can carry scripts.
+fn sniff_image(data: &[u8]) -> Option<(&'static str, &'static str)> {
+    if data.starts_with(&[0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A]) {
+        Some(("png", "image/png"))
+    } else if data.starts_with(b"GIF8") {
+        Some(("gif", "image/gif"))
+    } else if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some(("jpg", "image/jpeg"))
+    } else if data.len() > 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        Some(("webp", "image/webp"))
+    } else {
+        None
+    }
+}
+
+impl CustomEmojiService {
+    /// Resolve the emoji of a reaction in `chat_id`
+    ///
+    /// `:shortcode:` must name a custom emoji usable in the chat and returns
+    /// it; anything else must be a standard emoji and returns `None`.
+    pub async fn resolve_reaction(
+        db: &Database,
+        chat_id: Uuid,
+        emoji: &str,
+    ) -> AppResult<Option<CustomEmoji>> {
+        let Some(shortcode) = shortcode_ref(emoji) else {
+            validate_unicode_emoji(emoji)?;
+            return Ok(None);
+        };
+
+        let custom: Option<CustomEmoji> = sqlx::query_as(
+            r#"
+            SELECT * FROM custom_emoji
+            WHERE shortcode = $1 AND (chat_id = $2 OR chat_id IS NULL)
+            "#,
+        )
+        .bind(shortcode)
+        .bind(chat_id)
+        .fetch_optional(&db.pool)
+        .await?;
+
+        custom
+            .map(Some)
+            .ok_or_else(|| AppError::NotFound(format!("Custom emoji :{}: not found", shortcode)))
+    }
+
+    /// A custom emoji by id, if it can be used in `chat_id`
+    pub async fn usable(db: &Database, chat_id: Uuid, emoji_id: Uuid) -> AppResult<CustomEmoji> {
+        sqlx::query_as(
+            "SELECT * FROM custom_emoji WHERE id = $1 AND (chat_id = $2 OR chat_id IS NULL)",
+        )
+        .bind(emoji_id)
+        .bind(chat_id)
+        .fetch_optional(&db.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Custom emoji not found".to_string()))
+    }
+
+    /// Custom emoji a message uses in its reactions or text
+    pub async fn for_message(
+        db: &Database,
+        chat_id: Uuid,
+        reactions: &[Reaction],
+        text: Option<&str>,
+    ) -> AppResult<Vec<CustomEmojiResponse>> {
+        let ids: Vec<Uuid> = reactions.iter().filter_map(|r| r.custom_emoji_id).collect();
+        let shortcodes: Vec<&str> = text.map(shortcodes_in_text).unwrap_or_default();
+        if ids.is_empty() && shortcodes.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let emoji: Vec<CustomEmoji> = sqlx::query_as(
+            r#"
+            SELECT * FROM custom_emoji
+            WHERE id = ANY($1)
+               OR (shortcode = ANY($2) AND (chat_id = $3 OR chat_id IS NULL))
+            ORDER BY shortcode
+            "#,
+        )
+        .bind(&ids)
+        .bind(&shortcodes)
+        .bind(chat_id)
+        .fetch_all(&db.pool)
+        .await?;
+
+        Ok(emoji.iter().map(CustomEmoji::to_response).collect())
+    }
+
+    /// Custom emoji usable in a chat: its own, then the global ones
+    pub async fn list_for_chat(
+        db: &Database,
+        chat_id: Uuid,
+        user_id: Uuid,
+    ) -> AppResult<Vec<CustomEmoji>> {
+        if !ChatService::is_participant(db, chat_id, user_id).await? {
+            return Err(AppError::AccessDenied);
+        }
+
+        let emoji = sqlx::query_as(
+            r#"
+            SELECT * FROM custom_emoji
+            WHERE chat_id = $1 OR chat_id IS NULL
+            ORDER BY chat_id IS NULL, shortcode
+            "#,
+        )
+        .bind(chat_id)
+        .fetch_all(&db.pool)
+        .await?;
+
+        Ok(emoji)
+    }
+
+    /// Global custom emoji
+    pub async fn list_global(db: &Database) -> AppResult<Vec<CustomEmoji>> {
+        let emoji =
+            sqlx::query_as("SELECT * FROM custom_emoji WHERE chat_id IS NULL ORDER BY shortcode")
+                .fetch_all(&db.pool)
+                .await?;
+        Ok(emoji)
+    }
+
+    /// The caller's participation in `chat_id`, if they may add emoji to it
+    ///
+    /// Group chats follow `custom_emoji_policy`; in private and bot chats
+    /// either party may add them.
+    async fn ensure_can_add(
+        db: &Database,
+        chat_id: Uuid,
+        user_id: Uuid,
+    ) -> AppResult<ChatParticipant> {
+        let participant: ChatParticipant = sqlx::query_as(
+            "SELECT * FROM chat_participants WHERE chat_id = $1 AND user_id = $2",
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .fetch_optional(&db.pool)
+        .await?
+        .ok_or(AppError::AccessDenied)?;
+
+        let (chat_type, policy): (String, String) =
+            sqlx::query_as("SELECT type, custom_emoji_policy FROM chats WHERE id = $1")
+                .bind(chat_id)
+                .fetch_optional(&db.pool)
+                .await?
+                .ok_or(AppError::ChatNotFound)?;
+
+        let has_admins = ChatType::parse(&chat_type).is_some_and(ChatType::has_admins);
+        if has_admins && policy == POLICY_ADMINS && participant.role != "admin" {
+            return Err(AppError::Forbidden(
+                "Only chat admins can add custom emoji".to_string(),
+            ));
+        }
+
+        Ok(participant)
+    }
+
+    /// Store an uploaded emoji image for `chat_id`, or globally when `None`
+    ///
+    /// Global emoji are only added through the admin API; callers check that.
+    pub async fn create(
+        state: &AppState,
+        chat_id: Option<Uuid>,
+        user_id: Uuid,
+        shortcode: &str,
+        data: &[u8],
+    ) -> AppResult<CustomEmoji> {
+        validate_shortcode(shortcode)?;
+        if let Some(chat_id) = chat_id {
+            Self::ensure_can_add(&state.db, chat_id, user_id).await?;
+        }
+
+        if data.len() > state.config.custom_emoji_max_bytes {
+            return Err(AppError::FileTooLarge);
+        }
+        let (extension, mime_type) = sniff_image(data).ok_or(AppError::InvalidFileType)?;
+
+        // A chat emoji clashes with its chat's and the global ones; a global
+        // emoji clashes with every chat's
+        let taken: bool = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM custom_emoji
+                WHERE shortcode = $1
+                  AND ($2::uuid IS NULL OR chat_id = $2 OR chat_id IS NULL)
+            )
+            "#,
+        )
+        .bind(shortcode)
+        .bind(chat_id)
+        .fetch_one(&state.db.pool)
+        .await?;
+        if taken {
+            return Err(AppError::BadRequest(format!(
+                "Shortcode :{}: is already in use",
+                shortcode
+            )));
+        }
+
+        let id = Uuid::new_v4();
+        let stored_name = format!("{}.{}", id, extension);
+        tokio::fs::create_dir_all(UPLOAD_DIR)
+            .await
+            .map_err(|e| AppError::Internal(anyhow::anyhow!("Failed to create upload directory: {}", e)))?;
+        tokio::fs::write(format!("{}/{}", UPLOAD_DIR, stored_name), data)
+            .await
+            .map_err(|e| AppError::Internal(anyhow::anyhow!("Failed to write emoji: {}", e)))?;
+
+        let base_url = state.config.base_url.as_deref().unwrap_or("http://localhost:3000");
+        let url = format!("{}/uploads/{}", base_url, stored_name);
+
+        let inserted = sqlx::query_as(
+            r#"
+            INSERT INTO custom_emoji (id, chat_id, shortcode, stored_name, url, mime_type, size_bytes, created_by)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING *
+            "#,
+        )
+        .bind(id)
+        .bind(chat_id)
+        .bind(shortcode)
+        .bind(&stored_name)
+        .bind(&url)
+        .bind(mime_type)
+        .bind(data.len() as i32)
+        .bind(user_id)
+        .fetch_one(&state.db.pool)
+        .await;
+
+        match inserted {
+            Ok(emoji) => Ok(emoji),
+            Err(e) => {
+                AttachmentService::spawn_remove_files(vec![stored_name]);
+                match e {
+                    // Lost a race for the shortcode
+                    sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+                        Err(AppError::BadRequest(format!(
+                            "Shortcode :{}: is already in use",
+                            shortcode
+                        )))
+                    }
+                    e => Err(e.into()),
+                }
+            }
+        }
+    }
+
+    /// Delete one of a chat's custom emoji, with the reactions using it
+    ///
+    /// The uploader may delete their own emoji; chat admins may delete any.
+    pub async fn delete_from_chat(
+        db: &Database,
+        chat_id: Uuid,
+        emoji_id: Uuid,
+        user_id: Uuid,
+    ) -> AppResult<()> {
+        let participant: ChatParticipant = sqlx::query_as(
+            "SELECT * FROM chat_participants WHERE chat_id = $1 AND user_id = $2",
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .fetch_optional(&db.pool)
+        .await?
+        .ok_or(AppError::AccessDenied)?;
+
+        let emoji: CustomEmoji =
+            sqlx::query_as("SELECT * FROM custom_emoji WHERE id = $1 AND chat_id = $2")
+                .bind(emoji_id)
+                .bind(chat_id)
+                .fetch_optional(&db.pool)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Custom emoji not found".to_string()))?;
+
+        if participant.role != "admin" && emoji.created_by != Some(user_id) {
+            return Err(AppError::Forbidden(
+                "Only chat admins can delete other members' emoji".to_string(),
+            ));
+        }
+
+        Self::delete(db, emoji).await
+    }
+
+    /// Delete a global custom emoji (admin API)
+    pub async fn delete_global(db: &Database, emoji_id: Uuid) -> AppResult<()> {
+        let emoji: CustomEmoji =
+            sqlx::query_as("SELECT * FROM custom_emoji WHERE id = $1 AND chat_id IS NULL")
+                .bind(emoji_id)
+                .fetch_optional(&db.pool)
+                .await?
+                .ok_or_else(|| AppError::NotFound("Custom emoji not found".to_string()))?;
+
+        Self::delete(db, emoji).await
+    }
+
+    async fn delete(db: &Database, emoji: CustomEmoji) -> AppResult<()> {
+        sqlx::query("DELETE FROM custom_emoji WHERE id = $1")
+            .bind(emoji.id)
+            .execute(&db.pool)
+            .await?;
+
+        AttachmentService::spawn_remove_files(vec![emoji.stored_name]);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shortcode_ref() {
+        assert_eq!(shortcode_ref(":party_parrot:"), Some("party_parrot"));
+        assert_eq!(shortcode_ref("👍"), None);
+        assert_eq!(shortcode_ref(":Party:"), None);
+        assert_eq!(shortcode_ref(":a:"), None);
+        assert_eq!(shortcode_ref("::"), None);
+    }
+
+    #[test]
+    fn test_shortcodes_in_text() {
+        assert_eq!(
+            shortcodes_in_text("hi :wave: at 10:30 :wave: and :cat_2::dog:"),
+            vec!["wave", "cat_2", "dog"]
+        );
+        assert!(shortcodes_in_text("no emoji: here").is_empty());
+    }
+
+    #[test]
+    fn test_validate_unicode_emoji() {
+        assert!(validate_unicode_emoji("👍").is_ok());
+        assert!(validate_unicode_emoji("👍🏽").is_ok());
+        assert!(validate_unicode_emoji("").is_err());
+        assert!(validate_unicode_emoji("lol").is_err());
+        assert!(validate_unicode_emoji(":lol:").is_err());
+    }
+
+    #[test]
+    fn test_sniff_image_rejects_non_images() {
+        assert_eq!(sniff_image(b"GIF89a...."), Some(("gif", "image/gif")));
+        assert_eq!(sniff_image(b"RIFF\0\0\0\0WEBPVP8 "), Some(("webp", "image/webp")));
+        assert_eq!(sniff_image(b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>"), None);
+        assert_eq!(sniff_image(b"RIFF\0\0\0\0WAVEfmt "), None);
+    }
+}
@@ -0,0 +1,273 @@
+/// Disappearing Messages Service
+///
+/// Messages sent in a chat with a disappearing timer get a `delete_at`
+/// timestamp. A background reaper periodically deletes expired messages,
+/// removes uploaded files that are no longer referenced, and notifies
+/// participants with `message_deleted`.
+
+use std::collections::{hash_map::Entry, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::{db::Database, error::AppResult, services::ChatService, ws::events::ServerEvent, AppState};
+
+/// Maximum number of messages deleted per reaper pass
+const REAP_BATCH_SIZE: i64 = 500;
+
+/// Directory uploaded files are stored in
+const UPLOADS_DIR: &str = "uploads";
+
+/// A message removed by the reaper
+#[derive(Debug, Clone)]
+pub struct ExpiredMessage {
+    pub id: Uuid,
+    pub chat_id: Uuid,
+    pub attachment_urls: Vec<String>,
+}
+
+pub struct DisappearingMessageService;
+
+impl DisappearingMessageService {
+    /// Spawn the background reaper. Runs until the process exits.
+    pub fn spawn_reaper(state: Arc<AppState>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = Self::run_once(&state).await {
+                    tracing::error!("Disappearing message reaper failed: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Delete one batch of expired messages, clean up their files and notify clients.
+    pub async fn run_once(state: &AppState) -> AppResult<usize> {
+        let expired = Self::delete_expired(&state.db, REAP_BATCH_SIZE).await?;
+        if expired.is_empty() {
+            return Ok(0);
+        }
+
+        tracing::debug!("Reaped {} expired messages", expired.len());
+
+        for message in &expired {
+            for url in &message.attachment_urls {
+                // The messages are already gone; a leftover file must not
+                // keep the rest of the batch from being announced
+                if let Err(e) = Self::remove_upload_if_unreferenced(&state.db, url).await {
+                    tracing::warn!("Failed to clean up expired upload {}: {}", url, e);
+                }
+            }
+        }
+
+        let mut participants: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+        for message in &expired {
+            let ids = match participants.entry(message.chat_id) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    entry.insert(ChatService::get_participant_ids(&state.db, message.chat_id).await?)
+                }
+            };
+
+            let event = ServerEvent::MessageDeleted {
+                chat_id: message.chat_id,
+                message_id: message.id,
+            };
+            state
+                .ws_manager
+                .broadcast_to_chat_participants(ids, event, None)
+                .await;
+        }
+
+        Ok(expired.len())
+    }
+
+    /// Delete up to `limit` messages whose `delete_at` has passed.
+    ///
+    /// Attachment rows cascade with the message; their URLs are returned so the
+    /// stored files can be cleaned up. Thread roots that still have replies
+    /// are tombstoned like a deleted root instead, so the replies stay
+    /// readable; the tombstone goes once its last reply has expired.
+    pub async fn delete_expired(db: &Database, limit: i64) -> AppResult<Vec<ExpiredMessage>> {
+        let mut tx = db.pool.begin().await?;
+
+        // The outer SELECT sees the pre-delete snapshot, so attachments are still visible
+        let deleted: Vec<(Uuid, Uuid, Option<Uuid>, Option<String>)> = sqlx::query_as(
+            r#"
+            WITH expired AS (
+                DELETE FROM messages
+                WHERE id IN (
+                    SELECT id FROM messages
+                    WHERE delete_at IS NOT NULL AND delete_at <= NOW() AND reply_count = 0
+                    ORDER BY delete_at
+                    LIMIT $1
+                )
+                RETURNING id, chat_id, thread_root_id
+            )
+            SELECT e.id, e.chat_id, e.thread_root_id, a.url
+            FROM expired e
+            LEFT JOIN attachments a ON a.message_id = e.id
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let tombstoned: Vec<(Uuid, Uuid, Option<String>)> = sqlx::query_as(
+            r#"
+            WITH roots AS (
+                UPDATE messages
+                SET text = NULL, is_pinned = false, deleted_at = NOW(), updated_at = NOW()
+                WHERE id IN (
+                    SELECT id FROM messages
+                    WHERE delete_at IS NOT NULL AND delete_at <= NOW()
+                      AND reply_count > 0 AND deleted_at IS NULL
+                    ORDER BY delete_at
+                    LIMIT $1
+                )
+                RETURNING id, chat_id
+            )
+            SELECT r.id, r.chat_id, a.url
+            FROM roots r
+            LEFT JOIN attachments a ON a.message_id = r.id
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let tombstone_ids: Vec<Uuid> = tombstoned.iter().map(|(id, _, _)| *id).collect();
+        if !tombstone_ids.is_empty() {
+            for table in [
+                "attachments",
+                "message_entities",
+                "link_previews",
+                "reactions",
+                "message_mentions",
+            ] {
+                sqlx::query(&format!("DELETE FROM {} WHERE message_id = ANY($1)", table))
+                    .bind(&tombstone_ids)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            sqlx::query("UPDATE messages SET reply_snippet = NULL WHERE reply_to_id = ANY($1)")
+                .bind(&tombstone_ids)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        // Expired thread replies no longer count towards their root
+        let mut roots: Vec<Uuid> = Vec::new();
+        let mut expired: Vec<ExpiredMessage> = Vec::new();
+        for (id, chat_id, thread_root_id, url) in deleted {
+            if !expired.iter().any(|m| m.id == id) {
+                roots.extend(thread_root_id);
+            }
+            Self::collect(&mut expired, id, chat_id, url);
+        }
+        for (id, chat_id, url) in tombstoned {
+            Self::collect(&mut expired, id, chat_id, url);
+        }
+        if !roots.is_empty() {
+            sqlx::query(
+                r#"
+                UPDATE messages m
+                SET reply_count = GREATEST(m.reply_count - r.removed, 0)
+                FROM (
+                    SELECT root_id, COUNT(*)::integer AS removed
+                    FROM UNNEST($1::uuid[]) AS root_id
+                    GROUP BY root_id
+                ) r
+                WHERE m.id = r.root_id
+                "#,
+            )
+            .bind(&roots)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(expired)
+    }
+
+    /// Add an attachment row of a reaped message to `expired`
+    fn collect(expired: &mut Vec<ExpiredMessage>, id: Uuid, chat_id: Uuid, url: Option<String>) {
+        let index = match expired.iter().position(|m| m.id == id) {
+            Some(index) => index,
+            None => {
+                expired.push(ExpiredMessage {
+                    id,
+                    chat_id,
+                    attachment_urls: Vec::new(),
+                });
+                expired.len() - 1
+            }
+        };
+        if let Some(url) = url {
+            expired[index].attachment_urls.push(url);
+        }
+    }
+
+    /// Remove an uploaded file unless another attachment (e.g. a forward) still uses it.
+    pub(crate) async fn remove_upload_if_unreferenced(db: &Database, url: &str) -> AppResult<()> {
+        let Some(file_name) = upload_file_name(url) else {
+            return Ok(());
+        };
+
+        let still_used: Option<(i32,)> = sqlx::query_as("SELECT 1 FROM attachments WHERE url = $1 LIMIT 1")
+            .bind(url)
+            .fetch_optional(&db.pool)
+            .await?;
+        if still_used.is_some() {
+            return Ok(());
+        }
+
+        let path = Path::new(UPLOADS_DIR).join(file_name);
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => tracing::debug!("Removed expired upload {}", path.display()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::warn!("Failed to remove expired upload {}: {}", path.display(), e),
+        }
+
+        Ok(())
+    }
+}
+
+/// Extract the stored file name from an upload URL (`{base}/uploads/{name}`).
+///
+/// Returns None for external URLs and anything that isn't a plain file name,
+/// so a crafted attachment URL can never point outside the uploads directory.
+pub(crate) fn upload_file_name(url: &str) -> Option<&str> {
+    let (_, name) = url.rsplit_once("/uploads/")?;
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+    valid.then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upload_file_name() {
+        assert_eq!(
+            upload_file_name("http://localhost:3000/uploads/5f0c.png"),
+            Some("5f0c.png")
+        );
+        assert_eq!(upload_file_name("/uploads/abc-123.pdf"), Some("abc-123.pdf"));
+    }
+
+    #[test]
+    fn test_upload_file_name_rejects_unsafe_paths() {
+        assert_eq!(upload_file_name("https://cdn.example.com/image.png"), None);
+        assert_eq!(upload_file_name("http://host/uploads/../.env"), None);
+        assert_eq!(upload_file_name("http://host/uploads/cache/user/file.png"), None);
+        assert_eq!(upload_file_name("http://host/uploads/"), None);
+        assert_eq!(upload_file_name("http://host/uploads/.hidden"), None);
+    }
+}
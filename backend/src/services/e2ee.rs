@@ -0,0 +1,206 @@
+/// Key distribution for end-to-end encrypted chats.
+///
+/// The server never encrypts or decrypts anything: each participant
+/// publishes a public prekey bundle (identity key, signed prekey and a
+/// batch of one-time prekeys) per chat, and the others fetch it to open an
+/// E2EE session. Every fetch hands out one one-time prekey, so no two
+/// senders get the same one.
+use uuid::Uuid;
+
+use crate::{
+    db::Database,
+    error::{AppError, AppResult},
+    services::ChatService,
+};
+
+/// Longest key or signature accepted, in base64 characters
+pub const MAX_KEY_CHARS: usize = 1024;
+
+/// Most one-time prekeys a bundle may carry
+pub const MAX_ONE_TIME_PREKEYS: usize = 100;
+
+/// A participant's public keys as they publish them
+#[derive(Debug, Clone)]
+pub struct KeyBundleInput {
+    pub identity_key: String,
+    pub signed_prekey: String,
+    pub prekey_signature: String,
+    pub one_time_prekeys: Vec<String>,
+}
+
+/// A bundle fetched to start a session with its owner
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct FetchedKeyBundle {
+    pub identity_key: String,
+    pub signed_prekey: String,
+    pub prekey_signature: String,
+    /// `None` once the owner has run out; sessions then start without one
+    pub one_time_prekey: Option<String>,
+}
+
+pub struct E2eeKeyService;
+
+impl E2eeKeyService {
+    /// Publish the user's bundle for a chat, replacing any earlier one.
+    /// Returns how many one-time prekeys are on offer.
+    pub async fn publish(
+        db: &Database,
+        chat_id: Uuid,
+        user_id: Uuid,
+        bundle: KeyBundleInput,
+    ) -> AppResult<usize> {
+        validate_bundle(&bundle)?;
+        if !ChatService::is_participant(db, chat_id, user_id).await? {
+            return Err(AppError::AccessDenied);
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO chat_key_bundles
+                (chat_id, user_id, identity_key, signed_prekey, prekey_signature, one_time_prekeys)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (chat_id, user_id) DO UPDATE SET
+                identity_key = EXCLUDED.identity_key,
+                signed_prekey = EXCLUDED.signed_prekey,
+                prekey_signature = EXCLUDED.prekey_signature,
+                one_time_prekeys = EXCLUDED.one_time_prekeys,
+                updated_at = NOW()
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(&bundle.identity_key)
+        .bind(&bundle.signed_prekey)
+        .bind(&bundle.prekey_signature)
+        .bind(&bundle.one_time_prekeys)
+        .execute(&db.pool)
+        .await?;
+
+        Ok(bundle.one_time_prekeys.len())
+    }
+
+    /// Fetch `owner_id`'s bundle for a chat both users are in, taking its
+    /// oldest one-time prekey
+    pub async fn fetch(
+        db: &Database,
+        chat_id: Uuid,
+        requester_id: Uuid,
+        owner_id: Uuid,
+    ) -> AppResult<FetchedKeyBundle> {
+        if requester_id == owner_id {
+            return Err(AppError::BadRequest(
+                "Fetch another participant's keys, not your own".to_string(),
+            ));
+        }
+        if !ChatService::is_participant(db, chat_id, requester_id).await? {
+            return Err(AppError::AccessDenied);
+        }
+        if !ChatService::is_participant(db, chat_id, owner_id).await? {
+            return Err(AppError::NotFound("No key bundle published".to_string()));
+        }
+
+        // The row lock makes concurrent fetches take different prekeys
+        sqlx::query_as(
+            r#"
+            WITH current AS (
+                SELECT one_time_prekeys[1] AS one_time_prekey
+                FROM chat_key_bundles
+                WHERE chat_id = $1 AND user_id = $2
+                FOR UPDATE
+            )
+            UPDATE chat_key_bundles
+            SET one_time_prekeys = one_time_prekeys[2:]
+            WHERE chat_id = $1 AND user_id = $2
+            RETURNING identity_key, signed_prekey, prekey_signature,
+                      (SELECT one_time_prekey FROM current) AS one_time_prekey
+            "#,
+        )
+        .bind(chat_id)
+        .bind(owner_id)
+        .fetch_optional(&db.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("No key bundle published".to_string()))
+    }
+}
+
+/// Check every key is non-empty base64 of sane length
+pub fn validate_bundle(bundle: &KeyBundleInput) -> AppResult<()> {
+    validate_key("identityKey", &bundle.identity_key)?;
+    validate_key("signedPrekey", &bundle.signed_prekey)?;
+    validate_key("prekeySignature", &bundle.prekey_signature)?;
+
+    if bundle.one_time_prekeys.len() > MAX_ONE_TIME_PREKEYS {
+        return Err(AppError::BadRequest(format!(
+            "At most {} one-time prekeys may be published",
+            MAX_ONE_TIME_PREKEYS
+        )));
+    }
+    for key in &bundle.one_time_prekeys {
+        validate_key("oneTimePrekeys", key)?;
+    }
+    Ok(())
+}
+
+fn validate_key(field: &str, key: &str) -> AppResult<()> {
+    let is_base64 = key
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '-' | '_' | '='));
+    if key.is_empty() || key.len() > MAX_KEY_CHARS || !is_base64 {
+        return Err(AppError::BadRequest(format!(
+            "{} must be base64, at most {} characters",
+            field, MAX_KEY_CHARS
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundle() -> KeyBundleInput {
+        KeyBundleInput {
+            identity_key: "BQx1Yi+/abc=".to_string(),
+            signed_prekey: "c2lnbmVk".to_string(),
+            prekey_signature: "c2ln_-".to_string(),
+            one_time_prekeys: vec!["b3RrMQ==".to_string(), "b3RrMg==".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_valid_bundle() {
+        assert!(validate_bundle(&bundle()).is_ok());
+        let no_prekeys = KeyBundleInput {
+            one_time_prekeys: Vec::new(),
+            ..bundle()
+        };
+        assert!(validate_bundle(&no_prekeys).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_bundles_are_rejected() {
+        let empty_identity = KeyBundleInput {
+            identity_key: String::new(),
+            ..bundle()
+        };
+        let not_base64 = KeyBundleInput {
+            signed_prekey: "not base64!".to_string(),
+            ..bundle()
+        };
+        let too_long = KeyBundleInput {
+            prekey_signature: "A".repeat(MAX_KEY_CHARS + 1),
+            ..bundle()
+        };
+        let too_many = KeyBundleInput {
+            one_time_prekeys: vec!["AAAA".to_string(); MAX_ONE_TIME_PREKEYS + 1],
+            ..bundle()
+        };
+
+        for invalid in [empty_identity, not_base64, too_long, too_many] {
+            assert!(matches!(
+                validate_bundle(&invalid),
+                Err(AppError::BadRequest(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,524 @@
+/// Chat Export Service
+///
+/// Writes a chat's message history to a downloadable archive for data
+/// portability. Exports are requested by a participant and produced by a
+/// background job, one page of messages at a time, so a large chat never has
+/// to fit in memory:
+/// - without media the archive is `messages.jsonl`, one message per line,
+///   attachments listed by URL
+/// - with media it is a zip holding `messages.jsonl` plus the attachment
+///   files under `media/`, streamed from disk into the archive
+///
+/// Each user has one export in progress at a time and `EXPORT_DAILY_LIMIT`
+/// per 24 hours. Finished archives are removed after `EXPORT_TTL_HOURS`.
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use crate::{
+    db::Database,
+    error::{AppError, AppResult},
+    models::{Attachment, ChatExport, ExportStatus},
+    services::{attachment::UPLOAD_DIR, disappearing::upload_file_name, ChatService},
+    AppState,
+};
+
+/// Directory finished archives are written to
+pub const EXPORT_DIR: &str = "exports";
+
+/// How often expired archives are removed
+pub const EXPORT_SWEEP_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Messages read per query while writing an archive
+const EXPORT_PAGE_SIZE: i64 = 500;
+
+/// Exports still pending or running after this long were interrupted (the
+/// process exited mid-job) and are marked failed
+const STALE_EXPORT_HOURS: i32 = 6;
+
+/// Expired and failed export records are deleted after this long
+const EXPORT_RECORD_DAYS: i32 = 30;
+
+/// One line of `messages.jsonl`
+#[derive(Debug, Serialize, sqlx::FromRow)]
+struct ExportedMessage {
+    id: Uuid,
+    #[serde(rename = "senderId")]
+    sender_id: Uuid,
+    #[serde(rename = "senderType")]
+    sender_type: String,
+    #[serde(rename = "senderName")]
+    sender_name: String,
+    text: Option<String>,
+    #[serde(rename = "replyToId")]
+    reply_to_id: Option<Uuid>,
+    #[serde(rename = "threadRootId")]
+    thread_root_id: Option<Uuid>,
+    #[serde(rename = "isEdited")]
+    is_edited: bool,
+    #[serde(rename = "createdAt")]
+    created_at: DateTime<Utc>,
+    #[sqlx(skip)]
+    attachments: Vec<ExportedAttachment>,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportedAttachment {
+    #[serde(rename = "type")]
+    attachment_type: String,
+    name: String,
+    size: i64,
+    #[serde(rename = "mimeType")]
+    mime_type: Option<String>,
+    url: String,
+    /// Path of the bundled file inside the zip, for media exports
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<String>,
+}
+
+pub struct ExportService;
+
+impl ExportService {
+    /// Queue an export of `chat_id` for `user_id` and start it in the background
+    pub async fn request_export(
+        state: &Arc<AppState>,
+        chat_id: Uuid,
+        user_id: Uuid,
+        include_media: bool,
+    ) -> AppResult<ChatExport> {
+        if !ChatService::is_participant(&state.db, chat_id, user_id).await? {
+            return Err(AppError::AccessDenied);
+        }
+
+        let daily_limit = state.config.export_daily_limit as i64;
+        let export: Option<ChatExport> = sqlx::query_as(
+            r#"
+            INSERT INTO chat_exports (chat_id, user_id, include_media)
+            SELECT $1, $2, $3
+            WHERE NOT EXISTS (
+                SELECT 1 FROM chat_exports
+                WHERE user_id = $2 AND status IN ('pending', 'running')
+            )
+            AND ($4 = 0 OR (
+                SELECT COUNT(*) FROM chat_exports
+                WHERE user_id = $2 AND created_at > NOW() - INTERVAL '1 day'
+            ) < $4)
+            RETURNING *
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(include_media)
+        .bind(daily_limit)
+        .fetch_optional(&state.db.pool)
+        .await?;
+
+        let Some(export) = export else {
+            return Err(Self::rejection(&state.db, user_id).await?);
+        };
+
+        tokio::spawn(Self::run(
+            state.db.clone(),
+            export.clone(),
+            state.config.export_ttl_hours as i32,
+        ));
+
+        Ok(export)
+    }
+
+    /// Why a new export for `user_id` was not queued
+    async fn rejection(db: &Database, user_id: Uuid) -> AppResult<AppError> {
+        let in_progress: Option<i32> = sqlx::query_scalar(
+            "SELECT 1 FROM chat_exports WHERE user_id = $1 AND status IN ('pending', 'running')",
+        )
+        .bind(user_id)
+        .fetch_optional(&db.pool)
+        .await?;
+        if in_progress.is_some() {
+            return Ok(AppError::BadRequest(
+                "Another export is still in progress".to_string(),
+            ));
+        }
+
+        let retry_after: Option<f64> = sqlx::query_scalar(
+            r#"
+            SELECT EXTRACT(EPOCH FROM (MIN(created_at) + INTERVAL '1 day' - NOW()))::float8
+            FROM chat_exports
+            WHERE user_id = $1 AND created_at > NOW() - INTERVAL '1 day'
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(&db.pool)
+        .await?;
+
+        Ok(AppError::ExportRateLimited(
+            retry_after.unwrap_or(0.0).ceil().max(1.0) as u32,
+        ))
+    }
+
+    /// An export requested by `user_id`
+    pub async fn get_export(db: &Database, export_id: Uuid, user_id: Uuid) -> AppResult<ChatExport> {
+        sqlx::query_as("SELECT * FROM chat_exports WHERE id = $1 AND user_id = $2")
+            .bind(export_id)
+            .bind(user_id)
+            .fetch_optional(&db.pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Export not found".to_string()))
+    }
+
+    /// Path of a finished archive on disk
+    pub fn archive_path(export: &ChatExport) -> AppResult<PathBuf> {
+        match (export.status(), &export.file_name) {
+            (ExportStatus::Ready, Some(file_name)) => Ok(Path::new(EXPORT_DIR).join(file_name)),
+            (ExportStatus::Expired, _) => Err(AppError::NotFound("Export has expired".to_string())),
+            (ExportStatus::Failed, _) => Err(AppError::BadRequest("Export failed".to_string())),
+            _ => Err(AppError::BadRequest("Export is not ready yet".to_string())),
+        }
+    }
+
+    /// Write the archive and record the outcome
+    pub(crate) async fn run(db: Database, export: ChatExport, ttl_hours: i32) {
+        if let Err(e) = sqlx::query("UPDATE chat_exports SET status = 'running' WHERE id = $1")
+            .bind(export.id)
+            .execute(&db.pool)
+            .await
+        {
+            tracing::error!("Failed to start export {}: {}", export.id, e);
+            return;
+        }
+
+        let result = match Self::write_archive(&db, &export).await {
+            Ok((file_name, size, count)) => {
+                sqlx::query(
+                    r#"
+                    UPDATE chat_exports
+                    SET status = 'ready', file_name = $2, size_bytes = $3, message_count = $4,
+                        completed_at = NOW(), expires_at = NOW() + make_interval(hours => $5)
+                    WHERE id = $1
+                    "#,
+                )
+                .bind(export.id)
+                .bind(file_name)
+                .bind(size)
+                .bind(count)
+                .bind(ttl_hours)
+                .execute(&db.pool)
+                .await
+            }
+            Err(e) => {
+                tracing::error!("Export {} of chat {} failed: {}", export.id, export.chat_id, e);
+                sqlx::query(
+                    r#"
+                    UPDATE chat_exports
+                    SET status = 'failed', error = 'Export could not be written', completed_at = NOW()
+                    WHERE id = $1
+                    "#,
+                )
+                .bind(export.id)
+                .execute(&db.pool)
+                .await
+            }
+        };
+
+        if let Err(e) = result {
+            tracing::error!("Failed to record the outcome of export {}: {}", export.id, e);
+        }
+    }
+
+    /// Write the archive for `export`, returning its file name, size and
+    /// message count; partial files are removed on failure
+    async fn write_archive(db: &Database, export: &ChatExport) -> anyhow::Result<(String, i64, i32)> {
+        tokio::fs::create_dir_all(EXPORT_DIR).await?;
+
+        let jsonl_name = format!("{}.jsonl", export.id);
+        let jsonl_path = Path::new(EXPORT_DIR).join(&jsonl_name);
+
+        let written = Self::write_messages(db, export, &jsonl_path).await;
+        let (count, media) = match written {
+            Ok(written) => written,
+            Err(e) => {
+                let _ = tokio::fs::remove_file(&jsonl_path).await;
+                return Err(e);
+            }
+        };
+
+        let file_name = if export.include_media {
+            let zip_name = format!("{}.zip", export.id);
+            let zip_path = Path::new(EXPORT_DIR).join(&zip_name);
+            let (jsonl, zip) = (jsonl_path.clone(), zip_path.clone());
+            let bundled = tokio::task::spawn_blocking(move || write_zip(&jsonl, &media, &zip)).await;
+            let _ = tokio::fs::remove_file(&jsonl_path).await;
+
+            if let Err(e) = bundled.map_err(anyhow::Error::from).and_then(|r| r) {
+                let _ = tokio::fs::remove_file(&zip_path).await;
+                return Err(e);
+            }
+            zip_name
+        } else {
+            jsonl_name
+        };
+
+        let size = tokio::fs::metadata(Path::new(EXPORT_DIR).join(&file_name))
+            .await?
+            .len() as i64;
+        Ok((file_name, size, count))
+    }
+
+    /// Write every message of the chat to `path` as JSON lines, oldest first
+    ///
+    /// Returns the message count and, for media exports, the stored names of
+    /// the attachment files to bundle.
+    async fn write_messages(
+        db: &Database,
+        export: &ChatExport,
+        path: &Path,
+    ) -> anyhow::Result<(i32, Vec<String>)> {
+        let mut out = tokio::io::BufWriter::new(tokio::fs::File::create(path).await?);
+        let mut media: Vec<String> = Vec::new();
+        let mut bundled: HashSet<String> = HashSet::new();
+        let mut cursor: Option<(DateTime<Utc>, Uuid)> = None;
+        let mut count = 0;
+
+        loop {
+            let mut page: Vec<ExportedMessage> = sqlx::query_as(
+                r#"
+                SELECT m.id, m.sender_id, COALESCE(m.sender_type, 'user') AS sender_type,
+                       COALESCE(
+                           CASE WHEN m.sender_type = 'bot' THEN b.name ELSE u.name END,
+                           'Unknown'
+                       ) AS sender_name,
+                       m.text, m.reply_to_id, m.thread_root_id, m.is_edited, m.created_at
+                FROM messages m
+                LEFT JOIN users u ON u.id = m.sender_id
+                LEFT JOIN bots b ON b.id = m.sender_id
+                WHERE m.chat_id = $1 AND m.deleted_at IS NULL
+                  AND ($2::timestamptz IS NULL OR (m.created_at, m.id) > ($2, $3))
+                ORDER BY m.created_at, m.id
+                LIMIT $4
+                "#,
+            )
+            .bind(export.chat_id)
+            .bind(cursor.map(|(at, _)| at))
+            .bind(cursor.map(|(_, id)| id))
+            .bind(EXPORT_PAGE_SIZE)
+            .fetch_all(&db.pool)
+            .await?;
+
+            let ids: Vec<Uuid> = page.iter().map(|m| m.id).collect();
+            let attachments: Vec<Attachment> = sqlx::query_as(
+                "SELECT * FROM attachments WHERE message_id = ANY($1) ORDER BY created_at, id",
+            )
+            .bind(&ids)
+            .fetch_all(&db.pool)
+            .await?;
+
+            let mut by_message: HashMap<Uuid, Vec<ExportedAttachment>> = HashMap::new();
+            for attachment in attachments {
+                let mut file = None;
+                if export.include_media {
+                    if let Some(name) = upload_file_name(&attachment.url) {
+                        let on_disk = Path::new(UPLOAD_DIR).join(name);
+                        if tokio::fs::metadata(&on_disk).await.is_ok() {
+                            if bundled.insert(name.to_string()) {
+                                media.push(name.to_string());
+                            }
+                            file = Some(format!("media/{}", name));
+                        }
+                    }
+                }
+
+                by_message
+                    .entry(attachment.message_id)
+                    .or_default()
+                    .push(ExportedAttachment {
+                        attachment_type: attachment.attachment_type,
+                        name: attachment.name,
+                        size: attachment.size,
+                        mime_type: attachment.mime_type,
+                        url: attachment.url,
+                        file,
+                    });
+            }
+
+            for message in &mut page {
+                message.attachments = by_message.remove(&message.id).unwrap_or_default();
+                let mut line = serde_json::to_vec(message)?;
+                line.push(b'\n');
+                out.write_all(&line).await?;
+            }
+
+            count += page.len() as i32;
+            match page.last() {
+                Some(last) if page.len() as i64 == EXPORT_PAGE_SIZE => {
+                    cursor = Some((last.created_at, last.id));
+                }
+                _ => break,
+            }
+        }
+
+        out.flush().await?;
+        Ok((count, media))
+    }
+
+    /// Spawn the background sweeper. Runs until the process exits.
+    pub fn spawn_sweeper(state: Arc<AppState>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match Self::sweep(&state.db).await {
+                    Ok(0) => {}
+                    Ok(removed) => tracing::info!("Removed {} expired chat exports", removed),
+                    Err(e) => tracing::error!("Chat export sweeper failed: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Remove expired archives, fail interrupted jobs and drop old records
+    pub async fn sweep(db: &Database) -> AppResult<usize> {
+        let expired: Vec<String> = sqlx::query_scalar(
+            r#"
+            WITH expired AS (
+                SELECT id, file_name FROM chat_exports
+                WHERE status = 'ready' AND expires_at <= NOW()
+                FOR UPDATE SKIP LOCKED
+            )
+            UPDATE chat_exports e
+            SET status = 'expired', file_name = NULL
+            FROM expired
+            WHERE e.id = expired.id
+            RETURNING expired.file_name
+            "#,
+        )
+        .fetch_all(&db.pool)
+        .await?;
+
+        for file_name in &expired {
+            let path = Path::new(EXPORT_DIR).join(file_name);
+            match tokio::fs::remove_file(&path).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => tracing::warn!("Failed to remove export {}: {}", path.display(), e),
+            }
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE chat_exports
+            SET status = 'failed', error = 'Export was interrupted', completed_at = NOW()
+            WHERE status IN ('pending', 'running')
+              AND created_at < NOW() - make_interval(hours => $1)
+            "#,
+        )
+        .bind(STALE_EXPORT_HOURS)
+        .execute(&db.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM chat_exports
+            WHERE status IN ('expired', 'failed')
+              AND created_at < NOW() - make_interval(days => $1)
+            "#,
+        )
+        .bind(EXPORT_RECORD_DAYS)
+        .execute(&db.pool)
+        .await?;
+
+        Ok(expired.len())
+    }
+}
+
+/// Bundle `messages.jsonl` and the attachment files into a zip at `zip_path`
+///
+/// Files are copied into the archive in chunks and stored uncompressed;
+/// attachments are mostly compressed media already. Files deleted since the
+/// messages were written are skipped.
+fn write_zip(jsonl_path: &Path, media: &[String], zip_path: &Path) -> anyhow::Result<()> {
+    let file = std::fs::File::create(zip_path)?;
+    let mut zip = zip::ZipWriter::new(std::io::BufWriter::new(file));
+    let options = zip::write::FileOptions::default()
+        .compression_method(zip::CompressionMethod::Stored)
+        .large_file(true);
+
+    zip.start_file("messages.jsonl", options)?;
+    std::io::copy(&mut std::fs::File::open(jsonl_path)?, &mut zip)?;
+
+    for name in media {
+        let Ok(mut source) = std::fs::File::open(Path::new(UPLOAD_DIR).join(name)) else {
+            continue;
+        };
+        zip.start_file(format!("media/{}", name), options)?;
+        std::io::copy(&mut source, &mut zip)?;
+    }
+
+    zip.finish()?.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_write_zip_bundles_messages_and_media() {
+        let dir = std::env::temp_dir().join(format!("export-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let jsonl = dir.join("messages.jsonl");
+        std::fs::write(&jsonl, b"{\"id\":1}\n{\"id\":2}\n").unwrap();
+        let zip_path = dir.join("export.zip");
+
+        // A missing media file is skipped rather than failing the export
+        write_zip(&jsonl, &["missing-file.png".to_string()], &zip_path).unwrap();
+
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&zip_path).unwrap()).unwrap();
+        assert_eq!(archive.len(), 1);
+        let mut contents = String::new();
+        archive
+            .by_name("messages.jsonl")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "{\"id\":1}\n{\"id\":2}\n");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_archive_path_only_for_ready_exports() {
+        let mut export = ChatExport {
+            id: Uuid::new_v4(),
+            chat_id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            status: "running".to_string(),
+            include_media: false,
+            file_name: None,
+            size_bytes: None,
+            message_count: None,
+            error: None,
+            created_at: Utc::now(),
+            completed_at: None,
+            expires_at: None,
+        };
+        assert!(matches!(ExportService::archive_path(&export), Err(AppError::BadRequest(_))));
+
+        export.status = "ready".to_string();
+        export.file_name = Some(format!("{}.jsonl", export.id));
+        assert_eq!(
+            ExportService::archive_path(&export).unwrap(),
+            Path::new(EXPORT_DIR).join(format!("{}.jsonl", export.id))
+        );
+
+        export.status = "expired".to_string();
+        export.file_name = None;
+        assert!(matches!(ExportService::archive_path(&export), Err(AppError::NotFound(_))));
+    }
+}
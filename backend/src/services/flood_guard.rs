@@ -0,0 +1,381 @@
+/// Flood Guard Service
+///
+/// Heuristic flood protection on top of slow mode. Each user's recent
+/// messages in a chat are kept in Redis (`flood:recent:{chat_id}:{user_id}`,
+/// as `{unix_ms}|{normalized text}`). A user is muted in the chat for
+/// `FLOOD_MUTE_SECONDS` (`flood:mute:{chat_id}:{user_id}`) when:
+/// - `FLOOD_DUPLICATE_LIMIT` near-identical messages fall within the window, or
+/// - `FLOOD_BURST_LIMIT` messages fall within the window and at least half of
+///   them repeat an earlier one
+///
+/// Rate alone never mutes: a burst of distinct replies is a conversation.
+/// Without Redis nothing is enforced. Chat admins, chat creators and
+/// `ADMIN_USER_IDS` are exempt. Every auto-mute is written to `audit_log`.
+
+use crate::{config::Config, db::Database, error::{AppError, AppResult}};
+use chrono::Utc;
+use redis::aio::ConnectionManager;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Characters of normalized text kept per message for comparison
+const MAX_FINGERPRINT_CHARS: usize = 200;
+
+/// `audit_log.action` for an automatic flood mute
+pub const AUDIT_FLOOD_MUTE: &str = "flood_mute";
+
+/// Flood thresholds taken from `Config`; a limit of 0 disables its rule
+#[derive(Debug, Clone)]
+pub struct FloodGuardConfig {
+    /// Messages within the window that make a burst
+    pub burst_limit: u32,
+    /// Near-identical messages within the window that trigger a mute
+    pub duplicate_limit: u32,
+    pub window_seconds: u64,
+    /// How long a flooding user is muted in the chat
+    pub mute_seconds: u64,
+    /// Similarity (0-1) from which two messages count as near-identical
+    pub similarity: f64,
+    pub exempt_user_ids: Vec<Uuid>,
+}
+
+impl From<&Config> for FloodGuardConfig {
+    fn from(config: &Config) -> Self {
+        Self {
+            burst_limit: config.flood_burst_limit,
+            duplicate_limit: config.flood_duplicate_limit,
+            window_seconds: config.flood_window_seconds,
+            mute_seconds: config.flood_mute_seconds,
+            similarity: config.flood_similarity,
+            exempt_user_ids: config.admin_user_ids.clone(),
+        }
+    }
+}
+
+/// Why a user was muted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloodReason {
+    Duplicates,
+    Burst,
+}
+
+impl FloodReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FloodReason::Duplicates => "duplicates",
+            FloodReason::Burst => "burst",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct FloodGuard {
+    redis: Option<ConnectionManager>,
+    config: FloodGuardConfig,
+}
+
+impl FloodGuard {
+    pub fn new(redis: Option<ConnectionManager>, config: FloodGuardConfig) -> Self {
+        Self { redis, config }
+    }
+
+    /// Record a message `user_id` is about to send in `chat_id`
+    ///
+    /// Returns `AppError::FloodMuted(retry_after)` while the user is muted,
+    /// including for the message that triggers the mute.
+    pub async fn check(
+        &self,
+        db: &Database,
+        chat_id: Uuid,
+        user_id: Uuid,
+        text: Option<&str>,
+    ) -> AppResult<()> {
+        let Some(redis) = &self.redis else {
+            return Ok(());
+        };
+        if self.config.window_seconds == 0
+            || (self.config.burst_limit == 0 && self.config.duplicate_limit == 0)
+            || self.config.exempt_user_ids.contains(&user_id)
+        {
+            return Ok(());
+        }
+        let mut conn = redis.clone();
+
+        self.check_muted(chat_id, user_id).await?;
+
+        if Self::is_chat_admin(db, chat_id, user_id).await? {
+            return Ok(());
+        }
+
+        let now_ms = Utc::now().timestamp_millis();
+        let fingerprint = fingerprint(text.unwrap_or(""));
+        let recent_key = recent_key(chat_id, user_id);
+        let keep = self.config.burst_limit.max(self.config.duplicate_limit) as isize;
+
+        let (entries,): (Vec<String>,) = redis::pipe()
+            .atomic()
+            .cmd("LRANGE")
+            .arg(&recent_key)
+            .arg(0)
+            .arg(keep - 1)
+            .cmd("LPUSH")
+            .arg(&recent_key)
+            .arg(format!("{}|{}", now_ms, fingerprint))
+            .ignore()
+            .cmd("LTRIM")
+            .arg(&recent_key)
+            .arg(0)
+            .arg(keep - 1)
+            .ignore()
+            .cmd("EXPIRE")
+            .arg(&recent_key)
+            .arg(self.config.window_seconds)
+            .ignore()
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error)?;
+
+        let since_ms = now_ms - (self.config.window_seconds as i64) * 1000;
+        let recent: Vec<&str> = entries
+            .iter()
+            .filter_map(|entry| entry.split_once('|'))
+            .filter(|(at, _)| at.parse::<i64>().is_ok_and(|at| at > since_ms))
+            .map(|(_, text)| text)
+            .collect();
+
+        let Some(reason) = evaluate(&recent, &fingerprint, &self.config) else {
+            return Ok(());
+        };
+
+        let mute_seconds = self.config.mute_seconds.max(1);
+        let _: () = redis::pipe()
+            .atomic()
+            .cmd("SET")
+            .arg(mute_key(chat_id, user_id))
+            .arg(reason.as_str())
+            .arg("EX")
+            .arg(mute_seconds)
+            .ignore()
+            .cmd("DEL")
+            .arg(&recent_key)
+            .ignore()
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error)?;
+
+        tracing::warn!(
+            "Muted user {} in chat {} for {}s ({})",
+            user_id,
+            chat_id,
+            mute_seconds,
+            reason.as_str()
+        );
+        if let Err(e) = Self::audit(db, chat_id, user_id, reason, mute_seconds).await {
+            tracing::error!("Failed to write flood mute audit entry: {}", e);
+        }
+
+        Err(AppError::FloodMuted(mute_seconds as u32))
+    }
+
+    /// Returns `AppError::FloodMuted(retry_after)` while `user_id` is muted
+    /// in `chat_id`, without recording anything
+    ///
+    /// For posts that don't count towards flooding themselves, like polls.
+    pub async fn check_muted(&self, chat_id: Uuid, user_id: Uuid) -> AppResult<()> {
+        let Some(redis) = &self.redis else {
+            return Ok(());
+        };
+        let mut conn = redis.clone();
+
+        let ttl: i64 = redis::cmd("TTL")
+            .arg(mute_key(chat_id, user_id))
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error)?;
+        if ttl > 0 {
+            return Err(AppError::FloodMuted(ttl as u32));
+        }
+        Ok(())
+    }
+
+    async fn is_chat_admin(db: &Database, chat_id: Uuid, user_id: Uuid) -> AppResult<bool> {
+        let is_admin: Option<bool> = sqlx::query_scalar(
+            r#"
+            SELECT cp.role = 'admin' OR c.created_by = $2
+            FROM chats c
+            JOIN chat_participants cp ON cp.chat_id = c.id AND cp.user_id = $2
+            WHERE c.id = $1
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .fetch_optional(&db.pool)
+        .await?;
+
+        Ok(is_admin.unwrap_or(false))
+    }
+
+    async fn audit(
+        db: &Database,
+        chat_id: Uuid,
+        user_id: Uuid,
+        reason: FloodReason,
+        mute_seconds: u64,
+    ) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO audit_log (chat_id, target_user_id, action, details)
+            VALUES ($1, $2, $3, $4)
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(AUDIT_FLOOD_MUTE)
+        .bind(serde_json::json!({
+            "reason": reason.as_str(),
+            "muteSeconds": mute_seconds,
+        }))
+        .execute(&db.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Decide whether the current message (already normalized) completes a flood
+///
+/// `recent` holds the user's earlier messages in the window, newest first.
+fn evaluate(recent: &[&str], current: &str, config: &FloodGuardConfig) -> Option<FloodReason> {
+    let similar = |a: &str, b: &str| is_similar(a, b, config.similarity);
+
+    let duplicates = 1 + recent.iter().filter(|m| similar(m, current)).count();
+    if config.duplicate_limit > 0 && duplicates >= config.duplicate_limit as usize {
+        return Some(FloodReason::Duplicates);
+    }
+
+    // Oldest first, so each message is compared with the ones before it
+    let mut burst: Vec<&str> = recent.iter().rev().copied().collect();
+    burst.push(current);
+    if config.burst_limit > 0 && burst.len() >= config.burst_limit as usize {
+        let repeats = (1..burst.len())
+            .filter(|&i| burst[..i].iter().any(|earlier| similar(earlier, burst[i])))
+            .count();
+        if repeats * 2 >= burst.len() {
+            return Some(FloodReason::Burst);
+        }
+    }
+
+    None
+}
+
+/// Lowercased, whitespace-collapsed text used for comparison
+fn fingerprint(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+        .chars()
+        .filter(|c| *c != '|')
+        .take(MAX_FINGERPRINT_CHARS)
+        .collect()
+}
+
+/// Near-identical by character-bigram (Dice) similarity; empty texts
+/// (attachment-only messages) are never similar
+fn is_similar(a: &str, b: &str, threshold: f64) -> bool {
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+    if a == b {
+        return true;
+    }
+
+    let bigrams = |s: &str| -> HashSet<(char, char)> {
+        let chars: Vec<char> = s.chars().collect();
+        chars.windows(2).map(|w| (w[0], w[1])).collect()
+    };
+    let (x, y) = (bigrams(a), bigrams(b));
+    if x.is_empty() || y.is_empty() {
+        return false;
+    }
+    let shared = x.intersection(&y).count();
+    2.0 * shared as f64 / (x.len() + y.len()) as f64 >= threshold
+}
+
+fn recent_key(chat_id: Uuid, user_id: Uuid) -> String {
+    format!("flood:recent:{}:{}", chat_id, user_id)
+}
+
+fn mute_key(chat_id: Uuid, user_id: Uuid) -> String {
+    format!("flood:mute:{}:{}", chat_id, user_id)
+}
+
+fn redis_error(e: redis::RedisError) -> AppError {
+    AppError::Internal(anyhow::anyhow!("Redis error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> FloodGuardConfig {
+        FloodGuardConfig {
+            burst_limit: 6,
+            duplicate_limit: 3,
+            window_seconds: 30,
+            mute_seconds: 300,
+            similarity: 0.8,
+            exempt_user_ids: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_duplicates_trigger_mute() {
+        let recent = ["buy cheap followers now!!", "buy cheap followers now!"];
+        let current = fingerprint("Buy cheap   followers NOW!!!");
+        assert_eq!(
+            evaluate(&recent, &current, &config()),
+            Some(FloodReason::Duplicates)
+        );
+
+        // Two copies are not enough
+        assert_eq!(evaluate(&recent[..1], &current, &config()), None);
+    }
+
+    #[test]
+    fn test_rapid_distinct_replies_are_allowed() {
+        let recent = ["sure", "see you at 5", "i'll bring the slides", "ok", "thanks!"];
+        assert_eq!(evaluate(&recent, "what room?", &config()), None);
+    }
+
+    #[test]
+    fn test_repetitive_burst_triggers_mute() {
+        let config = FloodGuardConfig {
+            duplicate_limit: 0,
+            ..config()
+        };
+        let recent = ["spam spam", "hello", "spam spam", "hi", "spam spam!"];
+        assert_eq!(
+            evaluate(&recent, "hello", &config),
+            Some(FloodReason::Burst)
+        );
+
+        // The same burst with varied content is fine
+        let varied = ["spam spam", "hello", "how are you", "hi", "what's new"];
+        assert_eq!(evaluate(&varied, "ok bye", &config), None);
+    }
+
+    #[test]
+    fn test_attachment_only_messages_are_not_duplicates() {
+        let recent = ["", "", "", "", ""];
+        assert_eq!(evaluate(&recent, "", &config()), None);
+    }
+
+    #[test]
+    fn test_similarity() {
+        assert!(is_similar("hello world", "hello world!", 0.8));
+        assert!(!is_similar("hello world", "goodbye moon", 0.8));
+        assert!(!is_similar("", "", 0.8));
+        assert_eq!(fingerprint("  A  b|c "), "a bc");
+    }
+}
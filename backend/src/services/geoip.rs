@@ -0,0 +1,108 @@
+/// Coarse IP geolocation for realtime sessions
+///
+/// Lookups go through the `GeoIpLookup` trait so a real database (MaxMind,
+/// an HTTP service, ...) can be plugged in; the default resolves nothing.
+/// Addresses that cannot be located publicly (loopback, LAN, link-local)
+/// are never passed to the lookup.
+
+use std::net::IpAddr;
+
+/// Location shown when an address cannot be resolved
+pub const UNKNOWN_LOCATION: &str = "Unknown";
+
+/// Pluggable IP -> coarse location ("City, Country") resolver
+pub trait GeoIpLookup: Send + Sync {
+    fn lookup(&self, ip: IpAddr) -> Option<String>;
+}
+
+/// Default lookup that never resolves a location
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoGeoIp;
+
+impl GeoIpLookup for NoGeoIp {
+    fn lookup(&self, _ip: IpAddr) -> Option<String> {
+        None
+    }
+}
+
+/// Whether an address is private/LAN/loopback and so has no public location
+pub fn is_non_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                // 100.64.0.0/10 carrier-grade NAT
+                || (v4.octets()[0] == 100 && (v4.octets()[1] & 0xC0) == 64)
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_non_public(IpAddr::V4(v4)),
+            None => {
+                let first = v6.segments()[0];
+                v6.is_loopback()
+                    || v6.is_unspecified()
+                    // fc00::/7 unique local
+                    || (first & 0xFE00) == 0xFC00
+                    // fe80::/10 link-local
+                    || (first & 0xFFC0) == 0xFE80
+            }
+        },
+    }
+}
+
+/// Resolve a coarse location for `ip`, falling back to `UNKNOWN_LOCATION`
+pub fn resolve_location(lookup: &dyn GeoIpLookup, ip: IpAddr) -> String {
+    if is_non_public(ip) {
+        return UNKNOWN_LOCATION.to_string();
+    }
+    lookup
+        .lookup(ip)
+        .filter(|location| !location.trim().is_empty())
+        .unwrap_or_else(|| UNKNOWN_LOCATION.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedGeoIp;
+
+    impl GeoIpLookup for FixedGeoIp {
+        fn lookup(&self, _ip: IpAddr) -> Option<String> {
+            Some("Hanoi, Vietnam".to_string())
+        }
+    }
+
+    #[test]
+    fn test_non_public_addresses() {
+        for ip in [
+            "10.0.0.1",
+            "172.16.5.4",
+            "192.168.1.10",
+            "127.0.0.1",
+            "169.254.0.1",
+            "100.64.0.1",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:192.168.1.1",
+        ] {
+            assert!(is_non_public(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in ["8.8.8.8", "100.128.0.1", "2001:4860:4860::8888"] {
+            assert!(!is_non_public(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[test]
+    fn test_resolve_location() {
+        let public: IpAddr = "8.8.8.8".parse().unwrap();
+        let lan: IpAddr = "192.168.1.10".parse().unwrap();
+
+        assert_eq!(resolve_location(&FixedGeoIp, public), "Hanoi, Vietnam");
+        assert_eq!(resolve_location(&FixedGeoIp, lan), UNKNOWN_LOCATION);
+        assert_eq!(resolve_location(&NoGeoIp, public), UNKNOWN_LOCATION);
+    }
+}
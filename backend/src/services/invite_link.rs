@@ -1,10 +1,11 @@
 use crate::error::AppError;
+use crate::services::ChatService;
 use crate::models::{
     CreateInviteLinkRequest, InviteLink, InviteLinkResponse, UseInviteLinkResponse,
 };
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use rand::Rng;
-use sqlx::{PgPool, Row};
+use sqlx::{PgPool, Postgres, Row, Transaction};
 use uuid::Uuid;
 
 const INVITE_CODE_LENGTH: usize = 12;
@@ -188,19 +189,24 @@ pub async fn get_invite_link_by_code(
 }
 
 
-/// Use an invite link
-pub async fn use_invite_link(
+/// Redeem an invite link, adding the user to its chat.
+///
+/// The link row is locked for the whole transaction and the use count is
+/// incremented with a conditional update, so concurrent redemptions can never
+/// exceed `max_uses`. Redeeming a link the user has already used while still
+/// in the chat is idempotent and returns `joined: false`. Joining a chat that
+/// already has `max_participants` members fails with `ChatFull`.
+pub async fn redeem_invite_link(
     pool: &PgPool,
     user_id: Uuid,
     code: &str,
+    max_participants: i64,
 ) -> Result<UseInviteLinkResponse, AppError> {
-    // Start transaction
     let mut tx = pool.begin().await?;
 
-    // Get invite link with lock
     let invite_link: InviteLink = sqlx::query_as(
         r#"
-        SELECT id, code, type as link_type, chat_id, created_by, expires_at, max_uses, current_uses, is_active, created_at, updated_at
+        SELECT id, code, type as link_type, chat_id, created_by, expires_at, max_uses, current_uses, is_active, revoked_at, created_at, updated_at
         FROM invite_links
         WHERE code = $1
         FOR UPDATE
@@ -211,73 +217,108 @@ pub async fn use_invite_link(
     .await?
     .ok_or_else(|| AppError::NotFound("Invite link not found".to_string()))?;
 
-    // Validate invite link
-    if !invite_link.is_active {
-        return Err(AppError::BadRequest("Invite link is inactive".to_string()));
-    }
+    let already_used = sqlx::query("SELECT id FROM invite_link_uses WHERE invite_link_id = $1 AND user_id = $2")
+        .bind(invite_link.id)
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .is_some();
 
-    if let Some(expires_at) = invite_link.expires_at {
-        if expires_at < Utc::now() {
-            return Err(AppError::BadRequest("Invite link has expired".to_string()));
+    let existing_chat = find_joined_chat(&mut tx, &invite_link, user_id).await?;
+
+    if already_used {
+        if let Some(chat_id) = existing_chat {
+            let response = chat_response(&mut tx, chat_id, false).await?;
+            tx.commit().await?;
+            return Ok(response);
         }
     }
 
-    if let Some(max_uses) = invite_link.max_uses {
-        if invite_link.current_uses >= max_uses {
-            return Err(AppError::BadRequest(
-                "Invite link has reached maximum uses".to_string(),
-            ));
+    // A user who left after redeeming may rejoin without taking another slot
+    check_redeemable(&invite_link, Utc::now(), !already_used)?;
+
+    let (chat_id, joined) = match existing_chat {
+        Some(chat_id) => (chat_id, false),
+        None => (
+            join_chat(&mut tx, &invite_link, user_id, max_participants).await?,
+            true,
+        ),
+    };
+
+    if !already_used {
+        let claimed = sqlx::query(
+            r#"
+            UPDATE invite_links
+            SET current_uses = current_uses + 1, updated_at = NOW()
+            WHERE id = $1 AND (max_uses IS NULL OR current_uses < max_uses)
+            "#,
+        )
+        .bind(invite_link.id)
+        .execute(&mut *tx)
+        .await?;
+
+        if claimed.rows_affected() == 0 {
+            return Err(AppError::InviteLinkExhausted);
         }
-    }
 
-    // Check if user already used this link
-    let already_used = sqlx::query("SELECT id FROM invite_link_uses WHERE invite_link_id = $1 AND user_id = $2")
+        sqlx::query(
+            r#"
+            INSERT INTO invite_link_uses (invite_link_id, user_id) VALUES ($1, $2)
+            ON CONFLICT (invite_link_id, user_id) DO NOTHING
+            "#,
+        )
         .bind(invite_link.id)
         .bind(user_id)
-        .fetch_optional(&mut *tx)
+        .execute(&mut *tx)
         .await?;
+    }
 
-    if already_used.is_some() {
-        return Err(AppError::BadRequest(
-            "You have already used this invite link".to_string(),
-        ));
+    let response = chat_response(&mut tx, chat_id, joined).await?;
+    tx.commit().await?;
+
+    Ok(response)
+}
+
+/// Check that an invite link can still be redeemed at `now`.
+///
+/// Exhaustion only applies when the redemption takes a new use slot.
+fn check_redeemable(link: &InviteLink, now: DateTime<Utc>, new_use: bool) -> Result<(), AppError> {
+    if link.revoked_at.is_some() || !link.is_active {
+        return Err(AppError::InviteLinkRevoked);
+    }
+
+    if link.expires_at.is_some_and(|expires_at| expires_at <= now) {
+        return Err(AppError::InviteLinkExpired);
     }
 
-    let chat_id = match invite_link.link_type.as_str() {
+    if new_use && link.max_uses.is_some_and(|max_uses| link.current_uses >= max_uses) {
+        return Err(AppError::InviteLinkExhausted);
+    }
+
+    Ok(())
+}
+
+/// The chat the user already belongs to through this link, if any
+async fn find_joined_chat(
+    tx: &mut Transaction<'_, Postgres>,
+    link: &InviteLink,
+    user_id: Uuid,
+) -> Result<Option<Uuid>, AppError> {
+    match link.link_type.as_str() {
         "group" => {
-            let chat_id = invite_link
+            let chat_id = link
                 .chat_id
                 .ok_or_else(|| AppError::Internal(anyhow::Error::msg("Group link missing chat_id")))?;
 
-            // Check if user is already a participant
             let existing = sqlx::query("SELECT id FROM chat_participants WHERE chat_id = $1 AND user_id = $2")
                 .bind(chat_id)
                 .bind(user_id)
-                .fetch_optional(&mut *tx)
-                .await?;
-
-            if existing.is_none() {
-                // Add user to group (use ON CONFLICT to handle race conditions)
-                sqlx::query(
-                    r#"
-                    INSERT INTO chat_participants (chat_id, user_id, role) 
-                    VALUES ($1, $2, 'member')
-                    ON CONFLICT (chat_id, user_id) DO NOTHING
-                    "#
-                )
-                .bind(chat_id)
-                .bind(user_id)
-                .execute(&mut *tx)
+                .fetch_optional(&mut **tx)
                 .await?;
-            }
 
-            chat_id
+            Ok(existing.map(|_| chat_id))
         }
         "direct" => {
-            // Create or get existing direct chat with creator
-            let creator_id = invite_link.created_by;
-
-            // Check if direct chat already exists
             let existing_chat = sqlx::query(
                 r#"
                 SELECT c.id
@@ -290,70 +331,91 @@ pub async fn use_invite_link(
                 "#,
             )
             .bind(user_id)
-            .bind(creator_id)
-            .fetch_optional(&mut *tx)
+            .bind(link.created_by)
+            .fetch_optional(&mut **tx)
             .await?;
 
-            if let Some(chat) = existing_chat {
-                chat.get("id")
-            } else {
-                // Create new direct chat
-                let chat_row = sqlx::query("INSERT INTO chats (type, created_by) VALUES ('direct', $1) RETURNING id")
-                    .bind(user_id)
-                    .fetch_one(&mut *tx)
-                    .await?;
-                let new_chat_id: Uuid = chat_row.get("id");
-
-                // Add both users as participants (use ON CONFLICT to handle duplicates)
-                sqlx::query(
-                    r#"
-                    INSERT INTO chat_participants (chat_id, user_id, role) 
-                    VALUES ($1, $2, 'member'), ($1, $3, 'member')
-                    ON CONFLICT (chat_id, user_id) DO NOTHING
-                    "#
-                )
-                .bind(new_chat_id)
-                .bind(user_id)
-                .bind(creator_id)
-                .execute(&mut *tx)
-                .await?;
-
-                new_chat_id
-            }
+            Ok(existing_chat.map(|chat| chat.get("id")))
         }
-        _ => {
-            return Err(AppError::Internal(anyhow::Error::msg(
-                "Invalid invite link type"
-            )))
+        _ => Err(AppError::Internal(anyhow::Error::msg(
+            "Invalid invite link type"
+        ))),
+    }
+}
+
+/// Add the user to the link's group, or open a direct chat with its creator
+async fn join_chat(
+    tx: &mut Transaction<'_, Postgres>,
+    link: &InviteLink,
+    user_id: Uuid,
+    max_participants: i64,
+) -> Result<Uuid, AppError> {
+    match link.link_type.as_str() {
+        "group" => {
+            let chat_id = link
+                .chat_id
+                .ok_or_else(|| AppError::Internal(anyhow::Error::msg("Group link missing chat_id")))?;
+
+            ChatService::ensure_room_for_member(tx, chat_id, max_participants).await?;
+
+            // Use ON CONFLICT to handle race conditions
+            sqlx::query(
+                r#"
+                INSERT INTO chat_participants (chat_id, user_id, role)
+                VALUES ($1, $2, 'member')
+                ON CONFLICT (chat_id, user_id) DO NOTHING
+                "#
+            )
+            .bind(chat_id)
+            .bind(user_id)
+            .execute(&mut **tx)
+            .await?;
+
+            Ok(chat_id)
         }
-    };
+        "direct" => {
+            let chat_row = sqlx::query("INSERT INTO chats (type, created_by) VALUES ('direct', $1) RETURNING id")
+                .bind(user_id)
+                .fetch_one(&mut **tx)
+                .await?;
+            let new_chat_id: Uuid = chat_row.get("id");
 
-    // Record usage
-    sqlx::query("INSERT INTO invite_link_uses (invite_link_id, user_id) VALUES ($1, $2)")
-        .bind(invite_link.id)
-        .bind(user_id)
-        .execute(&mut *tx)
-        .await?;
+            sqlx::query(
+                r#"
+                INSERT INTO chat_participants (chat_id, user_id, role)
+                VALUES ($1, $2, 'member'), ($1, $3, 'member')
+                ON CONFLICT (chat_id, user_id) DO NOTHING
+                "#
+            )
+            .bind(new_chat_id)
+            .bind(user_id)
+            .bind(link.created_by)
+            .execute(&mut **tx)
+            .await?;
 
-    // Increment usage count
-    sqlx::query("UPDATE invite_links SET current_uses = current_uses + 1, updated_at = NOW() WHERE id = $1")
-        .bind(invite_link.id)
-        .execute(&mut *tx)
-        .await?;
+            Ok(new_chat_id)
+        }
+        _ => Err(AppError::Internal(anyhow::Error::msg(
+            "Invalid invite link type"
+        ))),
+    }
+}
 
-    // Get chat details
+async fn chat_response(
+    tx: &mut Transaction<'_, Postgres>,
+    chat_id: Uuid,
+    joined: bool,
+) -> Result<UseInviteLinkResponse, AppError> {
     let chat_row = sqlx::query("SELECT type, name FROM chats WHERE id = $1")
         .bind(chat_id)
-        .fetch_one(&mut *tx)
+        .fetch_one(&mut **tx)
         .await?;
 
-    tx.commit().await?;
-
     Ok(UseInviteLinkResponse {
         chat_id,
         chat_name: chat_row.get::<Option<String>, _>("name").unwrap_or_else(|| "Direct Chat".to_string()),
         chat_type: chat_row.get("type"),
-        joined: true,
+        joined,
     })
 }
 
@@ -418,7 +480,7 @@ pub async fn revoke_invite_link(
     user_id: Uuid,
     link_id: Uuid,
 ) -> Result<(), AppError> {
-    let result = sqlx::query("UPDATE invite_links SET is_active = false, updated_at = NOW() WHERE id = $1 AND created_by = $2")
+    let result = sqlx::query("UPDATE invite_links SET is_active = false, revoked_at = COALESCE(revoked_at, NOW()), updated_at = NOW() WHERE id = $1 AND created_by = $2")
         .bind(link_id)
         .bind(user_id)
         .execute(pool)
@@ -432,3 +494,70 @@ pub async fn revoke_invite_link(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(max_uses: Option<i32>, current_uses: i32) -> InviteLink {
+        let now = Utc::now();
+        InviteLink {
+            id: Uuid::new_v4(),
+            code: generate_invite_code(),
+            link_type: "group".to_string(),
+            chat_id: Some(Uuid::new_v4()),
+            created_by: Uuid::new_v4(),
+            expires_at: None,
+            max_uses,
+            current_uses,
+            is_active: true,
+            revoked_at: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn test_check_redeemable_ok() {
+        assert!(check_redeemable(&link(None, 100), Utc::now(), true).is_ok());
+        assert!(check_redeemable(&link(Some(2), 1), Utc::now(), true).is_ok());
+    }
+
+    #[test]
+    fn test_check_redeemable_revoked() {
+        let mut revoked = link(None, 0);
+        revoked.revoked_at = Some(Utc::now());
+        assert!(matches!(
+            check_redeemable(&revoked, Utc::now(), true),
+            Err(AppError::InviteLinkRevoked)
+        ));
+
+        let mut inactive = link(None, 0);
+        inactive.is_active = false;
+        assert!(matches!(
+            check_redeemable(&inactive, Utc::now(), true),
+            Err(AppError::InviteLinkRevoked)
+        ));
+    }
+
+    #[test]
+    fn test_check_redeemable_expired() {
+        let now = Utc::now();
+        let mut expired = link(None, 0);
+        expired.expires_at = Some(now - Duration::seconds(1));
+        assert!(matches!(
+            check_redeemable(&expired, now, true),
+            Err(AppError::InviteLinkExpired)
+        ));
+    }
+
+    #[test]
+    fn test_check_redeemable_exhausted_only_for_new_use() {
+        let exhausted = link(Some(1), 1);
+        assert!(matches!(
+            check_redeemable(&exhausted, Utc::now(), true),
+            Err(AppError::InviteLinkExhausted)
+        ));
+        assert!(check_redeemable(&exhausted, Utc::now(), false).is_ok());
+    }
+}
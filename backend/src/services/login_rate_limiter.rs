@@ -1,72 +1,233 @@
 /// Login Rate Limiter Service
-/// 
+///
 /// Protects against brute force attacks by tracking failed login attempts
 /// and temporarily blocking accounts/IPs that exceed thresholds.
+///
+/// Failure counters live in Redis (keyed by client IP and by target email)
+/// when it is available, falling back to the `login_attempts` table otherwise.
+/// Every attempt is still written to `login_attempts` for auditing.
 
-use crate::{db::Database, error::AppResult};
+use crate::{config::Config, db::Database, error::{AppError, AppResult}};
+use axum::http::HeaderMap;
 use chrono::{Duration, Utc};
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use std::net::SocketAddr;
 use uuid::Uuid;
 
-pub struct LoginRateLimiter;
+/// Thresholds for login throttling.
+#[derive(Debug, Clone)]
+pub struct LoginRateLimitConfig {
+    /// Failed attempts allowed per email within the window
+    pub max_attempts_per_email: u32,
+    /// Failed attempts allowed per IP within the window
+    pub max_attempts_per_ip: u32,
+    /// Window over which failed attempts are counted
+    pub window_seconds: u64,
+    /// Upper bound for a single lockout, so an attacker hammering a victim's
+    /// email can never lock the account for longer than this
+    pub max_lockout_seconds: u64,
+}
+
+impl Default for LoginRateLimitConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts_per_email: 5,
+            max_attempts_per_ip: 10,
+            window_seconds: 900,
+            max_lockout_seconds: 900,
+        }
+    }
+}
+
+impl From<&Config> for LoginRateLimitConfig {
+    fn from(config: &Config) -> Self {
+        Self {
+            max_attempts_per_email: config.login_max_attempts_per_email,
+            max_attempts_per_ip: config.login_max_attempts_per_ip,
+            window_seconds: config.login_attempt_window_seconds,
+            max_lockout_seconds: config.login_max_lockout_seconds,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct LoginRateLimiter {
+    redis: Option<ConnectionManager>,
+    config: LoginRateLimitConfig,
+}
 
 impl LoginRateLimiter {
+    pub fn new(redis: Option<ConnectionManager>, config: LoginRateLimitConfig) -> Self {
+        Self { redis, config }
+    }
+
     /// Check if login is allowed for this email/IP combination
-    /// 
-    /// Rules:
-    /// - Max 5 failed attempts per email in 15 minutes
-    /// - Max 10 failed attempts per IP in 15 minutes
-    /// 
-    /// Returns: (allowed, retry_after_seconds)
-    pub async fn check_rate_limit(
+    ///
+    /// Returns: Some(retry_after_seconds) if the email or IP is locked out
+    pub async fn check(
+        &self,
         db: &Database,
         email: &str,
         ip_address: Option<&str>,
-    ) -> AppResult<(bool, Option<u32>)> {
-        let fifteen_mins_ago = Utc::now() - Duration::minutes(15);
+    ) -> AppResult<Option<u32>> {
+        let email = normalize_email(email);
+
+        let Some(redis) = &self.redis else {
+            return self.check_db(db, &email, ip_address).await;
+        };
+        let mut conn = redis.clone();
+
+        let mut keys = vec![lock_key("email", &email)];
+        if let Some(ip) = ip_address {
+            keys.push(lock_key("ip", ip));
+        }
+
+        let mut retry_after: Option<u32> = None;
+        for key in keys {
+            let ttl: i64 = conn.ttl(&key).await.map_err(redis_error)?;
+            if ttl > 0 {
+                let ttl = ttl as u32;
+                retry_after = Some(retry_after.map_or(ttl, |r| r.max(ttl)));
+            }
+        }
+
+        Ok(retry_after)
+    }
+
+    /// Record a failed login and lock the email/IP once its threshold is hit.
+    pub async fn record_failure(&self, email: &str, ip_address: Option<&str>) -> AppResult<()> {
+        let Some(redis) = &self.redis else {
+            // Counted from login_attempts in check_db
+            return Ok(());
+        };
+        let mut conn = redis.clone();
+        let email = normalize_email(email);
+
+        self.bump(&mut conn, "email", &email, self.config.max_attempts_per_email)
+            .await?;
+        if let Some(ip) = ip_address {
+            self.bump(&mut conn, "ip", ip, self.config.max_attempts_per_ip)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Reset the per-account counters after a successful login.
+    ///
+    /// The per-IP counter is left to expire on its own so that logging into an
+    /// attacker-controlled account can't be used to clear it.
+    pub async fn reset(&self, db: &Database, email: &str) -> AppResult<()> {
+        let email = normalize_email(email);
+
+        match &self.redis {
+            Some(redis) => {
+                let mut conn = redis.clone();
+                let _: () = conn
+                    .del(vec![fail_key("email", &email), lock_key("email", &email)])
+                    .await
+                    .map_err(redis_error)?;
+            }
+            None => Self::clear_failed_attempts(db, &email).await?,
+        }
+
+        Ok(())
+    }
+
+    async fn bump(
+        &self,
+        conn: &mut ConnectionManager,
+        kind: &str,
+        id: &str,
+        max_attempts: u32,
+    ) -> AppResult<()> {
+        let key = fail_key(kind, id);
+        let failures: u32 = conn.incr(&key, 1).await.map_err(redis_error)?;
+        if failures == 1 {
+            let _: () = conn
+                .expire(&key, self.config.window_seconds as i64)
+                .await
+                .map_err(redis_error)?;
+        }
+
+        if failures >= max_attempts {
+            let lockout = self.lockout_seconds(failures, max_attempts);
+            let _: () = conn
+                .set_ex(lock_key(kind, id), failures, lockout)
+                .await
+                .map_err(redis_error)?;
+            tracing::warn!(
+                "Login locked for {} {} after {} failed attempts ({}s)",
+                kind,
+                id,
+                failures,
+                lockout
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Fallback check against the login_attempts table when Redis is unavailable
+    async fn check_db(
+        &self,
+        db: &Database,
+        email: &str,
+        ip_address: Option<&str>,
+    ) -> AppResult<Option<u32>> {
+        let window_start = Utc::now() - Duration::seconds(self.config.window_seconds as i64);
 
         // Check email-based rate limit
         let email_attempts: i64 = sqlx::query_scalar(
             r#"
-            SELECT COUNT(*) 
-            FROM login_attempts 
-            WHERE email = $1 
-              AND success = FALSE 
+            SELECT COUNT(*)
+            FROM login_attempts
+            WHERE email = $1
+              AND success = FALSE
               AND attempted_at > $2
             "#,
         )
         .bind(email)
-        .bind(fifteen_mins_ago)
+        .bind(window_start)
         .fetch_one(&db.pool)
         .await?;
 
-        if email_attempts >= 5 {
-            let retry_after = Self::calculate_retry_after(email_attempts);
-            return Ok((false, Some(retry_after)));
+        let max_email = self.config.max_attempts_per_email;
+        if email_attempts >= max_email as i64 {
+            return Ok(Some(self.lockout_seconds(email_attempts as u32, max_email) as u32));
         }
 
         // Check IP-based rate limit (if IP provided)
         if let Some(ip) = ip_address {
             let ip_attempts: i64 = sqlx::query_scalar(
                 r#"
-                SELECT COUNT(*) 
-                FROM login_attempts 
-                WHERE ip_address = $1 
-                  AND success = FALSE 
+                SELECT COUNT(*)
+                FROM login_attempts
+                WHERE ip_address = $1
+                  AND success = FALSE
                   AND attempted_at > $2
                 "#,
             )
             .bind(ip)
-            .bind(fifteen_mins_ago)
+            .bind(window_start)
             .fetch_one(&db.pool)
             .await?;
 
-            if ip_attempts >= 10 {
-                let retry_after = Self::calculate_retry_after(ip_attempts);
-                return Ok((false, Some(retry_after)));
+            let max_ip = self.config.max_attempts_per_ip;
+            if ip_attempts >= max_ip as i64 {
+                return Ok(Some(self.lockout_seconds(ip_attempts as u32, max_ip) as u32));
             }
         }
 
-        Ok((true, None))
+        Ok(None)
+    }
+
+    /// Lockout duration for the given failure count, capped at max_lockout_seconds.
+    /// Exponential backoff: 60s, 120s, 240s, ... starting at the threshold.
+    fn lockout_seconds(&self, failures: u32, max_attempts: u32) -> u64 {
+        let over = failures.saturating_sub(max_attempts).min(16);
+        (60u64 << over).min(self.config.max_lockout_seconds).max(1)
     }
 
     /// Record a login attempt
@@ -83,7 +244,7 @@ impl LoginRateLimiter {
             VALUES ($1, $2, $3, $4)
             "#,
         )
-        .bind(email)
+        .bind(normalize_email(email))
         .bind(ip_address)
         .bind(success)
         .bind(user_agent)
@@ -97,7 +258,7 @@ impl LoginRateLimiter {
     pub async fn clear_failed_attempts(db: &Database, email: &str) -> AppResult<()> {
         sqlx::query(
             r#"
-            DELETE FROM login_attempts 
+            DELETE FROM login_attempts
             WHERE email = $1 AND success = FALSE
             "#,
         )
@@ -108,18 +269,6 @@ impl LoginRateLimiter {
         Ok(())
     }
 
-    /// Calculate retry-after seconds based on number of attempts
-    /// Exponential backoff: 60s, 120s, 300s, 600s, 900s
-    fn calculate_retry_after(attempts: i64) -> u32 {
-        match attempts {
-            5..=6 => 60,    // 1 minute
-            7..=8 => 120,   // 2 minutes
-            9..=10 => 300,  // 5 minutes
-            11..=15 => 600, // 10 minutes
-            _ => 900,       // 15 minutes
-        }
-    }
-
     /// Cleanup old login attempts (run periodically)
     pub async fn cleanup_old_attempts(db: &Database) -> AppResult<u64> {
         let result = sqlx::query("SELECT cleanup_old_login_attempts()")
@@ -151,6 +300,46 @@ impl LoginRateLimiter {
     }
 }
 
+/// Resolve the client IP for a request.
+///
+/// When a trusted proxy header is configured, the right-most entry is used:
+/// that is the address appended by our own proxy, whereas entries further left
+/// are supplied by the client and can be spoofed. Otherwise the socket peer
+/// address is used.
+pub fn client_ip(
+    headers: &HeaderMap,
+    trusted_proxy_header: Option<&str>,
+    peer: Option<SocketAddr>,
+) -> Option<String> {
+    if let Some(header_name) = trusted_proxy_header {
+        let forwarded = headers
+            .get(header_name)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.rsplit(',').map(str::trim).find(|s| !s.is_empty()));
+        if let Some(ip) = forwarded {
+            return Some(ip.to_string());
+        }
+    }
+
+    peer.map(|addr| addr.ip().to_string())
+}
+
+fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+fn fail_key(kind: &str, id: &str) -> String {
+    format!("login_fail:{}:{}", kind, id)
+}
+
+fn lock_key(kind: &str, id: &str) -> String {
+    format!("login_lock:{}:{}", kind, id)
+}
+
+fn redis_error(e: redis::RedisError) -> AppError {
+    AppError::Internal(anyhow::anyhow!("Redis error: {}", e))
+}
+
 #[derive(Debug, sqlx::FromRow)]
 pub struct LoginAttempt {
     pub id: Uuid,
@@ -160,3 +349,76 @@ pub struct LoginAttempt {
     pub attempted_at: chrono::DateTime<Utc>,
     pub user_agent: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(max_lockout_seconds: u64) -> LoginRateLimiter {
+        LoginRateLimiter::new(
+            None,
+            LoginRateLimitConfig {
+                max_lockout_seconds,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_lockout_backoff_is_capped() {
+        let limiter = limiter(900);
+        assert_eq!(limiter.lockout_seconds(5, 5), 60);
+        assert_eq!(limiter.lockout_seconds(6, 5), 120);
+        assert_eq!(limiter.lockout_seconds(7, 5), 240);
+        assert_eq!(limiter.lockout_seconds(8, 5), 480);
+        assert_eq!(limiter.lockout_seconds(9, 5), 900);
+        assert_eq!(limiter.lockout_seconds(1000, 5), 900);
+    }
+
+    #[test]
+    fn test_lockout_respects_small_cap() {
+        let limiter = limiter(30);
+        assert_eq!(limiter.lockout_seconds(5, 5), 30);
+    }
+
+    #[test]
+    fn test_client_ip_uses_rightmost_forwarded_entry() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "1.1.1.1, 10.0.0.7".parse().unwrap());
+        let peer: SocketAddr = "127.0.0.1:5555".parse().unwrap();
+
+        assert_eq!(
+            client_ip(&headers, Some("x-forwarded-for"), Some(peer)),
+            Some("10.0.0.7".to_string())
+        );
+    }
+
+    #[test]
+    fn test_client_ip_ignores_header_when_not_trusted() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "1.1.1.1".parse().unwrap());
+        let peer: SocketAddr = "127.0.0.1:5555".parse().unwrap();
+
+        assert_eq!(
+            client_ip(&headers, None, Some(peer)),
+            Some("127.0.0.1".to_string())
+        );
+        assert_eq!(client_ip(&headers, None, None), None);
+    }
+
+    #[test]
+    fn test_client_ip_falls_back_to_peer_when_header_missing() {
+        let headers = HeaderMap::new();
+        let peer: SocketAddr = "[::1]:5555".parse().unwrap();
+
+        assert_eq!(
+            client_ip(&headers, Some("x-forwarded-for"), Some(peer)),
+            Some("::1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_normalize_email() {
+        assert_eq!(normalize_email("  Alice@Example.COM "), "alice@example.com");
+    }
+}
@@ -2,13 +2,67 @@ use crate::{
     db::Database,
     error::{AppError, AppResult},
     models::{
-        Attachment, AttachmentResponse, Message, MessageResponse, Reaction, ReactionResponse,
-        ReadByResponse, ReadReceipt, ReplyToResponse,
+        Attachment, AttachmentResponse, DeliveryStatus, ForwardError, ForwardResult,
+        ForwardedFromResponse, HighlightRange, LinkPreview, MentionResponse, Message, MessageEntity, MessageEntityRow,
+        MessageResponse, MessageSearchHit, MessageSearchResponse, Reaction, ReactionResponse,
+        ReadByResponse, ReadReceipt, ReactionSummary, ReplyToResponse, SavedMessage,
+        SavedMessageResponse, SeenByResponse, SeenByUser, SystemEventResponse, SystemEventType,
+        ThreadResponse, Upload,
     },
-    services::ChatService,
+    services::{
+        content::{extract_mentions, normalize_message_text, Mention},
+        attachment::AttachmentLimits,
+        bot_engine::InlineKeyboardStore,
+        outbox::{
+            OutboxService, EFFECT_BOTS, EFFECT_LINK_PREVIEW, EFFECT_MENTIONS,
+            EFFECT_OFFLINE_QUEUE, EFFECT_PUSH, EFFECT_REALTIME, EVENT_NEW_MESSAGE,
+        },
+        poll::normalize_poll,
+        AttachmentService, ChatService, CustomEmojiService, LinkPreviewService, PollService,
+        FloodGuard, MessageProcessor, SlowModeLimiter, WebSocketService,
+    },
+    ws::events::ServerEvent,
+    AppState,
 };
+use chrono::{DateTime, Utc};
+use sqlx::{Postgres, Transaction};
+use std::collections::HashMap;
+use unicode_normalization::UnicodeNormalization;
 use uuid::Uuid;
 
+/// Most thread replies returned per page
+pub const MAX_THREAD_PAGE_SIZE: i64 = 100;
+
+/// Longest reply snippet, in characters (before the ellipsis)
+pub const REPLY_SNIPPET_MAX_CHARS: usize = 100;
+
+/// Snippet shown in place of a replied-to message that has been deleted
+pub const DELETED_REPLY_SNIPPET: &str = "Message deleted";
+
+/// Snippet for replied-to messages without text
+const ATTACHMENT_REPLY_SNIPPET: &str = "Attachment";
+
+/// Snippet for replied-to messages whose text is end-to-end encrypted
+const ENCRYPTED_REPLY_SNIPPET: &str = "Encrypted message";
+
+/// Most search hits returned per page
+pub const MAX_SEARCH_PAGE_SIZE: i64 = 50;
+
+/// Most messages of context returned on each side of a search hit
+pub const MAX_SEARCH_CONTEXT: i64 = 5;
+
+/// Longest accepted search query, in characters
+pub const MAX_SEARCH_QUERY_CHARS: usize = 200;
+
+/// Most chats one message can be forwarded to in a single request
+pub const MAX_FORWARD_TARGETS: usize = 20;
+
+/// Most users listed in a message's "seen by" list
+pub const MAX_SEEN_BY_USERS: i64 = 100;
+
+/// Most saved messages returned per page
+pub const MAX_SAVED_PAGE_SIZE: i64 = 100;
+
 pub struct MessageService;
 
 impl MessageService {
@@ -27,8 +81,12 @@ impl MessageService {
         let messages: Vec<Message> = if let Some(before_id) = before {
             sqlx::query_as(
                 r#"
-                SELECT * FROM messages
-                WHERE chat_id = $1 AND created_at < (SELECT created_at FROM messages WHERE id = $2)
+                SELECT * FROM messages m
+                WHERE chat_id = $1 AND thread_root_id IS NULL
+                  AND created_at < (SELECT created_at FROM messages WHERE id = $2)
+                  AND NOT EXISTS (
+                      SELECT 1 FROM hidden_messages h WHERE h.message_id = m.id AND h.user_id = $4
+                  )
                 ORDER BY created_at DESC
                 LIMIT $3
                 "#,
@@ -36,14 +94,24 @@ impl MessageService {
             .bind(chat_id)
             .bind(before_id)
             .bind(limit + 1)
+            .bind(user_id)
             .fetch_all(&db.pool)
             .await?
         } else {
             sqlx::query_as(
-                "SELECT * FROM messages WHERE chat_id = $1 ORDER BY created_at DESC LIMIT $2",
+                r#"
+                SELECT * FROM messages m
+                WHERE chat_id = $1 AND thread_root_id IS NULL
+                  AND NOT EXISTS (
+                      SELECT 1 FROM hidden_messages h WHERE h.message_id = m.id AND h.user_id = $3
+                  )
+                ORDER BY created_at DESC
+                LIMIT $2
+                "#,
             )
             .bind(chat_id)
             .bind(limit + 1)
+            .bind(user_id)
             .fetch_all(&db.pool)
             .await?
         };
@@ -60,9 +128,56 @@ impl MessageService {
         // Reverse to get chronological order
         responses.reverse();
 
+        Self::attach_reaction_summaries(db, &mut responses, user_id).await?;
+
         Ok((responses, has_more))
     }
 
+    /// Fill `reaction_summary` of every message with one grouped query
+    ///
+    /// `reacted_by_me` is relative to `viewer_id`.
+    pub async fn attach_reaction_summaries(
+        db: &Database,
+        messages: &mut [MessageResponse],
+        viewer_id: Uuid,
+    ) -> AppResult<()> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+
+        let ids: Vec<Uuid> = messages.iter().map(|m| m.id).collect();
+        let rows: Vec<(Uuid, String, Option<Uuid>, i64, bool)> = sqlx::query_as(
+            r#"
+            SELECT message_id, emoji, custom_emoji_id, COUNT(*) AS count,
+                   BOOL_OR(user_id = $2) AS reacted_by_me
+            FROM reactions
+            WHERE message_id = ANY($1)
+            GROUP BY message_id, emoji, custom_emoji_id
+            ORDER BY message_id, count DESC, MIN(created_at), emoji
+            "#,
+        )
+        .bind(&ids)
+        .bind(viewer_id)
+        .fetch_all(&db.pool)
+        .await?;
+
+        let mut summaries: HashMap<Uuid, Vec<ReactionSummary>> = HashMap::new();
+        for (message_id, emoji, custom_emoji_id, count, reacted_by_me) in rows {
+            summaries.entry(message_id).or_default().push(ReactionSummary {
+                emoji,
+                custom_emoji_id,
+                count,
+                reacted_by_me,
+            });
+        }
+        for message in messages.iter_mut() {
+            message.reaction_summary = summaries.remove(&message.id).unwrap_or_default();
+        }
+
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub async fn send_message(
         db: &Database,
         chat_id: Uuid,
@@ -70,72 +185,69 @@ impl MessageService {
         text: Option<String>,
         attachments: Vec<AttachmentInput>,
         reply_to: Option<ReplyToInput>,
+        slow_mode: &SlowModeLimiter,
+        flood_guard: &FloodGuard,
+        attachment_limits: AttachmentLimits,
     ) -> AppResult<MessageResponse> {
         // Check access
-        if !ChatService::is_participant(db, chat_id, sender_id).await? {
-            return Err(AppError::AccessDenied);
-        }
+        ChatService::ensure_can_post(db, chat_id, sender_id).await?;
 
         // Validate message
         if text.as_ref().map(|t| t.trim().is_empty()).unwrap_or(true) && attachments.is_empty() {
             return Err(AppError::EmptyMessage);
         }
 
-        let reply_to_id = reply_to.as_ref().map(|r| r.id);
+        let uploads =
+            Self::resolve_attachments(db, sender_id, &attachments, attachment_limits).await?;
 
         // Validate reply_to message belongs to the same chat to prevent cross-chat leakage.
-        if let Some(reply_id) = reply_to_id {
-            let exists: Option<(Uuid,)> =
-                sqlx::query_as("SELECT id FROM messages WHERE id = $1 AND chat_id = $2")
-                    .bind(reply_id)
-                    .bind(chat_id)
-                    .fetch_optional(&db.pool)
-                    .await?;
-
-            if exists.is_none() {
-                return Err(AppError::BadRequest(
-                    "Invalid replyTo: message not found in this chat".to_string(),
-                ));
-            }
-        }
+        let reply = match &reply_to {
+            Some(r) => Some(Self::reply_target(db, chat_id, r.id).await?),
+            None => None,
+        };
+
+        // Enforce chat slow mode and flood protection (admins bypass)
+        slow_mode.check_and_mark(db, chat_id, sender_id).await?;
+        flood_guard.check(db, chat_id, sender_id, text.as_deref()).await?;
+
+        // The message and its outbox event commit together, so a stored
+        // message is always delivered (see `OutboxService`)
+        let mut tx = db.pool.begin().await?;
 
         // Create message with sender_type = 'user'
         let message: Message = sqlx::query_as(
             r#"
-            INSERT INTO messages (chat_id, sender_id, sender_type, text, reply_to_id, delivery_status)
-            VALUES ($1, $2, 'user', $3, $4, 'sent')
+            INSERT INTO messages (chat_id, sender_id, sender_type, text, reply_to_id,
+                                  reply_author_id, reply_author_name, reply_snippet, delivery_status, delete_at,
+                                  encrypted)
+            VALUES ($1, $2, 'user', $3, $4, $5, $6, $7, 'sent', (
+                SELECT NOW() + make_interval(secs => disappear_after_seconds)
+                FROM chats WHERE id = $1 AND disappear_after_seconds > 0
+            ), (SELECT e2ee FROM chats WHERE id = $1))
             RETURNING *
             "#,
         )
         .bind(chat_id)
         .bind(sender_id)
         .bind(&text)
-        .bind(reply_to_id)
-        .fetch_one(&db.pool)
+        .bind(reply.as_ref().map(|r| r.id))
+        .bind(reply.as_ref().map(|r| r.author_id))
+        .bind(reply.as_ref().map(|r| r.author_name.as_str()))
+        .bind(reply.as_ref().map(|r| r.snippet.as_str()))
+        .fetch_one(&mut *tx)
         .await?;
 
         // Add attachments
-        for att in attachments {
-            sqlx::query(
-                r#"
-                INSERT INTO attachments (message_id, type, name, size, url, mime_type)
-                VALUES ($1, $2, $3, $4, $5, $6)
-                "#,
-            )
-            .bind(message.id)
-            .bind(&att.attachment_type)
-            .bind(&att.name)
-            .bind(att.size)
-            .bind(&att.url)
-            .bind(&att.mime_type)
-            .execute(&db.pool)
-            .await?;
+        Self::insert_attachments(&mut tx, message.id, uploads).await?;
+        if !message.encrypted {
+            Self::insert_mentions(&mut tx, chat_id, message.id, sender_id, text.as_deref())
+                .await?;
         }
 
         // Update chat timestamp
         sqlx::query("UPDATE chats SET updated_at = NOW() WHERE id = $1")
             .bind(chat_id)
-            .execute(&db.pool)
+            .execute(&mut *tx)
             .await?;
 
         // Increment unread count for other participants
@@ -148,206 +260,1429 @@ impl MessageService {
         )
         .bind(chat_id)
         .bind(sender_id)
-        .execute(&db.pool)
+        .execute(&mut *tx)
         .await?;
 
+        OutboxService::enqueue(&mut tx, EVENT_NEW_MESSAGE, chat_id, message.id).await?;
+        tx.commit().await?;
+
         Self::build_message_response(db, message).await
     }
 
-    /// Send a message from a bot.
-    ///
-    /// This function creates a message with sender_type = 'bot'.
-    /// Unlike send_message, it doesn't check if the sender is a chat participant
-    /// because bots use their own subscription system (bot_chats table).
-    ///
-    /// # Arguments
-    /// * `db` - Database connection
-    /// * `chat_id` - The chat to send the message to
-    /// * `bot_id` - The bot's UUID
-    /// * `text` - The message text
-    /// * `reply_to_id` - Optional message ID to reply to
-    ///
-    /// # Returns
-    /// * `AppResult<MessageResponse>` - The created message
+    /// Post a poll: a message with the question as its text plus the poll
+    /// and its options, committed together
     ///
-    /// # Requirements
-    /// - 7.3: Mark the sender as Bot(bot_id)
-    pub async fn send_bot_message(
+    /// The caller delivers it like any new message (`deliver_or_defer`).
+    pub async fn create_poll(
         db: &Database,
         chat_id: Uuid,
-        bot_id: Uuid,
-        text: String,
-        reply_to_id: Option<Uuid>,
+        user_id: Uuid,
+        question: &str,
+        options: &[String],
+        multiple_choice: bool,
+        anonymous: bool,
     ) -> AppResult<MessageResponse> {
-        tracing::info!("Bot {} sending message to chat {}", bot_id, chat_id);
-
-        // Validate message
-        if text.trim().is_empty() {
-            tracing::warn!("Bot {} tried to send empty message", bot_id);
-            return Err(AppError::EmptyMessage);
+        ChatService::ensure_can_post(db, chat_id, user_id).await?;
+        // The server would have to see the question and options in plaintext
+        if ChatService::is_e2ee(db, chat_id).await? {
+            return Err(AppError::BadRequest(
+                "Polls are not available in end-to-end encrypted chats".to_string(),
+            ));
         }
+        let (question, options) = normalize_poll(question, options)?;
+
+        let mut tx = db.pool.begin().await?;
 
-        tracing::debug!("Inserting bot message into database...");
-        // Create message with sender_type = 'bot'
         let message: Message = sqlx::query_as(
             r#"
-            INSERT INTO messages (chat_id, sender_id, sender_type, text, reply_to_id, delivery_status)
-            VALUES ($1, $2, 'bot', $3, $4, 'sent')
+            INSERT INTO messages (chat_id, sender_id, sender_type, text, delivery_status, delete_at)
+            VALUES ($1, $2, 'user', $3, 'sent', (
+                SELECT NOW() + make_interval(secs => disappear_after_seconds)
+                FROM chats WHERE id = $1 AND disappear_after_seconds > 0
+            ))
             RETURNING *
             "#,
         )
         .bind(chat_id)
-        .bind(bot_id)
-        .bind(&text)
-        .bind(reply_to_id)
-        .fetch_one(&db.pool)
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to insert bot message: {:?}", e);
-            e
-        })?;
+        .bind(user_id)
+        .bind(&question)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let poll_id: Uuid = sqlx::query_scalar(
+            r#"
+            INSERT INTO polls (message_id, chat_id, creator_id, question,
+                               multiple_choice, anonymous)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id
+            "#,
+        )
+        .bind(message.id)
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(&question)
+        .bind(multiple_choice)
+        .bind(anonymous)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let positions: Vec<i32> = (0..options.len() as i32).collect();
+        sqlx::query(
+            r#"
+            INSERT INTO poll_options (poll_id, position, text)
+            SELECT $1, position, text FROM UNNEST($2::int[], $3::text[]) AS o(position, text)
+            "#,
+        )
+        .bind(poll_id)
+        .bind(&positions)
+        .bind(&options)
+        .execute(&mut *tx)
+        .await?;
 
-        tracing::debug!(
-            "Message inserted with id {}, updating chat timestamp...",
-            message.id
-        );
-        // Update chat timestamp
         sqlx::query("UPDATE chats SET updated_at = NOW() WHERE id = $1")
             .bind(chat_id)
-            .execute(&db.pool)
-            .await
-            .map_err(|e| {
-                tracing::error!("Failed to update chat timestamp: {:?}", e);
-                e
-            })?;
+            .execute(&mut *tx)
+            .await?;
 
-        tracing::debug!("Incrementing unread counts...");
-        // Increment unread count for all participants (bots don't have unread counts)
         sqlx::query(
             r#"
             UPDATE chat_participants
             SET unread_count = unread_count + 1
-            WHERE chat_id = $1
+            WHERE chat_id = $1 AND user_id != $2
             "#,
         )
         .bind(chat_id)
-        .execute(&db.pool)
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to increment unread counts: {:?}", e);
-            e
-        })?;
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
 
-        tracing::debug!("Building message response...");
-        let response = Self::build_message_response(db, message)
-            .await
-            .map_err(|e| {
-                tracing::error!("Failed to build message response: {:?}", e);
-                e
-            })?;
+        OutboxService::enqueue(&mut tx, EVENT_NEW_MESSAGE, chat_id, message.id).await?;
+        tx.commit().await?;
 
-        tracing::info!("Bot message sent successfully with id {}", response.id);
-        Ok(response)
+        Self::build_message_response(db, message).await
     }
 
-    pub async fn edit_message(
+    /// Store a server-authored message (`sender_type = 'system'`) in a chat
+    ///
+    /// `author_id` is the user on whose behalf the server posts it; they need
+    /// not be a participant. System messages skip validation, mentions and
+    /// the outbox, and don't count as unread; the caller broadcasts them.
+    pub async fn create_system_message(
         db: &Database,
         chat_id: Uuid,
-        message_id: Uuid,
-        user_id: Uuid,
+        author_id: Uuid,
         text: &str,
     ) -> AppResult<MessageResponse> {
-        // Check access
-        if !ChatService::is_participant(db, chat_id, user_id).await? {
-            return Err(AppError::AccessDenied);
-        }
+        Self::insert_system_message(db, chat_id, author_id, text, None).await
+    }
 
-        let message: Message =
-            sqlx::query_as("SELECT * FROM messages WHERE id = $1 AND chat_id = $2")
-                .bind(message_id)
-                .bind(chat_id)
-                .fetch_optional(&db.pool)
-                .await?
-                .ok_or(AppError::MessageNotFound)?;
+    /// Record a chat event as a system message posted on behalf of `actor_id`
+    ///
+    /// The text describes the event with the actor's name, for clients that
+    /// don't render `systemEvent`. The caller broadcasts the message.
+    pub async fn create_system_event(
+        db: &Database,
+        chat_id: Uuid,
+        actor_id: Uuid,
+        event: SystemEventType,
+        metadata: serde_json::Value,
+    ) -> AppResult<MessageResponse> {
+        let actor_name: String = sqlx::query_scalar("SELECT name FROM users WHERE id = $1")
+            .bind(actor_id)
+            .fetch_optional(&db.pool)
+            .await?
+            .ok_or(AppError::UserNotFound)?;
 
-        if message.sender_id != user_id {
-            return Err(AppError::NotMessageOwner);
-        }
+        let text = event.describe(&actor_name);
+        Self::insert_system_message(db, chat_id, actor_id, &text, Some((event, metadata))).await
+    }
 
-        let updated: Message = sqlx::query_as(
+    async fn insert_system_message(
+        db: &Database,
+        chat_id: Uuid,
+        author_id: Uuid,
+        text: &str,
+        event: Option<(SystemEventType, serde_json::Value)>,
+    ) -> AppResult<MessageResponse> {
+        let (event, metadata) = event.unzip();
+        let message: Message = sqlx::query_as(
             r#"
-            UPDATE messages SET text = $1, is_edited = true, updated_at = NOW()
-            WHERE id = $2
+            INSERT INTO messages (chat_id, sender_id, sender_type, text, delivery_status,
+                                  system_event, system_metadata)
+            SELECT id, $2, 'system', $3, 'sent', $4, $5 FROM chats WHERE id = $1
             RETURNING *
             "#,
         )
+        .bind(chat_id)
+        .bind(author_id)
         .bind(text)
-        .bind(message_id)
-        .fetch_one(&db.pool)
-        .await?;
+        .bind(event.map(SystemEventType::as_str))
+        .bind(metadata.map(|metadata| metadata.to_string()))
+        .fetch_optional(&db.pool)
+        .await?
+        .ok_or(AppError::ChatNotFound)?;
 
-        Self::build_message_response(db, updated).await
+        sqlx::query("UPDATE chats SET updated_at = NOW() WHERE id = $1")
+            .bind(chat_id)
+            .execute(&db.pool)
+            .await?;
+
+        Self::build_message_response(db, message).await
     }
 
-    pub async fn delete_message(
+    /// Forward a message into each of `target_chat_ids`
+    ///
+    /// The sender needs to see the source message and be a participant of
+    /// every target. Each target is checked on its own (participation, e2ee,
+    /// slow mode, flood protection) and the ones that pass get their copy in
+    /// one transaction; the others are reported in their result without
+    /// aborting the rest. Copies keep the text, formatting and attachments,
+    /// and credit the original author (only by name when their `forwards`
+    /// privacy setting is off). Results follow the order of the targets.
+    pub async fn forward_message(
         db: &Database,
-        chat_id: Uuid,
         message_id: Uuid,
-        user_id: Uuid,
-    ) -> AppResult<()> {
-        // Check access
-        if !ChatService::is_participant(db, chat_id, user_id).await? {
+        sender_id: Uuid,
+        target_chat_ids: &[Uuid],
+        slow_mode: &SlowModeLimiter,
+        flood_guard: &FloodGuard,
+    ) -> AppResult<Vec<ForwardResult>> {
+        let target_chat_ids = forward_targets(target_chat_ids)?;
+
+        let source: Message = sqlx::query_as(
+            "SELECT * FROM messages WHERE id = $1 AND deleted_at IS NULL",
+        )
+        .bind(message_id)
+        .fetch_optional(&db.pool)
+        .await?
+        .ok_or(AppError::MessageNotFound)?;
+        if !ChatService::is_participant(db, source.chat_id, sender_id).await? {
             return Err(AppError::AccessDenied);
         }
+        // Ciphertext is only readable with the source chat's keys
+        if source.encrypted {
+            return Err(AppError::BadRequest(
+                "End-to-end encrypted messages cannot be forwarded".to_string(),
+            ));
+        }
+        if PollService::for_message(db, source.id).await?.is_some() {
+            return Err(AppError::BadRequest("Polls cannot be forwarded".to_string()));
+        }
+        let (author_id, author_name) = Self::forward_author(db, &source).await?;
 
-        let message: Message =
-            sqlx::query_as("SELECT * FROM messages WHERE id = $1 AND chat_id = $2")
-                .bind(message_id)
-                .bind(chat_id)
-                .fetch_optional(&db.pool)
-                .await?
-                .ok_or(AppError::MessageNotFound)?;
-
-        if message.sender_id != user_id {
-            return Err(AppError::NotMessageOwner);
+        let mut results: Vec<ForwardResult> = Vec::with_capacity(target_chat_ids.len());
+        let mut accepted = Vec::new();
+        for &chat_id in &target_chat_ids {
+            match Self::check_forward_target(db, chat_id, sender_id, &source, slow_mode, flood_guard)
+                .await
+            {
+                Ok(()) => accepted.push(chat_id),
+                Err(e) => results.push(ForwardResult {
+                    chat_id,
+                    message: None,
+                    error: Some(forward_error(&e)),
+                }),
+            }
         }
 
-        sqlx::query("DELETE FROM messages WHERE id = $1")
-            .bind(message_id)
-            .execute(&db.pool)
-            .await?;
+        let mut copies = Vec::with_capacity(accepted.len());
+        if !accepted.is_empty() {
+            let stored =
+                Self::insert_forwards(db, &source, sender_id, &accepted, author_id, &author_name)
+                    .await;
+            match stored {
+                Ok(messages) => {
+                    for message in messages {
+                        copies.push(Self::build_message_response(db, message).await?);
+                    }
+                }
+                Err(e) => {
+                    tracing::error!("Failed to forward message {}: {}", source.id, e);
+                    let error = forward_error(&e);
+                    results.extend(accepted.iter().map(|&chat_id| ForwardResult {
+                        chat_id,
+                        message: None,
+                        error: Some(error.clone()),
+                    }));
+                }
+            }
+        }
+        results.extend(copies.into_iter().map(|message| ForwardResult {
+            chat_id: message.chat_id,
+            message: Some(message),
+            error: None,
+        }));
 
-        Ok(())
+        let order: HashMap<Uuid, usize> = target_chat_ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (*id, i))
+            .collect();
+        results.sort_by_key(|r| order.get(&r.chat_id).copied());
+        Ok(results)
     }
 
-    pub async fn clear_chat_messages(db: &Database, chat_id: Uuid, user_id: Uuid) -> AppResult<()> {
-        // Check access - user must be a participant
-        if !ChatService::is_participant(db, chat_id, user_id).await? {
-            return Err(AppError::AccessDenied);
+    /// Forward a message and deliver every copy like a new message
+    pub async fn forward_and_deliver(
+        state: &AppState,
+        message_id: Uuid,
+        sender_id: Uuid,
+        target_chat_ids: &[Uuid],
+    ) -> AppResult<Vec<ForwardResult>> {
+        state.ensure_writable()?;
+
+        let results = Self::forward_message(
+            &state.db,
+            message_id,
+            sender_id,
+            target_chat_ids,
+            &state.slow_mode,
+            &state.flood_guard,
+        )
+        .await?;
+
+        for message in results.iter().filter_map(|r| r.message.as_ref()) {
+            Self::deliver_or_defer(state, message).await;
         }
+        Ok(results)
+    }
 
-        // Delete all messages in the chat
-        sqlx::query("DELETE FROM messages WHERE chat_id = $1")
-            .bind(chat_id)
-            .execute(&db.pool)
-            .await?;
+    /// Author credited on a forward of `source`: the original author for a
+    /// forward of a forward, otherwise its sender
+    ///
+    /// The id is dropped when the author turned off linking forwards to them.
+    async fn forward_author(db: &Database, source: &Message) -> AppResult<(Option<Uuid>, String)> {
+        if let Some(name) = &source.forward_author_name {
+            return Ok((source.forward_author_id, name.clone()));
+        }
 
-        // Reset unread count for all participants
-        sqlx::query("UPDATE chat_participants SET unread_count = 0 WHERE chat_id = $1")
-            .bind(chat_id)
-            .execute(&db.pool)
-            .await?;
+        let name = Self::sender_name(db, source).await?;
+        if source.sender_type.as_deref() == Some("bot") {
+            return Ok((Some(source.sender_id), name));
+        }
+        let forwards_enabled: bool = sqlx::query_scalar(
+            "SELECT COALESCE((SELECT forwards_enabled FROM user_settings WHERE user_id = $1), TRUE)",
+        )
+        .bind(source.sender_id)
+        .fetch_one(&db.pool)
+        .await?;
+        Ok((forwards_enabled.then_some(source.sender_id), name))
+    }
 
+    /// Check `sender_id` may post a forward of `source` in `chat_id`
+    async fn check_forward_target(
+        db: &Database,
+        chat_id: Uuid,
+        sender_id: Uuid,
+        source: &Message,
+        slow_mode: &SlowModeLimiter,
+        flood_guard: &FloodGuard,
+    ) -> AppResult<()> {
+        ChatService::ensure_can_post(db, chat_id, sender_id).await?;
+        // Clients would expect ciphertext there
+        if ChatService::is_e2ee(db, chat_id).await? {
+            return Err(AppError::BadRequest(
+                "Messages cannot be forwarded into end-to-end encrypted chats".to_string(),
+            ));
+        }
+        slow_mode.check_and_mark(db, chat_id, sender_id).await?;
+        flood_guard.check(db, chat_id, sender_id, source.text.as_deref()).await?;
         Ok(())
     }
 
-    pub async fn toggle_reaction(
+    /// Store a copy of `source` in each chat, with its attachments, entities
+    /// and outbox events, in one transaction
+    async fn insert_forwards(
+        db: &Database,
+        source: &Message,
+        sender_id: Uuid,
+        chat_ids: &[Uuid],
+        author_id: Option<Uuid>,
+        author_name: &str,
+    ) -> AppResult<Vec<Message>> {
+        let mut tx = db.pool.begin().await?;
+        let mut messages = Vec::with_capacity(chat_ids.len());
+
+        for &chat_id in chat_ids {
+            let message: Message = sqlx::query_as(
+                r#"
+                INSERT INTO messages (chat_id, sender_id, sender_type, text, delivery_status,
+                                      delete_at, forwarded_from_id, forward_author_id,
+                                      forward_author_name)
+                VALUES ($1, $2, 'user', $3, 'sent', (
+                    SELECT NOW() + make_interval(secs => disappear_after_seconds)
+                    FROM chats WHERE id = $1 AND disappear_after_seconds > 0
+                ), $4, $5, $6)
+                RETURNING *
+                "#,
+            )
+            .bind(chat_id)
+            .bind(sender_id)
+            .bind(&source.text)
+            .bind(source.id)
+            .bind(author_id)
+            .bind(author_name)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            // Copies share the stored files, which stay until no attachment uses them
+            sqlx::query(
+                r#"
+                INSERT INTO attachments (message_id, type, name, size, url, mime_type, duration,
+                                         upload_id, width, height)
+                SELECT $1, type, name, size, url, mime_type, duration, upload_id, width, height
+                FROM attachments WHERE message_id = $2
+                ORDER BY created_at
+                "#,
+            )
+            .bind(message.id)
+            .bind(source.id)
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query(
+                r#"
+                INSERT INTO message_entities
+                    (message_id, position, type, start_offset, length, url, language)
+                SELECT $1, position, type, start_offset, length, url, language
+                FROM message_entities WHERE message_id = $2
+                "#,
+            )
+            .bind(message.id)
+            .bind(source.id)
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query("UPDATE chats SET updated_at = NOW() WHERE id = $1")
+                .bind(chat_id)
+                .execute(&mut *tx)
+                .await?;
+
+            sqlx::query(
+                r#"
+                UPDATE chat_participants
+                SET unread_count = unread_count + 1
+                WHERE chat_id = $1 AND user_id != $2
+                "#,
+            )
+            .bind(chat_id)
+            .bind(sender_id)
+            .execute(&mut *tx)
+            .await?;
+
+            OutboxService::enqueue(&mut tx, EVENT_NEW_MESSAGE, chat_id, message.id).await?;
+            messages.push(message);
+        }
+
+        tx.commit().await?;
+        Ok(messages)
+    }
+
+    /// Reply in the thread of `root_id`
+    ///
+    /// The root must be in the same chat and not deleted; replying to a
+    /// thread reply posts in that reply's thread. Thread replies stay out of
+    /// the main timeline, so they don't bump the chat or unread counts.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn reply_in_thread(
+        db: &Database,
+        chat_id: Uuid,
+        root_id: Uuid,
+        sender_id: Uuid,
+        text: Option<String>,
+        attachments: Vec<AttachmentInput>,
+        slow_mode: &SlowModeLimiter,
+        flood_guard: &FloodGuard,
+        attachment_limits: AttachmentLimits,
+    ) -> AppResult<MessageResponse> {
+        ChatService::ensure_can_post(db, chat_id, sender_id).await?;
+
+        if text.as_ref().map(|t| t.trim().is_empty()).unwrap_or(true) && attachments.is_empty() {
+            return Err(AppError::EmptyMessage);
+        }
+
+        let uploads =
+            Self::resolve_attachments(db, sender_id, &attachments, attachment_limits).await?;
+
+        let root: Message =
+            sqlx::query_as("SELECT * FROM messages WHERE id = $1 AND chat_id = $2")
+                .bind(root_id)
+                .bind(chat_id)
+                .fetch_optional(&db.pool)
+                .await?
+                .ok_or_else(|| {
+                    AppError::BadRequest(
+                        "Invalid thread root: message not found in this chat".to_string(),
+                    )
+                })?;
+        let root_id = root.thread_root_id.unwrap_or(root.id);
+        if root.thread_root_id.is_none() && root.deleted_at.is_some() {
+            return Err(AppError::BadRequest("Thread root was deleted".to_string()));
+        }
+
+        slow_mode.check_and_mark(db, chat_id, sender_id).await?;
+        flood_guard.check(db, chat_id, sender_id, text.as_deref()).await?;
+
+        let mut tx = db.pool.begin().await?;
+        let message: Message = sqlx::query_as(
+            r#"
+            INSERT INTO messages (chat_id, sender_id, sender_type, text, thread_root_id, delivery_status, delete_at,
+                                  encrypted)
+            VALUES ($1, $2, 'user', $3, $4, 'sent', (
+                SELECT NOW() + make_interval(secs => disappear_after_seconds)
+                FROM chats WHERE id = $1 AND disappear_after_seconds > 0
+            ), (SELECT e2ee FROM chats WHERE id = $1))
+            RETURNING *
+            "#,
+        )
+        .bind(chat_id)
+        .bind(sender_id)
+        .bind(&text)
+        .bind(root_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        Self::insert_attachments(&mut tx, message.id, uploads).await?;
+        if !message.encrypted {
+            Self::insert_mentions(&mut tx, chat_id, message.id, sender_id, text.as_deref())
+                .await?;
+        }
+
+        sqlx::query("UPDATE messages SET reply_count = reply_count + 1 WHERE id = $1")
+            .bind(root_id)
+            .execute(&mut *tx)
+            .await?;
+
+        OutboxService::enqueue(&mut tx, EVENT_NEW_MESSAGE, chat_id, message.id).await?;
+        tx.commit().await?;
+
+        Self::build_message_response(db, message).await
+    }
+
+    /// Reply in a thread and deliver the reply like any new message (see
+    /// `send_and_deliver`)
+    pub async fn reply_and_deliver(
+        state: &AppState,
+        chat_id: Uuid,
+        root_id: Uuid,
+        sender_id: Uuid,
+        text: Option<String>,
+        attachments: Vec<AttachmentInput>,
+    ) -> AppResult<MessageResponse> {
+        state.ensure_writable()?;
+
+        let text = normalize_message_text(
+            text.as_deref(),
+            !attachments.is_empty(),
+            state.config.max_message_bytes,
+        )?;
+
+        let message = Self::reply_in_thread(
+            &state.db,
+            chat_id,
+            root_id,
+            sender_id,
+            text,
+            attachments,
+            &state.slow_mode,
+            &state.flood_guard,
+            AttachmentLimits::from(&state.config),
+        )
+        .await?;
+
+        Self::deliver_or_defer(state, &message).await;
+        Ok(message)
+    }
+
+    /// Get a thread: the root plus up to `limit` replies after the `after` cursor
+    pub async fn get_thread(
+        db: &Database,
+        chat_id: Uuid,
+        root_id: Uuid,
+        user_id: Uuid,
+        limit: i64,
+        after: Option<Uuid>,
+    ) -> AppResult<ThreadResponse> {
+        if !ChatService::is_participant(db, chat_id, user_id).await? {
+            return Err(AppError::AccessDenied);
+        }
+
+        let root: Message = sqlx::query_as(
+            "SELECT * FROM messages WHERE id = $1 AND chat_id = $2 AND thread_root_id IS NULL",
+        )
+        .bind(root_id)
+        .bind(chat_id)
+        .fetch_optional(&db.pool)
+        .await?
+        .ok_or(AppError::MessageNotFound)?;
+
+        let limit = limit.clamp(1, MAX_THREAD_PAGE_SIZE);
+        let replies: Vec<Message> = sqlx::query_as(
+            r#"
+            SELECT * FROM messages
+            WHERE thread_root_id = $1
+              AND ($2::uuid IS NULL OR (created_at, id) > (
+                  SELECT created_at, id FROM messages WHERE id = $2
+              ))
+            ORDER BY created_at ASC, id ASC
+            LIMIT $3
+            "#,
+        )
+        .bind(root_id)
+        .bind(after)
+        .bind(limit + 1)
+        .fetch_all(&db.pool)
+        .await?;
+
+        let has_more = replies.len() > limit as usize;
+        let mut responses = vec![Self::build_message_response(db, root).await?];
+        for reply in replies.into_iter().take(limit as usize) {
+            responses.push(Self::build_message_response(db, reply).await?);
+        }
+        Self::attach_reaction_summaries(db, &mut responses, user_id).await?;
+
+        let root = responses.remove(0);
+        Ok(ThreadResponse {
+            root,
+            replies: responses,
+            has_more,
+        })
+    }
+
+    /// Full-text search within one chat's timeline
+    ///
+    /// Returns up to `limit` hits, newest first, older than the hit `before`
+    /// when given. Each hit carries its highlighted terms, its position in the
+    /// timeline and `context` messages on either side. Thread replies are not
+    /// searched.
+    pub async fn search_in_chat(
+        db: &Database,
+        chat_id: Uuid,
+        user_id: Uuid,
+        query: &str,
+        limit: i64,
+        before: Option<Uuid>,
+        context: i64,
+    ) -> AppResult<MessageSearchResponse> {
+        if !ChatService::is_participant(db, chat_id, user_id).await? {
+            return Err(AppError::AccessDenied);
+        }
+
+        let query: String = query.trim().nfc().collect();
+        if query.is_empty() {
+            return Err(AppError::BadRequest("Search query is empty".to_string()));
+        }
+        if query.chars().count() > MAX_SEARCH_QUERY_CHARS {
+            return Err(AppError::BadRequest(format!(
+                "Search query must be at most {} characters",
+                MAX_SEARCH_QUERY_CHARS
+            )));
+        }
+        let limit = limit.clamp(1, MAX_SEARCH_PAGE_SIZE);
+        let context = context.clamp(0, MAX_SEARCH_CONTEXT);
+
+        // Must match the expression of idx_messages_text_search
+        let matches: Vec<(Uuid, i64)> = sqlx::query_as(
+            r#"
+            SELECT m.id,
+                   (SELECT COUNT(*) FROM messages n
+                    WHERE n.chat_id = m.chat_id AND n.thread_root_id IS NULL
+                      AND n.created_at > m.created_at) AS position
+            FROM messages m
+            WHERE m.chat_id = $1 AND m.thread_root_id IS NULL AND NOT m.encrypted
+              AND to_tsvector('simple', COALESCE(m.text, '')) @@ plainto_tsquery('simple', $2)
+              AND ($3::uuid IS NULL OR m.created_at < (SELECT created_at FROM messages WHERE id = $3))
+            ORDER BY m.created_at DESC
+            LIMIT $4
+            "#,
+        )
+        .bind(chat_id)
+        .bind(&query)
+        .bind(before)
+        .bind(limit + 1)
+        .fetch_all(&db.pool)
+        .await?;
+
+        let has_more = matches.len() > limit as usize;
+        let ids: Vec<Uuid> = matches.iter().take(limit as usize).map(|(id, _)| *id).collect();
+        let found: Vec<Message> = sqlx::query_as("SELECT * FROM messages WHERE id = ANY($1)")
+            .bind(&ids)
+            .fetch_all(&db.pool)
+            .await?;
+
+        // Hits and their context are built into one list so reaction
+        // summaries can be filled in one query, then split back apart
+        let mut all = Vec::new();
+        let mut shapes = Vec::new();
+        for (id, position) in matches.into_iter().take(limit as usize) {
+            let Some(hit) = found.iter().find(|m| m.id == id).cloned() else {
+                continue;
+            };
+            let created_at = hit.created_at;
+
+            let mut earlier: Vec<Message> = sqlx::query_as(
+                r#"
+                SELECT * FROM messages
+                WHERE chat_id = $1 AND thread_root_id IS NULL AND created_at < $2
+                ORDER BY created_at DESC
+                LIMIT $3
+                "#,
+            )
+            .bind(chat_id)
+            .bind(created_at)
+            .bind(context)
+            .fetch_all(&db.pool)
+            .await?;
+            earlier.reverse();
+            let later: Vec<Message> = sqlx::query_as(
+                r#"
+                SELECT * FROM messages
+                WHERE chat_id = $1 AND thread_root_id IS NULL AND created_at > $2
+                ORDER BY created_at ASC
+                LIMIT $3
+                "#,
+            )
+            .bind(chat_id)
+            .bind(created_at)
+            .bind(context)
+            .fetch_all(&db.pool)
+            .await?;
+
+            shapes.push((position, earlier.len(), later.len()));
+            for msg in earlier.into_iter().chain(std::iter::once(hit)).chain(later) {
+                all.push(Self::build_message_response(db, msg).await?);
+            }
+        }
+        Self::attach_reaction_summaries(db, &mut all, user_id).await?;
+
+        let mut all = all.into_iter();
+        let mut hits = Vec::with_capacity(shapes.len());
+        for (position, before_count, after_count) in shapes {
+            let before: Vec<MessageResponse> = all.by_ref().take(before_count).collect();
+            let Some(message) = all.next() else {
+                break;
+            };
+            let after: Vec<MessageResponse> = all.by_ref().take(after_count).collect();
+            hits.push(MessageSearchHit {
+                highlights: highlight_ranges(message.text.as_deref().unwrap_or_default(), &query),
+                message,
+                position,
+                before,
+                after,
+            });
+        }
+
+        Ok(MessageSearchResponse { hits, has_more })
+    }
+
+    /// Send a user message and deliver it: broadcast `new_message` to the other
+    /// participants and hand it to subscribed bots.
+    ///
+    /// Shared by the HTTP, WebSocket and QUIC send paths so they all apply the
+    /// same validation (content normalization, participation, reply target,
+    /// slow mode). Delivery happens right away; if it doesn't finish, the
+    /// outbox relay retries it.
+    pub async fn send_and_deliver(
+        state: &AppState,
+        chat_id: Uuid,
+        sender_id: Uuid,
+        text: Option<String>,
+        attachments: Vec<AttachmentInput>,
+        reply_to: Option<ReplyToInput>,
+    ) -> AppResult<MessageResponse> {
+        state.ensure_writable()?;
+
+        let text = normalize_message_text(
+            text.as_deref(),
+            !attachments.is_empty(),
+            state.config.max_message_bytes,
+        )?;
+
+        let message = Self::send_message(
+            &state.db,
+            chat_id,
+            sender_id,
+            text,
+            attachments,
+            reply_to,
+            &state.slow_mode,
+            &state.flood_guard,
+            AttachmentLimits::from(&state.config),
+        )
+        .await?;
+
+        Self::deliver_or_defer(state, &message).await;
+        Ok(message)
+    }
+
+    /// Deliver a message just stored with an outbox event; if delivery
+    /// doesn't finish, the outbox relay retries it
+    pub async fn deliver_or_defer(state: &AppState, message: &MessageResponse) {
+        match Self::deliver_new_message(state, message).await {
+            Ok(()) => {
+                if let Err(e) =
+                    OutboxService::mark_message_processed(&state.db, EVENT_NEW_MESSAGE, message.id)
+                        .await
+                {
+                    tracing::warn!("Failed to mark message {} delivered: {}", message.id, e);
+                }
+            }
+            Err(e) => tracing::warn!(
+                "Delivery of message {} failed, leaving it to the outbox relay: {}",
+                message.id,
+                e
+            ),
+        }
+    }
+
+    /// Deliver a message the outbox relay found undelivered
+    ///
+    /// Messages deleted in the meantime are skipped.
+    pub async fn redeliver_new_message(state: &AppState, message_id: Uuid) -> AppResult<()> {
+        let message: Option<Message> =
+            sqlx::query_as("SELECT * FROM messages WHERE id = $1 AND deleted_at IS NULL")
+                .bind(message_id)
+                .fetch_optional(&state.db.pool)
+                .await?;
+        let Some(message) = message else {
+            return Ok(());
+        };
+
+        let message = Self::build_message_response(&state.db, message).await?;
+        Self::deliver_new_message(state, &message).await
+    }
+
+    /// The realtime event announcing a new message: `new_message`, or for a
+    /// thread reply `thread_reply` with the root's reply count
+    pub async fn new_message_event(db: &Database, message: &MessageResponse) -> AppResult<ServerEvent> {
+        let Some(root_id) = message.thread_root_id else {
+            return Ok(ServerEvent::NewMessage {
+                message: message.clone(),
+            });
+        };
+        let reply_count: Option<i32> =
+            sqlx::query_scalar("SELECT reply_count FROM messages WHERE id = $1")
+                .bind(root_id)
+                .fetch_optional(&db.pool)
+                .await?;
+        Ok(ServerEvent::ThreadReply {
+            root_id,
+            reply_count: reply_count.unwrap_or_default(),
+            message: message.clone(),
+        })
+    }
+
+    /// Fan a stored message out: send `new_message` to the other
+    /// participants, queue and push it for those it didn't reach, notify
+    /// mentions, generate a link preview and hand it to subscribed bots
+    async fn deliver_new_message(state: &AppState, message: &MessageResponse) -> AppResult<()> {
+        let (chat_id, sender_id) = (message.chat_id, message.sender_id);
+
+        // Side effects are recorded on the outbox event as they complete, so
+        // a replayed event skips the ones already done
+        let mut effects =
+            OutboxService::effects(&state.db, EVENT_NEW_MESSAGE, message.id).await?;
+
+        // Send the new message to the other participants over QUIC or
+        // WebSocket, then queue and push it for those it didn't reach. The
+        // three are recorded together: which recipients were reached is only
+        // known right after the send
+        if !effects.is_done(EFFECT_REALTIME) {
+            let event = Self::new_message_event(&state.db, message).await?;
+            let participant_ids = ChatService::get_participant_ids(&state.db, chat_id).await?;
+            let reached =
+                WebSocketService::send_to_recipients(state, &event, &participant_ids, sender_id)
+                    .await;
+
+            // Recipients it reached are marked delivered; the others, offline
+            // or with no working connection, get it queued and pushed
+            let mut unreached = Vec::new();
+            for &recipient in participant_ids.iter().filter(|&&id| id != sender_id) {
+                if reached.contains(&recipient) {
+                    WebSocketService::mark_delivered(state, recipient, &[message.id]).await;
+                } else {
+                    unreached.push(recipient);
+                }
+            }
+
+            if !effects.is_done(EFFECT_OFFLINE_QUEUE) {
+                state.offline_queue.push(&unreached, message.id).await;
+            }
+            if !effects.is_done(EFFECT_PUSH) {
+                state.push.spawn_for_message(state.db.clone(), message, unreached);
+            }
+            effects
+                .complete_all(&state.db, &[EFFECT_REALTIME, EFFECT_OFFLINE_QUEUE, EFFECT_PUSH])
+                .await;
+        }
+
+        // Past this point the message reached clients; a failed lookup only
+        // costs the mention notifications
+        if !effects.is_done(EFFECT_MENTIONS) {
+            let targets = if message.mentions.is_empty() && message.mention_all.is_none() {
+                Vec::new()
+            } else {
+                Self::mention_targets(&state.db, message)
+                    .await
+                    .unwrap_or_else(|e| {
+                        tracing::warn!("Failed to load mentions of {}: {}", message.id, e);
+                        Vec::new()
+                    })
+            };
+            for target in targets {
+                let online = state.ws_manager.is_user_online(target.user_id).await
+                    || state.connection_manager.is_user_connected(target.user_id).await;
+                if target.should_notify(online) {
+                    WebSocketService::send_mentioned(
+                        &state.ws_manager,
+                        target.user_id,
+                        message.chat_id,
+                        message.id,
+                    )
+                    .await;
+                }
+            }
+            effects.complete(&state.db, EFFECT_MENTIONS).await;
+        }
+
+        // Ciphertext has no links to preview and no commands for bots
+        if message.encrypted {
+            return Ok(());
+        }
+
+        if !effects.is_done(EFFECT_LINK_PREVIEW) {
+            LinkPreviewService::spawn_for_message(
+                state.db.clone(),
+                state.ws_manager.clone(),
+                message,
+            );
+            effects.complete(&state.db, EFFECT_LINK_PREVIEW).await;
+        }
+
+        // Process message for bot commands (Requirements 6.1, 6.2)
+        // This will parse commands and dispatch to subscribed bots
+        if !effects.is_done(EFFECT_BOTS) {
+            match MessageProcessor::process_message(&state.db, &state.bot_dispatcher, message)
+                .await
+            {
+                Ok(_) => effects.complete(&state.db, EFFECT_BOTS).await,
+                Err(e) => tracing::error!("Failed to process message for bots: {}", e),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Delivery status of `message_id` as seen from recipient `for_user`
+    ///
+    /// `read` is only reported when `for_user` shares read receipts.
+    pub async fn delivery_status(
+        db: &Database,
+        message_id: Uuid,
+        for_user: Uuid,
+    ) -> AppResult<DeliveryStatus> {
+        let (delivered, read, read_receipts_enabled): (bool, bool, bool) = sqlx::query_as(
+            r#"
+            SELECT
+                EXISTS(SELECT 1 FROM message_deliveries WHERE message_id = $1 AND user_id = $2),
+                EXISTS(SELECT 1 FROM read_receipts WHERE message_id = $1 AND user_id = $2),
+                COALESCE((SELECT read_receipts_enabled FROM user_settings WHERE user_id = $2), TRUE)
+            FROM messages WHERE id = $1
+            "#,
+        )
+        .bind(message_id)
+        .bind(for_user)
+        .fetch_optional(&db.pool)
+        .await?
+        .ok_or(AppError::MessageNotFound)?;
+
+        Ok(DeliveryStatus::resolve(delivered, read, read_receipts_enabled))
+    }
+
+    /// Record that `user_id`'s client has `message_ids`
+    ///
+    /// Ignores messages the user sent, cannot see or has already read. Returns
+    /// `(message_id, chat_id, sender_id)` for messages newly delivered.
+    pub async fn record_delivered(
+        db: &Database,
+        message_ids: &[Uuid],
+        user_id: Uuid,
+    ) -> AppResult<Vec<(Uuid, Uuid, Uuid)>> {
+        if message_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let delivered = sqlx::query_as(
+            r#"
+            WITH inserted AS (
+                INSERT INTO message_deliveries (message_id, user_id)
+                SELECT m.id, $2 FROM messages m
+                JOIN chat_participants cp ON cp.chat_id = m.chat_id AND cp.user_id = $2
+                WHERE m.id = ANY($1) AND m.sender_id != $2
+                  AND NOT EXISTS (
+                      SELECT 1 FROM read_receipts r WHERE r.message_id = m.id AND r.user_id = $2
+                  )
+                ON CONFLICT DO NOTHING
+                RETURNING message_id
+            )
+            UPDATE messages m
+            SET delivery_status = CASE WHEN m.delivery_status = 'sent' THEN 'delivered' ELSE m.delivery_status END
+            FROM inserted i
+            WHERE m.id = i.message_id
+            RETURNING m.id, m.chat_id, m.sender_id
+            "#,
+        )
+        .bind(message_ids)
+        .bind(user_id)
+        .fetch_all(&db.pool)
+        .await?;
+
+        Ok(delivered)
+    }
+
+    /// Whether `user_id` shares read receipts (on unless they turned it off)
+    pub async fn read_receipts_enabled(db: &Database, user_id: Uuid) -> AppResult<bool> {
+        let enabled: Option<bool> =
+            sqlx::query_scalar("SELECT read_receipts_enabled FROM user_settings WHERE user_id = $1")
+                .bind(user_id)
+                .fetch_optional(&db.pool)
+                .await?;
+        Ok(enabled.unwrap_or(true))
+    }
+
+    /// Group participants who have read `message_id`
+    ///
+    /// A participant has read a message once their read pointer is at or past
+    /// it. The sender and participants who keep read receipts private are left
+    /// out. At most `MAX_SEEN_BY_USERS` are listed; `total` counts them all.
+    pub async fn seen_by(
+        db: &Database,
+        chat_id: Uuid,
+        message_id: Uuid,
+        user_id: Uuid,
+    ) -> AppResult<SeenByResponse> {
+        if !ChatService::is_participant(db, chat_id, user_id).await? {
+            return Err(AppError::AccessDenied);
+        }
+
+        let chat_type: String = sqlx::query_scalar("SELECT type FROM chats WHERE id = $1")
+            .bind(chat_id)
+            .fetch_optional(&db.pool)
+            .await?
+            .ok_or(AppError::ChatNotFound)?;
+        if chat_type != "group" {
+            return Err(AppError::BadRequest(
+                "Seen-by lists are only available in group chats".to_string(),
+            ));
+        }
+
+        let message: Message = sqlx::query_as("SELECT * FROM messages WHERE id = $1 AND chat_id = $2")
+            .bind(message_id)
+            .bind(chat_id)
+            .fetch_optional(&db.pool)
+            .await?
+            .ok_or(AppError::MessageNotFound)?;
+
+        #[derive(sqlx::FromRow)]
+        struct SeenByRow {
+            #[sqlx(flatten)]
+            user: SeenByUser,
+            total: i64,
+        }
+
+        let rows: Vec<SeenByRow> = sqlx::query_as(
+            r#"
+            SELECT u.id AS user_id, u.name, u.username, u.avatar, COUNT(*) OVER () AS total
+            FROM chat_participants cp
+            JOIN users u ON u.id = cp.user_id
+            LEFT JOIN user_settings us ON us.user_id = cp.user_id
+            WHERE cp.chat_id = $1 AND cp.user_id != $2
+              AND COALESCE(us.read_receipts_enabled, TRUE)
+              AND (cp.last_read_message_at, cp.last_read_message_id) >= ($3, $4)
+            ORDER BY cp.last_read_message_at ASC, u.name ASC
+            LIMIT $5
+            "#,
+        )
+        .bind(chat_id)
+        .bind(message.sender_id)
+        .bind(message.created_at)
+        .bind(message.id)
+        .bind(MAX_SEEN_BY_USERS)
+        .fetch_all(&db.pool)
+        .await?;
+
+        let total = rows.first().map_or(0, |row| row.total);
+        let users = rows.into_iter().map(|row| row.user).collect();
+        Ok(SeenByResponse { users, total })
+    }
+
+    /// Send a message from a bot.
+    ///
+    /// This function creates a message with sender_type = 'bot'.
+    /// Unlike send_message, it doesn't check if the sender is a chat participant
+    /// because bots use their own subscription system (bot_chats table).
+    ///
+    /// # Arguments
+    /// * `db` - Database connection
+    /// * `chat_id` - The chat to send the message to
+    /// * `bot_id` - The bot's UUID
+    /// * `text` - The message text
+    /// * `reply_to_id` - Optional message ID to reply to
+    /// * `entities` - Formatting entities over `text` (empty for plain text)
+    ///
+    /// # Returns
+    /// * `AppResult<MessageResponse>` - The created message
+    ///
+    /// # Requirements
+    /// - 7.3: Mark the sender as Bot(bot_id)
+    pub async fn send_bot_message(
+        db: &Database,
+        chat_id: Uuid,
+        bot_id: Uuid,
+        text: String,
+        reply_to_id: Option<Uuid>,
+        entities: &[MessageEntity],
+    ) -> AppResult<MessageResponse> {
+        tracing::info!("Bot {} sending message to chat {}", bot_id, chat_id);
+
+        // Validate message
+        if text.trim().is_empty() {
+            tracing::warn!("Bot {} tried to send empty message", bot_id);
+            return Err(AppError::EmptyMessage);
+        }
+
+        let reply = match reply_to_id {
+            Some(reply_id) => Some(Self::reply_target(db, chat_id, reply_id).await?),
+            None => None,
+        };
+
+        tracing::debug!("Inserting bot message into database...");
+        // Create message with sender_type = 'bot'
+        let message: Message = sqlx::query_as(
+            r#"
+            INSERT INTO messages (chat_id, sender_id, sender_type, text, reply_to_id,
+                                  reply_author_id, reply_author_name, reply_snippet, delivery_status, delete_at)
+            VALUES ($1, $2, 'bot', $3, $4, $5, $6, $7, 'sent', (
+                SELECT NOW() + make_interval(secs => disappear_after_seconds)
+                FROM chats WHERE id = $1 AND disappear_after_seconds > 0
+            ))
+            RETURNING *
+            "#,
+        )
+        .bind(chat_id)
+        .bind(bot_id)
+        .bind(&text)
+        .bind(reply.as_ref().map(|r| r.id))
+        .bind(reply.as_ref().map(|r| r.author_id))
+        .bind(reply.as_ref().map(|r| r.author_name.as_str()))
+        .bind(reply.as_ref().map(|r| r.snippet.as_str()))
+        .fetch_one(&db.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to insert bot message: {:?}", e);
+            e
+        })?;
+
+        Self::insert_entities(db, message.id, entities).await?;
+
+        tracing::debug!(
+            "Message inserted with id {}, updating chat timestamp...",
+            message.id
+        );
+        // Update chat timestamp
+        sqlx::query("UPDATE chats SET updated_at = NOW() WHERE id = $1")
+            .bind(chat_id)
+            .execute(&db.pool)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to update chat timestamp: {:?}", e);
+                e
+            })?;
+
+        tracing::debug!("Incrementing unread counts...");
+        // Increment unread count for all participants (bots don't have unread counts)
+        sqlx::query(
+            r#"
+            UPDATE chat_participants
+            SET unread_count = unread_count + 1
+            WHERE chat_id = $1
+            "#,
+        )
+        .bind(chat_id)
+        .execute(&db.pool)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to increment unread counts: {:?}", e);
+            e
+        })?;
+
+        tracing::debug!("Building message response...");
+        let response = Self::build_message_response(db, message)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to build message response: {:?}", e);
+                e
+            })?;
+
+        tracing::info!("Bot message sent successfully with id {}", response.id);
+        Ok(response)
+    }
+
+    /// Edit a message's text
+    ///
+    /// Only the sender may edit, and only within `edit_window_seconds` of
+    /// sending (0 means no limit).
+    pub async fn edit_message(
+        db: &Database,
+        chat_id: Uuid,
+        message_id: Uuid,
+        user_id: Uuid,
+        text: &str,
+        edit_window_seconds: u64,
+    ) -> AppResult<MessageResponse> {
+        // Check access
+        if !ChatService::is_participant(db, chat_id, user_id).await? {
+            return Err(AppError::AccessDenied);
+        }
+
+        let message: Message =
+            sqlx::query_as("SELECT * FROM messages WHERE id = $1 AND chat_id = $2")
+                .bind(message_id)
+                .bind(chat_id)
+                .fetch_optional(&db.pool)
+                .await?
+                .ok_or(AppError::MessageNotFound)?;
+
+        if message.deleted_at.is_some() {
+            return Err(AppError::MessageNotFound);
+        }
+
+        if message.is_system() {
+            return Err(AppError::SystemMessage("edited"));
+        }
+
+        if message.sender_id != user_id {
+            return Err(AppError::NotMessageOwner);
+        }
+
+        if !within_window(message.created_at, Utc::now(), edit_window_seconds) {
+            return Err(AppError::MessageWindowExpired("edited"));
+        }
+
+        let updated: Message = sqlx::query_as(
+            r#"
+            UPDATE messages SET text = $1, is_edited = true, updated_at = NOW()
+            WHERE id = $2
+            RETURNING *
+            "#,
+        )
+        .bind(text)
+        .bind(message_id)
+        .fetch_one(&db.pool)
+        .await?;
+
+        Self::build_message_response(db, updated).await
+    }
+
+    /// Replace the text and entities of one of a bot's own messages
+    ///
+    /// Unlike user edits there is no edit window, so bots can update a
+    /// message in response to its keyboard buttons at any time.
+    pub async fn edit_bot_message(
+        db: &Database,
+        chat_id: Uuid,
+        message_id: Uuid,
+        bot_id: Uuid,
+        text: &str,
+        entities: &[MessageEntity],
+    ) -> AppResult<MessageResponse> {
+        if text.trim().is_empty() {
+            return Err(AppError::EmptyMessage);
+        }
+
+        let updated: Message = sqlx::query_as(
+            r#"
+            UPDATE messages SET text = $1, is_edited = true, updated_at = NOW()
+            WHERE id = $2 AND chat_id = $3 AND sender_id = $4 AND sender_type = 'bot'
+              AND deleted_at IS NULL
+            RETURNING *
+            "#,
+        )
+        .bind(text)
+        .bind(message_id)
+        .bind(chat_id)
+        .bind(bot_id)
+        .fetch_optional(&db.pool)
+        .await?
+        .ok_or(AppError::MessageNotFound)?;
+
+        sqlx::query("DELETE FROM message_entities WHERE message_id = $1")
+            .bind(message_id)
+            .execute(&db.pool)
+            .await?;
+        Self::insert_entities(db, message_id, entities).await?;
+
+        Self::build_message_response(db, updated).await
+    }
+
+    /// Delete a message, leaving a tombstone
+    ///
+    /// The row is kept with `deleted_at` set and its text, attachments,
+    /// entities and link preview removed, so replies and threads pointing at
+    /// it still resolve. Senders may delete their own messages within
+    /// `delete_window_seconds` (0 means no limit); group admins may delete
+    /// anyone's at any time. Returns the tombstone.
+    pub async fn delete_message(
         db: &Database,
         chat_id: Uuid,
         message_id: Uuid,
         user_id: Uuid,
-        emoji: &str,
+        delete_window_seconds: u64,
     ) -> AppResult<MessageResponse> {
+        let role = ChatService::participant(db, chat_id, user_id)
+            .await?
+            .ok_or(AppError::AccessDenied)?
+            .role;
+
+        let message: Message =
+            sqlx::query_as("SELECT * FROM messages WHERE id = $1 AND chat_id = $2")
+                .bind(message_id)
+                .bind(chat_id)
+                .fetch_optional(&db.pool)
+                .await?
+                .ok_or(AppError::MessageNotFound)?;
+
+        if message.deleted_at.is_some() {
+            return Err(AppError::MessageNotFound);
+        }
+
+        if message.sender_id == user_id {
+            if !within_window(message.created_at, Utc::now(), delete_window_seconds) {
+                return Err(AppError::MessageWindowExpired("deleted"));
+            }
+        } else if role != "admin" {
+            return Err(AppError::NotMessageOwner);
+        }
+
+        let mut tx = db.pool.begin().await?;
+
+        let tombstone: Message = sqlx::query_as(
+            r#"
+            UPDATE messages SET text = NULL, is_pinned = false, deleted_at = NOW(), updated_at = NOW()
+            WHERE id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(message_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        for table in [
+            "attachments",
+            "message_entities",
+            "link_previews",
+            "reactions",
+            "message_mentions",
+        ] {
+            sqlx::query(&format!("DELETE FROM {} WHERE message_id = $1", table))
+                .bind(message_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        // Quotes of this message fall back to the deleted placeholder
+        sqlx::query("UPDATE messages SET reply_snippet = NULL WHERE reply_to_id = $1")
+            .bind(message_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Self::build_message_response(db, tombstone).await
+    }
+
+    /// Remove a message and everything attached to it (moderation)
+    ///
+    /// Unlike `delete_message` no tombstone is kept: the row, its
+    /// attachments and its thread replies are deleted, and quotes of it in
+    /// other messages lose their copied text. Returns the chat the message
+    /// was in and the stored attachment files, which the caller removes.
+    pub async fn purge_message(db: &Database, message_id: Uuid) -> AppResult<(Uuid, Vec<String>)> {
+        // Thread replies are removed by the cascade, so collect their files too
+        let files: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT u.stored_name FROM attachments a
+            JOIN uploads u ON u.id = a.upload_id
+            JOIN messages m ON m.id = a.message_id
+            WHERE m.id = $1 OR m.thread_root_id = $1
+            "#,
+        )
+        .bind(message_id)
+        .fetch_all(&db.pool)
+        .await?;
+
+        let mut tx = db.pool.begin().await?;
+
+        let (chat_id, thread_root_id): (Uuid, Option<Uuid>) = sqlx::query_as(
+            "DELETE FROM messages WHERE id = $1 RETURNING chat_id, thread_root_id",
+        )
+        .bind(message_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(AppError::MessageNotFound)?;
+
+        if let Some(root_id) = thread_root_id {
+            sqlx::query(
+                "UPDATE messages SET reply_count = GREATEST(reply_count - 1, 0) WHERE id = $1",
+            )
+            .bind(root_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query(
+            "UPDATE messages SET reply_snippet = NULL, reply_author_name = NULL WHERE reply_to_id = $1",
+        )
+        .bind(message_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok((chat_id, files))
+    }
+
+    pub async fn clear_chat_messages(db: &Database, chat_id: Uuid, user_id: Uuid) -> AppResult<()> {
+        // Check access - user must be a participant
+        if !ChatService::is_participant(db, chat_id, user_id).await? {
+            return Err(AppError::AccessDenied);
+        }
+
+        // Delete all messages in the chat
+        sqlx::query("DELETE FROM messages WHERE chat_id = $1")
+            .bind(chat_id)
+            .execute(&db.pool)
+            .await?;
+
+        // Reset unread count for all participants
+        sqlx::query("UPDATE chat_participants SET unread_count = 0 WHERE chat_id = $1")
+            .bind(chat_id)
+            .execute(&db.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Add a reaction, or remove it if the user already reacted with it
+    ///
+    /// `emoji` is a standard emoji or a custom emoji's `:shortcode:`.
+    pub async fn toggle_reaction(
+        db: &Database,
+        chat_id: Uuid,
+        message_id: Uuid,
+        user_id: Uuid,
+        emoji: &str,
+    ) -> AppResult<ReactionChange> {
         // Check access
         if !ChatService::is_participant(db, chat_id, user_id).await? {
             return Err(AppError::AccessDenied);
@@ -361,6 +1696,13 @@ impl MessageService {
                 .await?
                 .ok_or(AppError::MessageNotFound)?;
 
+        if message.is_system() {
+            return Err(AppError::SystemMessage("reacted to"));
+        }
+
+        // `:shortcode:` must name a custom emoji usable in this chat
+        let custom = CustomEmojiService::resolve_reaction(db, chat_id, emoji).await?;
+
         // Check if reaction exists
         let existing: Option<Reaction> = sqlx::query_as(
             "SELECT * FROM reactions WHERE message_id = $1 AND user_id = $2 AND emoji = $3",
@@ -383,67 +1725,562 @@ impl MessageService {
             .await?;
         } else {
             // Add reaction
-            sqlx::query("INSERT INTO reactions (message_id, user_id, emoji) VALUES ($1, $2, $3)")
+            sqlx::query(
+                "INSERT INTO reactions (message_id, user_id, emoji, custom_emoji_id) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(message_id)
+            .bind(user_id)
+            .bind(emoji)
+            .bind(custom.map(|c| c.id))
+            .execute(&db.pool)
+            .await?;
+        }
+
+        Ok(ReactionChange {
+            message: Self::build_message_response(db, message).await?,
+            added: existing.is_none(),
+        })
+    }
+
+    /// Current state of a message, as sent in `reaction_updated` events
+    pub async fn get_message_response(
+        db: &Database,
+        chat_id: Uuid,
+        message_id: Uuid,
+    ) -> AppResult<MessageResponse> {
+        let message: Message =
+            sqlx::query_as("SELECT * FROM messages WHERE id = $1 AND chat_id = $2")
+                .bind(message_id)
+                .bind(chat_id)
+                .fetch_optional(&db.pool)
+                .await?
+                .ok_or(AppError::MessageNotFound)?;
+
+        Self::build_message_response(db, message).await
+    }
+
+    /// Hide a message from the user's own history
+    ///
+    /// Private: nobody is notified and other participants keep seeing it;
+    /// replies and threads can still reference it.
+    pub async fn hide_for_user(db: &Database, message_id: Uuid, user_id: Uuid) -> AppResult<()> {
+        let chat_id: Uuid = sqlx::query_scalar("SELECT chat_id FROM messages WHERE id = $1")
+            .bind(message_id)
+            .fetch_optional(&db.pool)
+            .await?
+            .ok_or(AppError::MessageNotFound)?;
+
+        if !ChatService::is_participant(db, chat_id, user_id).await? {
+            return Err(AppError::AccessDenied);
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO hidden_messages (user_id, message_id) VALUES ($1, $2)
+            ON CONFLICT (user_id, message_id) DO NOTHING
+            "#,
+        )
+        .bind(user_id)
+        .bind(message_id)
+        .execute(&db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Show a message the user hid again
+    pub async fn unhide_for_user(db: &Database, message_id: Uuid, user_id: Uuid) -> AppResult<()> {
+        let result =
+            sqlx::query("DELETE FROM hidden_messages WHERE user_id = $1 AND message_id = $2")
+                .bind(user_id)
                 .bind(message_id)
+                .execute(&db.pool)
+                .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Hidden message not found".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Save a message to the user's "Saved Messages"
+    ///
+    /// Private: nobody else is notified. Saving an already saved message
+    /// keeps its original `saved_at`.
+    pub async fn save_message(
+        db: &Database,
+        message_id: Uuid,
+        user_id: Uuid,
+    ) -> AppResult<SavedMessageResponse> {
+        let message: Message = sqlx::query_as("SELECT * FROM messages WHERE id = $1")
+            .bind(message_id)
+            .fetch_optional(&db.pool)
+            .await?
+            .ok_or(AppError::MessageNotFound)?;
+
+        if !ChatService::is_participant(db, message.chat_id, user_id).await? {
+            return Err(AppError::AccessDenied);
+        }
+        if message.deleted_at.is_some() {
+            return Err(AppError::MessageNotFound);
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO saved_messages (user_id, message_id, chat_id)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_id, message_id) DO NOTHING
+            "#,
+        )
+        .bind(user_id)
+        .bind(message_id)
+        .bind(message.chat_id)
+        .execute(&db.pool)
+        .await?;
+
+        let saved: SavedMessage = sqlx::query_as(
+            r#"
+            SELECT s.*, c.name AS chat_name, c.type AS chat_type
+            FROM saved_messages s
+            LEFT JOIN chats c ON c.id = s.chat_id
+            WHERE s.user_id = $1 AND s.message_id = $2
+            "#,
+        )
+        .bind(user_id)
+        .bind(message_id)
+        .fetch_one(&db.pool)
+        .await?;
+
+        Self::build_saved_response(db, saved).await
+    }
+
+    /// Remove a message from the user's "Saved Messages"
+    pub async fn unsave_message(db: &Database, message_id: Uuid, user_id: Uuid) -> AppResult<()> {
+        let result =
+            sqlx::query("DELETE FROM saved_messages WHERE user_id = $1 AND message_id = $2")
                 .bind(user_id)
-                .bind(emoji)
+                .bind(message_id)
                 .execute(&db.pool)
                 .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Saved message not found".to_string()));
+        }
+        Ok(())
+    }
+
+    /// A page of the user's saved messages, most recently saved first
+    ///
+    /// `before` is the message id of the last entry of the previous page.
+    /// Originals that were deleted are listed as tombstones.
+    pub async fn list_saved(
+        db: &Database,
+        user_id: Uuid,
+        limit: i64,
+        before: Option<Uuid>,
+    ) -> AppResult<(Vec<SavedMessageResponse>, bool)> {
+        let limit = limit.clamp(1, MAX_SAVED_PAGE_SIZE);
+
+        let saved: Vec<SavedMessage> = sqlx::query_as(
+            r#"
+            SELECT s.*, c.name AS chat_name, c.type AS chat_type
+            FROM saved_messages s
+            LEFT JOIN chats c ON c.id = s.chat_id
+            WHERE s.user_id = $1
+              AND ($2::uuid IS NULL OR (s.saved_at, s.message_id) < (
+                  SELECT saved_at, message_id FROM saved_messages
+                  WHERE user_id = $1 AND message_id = $2
+              ))
+            ORDER BY s.saved_at DESC, s.message_id DESC
+            LIMIT $3
+            "#,
+        )
+        .bind(user_id)
+        .bind(before)
+        .bind(limit + 1)
+        .fetch_all(&db.pool)
+        .await?;
+
+        let has_more = saved.len() > limit as usize;
+        let mut responses = Vec::new();
+        for entry in saved.into_iter().take(limit as usize) {
+            responses.push(Self::build_saved_response(db, entry).await?);
         }
 
-        Self::build_message_response(db, message).await
+        Ok((responses, has_more))
+    }
+
+    async fn build_saved_response(
+        db: &Database,
+        saved: SavedMessage,
+    ) -> AppResult<SavedMessageResponse> {
+        let message: Option<Message> = sqlx::query_as("SELECT * FROM messages WHERE id = $1")
+            .bind(saved.message_id)
+            .fetch_optional(&db.pool)
+            .await?;
+
+        let is_deleted = message.as_ref().is_none_or(|m| m.deleted_at.is_some());
+        let message = match message {
+            Some(message) => Some(Self::build_message_response(db, message).await?),
+            None => None,
+        };
+
+        Ok(SavedMessageResponse {
+            message_id: saved.message_id,
+            chat_id: saved.chat_id,
+            chat_name: saved.chat_name,
+            chat_type: saved.chat_type,
+            saved_at: saved.saved_at,
+            message,
+            is_deleted,
+        })
     }
 
+    /// Pin a message, recording a `message_pinned` system message
     pub async fn pin_message(
         db: &Database,
         chat_id: Uuid,
         message_id: Uuid,
         user_id: Uuid,
-    ) -> AppResult<MessageResponse> {
+    ) -> AppResult<PinChange> {
+        Self::set_pinned(db, chat_id, message_id, user_id, true).await
+    }
+
+    /// Unpin a message, recording a `message_unpinned` system message
+    pub async fn unpin_message(
+        db: &Database,
+        chat_id: Uuid,
+        message_id: Uuid,
+        user_id: Uuid,
+    ) -> AppResult<PinChange> {
+        Self::set_pinned(db, chat_id, message_id, user_id, false).await
+    }
+
+    async fn set_pinned(
+        db: &Database,
+        chat_id: Uuid,
+        message_id: Uuid,
+        user_id: Uuid,
+        pinned: bool,
+    ) -> AppResult<PinChange> {
         if !ChatService::is_participant(db, chat_id, user_id).await? {
             return Err(AppError::AccessDenied);
         }
 
-        let message: Message = sqlx::query_as(
-            "UPDATE messages SET is_pinned = true WHERE id = $1 AND chat_id = $2 RETURNING *",
+        let mut tx = db.pool.begin().await?;
+        let was_pinned: bool = sqlx::query_scalar(
+            "SELECT is_pinned FROM messages WHERE id = $1 AND chat_id = $2 FOR UPDATE",
         )
         .bind(message_id)
         .bind(chat_id)
-        .fetch_optional(&db.pool)
+        .fetch_optional(&mut *tx)
         .await?
         .ok_or(AppError::MessageNotFound)?;
 
-        Self::build_message_response(db, message).await
+        let message: Message =
+            sqlx::query_as("UPDATE messages SET is_pinned = $2 WHERE id = $1 RETURNING *")
+                .bind(message_id)
+                .bind(pinned)
+                .fetch_one(&mut *tx)
+                .await?;
+        tx.commit().await?;
+
+        // Pinning an already pinned message changes nothing worth recording
+        let system_message = if was_pinned != pinned {
+            let event = if pinned {
+                SystemEventType::MessagePinned
+            } else {
+                SystemEventType::MessageUnpinned
+            };
+            let metadata = serde_json::json!({ "messageId": message_id });
+            Some(Self::create_system_event(db, chat_id, user_id, event, metadata).await?)
+        } else {
+            None
+        };
+
+        Ok(PinChange {
+            message: Self::build_message_response(db, message).await?,
+            system_message,
+        })
     }
 
-    pub async fn unpin_message(
+    /// Check the referenced uploads may be attached by `sender_id`
+    async fn resolve_attachments(
+        db: &Database,
+        sender_id: Uuid,
+        attachments: &[AttachmentInput],
+        limits: AttachmentLimits,
+    ) -> AppResult<Vec<Upload>> {
+        let upload_ids: Vec<Uuid> = attachments.iter().map(|a| a.upload_id).collect();
+        AttachmentService::resolve(db, sender_id, &upload_ids, limits).await
+    }
+
+    async fn insert_attachments(
+        tx: &mut Transaction<'_, Postgres>,
+        message_id: Uuid,
+        uploads: Vec<Upload>,
+    ) -> AppResult<()> {
+        for upload in uploads {
+            sqlx::query(
+                r#"
+                INSERT INTO attachments (message_id, type, name, size, url, mime_type, upload_id, width, height)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                "#,
+            )
+            .bind(message_id)
+            .bind(&upload.attachment_type)
+            .bind(&upload.name)
+            .bind(upload.size)
+            .bind(&upload.url)
+            .bind(&upload.mime_type)
+            .bind(upload.id)
+            .bind(upload.width)
+            .bind(upload.height)
+            .execute(&mut **tx)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Store formatting entities in order
+    async fn insert_entities(
         db: &Database,
+        message_id: Uuid,
+        entities: &[MessageEntity],
+    ) -> AppResult<()> {
+        for (position, entity) in entities.iter().enumerate() {
+            sqlx::query(
+                r#"
+                INSERT INTO message_entities
+                    (message_id, position, type, start_offset, length, url, language)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                "#,
+            )
+            .bind(message_id)
+            .bind(position as i32)
+            .bind(entity.kind.as_str())
+            .bind(entity.offset as i32)
+            .bind(entity.length as i32)
+            .bind(&entity.url)
+            .bind(&entity.language)
+            .execute(&db.pool)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Resolve and store the `@` mentions in `text`
+    ///
+    /// `@username` only counts for participants of the chat; anything else
+    /// stays plain text. `@everyone` and `@here` mention every other
+    /// participant but only when the sender is a chat admin.
+    async fn insert_mentions(
+        tx: &mut Transaction<'_, Postgres>,
         chat_id: Uuid,
         message_id: Uuid,
-        user_id: Uuid,
-    ) -> AppResult<MessageResponse> {
-        if !ChatService::is_participant(db, chat_id, user_id).await? {
-            return Err(AppError::AccessDenied);
+        sender_id: Uuid,
+        text: Option<&str>,
+    ) -> AppResult<()> {
+        let mentions = extract_mentions(text.unwrap_or_default());
+        if mentions.is_empty() {
+            return Ok(());
         }
 
-        let message: Message = sqlx::query_as(
-            "UPDATE messages SET is_pinned = false WHERE id = $1 AND chat_id = $2 RETURNING *",
+        let usernames: Vec<&str> = mentions
+            .iter()
+            .filter_map(|m| match m {
+                Mention::User(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        if !usernames.is_empty() {
+            sqlx::query(
+                r#"
+                INSERT INTO message_mentions (message_id, user_id, kind)
+                SELECT $1, u.id, 'user' FROM users u
+                JOIN chat_participants cp ON cp.user_id = u.id AND cp.chat_id = $2
+                WHERE LOWER(u.username) = ANY($3)
+                ON CONFLICT DO NOTHING
+                "#,
+            )
+            .bind(message_id)
+            .bind(chat_id)
+            .bind(&usernames)
+            .execute(&mut **tx)
+            .await?;
+        }
+
+        // @everyone wins when both are used
+        let kind = if mentions.contains(&Mention::Everyone) {
+            "everyone"
+        } else if mentions.contains(&Mention::Here) {
+            "here"
+        } else {
+            return Ok(());
+        };
+        let is_admin: bool = sqlx::query_scalar(
+            r#"
+            SELECT COALESCE(cp.role = 'admin' OR c.created_by = $2, FALSE)
+            FROM chats c
+            LEFT JOIN chat_participants cp ON cp.chat_id = c.id AND cp.user_id = $2
+            WHERE c.id = $1
+            "#,
+        )
+        .bind(chat_id)
+        .bind(sender_id)
+        .fetch_optional(&mut **tx)
+        .await?
+        .unwrap_or(false);
+        if !is_admin {
+            return Ok(());
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO message_mentions (message_id, user_id, kind)
+            SELECT $1, user_id, $4 FROM chat_participants
+            WHERE chat_id = $2 AND user_id != $3
+            ON CONFLICT DO NOTHING
+            "#,
         )
         .bind(message_id)
         .bind(chat_id)
+        .bind(sender_id)
+        .bind(kind)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Users mentioned in a message, other than its sender, with what they
+    /// need to decide whether to be notified
+    pub async fn mention_targets(
+        db: &Database,
+        message: &MessageResponse,
+    ) -> AppResult<Vec<MentionTarget>> {
+        let targets = sqlx::query_as(
+            r#"
+            SELECT m.user_id, m.kind,
+                   COALESCE(cs.muted, FALSE) AS muted,
+                   COALESCE(us.mention_notify_when_muted, FALSE) AS notify_when_muted
+            FROM message_mentions m
+            LEFT JOIN chat_user_state cs ON cs.chat_id = $2 AND cs.user_id = m.user_id
+            LEFT JOIN user_settings us ON us.user_id = m.user_id
+            WHERE m.message_id = $1 AND m.user_id != $3
+            "#,
+        )
+        .bind(message.id)
+        .bind(message.chat_id)
+        .bind(message.sender_id)
+        .fetch_all(&db.pool)
+        .await?;
+
+        Ok(targets)
+    }
+
+    /// Look up the message being replied to in `chat_id`
+    ///
+    /// Rejects messages from other chats and deleted messages. Returns the
+    /// author and snippet to copy onto the reply.
+    async fn reply_target(db: &Database, chat_id: Uuid, reply_id: Uuid) -> AppResult<ReplyTarget> {
+        let target: Message = sqlx::query_as(
+            "SELECT * FROM messages WHERE id = $1 AND chat_id = $2 AND deleted_at IS NULL",
+        )
+        .bind(reply_id)
+        .bind(chat_id)
         .fetch_optional(&db.pool)
         .await?
-        .ok_or(AppError::MessageNotFound)?;
+        .ok_or_else(|| {
+            AppError::BadRequest("Invalid replyTo: message not found in this chat".to_string())
+        })?;
 
-        Self::build_message_response(db, message).await
+        Ok(ReplyTarget {
+            id: target.id,
+            author_id: target.sender_id,
+            author_name: Self::sender_name(db, &target).await?,
+            snippet: if target.encrypted {
+                ENCRYPTED_REPLY_SNIPPET.to_string()
+            } else {
+                reply_snippet(target.text.as_deref())
+            },
+        })
     }
 
-    async fn build_message_response(db: &Database, message: Message) -> AppResult<MessageResponse> {
-        let mut response = Self::build_message_response_public(db, message).await?;
-        response.inline_keyboard = None;
+    /// Display name of a message's sender (user or bot)
+    async fn sender_name(db: &Database, message: &Message) -> AppResult<String> {
+        if message.sender_type.as_deref() == Some("bot") {
+            let res: Option<(String,)> = sqlx::query_as("SELECT name FROM bots WHERE id = $1")
+                .bind(message.sender_id)
+                .fetch_optional(&db.pool)
+                .await?;
+            Ok(res.map(|r| r.0).unwrap_or_else(|| "Unknown Bot".to_string()))
+        } else {
+            let res: Option<(String,)> = sqlx::query_as("SELECT name FROM users WHERE id = $1")
+                .bind(message.sender_id)
+                .fetch_optional(&db.pool)
+                .await?;
+            Ok(res.map(|r| r.0).unwrap_or_else(|| "Unknown User".to_string()))
+        }
+    }
+
+    /// Quote of the message `message` replies to
+    ///
+    /// The snippet is the one copied when the reply was sent. When the
+    /// original has since been deleted, a placeholder with the copied author
+    /// is returned instead; replies sent before snippets were stored have no
+    /// author to show and get `None`.
+    async fn reply_to_response(
+        db: &Database,
+        message: &Message,
+        reply_id: Uuid,
+    ) -> AppResult<Option<ReplyToResponse>> {
+        let original: Option<Message> =
+            sqlx::query_as("SELECT * FROM messages WHERE id = $1 AND chat_id = $2")
+                .bind(reply_id)
+                .bind(message.chat_id)
+                .fetch_optional(&db.pool)
+                .await?;
+
+        let response = match original {
+            Some(rm) if rm.deleted_at.is_none() => {
+                let snippet = message
+                    .reply_snippet
+                    .clone()
+                    .unwrap_or_else(|| reply_snippet(rm.text.as_deref()));
+                Some(ReplyToResponse {
+                    id: rm.id,
+                    sender_name: Self::sender_name(db, &rm).await?,
+                    sender_id: rm.sender_id,
+                    text: rm.text,
+                    snippet,
+                    is_deleted: false,
+                })
+            }
+            Some(rm) => {
+                let sender_name = match &message.reply_author_name {
+                    Some(name) => name.clone(),
+                    None => Self::sender_name(db, &rm).await?,
+                };
+                Some(deleted_reply(reply_id, rm.sender_id, sender_name))
+            }
+            None => match (message.reply_author_id, &message.reply_author_name) {
+                (Some(author_id), Some(name)) => {
+                    Some(deleted_reply(reply_id, author_id, name.clone()))
+                }
+                _ => None,
+            },
+        };
+
         Ok(response)
     }
 
+    async fn build_message_response(db: &Database, message: Message) -> AppResult<MessageResponse> {
+        Self::build_message_response_public(db, message).await
+    }
+
     pub async fn build_message_response_public(
         db: &Database,
         message: Message,
@@ -469,47 +2306,66 @@ impl MessageService {
                 .fetch_all(&db.pool)
                 .await?;
 
+        let link_preview: Option<LinkPreview> =
+            sqlx::query_as("SELECT * FROM link_previews WHERE message_id = $1")
+                .bind(message.id)
+                .fetch_optional(&db.pool)
+                .await?;
+
+        let entities: Vec<MessageEntityRow> = sqlx::query_as(
+            r#"
+            SELECT type, start_offset, length, url, language
+            FROM message_entities WHERE message_id = $1 ORDER BY position
+            "#,
+        )
+        .bind(message.id)
+        .fetch_all(&db.pool)
+        .await?;
+
+        let mentions: Vec<MentionResponse> = sqlx::query_as(
+            r#"
+            SELECT m.user_id, u.username
+            FROM message_mentions m JOIN users u ON u.id = m.user_id
+            WHERE m.message_id = $1 AND m.kind = 'user'
+            "#,
+        )
+        .bind(message.id)
+        .fetch_all(&db.pool)
+        .await?;
+
+        let mention_all: Option<String> = sqlx::query_scalar(
+            "SELECT kind FROM message_mentions WHERE message_id = $1 AND kind != 'user' LIMIT 1",
+        )
+        .bind(message.id)
+        .fetch_optional(&db.pool)
+        .await?;
+
         // Get reply_to info (restricted to same chat)
-        let reply_to = if let Some(reply_id) = message.reply_to_id {
-            let reply_msg: Option<Message> =
-                sqlx::query_as("SELECT * FROM messages WHERE id = $1 AND chat_id = $2")
-                    .bind(reply_id)
-                    .bind(message.chat_id)
-                    .fetch_optional(&db.pool)
-                    .await?;
-
-            if let Some(rm) = reply_msg {
-                let sender_name: String = if rm.sender_type.as_deref() == Some("bot") {
-                    let res: Option<(String,)> =
-                        sqlx::query_as("SELECT name FROM bots WHERE id = $1")
-                            .bind(rm.sender_id)
-                            .fetch_optional(&db.pool)
-                            .await?;
-                    res.map(|r| r.0)
-                        .unwrap_or_else(|| "Unknown Bot".to_string())
-                } else {
-                    let res: Option<(String,)> =
-                        sqlx::query_as("SELECT name FROM users WHERE id = $1")
-                            .bind(rm.sender_id)
-                            .fetch_optional(&db.pool)
-                            .await?;
-                    res.map(|r| r.0)
-                        .unwrap_or_else(|| "Unknown User".to_string())
-                };
+        let reply_to = match message.reply_to_id {
+            Some(reply_id) => Self::reply_to_response(db, &message, reply_id).await?,
+            None => None,
+        };
 
-                Some(ReplyToResponse {
-                    id: rm.id,
-                    text: rm.text,
-                    sender_id: rm.sender_id,
-                    sender_name,
-                })
-            } else {
-                None
-            }
+        let poll = PollService::for_message(db, message.id).await?;
+
+        // Only bot messages carry keyboards; tombstones no longer show them
+        let inline_keyboard = if message.sender_type.as_deref() == Some("bot")
+            && message.deleted_at.is_none()
+        {
+            InlineKeyboardStore::load(db, message.id).await?
         } else {
             None
         };
 
+        // Ciphertext can't reference emoji the server could resolve
+        let text = message.text.as_deref().filter(|_| !message.encrypted);
+        let custom_emoji =
+            CustomEmojiService::for_message(db, message.chat_id, &reactions, text).await?;
+        let system_event = system_event_response(
+            message.system_event.as_deref(),
+            message.system_metadata.as_deref(),
+        );
+
         Ok(MessageResponse {
             id: message.id,
             chat_id: message.chat_id,
@@ -528,8 +2384,10 @@ impl MessageService {
                 .map(|r| ReactionResponse {
                     emoji: r.emoji,
                     user_id: r.user_id,
+                    custom_emoji_id: r.custom_emoji_id,
                 })
                 .collect(),
+            reaction_summary: Vec::new(),
             attachments: attachments
                 .into_iter()
                 .map(|a| AttachmentResponse {
@@ -539,6 +2397,8 @@ impl MessageService {
                     size: a.size,
                     url: a.url,
                     mime_type: a.mime_type,
+                    width: a.width,
+                    height: a.height,
                 })
                 .collect(),
             reply_to,
@@ -550,21 +2410,320 @@ impl MessageService {
                     read_at: r.read_at,
                 })
                 .collect(),
-            inline_keyboard: None,
+            inline_keyboard,
+            delete_at: message.delete_at,
+            thread_root_id: message.thread_root_id,
+            reply_count: message.reply_count,
+            is_deleted: message.deleted_at.is_some(),
+            encrypted: message.encrypted,
+            link_preview: link_preview.map(Into::into),
+            entities: entities
+                .into_iter()
+                .filter_map(MessageEntityRow::into_entity)
+                .collect(),
+            mentions,
+            mention_all,
+            poll,
+            forwarded_from: message.forward_author_name.map(|sender_name| ForwardedFromResponse {
+                sender_id: message.forward_author_id,
+                sender_name,
+            }),
+            custom_emoji,
+            system_event,
         })
     }
 }
 
+/// A pinned or unpinned message and the system message recording the change
+#[derive(Debug)]
+pub struct PinChange {
+    pub message: MessageResponse,
+    /// `None` when the message already was in the requested state
+    pub system_message: Option<MessageResponse>,
+}
+
+/// A message after one of its reactions was toggled
+#[derive(Debug)]
+pub struct ReactionChange {
+    pub message: MessageResponse,
+    /// Whether the reaction was added (false: removed)
+    pub added: bool,
+}
+
+/// Reference to one of the sender's uploads (the `id` returned by `POST /upload`)
 #[derive(Debug)]
 pub struct AttachmentInput {
-    pub attachment_type: String,
-    pub name: String,
-    pub size: i64,
-    pub url: String,
-    pub mime_type: Option<String>,
+    pub upload_id: Uuid,
 }
 
 #[derive(Debug)]
 pub struct ReplyToInput {
     pub id: Uuid,
 }
+
+/// A user mentioned in a message
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct MentionTarget {
+    pub user_id: Uuid,
+    /// `user`, `everyone` or `here`
+    pub kind: String,
+    /// The user muted the chat
+    pub muted: bool,
+    /// The user wants mentions even from muted chats
+    pub notify_when_muted: bool,
+}
+
+impl MentionTarget {
+    /// Whether to send this user a `Mentioned` event
+    ///
+    /// Muted chats only notify users who opted into mention exceptions, and
+    /// `@here` only reaches users who are connected right now.
+    pub fn should_notify(&self, online: bool) -> bool {
+        (online || self.kind != "here") && (!self.muted || self.notify_when_muted)
+    }
+}
+
+/// Replied-to message details copied onto a reply
+struct ReplyTarget {
+    id: Uuid,
+    author_id: Uuid,
+    author_name: String,
+    snippet: String,
+}
+
+/// Structured event of a system message, if it records one
+fn system_event_response(
+    event: Option<&str>,
+    metadata: Option<&str>,
+) -> Option<SystemEventResponse> {
+    Some(SystemEventResponse {
+        event_type: SystemEventType::parse(event?)?,
+        metadata: metadata
+            .and_then(|metadata| serde_json::from_str(metadata).ok())
+            .unwrap_or_else(|| serde_json::json!({})),
+    })
+}
+
+/// Target chats of a forward, without duplicates and in request order
+fn forward_targets(target_chat_ids: &[Uuid]) -> AppResult<Vec<Uuid>> {
+    let mut targets = Vec::with_capacity(target_chat_ids.len());
+    for id in target_chat_ids {
+        if !targets.contains(id) {
+            targets.push(*id);
+        }
+    }
+    if targets.is_empty() {
+        return Err(AppError::BadRequest("No target chats given".to_string()));
+    }
+    if targets.len() > MAX_FORWARD_TARGETS {
+        return Err(AppError::BadRequest(format!(
+            "A message can be forwarded to at most {} chats at once",
+            MAX_FORWARD_TARGETS
+        )));
+    }
+    Ok(targets)
+}
+
+/// Per-target failure reported in place of a forwarded copy
+fn forward_error(error: &AppError) -> ForwardError {
+    let (_, code) = error.status_and_code();
+    ForwardError {
+        code: code.to_string(),
+        message: error.to_string(),
+    }
+}
+
+/// Whether a message sent at `created_at` is still within a window of
+/// `window_seconds` (0 means no limit)
+pub fn within_window(created_at: DateTime<Utc>, now: DateTime<Utc>, window_seconds: u64) -> bool {
+    window_seconds == 0
+        || (now - created_at).num_seconds() <= i64::try_from(window_seconds).unwrap_or(i64::MAX)
+}
+
+/// One-line preview of a replied-to message
+///
+/// Whitespace runs collapse to single spaces and text longer than
+/// `REPLY_SNIPPET_MAX_CHARS` is cut with an ellipsis. Messages without text
+/// (attachments only) get a generic label.
+pub fn reply_snippet(text: Option<&str>) -> String {
+    let collapsed = text
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if collapsed.is_empty() {
+        return ATTACHMENT_REPLY_SNIPPET.to_string();
+    }
+
+    match collapsed.char_indices().nth(REPLY_SNIPPET_MAX_CHARS) {
+        Some((cut, _)) => format!("{}…", collapsed[..cut].trim_end()),
+        None => collapsed,
+    }
+}
+
+/// Words of `text` equal (ignoring case) to a word of `query`
+///
+/// Words are runs of alphanumeric characters, as in the `simple` text
+/// search configuration. Offsets are in UTF-16 code units.
+pub fn highlight_ranges(text: &str, query: &str) -> Vec<HighlightRange> {
+    let terms: Vec<String> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+
+    let mut ranges = Vec::new();
+    let mut word: Option<(usize, usize)> = None;
+    let mut units = 0;
+    // The trailing space closes a word that ends the text
+    for (i, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+        if c.is_alphanumeric() {
+            word.get_or_insert((i, units));
+        } else if let Some((byte, start)) = word.take() {
+            if terms.contains(&text[byte..i].to_lowercase()) {
+                ranges.push(HighlightRange { start, end: units });
+            }
+        }
+        units += c.len_utf16();
+    }
+    ranges
+}
+
+fn deleted_reply(id: Uuid, sender_id: Uuid, sender_name: String) -> ReplyToResponse {
+    ReplyToResponse {
+        id,
+        text: None,
+        sender_id,
+        sender_name,
+        snippet: DELETED_REPLY_SNIPPET.to_string(),
+        is_deleted: true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn mention(kind: &str, muted: bool, notify_when_muted: bool) -> MentionTarget {
+        MentionTarget {
+            user_id: Uuid::new_v4(),
+            kind: kind.to_string(),
+            muted,
+            notify_when_muted,
+        }
+    }
+
+    #[test]
+    fn test_mention_in_muted_chat_needs_opt_in() {
+        assert!(mention("user", false, false).should_notify(false));
+        assert!(!mention("user", true, false).should_notify(true));
+        assert!(mention("user", true, true).should_notify(true));
+        assert!(mention("everyone", true, true).should_notify(false));
+    }
+
+    #[test]
+    fn test_forward_targets_deduplicated_and_capped() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        assert_eq!(forward_targets(&[a, b, a]).unwrap(), vec![a, b]);
+
+        assert!(forward_targets(&[]).is_err());
+        let too_many: Vec<Uuid> = (0..=MAX_FORWARD_TARGETS).map(|_| Uuid::new_v4()).collect();
+        assert!(forward_targets(&too_many).is_err());
+    }
+
+    #[test]
+    fn test_here_mention_only_reaches_online_users() {
+        assert!(mention("here", false, false).should_notify(true));
+        assert!(!mention("here", false, false).should_notify(false));
+        assert!(!mention("here", true, false).should_notify(true));
+    }
+
+    #[test]
+    fn test_reply_snippet_keeps_short_text() {
+        assert_eq!(reply_snippet(Some("see you at 5")), "see you at 5");
+    }
+
+    #[test]
+    fn test_reply_snippet_collapses_whitespace() {
+        assert_eq!(reply_snippet(Some("  line one\n\n line\ttwo ")), "line one line two");
+    }
+
+    #[test]
+    fn test_reply_snippet_truncates_on_char_boundary() {
+        let text = "é".repeat(REPLY_SNIPPET_MAX_CHARS + 5);
+        let snippet = reply_snippet(Some(text.as_str()));
+        assert_eq!(snippet.chars().count(), REPLY_SNIPPET_MAX_CHARS + 1);
+        assert!(snippet.ends_with('…'));
+
+        let exact = "a".repeat(REPLY_SNIPPET_MAX_CHARS);
+        assert_eq!(reply_snippet(Some(exact.as_str())), exact);
+    }
+
+    #[test]
+    fn test_reply_snippet_without_text() {
+        assert_eq!(reply_snippet(None), ATTACHMENT_REPLY_SNIPPET);
+        assert_eq!(reply_snippet(Some("   ")), ATTACHMENT_REPLY_SNIPPET);
+    }
+
+    #[test]
+    fn test_within_window() {
+        let sent = Utc::now();
+        assert!(within_window(sent, sent + Duration::seconds(60), 60));
+        assert!(!within_window(sent, sent + Duration::seconds(61), 60));
+        assert!(within_window(sent, sent + Duration::days(3650), 0));
+        assert!(within_window(sent, sent + Duration::seconds(1), u64::MAX));
+    }
+
+    #[test]
+    fn test_highlight_ranges_match_whole_words_ignoring_case() {
+        let ranges = highlight_ranges("Lunch at noon? lunchbox LUNCH", "lunch");
+        assert_eq!(
+            ranges,
+            vec![
+                HighlightRange { start: 0, end: 5 },
+                HighlightRange { start: 24, end: 29 },
+            ]
+        );
+        assert!(highlight_ranges("nothing here", "lunch").is_empty());
+        assert!(highlight_ranges("lunch", "   ").is_empty());
+    }
+
+    #[test]
+    fn test_highlight_ranges_use_utf16_offsets() {
+        // The emoji is two UTF-16 code units
+        let ranges = highlight_ranges("😀 Phở bò", "phở");
+        assert_eq!(ranges, vec![HighlightRange { start: 3, end: 6 }]);
+    }
+
+    #[test]
+    fn test_deleted_reply_placeholder() {
+        let author = Uuid::new_v4();
+        let reply = deleted_reply(Uuid::new_v4(), author, "Alice".to_string());
+        assert!(reply.is_deleted);
+        assert_eq!(reply.text, None);
+        assert_eq!(reply.sender_id, author);
+        assert_eq!(reply.snippet, DELETED_REPLY_SNIPPET);
+    }
+
+    #[test]
+    fn test_system_event_response() {
+        let event = system_event_response(
+            Some("message_pinned"),
+            Some(r#"{"messageId":"00000000-0000-0000-0000-000000000001"}"#),
+        )
+        .unwrap();
+        assert_eq!(event.event_type, SystemEventType::MessagePinned);
+        assert_eq!(event.metadata["messageId"], "00000000-0000-0000-0000-000000000001");
+
+        // Announcements carry no event; unknown events are left out
+        assert!(system_event_response(None, None).is_none());
+        assert!(system_event_response(Some("chat_renamed"), None).is_none());
+        let event = system_event_response(Some("member_joined"), None).unwrap();
+        assert_eq!(event.metadata, serde_json::json!({}));
+    }
+}
+
+#[cfg(test)]
+#[path = "message_integration_tests.rs"]
+mod message_integration_tests;
@@ -8,6 +8,32 @@ pub mod bot;
 pub mod bot_engine;
 pub mod login_rate_limiter;
 pub mod invite_link;
+pub mod slow_mode;
+pub mod disappearing;
+pub mod retention;
+pub mod chat_purge;
+pub mod request_id;
+pub mod geoip;
+pub mod content;
+pub mod link_preview;
+pub mod turn;
+pub mod call_log;
+pub mod upload_quota;
+pub mod attachment;
+pub mod admin_stats;
+pub mod flood_guard;
+pub mod push;
+pub mod outbox;
+pub mod crypto;
+pub mod timezone;
+pub mod poll;
+pub mod e2ee;
+pub mod announcement;
+pub mod export;
+pub mod custom_emoji;
+pub mod reaction_limiter;
+pub mod unfurl;
+pub mod offline_queue;
 
 pub use auth::AuthService;
 pub use user::UserService;
@@ -17,4 +43,24 @@ pub use settings::SettingsService;
 pub use websocket::WebSocketService;
 pub use bot::BotService;
 pub use login_rate_limiter::LoginRateLimiter;
+pub use slow_mode::SlowModeLimiter;
+pub use disappearing::DisappearingMessageService;
+pub use retention::MessageRetentionService;
+pub use chat_purge::ChatPurgeService;
+pub use link_preview::LinkPreviewService;
+pub use turn::TurnService;
+pub use call_log::CallLogService;
+pub use upload_quota::UploadQuota;
+pub use flood_guard::FloodGuard;
+pub use push::PushService;
+pub use outbox::OutboxService;
+pub use attachment::AttachmentService;
+pub use poll::PollService;
+pub use e2ee::E2eeKeyService;
+pub use announcement::AnnouncementService;
+pub use export::ExportService;
+pub use custom_emoji::CustomEmojiService;
+pub use reaction_limiter::{ReactionDebouncer, ReactionLimiter};
+pub use unfurl::UnfurlService;
+pub use offline_queue::OfflineQueue;
 pub use bot_engine::{ParsedCommand, MessageProcessor};
@@ -0,0 +1,297 @@
+/// Offline Delivery Queue Service
+///
+/// A `new_message` event only reaches users with a live connection. For
+/// recipients it could not be sent to over either transport the message id
+/// is queued in Redis (`offline:queue:{user_id}`, oldest first), and the messages are
+/// sent as `new_message` (or `thread_reply`) events when the user connects again:
+/// - A queue holds at most `OFFLINE_QUEUE_SIZE` ids. One more marks it
+///   overflowed (`offline:overflow:{user_id}`); the user then gets a single
+///   `resync_required` instead of a partial replay
+/// - Queues expire `OFFLINE_QUEUE_TTL_SECONDS` after the last message queued
+/// - Messages deleted or hidden by the user in the meantime are skipped
+/// - Queued ids are only removed once their events are built, so a failed
+///   replay leaves them for the next connection
+///
+/// Without Redis nothing is queued and clients catch up by fetching history.
+use redis::aio::ConnectionManager;
+use redis::RedisError;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    config::Config,
+    db::Database,
+    error::AppResult,
+    models::Message,
+    services::MessageService,
+    ws::events::ServerEvent,
+};
+
+/// Append ARGV[1] to the queue in KEYS[1] unless it overflowed (KEYS[2])
+///
+/// ARGV: message id, capacity, TTL in seconds.
+const PUSH_SCRIPT: &str = r#"
+local capacity = tonumber(ARGV[2])
+if redis.call('EXISTS', KEYS[2]) == 1 then
+    redis.call('EXPIRE', KEYS[2], ARGV[3])
+    return 0
+end
+if redis.call('RPUSH', KEYS[1], ARGV[1]) > capacity then
+    redis.call('DEL', KEYS[1])
+    redis.call('SET', KEYS[2], 1, 'EX', ARGV[3])
+    return 0
+end
+redis.call('EXPIRE', KEYS[1], ARGV[3])
+return 0
+"#;
+
+/// Return the queue in KEYS[1]; nil when it overflowed (KEYS[2])
+const READ_SCRIPT: &str = r#"
+if redis.call('EXISTS', KEYS[2]) == 1 then
+    return false
+end
+return redis.call('LRANGE', KEYS[1], 0, -1)
+"#;
+
+/// Drop the first ARGV[1] ids of the queue in KEYS[1], and the overflow mark
+/// (KEYS[2]) when ARGV[2] is 1
+const REMOVE_SCRIPT: &str = r#"
+if ARGV[2] == '1' then
+    redis.call('DEL', KEYS[2])
+end
+redis.call('LTRIM', KEYS[1], ARGV[1], -1)
+return 0
+"#;
+
+type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, RedisError>> + Send + 'a>>;
+
+/// Queue storage behind `OfflineQueue`; Redis outside of tests
+pub trait OfflineQueueStore: Send + Sync {
+    /// Queue `message_id` for `user_id`; a queue growing past `capacity` is
+    /// dropped and marked overflowed
+    fn push(&self, user_id: Uuid, message_id: Uuid, capacity: usize, ttl_seconds: u64)
+        -> StoreFuture<'_, ()>;
+    /// The user's queue, oldest first; `None` when it overflowed
+    fn read(&self, user_id: Uuid) -> StoreFuture<'_, Option<Vec<Uuid>>>;
+    /// Drop the first `count` ids read from the user's queue; `overflowed`
+    /// also clears the overflow mark
+    fn remove(&self, user_id: Uuid, count: usize, overflowed: bool) -> StoreFuture<'_, ()>;
+}
+
+impl OfflineQueueStore for ConnectionManager {
+    fn push(
+        &self,
+        user_id: Uuid,
+        message_id: Uuid,
+        capacity: usize,
+        ttl_seconds: u64,
+    ) -> StoreFuture<'_, ()> {
+        let mut conn = self.clone();
+        Box::pin(async move {
+            redis::Script::new(PUSH_SCRIPT)
+                .key(queue_key(user_id))
+                .key(overflow_key(user_id))
+                .arg(message_id.to_string())
+                .arg(capacity)
+                .arg(ttl_seconds.max(1))
+                .invoke_async(&mut conn)
+                .await
+        })
+    }
+
+    fn read(&self, user_id: Uuid) -> StoreFuture<'_, Option<Vec<Uuid>>> {
+        let mut conn = self.clone();
+        Box::pin(async move {
+            let ids: Option<Vec<String>> = redis::Script::new(READ_SCRIPT)
+                .key(queue_key(user_id))
+                .key(overflow_key(user_id))
+                .invoke_async(&mut conn)
+                .await?;
+            Ok(ids.map(|ids| ids.iter().filter_map(|id| id.parse().ok()).collect()))
+        })
+    }
+
+    fn remove(&self, user_id: Uuid, count: usize, overflowed: bool) -> StoreFuture<'_, ()> {
+        let mut conn = self.clone();
+        Box::pin(async move {
+            redis::Script::new(REMOVE_SCRIPT)
+                .key(queue_key(user_id))
+                .key(overflow_key(user_id))
+                .arg(count)
+                .arg(u8::from(overflowed))
+                .invoke_async(&mut conn)
+                .await
+        })
+    }
+}
+
+fn queue_key(user_id: Uuid) -> String {
+    format!("offline:queue:{}", user_id)
+}
+
+fn overflow_key(user_id: Uuid) -> String {
+    format!("offline:overflow:{}", user_id)
+}
+
+/// Queue limits taken from `Config`; a size of 0 disables queueing
+#[derive(Debug, Clone)]
+pub struct OfflineQueueConfig {
+    pub size: usize,
+    pub ttl_seconds: u64,
+}
+
+impl From<&Config> for OfflineQueueConfig {
+    fn from(config: &Config) -> Self {
+        Self {
+            size: config.offline_queue_size,
+            ttl_seconds: config.offline_queue_ttl_seconds,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct OfflineQueue {
+    store: Option<Arc<dyn OfflineQueueStore>>,
+    config: OfflineQueueConfig,
+}
+
+impl OfflineQueue {
+    pub fn new(redis: Option<ConnectionManager>, config: OfflineQueueConfig) -> Self {
+        Self {
+            store: redis.map(|redis| Arc::new(redis) as Arc<dyn OfflineQueueStore>),
+            config,
+        }
+    }
+
+    /// Create a queue over any store
+    pub fn with_store(store: Arc<dyn OfflineQueueStore>, config: OfflineQueueConfig) -> Self {
+        Self {
+            store: Some(store),
+            config,
+        }
+    }
+
+    fn store(&self) -> Option<&Arc<dyn OfflineQueueStore>> {
+        self.store.as_ref().filter(|_| self.config.size > 0)
+    }
+
+    /// Queue a new message for recipients it could not be sent to
+    pub async fn push(&self, user_ids: &[Uuid], message_id: Uuid) {
+        let Some(store) = self.store() else {
+            return;
+        };
+
+        for &user_id in user_ids {
+            if let Err(e) = store
+                .push(user_id, message_id, self.config.size, self.config.ttl_seconds)
+                .await
+            {
+                tracing::warn!("Failed to queue message {} for user {}: {}", message_id, user_id, e);
+            }
+        }
+    }
+
+    /// Take the events a user missed while offline, emptying their queue
+    ///
+    /// Returns the queued messages as `new_message` or `thread_reply`
+    /// events, oldest first, or a single `resync_required` when the queue
+    /// overflowed. On error the queue is left as it was.
+    pub async fn take_events(&self, db: &Database, user_id: Uuid) -> AppResult<Vec<ServerEvent>> {
+        let Some(store) = self.store() else {
+            return Ok(Vec::new());
+        };
+
+        let message_ids = match store.read(user_id).await {
+            Ok(Some(ids)) => ids,
+            Ok(None) => {
+                Self::remove(store.as_ref(), user_id, 0, true).await;
+                return Ok(vec![ServerEvent::ResyncRequired]);
+            }
+            Err(e) => {
+                tracing::warn!("Failed to read offline queue of user {}: {}", user_id, e);
+                return Ok(Vec::new());
+            }
+        };
+        if message_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let messages: Vec<Message> = sqlx::query_as(
+            r#"
+            SELECT m.* FROM messages m
+            JOIN chats c ON c.id = m.chat_id AND c.deleted_at IS NULL
+            JOIN chat_participants cp ON cp.chat_id = m.chat_id AND cp.user_id = $2
+            WHERE m.id = ANY($1) AND m.deleted_at IS NULL
+              AND NOT EXISTS (
+                  SELECT 1 FROM hidden_messages h WHERE h.message_id = m.id AND h.user_id = $2
+              )
+            ORDER BY m.created_at
+            "#,
+        )
+        .bind(&message_ids)
+        .bind(user_id)
+        .fetch_all(&db.pool)
+        .await?;
+
+        let mut events = Vec::with_capacity(messages.len());
+        for message in messages {
+            let message = MessageService::build_message_response_public(db, message).await?;
+            events.push(MessageService::new_message_event(db, &message).await?);
+        }
+
+        Self::remove(store.as_ref(), user_id, message_ids.len(), false).await;
+        Ok(events)
+    }
+
+    /// Drop what was taken from a user's queue; a failure only means the
+    /// messages are replayed once more
+    async fn remove(store: &dyn OfflineQueueStore, user_id: Uuid, count: usize, overflowed: bool) {
+        if let Err(e) = store.remove(user_id, count, overflowed).await {
+            tracing::warn!("Failed to clear offline queue of user {}: {}", user_id, e);
+        }
+    }
+}
+
+/// In-memory store with the same capacity and overflow rules as Redis
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryQueueStore {
+    /// `None` marks an overflowed queue
+    queues: std::sync::Mutex<std::collections::HashMap<Uuid, Option<Vec<Uuid>>>>,
+}
+
+#[cfg(test)]
+impl OfflineQueueStore for MemoryQueueStore {
+    fn push(&self, user_id: Uuid, message_id: Uuid, capacity: usize, _: u64) -> StoreFuture<'_, ()> {
+        let mut queues = self.queues.lock().unwrap();
+        let queue = queues.entry(user_id).or_insert_with(|| Some(Vec::new()));
+        if let Some(ids) = queue {
+            ids.push(message_id);
+            if ids.len() > capacity {
+                *queue = None;
+            }
+        }
+        Box::pin(async { Ok(()) })
+    }
+
+    fn read(&self, user_id: Uuid) -> StoreFuture<'_, Option<Vec<Uuid>>> {
+        let queue = self.queues.lock().unwrap().get(&user_id).cloned().unwrap_or(Some(Vec::new()));
+        Box::pin(async move { Ok(queue) })
+    }
+
+    fn remove(&self, user_id: Uuid, count: usize, overflowed: bool) -> StoreFuture<'_, ()> {
+        let mut queues = self.queues.lock().unwrap();
+        match queues.get_mut(&user_id) {
+            Some(Some(ids)) => {
+                ids.drain(..count.min(ids.len()));
+            }
+            Some(None) if overflowed => {
+                queues.remove(&user_id);
+            }
+            _ => {}
+        }
+        Box::pin(async { Ok(()) })
+    }
+}
@@ -0,0 +1,296 @@
+/// Transactional outbox for realtime events
+///
+/// A new message and its `event_outbox` row are written in one transaction,
+/// so every stored message has a pending delivery. The writer holds a lease
+/// on the event and fans it out itself right after commit (WebSocket, push,
+/// mentions, link previews, bots), then marks it processed. If the process
+/// dies in between, the lease runs out and the background relay delivers
+/// the event instead: at-least-once delivery after a durable write.
+///
+/// Leases are taken with `FOR UPDATE SKIP LOCKED` and an event can only be
+/// marked processed once, so each event id has a single owner at a time and
+/// is never replayed once processed. A crash after delivery but before the
+/// mark replays it. Side effects (the realtime send, offline queue, push,
+/// mentions, link previews, bots) are recorded on the event as they
+/// complete, so a replay skips the ones already done. Only a crash between
+/// an effect and its record repeats it.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{db::Database, error::AppResult, services::MessageService, AppState};
+
+/// Event kind for a message sent to a chat
+pub const EVENT_NEW_MESSAGE: &str = "new_message";
+
+/// Side effects of a `new_message` event, recorded as they complete
+pub const EFFECT_REALTIME: &str = "realtime";
+pub const EFFECT_OFFLINE_QUEUE: &str = "offline_queue";
+pub const EFFECT_PUSH: &str = "push";
+pub const EFFECT_MENTIONS: &str = "mentions";
+pub const EFFECT_LINK_PREVIEW: &str = "link_preview";
+pub const EFFECT_BOTS: &str = "bots";
+
+/// How long the writer (or the relay) owns an event before it is up for grabs
+pub const OUTBOX_LEASE: Duration = Duration::from_secs(60);
+
+/// Most events the relay delivers per pass
+const RELAY_BATCH_SIZE: i64 = 100;
+
+/// Deliveries attempted by the relay before an event is given up on
+const MAX_RELAY_ATTEMPTS: i32 = 5;
+
+/// Processed events are kept this long, then deleted
+const PROCESSED_RETENTION_HOURS: i32 = 24;
+
+/// A pending event claimed by the relay
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct OutboxEvent {
+    pub id: Uuid,
+    pub kind: String,
+    pub chat_id: Uuid,
+    pub message_id: Uuid,
+    /// Relay deliveries started, this one included
+    pub attempts: i32,
+}
+
+/// Side effects already completed for an event
+#[derive(Debug, Default)]
+pub struct EventEffects {
+    /// `None` when the message has no pending event; nothing is recorded then
+    event_id: Option<Uuid>,
+    completed: Vec<String>,
+}
+
+impl EventEffects {
+    pub fn is_done(&self, effect: &str) -> bool {
+        self.completed.iter().any(|done| done == effect)
+    }
+
+    /// Record `effect` as completed; if that fails, a replay repeats it
+    pub async fn complete(&mut self, db: &Database, effect: &str) {
+        self.complete_all(db, &[effect]).await;
+    }
+
+    /// Record several effects as completed in one write, so a replay either
+    /// skips all of them or repeats all of them
+    pub async fn complete_all(&mut self, db: &Database, effects: &[&str]) {
+        let Some(event_id) = self.event_id else {
+            return;
+        };
+        let pending: Vec<&str> = effects
+            .iter()
+            .copied()
+            .filter(|effect| !self.is_done(effect))
+            .collect();
+        if pending.is_empty() {
+            return;
+        }
+        match OutboxService::complete_effects(db, event_id, &pending).await {
+            Ok(()) => self
+                .completed
+                .extend(pending.iter().map(|effect| effect.to_string())),
+            Err(e) => tracing::warn!(
+                "Failed to record {} of outbox event {}: {}",
+                pending.join(", "),
+                event_id,
+                e
+            ),
+        }
+    }
+}
+
+pub struct OutboxService;
+
+impl OutboxService {
+    /// Record an event in the transaction making the write
+    ///
+    /// The event starts leased to the caller, who delivers it after commit.
+    pub async fn enqueue(
+        tx: &mut Transaction<'_, Postgres>,
+        kind: &str,
+        chat_id: Uuid,
+        message_id: Uuid,
+    ) -> AppResult<Uuid> {
+        let id: Uuid = sqlx::query_scalar(
+            r#"
+            INSERT INTO event_outbox (kind, chat_id, message_id, locked_until)
+            VALUES ($1, $2, $3, NOW() + make_interval(secs => $4))
+            RETURNING id
+            "#,
+        )
+        .bind(kind)
+        .bind(chat_id)
+        .bind(message_id)
+        .bind(OUTBOX_LEASE.as_secs_f64())
+        .fetch_one(&mut **tx)
+        .await?;
+
+        Ok(id)
+    }
+
+    /// Mark an event delivered; `false` if it already was
+    pub async fn mark_processed(db: &Database, event_id: Uuid) -> AppResult<bool> {
+        let result = sqlx::query(
+            "UPDATE event_outbox SET processed_at = NOW() WHERE id = $1 AND processed_at IS NULL",
+        )
+        .bind(event_id)
+        .execute(&db.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Mark the `kind` event of a message delivered, for writers that only
+    /// know the message; `false` if it already was
+    pub async fn mark_message_processed(
+        db: &Database,
+        kind: &str,
+        message_id: Uuid,
+    ) -> AppResult<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE event_outbox SET processed_at = NOW()
+            WHERE message_id = $1 AND kind = $2 AND processed_at IS NULL
+            "#,
+        )
+        .bind(message_id)
+        .bind(kind)
+        .execute(&db.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Completed side effects of the pending `kind` event of a message
+    pub async fn effects(db: &Database, kind: &str, message_id: Uuid) -> AppResult<EventEffects> {
+        let event: Option<(Uuid, Vec<String>)> = sqlx::query_as(
+            r#"
+            SELECT id, completed_effects FROM event_outbox
+            WHERE message_id = $1 AND kind = $2 AND processed_at IS NULL
+            ORDER BY created_at
+            LIMIT 1
+            "#,
+        )
+        .bind(message_id)
+        .bind(kind)
+        .fetch_optional(&db.pool)
+        .await?;
+
+        Ok(event
+            .map(|(id, completed)| EventEffects {
+                event_id: Some(id),
+                completed,
+            })
+            .unwrap_or_default())
+    }
+
+    /// Record side effects of an event as completed
+    pub async fn complete_effects(db: &Database, event_id: Uuid, effects: &[&str]) -> AppResult<()> {
+        sqlx::query(
+            r#"
+            UPDATE event_outbox
+            SET completed_effects = completed_effects
+                || ARRAY(SELECT unnest($2::text[]) EXCEPT SELECT unnest(completed_effects))
+            WHERE id = $1
+            "#,
+        )
+        .bind(event_id)
+        .bind(effects)
+        .execute(&db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Take the lease on up to `limit` unprocessed events whose lease ran
+    /// out, oldest first
+    pub async fn claim_due(db: &Database, limit: i64) -> AppResult<Vec<OutboxEvent>> {
+        let events = sqlx::query_as(
+            r#"
+            UPDATE event_outbox
+            SET locked_until = NOW() + make_interval(secs => $2), attempts = attempts + 1
+            WHERE id IN (
+                SELECT id FROM event_outbox
+                WHERE processed_at IS NULL AND locked_until < NOW()
+                ORDER BY created_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, kind, chat_id, message_id, attempts
+            "#,
+        )
+        .bind(limit)
+        .bind(OUTBOX_LEASE.as_secs_f64())
+        .fetch_all(&db.pool)
+        .await?;
+
+        Ok(events)
+    }
+
+    /// Spawn the background relay. Runs until the process exits.
+    pub fn spawn_relay(state: Arc<AppState>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = Self::run_once(&state).await {
+                    tracing::error!("Outbox relay failed: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Deliver one batch of abandoned events and drop old processed ones
+    pub async fn run_once(state: &AppState) -> AppResult<usize> {
+        let events = Self::claim_due(&state.db, RELAY_BATCH_SIZE).await?;
+
+        for event in &events {
+            if event.attempts > MAX_RELAY_ATTEMPTS {
+                tracing::error!(
+                    "Giving up on outbox event {} ({} for message {}) after {} attempts",
+                    event.id,
+                    event.kind,
+                    event.message_id,
+                    MAX_RELAY_ATTEMPTS
+                );
+            } else if let Err(e) = Self::deliver(state, event).await {
+                // Left unprocessed: retried once the lease runs out
+                tracing::warn!("Outbox event {} not delivered: {}", event.id, e);
+                continue;
+            }
+            Self::mark_processed(&state.db, event.id).await?;
+        }
+
+        if !events.is_empty() {
+            tracing::info!("Outbox relay handled {} abandoned events", events.len());
+        }
+
+        sqlx::query(
+            r#"
+            DELETE FROM event_outbox
+            WHERE processed_at < NOW() - make_interval(hours => $1)
+            "#,
+        )
+        .bind(PROCESSED_RETENTION_HOURS)
+        .execute(&state.db.pool)
+        .await?;
+
+        Ok(events.len())
+    }
+
+    async fn deliver(state: &AppState, event: &OutboxEvent) -> AppResult<()> {
+        match event.kind.as_str() {
+            EVENT_NEW_MESSAGE => {
+                MessageService::redeliver_new_message(state, event.message_id).await
+            }
+            other => {
+                tracing::warn!("Dropping outbox event {} of unknown kind {}", event.id, other);
+                Ok(())
+            }
+        }
+    }
+}
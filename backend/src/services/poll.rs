@@ -0,0 +1,311 @@
+/// Polls - a message type chat members vote on.
+///
+/// A poll is created together with its message (see
+/// `MessageService::create_poll`); this module handles the votes. Voting
+/// upserts the voter's slots, so concurrent votes by the same user never
+/// leave a single-choice poll with two picks, and tallies are recomputed in
+/// one query after every change.
+use std::collections::HashSet;
+
+use uuid::Uuid;
+
+use crate::{
+    db::Database,
+    error::{AppError, AppResult},
+    models::{Poll, PollResponse, PollTally},
+    services::{content::normalize_text, ChatService},
+};
+
+/// Fewest options a poll can have
+pub const MIN_POLL_OPTIONS: usize = 2;
+
+/// Most options a poll can have
+pub const MAX_POLL_OPTIONS: usize = 10;
+
+/// Longest poll question, in characters
+pub const MAX_POLL_QUESTION_CHARS: usize = 300;
+
+/// Longest poll option, in characters
+pub const MAX_POLL_OPTION_CHARS: usize = 100;
+
+pub struct PollService;
+
+impl PollService {
+    /// A poll as seen by a participant of its chat
+    pub async fn get(db: &Database, poll_id: Uuid, user_id: Uuid) -> AppResult<PollResponse> {
+        let poll = Self::find(db, poll_id).await?;
+        if !ChatService::is_participant(db, poll.chat_id, user_id).await? {
+            return Err(AppError::AccessDenied);
+        }
+        Self::response(db, &poll).await
+    }
+
+    /// The poll a message carries, if any
+    pub async fn for_message(db: &Database, message_id: Uuid) -> AppResult<Option<PollResponse>> {
+        let poll: Option<Poll> = sqlx::query_as("SELECT * FROM polls WHERE message_id = $1")
+            .bind(message_id)
+            .fetch_optional(&db.pool)
+            .await?;
+
+        match poll {
+            Some(poll) => Ok(Some(Self::response(db, &poll).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Replace the user's votes with `option_ids`; an empty list retracts
+    /// them. Single-choice polls take at most one option.
+    ///
+    /// Returns the poll and its new tallies.
+    pub async fn vote(
+        db: &Database,
+        poll_id: Uuid,
+        user_id: Uuid,
+        option_ids: Vec<Uuid>,
+    ) -> AppResult<(Poll, PollResponse)> {
+        let mut tx = db.pool.begin().await?;
+
+        // The share lock keeps the poll from closing while the vote lands
+        let poll: Poll = sqlx::query_as(
+            r#"
+            SELECT p.* FROM polls p
+            JOIN messages m ON m.id = p.message_id
+            WHERE p.id = $1 AND m.deleted_at IS NULL
+            FOR SHARE OF p
+            "#,
+        )
+        .bind(poll_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Poll not found".to_string()))?;
+
+        if !ChatService::is_participant(db, poll.chat_id, user_id).await? {
+            return Err(AppError::AccessDenied);
+        }
+        if poll.closed_at.is_some() {
+            return Err(AppError::BadRequest("Poll is closed".to_string()));
+        }
+        let option_ids = validate_choices(poll.multiple_choice, option_ids)?;
+
+        let known: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM poll_options WHERE poll_id = $1 AND id = ANY($2)",
+        )
+        .bind(poll_id)
+        .bind(&option_ids)
+        .fetch_one(&mut *tx)
+        .await?;
+        if known != option_ids.len() as i64 {
+            return Err(AppError::BadRequest("Unknown poll option".to_string()));
+        }
+
+        sqlx::query(
+            "DELETE FROM poll_votes WHERE poll_id = $1 AND user_id = $2 AND option_id <> ALL($3)",
+        )
+        .bind(poll_id)
+        .bind(user_id)
+        .bind(&option_ids)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO poll_votes (poll_id, user_id, slot, option_id)
+            SELECT $1, $2, CASE WHEN $3 THEN o.position ELSE 0 END, o.id
+            FROM poll_options o
+            WHERE o.poll_id = $1 AND o.id = ANY($4)
+            ON CONFLICT (poll_id, user_id, slot)
+            DO UPDATE SET option_id = EXCLUDED.option_id, voted_at = NOW()
+            "#,
+        )
+        .bind(poll_id)
+        .bind(user_id)
+        .bind(poll.multiple_choice)
+        .bind(&option_ids)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        let response = Self::response(db, &poll).await?;
+        Ok((poll, response))
+    }
+
+    /// Close a poll so it takes no more votes (its creator or a chat admin).
+    /// Closing a closed poll changes nothing.
+    ///
+    /// Returns the poll and its final tallies.
+    pub async fn close(
+        db: &Database,
+        poll_id: Uuid,
+        user_id: Uuid,
+    ) -> AppResult<(Poll, PollResponse)> {
+        let poll = Self::find(db, poll_id).await?;
+
+        let participant = ChatService::participant(db, poll.chat_id, user_id)
+            .await?
+            .ok_or(AppError::AccessDenied)?;
+
+        if poll.creator_id != user_id && participant.role != "admin" {
+            return Err(AppError::Forbidden(
+                "Only the poll's creator or a chat admin can close it".to_string(),
+            ));
+        }
+
+        let poll: Poll = sqlx::query_as(
+            "UPDATE polls SET closed_at = COALESCE(closed_at, NOW()) WHERE id = $1 RETURNING *",
+        )
+        .bind(poll_id)
+        .fetch_one(&db.pool)
+        .await?;
+
+        let response = Self::response(db, &poll).await?;
+        Ok((poll, response))
+    }
+
+    async fn find(db: &Database, poll_id: Uuid) -> AppResult<Poll> {
+        sqlx::query_as("SELECT * FROM polls WHERE id = $1")
+            .bind(poll_id)
+            .fetch_optional(&db.pool)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Poll not found".to_string()))
+    }
+
+    async fn response(db: &Database, poll: &Poll) -> AppResult<PollResponse> {
+        Ok(PollResponse {
+            id: poll.id,
+            question: poll.question.clone(),
+            multiple_choice: poll.multiple_choice,
+            anonymous: poll.anonymous,
+            is_closed: poll.closed_at.is_some(),
+            options: Self::tallies(db, poll).await?,
+        })
+    }
+
+    /// Vote counts of every option, in option order
+    async fn tallies(db: &Database, poll: &Poll) -> AppResult<Vec<PollTally>> {
+        let rows: Vec<(Uuid, String, i64, Vec<Uuid>)> = sqlx::query_as(
+            r#"
+            SELECT o.id, o.text, COUNT(v.user_id),
+                   ARRAY_REMOVE(ARRAY_AGG(v.user_id ORDER BY v.voted_at), NULL)
+            FROM poll_options o
+            LEFT JOIN poll_votes v ON v.option_id = o.id
+            WHERE o.poll_id = $1
+            GROUP BY o.id, o.text, o.position
+            ORDER BY o.position
+            "#,
+        )
+        .bind(poll.id)
+        .fetch_all(&db.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(option_id, text, votes, voters)| PollTally {
+                option_id,
+                text,
+                votes,
+                voters: (!poll.anonymous).then_some(voters),
+            })
+            .collect())
+    }
+}
+
+/// Validate a poll's question and options, returning them normalized
+pub fn normalize_poll(question: &str, options: &[String]) -> AppResult<(String, Vec<String>)> {
+    let question = normalize_poll_text(question, MAX_POLL_QUESTION_CHARS, "question")?;
+
+    if !(MIN_POLL_OPTIONS..=MAX_POLL_OPTIONS).contains(&options.len()) {
+        return Err(AppError::BadRequest(format!(
+            "A poll needs between {} and {} options",
+            MIN_POLL_OPTIONS, MAX_POLL_OPTIONS
+        )));
+    }
+
+    let mut seen = HashSet::new();
+    let mut normalized = Vec::with_capacity(options.len());
+    for option in options {
+        let option = normalize_poll_text(option, MAX_POLL_OPTION_CHARS, "option")?;
+        if !seen.insert(option.to_lowercase()) {
+            return Err(AppError::BadRequest(format!(
+                "Poll option '{}' is listed twice",
+                option
+            )));
+        }
+        normalized.push(option);
+    }
+
+    Ok((question, normalized))
+}
+
+fn normalize_poll_text(text: &str, max_chars: usize, what: &str) -> AppResult<String> {
+    let text = normalize_text(text, usize::MAX).map_err(|e| match e {
+        AppError::EmptyMessage => AppError::BadRequest(format!("Poll {} is empty", what)),
+        other => other,
+    })?;
+    if text.chars().count() > max_chars {
+        return Err(AppError::BadRequest(format!(
+            "Poll {} exceeds {} characters",
+            what, max_chars
+        )));
+    }
+    Ok(text)
+}
+
+/// Drop repeated options and check single-choice polls get at most one
+fn validate_choices(multiple_choice: bool, option_ids: Vec<Uuid>) -> AppResult<Vec<Uuid>> {
+    let mut seen = HashSet::new();
+    let option_ids: Vec<Uuid> = option_ids
+        .into_iter()
+        .filter(|id| seen.insert(*id))
+        .collect();
+
+    if !multiple_choice && option_ids.len() > 1 {
+        return Err(AppError::BadRequest(
+            "This poll allows a single choice".to_string(),
+        ));
+    }
+    Ok(option_ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn test_normalize_poll_trims_question_and_options() {
+        let (question, normalized) =
+            normalize_poll("  Lunch?  ", &options(&[" Pizza", "Sushi "])).unwrap();
+        assert_eq!(question, "Lunch?");
+        assert_eq!(normalized, ["Pizza", "Sushi"]);
+    }
+
+    #[test]
+    fn test_normalize_poll_rejects_bad_options() {
+        let too_many: Vec<String> = (0..=MAX_POLL_OPTIONS).map(|i| i.to_string()).collect();
+
+        assert!(normalize_poll("Q", &options(&["only one"])).is_err());
+        assert!(normalize_poll("Q", &too_many).is_err());
+        assert!(normalize_poll("Q", &options(&["Yes", "  "])).is_err());
+        assert!(normalize_poll("Q", &options(&["Yes", "yes"])).is_err());
+        assert!(normalize_poll(" ", &options(&["Yes", "No"])).is_err());
+        assert!(normalize_poll(
+            &"?".repeat(MAX_POLL_QUESTION_CHARS + 1),
+            &options(&["Yes", "No"])
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_validate_choices() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+
+        assert_eq!(validate_choices(false, vec![a, a]).unwrap(), [a]);
+        assert!(validate_choices(false, vec![a, b]).is_err());
+        assert_eq!(validate_choices(true, vec![b, a, b]).unwrap(), [b, a]);
+        assert!(validate_choices(false, Vec::new()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,570 @@
+/// Web Push Service
+///
+/// Browsers register a push subscription (endpoint plus ECDH/auth keys) per
+/// device. When a message is sent, recipients with no live WebSocket or QUIC
+/// connection get a VAPID-signed, aes128gcm-encrypted push on every
+/// subscription, unless they muted the chat or turned off notifications for
+/// its kind (private, group, channel). Subscriptions registered from a login
+/// session follow that device's notification toggles, falling back to the
+/// user's settings. Admin system announcements can be pushed to offline users
+/// as well.
+///
+/// Subscriptions the push service reports as gone (404/410) are deleted.
+/// Push is disabled when no VAPID key pair is configured.
+
+use std::time::Duration;
+
+use anyhow::Context;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use reqwest::{header, redirect, StatusCode};
+use serde::Serialize;
+use url::{Host, Url};
+use uuid::Uuid;
+use web_push::{ContentEncoding, SubscriptionInfo, VapidSignatureBuilder, WebPushMessageBuilder};
+
+use crate::{
+    config::Config,
+    db::Database,
+    error::{AppError, AppResult},
+    models::{AnnouncementLevel, MessageResponse, PushSubscription},
+    services::geoip::is_non_public,
+};
+
+/// How long push services keep an undelivered notification (24 hours)
+pub const PUSH_TTL_SECONDS: u32 = 24 * 3600;
+
+/// Most subscriptions kept per user; registering more drops the oldest
+pub const MAX_SUBSCRIPTIONS_PER_USER: i64 = 20;
+
+/// Time allowed for one request to a push service
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest message preview shown in a notification
+const MAX_BODY_CHARS: usize = 120;
+
+/// VAPID settings taken from `Config`
+#[derive(Debug, Clone)]
+pub struct PushConfig {
+    pub public_key: Option<String>,
+    pub private_key: Option<String>,
+    pub subject: String,
+}
+
+impl From<&Config> for PushConfig {
+    fn from(config: &Config) -> Self {
+        Self {
+            public_key: config.vapid_public_key.clone(),
+            private_key: config.vapid_private_key.clone(),
+            subject: config.vapid_subject.clone(),
+        }
+    }
+}
+
+/// Notification content, encrypted and handed to the service worker as JSON
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PushPayload {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chat_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<Uuid>,
+    pub title: String,
+    pub body: String,
+}
+
+/// What a push service answered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SendOutcome {
+    Delivered,
+    /// The subscription expired or was revoked; delete it
+    Gone,
+    Failed,
+}
+
+#[derive(Clone)]
+pub struct PushService {
+    http: reqwest::Client,
+    config: PushConfig,
+}
+
+impl PushService {
+    pub fn new(config: PushConfig) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(SEND_TIMEOUT)
+            .redirect(redirect::Policy::none())
+            .build()
+            .unwrap_or_default();
+        Self { http, config }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.private_key.is_some() && self.config.public_key.is_some()
+    }
+
+    /// Application server key clients pass to `pushManager.subscribe`
+    pub fn public_key(&self) -> Option<&str> {
+        self.config.public_key.as_deref()
+    }
+
+    /// Register (or re-register) a browser subscription for `user_id`
+    ///
+    /// The subscription is tied to the login session behind `session_token`,
+    /// so that session's device notification settings apply to it.
+    pub async fn subscribe(
+        db: &Database,
+        user_id: Uuid,
+        endpoint: &str,
+        p256dh: &str,
+        auth: &str,
+        user_agent: Option<&str>,
+        session_token: &str,
+    ) -> AppResult<PushSubscription> {
+        validate_subscription(endpoint, p256dh, auth)?;
+
+        let subscription: PushSubscription = sqlx::query_as(
+            r#"
+            INSERT INTO push_subscriptions (user_id, endpoint, p256dh, auth, user_agent, session_id)
+            VALUES ($1, $2, $3, $4, $5,
+                    (SELECT id FROM sessions WHERE user_id = $1 AND token = $6))
+            ON CONFLICT (endpoint) DO UPDATE
+            SET user_id = EXCLUDED.user_id, p256dh = EXCLUDED.p256dh,
+                auth = EXCLUDED.auth, user_agent = EXCLUDED.user_agent,
+                session_id = EXCLUDED.session_id, created_at = NOW()
+            RETURNING *
+            "#,
+        )
+        .bind(user_id)
+        .bind(endpoint)
+        .bind(p256dh.trim_end_matches('='))
+        .bind(auth.trim_end_matches('='))
+        .bind(user_agent)
+        .bind(session_token)
+        .fetch_one(&db.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM push_subscriptions
+            WHERE user_id = $1 AND id NOT IN (
+                SELECT id FROM push_subscriptions WHERE user_id = $1
+                ORDER BY created_at DESC LIMIT $2
+            )
+            "#,
+        )
+        .bind(user_id)
+        .bind(MAX_SUBSCRIPTIONS_PER_USER)
+        .execute(&db.pool)
+        .await?;
+
+        Ok(subscription)
+    }
+
+    /// Remove one of `user_id`'s subscriptions
+    pub async fn unsubscribe(db: &Database, user_id: Uuid, endpoint: &str) -> AppResult<()> {
+        let result =
+            sqlx::query("DELETE FROM push_subscriptions WHERE user_id = $1 AND endpoint = $2")
+                .bind(user_id)
+                .bind(endpoint)
+                .execute(&db.pool)
+                .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound(
+                "Push subscription not found".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Push `payload` to every subscription of `user_id`
+    ///
+    /// Returns how many push services accepted it. Subscriptions reported as
+    /// gone are deleted; other failures are logged and skipped.
+    pub async fn notify(
+        &self,
+        db: &Database,
+        user_id: Uuid,
+        payload: &PushPayload,
+    ) -> AppResult<usize> {
+        if !self.is_enabled() {
+            return Ok(0);
+        }
+
+        let subscriptions: Vec<PushSubscription> =
+            sqlx::query_as("SELECT * FROM push_subscriptions WHERE user_id = $1")
+                .bind(user_id)
+                .fetch_all(&db.pool)
+                .await?;
+        self.deliver(db, &subscriptions, payload).await
+    }
+
+    /// Push `payload` about a message in `chat_id` to `user_id`'s devices
+    ///
+    /// Only subscriptions whose device has notifications for the chat's kind
+    /// turned on are used.
+    async fn notify_for_chat(
+        &self,
+        db: &Database,
+        user_id: Uuid,
+        chat_id: Uuid,
+        payload: &PushPayload,
+    ) -> AppResult<usize> {
+        let subscriptions: Vec<PushSubscription> = sqlx::query_as(
+            r#"
+            SELECT ps.* FROM push_subscriptions ps
+            JOIN chats c ON c.id = $2
+            LEFT JOIN sessions s ON s.id = ps.session_id
+            LEFT JOIN user_settings us ON us.user_id = ps.user_id
+            WHERE ps.user_id = $1
+              AND CASE c.type
+                    WHEN 'group' THEN COALESCE(s.group_notifications, us.group_notifications, TRUE)
+                    WHEN 'channel' THEN COALESCE(s.channel_notifications, us.channel_notifications, TRUE)
+                    ELSE COALESCE(s.message_notifications, us.message_notifications, TRUE)
+                  END
+            "#,
+        )
+        .bind(user_id)
+        .bind(chat_id)
+        .fetch_all(&db.pool)
+        .await?;
+        self.deliver(db, &subscriptions, payload).await
+    }
+
+    /// Send `payload` to each of `subscriptions`
+    async fn deliver(
+        &self,
+        db: &Database,
+        subscriptions: &[PushSubscription],
+        payload: &PushPayload,
+    ) -> AppResult<usize> {
+        if subscriptions.is_empty() {
+            return Ok(0);
+        }
+        let body = serde_json::to_vec(payload)
+            .map_err(|e| AppError::Internal(anyhow::anyhow!("Push payload: {}", e)))?;
+
+        let mut delivered = 0;
+        for subscription in subscriptions {
+            match self.send(subscription, &body).await {
+                Ok(SendOutcome::Delivered) => {
+                    delivered += 1;
+                    sqlx::query("UPDATE push_subscriptions SET last_used_at = NOW() WHERE id = $1")
+                        .bind(subscription.id)
+                        .execute(&db.pool)
+                        .await?;
+                }
+                Ok(SendOutcome::Gone) => {
+                    tracing::debug!("Pruning expired push subscription {}", subscription.id);
+                    sqlx::query("DELETE FROM push_subscriptions WHERE id = $1")
+                        .bind(subscription.id)
+                        .execute(&db.pool)
+                        .await?;
+                }
+                Ok(SendOutcome::Failed) => {}
+                Err(e) => {
+                    tracing::warn!("Push to subscription {} failed: {:#}", subscription.id, e);
+                }
+            }
+        }
+
+        Ok(delivered)
+    }
+
+    /// Notify offline `recipients` of a new message in the background
+    ///
+    /// Recipients who muted the chat or have no subscriptions are skipped,
+    /// as are devices with notifications for this kind of chat turned off. A muted chat still
+    /// notifies users mentioned in the message who opted into mention
+    /// exceptions.
+    pub fn spawn_for_message(
+        &self,
+        db: Database,
+        message: &MessageResponse,
+        recipients: Vec<Uuid>,
+    ) {
+        if !self.is_enabled() || recipients.is_empty() {
+            return;
+        }
+
+        let service = self.clone();
+        let message = message.clone();
+        tokio::spawn(async move {
+            if let Err(e) = service.notify_message(&db, &message, &recipients).await {
+                tracing::warn!(
+                    "Push notifications for message {} failed: {}",
+                    message.id,
+                    e
+                );
+            }
+        });
+    }
+
+    async fn notify_message(
+        &self,
+        db: &Database,
+        message: &MessageResponse,
+        recipients: &[Uuid],
+    ) -> AppResult<()> {
+        let targets: Vec<Uuid> = sqlx::query_scalar(
+            r#"
+            SELECT cp.user_id FROM chat_participants cp
+            LEFT JOIN chat_user_state cs ON cs.chat_id = cp.chat_id AND cs.user_id = cp.user_id
+            LEFT JOIN user_settings us ON us.user_id = cp.user_id
+            WHERE cp.chat_id = $1 AND cp.user_id = ANY($2)
+              AND (
+                    COALESCE(cs.muted, FALSE) = FALSE
+                    OR (COALESCE(us.mention_notify_when_muted, FALSE) AND EXISTS (
+                        SELECT 1 FROM message_mentions mm
+                        WHERE mm.message_id = $3 AND mm.user_id = cp.user_id
+                          AND mm.kind != 'here'
+                    ))
+                  )
+              AND EXISTS (SELECT 1 FROM push_subscriptions ps WHERE ps.user_id = cp.user_id)
+            "#,
+        )
+        .bind(message.chat_id)
+        .bind(recipients)
+        .bind(message.id)
+        .fetch_all(&db.pool)
+        .await?;
+        if targets.is_empty() {
+            return Ok(());
+        }
+
+        let title: Option<String> = sqlx::query_scalar(
+            r#"
+            SELECT COALESCE(
+                (SELECT name FROM users WHERE id = $1),
+                (SELECT name FROM bots WHERE id = $1)
+            )
+            "#,
+        )
+        .bind(message.sender_id)
+        .fetch_one(&db.pool)
+        .await?;
+
+        let payload = PushPayload {
+            kind: "new_message".to_string(),
+            chat_id: Some(message.chat_id),
+            message_id: Some(message.id),
+            title: title.unwrap_or_else(|| "New message".to_string()),
+            body: notification_body(
+                // Ciphertext says nothing to the reader
+                message.text.as_deref().filter(|_| !message.encrypted),
+                !message.attachments.is_empty(),
+            ),
+        };
+        for user_id in targets {
+            self.notify_for_chat(db, user_id, message.chat_id, &payload).await?;
+        }
+        Ok(())
+    }
+
+    /// Push a system announcement to `recipients` in the background
+    ///
+    /// Announcements ignore chat mutes and per-kind notification settings.
+    pub fn spawn_for_announcement(
+        &self,
+        db: Database,
+        text: &str,
+        level: AnnouncementLevel,
+        recipients: Vec<Uuid>,
+    ) {
+        if !self.is_enabled() || recipients.is_empty() {
+            return;
+        }
+
+        let service = self.clone();
+        let payload = PushPayload {
+            kind: "system_announcement".to_string(),
+            chat_id: None,
+            message_id: None,
+            title: announcement_title(level).to_string(),
+            body: notification_body(Some(text), false),
+        };
+        tokio::spawn(async move {
+            for user_id in recipients {
+                if let Err(e) = service.notify(&db, user_id, &payload).await {
+                    tracing::warn!("Announcement push to user {} failed: {}", user_id, e);
+                }
+            }
+        });
+    }
+
+    /// Encrypt `body` for one subscription and post it to its push service
+    async fn send(
+        &self,
+        subscription: &PushSubscription,
+        body: &[u8],
+    ) -> anyhow::Result<SendOutcome> {
+        let private_key = self
+            .config
+            .private_key
+            .as_deref()
+            .context("VAPID private key not configured")?;
+        let info = SubscriptionInfo::new(
+            subscription.endpoint.as_str(),
+            subscription.p256dh.as_str(),
+            subscription.auth.as_str(),
+        );
+
+        let mut signature = VapidSignatureBuilder::from_base64(private_key, &info)
+            .context("Invalid VAPID private key")?;
+        signature.add_claim("sub", self.config.subject.as_str());
+
+        let mut builder = WebPushMessageBuilder::new(&info);
+        builder.set_ttl(PUSH_TTL_SECONDS);
+        builder.set_payload(ContentEncoding::Aes128Gcm, body);
+        builder.set_vapid_signature(signature.build().context("VAPID signing failed")?);
+        let message = builder.build().context("Push encryption failed")?;
+
+        let mut request = self
+            .http
+            .post(subscription.endpoint.as_str())
+            .header("TTL", message.ttl.to_string());
+        if let Some(payload) = message.payload {
+            request = request
+                .header(header::CONTENT_ENCODING, payload.content_encoding.to_str())
+                .header(header::CONTENT_TYPE, "application/octet-stream");
+            // Includes the VAPID `Authorization` header
+            for (name, value) in payload.crypto_headers {
+                request = request.header(name, value);
+            }
+            request = request.body(payload.content);
+        }
+
+        let status = request
+            .send()
+            .await
+            .context("Push request failed")?
+            .status();
+        let outcome = classify_status(status);
+        if outcome == SendOutcome::Failed {
+            tracing::warn!(
+                "Push service rejected subscription {}: {}",
+                subscription.id,
+                status
+            );
+        }
+        Ok(outcome)
+    }
+}
+
+fn classify_status(status: StatusCode) -> SendOutcome {
+    match status {
+        s if s.is_success() => SendOutcome::Delivered,
+        StatusCode::NOT_FOUND | StatusCode::GONE => SendOutcome::Gone,
+        _ => SendOutcome::Failed,
+    }
+}
+
+/// Notification title of a system announcement
+fn announcement_title(level: AnnouncementLevel) -> &'static str {
+    match level {
+        AnnouncementLevel::Info => "Announcement",
+        AnnouncementLevel::Warning => "Warning",
+        AnnouncementLevel::Critical => "Critical notice",
+    }
+}
+
+/// Message preview shown in the notification
+fn notification_body(text: Option<&str>, has_attachments: bool) -> String {
+    match text.map(str::trim).filter(|t| !t.is_empty()) {
+        Some(text) if text.chars().count() > MAX_BODY_CHARS => {
+            let mut preview: String = text.chars().take(MAX_BODY_CHARS).collect();
+            preview.push('…');
+            preview
+        }
+        Some(text) => text.to_string(),
+        None if has_attachments => "Sent an attachment".to_string(),
+        None => "New message".to_string(),
+    }
+}
+
+/// Check a subscription before storing it
+///
+/// The endpoint must be an https URL that does not point at a non-public
+/// address, `p256dh` an uncompressed P-256 point (65 bytes) and `auth` a
+/// 16-byte secret, both base64url.
+pub fn validate_subscription(endpoint: &str, p256dh: &str, auth: &str) -> AppResult<()> {
+    let invalid =
+        |reason: &str| AppError::BadRequest(format!("Invalid push subscription: {}", reason));
+
+    let url = Url::parse(endpoint).map_err(|_| invalid("endpoint is not a URL"))?;
+    if url.scheme() != "https" {
+        return Err(invalid("endpoint must use https"));
+    }
+    match url.host() {
+        Some(Host::Domain(domain)) if domain != "localhost" && domain.contains('.') => {}
+        Some(Host::Ipv4(ip)) if !is_non_public(ip.into()) => {}
+        Some(Host::Ipv6(ip)) if !is_non_public(ip.into()) => {}
+        _ => return Err(invalid("endpoint host is not public")),
+    }
+
+    let decode = |key: &str| URL_SAFE_NO_PAD.decode(key.trim_end_matches('=')).ok();
+    if decode(p256dh).map(|k| k.len()) != Some(65) {
+        return Err(invalid("p256dh must be a 65-byte P-256 public key"));
+    }
+    if decode(auth).map(|k| k.len()) != Some(16) {
+        return Err(invalid("auth must be a 16-byte secret"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(len: usize) -> String {
+        URL_SAFE_NO_PAD.encode(vec![4u8; len])
+    }
+
+    #[test]
+    fn test_validate_subscription() {
+        let endpoint = "https://fcm.googleapis.com/fcm/send/abc";
+        assert!(validate_subscription(endpoint, &key(65), &key(16)).is_ok());
+        // Padded base64 is accepted too
+        assert!(validate_subscription(endpoint, &key(65), "BAQEBAQEBAQEBAQEBAQEBA==").is_ok());
+
+        assert!(validate_subscription("http://push.example.com/x", &key(65), &key(16)).is_err());
+        assert!(validate_subscription("https://127.0.0.1/x", &key(65), &key(16)).is_err());
+        assert!(validate_subscription("https://localhost/x", &key(65), &key(16)).is_err());
+        assert!(validate_subscription(endpoint, &key(33), &key(16)).is_err());
+        assert!(validate_subscription(endpoint, &key(65), "not base64!").is_err());
+    }
+
+    #[test]
+    fn test_classify_status() {
+        assert_eq!(classify_status(StatusCode::CREATED), SendOutcome::Delivered);
+        assert_eq!(classify_status(StatusCode::GONE), SendOutcome::Gone);
+        assert_eq!(classify_status(StatusCode::NOT_FOUND), SendOutcome::Gone);
+        assert_eq!(
+            classify_status(StatusCode::TOO_MANY_REQUESTS),
+            SendOutcome::Failed
+        );
+    }
+
+    #[test]
+    fn test_notification_body() {
+        assert_eq!(notification_body(Some(" hi "), false), "hi");
+        assert_eq!(notification_body(None, true), "Sent an attachment");
+        assert_eq!(notification_body(Some(""), false), "New message");
+
+        let long = "é".repeat(200);
+        let body = notification_body(Some(&long), false);
+        assert_eq!(body.chars().count(), MAX_BODY_CHARS + 1);
+        assert!(body.ends_with('…'));
+    }
+
+    #[test]
+    fn test_disabled_without_keys() {
+        let service = PushService::new(PushConfig {
+            public_key: Some("BPublic".to_string()),
+            private_key: None,
+            subject: "mailto:admin@example.com".to_string(),
+        });
+        assert!(!service.is_enabled());
+    }
+}
@@ -0,0 +1,228 @@
+/// Reaction Limiter Service
+///
+/// Keeps reaction toggling from flooding chats:
+/// - `ReactionLimiter`, a per-user token bucket in Redis
+///   (`reaction:bucket:{user_id}`) holding up to `REACTION_BURST` toggles,
+///   refilled at `REACTIONS_PER_MINUTE`. Without Redis reactions are not
+///   limited; `ADMIN_USER_IDS` are exempt.
+/// - `ReactionDebouncer`, which turns the changes made to a message within
+///   `REACTION_DEBOUNCE_MS` into a single `reaction_updated` broadcast
+///   carrying the counts as they are at the end of the window.
+use crate::{
+    config::Config,
+    db::Database,
+    error::{AppError, AppResult},
+    services::{ChatService, MessageService, WebSocketService},
+    ws::WsManager,
+};
+use chrono::Utc;
+use redis::aio::ConnectionManager;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Take one token from the bucket in KEYS[1]
+///
+/// ARGV: capacity, tokens refilled per millisecond, now (unix ms).
+/// Returns 0 when a token was taken, otherwise the milliseconds until one
+/// is available.
+const TOKEN_BUCKET_SCRIPT: &str = r#"
+local capacity = tonumber(ARGV[1])
+local refill_per_ms = tonumber(ARGV[2])
+local now = tonumber(ARGV[3])
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(bucket[1]) or capacity
+local ts = tonumber(bucket[2]) or now
+tokens = math.min(capacity, tokens + math.max(0, now - ts) * refill_per_ms)
+local wait = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+else
+    wait = math.ceil((1 - tokens) / refill_per_ms)
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil(capacity / refill_per_ms))
+return wait
+"#;
+
+/// Reaction limits taken from `Config`; 0 reactions per minute disables the limit
+#[derive(Debug, Clone)]
+pub struct ReactionLimitConfig {
+    pub reactions_per_minute: u32,
+    pub burst: u32,
+    pub exempt_user_ids: Vec<Uuid>,
+}
+
+impl From<&Config> for ReactionLimitConfig {
+    fn from(config: &Config) -> Self {
+        Self {
+            reactions_per_minute: config.reactions_per_minute,
+            burst: config.reaction_burst,
+            exempt_user_ids: config.admin_user_ids.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ReactionLimiter {
+    redis: Option<ConnectionManager>,
+    config: ReactionLimitConfig,
+}
+
+impl ReactionLimiter {
+    pub fn new(redis: Option<ConnectionManager>, config: ReactionLimitConfig) -> Self {
+        Self { redis, config }
+    }
+
+    /// Take a token for one reaction toggle by `user_id`
+    ///
+    /// Returns `AppError::ReactionRateLimited(retry_after)` when the user's
+    /// bucket is empty.
+    pub async fn check(&self, user_id: Uuid) -> AppResult<()> {
+        if self.config.reactions_per_minute == 0
+            || self.config.exempt_user_ids.contains(&user_id)
+        {
+            return Ok(());
+        }
+        let Some(redis) = &self.redis else {
+            return Ok(());
+        };
+        let mut conn = redis.clone();
+
+        let refill_per_ms = f64::from(self.config.reactions_per_minute) / 60_000.0;
+        let wait_ms: u64 = redis::Script::new(TOKEN_BUCKET_SCRIPT)
+            .key(bucket_key(user_id))
+            .arg(self.config.burst.max(1))
+            .arg(refill_per_ms)
+            .arg(Utc::now().timestamp_millis())
+            .invoke_async(&mut conn)
+            .await
+            .map_err(redis_error)?;
+
+        if wait_ms > 0 {
+            return Err(AppError::ReactionRateLimited(retry_after_seconds(wait_ms)));
+        }
+        Ok(())
+    }
+}
+
+fn bucket_key(user_id: Uuid) -> String {
+    format!("reaction:bucket:{}", user_id)
+}
+
+fn redis_error(e: redis::RedisError) -> AppError {
+    AppError::Internal(anyhow::anyhow!("Redis error: {}", e))
+}
+
+/// Whole seconds to wait, rounded up so clients never retry too early
+fn retry_after_seconds(wait_ms: u64) -> u32 {
+    u32::try_from(wait_ms.div_ceil(1000)).unwrap_or(u32::MAX)
+}
+
+/// Reaction changes to one message waiting to be broadcast
+#[derive(Debug)]
+struct PendingUpdate {
+    chat_id: Uuid,
+    /// Users who toggled a reaction during the window
+    reactors: HashSet<Uuid>,
+}
+
+/// Coalesces `reaction_updated` broadcasts per message
+pub struct ReactionDebouncer {
+    db: Database,
+    ws_manager: Arc<WsManager>,
+    window: Duration,
+    pending: Mutex<HashMap<Uuid, PendingUpdate>>,
+}
+
+impl ReactionDebouncer {
+    pub fn new(db: Database, ws_manager: Arc<WsManager>, window: Duration) -> Arc<Self> {
+        Arc::new(Self {
+            db,
+            ws_manager,
+            window,
+            pending: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Broadcast the reactions of a message `user_id` just changed
+    ///
+    /// The first change to a message starts the window; later changes in
+    /// it only join the broadcast sent when it ends. A user who was the only
+    /// one to react during the window already has the final state from
+    /// their own response and is left out.
+    pub async fn schedule(self: &Arc<Self>, chat_id: Uuid, message_id: Uuid, user_id: Uuid) {
+        if self.window.is_zero() {
+            if let Err(e) = self.broadcast(chat_id, message_id, Some(user_id)).await {
+                tracing::warn!("Failed to broadcast reactions of message {}: {}", message_id, e);
+            }
+            return;
+        }
+
+        {
+            let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(update) = pending.get_mut(&message_id) {
+                update.reactors.insert(user_id);
+                return;
+            }
+            pending.insert(
+                message_id,
+                PendingUpdate {
+                    chat_id,
+                    reactors: HashSet::from([user_id]),
+                },
+            );
+        }
+
+        let debouncer = Arc::clone(self);
+        tokio::spawn(async move {
+            tokio::time::sleep(debouncer.window).await;
+            debouncer.flush(message_id).await;
+        });
+    }
+
+    async fn flush(&self, message_id: Uuid) {
+        let update = self
+            .pending
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&message_id);
+        let Some(update) = update else {
+            return;
+        };
+
+        let exclude = match update.reactors.len() {
+            1 => update.reactors.into_iter().next(),
+            _ => None,
+        };
+        if let Err(e) = self.broadcast(update.chat_id, message_id, exclude).await {
+            tracing::warn!("Failed to broadcast reactions of message {}: {}", message_id, e);
+        }
+    }
+
+    async fn broadcast(&self, chat_id: Uuid, message_id: Uuid, exclude: Option<Uuid>) -> AppResult<()> {
+        let message = MessageService::get_message_response(&self.db, chat_id, message_id).await?;
+        let participant_ids = ChatService::get_participant_ids(&self.db, chat_id).await?;
+        WebSocketService::broadcast_reaction_updated(
+            &self.ws_manager,
+            message,
+            &participant_ids,
+            exclude,
+        )
+        .await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_after_rounds_up() {
+        assert_eq!(retry_after_seconds(1), 1);
+        assert_eq!(retry_after_seconds(1000), 1);
+        assert_eq!(retry_after_seconds(1001), 2);
+    }
+}
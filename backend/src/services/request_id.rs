@@ -0,0 +1,118 @@
+/// Request correlation IDs
+///
+/// Every HTTP request, WebSocket client event and QUIC frame runs inside a
+/// `request` tracing span carrying a `request_id`, so the logs of a single
+/// user action can be grepped end to end across transports. The id is also
+/// kept in a task-local so it can be forwarded to bots and webhooks.
+
+use axum::{extract::Request, http::HeaderValue, middleware::Next, response::Response};
+use serde::Deserialize;
+use std::future::Future;
+use tracing::Instrument;
+use uuid::Uuid;
+
+/// Header accepted from HTTP clients and echoed on every response
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest caller-supplied id that is accepted as-is
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Optional trace id carried at the top level of a WebSocket/QUIC frame,
+/// next to `event` and `data`
+#[derive(Deserialize)]
+struct FrameEnvelope {
+    #[serde(rename = "traceId")]
+    trace_id: Option<String>,
+}
+
+/// Generate a fresh request id
+pub fn generate() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+/// Use the caller's id if it is short printable ASCII, otherwise generate one
+pub fn resolve(candidate: Option<&str>) -> String {
+    candidate
+        .map(str::trim)
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LEN
+                && id.bytes().all(|b| b.is_ascii_graphic())
+        })
+        .map(str::to_string)
+        .unwrap_or_else(generate)
+}
+
+/// Resolve the request id for a raw WebSocket/QUIC frame
+pub fn from_frame(text: &str) -> String {
+    let trace_id = serde_json::from_str::<FrameEnvelope>(text)
+        .ok()
+        .and_then(|envelope| envelope.trace_id);
+    resolve(trace_id.as_deref())
+}
+
+/// The request id of the action currently being handled, if any
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Run `fut` with `request_id` as the current id and inside a `request` span
+pub async fn scope<F: Future>(request_id: String, fut: F) -> F::Output {
+    let span = tracing::info_span!("request", request_id = %request_id);
+    REQUEST_ID.scope(request_id, fut.instrument(span)).await
+}
+
+/// Axum middleware: accept or generate `X-Request-Id` and echo it in the response
+pub async fn middleware(request: Request, next: Next) -> Response {
+    let request_id = resolve(
+        request
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok()),
+    );
+
+    let mut response = scope(request_id.clone(), next.run(request)).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_keeps_valid_id() {
+        assert_eq!(resolve(Some("abc-123")), "abc-123");
+        assert_eq!(resolve(Some("  abc-123 ")), "abc-123");
+    }
+
+    #[test]
+    fn test_resolve_replaces_invalid_id() {
+        for candidate in [None, Some(""), Some("has space"), Some("line\nbreak")] {
+            let id = resolve(candidate);
+            assert_eq!(id.len(), 32, "{candidate:?}");
+        }
+        assert_eq!(resolve(Some(&"a".repeat(MAX_REQUEST_ID_LEN + 1))).len(), 32);
+    }
+
+    #[test]
+    fn test_from_frame() {
+        let frame = r#"{"event":"start_typing","data":{"chatId":"00000000-0000-0000-0000-000000000000"},"traceId":"trace-1"}"#;
+        assert_eq!(from_frame(frame), "trace-1");
+        assert_eq!(from_frame(r#"{"event":"start_typing"}"#).len(), 32);
+        assert_eq!(from_frame("not json").len(), 32);
+    }
+
+    #[tokio::test]
+    async fn test_scope_sets_current() {
+        assert_eq!(current(), None);
+        let seen = scope("req-1".to_string(), async { current() }).await;
+        assert_eq!(seen.as_deref(), Some("req-1"));
+    }
+}
@@ -0,0 +1,133 @@
+/// Message Retention Service
+///
+/// Deletes messages older than the retention period: `chats.retention_days`
+/// when set, otherwise `MESSAGE_RETENTION_DAYS` (0 keeps messages forever).
+/// Pinned messages are kept, and thread roots wait until their replies are
+/// gone. Deletion runs in small transactional batches and is silent: clients
+/// are not notified, the messages just stop being returned.
+
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::{
+    db::Database,
+    error::AppResult,
+    services::DisappearingMessageService,
+    AppState,
+};
+
+/// Messages deleted per transaction
+const PRUNE_BATCH_SIZE: i64 = 500;
+
+pub struct MessageRetentionService;
+
+impl MessageRetentionService {
+    /// Spawn the background pruner. Runs until the process exits.
+    pub fn spawn_pruner(state: Arc<AppState>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match Self::run_once(&state).await {
+                    Ok(0) => {}
+                    Ok(pruned) => tracing::info!("Pruned {} messages past retention", pruned),
+                    Err(e) => tracing::error!("Message retention pruner failed: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Delete every expired message, one batch at a time
+    pub async fn run_once(state: &AppState) -> AppResult<usize> {
+        let global_days = state.config.message_retention_days as i32;
+        let mut total = 0;
+
+        loop {
+            let (pruned, urls) = Self::prune_batch(&state.db, global_days, PRUNE_BATCH_SIZE).await?;
+            total += pruned;
+
+            for url in &urls {
+                DisappearingMessageService::remove_upload_if_unreferenced(&state.db, url).await?;
+            }
+
+            if (pruned as i64) < PRUNE_BATCH_SIZE {
+                return Ok(total);
+            }
+            // Let other queries in between batches
+            tokio::task::yield_now().await;
+        }
+    }
+
+    /// Delete up to `limit` expired messages in one transaction
+    ///
+    /// Returns how many were deleted and the URLs of their attachments, so
+    /// the stored files can be removed once the transaction has committed.
+    async fn prune_batch(
+        db: &Database,
+        global_days: i32,
+        limit: i64,
+    ) -> AppResult<(usize, Vec<String>)> {
+        let mut tx = db.pool.begin().await?;
+
+        // The outer SELECT sees the pre-delete snapshot, so attachments are still visible
+        let rows: Vec<(Uuid, Option<Uuid>, Option<String>)> = sqlx::query_as(
+            r#"
+            WITH expired AS (
+                DELETE FROM messages
+                WHERE id IN (
+                    SELECT m.id FROM messages m
+                    JOIN chats c ON c.id = m.chat_id
+                    WHERE COALESCE(c.retention_days, $1) > 0
+                      AND m.created_at < NOW() - make_interval(days => COALESCE(c.retention_days, $1))
+                      AND m.is_pinned IS NOT TRUE
+                      AND m.reply_count = 0
+                    ORDER BY m.created_at
+                    LIMIT $2
+                )
+                RETURNING id, thread_root_id
+            )
+            SELECT e.id, e.thread_root_id, a.url
+            FROM expired e
+            LEFT JOIN attachments a ON a.message_id = e.id
+            "#,
+        )
+        .bind(global_days)
+        .bind(limit)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let mut deleted: Vec<(Uuid, Option<Uuid>)> = Vec::new();
+        let mut urls = Vec::new();
+        for (id, thread_root_id, url) in rows {
+            if !deleted.iter().any(|(d, _)| *d == id) {
+                deleted.push((id, thread_root_id));
+            }
+            urls.extend(url);
+        }
+
+        // Deleted thread replies no longer count towards their root
+        let roots: Vec<Uuid> = deleted.iter().filter_map(|(_, root)| *root).collect();
+        if !roots.is_empty() {
+            sqlx::query(
+                r#"
+                UPDATE messages m
+                SET reply_count = GREATEST(m.reply_count - r.removed, 0)
+                FROM (
+                    SELECT root_id, COUNT(*)::integer AS removed
+                    FROM UNNEST($1::uuid[]) AS root_id
+                    GROUP BY root_id
+                ) r
+                WHERE m.id = r.root_id
+                "#,
+            )
+            .bind(&roots)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok((deleted.len(), urls))
+    }
+}
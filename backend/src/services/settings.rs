@@ -2,9 +2,12 @@ use crate::{
     db::Database,
     error::{AppError, AppResult},
     models::{
-        AppearanceSettings, ChatSettings, DataStorageSettings, DeviceResponse,
-        NotificationSettings, PrivacySettings, ProfileResponse, Session, User, UserSettings,
+        AppearanceSettings, ChatSettings, DataStorageSettings, DeviceNotificationPrefs,
+        DeviceNotificationSettings, DeviceResponse, NotificationSettings, RealtimeConnectionResponse, PrivacySettings, ProfileResponse, Session, User, UserSettings,
     },
+    quic::{ConnectionId, ConnectionInfo, ConnectionManager, TransportType},
+    services::{geoip::UNKNOWN_LOCATION, timezone::parse_timezone, user::phone_hash},
+    ws::WsManager,
 };
 use uuid::Uuid;
 
@@ -26,9 +29,16 @@ impl SettingsService {
             phone: user.phone,
             email: user.email,
             avatar: user.avatar,
+            version: user.profile_version,
         })
     }
 
+    /// Update profile fields that are `Some`
+    ///
+    /// With `expected_version`, the update only applies if the stored version
+    /// still matches; otherwise `AppError::VersionConflict` carries the
+    /// current profile.
+    #[allow(clippy::too_many_arguments)]
     pub async fn update_profile(
         db: &Database,
         user_id: Uuid,
@@ -37,17 +47,21 @@ impl SettingsService {
         bio: Option<String>,
         phone: Option<String>,
         email: Option<String>,
+        expected_version: Option<i32>,
     ) -> AppResult<ProfileResponse> {
-        let user: User = sqlx::query_as(
+        let hash = phone.as_deref().and_then(phone_hash);
+        let user: Option<User> = sqlx::query_as(
             r#"
             UPDATE users SET
                 name = COALESCE($2, name),
                 username = COALESCE($3, username),
                 bio = COALESCE($4, bio),
                 phone = COALESCE($5, phone),
+                phone_hash = CASE WHEN $5::text IS NULL THEN phone_hash ELSE $8 END,
                 email = COALESCE($6, email),
+                profile_version = profile_version + 1,
                 updated_at = NOW()
-            WHERE id = $1
+            WHERE id = $1 AND ($7::integer IS NULL OR profile_version = $7)
             RETURNING *
             "#,
         )
@@ -57,9 +71,16 @@ impl SettingsService {
         .bind(bio)
         .bind(phone)
         .bind(email)
-        .fetch_one(&db.pool)
+        .bind(expected_version)
+        .bind(hash)
+        .fetch_optional(&db.pool)
         .await?;
 
+        let Some(user) = user else {
+            let current = Self::get_profile(db, user_id).await?;
+            return Err(version_conflict(&current));
+        };
+
         Ok(ProfileResponse {
             id: user.id,
             name: user.name,
@@ -68,6 +89,7 @@ impl SettingsService {
             phone: user.phone,
             email: user.email,
             avatar: user.avatar,
+            version: user.profile_version,
         })
     }
 
@@ -83,9 +105,14 @@ impl SettingsService {
             forwards: settings.forwards_enabled,
             read_receipts: settings.read_receipts_enabled,
             two_factor_auth: settings.two_factor_enabled,
+            link_previews: settings.link_previews_enabled,
+            version: settings.privacy_version,
         })
     }
 
+    /// Update privacy settings that are `Some`, with the same version check
+    /// as `update_profile`
+    #[allow(clippy::too_many_arguments)]
     pub async fn update_privacy(
         db: &Database,
         user_id: Uuid,
@@ -96,8 +123,13 @@ impl SettingsService {
         forwards: Option<bool>,
         read_receipts: Option<bool>,
         two_factor_auth: Option<bool>,
+        link_previews: Option<bool>,
+        expected_version: Option<i32>,
     ) -> AppResult<PrivacySettings> {
-        let settings: UserSettings = sqlx::query_as(
+        // Make sure the row exists so a missed update can only mean a conflict
+        Self::get_or_create_settings(db, user_id).await?;
+
+        let settings: Option<UserSettings> = sqlx::query_as(
             r#"
             UPDATE user_settings SET
                 last_seen_visibility = COALESCE($2, last_seen_visibility),
@@ -107,8 +139,10 @@ impl SettingsService {
                 forwards_enabled = COALESCE($6, forwards_enabled),
                 read_receipts_enabled = COALESCE($7, read_receipts_enabled),
                 two_factor_enabled = COALESCE($8, two_factor_enabled),
+                link_previews_enabled = COALESCE($9, link_previews_enabled),
+                privacy_version = privacy_version + 1,
                 updated_at = NOW()
-            WHERE user_id = $1
+            WHERE user_id = $1 AND ($10::integer IS NULL OR privacy_version = $10)
             RETURNING *
             "#,
         )
@@ -120,9 +154,16 @@ impl SettingsService {
         .bind(forwards)
         .bind(read_receipts)
         .bind(two_factor_auth)
-        .fetch_one(&db.pool)
+        .bind(link_previews)
+        .bind(expected_version)
+        .fetch_optional(&db.pool)
         .await?;
 
+        let Some(settings) = settings else {
+            let current = Self::get_privacy(db, user_id).await?;
+            return Err(version_conflict(&current));
+        };
+
         Ok(PrivacySettings {
             last_seen: settings.last_seen_visibility,
             profile_photo: settings.profile_photo_visibility,
@@ -131,6 +172,8 @@ impl SettingsService {
             forwards: settings.forwards_enabled,
             read_receipts: settings.read_receipts_enabled,
             two_factor_auth: settings.two_factor_enabled,
+            link_previews: settings.link_previews_enabled,
+            version: settings.privacy_version,
         })
     }
 
@@ -146,9 +189,11 @@ impl SettingsService {
             in_app_vibrate: settings.in_app_vibrate,
             in_app_preview: settings.in_app_preview,
             contact_joined: settings.contact_joined_notify,
+            mentions_when_muted: settings.mention_notify_when_muted,
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn update_notifications(
         db: &Database,
         user_id: Uuid,
@@ -159,6 +204,7 @@ impl SettingsService {
         in_app_vibrate: Option<bool>,
         in_app_preview: Option<bool>,
         contact_joined: Option<bool>,
+        mentions_when_muted: Option<bool>,
     ) -> AppResult<NotificationSettings> {
         let settings: UserSettings = sqlx::query_as(
             r#"
@@ -170,6 +216,7 @@ impl SettingsService {
                 in_app_vibrate = COALESCE($6, in_app_vibrate),
                 in_app_preview = COALESCE($7, in_app_preview),
                 contact_joined_notify = COALESCE($8, contact_joined_notify),
+                mention_notify_when_muted = COALESCE($9, mention_notify_when_muted),
                 updated_at = NOW()
             WHERE user_id = $1
             RETURNING *
@@ -183,6 +230,7 @@ impl SettingsService {
         .bind(in_app_vibrate)
         .bind(in_app_preview)
         .bind(contact_joined)
+        .bind(mentions_when_muted)
         .fetch_one(&db.pool)
         .await?;
 
@@ -194,6 +242,7 @@ impl SettingsService {
             in_app_vibrate: settings.in_app_vibrate,
             in_app_preview: settings.in_app_preview,
             contact_joined: settings.contact_joined_notify,
+            mentions_when_muted: settings.mention_notify_when_muted,
         })
     }
 
@@ -349,9 +398,13 @@ impl SettingsService {
             chat_background: settings.chat_background,
             bubble_style: settings.bubble_style,
             animations_enabled: settings.animations_enabled,
+            timezone: settings.timezone,
         })
     }
 
+    /// Update appearance settings. An empty `timezone` clears it back to UTC;
+    /// anything else must be a name from the tz database.
+    #[allow(clippy::too_many_arguments)]
     pub async fn update_appearance(
         db: &Database,
         user_id: Uuid,
@@ -361,7 +414,14 @@ impl SettingsService {
         chat_background: Option<String>,
         bubble_style: Option<String>,
         animations_enabled: Option<bool>,
+        timezone: Option<String>,
     ) -> AppResult<AppearanceSettings> {
+        let timezone = match timezone.as_deref().map(str::trim) {
+            None => None,
+            Some("") => Some(None),
+            Some(name) => Some(Some(parse_timezone(name)?.name().to_string())),
+        };
+
         let settings: UserSettings = sqlx::query_as(
             r#"
             UPDATE user_settings SET
@@ -371,6 +431,7 @@ impl SettingsService {
                 chat_background = COALESCE($5, chat_background),
                 bubble_style = COALESCE($6, bubble_style),
                 animations_enabled = COALESCE($7, animations_enabled),
+                timezone = CASE WHEN $8 THEN $9 ELSE timezone END,
                 updated_at = NOW()
             WHERE user_id = $1
             RETURNING *
@@ -383,6 +444,8 @@ impl SettingsService {
         .bind(chat_background)
         .bind(bubble_style)
         .bind(animations_enabled)
+        .bind(timezone.is_some())
+        .bind(timezone.flatten())
         .fetch_one(&db.pool)
         .await?;
 
@@ -393,6 +456,7 @@ impl SettingsService {
             chat_background: settings.chat_background,
             bubble_style: settings.bubble_style,
             animations_enabled: settings.animations_enabled,
+            timezone: settings.timezone,
         })
     }
 
@@ -433,11 +497,127 @@ impl SettingsService {
                     location,
                     last_active: s.last_active,
                     is_current,
+                    session_type: "web".to_string(),
                 }
             })
             .collect())
     }
 
+    /// Live QUIC sessions of the user, listed alongside web sessions
+    pub async fn get_realtime_devices(
+        connection_manager: &ConnectionManager,
+        user_id: Uuid,
+    ) -> Vec<DeviceResponse> {
+        connection_manager
+            .get_user_sessions(user_id)
+            .await
+            .into_iter()
+            .filter(|session| session.transport == TransportType::Quic)
+            .map(|session| {
+                let info = session.info.unwrap_or_else(|| ConnectionInfo {
+                    remote_ip: std::net::Ipv4Addr::UNSPECIFIED.into(),
+                    location: UNKNOWN_LOCATION.to_string(),
+                    device_name: None,
+                    device_type: None,
+                });
+                let device_type = info.device_type.unwrap_or_else(|| "Unknown".to_string());
+                let name = info
+                    .device_name
+                    .unwrap_or_else(|| format!("{} Device", device_type));
+
+                DeviceResponse {
+                    id: session.connection_id.as_uuid(),
+                    name,
+                    device_type,
+                    location: info.location,
+                    last_active: session.last_activity,
+                    is_current: false,
+                    session_type: "quic".to_string(),
+                }
+            })
+            .collect()
+    }
+
+    /// Terminate a live QUIC session by closing its connection
+    ///
+    /// Returns `false` if `device_id` is not one of the user's QUIC sessions.
+    pub async fn terminate_realtime_device(
+        connection_manager: &ConnectionManager,
+        user_id: Uuid,
+        device_id: Uuid,
+    ) -> bool {
+        connection_manager
+            .close_user_connection(user_id, ConnectionId::from_uuid(device_id))
+            .await
+    }
+
+    /// The user's live QUIC and WebSocket connections, most recently active
+    /// first; `current` marks the connection the caller is using
+    pub async fn get_realtime_connections(
+        connection_manager: &ConnectionManager,
+        ws_manager: &WsManager,
+        user_id: Uuid,
+        current: Option<Uuid>,
+    ) -> Vec<RealtimeConnectionResponse> {
+        let quic = connection_manager
+            .get_user_sessions(user_id)
+            .await
+            .into_iter()
+            .filter(|session| session.transport == TransportType::Quic)
+            .map(|session| {
+                let id = session.connection_id.as_uuid();
+                let info = session.info;
+                RealtimeConnectionResponse {
+                    id,
+                    transport: "quic".to_string(),
+                    connected_at: session.connected_at,
+                    last_activity: session.last_activity,
+                    location: info.as_ref().map(|info| info.location.clone()),
+                    device_name: info.as_ref().and_then(|info| info.device_name.clone()),
+                    device_type: info.and_then(|info| info.device_type),
+                    is_current: current == Some(id),
+                }
+            });
+        let websocket = ws_manager
+            .user_clients(user_id)
+            .await
+            .into_iter()
+            .map(|client| RealtimeConnectionResponse {
+                id: client.connection_id,
+                transport: "websocket".to_string(),
+                connected_at: client.connected_at,
+                last_activity: client.last_activity(),
+                location: None,
+                device_name: None,
+                device_type: None,
+                is_current: current == Some(client.connection_id),
+            });
+
+        let mut connections: Vec<_> = quic.chain(websocket).collect();
+        connections.sort_by_key(|connection| std::cmp::Reverse(connection.last_activity));
+        connections
+    }
+
+    /// Close one of the user's live QUIC or WebSocket connections
+    ///
+    /// Connections of other users are reported as not found.
+    pub async fn terminate_realtime_connection(
+        connection_manager: &ConnectionManager,
+        ws_manager: &WsManager,
+        user_id: Uuid,
+        connection_id: Uuid,
+    ) -> AppResult<()> {
+        if Self::terminate_realtime_device(connection_manager, user_id, connection_id).await
+            || ws_manager
+                .disconnect_client(user_id, connection_id, "session terminated")
+                .await
+        {
+            Ok(())
+        } else {
+            Err(AppError::NotFound("Connection not found".to_string()))
+        }
+    }
+
     pub async fn terminate_device(
         db: &Database,
         user_id: Uuid,
@@ -465,18 +645,92 @@ impl SettingsService {
         Ok(())
     }
 
+    /// Delete every session of the user but the current one, returning the
+    /// ids of the sessions removed
     pub async fn terminate_all_other_devices(
         db: &Database,
         user_id: Uuid,
         current_token: &str,
-    ) -> AppResult<()> {
-        sqlx::query("DELETE FROM sessions WHERE user_id = $1 AND token != $2")
-            .bind(user_id)
-            .bind(current_token)
-            .execute(&db.pool)
-            .await?;
+    ) -> AppResult<Vec<Uuid>> {
+        let terminated: Vec<Uuid> = sqlx::query_scalar(
+            "DELETE FROM sessions WHERE user_id = $1 AND token != $2 RETURNING id",
+        )
+        .bind(user_id)
+        .bind(current_token)
+        .fetch_all(&db.pool)
+        .await?;
 
-        Ok(())
+        Ok(terminated)
+    }
+
+    /// Close the realtime connections opened with a terminated session
+    ///
+    /// New connections are refused once the session is gone; this closes the
+    /// ones already open. Returns how many were closed.
+    pub async fn disconnect_session(
+        connection_manager: &ConnectionManager,
+        ws_manager: &WsManager,
+        user_id: Uuid,
+        session_id: Uuid,
+    ) -> usize {
+        let quic = connection_manager
+            .close_session_connections(user_id, session_id)
+            .await;
+        let websocket = ws_manager
+            .disconnect_session(user_id, session_id, "Session terminated")
+            .await;
+        quic + websocket
+    }
+
+    /// Effective push notification toggles of one of the user's devices
+    pub async fn get_device_notifications(
+        db: &Database,
+        user_id: Uuid,
+        device_id: Uuid,
+    ) -> AppResult<DeviceNotificationSettings> {
+        let session: Session = sqlx::query_as(
+            "SELECT * FROM sessions WHERE id = $1 AND user_id = $2 AND expires_at > NOW()",
+        )
+        .bind(device_id)
+        .bind(user_id)
+        .fetch_optional(&db.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Device not found".to_string()))?;
+
+        let settings = Self::get_or_create_settings(db, user_id).await?;
+        Ok(device_notifications(&session, &settings))
+    }
+
+    /// Override push notification toggles for one of the user's devices
+    ///
+    /// Toggles left unset keep following the user's global settings.
+    pub async fn update_device_notifications(
+        db: &Database,
+        user_id: Uuid,
+        device_id: Uuid,
+        prefs: DeviceNotificationPrefs,
+    ) -> AppResult<DeviceNotificationSettings> {
+        let session: Session = sqlx::query_as(
+            r#"
+            UPDATE sessions SET
+                message_notifications = COALESCE($3, message_notifications),
+                group_notifications = COALESCE($4, group_notifications),
+                channel_notifications = COALESCE($5, channel_notifications)
+            WHERE id = $1 AND user_id = $2 AND expires_at > NOW()
+            RETURNING *
+            "#,
+        )
+        .bind(device_id)
+        .bind(user_id)
+        .bind(prefs.message_notifications)
+        .bind(prefs.group_notifications)
+        .bind(prefs.channel_notifications)
+        .fetch_optional(&db.pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Device not found".to_string()))?;
+
+        let settings = Self::get_or_create_settings(db, user_id).await?;
+        Ok(device_notifications(&session, &settings))
     }
 
     async fn get_or_create_settings(db: &Database, user_id: Uuid) -> AppResult<UserSettings> {
@@ -547,6 +801,27 @@ impl SettingsService {
     }
 }
 
+/// A device's toggles, falling back to the user's global ones
+fn device_notifications(session: &Session, settings: &UserSettings) -> DeviceNotificationSettings {
+    DeviceNotificationSettings {
+        device_id: session.id,
+        message_notifications: session
+            .message_notifications
+            .unwrap_or(settings.message_notifications),
+        group_notifications: session
+            .group_notifications
+            .unwrap_or(settings.group_notifications),
+        channel_notifications: session
+            .channel_notifications
+            .unwrap_or(settings.channel_notifications),
+    }
+}
+
+/// Conflict error carrying the current state for the client to merge
+fn version_conflict<T: serde::Serialize>(current: &T) -> AppError {
+    AppError::VersionConflict(serde_json::to_value(current).unwrap_or_default())
+}
+
 #[cfg(test)]
 #[path = "settings_integration_tests.rs"]
 mod settings_integration_tests;
@@ -3,7 +3,7 @@ mod integration_tests {
     use super::super::SettingsService;
     use crate::{
         db::Database,
-        models::{User, Session},
+        models::{DeviceNotificationPrefs, User, Session},
     };
     use uuid::Uuid;
     use sqlx::PgPool;
@@ -99,6 +99,7 @@ mod integration_tests {
             Some("Updated bio".to_string()),
             Some("+1234567890".to_string()),
             Some("updated@example.com".to_string()),
+            None,
         )
         .await
         .expect("Failed to update profile");
@@ -136,6 +137,8 @@ mod integration_tests {
             Some(false),
             Some(false),
             Some(true),
+            None,
+            None,
         )
         .await
         .expect("Failed to update privacy settings");
@@ -175,6 +178,7 @@ mod integration_tests {
             Some(false),
             Some(true),
             Some(true),
+            None,
         )
         .await
         .expect("Failed to update notification settings");
@@ -285,6 +289,7 @@ mod integration_tests {
             Some("custom".to_string()),
             Some("square".to_string()),
             Some(false),
+            Some("Europe/Berlin".to_string()),
         )
         .await
         .expect("Failed to update appearance settings");
@@ -295,6 +300,7 @@ mod integration_tests {
         assert_eq!(updated_appearance.chat_background, "custom");
         assert_eq!(updated_appearance.bubble_style, "square");
         assert_eq!(updated_appearance.animations_enabled, false);
+        assert_eq!(updated_appearance.timezone.as_deref(), Some("Europe/Berlin"));
         
         // Verify persistence
         let fetched_appearance = SettingsService::get_appearance(&db, user.id)
@@ -303,6 +309,25 @@ mod integration_tests {
         
         assert_eq!(fetched_appearance.theme, "dark");
         assert_eq!(fetched_appearance.animations_enabled, false);
+        assert_eq!(fetched_appearance.timezone.as_deref(), Some("Europe/Berlin"));
+
+        // Unknown zones are rejected and leave the stored one alone
+        let rejected = SettingsService::update_appearance(
+            &db, user.id, None, None, None, None, None, None,
+            Some("Mars/Olympus_Mons".to_string()),
+        )
+        .await;
+        assert!(matches!(rejected, Err(crate::error::AppError::BadRequest(_))));
+
+        // An empty string resets to UTC
+        let cleared = SettingsService::update_appearance(
+            &db, user.id, None, None, None, None, None, None,
+            Some(String::new()),
+        )
+        .await
+        .expect("Failed to clear timezone");
+        assert_eq!(cleared.timezone, None);
+        assert_eq!(cleared.theme, "dark");
         
         cleanup_test_user(&db, user.id).await;
     }
@@ -469,6 +494,7 @@ mod integration_tests {
             None,
             None,
             None,
+            None,
         )
         .await;
         
@@ -498,6 +524,58 @@ mod integration_tests {
         cleanup_test_user(&db, user.id).await;
     }
 
+    #[tokio::test]
+    async fn test_device_notifications_override_global_settings() {
+        let db = setup_test_db().await;
+        let user = create_test_user(&db).await;
+        let other = create_test_user(&db).await;
+        let phone = create_test_session(&db, user.id).await;
+        let desktop = create_test_session(&db, user.id).await;
+
+        // New devices follow the user's global settings
+        SettingsService::get_notifications(&db, user.id)
+            .await
+            .expect("Failed to create settings");
+        SettingsService::update_notifications(
+            &db, user.id, None, Some(false), None, None, None, None, None, None,
+        )
+        .await
+        .expect("Failed to update notification settings");
+        let inherited = SettingsService::get_device_notifications(&db, user.id, desktop.id)
+            .await
+            .expect("Failed to fetch device notifications");
+        assert!(inherited.message_notifications);
+        assert!(!inherited.group_notifications);
+
+        // Muting the desktop leaves the phone alone
+        let prefs = DeviceNotificationPrefs {
+            message_notifications: Some(false),
+            group_notifications: Some(true),
+            ..Default::default()
+        };
+        let updated = SettingsService::update_device_notifications(&db, user.id, desktop.id, prefs)
+            .await
+            .expect("Failed to update device notifications");
+        assert_eq!(updated.device_id, desktop.id);
+        assert!(!updated.message_notifications);
+        assert!(updated.group_notifications);
+        assert!(updated.channel_notifications);
+
+        let phone_prefs = SettingsService::get_device_notifications(&db, user.id, phone.id)
+            .await
+            .expect("Failed to fetch device notifications");
+        assert!(phone_prefs.message_notifications);
+        assert!(!phone_prefs.group_notifications);
+
+        // Another user's device is not found
+        let result =
+            SettingsService::update_device_notifications(&db, other.id, desktop.id, prefs).await;
+        assert!(result.is_err());
+
+        cleanup_test_user(&db, user.id).await;
+        cleanup_test_user(&db, other.id).await;
+    }
+
     /// Task 9.5: Test settings persistence
     /// Requirements: 12.1, 12.2, 12.3, 12.4, 12.5
     
@@ -515,6 +593,7 @@ mod integration_tests {
             None,
             None,
             None,
+            None,
         )
         .await
         .expect("Failed to update profile");
@@ -529,6 +608,8 @@ mod integration_tests {
             None,
             None,
             None,
+            None,
+            None,
         )
         .await
         .expect("Failed to update privacy");
@@ -562,6 +643,7 @@ mod integration_tests {
             Some("Initial bio".to_string()),
             None,
             None,
+            None,
         )
         .await
         .expect("Failed to set initial profile");
@@ -575,6 +657,7 @@ mod integration_tests {
             None,
             None,
             None,
+            None,
         )
         .await
         .expect("Failed to update profile");
@@ -625,4 +708,106 @@ mod integration_tests {
         
         cleanup_test_user(&db, user.id).await;
     }
+
+    #[tokio::test]
+    async fn test_concurrent_profile_updates_reject_stale_version() {
+        let db = setup_test_db().await;
+        let user = create_test_user(&db).await;
+
+        // Both devices load the profile at the same version
+        let loaded = SettingsService::get_profile(&db, user.id)
+            .await
+            .expect("Failed to fetch profile");
+
+        let first = SettingsService::update_profile(
+            &db,
+            user.id,
+            Some("Phone Name".to_string()),
+            None,
+            None,
+            None,
+            None,
+            Some(loaded.version),
+        )
+        .await
+        .expect("First update should apply");
+        assert_eq!(first.version, loaded.version + 1);
+
+        let stale = SettingsService::update_profile(
+            &db,
+            user.id,
+            Some("Laptop Name".to_string()),
+            None,
+            None,
+            None,
+            None,
+            Some(loaded.version),
+        )
+        .await;
+
+        match stale {
+            Err(crate::error::AppError::VersionConflict(current)) => {
+                assert_eq!(current["name"], "Phone Name");
+                assert_eq!(current["version"], first.version);
+            }
+            other => panic!("expected version conflict, got {:?}", other.map(|p| p.name)),
+        }
+
+        let fetched = SettingsService::get_profile(&db, user.id)
+            .await
+            .expect("Failed to fetch profile");
+        assert_eq!(fetched.name, "Phone Name");
+
+        cleanup_test_user(&db, user.id).await;
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_privacy_updates_reject_stale_version() {
+        let db = setup_test_db().await;
+        let user = create_test_user(&db).await;
+
+        let loaded = SettingsService::get_privacy(&db, user.id)
+            .await
+            .expect("Failed to fetch privacy settings");
+
+        SettingsService::update_privacy(
+            &db,
+            user.id,
+            Some("nobody".to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(loaded.version),
+        )
+        .await
+        .expect("First update should apply");
+
+        let stale = SettingsService::update_privacy(
+            &db,
+            user.id,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(false),
+            None,
+            None,
+            Some(loaded.version),
+        )
+        .await;
+        assert!(matches!(stale, Err(crate::error::AppError::VersionConflict(_))));
+
+        let fetched = SettingsService::get_privacy(&db, user.id)
+            .await
+            .expect("Failed to fetch privacy settings");
+        assert_eq!(fetched.last_seen, "nobody");
+        assert!(fetched.read_receipts);
+
+        cleanup_test_user(&db, user.id).await;
+    }
 }
@@ -0,0 +1,165 @@
+/// Slow Mode Service
+///
+/// Throttles how often non-admin participants can post in a chat.
+/// The interval is configured per chat (`chats.slow_mode_seconds`); each
+/// user's last send is tracked in Redis under `slowmode:{chat_id}:{user_id}`,
+/// falling back to the user's latest message in the chat without Redis.
+
+use crate::{db::Database, error::{AppError, AppResult}};
+use chrono::{DateTime, Utc};
+use redis::aio::ConnectionManager;
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct SlowModeLimiter {
+    redis: Option<ConnectionManager>,
+}
+
+impl SlowModeLimiter {
+    pub fn new(redis: Option<ConnectionManager>) -> Self {
+        Self { redis }
+    }
+
+    /// Check whether `user_id` may post in `chat_id` and, if so, start their
+    /// next slow-mode window.
+    ///
+    /// Returns `AppError::SlowModeActive(retry_after)` when the user has to wait.
+    /// Chat admins and the chat creator are never throttled.
+    pub async fn check_and_mark(
+        &self,
+        db: &Database,
+        chat_id: Uuid,
+        user_id: Uuid,
+    ) -> AppResult<()> {
+        let row: Option<(i32, Option<Uuid>, String)> = sqlx::query_as(
+            r#"
+            SELECT c.slow_mode_seconds, c.created_by, cp.role
+            FROM chats c
+            JOIN chat_participants cp ON cp.chat_id = c.id AND cp.user_id = $2
+            WHERE c.id = $1
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .fetch_optional(&db.pool)
+        .await?;
+
+        let Some((seconds, created_by, role)) = row else {
+            return Ok(());
+        };
+
+        if seconds <= 0 || role == "admin" || created_by == Some(user_id) {
+            return Ok(());
+        }
+
+        let retry_after = match &self.redis {
+            Some(redis) => Self::mark_redis(redis, chat_id, user_id, seconds).await?,
+            None => Self::check_db(db, chat_id, user_id, seconds).await?,
+        };
+
+        match retry_after {
+            Some(retry_after) => Err(AppError::SlowModeActive(retry_after)),
+            None => Ok(()),
+        }
+    }
+
+    /// Atomically claim the slow-mode window; returns the remaining wait if
+    /// the window is already taken.
+    async fn mark_redis(
+        redis: &ConnectionManager,
+        chat_id: Uuid,
+        user_id: Uuid,
+        seconds: i32,
+    ) -> AppResult<Option<u32>> {
+        let mut conn = redis.clone();
+        let key = slow_mode_key(chat_id, user_id);
+
+        let claimed: Option<String> = redis::cmd("SET")
+            .arg(&key)
+            .arg(Utc::now().timestamp())
+            .arg("NX")
+            .arg("EX")
+            .arg(seconds)
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error)?;
+
+        if claimed.is_some() {
+            return Ok(None);
+        }
+
+        let ttl: i64 = redis::cmd("TTL")
+            .arg(&key)
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error)?;
+
+        // The key may have expired between SET and TTL; report at least 1s
+        Ok(Some(ttl.max(1) as u32))
+    }
+
+    /// Fallback when Redis is unavailable: compare against the user's latest message
+    async fn check_db(
+        db: &Database,
+        chat_id: Uuid,
+        user_id: Uuid,
+        seconds: i32,
+    ) -> AppResult<Option<u32>> {
+        let last_sent: Option<DateTime<Utc>> = sqlx::query_scalar(
+            r#"
+            SELECT MAX(created_at) FROM messages
+            WHERE chat_id = $1 AND sender_id = $2 AND sender_type = 'user'
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .fetch_one(&db.pool)
+        .await?;
+
+        Ok(last_sent.and_then(|sent| retry_after(sent, Utc::now(), seconds)))
+    }
+}
+
+/// Seconds left in the slow-mode window started at `last_sent`, if any
+fn retry_after(last_sent: DateTime<Utc>, now: DateTime<Utc>, seconds: i32) -> Option<u32> {
+    let elapsed = (now - last_sent).num_seconds();
+    let remaining = seconds as i64 - elapsed;
+    (remaining > 0).then_some(remaining as u32)
+}
+
+fn slow_mode_key(chat_id: Uuid, user_id: Uuid) -> String {
+    format!("slowmode:{}:{}", chat_id, user_id)
+}
+
+fn redis_error(e: redis::RedisError) -> AppError {
+    AppError::Internal(anyhow::anyhow!("Redis error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_slow_mode_key_format() {
+        let chat_id = Uuid::nil();
+        let user_id = Uuid::from_u128(1);
+        assert_eq!(
+            slow_mode_key(chat_id, user_id),
+            format!("slowmode:{}:{}", chat_id, user_id)
+        );
+    }
+
+    #[test]
+    fn test_retry_after_within_window() {
+        let now = Utc::now();
+        assert_eq!(retry_after(now - Duration::seconds(10), now, 30), Some(20));
+    }
+
+    #[test]
+    fn test_retry_after_window_elapsed() {
+        let now = Utc::now();
+        assert_eq!(retry_after(now - Duration::seconds(30), now, 30), None);
+        assert_eq!(retry_after(now - Duration::seconds(90), now, 30), None);
+    }
+}
@@ -0,0 +1,83 @@
+/// Timezone-aware rendering of timestamps in server-generated text
+///
+/// Users pick an IANA timezone in their appearance settings; BotFather and
+/// built-in bot replies render dates in it. Users without one get UTC.
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use uuid::Uuid;
+
+use crate::{
+    db::Database,
+    error::{AppError, AppResult},
+};
+
+/// Format used for dates in server-rendered text
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M %Z";
+
+/// Check a timezone name against the tz database
+pub fn parse_timezone(name: &str) -> AppResult<Tz> {
+    name.trim()
+        .parse::<Tz>()
+        .map_err(|_| AppError::BadRequest(format!("Unknown timezone: {}", name.trim())))
+}
+
+/// Render `dt` in the user's timezone, or UTC when unset or unknown
+pub fn format_timestamp(dt: DateTime<Utc>, user_tz: Option<&str>) -> String {
+    match user_tz.and_then(|name| parse_timezone(name).ok()) {
+        Some(tz) => dt.with_timezone(&tz).format(TIMESTAMP_FORMAT).to_string(),
+        None => dt.format(TIMESTAMP_FORMAT).to_string(),
+    }
+}
+
+/// The user's timezone preference, if they set one
+pub async fn user_timezone(db: &Database, user_id: Uuid) -> AppResult<Option<String>> {
+    let timezone: Option<Option<String>> =
+        sqlx::query_scalar("SELECT timezone FROM user_settings WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_optional(&db.pool)
+            .await?;
+
+    Ok(timezone.flatten())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_format_timestamp_defaults_to_utc() {
+        let dt = Utc.with_ymd_and_hms(2026, 1, 15, 9, 30, 0).unwrap();
+        assert_eq!(format_timestamp(dt, None), "2026-01-15 09:30 UTC");
+        assert_eq!(
+            format_timestamp(dt, Some("Not/AZone")),
+            "2026-01-15 09:30 UTC"
+        );
+    }
+
+    #[test]
+    fn test_format_timestamp_in_user_timezone() {
+        let winter = Utc.with_ymd_and_hms(2026, 1, 15, 9, 30, 0).unwrap();
+        let summer = Utc.with_ymd_and_hms(2026, 7, 15, 9, 30, 0).unwrap();
+        assert_eq!(
+            format_timestamp(winter, Some("Europe/Berlin")),
+            "2026-01-15 10:30 CET"
+        );
+        assert_eq!(
+            format_timestamp(summer, Some("Europe/Berlin")),
+            "2026-07-15 11:30 CEST"
+        );
+        assert_eq!(
+            format_timestamp(winter, Some("Asia/Tokyo")),
+            "2026-01-15 18:30 JST"
+        );
+    }
+
+    #[test]
+    fn test_parse_timezone() {
+        assert!(parse_timezone("America/New_York").is_ok());
+        assert!(parse_timezone(" UTC ").is_ok());
+        assert!(parse_timezone("Mars/Olympus_Mons").is_err());
+        assert!(parse_timezone("").is_err());
+    }
+}
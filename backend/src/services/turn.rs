@@ -0,0 +1,140 @@
+/// TURN Credential Service
+///
+/// Issues time-limited TURN credentials following the "REST API for Access to
+/// TURN Services" scheme (draft-uberti-behave-turn-rest, as implemented by
+/// coturn's `use-auth-secret`):
+/// - username is `{expiry_unix_timestamp}:{user_id}`
+/// - password is `base64(HMAC-SHA1(shared_secret, username))`
+///
+/// The TURN server recomputes the password from the same secret and rejects
+/// usernames whose timestamp has passed, so nothing has to be stored here.
+
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha1::Sha1;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::error::{AppError, AppResult};
+
+type HmacSha1 = Hmac<Sha1>;
+
+/// TURN settings taken from `Config`
+#[derive(Debug, Clone)]
+pub struct TurnConfig {
+    pub urls: Vec<String>,
+    pub shared_secret: Option<String>,
+    pub ttl_seconds: u64,
+}
+
+impl From<&Config> for TurnConfig {
+    fn from(config: &Config) -> Self {
+        Self {
+            urls: config.turn_urls.clone(),
+            shared_secret: config.turn_shared_secret.clone(),
+            ttl_seconds: config.turn_credential_ttl_seconds,
+        }
+    }
+}
+
+/// ICE server entry handed to a call participant
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TurnCredentials {
+    pub urls: Vec<String>,
+    pub username: String,
+    pub credential: String,
+    pub ttl: u64,
+    pub expires_at: DateTime<Utc>,
+}
+
+pub struct TurnService;
+
+impl TurnService {
+    /// Issue credentials for `user_id`, valid for the configured TTL from `now`
+    pub fn issue_credentials(
+        config: &TurnConfig,
+        user_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> AppResult<TurnCredentials> {
+        let secret = match config.shared_secret.as_deref() {
+            Some(secret) if !config.urls.is_empty() => secret,
+            _ => {
+                return Err(AppError::NotFound(
+                    "TURN server is not configured".to_string(),
+                ))
+            }
+        };
+
+        let expires_at = now + Duration::seconds(config.ttl_seconds as i64);
+        let username = format!("{}:{}", expires_at.timestamp(), user_id);
+
+        Ok(TurnCredentials {
+            urls: config.urls.clone(),
+            credential: sign(secret, &username),
+            username,
+            ttl: config.ttl_seconds,
+            expires_at,
+        })
+    }
+}
+
+/// `base64(HMAC-SHA1(secret, username))`
+fn sign(secret: &str, username: &str) -> String {
+    let mut mac =
+        HmacSha1::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(username.as_bytes());
+    base64::engine::general_purpose::STANDARD.encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn config(secret: Option<&str>) -> TurnConfig {
+        TurnConfig {
+            urls: vec!["turn:turn.example.com:3478".to_string()],
+            shared_secret: secret.map(String::from),
+            ttl_seconds: 3600,
+        }
+    }
+
+    #[test]
+    fn test_issue_credentials() {
+        let user_id = Uuid::parse_str("6f1c2a5e-2d4b-4b8e-9a51-0c3f7f1d2e10").unwrap();
+        let now = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+
+        let creds = TurnService::issue_credentials(&config(Some("s3cret")), user_id, now).unwrap();
+        assert_eq!(creds.username, format!("1700003600:{}", user_id));
+        assert_eq!(creds.expires_at.timestamp(), 1_700_003_600);
+        assert_eq!(creds.ttl, 3600);
+        assert_eq!(creds.credential, sign("s3cret", &creds.username));
+        // 20-byte SHA-1 digest in padded base64
+        assert_eq!(creds.credential.len(), 28);
+
+        let other = TurnService::issue_credentials(&config(Some("other")), user_id, now).unwrap();
+        assert_ne!(creds.credential, other.credential);
+    }
+
+    #[test]
+    fn test_known_hmac() {
+        // RFC 2202 test case 2
+        assert_eq!(
+            sign("Jefe", "what do ya want for nothing?"),
+            "7/zfauXrL6LSdBbV8YTfnCWafHk="
+        );
+    }
+
+    #[test]
+    fn test_not_configured() {
+        let now = Utc::now();
+        assert!(TurnService::issue_credentials(&config(None), Uuid::new_v4(), now).is_err());
+
+        let mut no_urls = config(Some("s3cret"));
+        no_urls.urls.clear();
+        assert!(TurnService::issue_credentials(&no_urls, Uuid::new_v4(), now).is_err());
+    }
+}
@@ -0,0 +1,253 @@
+/// Unfurl Service
+///
+/// Previews a link while a message is being composed, before it is sent:
+/// - Pages are fetched with the link preview fetcher, so the same SSRF
+///   checks, `FETCH_TIMEOUT` and `MAX_BODY_BYTES` limits apply
+/// - Previews are cached in Redis under `unfurl:{sha256 of the URL}` for
+///   `UNFURL_CACHE_TTL_SECONDS`; failed fetches are not cached
+/// - Each user may unfurl `UNFURLS_PER_MINUTE` links, cache hits included,
+///   counted under `unfurl:rate:{user_id}:{minute}`
+///
+/// Without Redis nothing is cached and unfurls are not limited. Admins
+/// (`ADMIN_USER_IDS`) are exempt from the limit.
+use crate::{
+    config::Config,
+    error::{AppError, AppResult},
+    models::LinkPreviewResponse,
+    services::LinkPreviewService,
+};
+use chrono::{DateTime, Utc};
+use redis::aio::ConnectionManager;
+use sha2::{Digest, Sha256};
+use url::Url;
+use uuid::Uuid;
+
+/// Longest URL accepted, in bytes
+pub const MAX_UNFURL_URL_BYTES: usize = 2048;
+
+/// Unfurl limits taken from `Config`; 0 unfurls per minute disables the limit
+#[derive(Debug, Clone)]
+pub struct UnfurlConfig {
+    pub unfurls_per_minute: u32,
+    pub cache_ttl_seconds: u64,
+    pub exempt_user_ids: Vec<Uuid>,
+}
+
+impl From<&Config> for UnfurlConfig {
+    fn from(config: &Config) -> Self {
+        Self {
+            unfurls_per_minute: config.unfurls_per_minute,
+            cache_ttl_seconds: config.unfurl_cache_ttl_seconds,
+            exempt_user_ids: config.admin_user_ids.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct UnfurlService {
+    redis: Option<ConnectionManager>,
+    config: UnfurlConfig,
+}
+
+impl UnfurlService {
+    pub fn new(redis: Option<ConnectionManager>, config: UnfurlConfig) -> Self {
+        Self { redis, config }
+    }
+
+    /// Preview `url` for `user_id`
+    ///
+    /// Returns the preview and whether it came from the cache. Links that
+    /// cannot be fetched or have no title or description are a bad request;
+    /// the reason is only logged so responses don't reveal how internal
+    /// hosts resolve.
+    pub async fn unfurl(&self, user_id: Uuid, url: &str) -> AppResult<(LinkPreviewResponse, bool)> {
+        let url = url.trim();
+        if url.is_empty() || url.len() > MAX_UNFURL_URL_BYTES {
+            return Err(AppError::BadRequest(format!(
+                "url must be 1-{} bytes",
+                MAX_UNFURL_URL_BYTES
+            )));
+        }
+        let url = Url::parse(url)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .ok_or_else(|| AppError::BadRequest("url must be an http(s) URL".to_string()))?;
+
+        self.check_rate(user_id).await?;
+
+        let key = cache_key(&url);
+        if let Some(preview) = self.cached(&key).await {
+            return Ok((preview, true));
+        }
+
+        let (final_url, metadata) = LinkPreviewService::fetch_metadata(url.as_str())
+            .await
+            .map_err(|e| {
+                tracing::debug!("Failed to unfurl {}: {:#}", url, e);
+                AppError::BadRequest("Could not fetch a preview for this link".to_string())
+            })?;
+        if metadata.title.is_none() && metadata.description.is_none() {
+            return Err(AppError::BadRequest("This link has no preview".to_string()));
+        }
+
+        let preview = LinkPreviewResponse {
+            url: final_url.to_string(),
+            title: metadata.title,
+            description: metadata.description,
+            image_url: metadata.image_url,
+            site_name: metadata.site_name,
+        };
+        self.store(&key, &preview).await;
+
+        Ok((preview, false))
+    }
+
+    /// Count an unfurl against the per-minute limit
+    ///
+    /// Returns `AppError::UnfurlRateLimited(retry_after)` when unfurling too often.
+    async fn check_rate(&self, user_id: Uuid) -> AppResult<()> {
+        if self.config.unfurls_per_minute == 0
+            || self.config.exempt_user_ids.contains(&user_id)
+        {
+            return Ok(());
+        }
+        let Some(redis) = &self.redis else {
+            return Ok(());
+        };
+        let mut conn = redis.clone();
+        let now = Utc::now();
+
+        let key = rate_key(user_id, now);
+        let (count,): (u32,) = redis::pipe()
+            .atomic()
+            .cmd("INCR")
+            .arg(&key)
+            .cmd("EXPIRE")
+            .arg(&key)
+            .arg(60)
+            .ignore()
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error)?;
+
+        if count > self.config.unfurls_per_minute {
+            return Err(AppError::UnfurlRateLimited(seconds_to_next_minute(now)));
+        }
+        Ok(())
+    }
+
+    /// The cached preview under `key`; cache errors count as a miss
+    async fn cached(&self, key: &str) -> Option<LinkPreviewResponse> {
+        if self.config.cache_ttl_seconds == 0 {
+            return None;
+        }
+        let mut conn = self.redis.clone()?;
+
+        let cached: Option<String> = match redis::cmd("GET").arg(key).query_async(&mut conn).await {
+            Ok(cached) => cached,
+            Err(e) => {
+                tracing::warn!("Failed to read unfurl cache: {}", e);
+                return None;
+            }
+        };
+        cached.and_then(|json| serde_json::from_str(&json).ok())
+    }
+
+    async fn store(&self, key: &str, preview: &LinkPreviewResponse) {
+        if self.config.cache_ttl_seconds == 0 {
+            return;
+        }
+        let Some(mut conn) = self.redis.clone() else {
+            return;
+        };
+        let Ok(json) = serde_json::to_string(preview) else {
+            return;
+        };
+
+        let result: redis::RedisResult<()> = redis::cmd("SET")
+            .arg(key)
+            .arg(json)
+            .arg("EX")
+            .arg(self.config.cache_ttl_seconds)
+            .query_async(&mut conn)
+            .await;
+        if let Err(e) = result {
+            tracing::warn!("Failed to cache unfurl: {}", e);
+        }
+    }
+}
+
+/// Cache key of a parsed URL, so equivalent spellings share an entry
+fn cache_key(url: &Url) -> String {
+    format!("unfurl:{:x}", Sha256::digest(url.as_str().as_bytes()))
+}
+
+fn rate_key(user_id: Uuid, now: DateTime<Utc>) -> String {
+    format!("unfurl:rate:{}:{}", user_id, now.timestamp().div_euclid(60))
+}
+
+fn seconds_to_next_minute(now: DateTime<Utc>) -> u32 {
+    (60 - now.timestamp().rem_euclid(60)) as u32
+}
+
+fn redis_error(e: redis::RedisError) -> AppError {
+    AppError::Internal(anyhow::anyhow!("Redis error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service() -> UnfurlService {
+        UnfurlService::new(
+            None,
+            UnfurlConfig {
+                unfurls_per_minute: 20,
+                cache_ttl_seconds: 3600,
+                exempt_user_ids: Vec::new(),
+            },
+        )
+    }
+
+    #[test]
+    fn test_cache_key_uses_the_parsed_url() {
+        let key = cache_key(&Url::parse("https://Example.com").unwrap());
+        assert_eq!(key, cache_key(&Url::parse("https://example.com/").unwrap()));
+        assert_ne!(key, cache_key(&Url::parse("https://example.com/other").unwrap()));
+        assert!(key.starts_with("unfurl:"));
+        assert_eq!(key.len(), "unfurl:".len() + 64);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_urls_are_rejected() {
+        let service = service();
+        let user = Uuid::new_v4();
+
+        for url in ["", "not a url", "ftp://example.com/file", "javascript:alert(1)"] {
+            assert!(matches!(
+                service.unfurl(user, url).await,
+                Err(AppError::BadRequest(_))
+            ));
+        }
+        let long = format!("https://example.com/{}", "a".repeat(MAX_UNFURL_URL_BYTES));
+        assert!(matches!(service.unfurl(user, &long).await, Err(AppError::BadRequest(_))));
+    }
+
+    #[tokio::test]
+    async fn test_internal_addresses_are_not_fetched() {
+        let service = service();
+        let user = Uuid::new_v4();
+
+        for url in [
+            "http://127.0.0.1/",
+            "http://10.0.0.1/",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://[::1]/",
+            "http://localhost/",
+            "http://example.com:8080/",
+        ] {
+            let result = service.unfurl(user, url).await;
+            assert!(matches!(result, Err(AppError::BadRequest(_))), "{} was fetched", url);
+        }
+    }
+}
@@ -0,0 +1,228 @@
+/// Upload Quota Service
+///
+/// Caps how often and how much each user uploads:
+/// - a per-minute upload count, under `upload:rate:{user_id}:{minute}`
+/// - a rolling 24-hour byte quota, kept as hourly buckets under
+///   `upload:bytes:{user_id}:{hour}` and summed over the last 24 hours
+///
+/// Counters live in Redis; without Redis uploads are not limited. Admins
+/// (`ADMIN_USER_IDS`) are exempt. Bytes are only counted once an upload has
+/// been stored, so failed uploads don't use up the quota.
+
+use crate::{config::Config, error::{AppError, AppResult}};
+use chrono::{DateTime, Utc};
+use redis::aio::ConnectionManager;
+use uuid::Uuid;
+
+/// Hourly buckets summed for the rolling daily quota
+const QUOTA_WINDOW_HOURS: i64 = 24;
+
+/// Upload limits taken from `Config`; 0 disables a limit
+#[derive(Debug, Clone)]
+pub struct UploadQuotaConfig {
+    pub max_uploads_per_minute: u32,
+    pub daily_quota_bytes: u64,
+    pub exempt_user_ids: Vec<Uuid>,
+}
+
+impl From<&Config> for UploadQuotaConfig {
+    fn from(config: &Config) -> Self {
+        Self {
+            max_uploads_per_minute: config.upload_max_per_minute,
+            daily_quota_bytes: config.upload_daily_quota_bytes,
+            exempt_user_ids: config.admin_user_ids.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct UploadQuota {
+    redis: Option<ConnectionManager>,
+    config: UploadQuotaConfig,
+}
+
+impl UploadQuota {
+    pub fn new(redis: Option<ConnectionManager>, config: UploadQuotaConfig) -> Self {
+        Self { redis, config }
+    }
+
+    /// Count an upload attempt against the per-minute limit
+    ///
+    /// Called before the body is read. Returns
+    /// `AppError::UploadRateLimitExceeded(retry_after)` when uploading too often.
+    pub async fn check_rate(&self, user_id: Uuid) -> AppResult<()> {
+        if self.config.max_uploads_per_minute == 0 {
+            return Ok(());
+        }
+        let Some(redis) = self.redis_for(user_id) else {
+            return Ok(());
+        };
+        let mut conn = redis.clone();
+        let now = Utc::now();
+
+        let key = rate_key(user_id, now);
+        let (count,): (u32,) = redis::pipe()
+            .atomic()
+            .cmd("INCR")
+            .arg(&key)
+            .cmd("EXPIRE")
+            .arg(&key)
+            .arg(60)
+            .ignore()
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error)?;
+
+        if count > self.config.max_uploads_per_minute {
+            return Err(AppError::UploadRateLimitExceeded(seconds_to_next_minute(now)));
+        }
+        Ok(())
+    }
+
+    /// Check that `size` bytes fit in the user's remaining daily quota
+    ///
+    /// Returns `AppError::FileTooLarge` when they don't, otherwise the bytes
+    /// remaining before this upload (`None` when the user is not limited).
+    pub async fn check_quota(&self, user_id: Uuid, size: u64) -> AppResult<Option<u64>> {
+        if self.config.daily_quota_bytes == 0 {
+            return Ok(None);
+        }
+        let Some(redis) = self.redis_for(user_id) else {
+            return Ok(None);
+        };
+        let mut conn = redis.clone();
+
+        let used = Self::used_bytes(&mut conn, user_id, Utc::now()).await?;
+        let remaining = self.config.daily_quota_bytes.saturating_sub(used);
+        if size > remaining {
+            return Err(AppError::FileTooLarge);
+        }
+        Ok(Some(remaining))
+    }
+
+    /// Count `size` stored bytes against the user's daily quota
+    ///
+    /// Call once per completed upload (for chunked uploads, on completion).
+    /// Returns the bytes remaining afterwards, `None` when not limited.
+    pub async fn record(&self, user_id: Uuid, size: u64) -> AppResult<Option<u64>> {
+        if self.config.daily_quota_bytes == 0 {
+            return Ok(None);
+        }
+        let Some(redis) = self.redis_for(user_id) else {
+            return Ok(None);
+        };
+        let mut conn = redis.clone();
+        let now = Utc::now();
+
+        let key = bytes_key(user_id, hour_index(now));
+        let _: () = redis::pipe()
+            .atomic()
+            .cmd("INCRBY")
+            .arg(&key)
+            .arg(size)
+            .ignore()
+            .cmd("EXPIRE")
+            .arg(&key)
+            .arg((QUOTA_WINDOW_HOURS + 1) * 3600)
+            .ignore()
+            .query_async(&mut conn)
+            .await
+            .map_err(redis_error)?;
+
+        let used = Self::used_bytes(&mut conn, user_id, now).await?;
+        Ok(Some(self.config.daily_quota_bytes.saturating_sub(used)))
+    }
+
+    /// Redis connection to count against, or `None` if `user_id` is not limited
+    fn redis_for(&self, user_id: Uuid) -> Option<&ConnectionManager> {
+        if self.config.exempt_user_ids.contains(&user_id) {
+            return None;
+        }
+        self.redis.as_ref()
+    }
+
+    /// Bytes uploaded over the last 24 hourly buckets
+    async fn used_bytes(
+        conn: &mut ConnectionManager,
+        user_id: Uuid,
+        now: DateTime<Utc>,
+    ) -> AppResult<u64> {
+        let buckets: Vec<Option<u64>> = redis::cmd("MGET")
+            .arg(window_keys(user_id, now))
+            .query_async(conn)
+            .await
+            .map_err(redis_error)?;
+        Ok(buckets.into_iter().flatten().sum())
+    }
+}
+
+fn rate_key(user_id: Uuid, now: DateTime<Utc>) -> String {
+    format!("upload:rate:{}:{}", user_id, now.timestamp() / 60)
+}
+
+fn bytes_key(user_id: Uuid, hour: i64) -> String {
+    format!("upload:bytes:{}:{}", user_id, hour)
+}
+
+fn hour_index(now: DateTime<Utc>) -> i64 {
+    now.timestamp() / 3600
+}
+
+/// Keys of the hourly buckets covering the rolling window ending at `now`
+fn window_keys(user_id: Uuid, now: DateTime<Utc>) -> Vec<String> {
+    let current = hour_index(now);
+    (current - QUOTA_WINDOW_HOURS + 1..=current)
+        .map(|hour| bytes_key(user_id, hour))
+        .collect()
+}
+
+/// Seconds until the per-minute counter resets (at least 1)
+fn seconds_to_next_minute(now: DateTime<Utc>) -> u32 {
+    (60 - now.timestamp().rem_euclid(60)) as u32
+}
+
+fn redis_error(e: redis::RedisError) -> AppError {
+    AppError::Internal(anyhow::anyhow!("Redis error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_window_keys_cover_last_24_hours() {
+        let user_id = Uuid::nil();
+        let now = Utc.timestamp_opt(100 * 3600 + 59, 0).unwrap();
+
+        let keys = window_keys(user_id, now);
+        assert_eq!(keys.len(), 24);
+        assert_eq!(keys.first().unwrap(), &format!("upload:bytes:{}:77", user_id));
+        assert_eq!(keys.last().unwrap(), &format!("upload:bytes:{}:100", user_id));
+    }
+
+    #[test]
+    fn test_seconds_to_next_minute() {
+        assert_eq!(seconds_to_next_minute(Utc.timestamp_opt(120, 0).unwrap()), 60);
+        assert_eq!(seconds_to_next_minute(Utc.timestamp_opt(179, 0).unwrap()), 1);
+    }
+
+    #[tokio::test]
+    async fn test_admins_and_missing_redis_are_not_limited() {
+        let admin = Uuid::new_v4();
+        let quota = UploadQuota::new(
+            None,
+            UploadQuotaConfig {
+                max_uploads_per_minute: 1,
+                daily_quota_bytes: 10,
+                exempt_user_ids: vec![admin],
+            },
+        );
+
+        quota.check_rate(admin).await.unwrap();
+        quota.check_rate(admin).await.unwrap();
+        assert_eq!(quota.check_quota(admin, 1_000).await.unwrap(), None);
+        assert_eq!(quota.check_quota(Uuid::new_v4(), 1_000).await.unwrap(), None);
+        assert_eq!(quota.record(admin, 1_000).await.unwrap(), None);
+    }
+}
@@ -1,10 +1,19 @@
 use crate::{
     db::Database,
     error::{AppError, AppResult},
-    models::{User, UserPublic},
+    models::{Contact, LastSeenApprox, PresenceState, User, UserPublic, VisibleLastSeen},
 };
+use chrono::{DateTime, Duration, Utc};
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
+/// Most usernames plus phone hashes accepted in one contact import
+pub const MAX_CONTACT_IMPORT: usize = 1000;
+
+/// `contact_imports.kind` values
+const IMPORT_KIND_USERNAME: &str = "username";
+const IMPORT_KIND_PHONE_HASH: &str = "phone_hash";
+
 pub struct UserService;
 
 impl UserService {
@@ -129,6 +138,7 @@ impl UserService {
         phone: Option<String>,
         avatar: Option<String>,
     ) -> AppResult<User> {
+        let hash = phone.as_deref().and_then(phone_hash);
         let user: User = sqlx::query_as(
             r#"
             UPDATE users SET
@@ -136,6 +146,7 @@ impl UserService {
                 username = COALESCE($3, username),
                 bio = COALESCE($4, bio),
                 phone = COALESCE($5, phone),
+                phone_hash = CASE WHEN $5::text IS NULL THEN phone_hash ELSE $7 END,
                 avatar = COALESCE($6, avatar),
                 updated_at = NOW()
             WHERE id = $1
@@ -148,6 +159,7 @@ impl UserService {
         .bind(bio)
         .bind(phone)
         .bind(avatar)
+        .bind(hash)
         .fetch_one(&db.pool)
         .await?;
 
@@ -172,4 +184,524 @@ impl UserService {
 
         Ok(())
     }
+
+    /// Presence the user picked, restored when they connect
+    pub async fn get_presence(db: &Database, user_id: Uuid) -> AppResult<PresenceState> {
+        let presence: Option<(String,)> = sqlx::query_as("SELECT presence FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(&db.pool)
+            .await?;
+
+        let (presence,) = presence.ok_or(AppError::UserNotFound)?;
+        Ok(PresenceState::parse(&presence).unwrap_or_default())
+    }
+
+    /// Store the presence a user picked along with the status others see
+    ///
+    /// Going invisible records `last_seen` as if the user had disconnected;
+    /// staying invisible keeps the original time.
+    pub async fn set_presence(
+        db: &Database,
+        user_id: Uuid,
+        presence: PresenceState,
+    ) -> AppResult<()> {
+        let result = sqlx::query(
+            r#"
+            UPDATE users
+            SET presence = $2,
+                status = $3,
+                last_seen = CASE
+                    WHEN $3 <> 'offline' THEN NULL
+                    WHEN status = 'offline' THEN last_seen
+                    ELSE NOW()
+                END,
+                updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(user_id)
+        .bind(presence.as_str())
+        .bind(presence.visible().as_str())
+        .execute(&db.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::UserNotFound);
+        }
+        Ok(())
+    }
+
+    /// Get `target_id`'s profile as seen by `viewer_id`, with last-seen
+    /// filtered by the target's privacy settings.
+    pub async fn get_user_for_viewer(
+        db: &Database,
+        viewer_id: Uuid,
+        target_id: Uuid,
+    ) -> AppResult<UserPublic> {
+        let mut user = Self::get_user_by_id(db, target_id).await?;
+        let visible = Self::visible_last_seen(db, viewer_id, target_id).await?;
+        user.last_seen = visible.and_then(VisibleLastSeen::exact);
+        user.last_seen_approx = visible.and_then(VisibleLastSeen::approx);
+        Ok(user)
+    }
+
+    /// The last-seen time of `target_id` that `viewer_id` may see.
+    ///
+    /// Depending on the target's `last_seen_visibility` this is the exact
+    /// timestamp, a coarse bucket, or nothing.
+    pub async fn visible_last_seen(
+        db: &Database,
+        viewer_id: Uuid,
+        target_id: Uuid,
+    ) -> AppResult<Option<VisibleLastSeen>> {
+        let row: Option<(Option<DateTime<Utc>>, Option<String>, bool)> = sqlx::query_as(
+            r#"
+            SELECT u.last_seen, s.last_seen_visibility,
+                EXISTS (SELECT 1 FROM contacts c WHERE c.owner_id = $2 AND c.contact_id = $1)
+            FROM users u
+            LEFT JOIN user_settings s ON s.user_id = u.id
+            WHERE u.id = $2
+            "#,
+        )
+        .bind(viewer_id)
+        .bind(target_id)
+        .fetch_optional(&db.pool)
+        .await?;
+
+        let (last_seen, visibility, is_contact) = row.ok_or(AppError::UserNotFound)?;
+
+        Ok(resolve_last_seen(
+            last_seen,
+            visibility.as_deref(),
+            viewer_id == target_id,
+            is_contact,
+            Utc::now(),
+        ))
+    }
+
+    /// `owner_id`'s contacts, by name
+    pub async fn list_contacts(db: &Database, owner_id: Uuid) -> AppResult<Vec<Contact>> {
+        Self::fetch_contacts(db, owner_id, None).await
+    }
+
+    /// Add `contact_id` to `owner_id`'s contacts
+    ///
+    /// With `mutual` the owner is added to the contact's list too. Adding an
+    /// existing contact is not an error.
+    pub async fn add_contact(
+        db: &Database,
+        owner_id: Uuid,
+        contact_id: Uuid,
+        mutual: bool,
+    ) -> AppResult<Contact> {
+        if owner_id == contact_id {
+            return Err(AppError::BadRequest(
+                "You cannot add yourself as a contact".to_string(),
+            ));
+        }
+        Self::get_user_by_id(db, contact_id).await?;
+
+        Self::insert_contacts(db, owner_id, &[contact_id], mutual).await?;
+
+        Self::fetch_contacts(db, owner_id, Some(&[contact_id]))
+            .await?
+            .pop()
+            .ok_or(AppError::UserNotFound)
+    }
+
+    /// Remove `contact_id` from `owner_id`'s contacts (and, with `mutual`,
+    /// the owner from theirs)
+    pub async fn remove_contact(
+        db: &Database,
+        owner_id: Uuid,
+        contact_id: Uuid,
+        mutual: bool,
+    ) -> AppResult<()> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM contacts
+            WHERE (owner_id = $1 AND contact_id = $2)
+               OR ($3 AND owner_id = $2 AND contact_id = $1)
+            "#,
+        )
+        .bind(owner_id)
+        .bind(contact_id)
+        .bind(mutual)
+        .execute(&db.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NotFound("Contact not found".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Add the users matching imported usernames or phone hashes as contacts
+    ///
+    /// Identifiers must already be normalized (`normalize_contact_import`).
+    /// Those matching nobody are kept so the owner can be told when a
+    /// matching user appears (`claim_contact_imports`). Returns the new
+    /// contacts and how many identifiers are still pending.
+    pub async fn import_contacts(
+        db: &Database,
+        owner_id: Uuid,
+        usernames: &[String],
+        phone_hashes: &[String],
+        mutual: bool,
+    ) -> AppResult<(Vec<Contact>, usize)> {
+        let matches: Vec<(Uuid, Option<String>, Option<String>)> = sqlx::query_as(
+            r#"
+            SELECT id, LOWER(username), phone_hash FROM users
+            WHERE LOWER(username) = ANY($1) OR phone_hash = ANY($2)
+            "#,
+        )
+        .bind(usernames)
+        .bind(phone_hashes)
+        .fetch_all(&db.pool)
+        .await?;
+
+        let pending_usernames: Vec<String> = usernames
+            .iter()
+            .filter(|name| !matches.iter().any(|(_, u, _)| u.as_ref() == Some(*name)))
+            .cloned()
+            .collect();
+        let pending_hashes: Vec<String> = phone_hashes
+            .iter()
+            .filter(|hash| !matches.iter().any(|(_, _, h)| h.as_ref() == Some(*hash)))
+            .cloned()
+            .collect();
+
+        let mut contact_ids: Vec<Uuid> = Vec::new();
+        for (id, _, _) in &matches {
+            if *id != owner_id && !contact_ids.contains(id) {
+                contact_ids.push(*id);
+            }
+        }
+
+        let mut tx = db.pool.begin().await?;
+        for (kind, identifiers) in [
+            (IMPORT_KIND_USERNAME, &pending_usernames),
+            (IMPORT_KIND_PHONE_HASH, &pending_hashes),
+        ] {
+            if identifiers.is_empty() {
+                continue;
+            }
+            sqlx::query(
+                r#"
+                INSERT INTO contact_imports (owner_id, kind, identifier)
+                SELECT $1, $2, identifier FROM UNNEST($3::text[]) AS identifier
+                ON CONFLICT DO NOTHING
+                "#,
+            )
+            .bind(owner_id)
+            .bind(kind)
+            .bind(identifiers)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Self::insert_contacts(db, owner_id, &contact_ids, mutual).await?;
+        let contacts = Self::fetch_contacts(db, owner_id, Some(&contact_ids)).await?;
+
+        Ok((contacts, pending_usernames.len() + pending_hashes.len()))
+    }
+
+    /// Turn pending imports matching `user_id`'s username or phone number
+    /// into contacts
+    ///
+    /// Call after a user sets their username or phone number. Returns the
+    /// owners who want to be told (`contact_joined_notify`).
+    pub async fn claim_contact_imports(
+        db: &Database,
+        user_id: Uuid,
+        mutual: bool,
+    ) -> AppResult<Vec<Uuid>> {
+        let owners: Vec<Uuid> = sqlx::query_scalar(
+            r#"
+            DELETE FROM contact_imports i
+            USING users u
+            WHERE u.id = $1 AND i.owner_id <> $1
+              AND ((i.kind = $2 AND i.identifier = LOWER(u.username))
+                OR (i.kind = $3 AND i.identifier = u.phone_hash))
+            RETURNING i.owner_id
+            "#,
+        )
+        .bind(user_id)
+        .bind(IMPORT_KIND_USERNAME)
+        .bind(IMPORT_KIND_PHONE_HASH)
+        .fetch_all(&db.pool)
+        .await?;
+
+        let mut claimed: Vec<Uuid> = Vec::new();
+        for owner_id in owners {
+            if !claimed.contains(&owner_id) {
+                Self::insert_contacts(db, owner_id, &[user_id], mutual).await?;
+                claimed.push(owner_id);
+            }
+        }
+
+        if claimed.is_empty() {
+            return Ok(claimed);
+        }
+
+        let notify = sqlx::query_scalar(
+            r#"
+            SELECT owner_id FROM UNNEST($1::uuid[]) AS owner_id
+            JOIN user_settings s ON s.user_id = owner_id
+            WHERE s.contact_joined_notify
+            "#,
+        )
+        .bind(&claimed)
+        .fetch_all(&db.pool)
+        .await?;
+
+        Ok(notify)
+    }
+
+    /// Add `contact_ids` to `owner_id`'s list, and with `mutual` the reverse
+    async fn insert_contacts(
+        db: &Database,
+        owner_id: Uuid,
+        contact_ids: &[Uuid],
+        mutual: bool,
+    ) -> AppResult<()> {
+        if contact_ids.is_empty() {
+            return Ok(());
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO contacts (owner_id, contact_id)
+            SELECT $1, contact_id FROM UNNEST($2::uuid[]) AS contact_id
+            WHERE contact_id <> $1
+            UNION
+            SELECT contact_id, $1 FROM UNNEST($2::uuid[]) AS contact_id
+            WHERE $3 AND contact_id <> $1
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(owner_id)
+        .bind(contact_ids)
+        .bind(mutual)
+        .execute(&db.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn fetch_contacts(
+        db: &Database,
+        owner_id: Uuid,
+        only: Option<&[Uuid]>,
+    ) -> AppResult<Vec<Contact>> {
+        let contacts = sqlx::query_as(
+            r#"
+            SELECT u.id, u.name, u.username, u.avatar,
+                EXISTS (SELECT 1 FROM contacts r WHERE r.owner_id = u.id AND r.contact_id = $1) AS is_mutual,
+                c.created_at AS added_at
+            FROM contacts c
+            JOIN users u ON u.id = c.contact_id
+            WHERE c.owner_id = $1 AND ($2::uuid[] IS NULL OR c.contact_id = ANY($2))
+            ORDER BY u.name
+            "#,
+        )
+        .bind(owner_id)
+        .bind(only)
+        .fetch_all(&db.pool)
+        .await?;
+
+        Ok(contacts)
+    }
+}
+
+/// SHA-256 (hex) of a phone number with everything but digits and a
+/// leading `+` stripped; `None` when it has no digits
+///
+/// Clients hash their address book the same way before importing, so raw
+/// numbers of people who aren't users never reach the server.
+pub fn phone_hash(phone: &str) -> Option<String> {
+    let phone = phone.trim();
+    let digits: String = phone.chars().filter(char::is_ascii_digit).collect();
+    if digits.is_empty() {
+        return None;
+    }
+
+    let normalized = if phone.starts_with('+') {
+        format!("+{}", digits)
+    } else {
+        digits
+    };
+    Some(format!("{:x}", Sha256::digest(normalized.as_bytes())))
+}
+
+/// Clean up a contact import: usernames are trimmed, lowercased and lose a
+/// leading `@`; phone hashes must be hex SHA-256. Duplicates and blanks are
+/// dropped.
+pub fn normalize_contact_import(
+    usernames: &[String],
+    phone_hashes: &[String],
+) -> AppResult<(Vec<String>, Vec<String>)> {
+    if usernames.len() + phone_hashes.len() > MAX_CONTACT_IMPORT {
+        return Err(AppError::BadRequest(format!(
+            "At most {} contacts can be imported at once",
+            MAX_CONTACT_IMPORT
+        )));
+    }
+
+    let mut names: Vec<String> = Vec::new();
+    for name in usernames {
+        let name = name.trim().trim_start_matches('@').to_lowercase();
+        if name.len() > 255 {
+            return Err(AppError::BadRequest("Username is too long".to_string()));
+        }
+        if !name.is_empty() && !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    let mut hashes: Vec<String> = Vec::new();
+    for hash in phone_hashes {
+        let hash = hash.trim().to_lowercase();
+        if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(AppError::BadRequest(
+                "Phone hashes must be hex-encoded SHA-256".to_string(),
+            ));
+        }
+        if !hashes.contains(&hash) {
+            hashes.push(hash);
+        }
+    }
+
+    Ok((names, hashes))
+}
+
+/// Apply a user's last-seen privacy setting for one viewer.
+///
+/// Users always see their own exact time. Otherwise "everyone" shows the
+/// exact time, "contacts" shows it to contacts (people in the user's contact
+/// list) and a coarse bucket to everyone else, and "nobody" hides it
+/// entirely.
+pub fn resolve_last_seen(
+    last_seen: Option<DateTime<Utc>>,
+    visibility: Option<&str>,
+    is_self: bool,
+    is_contact: bool,
+    now: DateTime<Utc>,
+) -> Option<VisibleLastSeen> {
+    let last_seen = last_seen?;
+
+    if is_self {
+        return Some(VisibleLastSeen::Exact(last_seen));
+    }
+
+    match visibility.unwrap_or("everyone") {
+        "nobody" => None,
+        "contacts" if !is_contact => Some(VisibleLastSeen::Approximate(approximate(last_seen, now))),
+        _ => Some(VisibleLastSeen::Exact(last_seen)),
+    }
+}
+
+fn approximate(last_seen: DateTime<Utc>, now: DateTime<Utc>) -> LastSeenApprox {
+    let elapsed = now - last_seen;
+    if elapsed <= Duration::days(3) {
+        LastSeenApprox::Recently
+    } else if elapsed <= Duration::days(7) {
+        LastSeenApprox::LastWeek
+    } else if elapsed <= Duration::days(30) {
+        LastSeenApprox::LastMonth
+    } else {
+        LastSeenApprox::LongAgo
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_everyone_sees_exact() {
+        let now = Utc::now();
+        let seen = now - Duration::hours(2);
+        for visibility in [Some("everyone"), Some("everybody"), None] {
+            assert_eq!(
+                resolve_last_seen(Some(seen), visibility, false, false, now),
+                Some(VisibleLastSeen::Exact(seen))
+            );
+        }
+    }
+
+    #[test]
+    fn test_contacts_visibility() {
+        let now = Utc::now();
+        let seen = now - Duration::days(5);
+        assert_eq!(
+            resolve_last_seen(Some(seen), Some("contacts"), false, true, now),
+            Some(VisibleLastSeen::Exact(seen))
+        );
+        assert_eq!(
+            resolve_last_seen(Some(seen), Some("contacts"), false, false, now),
+            Some(VisibleLastSeen::Approximate(LastSeenApprox::LastWeek))
+        );
+    }
+
+    #[test]
+    fn test_nobody_hides_last_seen() {
+        let now = Utc::now();
+        let seen = now - Duration::minutes(1);
+        assert_eq!(resolve_last_seen(Some(seen), Some("nobody"), false, true, now), None);
+        assert_eq!(resolve_last_seen(Some(seen), Some("nobody"), false, false, now), None);
+    }
+
+    #[test]
+    fn test_self_always_sees_exact() {
+        let now = Utc::now();
+        let seen = now - Duration::days(1);
+        assert_eq!(
+            resolve_last_seen(Some(seen), Some("nobody"), true, false, now),
+            Some(VisibleLastSeen::Exact(seen))
+        );
+    }
+
+    #[test]
+    fn test_no_last_seen() {
+        assert_eq!(resolve_last_seen(None, Some("everyone"), false, true, Utc::now()), None);
+    }
+
+    #[test]
+    fn test_phone_hash_ignores_formatting() {
+        let hash = phone_hash("+1 (555) 010-0199").unwrap();
+        assert_eq!(hash.len(), 64);
+        assert_eq!(phone_hash("+15550100199").unwrap(), hash);
+        assert_ne!(phone_hash("15550100199").unwrap(), hash);
+        assert_eq!(phone_hash(" - "), None);
+    }
+
+    #[test]
+    fn test_normalize_contact_import() {
+        let hash = phone_hash("+15550100199").unwrap();
+        let (names, hashes) = normalize_contact_import(
+            &["@Alice".to_string(), " alice ".to_string(), "".to_string(), "Bob".to_string()],
+            &[hash.to_uppercase(), hash.clone()],
+        )
+        .unwrap();
+        assert_eq!(names, vec!["alice", "bob"]);
+        assert_eq!(hashes, vec![hash]);
+    }
+
+    #[test]
+    fn test_normalize_contact_import_rejects_raw_numbers() {
+        assert!(normalize_contact_import(&[], &["+15550100199".to_string()]).is_err());
+        let too_many = vec!["user".to_string(); MAX_CONTACT_IMPORT + 1];
+        assert!(normalize_contact_import(&too_many, &[]).is_err());
+    }
+
+    #[test]
+    fn test_approximate_buckets() {
+        let now = Utc::now();
+        assert_eq!(approximate(now - Duration::hours(1), now), LastSeenApprox::Recently);
+        assert_eq!(approximate(now - Duration::days(6), now), LastSeenApprox::LastWeek);
+        assert_eq!(approximate(now - Duration::days(20), now), LastSeenApprox::LastMonth);
+        assert_eq!(approximate(now - Duration::days(90), now), LastSeenApprox::LongAgo);
+    }
 }
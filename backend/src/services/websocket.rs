@@ -3,10 +3,22 @@ use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
 use crate::{
-    models::MessageResponse,
-    ws::{events::{ReadByInfo, ServerEvent}, WsManager},
+    config::PresenceScope,
+    db::Database,
+    error::{AppError, AppResult},
+    models::{
+        CallbackQuery, Chat, ChatUserState, DeliveryStatus, LinkPreviewResponse, MessageResponse,
+        Poll, PollResponse, PresenceState, VisibleLastSeen,
+    },
+    quic::ConnectionManagerError,
+    services::{message::ReplyToInput, user::resolve_last_seen, MessageService, UserService},
+    ws::{events::{PresenceStatus, ReadByInfo, ServerEvent}, PresenceAudience, WsManager},
+    AppState,
 };
 
+/// Most message ids accepted in one delivery acknowledgement
+pub const MAX_DELIVERY_ACK_IDS: usize = 200;
+
 /// Service for broadcasting WebSocket events
 pub struct WebSocketService;
 
@@ -24,6 +36,61 @@ impl WebSocketService {
             .await;
     }
 
+    /// Send a new message event to every device of each recipient over its
+    /// preferred transport, falling back to the other one (see
+    /// `ConnectionManager::send_to_user_preferred`)
+    ///
+    /// Recipients are served concurrently. Returns the recipients it reached.
+    pub async fn send_to_recipients(
+        state: &AppState,
+        event: &ServerEvent,
+        participant_ids: &[Uuid],
+        sender_id: Uuid,
+    ) -> Vec<Uuid> {
+        let data = match serde_json::to_vec(event) {
+            Ok(data) => data,
+            Err(e) => {
+                tracing::error!("Failed to serialize event: {}", e);
+                return Vec::new();
+            }
+        };
+
+        let recipients: Vec<Uuid> = participant_ids
+            .iter()
+            .copied()
+            .filter(|&id| id != sender_id)
+            .collect();
+        let results = futures::future::join_all(
+            recipients
+                .iter()
+                .map(|&recipient| state.connection_manager.send_to_user_preferred(recipient, &data)),
+        )
+        .await;
+
+        let mut reached = Vec::new();
+        for (recipient, result) in recipients.into_iter().zip(results) {
+            match result {
+                Ok(_) => reached.push(recipient),
+                Err(ConnectionManagerError::UserNotFound(_)) => {}
+                Err(e) => tracing::warn!("Failed to send to user {}: {}", recipient, e),
+            }
+        }
+        reached
+    }
+
+    /// Broadcast a system message to all chat participants, including the
+    /// user whose action it records
+    pub async fn broadcast_system_message(
+        ws_manager: &Arc<WsManager>,
+        message: MessageResponse,
+        participant_ids: &[Uuid],
+    ) {
+        let event = ServerEvent::NewMessage { message };
+        ws_manager
+            .broadcast_to_chat_participants(participant_ids, event, None)
+            .await;
+    }
+
     /// Broadcast message updated (edited) to all chat participants
     pub async fn broadcast_message_updated(
         ws_manager: &Arc<WsManager>,
@@ -51,6 +118,43 @@ impl WebSocketService {
             .await;
     }
 
+    /// Broadcast a generated link preview to all chat participants, sender included
+    pub async fn broadcast_link_preview(
+        ws_manager: &Arc<WsManager>,
+        chat_id: Uuid,
+        message_id: Uuid,
+        preview: LinkPreviewResponse,
+        participant_ids: &[Uuid],
+    ) {
+        let event = ServerEvent::LinkPreviewReady {
+            message_id,
+            chat_id,
+            preview,
+        };
+        ws_manager
+            .broadcast_to_chat_participants(participant_ids, event, None)
+            .await;
+    }
+
+    /// Broadcast new poll tallies to all chat participants, voter included
+    pub async fn broadcast_poll_updated(
+        ws_manager: &Arc<WsManager>,
+        poll: &Poll,
+        response: PollResponse,
+        participant_ids: &[Uuid],
+    ) {
+        let event = ServerEvent::PollUpdated {
+            poll_id: poll.id,
+            chat_id: poll.chat_id,
+            message_id: poll.message_id,
+            tallies: response.options,
+            is_closed: response.is_closed,
+        };
+        ws_manager
+            .broadcast_to_chat_participants(participant_ids, event, None)
+            .await;
+    }
+
     /// Broadcast message pinned/unpinned to all chat participants
     pub async fn broadcast_message_pinned(
         ws_manager: &Arc<WsManager>,
@@ -66,20 +170,94 @@ impl WebSocketService {
             .await;
     }
 
-    /// Broadcast reaction updated to all chat participants
+    /// Broadcast slow mode change to all chat participants (including the admin who changed it)
+    pub async fn broadcast_slow_mode_changed(
+        ws_manager: &Arc<WsManager>,
+        chat_id: Uuid,
+        slow_mode_seconds: i32,
+        participant_ids: &[Uuid],
+    ) {
+        let event = ServerEvent::SlowModeChanged { chat_id, slow_mode_seconds };
+        ws_manager
+            .broadcast_to_chat_participants(participant_ids, event, None)
+            .await;
+    }
+
+    /// Broadcast a group chat's edited metadata to its participants
+    pub async fn broadcast_chat_updated(
+        ws_manager: &Arc<WsManager>,
+        chat: &Chat,
+        participant_ids: &[Uuid],
+    ) {
+        let event = ServerEvent::ChatUpdated {
+            chat_id: chat.id,
+            name: chat.name.clone().unwrap_or_default(),
+            description: chat.description.clone(),
+            avatar: chat.avatar.clone(),
+        };
+        ws_manager
+            .broadcast_to_chat_participants(participant_ids, event, None)
+            .await;
+    }
+
+    /// Sync a user's chat archive/folder/mute state to their other devices
+    pub async fn send_chat_state_updated(
+        ws_manager: &Arc<WsManager>,
+        user_id: Uuid,
+        chat_id: Uuid,
+        state: &ChatUserState,
+    ) {
+        let event = ServerEvent::ChatStateUpdated {
+            chat_id,
+            is_archived: state.archived,
+            folder: state.folder.clone(),
+            is_muted: state.muted,
+        };
+        ws_manager.send_to_user(user_id, event).await;
+    }
+
+    /// Tell a mentioned user about the message mentioning them
+    pub async fn send_mentioned(
+        ws_manager: &Arc<WsManager>,
+        user_id: Uuid,
+        chat_id: Uuid,
+        message_id: Uuid,
+    ) {
+        let event = ServerEvent::Mentioned { message_id, chat_id };
+        ws_manager.send_to_user(user_id, event).await;
+    }
+
+    /// Pass a bot's answer to a button press on to the user who pressed it
+    pub async fn send_callback_answer(
+        ws_manager: &Arc<WsManager>,
+        query: &CallbackQuery,
+        text: Option<String>,
+        show_alert: bool,
+    ) {
+        let event = ServerEvent::CallbackAnswer {
+            callback_query_id: query.id,
+            message_id: query.message_id,
+            text,
+            show_alert,
+        };
+        ws_manager.send_to_user(query.user_id, event).await;
+    }
+
+    /// Broadcast reaction updated to all chat participants except `exclude`
     pub async fn broadcast_reaction_updated(
         ws_manager: &Arc<WsManager>,
         message: MessageResponse,
         participant_ids: &[Uuid],
-        sender_id: Uuid,
+        exclude: Option<Uuid>,
     ) {
         let event = ServerEvent::ReactionUpdated { message };
         ws_manager
-            .broadcast_to_chat_participants(participant_ids, event, Some(sender_id))
+            .broadcast_to_chat_participants(participant_ids, event, exclude)
             .await;
     }
 
-    /// Broadcast typing indicator to chat participants
+    /// Broadcast typing indicator to chat participants; a start expires on
+    /// its own after `TYPING_TIMEOUT`
     pub async fn broadcast_typing(
         ws_manager: &Arc<WsManager>,
         chat_id: Uuid,
@@ -87,28 +265,37 @@ impl WebSocketService {
         user_name: String,
         is_typing: bool,
     ) {
-        let event = ServerEvent::Typing {
-            chat_id,
-            user_id,
-            user_name,
-            is_typing,
-        };
-        ws_manager.broadcast_to_room(chat_id, event, Some(user_id)).await;
+        if is_typing {
+            ws_manager.start_typing(chat_id, user_id, user_name).await;
+        } else {
+            ws_manager.stop_typing(chat_id, user_id, user_name).await;
+        }
     }
 
-    /// Broadcast user status change (online/offline)
+    /// Broadcast user status change (online/offline) within the configured
+    /// `PRESENCE_SCOPE`
     pub async fn broadcast_user_status(
-        ws_manager: &Arc<WsManager>,
+        state: &AppState,
         user_id: Uuid,
-        status: String,
+        status: &str,
         last_seen: Option<DateTime<Utc>>,
     ) {
-        let event = ServerEvent::UserStatus {
-            user_id,
-            status,
-            last_seen,
-        };
-        ws_manager.broadcast_user_status(event).await;
+        let audience = Self::presence_audience(state, user_id).await;
+        state
+            .ws_manager
+            .broadcast_user_status(user_id, status, last_seen, &audience)
+            .await;
+    }
+
+    /// Who hears about `user_id`'s presence; if that can't be looked up, the
+    /// change is kept to the user's own connections
+    async fn presence_audience(state: &AppState, user_id: Uuid) -> PresenceAudience {
+        WsManager::presence_audience(&state.db, user_id, state.config.presence_scope)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::error!("Failed to load presence audience of user {}: {}", user_id, e);
+                PresenceAudience::nobody()
+            })
     }
 
     /// Broadcast message delivery status update
@@ -116,18 +303,46 @@ impl WebSocketService {
         ws_manager: &Arc<WsManager>,
         chat_id: Uuid,
         message_id: Uuid,
-        status: String,
+        status: DeliveryStatus,
+        recipient_id: Uuid,
         sender_id: Uuid,
     ) {
         let event = ServerEvent::MessageStatus {
             chat_id,
             message_id,
             status,
+            user_id: recipient_id,
         };
         // Send to the message sender
         ws_manager.send_to_user(sender_id, event).await;
     }
 
+    /// Mark `message_ids` delivered to `user_id` and tell each sender
+    ///
+    /// Used both for recipients online at send time and for client acks.
+    pub async fn mark_delivered(state: &AppState, user_id: Uuid, message_ids: &[Uuid]) {
+        let message_ids = &message_ids[..message_ids.len().min(MAX_DELIVERY_ACK_IDS)];
+        let delivered = match MessageService::record_delivered(&state.db, message_ids, user_id).await {
+            Ok(delivered) => delivered,
+            Err(e) => {
+                tracing::warn!("Failed to record delivery for user {}: {}", user_id, e);
+                return;
+            }
+        };
+
+        for (message_id, chat_id, sender_id) in delivered {
+            Self::broadcast_message_status(
+                &state.ws_manager,
+                chat_id,
+                message_id,
+                DeliveryStatus::Delivered,
+                user_id,
+                sender_id,
+            )
+            .await;
+        }
+    }
+
     /// Broadcast message read receipt
     pub async fn broadcast_message_read(
         ws_manager: &Arc<WsManager>,
@@ -223,4 +438,347 @@ impl WebSocketService {
         let event = ServerEvent::UserBusy { call_id };
         ws_manager.send_to_user(caller_id, event).await;
     }
+
+    /// Handle a `send_message` client event from a socket transport
+    ///
+    /// Returns the acknowledgement for the connection the event came in on:
+    /// `message_ack` with the server message id, or `message_failed`
+    /// carrying the same error code the HTTP API would return.
+    pub async fn send_client_message(
+        state: &AppState,
+        sender_id: Uuid,
+        chat_id: Uuid,
+        content: String,
+        client_msg_id: Option<String>,
+        reply_to: Option<Uuid>,
+    ) -> ServerEvent {
+        let reply_to = reply_to.map(|id| ReplyToInput { id });
+
+        let result = MessageService::send_and_deliver(
+            state,
+            chat_id,
+            sender_id,
+            Some(content),
+            Vec::new(),
+            reply_to,
+        )
+        .await;
+
+        match result {
+            Ok(message) => ServerEvent::MessageAck {
+                client_msg_id,
+                chat_id,
+                message_id: message.id,
+                timestamp: message.timestamp,
+            },
+            Err(e) => {
+                tracing::debug!("Rejected socket message from user {}: {}", sender_id, e);
+                ServerEvent::MessageFailed {
+                    client_msg_id,
+                    chat_id,
+                    code: e.status_and_code().1.to_string(),
+                    message: e.to_string(),
+                }
+            }
+        }
+    }
+
+    /// Events queued for a user while they were offline, for a new connection
+    /// to send once it is set up
+    ///
+    /// The queued messages are recorded as delivered. Failures only cost the
+    /// replay; the messages are still in the user's history.
+    pub async fn offline_events(state: &AppState, user_id: Uuid) -> Vec<ServerEvent> {
+        let events = match state.offline_queue.take_events(&state.db, user_id).await {
+            Ok(events) => events,
+            Err(e) => {
+                tracing::error!("Failed to load queued messages of user {}: {}", user_id, e);
+                return Vec::new();
+            }
+        };
+
+        let message_ids: Vec<Uuid> = events
+            .iter()
+            .filter_map(|event| match event {
+                ServerEvent::NewMessage { message } => Some(message.id),
+                _ => None,
+            })
+            .collect();
+        for ids in message_ids.chunks(MAX_DELIVERY_ACK_IDS) {
+            Self::mark_delivered(state, user_id, ids).await;
+        }
+        events
+    }
+
+    /// Build the presence baseline for a newly connected user: everyone whose
+    /// presence changes would reach them under `scope` (the same audience as
+    /// `broadcast_user_status`), online according to the live connection set.
+    pub async fn presence_snapshot(
+        db: &Database,
+        ws_manager: &WsManager,
+        scope: PresenceScope,
+        user_id: Uuid,
+    ) -> AppResult<Vec<PresenceStatus>> {
+        #[derive(sqlx::FromRow)]
+        struct PartnerRow {
+            id: Uuid,
+            last_seen: Option<DateTime<Utc>>,
+            last_seen_visibility: Option<String>,
+            shares_chat: bool,
+            lists_me: bool,
+        }
+
+        let presences = ws_manager.visible_presences().await;
+        // With `PresenceScope::All` every connected user is in the audience
+        let connected: Vec<Uuid> = match scope {
+            PresenceScope::All => presences.keys().copied().collect(),
+            _ => Vec::new(),
+        };
+
+        // Each candidate's audience membership in one pass, mirroring
+        // `WsManager::presence_audience`
+        let partners: Vec<PartnerRow> = sqlx::query_as(
+            r#"
+            WITH chat_partners AS (
+                SELECT DISTINCT cp.user_id
+                FROM chat_participants me
+                JOIN chats c ON c.id = me.chat_id AND c.deleted_at IS NULL
+                JOIN chat_participants cp ON cp.chat_id = me.chat_id AND cp.user_id <> me.user_id
+                WHERE me.user_id = $1
+            ),
+            listed_by AS (
+                SELECT owner_id AS user_id FROM contacts WHERE contact_id = $1
+            )
+            SELECT
+                u.id,
+                u.last_seen,
+                s.last_seen_visibility,
+                u.id IN (SELECT user_id FROM chat_partners) AS shares_chat,
+                u.id IN (SELECT user_id FROM listed_by) AS lists_me
+            FROM users u
+            LEFT JOIN user_settings s ON s.user_id = u.id
+            WHERE u.id <> $1
+              AND (
+                  u.id IN (SELECT user_id FROM chat_partners)
+                  OR u.id IN (SELECT user_id FROM listed_by)
+                  OR u.id = ANY($2)
+              )
+            "#,
+        )
+        .bind(user_id)
+        .bind(&connected)
+        .fetch_all(&db.pool)
+        .await?;
+
+        let now = Utc::now();
+        let mut statuses = Vec::with_capacity(partners.len());
+        for partner in partners {
+            let reaches = match scope {
+                PresenceScope::All => true,
+                PresenceScope::Contacts => partner.lists_me,
+                PresenceScope::SharedChats => partner.shares_chat,
+            };
+            if !reaches {
+                continue;
+            }
+            let presence = presences
+                .get(&partner.id)
+                .copied()
+                .unwrap_or(PresenceState::Offline);
+            let visible = resolve_last_seen(
+                partner.last_seen,
+                partner.last_seen_visibility.as_deref(),
+                false,
+                partner.lists_me,
+                now,
+            );
+            statuses.push(presence_status(partner.id, presence, visible));
+        }
+        Ok(statuses)
+    }
+
+    /// Apply a presence the user picked (`set_presence`) and announce it
+    ///
+    /// `offline` cannot be picked while connected; `invisible` is the way to
+    /// appear offline.
+    pub async fn set_presence(
+        state: &AppState,
+        user_id: Uuid,
+        presence: PresenceState,
+    ) -> AppResult<()> {
+        if presence == PresenceState::Offline {
+            return Err(AppError::BadRequest(
+                "Use 'invisible' to appear offline".to_string(),
+            ));
+        }
+
+        let before = state.ws_manager.presence_of(user_id).await;
+        let after = state.ws_manager.set_presence(user_id, presence).await;
+        UserService::set_presence(&state.db, user_id, presence).await?;
+        Self::announce_presence(state, user_id, before, after).await;
+        Ok(())
+    }
+
+    /// Apply a client-reported idle/active transition (auto-away)
+    pub async fn set_idle(state: &AppState, user_id: Uuid, idle: bool) -> AppResult<()> {
+        let before = state.ws_manager.presence_of(user_id).await;
+        let after = state.ws_manager.set_idle(user_id, idle).await;
+        if before != after {
+            UserService::update_status(&state.db, user_id, after.visible().as_str()).await?;
+            Self::announce_presence(state, user_id, before, after).await;
+        }
+        Ok(())
+    }
+
+    /// Add `user_id` to the contacts of everyone who imported their username
+    /// or phone hash, and tell those who asked for `contact_joined` alerts
+    ///
+    /// Call after the user's username or phone number changed.
+    pub async fn announce_contact_joined(state: &AppState, user_id: Uuid) -> AppResult<()> {
+        let owners =
+            UserService::claim_contact_imports(&state.db, user_id, state.config.contacts_mutual)
+                .await?;
+        if owners.is_empty() {
+            return Ok(());
+        }
+
+        let (name, username): (String, Option<String>) =
+            sqlx::query_as("SELECT name, username FROM users WHERE id = $1")
+                .bind(user_id)
+                .fetch_one(&state.db.pool)
+                .await?;
+        let event = ServerEvent::ContactJoined {
+            user_id,
+            name,
+            username,
+        };
+        for owner_id in owners {
+            state.ws_manager.send_to_user(owner_id, event.clone()).await;
+        }
+        Ok(())
+    }
+
+    /// Tell the user their real presence and, if what others see changed,
+    /// tell the users in their presence scope the visible one
+    async fn announce_presence(
+        state: &AppState,
+        user_id: Uuid,
+        before: PresenceState,
+        after: PresenceState,
+    ) {
+        let own = ServerEvent::UserStatus {
+            user_id,
+            status: after.as_str().to_string(),
+            last_seen: None,
+        };
+
+        if before.visible() == after.visible() {
+            state.ws_manager.send_to_user(user_id, own).await;
+            return;
+        }
+
+        let visible = after.visible();
+        let last_seen = (visible == PresenceState::Offline).then(Utc::now);
+        let audience = Self::presence_audience(state, user_id).await;
+        state
+            .ws_manager
+            .broadcast_presence(user_id, own, visible.as_str(), last_seen, &audience)
+            .await;
+    }
+}
+
+/// Presence entry for one user. Connected users carry no last-seen; offline
+/// users carry whatever their privacy settings let the viewer see.
+fn presence_status(
+    user_id: Uuid,
+    presence: PresenceState,
+    last_seen: Option<VisibleLastSeen>,
+) -> PresenceStatus {
+    let last_seen = if presence == PresenceState::Offline { last_seen } else { None };
+
+    PresenceStatus {
+        user_id,
+        status: presence.as_str().to_string(),
+        last_seen: last_seen.and_then(VisibleLastSeen::exact),
+        last_seen_approx: last_seen.and_then(VisibleLastSeen::approx),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::LastSeenApprox;
+
+    #[test]
+    fn test_presence_status_online_has_no_timestamp() {
+        let status = presence_status(
+            Uuid::nil(),
+            PresenceState::Online,
+            Some(VisibleLastSeen::Exact(Utc::now())),
+        );
+        assert_eq!(status.status, "online");
+        assert_eq!(status.last_seen, None);
+        assert_eq!(status.last_seen_approx, None);
+    }
+
+    #[test]
+    fn test_presence_status_respects_hidden_last_seen() {
+        let seen = Utc::now();
+        let hidden = presence_status(Uuid::nil(), PresenceState::Offline, None);
+        assert_eq!(hidden.status, "offline");
+        assert_eq!(hidden.last_seen, None);
+
+        let exact = presence_status(
+            Uuid::nil(),
+            PresenceState::Offline,
+            Some(VisibleLastSeen::Exact(seen)),
+        );
+        assert_eq!(exact.last_seen, Some(seen));
+
+        let approx = presence_status(
+            Uuid::nil(),
+            PresenceState::Offline,
+            Some(VisibleLastSeen::Approximate(LastSeenApprox::Recently)),
+        );
+        assert_eq!(approx.last_seen, None);
+        assert_eq!(approx.last_seen_approx, Some(LastSeenApprox::Recently));
+    }
+
+    #[test]
+    fn test_presence_status_away_and_busy() {
+        let seen = Some(VisibleLastSeen::Exact(Utc::now()));
+        let away = presence_status(Uuid::nil(), PresenceState::Away, seen);
+        assert_eq!(away.status, "away");
+        assert_eq!(away.last_seen, None);
+
+        let busy = presence_status(Uuid::nil(), PresenceState::Busy, seen);
+        assert_eq!(busy.status, "busy");
+        assert_eq!(busy.last_seen, None);
+    }
+
+    #[test]
+    fn test_delivery_status_respects_read_receipt_privacy() {
+        assert_eq!(DeliveryStatus::resolve(false, false, true), DeliveryStatus::Sent);
+        assert_eq!(DeliveryStatus::resolve(true, false, true), DeliveryStatus::Delivered);
+        assert_eq!(DeliveryStatus::resolve(true, true, true), DeliveryStatus::Read);
+        // Read without a delivery ack still implies delivery
+        assert_eq!(DeliveryStatus::resolve(false, true, true), DeliveryStatus::Read);
+        // Private readers never show as read
+        assert_eq!(DeliveryStatus::resolve(true, true, false), DeliveryStatus::Delivered);
+        assert_eq!(DeliveryStatus::resolve(false, true, false), DeliveryStatus::Delivered);
+    }
+
+    #[test]
+    fn test_message_status_event_shape() {
+        let event = ServerEvent::MessageStatus {
+            chat_id: Uuid::nil(),
+            message_id: Uuid::nil(),
+            status: DeliveryStatus::Delivered,
+            user_id: Uuid::nil(),
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], "message_status");
+        assert_eq!(json["data"]["status"], "delivered");
+        assert_eq!(json["data"]["userId"], Uuid::nil().to_string());
+    }
 }
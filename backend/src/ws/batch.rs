@@ -0,0 +1,170 @@
+/// WebSocket event batching - fewer frames during message bursts.
+///
+/// Clients that connect with `?batch=1` get the events produced within
+/// `WS_BATCH_WINDOW_MS` of the first queued one in a single `batch` frame.
+/// A batch also goes out early once it holds `WS_BATCH_MAX_EVENTS` events.
+/// Events keep the order they were queued in, and a window holding a single
+/// event sends it as a plain frame.
+use std::time::Duration;
+use tokio::time::Instant;
+
+use super::events::ServerEvent;
+
+#[derive(Debug)]
+pub struct EventBatcher {
+    window: Option<Duration>,
+    max_events: usize,
+    pending: Vec<ServerEvent>,
+    /// When the current window closes; `None` while nothing is pending
+    flush_at: Option<Instant>,
+}
+
+impl EventBatcher {
+    /// A zero window turns batching off: every event is sent as it arrives
+    pub fn new(window: Duration, max_events: usize) -> Self {
+        Self {
+            window: (!window.is_zero()).then_some(window),
+            max_events: max_events.max(1),
+            pending: Vec::new(),
+            flush_at: None,
+        }
+    }
+
+    pub fn from_millis(window_ms: u64, max_events: usize) -> Self {
+        Self::new(Duration::from_millis(window_ms), max_events)
+    }
+
+    /// Batcher for clients that didn't opt in
+    pub fn disabled() -> Self {
+        Self::new(Duration::ZERO, 1)
+    }
+
+    /// Queue an event, returning a frame to send right away when batching
+    /// is off or the batch just filled up
+    pub fn push(&mut self, event: ServerEvent) -> Option<ServerEvent> {
+        let Some(window) = self.window else {
+            return Some(event);
+        };
+
+        if self.pending.is_empty() {
+            self.flush_at = Some(Instant::now() + window);
+        }
+        self.pending.push(event);
+
+        if self.pending.len() >= self.max_events {
+            self.flush()
+        } else {
+            None
+        }
+    }
+
+    /// Take everything queued as one frame
+    pub fn flush(&mut self) -> Option<ServerEvent> {
+        self.flush_at = None;
+        match self.pending.len() {
+            0 => None,
+            1 => self.pending.pop(),
+            _ => Some(ServerEvent::Batch {
+                events: std::mem::take(&mut self.pending),
+            }),
+        }
+    }
+
+    /// Wait until the current window closes; never completes while nothing
+    /// is queued
+    pub async fn ready(&self) {
+        match self.flush_at {
+            Some(at) => tokio::time::sleep_until(at).await,
+            None => std::future::pending().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHORT: Duration = Duration::from_millis(50);
+
+    fn error(code: &str) -> ServerEvent {
+        ServerEvent::Error {
+            code: code.to_string(),
+            message: String::new(),
+        }
+    }
+
+    fn codes(frame: Option<ServerEvent>) -> Vec<String> {
+        let events = match frame {
+            Some(ServerEvent::Batch { events }) => events,
+            Some(event) => vec![event],
+            None => Vec::new(),
+        };
+        events
+            .into_iter()
+            .map(|event| match event {
+                ServerEvent::Error { code, .. } => code,
+                other => panic!("unexpected event {:?}", other),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_events_within_window_arrive_as_one_batch_in_order() {
+        let mut batcher = EventBatcher::new(SHORT, 64);
+        assert!(batcher.push(error("first")).is_none());
+        assert!(batcher.push(error("second")).is_none());
+        assert!(batcher.push(error("third")).is_none());
+
+        assert!(tokio::time::timeout(SHORT * 4, batcher.ready())
+            .await
+            .is_ok());
+        let frame = batcher.flush();
+        assert!(matches!(frame, Some(ServerEvent::Batch { .. })));
+        assert_eq!(codes(frame), ["first", "second", "third"]);
+
+        // Nothing left over, so the next window only starts with a new event
+        assert!(batcher.flush().is_none());
+        assert!(tokio::time::timeout(SHORT * 2, batcher.ready())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_full_batch_flushes_early() {
+        let mut batcher = EventBatcher::new(Duration::from_secs(60), 2);
+        assert!(batcher.push(error("a")).is_none());
+        assert_eq!(codes(batcher.push(error("b"))), ["a", "b"]);
+
+        assert!(batcher.push(error("c")).is_none());
+        assert_eq!(codes(batcher.flush()), ["c"]);
+    }
+
+    #[test]
+    fn test_single_event_is_sent_plain() {
+        let mut batcher = EventBatcher::new(SHORT, 64);
+        batcher.push(error("only"));
+        assert!(matches!(batcher.flush(), Some(ServerEvent::Error { .. })));
+    }
+
+    #[test]
+    fn test_disabled_batcher_passes_events_through() {
+        let mut batcher = EventBatcher::disabled();
+        assert_eq!(codes(batcher.push(error("a"))), ["a"]);
+        assert_eq!(codes(batcher.push(error("b"))), ["b"]);
+        assert!(batcher.flush().is_none());
+
+        let mut zero_window = EventBatcher::from_millis(0, 64);
+        assert_eq!(codes(zero_window.push(error("c"))), ["c"]);
+    }
+
+    #[test]
+    fn test_batch_frame_shape() {
+        let frame = ServerEvent::Batch {
+            events: vec![error("a"), error("b")],
+        };
+        let json = serde_json::to_value(&frame).unwrap();
+        assert_eq!(json["event"], "batch");
+        assert_eq!(json["data"]["events"][0]["event"], "error");
+        assert_eq!(json["data"]["events"][1]["data"]["code"], "b");
+    }
+}
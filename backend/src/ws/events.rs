@@ -2,7 +2,10 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::models::MessageResponse;
+use crate::models::{
+    AnnouncementLevel, DeliveryStatus, LastSeenApprox, LinkPreviewResponse, MessageResponse,
+    PollTally, PresenceState,
+};
 
 // ==================== Bot WebSocket Events ====================
 
@@ -17,6 +20,19 @@ pub enum BotServerEvent {
         update_id: Uuid,
         message: BotUpdateMessage,
     },
+    /// A user reacted to a message in a subscribed chat
+    ReactionAdded {
+        #[serde(rename = "updateId")]
+        update_id: Uuid,
+        reaction: BotUpdateReaction,
+    },
+    /// A user pressed an inline keyboard button on one of the bot's messages
+    CallbackQuery {
+        #[serde(rename = "updateId")]
+        update_id: Uuid,
+        #[serde(rename = "callbackQuery")]
+        callback_query: BotUpdateCallbackQuery,
+    },
     /// Bot authenticated successfully
     BotConnected {
         #[serde(rename = "botId")]
@@ -38,6 +54,31 @@ pub struct BotUpdateMessage {
     pub text: String,
 }
 
+/// Reaction data in bot update
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BotUpdateReaction {
+    /// The message reacted to
+    #[serde(rename = "messageId")]
+    pub message_id: Uuid,
+    pub chat: BotUpdateChat,
+    /// The user who reacted
+    pub from: BotUpdateUser,
+    pub emoji: String,
+}
+
+/// Button press in bot update; answer it with answerCallbackQuery
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BotUpdateCallbackQuery {
+    pub id: Uuid,
+    /// The message carrying the button
+    #[serde(rename = "messageId")]
+    pub message_id: Uuid,
+    pub chat: BotUpdateChat,
+    /// The user who pressed the button
+    pub from: BotUpdateUser,
+    pub data: String,
+}
+
 /// Chat info in bot update
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BotUpdateChat {
@@ -62,9 +103,28 @@ pub struct BotUpdateUser {
 pub enum ServerEvent {
     /// New message received
     NewMessage { message: MessageResponse },
+    /// Acknowledges a message sent over the socket with its server-assigned id
+    MessageAck {
+        #[serde(rename = "clientMsgId")]
+        client_msg_id: Option<String>,
+        #[serde(rename = "chatId")]
+        chat_id: Uuid,
+        #[serde(rename = "messageId")]
+        message_id: Uuid,
+        timestamp: DateTime<Utc>,
+    },
+    /// A message sent over the socket was rejected
+    MessageFailed {
+        #[serde(rename = "clientMsgId")]
+        client_msg_id: Option<String>,
+        #[serde(rename = "chatId")]
+        chat_id: Uuid,
+        code: String,
+        message: String,
+    },
     /// Message updated (edited)
     MessageUpdated { message: MessageResponse },
-    /// Message deleted
+    /// Message deleted; it stays in history as a tombstone unless purged
     MessageDeleted {
         #[serde(rename = "chatId")]
         chat_id: Uuid,
@@ -80,6 +140,78 @@ pub enum ServerEvent {
         #[serde(rename = "isPinned")]
         is_pinned: bool,
     },
+    /// Votes on a poll changed, or the poll was closed
+    PollUpdated {
+        #[serde(rename = "pollId")]
+        poll_id: Uuid,
+        #[serde(rename = "chatId")]
+        chat_id: Uuid,
+        #[serde(rename = "messageId")]
+        message_id: Uuid,
+        tallies: Vec<PollTally>,
+        #[serde(rename = "isClosed")]
+        is_closed: bool,
+    },
+    /// A reply was posted in a message thread
+    ThreadReply {
+        #[serde(rename = "rootId")]
+        root_id: Uuid,
+        #[serde(rename = "replyCount")]
+        reply_count: i32,
+        message: MessageResponse,
+    },
+    /// A link preview was generated for a message after it was sent
+    LinkPreviewReady {
+        #[serde(rename = "messageId")]
+        message_id: Uuid,
+        #[serde(rename = "chatId")]
+        chat_id: Uuid,
+        preview: LinkPreviewResponse,
+    },
+    /// Chat slow mode changed (0 = disabled)
+    SlowModeChanged {
+        #[serde(rename = "chatId")]
+        chat_id: Uuid,
+        #[serde(rename = "slowModeSeconds")]
+        slow_mode_seconds: i32,
+    },
+    /// A group chat's name, description or avatar was edited
+    ChatUpdated {
+        #[serde(rename = "chatId")]
+        chat_id: Uuid,
+        name: String,
+        description: Option<String>,
+        avatar: Option<String>,
+    },
+    /// The user's own archive/folder/mute state of a chat changed (sent only
+    /// to that user's devices)
+    ChatStateUpdated {
+        #[serde(rename = "chatId")]
+        chat_id: Uuid,
+        #[serde(rename = "isArchived")]
+        is_archived: bool,
+        folder: Option<String>,
+        #[serde(rename = "isMuted")]
+        is_muted: bool,
+    },
+    /// The user was @-mentioned in a message (sent only to that user)
+    Mentioned {
+        #[serde(rename = "messageId")]
+        message_id: Uuid,
+        #[serde(rename = "chatId")]
+        chat_id: Uuid,
+    },
+    /// A bot answered the user's press of one of its buttons (sent only to
+    /// that user)
+    CallbackAnswer {
+        #[serde(rename = "callbackQueryId")]
+        callback_query_id: Uuid,
+        #[serde(rename = "messageId")]
+        message_id: Uuid,
+        text: Option<String>,
+        #[serde(rename = "showAlert")]
+        show_alert: bool,
+    },
     /// Reaction added/removed
     ReactionUpdated { message: MessageResponse },
     /// User typing indicator
@@ -101,13 +233,25 @@ pub enum ServerEvent {
         #[serde(rename = "lastSeen")]
         last_seen: Option<DateTime<Utc>>,
     },
-    /// Message delivery status update
+    /// One of the user's bots connected (`online`) or lost its last
+    /// WebSocket connection (`offline`); sent to the bot's owner
+    BotStatus {
+        #[serde(rename = "botId")]
+        bot_id: Uuid,
+        status: String,
+        #[serde(rename = "lastSeen")]
+        last_seen: Option<DateTime<Utc>>,
+    },
+    /// Delivery status of a message for one recipient, sent to its sender
     MessageStatus {
         #[serde(rename = "chatId")]
         chat_id: Uuid,
         #[serde(rename = "messageId")]
         message_id: Uuid,
-        status: String,
+        status: DeliveryStatus,
+        /// The recipient whose status changed
+        #[serde(rename = "userId")]
+        user_id: Uuid,
     },
     /// Message read receipt
     MessageRead {
@@ -124,7 +268,24 @@ pub enum ServerEvent {
     Connected {
         #[serde(rename = "userId")]
         user_id: Uuid,
+        /// Id of this connection, as listed under the user's connections
+        #[serde(rename = "connectionId")]
+        connection_id: Uuid,
     },
+    /// Presence of the user's chat partners, sent right after `connected`
+    PresenceSnapshot { statuses: Vec<PresenceStatus> },
+    /// More messages arrived while the user was offline than were queued for
+    /// them; the client should refetch its chats instead of waiting for them
+    ResyncRequired,
+    /// The connection was terminated by the server; the socket closes right after
+    SessionTerminated { reason: String },
+    /// Operator notice, e.g. upcoming maintenance
+    SystemAnnouncement {
+        text: String,
+        level: AnnouncementLevel,
+    },
+    /// Events coalesced into one frame for clients that connected with `?batch=1`
+    Batch { events: Vec<ServerEvent> },
     /// Incoming call notification
     IncomingCall {
         #[serde(rename = "callId")]
@@ -158,7 +319,7 @@ pub enum ServerEvent {
     CallEnded {
         #[serde(rename = "callId")]
         call_id: Uuid,
-        reason: String, // "ended" | "timeout" | "error"
+        reason: String, // "ended" | "timeout" | "missed" | "error"
     },
     /// User busy (already in another call)
     UserBusy {
@@ -170,6 +331,31 @@ pub enum ServerEvent {
         #[serde(rename = "callId")]
         call_id: Uuid,
     },
+    /// Sent to the callee when a call went unanswered
+    MissedCall {
+        #[serde(rename = "callId")]
+        call_id: Uuid,
+        #[serde(rename = "callerId")]
+        caller_id: Uuid,
+        #[serde(rename = "chatId")]
+        chat_id: Uuid,
+        #[serde(rename = "callType")]
+        call_type: String,
+    },
+    /// Someone from the user's imported contacts signed up or set a matching
+    /// username or phone number, and was added to their contacts
+    ContactJoined {
+        #[serde(rename = "userId")]
+        user_id: Uuid,
+        name: String,
+        username: Option<String>,
+    },
+    /// The connection has been idle and will be closed unless the client
+    /// sends something (e.g. a `ping`) within `disconnectInSeconds`
+    IdleWarning {
+        #[serde(rename = "disconnectInSeconds")]
+        disconnect_in_seconds: u64,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -180,6 +366,18 @@ pub struct ReadByInfo {
     pub read_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PresenceStatus {
+    #[serde(rename = "userId")]
+    pub user_id: Uuid,
+    pub status: String,
+    #[serde(rename = "lastSeen")]
+    pub last_seen: Option<DateTime<Utc>>,
+    /// Coarse last-seen when privacy settings hide the exact time
+    #[serde(rename = "lastSeenApprox", skip_serializing_if = "Option::is_none")]
+    pub last_seen_approx: Option<LastSeenApprox>,
+}
+
 /// Events sent from client to server
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "event", content = "data")]
@@ -205,8 +403,27 @@ pub enum ClientEvent {
         #[serde(rename = "chatId")]
         chat_id: Uuid,
     },
+    /// Send a text message (acknowledged with `message_ack` or `message_failed`)
+    SendMessage {
+        #[serde(rename = "chatId")]
+        chat_id: Uuid,
+        content: String,
+        #[serde(rename = "clientMsgId")]
+        client_msg_id: Option<String>,
+        #[serde(rename = "replyTo")]
+        reply_to: Option<Uuid>,
+    },
+    /// Acknowledge receipt of messages (marks them `delivered` for the sender)
+    MessagesDelivered {
+        #[serde(rename = "messageIds")]
+        message_ids: Vec<Uuid>,
+    },
     /// Ping to keep connection alive
     Ping,
+    /// Pick a presence: online, away, busy or invisible
+    SetPresence { status: PresenceState },
+    /// Client idle/active transitions; an idle `online` user shows as `away`
+    SetIdle { idle: bool },
     /// Initiate a call
     InitiateCall {
         #[serde(rename = "targetUserId")]
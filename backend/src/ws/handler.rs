@@ -1,25 +1,37 @@
 use axum::{
     extract::{
-        ws::{Message, WebSocket, WebSocketUpgrade},
+        ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
         Query, State,
     },
     response::IntoResponse,
 };
-use futures::{SinkExt, StreamExt};
+use futures::{Sink, SinkExt, StreamExt};
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use uuid::Uuid;
 
-use crate::{services::AuthService, services::bot_engine::BotEngineService, AppState};
+use crate::{
+    models::{CallOutcome, PresenceState},
+    quic::{ConnectionId, ManagedConnection, WebSocketConnection},
+    services::AuthService, services::bot_engine::BotEngineService, services::WebSocketService,
+    services::{CallLogService, UserService},
+    services::request_id,
+    AppState,
+};
 
 use super::{
+    batch::EventBatcher,
     events::{BotServerEvent, ClientEvent, ServerEvent},
+    heartbeat::{self, Heartbeat, Received},
     manager::{BotClient, Client, WsManager},
 };
 
 #[derive(Debug, serde::Deserialize)]
 pub struct WsQuery {
     token: String,
+    /// `?batch=1` opts into coalesced `batch` frames
+    #[serde(default)]
+    batch: u8,
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -33,7 +45,6 @@ pub async fn ws_handler(
     Query(query): Query<WsQuery>,
     State(state): State<Arc<AppState>>,
 ) -> impl IntoResponse {
-    let ws_manager = state.ws_manager.clone();
     // Verify JWT token
     let claims = match AuthService::verify_token(&query.token, &state.config.jwt_secret) {
         Ok(claims) => claims,
@@ -68,8 +79,13 @@ pub async fn ws_handler(
     };
 
     let user_name = claims.name.clone();
+    let issued_at = claims.iat;
+    let session_id = claims.sid;
+    let batch = query.batch != 0;
 
-    ws.on_upgrade(move |socket| handle_socket(socket, user_id, user_name, state, ws_manager))
+    ws.on_upgrade(move |socket| {
+        handle_socket(socket, user_id, user_name, issued_at, session_id, batch, state)
+    })
 }
 
 /// Bot WebSocket upgrade handler with token authentication
@@ -116,8 +132,11 @@ pub async fn bot_ws_handler(
 
     let bot_id = bot.id;
     let bot_name = bot.name.clone();
+    let owner_id = bot.owner_id;
 
-    ws.on_upgrade(move |socket| handle_bot_socket(socket, bot_id, bot_name, state, ws_manager))
+    ws.on_upgrade(move |socket| {
+        handle_bot_socket(socket, bot_id, bot_name, owner_id, state, ws_manager)
+    })
 }
 
 /// Handle an individual bot WebSocket connection
@@ -125,10 +144,14 @@ pub async fn bot_ws_handler(
 /// # Requirements
 /// - 9.1: Authenticate and establish bot WebSocket connection
 /// - 9.3: Track bot connections in WsManager
+///
+/// The bot's owner gets a `bot_status` event when its first connection
+/// opens and when its last one drops.
 async fn handle_bot_socket(
     socket: WebSocket,
     bot_id: Uuid,
     bot_name: String,
+    owner_id: Uuid,
     _state: Arc<AppState>,
     ws_manager: Arc<WsManager>,
 ) {
@@ -143,7 +166,9 @@ async fn handle_bot_socket(
         bot_name: bot_name.clone(),
         sender: tx.clone(),
     };
-    ws_manager.add_bot_client(client).await;
+    if ws_manager.add_bot_client(client).await {
+        notify_bot_status(&ws_manager, owner_id, bot_id).await;
+    }
 
     // Send connected confirmation
     let connected_event = BotServerEvent::BotConnected {
@@ -170,7 +195,9 @@ async fn handle_bot_socket(
                 }
             }
         }
-        ws_manager_clone.remove_bot_client(bot_id, &tx_clone).await;
+        if ws_manager_clone.remove_bot_client(bot_id, &tx_clone).await {
+            notify_bot_status(&ws_manager_clone, owner_id, bot_id).await;
+        }
     });
 
     // Task to receive messages from WebSocket (bots don't send events, just keep-alive)
@@ -209,48 +236,145 @@ async fn handle_bot_socket(
     }
 
     // Cleanup: remove bot client
-    ws_manager.remove_bot_client(bot_id, &tx).await;
+    if ws_manager.remove_bot_client(bot_id, &tx).await {
+        notify_bot_status(&ws_manager, owner_id, bot_id).await;
+    }
     tracing::info!("Bot WebSocket disconnected: bot_id={}", bot_id);
 }
 
+/// Tell a bot's owner whether it is connected now
+async fn notify_bot_status(ws_manager: &WsManager, owner_id: Uuid, bot_id: Uuid) {
+    let status = ws_manager.bot_status(bot_id).await;
+    let event = ServerEvent::BotStatus {
+        bot_id,
+        status: if status.connected { "online" } else { "offline" }.to_string(),
+        last_seen: status.last_seen,
+    };
+    ws_manager.send_to_user(owner_id, event).await;
+}
+
 /// Handle an individual WebSocket connection
 async fn handle_socket(
     socket: WebSocket,
     user_id: Uuid,
     user_name: String,
+    issued_at: i64,
+    session_id: Option<Uuid>,
+    batch: bool,
     state: Arc<AppState>,
-    ws_manager: Arc<WsManager>,
 ) {
+    let ws_manager = state.ws_manager.clone();
     let (mut ws_sender, mut ws_receiver) = socket.split();
 
     // Create channel for sending messages to this client
     let (tx, mut rx) = mpsc::unbounded_channel::<ServerEvent>();
 
     // Register client
-    let client = Client {
-        user_id,
-        user_name: user_name.clone(),
-        sender: tx.clone(),
-    };
+    let client = Client::new(user_id, user_name.clone(), tx.clone()).with_session(session_id);
+    let connection_id = client.connection_id;
+    let activity = client.clone();
     ws_manager.add_client(client).await;
 
-    // Update user status to online
-    if let Err(e) = update_user_status(&state, user_id, "online").await {
-        tracing::error!("Failed to update user status: {}", e);
+    // Count the socket against the user's limit across both transports
+    let managed = ManagedConnection::WebSocket(
+        WebSocketConnection::new(ConnectionId::from_uuid(connection_id), user_id)
+            .with_session(session_id),
+    );
+    if let Err(e) = state.connection_manager.register_connection(managed).await {
+        tracing::warn!("WebSocket connection of user {} refused: {}", user_id, e);
+        ws_manager.remove_client(user_id, &tx).await;
+        let error = ServerEvent::Error {
+            code: "TOO_MANY_CONNECTIONS".to_string(),
+            message: "Too many open connections".to_string(),
+        };
+        let _ = send_event(&mut ws_sender, &error).await;
+        let _ = ws_sender
+            .send(Message::Close(Some(CloseFrame {
+                code: close_code::POLICY,
+                reason: "Too many connections".into(),
+            })))
+            .await;
+        return;
     }
 
-    // Broadcast user online status
-    let status_event = ServerEvent::UserStatus {
+    // Checked after registering so an admin disconnect or a terminated
+    // session racing this connect either sees the client or is seen here
+    let revoked = AuthService::is_realtime_access_revoked(
+        &state.db,
         user_id,
-        status: "online".to_string(),
-        last_seen: None,
-    };
-    ws_manager.broadcast_user_status(status_event).await;
+        session_id,
+        issued_at,
+        state.config.realtime_require_session,
+    )
+    .await
+    .unwrap_or_else(|e| {
+        tracing::error!("Failed to check token revocation: {}", e);
+        true
+    });
+    if revoked {
+        ws_manager.remove_client(user_id, &tx).await;
+        unregister_connection(&state, connection_id).await;
+        let error = ServerEvent::Error {
+            code: "INVALID_TOKEN".to_string(),
+            message: "Token has been revoked".to_string(),
+        };
+        let _ = send_event(&mut ws_sender, &error).await;
+        let _ = ws_sender.send(Message::Close(None)).await;
+        return;
+    }
+
+    // Restore the presence the user picked; invisible users stay hidden
+    let chosen = UserService::get_presence(&state.db, user_id)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::error!("Failed to load user presence: {}", e);
+            PresenceState::Online
+        });
+    let presence = ws_manager.set_presence(user_id, chosen).await;
+
+    if presence.visible() != PresenceState::Offline {
+        // Update user status
+        if let Err(e) = update_user_status(&state, user_id, presence.as_str()).await {
+            tracing::error!("Failed to update user status: {}", e);
+        }
+
+        // Broadcast user status
+        WebSocketService::broadcast_user_status(&state, user_id, presence.as_str(), None).await;
+    } else {
+        let own_status = ServerEvent::UserStatus {
+            user_id,
+            status: presence.as_str().to_string(),
+            last_seen: None,
+        };
+        let _ = tx.send(own_status);
+    }
 
     // Send connected confirmation
-    let connected_event = ServerEvent::Connected { user_id };
+    let connected_event = ServerEvent::Connected {
+        user_id,
+        connection_id,
+    };
     let _ = tx.send(connected_event);
 
+    // Send the presence baseline before any incremental user_status updates
+    match WebSocketService::presence_snapshot(
+        &state.db,
+        &ws_manager,
+        state.config.presence_scope,
+        user_id,
+    )
+    .await {
+        Ok(statuses) => {
+            let _ = tx.send(ServerEvent::PresenceSnapshot { statuses });
+        }
+        Err(e) => tracing::error!("Failed to build presence snapshot: {}", e),
+    }
+
+    // Messages that arrived while the user had no connection
+    for event in WebSocketService::offline_events(&state, user_id).await {
+        let _ = tx.send(event);
+    }
+
     // Auto-join user's chat rooms
     if let Ok(chat_ids) = get_user_chat_ids(&state, user_id).await {
         for chat_id in chat_ids {
@@ -258,19 +382,72 @@ async fn handle_socket(
         }
     }
 
+    // Fired by the receive task to close the socket (or dropped when it ends)
+    let (close_tx, mut close_rx) = oneshot::channel::<CloseFrame<'static>>();
+
+    let heartbeat = Heartbeat::from_secs(
+        state.config.ws_keepalive_seconds,
+        state.config.ws_idle_timeout_seconds,
+    );
+
     // Task to forward messages from channel to WebSocket
     let ws_manager_clone = ws_manager.clone();
     let tx_clone = tx.clone();
+    let mut ping_timer = heartbeat.ping_timer();
+    let mut batcher = if batch {
+        EventBatcher::from_millis(state.config.ws_batch_window_ms, state.config.ws_batch_max_events)
+    } else {
+        EventBatcher::disabled()
+    };
     let send_task = tokio::spawn(async move {
-        while let Some(event) = rx.recv().await {
-            match serde_json::to_string(&event) {
-                Ok(json) => {
-                    if ws_sender.send(Message::Text(json)).await.is_err() {
+        loop {
+            tokio::select! {
+                _ = heartbeat::tick(&mut ping_timer) => {
+                    if ws_sender.send(Message::Ping(Vec::new())).await.is_err() {
                         break;
                     }
                 }
-                Err(e) => {
-                    tracing::error!("Failed to serialize event: {}", e);
+                _ = batcher.ready() => {
+                    if send_frame(&mut ws_sender, batcher.flush()).await.is_err() {
+                        break;
+                    }
+                }
+                event = rx.recv() => {
+                    let Some(event) = event else { break };
+                    let terminated = match &event {
+                        ServerEvent::SessionTerminated { reason } => Some(reason.clone()),
+                        _ => None,
+                    };
+                    // The termination notice goes out with whatever is batched ahead of it
+                    let frame = match batcher.push(event) {
+                        None if terminated.is_some() => batcher.flush(),
+                        frame => frame,
+                    };
+                    if send_frame(&mut ws_sender, frame).await.is_err() {
+                        break;
+                    }
+                    if let Some(reason) = terminated {
+                        let _ = ws_sender
+                            .send(Message::Close(Some(CloseFrame {
+                                code: close_code::POLICY,
+                                reason: reason.into(),
+                            })))
+                            .await;
+                        break;
+                    }
+                }
+                close = &mut close_rx => {
+                    // Flush events queued before the close, e.g. a rejection error
+                    while let Ok(event) = rx.try_recv() {
+                        if send_frame(&mut ws_sender, batcher.push(event)).await.is_err() {
+                            break;
+                        }
+                    }
+                    let _ = send_frame(&mut ws_sender, batcher.flush()).await;
+                    if let Ok(frame) = close {
+                        let _ = ws_sender.send(Message::Close(Some(frame))).await;
+                    }
+                    break;
                 }
             }
         }
@@ -280,16 +457,51 @@ async fn handle_socket(
     // Task to receive messages from WebSocket
     let ws_manager_clone = ws_manager.clone();
     let state_clone = state.clone();
+    let tx_clone = tx.clone();
+    let max_frame_bytes = state.config.ws_max_frame_bytes;
     let recv_task = tokio::spawn(async move {
-        while let Some(result) = ws_receiver.next().await {
+        loop {
+            let result = match heartbeat.next_frame(&mut ws_receiver).await {
+                Received::Frame(result) => result,
+                Received::Ended => break,
+                Received::Idle => {
+                    // Half-open connection: the client stopped answering pings
+                    tracing::info!("Closing idle WebSocket for user {}", user_id);
+                    let _ = close_tx.send(CloseFrame {
+                        code: close_code::AWAY,
+                        reason: "idle timeout".into(),
+                    });
+                    break;
+                }
+            };
             match result {
-                Ok(Message::Text(text)) => {
-                    handle_client_message(
-                        &text,
-                        user_id,
-                        &user_name,
-                        &state_clone,
+                Ok(Message::Text(text)) if text.len() > max_frame_bytes => {
+                    reject_frame(
+                        &tx_clone,
                         &ws_manager_clone,
+                        user_id,
+                        "FRAME_TOO_LARGE",
+                        format!("Frame exceeds {} bytes", max_frame_bytes),
+                    );
+                    let _ = close_tx.send(CloseFrame {
+                        code: close_code::SIZE,
+                        reason: "frame too large".into(),
+                    });
+                    break;
+                }
+                Ok(Message::Text(text)) => {
+                    activity.touch();
+                    let request_id = request_id::from_frame(&text);
+                    request_id::scope(
+                        request_id,
+                        handle_client_message(
+                            &text,
+                            user_id,
+                            &user_name,
+                            &state_clone,
+                            &ws_manager_clone,
+                            &tx_clone,
+                        ),
                     )
                     .await;
                 }
@@ -305,7 +517,18 @@ async fn handle_socket(
                     break;
                 }
                 Ok(Message::Binary(_)) => {
-                    tracing::warn!("Received unexpected binary message from user {}", user_id);
+                    reject_frame(
+                        &tx_clone,
+                        &ws_manager_clone,
+                        user_id,
+                        "PROTOCOL_ERROR",
+                        "Binary frames are not supported".to_string(),
+                    );
+                    let _ = close_tx.send(CloseFrame {
+                        code: close_code::PROTOCOL,
+                        reason: "binary frames are not supported".into(),
+                    });
+                    break;
                 }
                 Err(e) => {
                     tracing::error!("WebSocket error for user {}: {}", user_id, e);
@@ -321,11 +544,15 @@ async fn handle_socket(
         _ = recv_task => {},
     }
 
+    // Invisible users already appear offline to everyone else
+    let was_visible = ws_manager.presence_of(user_id).await.visible() != PresenceState::Offline;
+
     // Cleanup: remove client and update status
     ws_manager.remove_client(user_id, &tx).await;
+    unregister_connection(&state, connection_id).await;
 
     // Check if user has no more connections
-    if !ws_manager.is_user_online(user_id).await {
+    if was_visible && !ws_manager.is_user_online(user_id).await {
         // Update user status to offline
         if let Err(e) = update_user_status(&state, user_id, "offline").await {
             tracing::error!("Failed to update user status: {}", e);
@@ -333,15 +560,66 @@ async fn handle_socket(
 
         // Broadcast user offline status
         let last_seen = chrono::Utc::now();
-        let status_event = ServerEvent::UserStatus {
-            user_id,
-            status: "offline".to_string(),
-            last_seen: Some(last_seen),
-        };
-        ws_manager.broadcast_user_status(status_event).await;
+        WebSocketService::broadcast_user_status(&state, user_id, "offline", Some(last_seen)).await;
+    }
+}
+
+/// Drop a WebSocket connection from its user's connection count
+async fn unregister_connection(state: &AppState, connection_id: Uuid) {
+    // Not found when it was closed to make room for a newer connection
+    let _ = state
+        .connection_manager
+        .unregister_connection(ConnectionId::from_uuid(connection_id))
+        .await;
+}
+
+/// Serialize and send one event to the client
+async fn send_event<S>(ws_sender: &mut S, event: &ServerEvent) -> Result<(), S::Error>
+where
+    S: Sink<Message> + Unpin,
+{
+    match serde_json::to_string(event) {
+        Ok(json) => ws_sender.send(Message::Text(json)).await,
+        Err(e) => {
+            tracing::error!("Failed to serialize event: {}", e);
+            Ok(())
+        }
     }
 }
 
+/// Send a frame produced by the batcher, if any
+async fn send_frame<S>(ws_sender: &mut S, frame: Option<ServerEvent>) -> Result<(), S::Error>
+where
+    S: Sink<Message> + Unpin,
+{
+    match frame {
+        Some(event) => send_event(ws_sender, &event).await,
+        None => Ok(()),
+    }
+}
+
+/// Tell the client why its frame was rejected and count it; the caller then
+/// closes the connection.
+fn reject_frame(
+    tx: &mpsc::UnboundedSender<ServerEvent>,
+    ws_manager: &WsManager,
+    user_id: Uuid,
+    code: &str,
+    message: String,
+) {
+    ws_manager.record_rejected_frame();
+    tracing::warn!(
+        "Closing WebSocket for user {}: {} ({} frames rejected so far)",
+        user_id,
+        message,
+        ws_manager.rejected_frame_count()
+    );
+    let _ = tx.send(ServerEvent::Error {
+        code: code.to_string(),
+        message,
+    });
+}
+
 /// Handle incoming client messages
 async fn handle_client_message(
     text: &str,
@@ -349,6 +627,7 @@ async fn handle_client_message(
     user_name: &str,
     state: &Arc<AppState>,
     ws_manager: &Arc<WsManager>,
+    tx: &mpsc::UnboundedSender<ServerEvent>,
 ) {
     tracing::debug!("Received message: {}", text);
     let event: ClientEvent = match serde_json::from_str(text) {
@@ -366,14 +645,8 @@ async fn handle_client_message(
                 return;
             }
 
-            let typing_event = ServerEvent::Typing {
-                chat_id,
-                user_id,
-                user_name: user_name.to_string(),
-                is_typing: true,
-            };
             ws_manager
-                .broadcast_to_room(chat_id, typing_event, Some(user_id))
+                .start_typing(chat_id, user_id, user_name.to_string())
                 .await;
         }
         ClientEvent::StopTyping { chat_id } => {
@@ -382,14 +655,8 @@ async fn handle_client_message(
                 return;
             }
 
-            let typing_event = ServerEvent::Typing {
-                chat_id,
-                user_id,
-                user_name: user_name.to_string(),
-                is_typing: false,
-            };
             ws_manager
-                .broadcast_to_room(chat_id, typing_event, Some(user_id))
+                .stop_typing(chat_id, user_id, user_name.to_string())
                 .await;
         }
         ClientEvent::JoinChat { chat_id } => {
@@ -402,10 +669,45 @@ async fn handle_client_message(
         ClientEvent::LeaveChat { chat_id } => {
             ws_manager.leave_room(user_id, chat_id).await;
         }
+        ClientEvent::SendMessage {
+            chat_id,
+            content,
+            client_msg_id,
+            reply_to,
+        } => {
+            // Acknowledged on this connection only
+            let ack = WebSocketService::send_client_message(
+                state,
+                user_id,
+                chat_id,
+                content,
+                client_msg_id,
+                reply_to,
+            )
+            .await;
+            let _ = tx.send(ack);
+        }
+        ClientEvent::MessagesDelivered { message_ids } => {
+            WebSocketService::mark_delivered(state, user_id, &message_ids).await;
+        }
         ClientEvent::Ping => {
             // Client ping - no action needed, connection is alive
             tracing::debug!("Received ping from user {}", user_id);
         }
+        ClientEvent::SetPresence { status } => {
+            if let Err(e) = WebSocketService::set_presence(state, user_id, status).await {
+                let error = ServerEvent::Error {
+                    code: e.status_and_code().1.to_string(),
+                    message: e.to_string(),
+                };
+                ws_manager.send_to_user(user_id, error).await;
+            }
+        }
+        ClientEvent::SetIdle { idle } => {
+            if let Err(e) = WebSocketService::set_idle(state, user_id, idle).await {
+                tracing::error!("Failed to update idle state for user {}: {}", user_id, e);
+            }
+        }
         ClientEvent::InitiateCall {
             target_user_id,
             chat_id,
@@ -426,15 +728,15 @@ async fn handle_client_message(
             handle_accept_call(user_id, call_id, state, ws_manager).await;
         }
         ClientEvent::DeclineCall { call_id } => {
-            handle_decline_call(user_id, call_id, ws_manager).await;
+            handle_decline_call(user_id, call_id, state, ws_manager).await;
         }
         ClientEvent::EndCall { call_id } => {
-            handle_end_call(user_id, call_id, ws_manager).await;
+            handle_end_call(user_id, call_id, state, ws_manager).await;
         }
     }
 }
 
-/// Update user online/offline status in database
+/// Update user status (online/away/busy/offline) in database
 async fn update_user_status(
     state: &Arc<AppState>,
     user_id: Uuid,
@@ -459,7 +761,13 @@ async fn update_user_status(
 /// Get all chat IDs for a user
 async fn get_user_chat_ids(state: &Arc<AppState>, user_id: Uuid) -> Result<Vec<Uuid>, sqlx::Error> {
     let chat_ids: Vec<(Uuid,)> =
-        sqlx::query_as("SELECT chat_id FROM chat_participants WHERE user_id = $1")
+        sqlx::query_as(
+            r#"
+            SELECT cp.chat_id FROM chat_participants cp
+            JOIN chats c ON c.id = cp.chat_id
+            WHERE cp.user_id = $1 AND c.deleted_at IS NULL
+            "#,
+        )
             .bind(user_id)
             .fetch_all(&state.db.pool)
             .await?;
@@ -476,7 +784,11 @@ async fn is_chat_participant(state: &Arc<AppState>, chat_id: Uuid, user_id: Uuid
     );
 
     let query_result = sqlx::query_as::<_, (i32,)>(
-        "SELECT 1 FROM chat_participants WHERE chat_id = $1 AND user_id = $2",
+        r#"
+        SELECT 1 FROM chat_participants cp
+        JOIN chats c ON c.id = cp.chat_id
+        WHERE cp.chat_id = $1 AND cp.user_id = $2 AND c.deleted_at IS NULL
+        "#,
     )
     .bind(chat_id)
     .bind(user_id)
@@ -581,6 +893,7 @@ async fn handle_initiate_call(
             call_id: session.call_id,
         };
         ws_manager.send_to_user(caller_id, busy_event).await;
+        CallLogService::record_started(&state.db, &session, CallOutcome::Busy).await;
 
         // Clean up the temporary session
         ws_manager.end_call(session.call_id).await;
@@ -592,6 +905,9 @@ async fn handle_initiate_call(
         .create_call_session(caller_id, target_user_id, chat_id, call_type.clone())
         .await;
 
+    CallLogService::record_started(&state.db, &session, CallOutcome::Ringing).await;
+    CallLogService::spawn_ring_timeout(state.clone(), session.call_id);
+
     // Get caller avatar
     let caller_avatar = ws_manager.get_user_avatar(caller_id).await;
 
@@ -662,6 +978,7 @@ async fn handle_accept_call(
             return;
         }
     };
+    CallLogService::record_accepted(&state.db, call_id).await;
 
     // Get mediasoup URL from config
     let mediasoup_url = state.config.mediasoup_url.clone();
@@ -688,7 +1005,12 @@ async fn handle_accept_call(
 }
 
 /// Handle DeclineCall event
-async fn handle_decline_call(user_id: Uuid, call_id: Uuid, ws_manager: &Arc<WsManager>) {
+async fn handle_decline_call(
+    user_id: Uuid,
+    call_id: Uuid,
+    state: &Arc<AppState>,
+    ws_manager: &Arc<WsManager>,
+) {
     // Get the call session
     let session = match ws_manager.get_call_session(call_id).await {
         Some(s) => s,
@@ -714,6 +1036,7 @@ async fn handle_decline_call(user_id: Uuid, call_id: Uuid, ws_manager: &Arc<WsMa
 
     // End the call
     ws_manager.end_call(call_id).await;
+    CallLogService::record_ended(&state.db, call_id, CallOutcome::Declined).await;
 
     // Send CallDeclined to caller
     let declined_event = ServerEvent::CallDeclined { call_id };
@@ -725,7 +1048,12 @@ async fn handle_decline_call(user_id: Uuid, call_id: Uuid, ws_manager: &Arc<WsMa
 }
 
 /// Handle EndCall event
-async fn handle_end_call(user_id: Uuid, call_id: Uuid, ws_manager: &Arc<WsManager>) {
+async fn handle_end_call(
+    user_id: Uuid,
+    call_id: Uuid,
+    state: &Arc<AppState>,
+    ws_manager: &Arc<WsManager>,
+) {
     // Get the call session
     let session = match ws_manager.get_call_session(call_id).await {
         Some(s) => s,
@@ -751,6 +1079,8 @@ async fn handle_end_call(user_id: Uuid, call_id: Uuid, ws_manager: &Arc<WsManage
 
     // End the call
     ws_manager.end_call(call_id).await;
+    let outcome = CallLogService::outcome_on_hangup(&session);
+    CallLogService::record_ended(&state.db, call_id, outcome).await;
 
     // Send CallEnded to both parties
     let ended_event = ServerEvent::CallEnded {
@@ -764,6 +1094,9 @@ async fn handle_end_call(user_id: Uuid, call_id: Uuid, ws_manager: &Arc<WsManage
     ws_manager
         .send_to_user(session.callee_id, ended_event)
         .await;
+    if outcome == CallOutcome::Missed {
+        CallLogService::notify_missed(state, &session).await;
+    }
 
     tracing::info!("Call ended: call_id={}", call_id);
 }
@@ -0,0 +1,126 @@
+/// WebSocket heartbeat - detects half-open connections.
+///
+/// The server pings every `WS_KEEPALIVE_SECS`; any frame from the client,
+/// the pong included, counts as traffic. A connection that stays silent for
+/// `WS_IDLE_TIMEOUT_SECS` is closed, which runs the normal disconnect path
+/// (including the offline transition). Either interval can be 0 to disable it.
+use futures::{Stream, StreamExt};
+use std::time::Duration;
+use tokio::time::{Instant, Interval, MissedTickBehavior};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Heartbeat {
+    keepalive: Option<Duration>,
+    idle_timeout: Option<Duration>,
+}
+
+/// What `Heartbeat::next_frame` got from the client
+#[derive(Debug, PartialEq)]
+pub enum Received<T> {
+    Frame(T),
+    /// The stream ended
+    Ended,
+    /// Nothing arrived within the idle timeout
+    Idle,
+}
+
+impl Heartbeat {
+    pub fn new(keepalive: Duration, idle_timeout: Duration) -> Self {
+        Self {
+            keepalive: (!keepalive.is_zero()).then_some(keepalive),
+            idle_timeout: (!idle_timeout.is_zero()).then_some(idle_timeout),
+        }
+    }
+
+    pub fn from_secs(keepalive_secs: u64, idle_timeout_secs: u64) -> Self {
+        Self::new(
+            Duration::from_secs(keepalive_secs),
+            Duration::from_secs(idle_timeout_secs),
+        )
+    }
+
+    /// Timer for server pings, first firing one interval from now; `None`
+    /// when keep-alive is disabled
+    pub fn ping_timer(&self) -> Option<Interval> {
+        self.keepalive.map(|period| {
+            let mut timer = tokio::time::interval_at(Instant::now() + period, period);
+            timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            timer
+        })
+    }
+
+    /// Wait for the client's next frame, giving up after the idle timeout
+    pub async fn next_frame<S>(&self, stream: &mut S) -> Received<S::Item>
+    where
+        S: Stream + Unpin,
+    {
+        let next = match self.idle_timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, stream.next()).await {
+                Ok(next) => next,
+                Err(_) => return Received::Idle,
+            },
+            None => stream.next().await,
+        };
+
+        match next {
+            Some(frame) => Received::Frame(frame),
+            None => Received::Ended,
+        }
+    }
+}
+
+/// Wait for the next ping; never completes without a timer
+pub async fn tick(timer: &mut Option<Interval>) {
+    match timer {
+        Some(timer) => {
+            timer.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+
+    const SHORT: Duration = Duration::from_millis(50);
+
+    #[tokio::test]
+    async fn test_stalled_socket_times_out() {
+        let heartbeat = Heartbeat::new(Duration::ZERO, SHORT);
+        // One frame, then a client that never sends anything again
+        let mut socket = stream::iter(vec!["hello"]).chain(stream::pending());
+
+        assert_eq!(heartbeat.next_frame(&mut socket).await, Received::Frame("hello"));
+        assert_eq!(heartbeat.next_frame(&mut socket).await, Received::Idle);
+    }
+
+    #[tokio::test]
+    async fn test_closed_socket_ends() {
+        let heartbeat = Heartbeat::new(Duration::ZERO, SHORT);
+        let mut socket = stream::iter(Vec::<&str>::new());
+
+        assert_eq!(heartbeat.next_frame(&mut socket).await, Received::Ended);
+    }
+
+    #[tokio::test]
+    async fn test_disabled_idle_timeout_waits() {
+        let heartbeat = Heartbeat::new(Duration::ZERO, Duration::ZERO);
+        let mut socket = stream::pending::<&str>();
+
+        let waited = tokio::time::timeout(SHORT * 2, heartbeat.next_frame(&mut socket)).await;
+        assert!(waited.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ping_timer() {
+        let mut timer = Heartbeat::new(SHORT, Duration::ZERO).ping_timer();
+        assert!(timer.is_some());
+        assert!(tokio::time::timeout(SHORT * 4, tick(&mut timer)).await.is_ok());
+
+        let mut disabled = Heartbeat::new(Duration::ZERO, SHORT).ping_timer();
+        assert!(disabled.is_none());
+        assert!(tokio::time::timeout(SHORT, tick(&mut disabled)).await.is_err());
+    }
+}
@@ -1,8 +1,18 @@
 use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
 use tokio::sync::{mpsc, RwLock};
+use tokio::task::AbortHandle;
 use uuid::Uuid;
 
+use crate::config::PresenceScope;
+use crate::db::Database;
+use crate::error::AppResult;
+use crate::models::{PresenceState, VisibleLastSeen};
+use crate::services::user::resolve_last_seen;
+
 use super::events::{BotServerEvent, ServerEvent};
 
 /// Represents a connected WebSocket client
@@ -11,6 +21,45 @@ pub struct Client {
     pub user_id: Uuid,
     pub user_name: String,
     pub sender: mpsc::UnboundedSender<ServerEvent>,
+    /// Identifies this connection in the user's connection list
+    pub connection_id: Uuid,
+    /// Login session the client authenticated with, if its token carried one
+    pub session_id: Option<Uuid>,
+    pub connected_at: DateTime<Utc>,
+    /// Unix milliseconds of the last frame received, shared by all clones
+    last_activity_ms: Arc<AtomicI64>,
+}
+
+impl Client {
+    pub fn new(user_id: Uuid, user_name: String, sender: mpsc::UnboundedSender<ServerEvent>) -> Self {
+        let now = Utc::now();
+        Self {
+            user_id,
+            user_name,
+            sender,
+            connection_id: Uuid::new_v4(),
+            session_id: None,
+            connected_at: now,
+            last_activity_ms: Arc::new(AtomicI64::new(now.timestamp_millis())),
+        }
+    }
+
+    /// Bind the client to the login session its token was issued for
+    pub fn with_session(mut self, session_id: Option<Uuid>) -> Self {
+        self.session_id = session_id;
+        self
+    }
+
+    /// Record a frame from the client
+    pub fn touch(&self) {
+        self.last_activity_ms
+            .store(Utc::now().timestamp_millis(), Ordering::Relaxed);
+    }
+
+    pub fn last_activity(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp_millis(self.last_activity_ms.load(Ordering::Relaxed))
+            .unwrap_or(self.connected_at)
+    }
 }
 
 /// Represents a connected bot WebSocket client
@@ -21,6 +70,15 @@ pub struct BotClient {
     pub sender: mpsc::UnboundedSender<BotServerEvent>,
 }
 
+/// Whether a bot has a live WebSocket connection and when it was last seen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BotConnectionStatus {
+    pub connected: bool,
+    /// Now while connected, otherwise when its last connection dropped;
+    /// `None` if it hasn't connected since the server started
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
 /// Represents an active call session
 #[derive(Debug, Clone)]
 pub struct CallSession {
@@ -34,7 +92,38 @@ pub struct CallSession {
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
+/// Presence of a connected user: the state they picked plus whether their
+/// client reported them idle
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UserPresence {
+    pub chosen: PresenceState,
+    pub idle: bool,
+}
+
+impl UserPresence {
+    /// Idle users who picked `Online` show as `Away`; explicit states win
+    pub fn effective(self) -> PresenceState {
+        if self.idle && self.chosen == PresenceState::Online {
+            PresenceState::Away
+        } else {
+            self.chosen
+        }
+    }
+}
+
 /// Call state
+/// How long a typing indicator lasts unless refreshed by another StartTyping
+pub const TYPING_TIMEOUT: Duration = Duration::from_secs(8);
+
+/// Pending automatic stop of a user's typing indicator in a chat
+#[derive(Debug)]
+struct TypingTimer {
+    /// Distinguishes this timer from the ones it replaced
+    generation: u64,
+    user_name: String,
+    expiry: AbortHandle,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum CallState {
     Pending,
@@ -42,6 +131,61 @@ pub enum CallState {
     Ended,
 }
 
+/// The users told about one user's presence changes
+#[derive(Debug, Clone, Default)]
+pub struct PresenceAudience {
+    /// Users in the presence scope; `None` means every connected user
+    recipients: Option<HashSet<Uuid>>,
+    /// The user's `last_seen_visibility` setting (`None` = everyone)
+    last_seen_visibility: Option<String>,
+    /// Users in the user's contacts
+    contacts: HashSet<Uuid>,
+}
+
+impl PresenceAudience {
+    /// Every connected user, all allowed to see the last-seen time
+    pub fn everyone() -> Self {
+        Self::default()
+    }
+
+    /// Nobody but the user's own connections
+    pub fn nobody() -> Self {
+        Self {
+            recipients: Some(HashSet::new()),
+            ..Self::default()
+        }
+    }
+
+    /// Whether `viewer` hears about the user's presence
+    pub fn reaches(&self, viewer: Uuid) -> bool {
+        self.recipients
+            .as_ref()
+            .is_none_or(|recipients| recipients.contains(&viewer))
+    }
+
+    /// `last_seen` as the user's privacy setting shows it to `viewer`
+    pub fn visible_last_seen(
+        &self,
+        viewer: Uuid,
+        last_seen: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Option<VisibleLastSeen> {
+        resolve_last_seen(
+            last_seen,
+            self.last_seen_visibility.as_deref(),
+            false,
+            self.contacts.contains(&viewer),
+            now,
+        )
+    }
+
+    /// `last_seen` if the user's privacy setting shows `viewer` the exact time
+    fn last_seen_for(&self, viewer: Uuid, last_seen: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+        self.visible_last_seen(viewer, last_seen, Utc::now())
+            .and_then(VisibleLastSeen::exact)
+    }
+}
+
 /// Manages WebSocket connections and room subscriptions
 #[derive(Debug, Default)]
 pub struct WsManager {
@@ -57,6 +201,15 @@ pub struct WsManager {
     user_calls: RwLock<HashMap<Uuid, Uuid>>,
     /// Map of bot_id to their connected bot clients (supports multiple connections per bot)
     bot_clients: RwLock<HashMap<Uuid, Vec<BotClient>>>,
+    /// When each bot's last WebSocket connection dropped
+    bot_last_seen: RwLock<HashMap<Uuid, DateTime<Utc>>>,
+    /// Client frames rejected for being oversized or binary
+    rejected_frames: AtomicU64,
+    /// Presence of connected users; users without an entry are plain online
+    presence: RwLock<HashMap<Uuid, UserPresence>>,
+    /// Active typing indicators by (chat_id, user_id)
+    typing: RwLock<HashMap<(Uuid, Uuid), TypingTimer>>,
+    typing_generation: AtomicU64,
 }
 
 impl WsManager {
@@ -68,9 +221,24 @@ impl WsManager {
             active_calls: RwLock::new(HashMap::new()),
             user_calls: RwLock::new(HashMap::new()),
             bot_clients: RwLock::new(HashMap::new()),
+            bot_last_seen: RwLock::new(HashMap::new()),
+            rejected_frames: AtomicU64::new(0),
+            presence: RwLock::new(HashMap::new()),
+            typing: RwLock::new(HashMap::new()),
+            typing_generation: AtomicU64::new(0),
         })
     }
 
+    /// Count a client frame rejected for being oversized or malformed
+    pub fn record_rejected_frame(&self) {
+        self.rejected_frames.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Total client frames rejected since startup
+    pub fn rejected_frame_count(&self) -> u64 {
+        self.rejected_frames.load(Ordering::Relaxed)
+    }
+
     /// Register a new client connection
     pub async fn add_client(&self, client: Client) {
         let user_id = client.user_id;
@@ -89,8 +257,10 @@ impl WsManager {
             }
         }
 
-        // Clean up room subscriptions if no more connections
+        // Clean up presence and room subscriptions if no more connections
         if !clients.contains_key(&user_id) {
+            self.presence.write().await.remove(&user_id);
+
             let mut user_rooms = self.user_rooms.write().await;
             if let Some(rooms) = user_rooms.remove(&user_id) {
                 let mut rooms_map = self.rooms.write().await;
@@ -104,6 +274,10 @@ impl WsManager {
                 }
             }
         }
+        drop(clients);
+
+        // The dropped connection can no longer send StopTyping itself
+        self.clear_typing(user_id).await;
 
         tracing::info!("Client disconnected: user_id={}", user_id);
     }
@@ -137,8 +311,177 @@ impl WsManager {
         tracing::debug!("User {} left room {}", user_id, chat_id);
     }
 
+    /// Broadcast that a user is typing in a chat
+    ///
+    /// The indicator is cleared automatically after `TYPING_TIMEOUT` unless
+    /// another start refreshes it first.
+    pub async fn start_typing(self: &Arc<Self>, chat_id: Uuid, user_id: Uuid, user_name: String) {
+        self.start_typing_for(chat_id, user_id, user_name, TYPING_TIMEOUT)
+            .await;
+    }
+
+    async fn start_typing_for(
+        self: &Arc<Self>,
+        chat_id: Uuid,
+        user_id: Uuid,
+        user_name: String,
+        timeout: Duration,
+    ) {
+        let generation = self.typing_generation.fetch_add(1, Ordering::Relaxed);
+        let manager = Arc::downgrade(self);
+
+        {
+            // Held while spawning so the expiry can't run before it is recorded
+            let mut typing = self.typing.write().await;
+            let expiry = tokio::spawn(async move {
+                tokio::time::sleep(timeout).await;
+                if let Some(manager) = manager.upgrade() {
+                    manager.expire_typing(chat_id, user_id, generation).await;
+                }
+            });
+            let timer = TypingTimer {
+                generation,
+                user_name: user_name.clone(),
+                expiry: expiry.abort_handle(),
+            };
+            if let Some(previous) = typing.insert((chat_id, user_id), timer) {
+                previous.expiry.abort();
+            }
+        }
+
+        self.broadcast_typing(chat_id, user_id, user_name, true).await;
+    }
+
+    /// Broadcast that a user stopped typing in a chat
+    pub async fn stop_typing(&self, chat_id: Uuid, user_id: Uuid, user_name: String) {
+        if let Some(timer) = self.typing.write().await.remove(&(chat_id, user_id)) {
+            timer.expiry.abort();
+        }
+        self.broadcast_typing(chat_id, user_id, user_name, false).await;
+    }
+
+    /// Stop every typing indicator of a user, e.g. when a connection drops
+    pub async fn clear_typing(&self, user_id: Uuid) {
+        let stopped: Vec<(Uuid, TypingTimer)> = {
+            let mut typing = self.typing.write().await;
+            let keys: Vec<(Uuid, Uuid)> = typing
+                .keys()
+                .filter(|(_, typing_user)| *typing_user == user_id)
+                .copied()
+                .collect();
+            keys.into_iter()
+                .filter_map(|key| typing.remove(&key).map(|timer| (key.0, timer)))
+                .collect()
+        };
+
+        for (chat_id, timer) in stopped {
+            timer.expiry.abort();
+            self.broadcast_typing(chat_id, user_id, timer.user_name, false)
+                .await;
+        }
+    }
+
+    /// Timeout of a typing indicator, unless it was refreshed or stopped since
+    async fn expire_typing(&self, chat_id: Uuid, user_id: Uuid, generation: u64) {
+        let timer = {
+            let mut typing = self.typing.write().await;
+            match typing.get(&(chat_id, user_id)) {
+                Some(timer) if timer.generation == generation => {
+                    typing.remove(&(chat_id, user_id))
+                }
+                _ => None,
+            }
+        };
+
+        if let Some(timer) = timer {
+            tracing::debug!("Typing indicator of user {} in chat {} expired", user_id, chat_id);
+            self.broadcast_typing(chat_id, user_id, timer.user_name, false)
+                .await;
+        }
+    }
+
+    async fn broadcast_typing(&self, chat_id: Uuid, user_id: Uuid, user_name: String, is_typing: bool) {
+        let event = ServerEvent::Typing {
+            chat_id,
+            user_id,
+            user_name,
+            is_typing,
+        };
+        self.broadcast_to_room(chat_id, event, Some(user_id)).await;
+    }
+
 
     /// Send event to a specific user (all their connections)
+    /// Tell every WebSocket client of a user to close, returning how many were signalled
+    ///
+    /// Each client's send task forwards the `session_terminated` event, closes
+    /// the socket and unregisters itself.
+    pub async fn disconnect_user(&self, user_id: Uuid, reason: &str) -> usize {
+        let clients = self.clients.read().await;
+        let Some(user_clients) = clients.get(&user_id) else {
+            return 0;
+        };
+
+        user_clients
+            .iter()
+            .filter(|client| {
+                client
+                    .sender
+                    .send(ServerEvent::SessionTerminated {
+                        reason: reason.to_string(),
+                    })
+                    .is_ok()
+            })
+            .count()
+    }
+
+    /// Tell one WebSocket client of a user to close
+    ///
+    /// Returns `false` if `connection_id` is not one of the user's clients.
+    pub async fn disconnect_client(&self, user_id: Uuid, connection_id: Uuid, reason: &str) -> bool {
+        let clients = self.clients.read().await;
+        clients
+            .get(&user_id)
+            .into_iter()
+            .flatten()
+            .find(|client| client.connection_id == connection_id)
+            .is_some_and(|client| {
+                client
+                    .sender
+                    .send(ServerEvent::SessionTerminated {
+                        reason: reason.to_string(),
+                    })
+                    .is_ok()
+            })
+    }
+
+    /// Tell every WebSocket client authenticated with a login session to close
+    ///
+    /// Returns how many clients were told.
+    pub async fn disconnect_session(&self, user_id: Uuid, session_id: Uuid, reason: &str) -> usize {
+        let clients = self.clients.read().await;
+        clients
+            .get(&user_id)
+            .into_iter()
+            .flatten()
+            .filter(|client| client.session_id == Some(session_id))
+            .filter(|client| {
+                client
+                    .sender
+                    .send(ServerEvent::SessionTerminated {
+                        reason: reason.to_string(),
+                    })
+                    .is_ok()
+            })
+            .count()
+    }
+
+    /// The user's connected WebSocket clients
+    pub async fn user_clients(&self, user_id: Uuid) -> Vec<Client> {
+        let clients = self.clients.read().await;
+        clients.get(&user_id).cloned().unwrap_or_default()
+    }
+
     pub async fn send_to_user(&self, user_id: Uuid, event: ServerEvent) {
         let clients = self.clients.read().await;
         if let Some(user_clients) = clients.get(&user_id) {
@@ -150,6 +493,20 @@ impl WsManager {
         }
     }
 
+    /// Send an event to one WebSocket client of a user
+    ///
+    /// Returns `false` if `connection_id` is not one of the user's clients or
+    /// its socket is gone.
+    pub async fn send_to_client(&self, user_id: Uuid, connection_id: Uuid, event: ServerEvent) -> bool {
+        let clients = self.clients.read().await;
+        clients
+            .get(&user_id)
+            .into_iter()
+            .flatten()
+            .find(|client| client.connection_id == connection_id)
+            .is_some_and(|client| client.sender.send(event).is_ok())
+    }
+
     /// Send event to all users in a chat room
     pub async fn broadcast_to_room(&self, chat_id: Uuid, event: ServerEvent, exclude_user: Option<Uuid>) {
         let rooms = self.rooms.read().await;
@@ -190,16 +547,88 @@ impl WsManager {
         }
     }
 
-    /// Broadcast user status change to all connected clients
-    pub async fn broadcast_user_status(&self, event: ServerEvent) {
+    /// Look up who is told about `user_id`'s presence changes under `scope`,
+    /// along with the user's last-seen privacy setting
+    pub async fn presence_audience(
+        db: &Database,
+        user_id: Uuid,
+        scope: PresenceScope,
+    ) -> AppResult<PresenceAudience> {
+        let contacts: Vec<Uuid> =
+            sqlx::query_scalar("SELECT contact_id FROM contacts WHERE owner_id = $1")
+                .bind(user_id)
+                .fetch_all(&db.pool)
+                .await?;
+        let contacts: HashSet<Uuid> = contacts.into_iter().collect();
+
+        let last_seen_visibility: Option<String> = sqlx::query_scalar(
+            "SELECT last_seen_visibility FROM user_settings WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(&db.pool)
+        .await?;
+
+        let recipients = match scope {
+            PresenceScope::All => None,
+            PresenceScope::Contacts => Some(contacts.clone()),
+            PresenceScope::SharedChats => {
+                let partners: Vec<Uuid> = sqlx::query_scalar(
+                    r#"
+                    SELECT DISTINCT cp.user_id
+                    FROM chat_participants me
+                    JOIN chats c ON c.id = me.chat_id AND c.deleted_at IS NULL
+                    JOIN chat_participants cp ON cp.chat_id = me.chat_id AND cp.user_id <> me.user_id
+                    WHERE me.user_id = $1
+                    "#,
+                )
+                .bind(user_id)
+                .fetch_all(&db.pool)
+                .await?;
+                Some(partners.into_iter().collect())
+            }
+        };
+
+        Ok(PresenceAudience {
+            recipients,
+            last_seen_visibility,
+            contacts,
+        })
+    }
+
+    /// Broadcast a user's status change to their connections and `audience`
+    pub async fn broadcast_user_status(
+        &self,
+        user_id: Uuid,
+        status: &str,
+        last_seen: Option<DateTime<Utc>>,
+        audience: &PresenceAudience,
+    ) {
+        let own = ServerEvent::UserStatus {
+            user_id,
+            status: status.to_string(),
+            last_seen,
+        };
+        self.broadcast_presence(user_id, own, status, last_seen, audience)
+            .await;
+    }
+
+    /// Send event to every connected user, returning how many were reached
+    pub async fn broadcast_to_all(&self, event: ServerEvent) -> usize {
         let clients = self.clients.read().await;
-        for (_, user_clients) in clients.iter() {
+        let mut reached = 0;
+        for (user_id, user_clients) in clients.iter() {
+            let mut sent = false;
             for client in user_clients {
-                if let Err(e) = client.sender.send(event.clone()) {
-                    tracing::warn!("Failed to broadcast status: {}", e);
+                match client.sender.send(event.clone()) {
+                    Ok(()) => sent = true,
+                    Err(e) => tracing::warn!("Failed to send to user {}: {}", user_id, e),
                 }
             }
+            if sent {
+                reached += 1;
+            }
         }
+        reached
     }
 
     /// Check if a user is currently online (has active connections)
@@ -214,6 +643,80 @@ impl WsManager {
         clients.keys().cloned().collect()
     }
 
+    /// Real presence of a user (`Offline` when not connected)
+    pub async fn presence_of(&self, user_id: Uuid) -> PresenceState {
+        let clients = self.clients.read().await;
+        if !clients.contains_key(&user_id) {
+            return PresenceState::Offline;
+        }
+        let presence = self.presence.read().await;
+        presence.get(&user_id).copied().unwrap_or_default().effective()
+    }
+
+    /// Presence of every connected user as other users see it
+    pub async fn visible_presences(&self) -> HashMap<Uuid, PresenceState> {
+        let clients = self.clients.read().await;
+        let presence = self.presence.read().await;
+        clients
+            .keys()
+            .map(|user_id| {
+                let state = presence.get(user_id).copied().unwrap_or_default().effective();
+                (*user_id, state.visible())
+            })
+            .collect()
+    }
+
+    /// Set the presence a user picked; returns their new effective presence
+    pub async fn set_presence(&self, user_id: Uuid, chosen: PresenceState) -> PresenceState {
+        let mut presence = self.presence.write().await;
+        let entry = presence.entry(user_id).or_default();
+        entry.chosen = chosen;
+        entry.effective()
+    }
+
+    /// Record a client-reported idle/active transition; returns the new
+    /// effective presence
+    pub async fn set_idle(&self, user_id: Uuid, idle: bool) -> PresenceState {
+        let mut presence = self.presence.write().await;
+        let entry = presence.entry(user_id).or_default();
+        entry.idle = idle;
+        entry.effective()
+    }
+
+    /// Send a presence change: the user's own connections get `own` (their
+    /// real state), connected users in `audience` get the public `status`
+    ///
+    /// `last_seen` is only passed on to viewers the user's last-seen privacy
+    /// setting shows the exact time.
+    pub async fn broadcast_presence(
+        &self,
+        user_id: Uuid,
+        own: ServerEvent,
+        status: &str,
+        last_seen: Option<DateTime<Utc>>,
+        audience: &PresenceAudience,
+    ) {
+        let clients = self.clients.read().await;
+        for (id, user_clients) in clients.iter() {
+            let event = if *id == user_id {
+                own.clone()
+            } else if audience.reaches(*id) {
+                ServerEvent::UserStatus {
+                    user_id,
+                    status: status.to_string(),
+                    last_seen: audience.last_seen_for(*id, last_seen),
+                }
+            } else {
+                continue;
+            };
+            for client in user_clients {
+                if let Err(e) = client.sender.send(event.clone()) {
+                    tracing::warn!("Failed to broadcast status: {}", e);
+                }
+            }
+        }
+    }
+
     /// Get user name for a connected user
     pub async fn get_user_name(&self, user_id: Uuid) -> Option<String> {
         let clients = self.clients.read().await;
@@ -299,6 +802,32 @@ impl WsManager {
         }
     }
 
+    /// End a call only if it is still ringing
+    ///
+    /// Used by the ring timeout so a call accepted at the last moment is not
+    /// torn down; the state check and removal happen under one lock.
+    pub async fn end_call_if_pending(&self, call_id: Uuid) -> Option<CallSession> {
+        let mut active_calls = self.active_calls.write().await;
+        if active_calls.get(&call_id)?.state != CallState::Pending {
+            return None;
+        }
+        let mut session = active_calls.remove(&call_id)?;
+        session.state = CallState::Ended;
+        drop(active_calls);
+
+        let mut user_calls = self.user_calls.write().await;
+        user_calls.remove(&session.caller_id);
+        user_calls.remove(&session.callee_id);
+
+        tracing::info!("Unanswered call ended: call_id={}", call_id);
+        Some(session)
+    }
+
+    /// Number of calls that are ringing or in progress
+    pub async fn active_call_count(&self) -> usize {
+        self.active_calls.read().await.len()
+    }
+
     /// Check if a user is currently in a call
     pub async fn is_user_in_call(&self, user_id: Uuid) -> bool {
         let user_calls = self.user_calls.read().await;
@@ -331,13 +860,18 @@ impl WsManager {
     /// # Arguments
     /// * `client` - The bot client to register
     ///
+    /// # Returns
+    /// * `bool` - True if this is the bot's only connection, i.e. it just came online
+    ///
     /// # Requirements
     /// - 9.1: Authenticate and establish bot WebSocket connection
-    pub async fn add_bot_client(&self, client: BotClient) {
+    pub async fn add_bot_client(&self, client: BotClient) -> bool {
         let bot_id = client.bot_id;
         let mut bot_clients = self.bot_clients.write().await;
-        bot_clients.entry(bot_id).or_default().push(client);
+        let connections = bot_clients.entry(bot_id).or_default();
+        connections.push(client);
         tracing::info!("Bot client connected: bot_id={}", bot_id);
+        connections.len() == 1
     }
 
     /// Remove a bot client connection
@@ -345,15 +879,46 @@ impl WsManager {
     /// # Arguments
     /// * `bot_id` - The bot's UUID
     /// * `sender` - The sender channel to identify the specific connection
-    pub async fn remove_bot_client(&self, bot_id: Uuid, sender: &mpsc::UnboundedSender<BotServerEvent>) {
+    ///
+    /// # Returns
+    /// * `bool` - True if that was the bot's last connection, i.e. it just went offline.
+    ///   Removing an already removed connection returns false.
+    pub async fn remove_bot_client(
+        &self,
+        bot_id: Uuid,
+        sender: &mpsc::UnboundedSender<BotServerEvent>,
+    ) -> bool {
         let mut bot_clients = self.bot_clients.write().await;
-        if let Some(clients) = bot_clients.get_mut(&bot_id) {
-            clients.retain(|c| !c.sender.same_channel(sender));
-            if clients.is_empty() {
-                bot_clients.remove(&bot_id);
-            }
+        let Some(clients) = bot_clients.get_mut(&bot_id) else {
+            return false;
+        };
+        let before = clients.len();
+        clients.retain(|c| !c.sender.same_channel(sender));
+        if clients.len() == before {
+            return false;
         }
         tracing::info!("Bot client disconnected: bot_id={}", bot_id);
+
+        if !clients.is_empty() {
+            return false;
+        }
+        bot_clients.remove(&bot_id);
+        self.bot_last_seen.write().await.insert(bot_id, Utc::now());
+        true
+    }
+
+    /// Connection state of a bot as shown to its owner
+    pub async fn bot_status(&self, bot_id: Uuid) -> BotConnectionStatus {
+        if self.is_bot_connected(bot_id).await {
+            return BotConnectionStatus {
+                connected: true,
+                last_seen: Some(Utc::now()),
+            };
+        }
+        BotConnectionStatus {
+            connected: false,
+            last_seen: self.bot_last_seen.read().await.get(&bot_id).copied(),
+        }
     }
 
     /// Check if a bot is currently connected via WebSocket
@@ -406,3 +971,199 @@ impl WsManager {
         bot_clients.keys().cloned().collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn connect(manager: &WsManager, user_id: Uuid) -> mpsc::UnboundedReceiver<ServerEvent> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        manager
+            .add_client(Client::new(user_id, "test".to_string(), tx))
+            .await;
+        rx
+    }
+
+    #[test]
+    fn test_effective_presence() {
+        let idle_online = UserPresence { chosen: PresenceState::Online, idle: true };
+        assert_eq!(idle_online.effective(), PresenceState::Away);
+
+        let idle_busy = UserPresence { chosen: PresenceState::Busy, idle: true };
+        assert_eq!(idle_busy.effective(), PresenceState::Busy);
+
+        let idle_invisible = UserPresence { chosen: PresenceState::Invisible, idle: true };
+        assert_eq!(idle_invisible.effective(), PresenceState::Invisible);
+        assert_eq!(UserPresence::default().effective(), PresenceState::Online);
+    }
+
+    #[tokio::test]
+    async fn test_invisible_user_hidden_but_receives_events() {
+        let manager = WsManager::new();
+        let hidden = Uuid::new_v4();
+        let watcher = Uuid::new_v4();
+        let mut hidden_rx = connect(&manager, hidden).await;
+        let _watcher_rx = connect(&manager, watcher).await;
+
+        manager.set_presence(hidden, PresenceState::Invisible).await;
+
+        assert_eq!(manager.presence_of(hidden).await, PresenceState::Invisible);
+        let visible = manager.visible_presences().await;
+        assert_eq!(visible[&hidden], PresenceState::Offline);
+        assert_eq!(visible[&watcher], PresenceState::Online);
+
+        // Still connected and still delivered to
+        assert!(manager.is_user_online(hidden).await);
+        let chat_id = Uuid::new_v4();
+        manager.join_room(hidden, chat_id).await;
+        let event = ServerEvent::Connected {
+            user_id: watcher,
+            connection_id: Uuid::new_v4(),
+        };
+        manager.broadcast_to_room(chat_id, event, None).await;
+        assert!(matches!(hidden_rx.try_recv(), Ok(ServerEvent::Connected { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_presence_splits_own_and_public() {
+        let manager = WsManager::new();
+        let user = Uuid::new_v4();
+        let other = Uuid::new_v4();
+        let mut user_rx = connect(&manager, user).await;
+        let mut other_rx = connect(&manager, other).await;
+
+        let own = ServerEvent::UserStatus {
+            user_id: user,
+            status: "invisible".to_string(),
+            last_seen: None,
+        };
+        manager
+            .broadcast_presence(user, own, "offline", None, &PresenceAudience::everyone())
+            .await;
+
+        assert!(matches!(user_rx.try_recv(), Ok(ServerEvent::UserStatus { status, .. }) if status == "invisible"));
+        assert!(matches!(other_rx.try_recv(), Ok(ServerEvent::UserStatus { status, .. }) if status == "offline"));
+    }
+
+    #[tokio::test]
+    async fn test_presence_cleared_on_disconnect() {
+        let manager = WsManager::new();
+        let user = Uuid::new_v4();
+        let (tx, _rx) = mpsc::unbounded_channel();
+        manager
+            .add_client(Client::new(user, "test".to_string(), tx.clone()))
+            .await;
+        manager.set_presence(user, PresenceState::Busy).await;
+        manager.set_idle(user, true).await;
+
+        manager.remove_client(user, &tx).await;
+        assert_eq!(manager.presence_of(user).await, PresenceState::Offline);
+
+        manager
+            .add_client(Client::new(user, "test".to_string(), tx))
+            .await;
+        assert_eq!(manager.presence_of(user).await, PresenceState::Online);
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_client_targets_one_own_connection() {
+        let manager = WsManager::new();
+        let user = Uuid::new_v4();
+        let other = Uuid::new_v4();
+        let mut first_rx = connect(&manager, user).await;
+        let mut second_rx = connect(&manager, user).await;
+        let mut other_rx = connect(&manager, other).await;
+
+        let clients = manager.user_clients(user).await;
+        assert_eq!(clients.len(), 2);
+        let first = clients[0].connection_id;
+        let other_connection = manager.user_clients(other).await[0].connection_id;
+
+        // Someone else's connection id is not found
+        assert!(!manager.disconnect_client(user, other_connection, "session terminated").await);
+        assert!(other_rx.try_recv().is_err());
+
+        assert!(manager.disconnect_client(user, first, "session terminated").await);
+        assert!(matches!(first_rx.try_recv(), Ok(ServerEvent::SessionTerminated { .. })));
+        assert!(second_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bot_status_transitions_once_per_bot() {
+        let manager = WsManager::new();
+        let bot_id = Uuid::new_v4();
+        let bot = |sender| BotClient { bot_id, bot_name: "bot".to_string(), sender };
+        let (first, _first_rx) = mpsc::unbounded_channel();
+        let (second, _second_rx) = mpsc::unbounded_channel();
+
+        assert_eq!(manager.bot_status(bot_id).await.last_seen, None);
+        assert!(manager.add_bot_client(bot(first.clone())).await);
+        assert!(!manager.add_bot_client(bot(second.clone())).await);
+        assert!(manager.bot_status(bot_id).await.connected);
+
+        assert!(!manager.remove_bot_client(bot_id, &first).await);
+        assert!(manager.remove_bot_client(bot_id, &second).await);
+        // A second cleanup of the same connection doesn't report it again
+        assert!(!manager.remove_bot_client(bot_id, &second).await);
+
+        let status = manager.bot_status(bot_id).await;
+        assert!(!status.connected);
+        assert!(status.last_seen.is_some());
+    }
+
+    fn is_typing(event: Result<ServerEvent, mpsc::error::TryRecvError>) -> Option<bool> {
+        match event {
+            Ok(ServerEvent::Typing { is_typing, .. }) => Some(is_typing),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_typing_indicator_expires_without_stop() {
+        let manager = WsManager::new();
+        let (typist, watcher) = (Uuid::new_v4(), Uuid::new_v4());
+        let chat_id = Uuid::new_v4();
+        let _typist_rx = connect(&manager, typist).await;
+        let mut watcher_rx = connect(&manager, watcher).await;
+        manager.join_room(watcher, chat_id).await;
+
+        let timeout = Duration::from_millis(100);
+        manager
+            .start_typing_for(chat_id, typist, "typist".to_string(), timeout)
+            .await;
+        assert_eq!(is_typing(watcher_rx.try_recv()), Some(true));
+
+        // A refresh restarts the timeout instead of adding a second stop
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        manager
+            .start_typing_for(chat_id, typist, "typist".to_string(), timeout)
+            .await;
+        assert_eq!(is_typing(watcher_rx.try_recv()), Some(true));
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert!(watcher_rx.try_recv().is_err());
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(is_typing(watcher_rx.try_recv()), Some(false));
+        assert!(watcher_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_typing_stops_when_connection_drops() {
+        let manager = WsManager::new();
+        let (typist, watcher) = (Uuid::new_v4(), Uuid::new_v4());
+        let chat_id = Uuid::new_v4();
+        let (tx, _rx) = mpsc::unbounded_channel();
+        manager
+            .add_client(Client::new(typist, "typist".to_string(), tx.clone()))
+            .await;
+        let mut watcher_rx = connect(&manager, watcher).await;
+        manager.join_room(watcher, chat_id).await;
+
+        manager.start_typing(chat_id, typist, "typist".to_string()).await;
+        assert_eq!(is_typing(watcher_rx.try_recv()), Some(true));
+
+        manager.remove_client(typist, &tx).await;
+        assert_eq!(is_typing(watcher_rx.try_recv()), Some(false));
+        assert!(manager.typing.read().await.is_empty());
+    }
+}
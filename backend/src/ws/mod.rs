@@ -1,4 +1,6 @@
+pub mod batch;
 pub mod handler;
+pub mod heartbeat;
 pub mod manager;
 pub mod events;
 
@@ -1,7 +1,12 @@
 /// Integration test for QUIC server
 /// This test verifies that the QUIC server can start and accept connections
 use anyhow::Result;
-use chat_backend::quic::{QuicServerConfig, QuicServer};
+use chat_backend::quic::{
+    ConnectionId, ConnectionManager, ManagedConnection, MessageType, QuicConnection,
+    QuicServerConfig, QuicServer, StreamIdleTimeouts, StreamPriorities, TransportType,
+    WebSocketConnection, WebSocketSendFuture, AUTH_TIMEOUT_CLOSE_CODE,
+    AUTH_TIMEOUT_CLOSE_REASON, CONNECTION_LIMIT_CLOSE_CODE, CONNECTION_LIMIT_CLOSE_REASON,
+};
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
@@ -28,6 +33,10 @@ async fn test_quic_server_starts_and_accepts_connections() -> Result<()> {
         max_streams_per_connection: 10,
         idle_timeout_ms: 5000,
         keep_alive_interval_ms: 1000,
+        stream_priorities: StreamPriorities::default(),
+        stream_idle_timeouts: StreamIdleTimeouts::default(),
+        alpn_protocols: vec!["giano/1".to_string()],
+        ..QuicServerConfig::default()
     };
 
     // Create and initialize QUIC server
@@ -98,12 +107,269 @@ async fn test_quic_server_starts_and_accepts_connections() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_stream_priorities_applied_by_message_type() -> Result<()> {
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    let priorities = StreamPriorities::default();
+    let config = QuicServerConfig {
+        enabled: true,
+        bind_address: "127.0.0.1".to_string(),
+        port: 14434,
+        cert_path: PathBuf::from("./certs/server.crt"),
+        key_path: PathBuf::from("./certs/server.key"),
+        max_connections: 100,
+        max_streams_per_connection: 10,
+        idle_timeout_ms: 5000,
+        keep_alive_interval_ms: 1000,
+        stream_priorities: priorities,
+        stream_idle_timeouts: StreamIdleTimeouts::default(),
+        alpn_protocols: vec!["giano/1".to_string()],
+        ..QuicServerConfig::default()
+    };
+
+    let mut server = QuicServer::new(config);
+    server.initialize().await?;
+    server.start().await?;
+    let server_addr = server.local_addr().unwrap();
+
+    let mut client_endpoint = quinn::Endpoint::client("127.0.0.1:0".parse()?)?;
+    client_endpoint.set_default_client_config(create_test_client_config()?);
+
+    let (server_conn, client_conn) = tokio::join!(
+        server.accept(),
+        async { client_endpoint.connect(server_addr, "localhost")?.await.map_err(anyhow::Error::from) },
+    );
+    let server_conn = server_conn?;
+    let _client_conn = client_conn?;
+
+    // Open a file transfer stream and a control stream at the same time
+    let (file_stream, control_stream) = tokio::join!(
+        server.open_prioritized_stream(&server_conn, MessageType::FileTransfer),
+        server.open_prioritized_stream(&server_conn, MessageType::Control),
+    );
+    let (file_send, _file_recv) = file_stream?;
+    let (control_send, _control_recv) = control_stream?;
+
+    assert_eq!(file_send.priority()?, priorities.file_transfer);
+    assert_eq!(control_send.priority()?, priorities.control);
+    assert!(control_send.priority()? > file_send.priority()?);
+
+    client_endpoint.close(0u32.into(), b"test complete");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_connections_over_limit_are_refused() -> Result<()> {
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    let config = QuicServerConfig {
+        enabled: true,
+        bind_address: "127.0.0.1".to_string(),
+        port: 14435,
+        cert_path: PathBuf::from("./certs/server.crt"),
+        key_path: PathBuf::from("./certs/server.key"),
+        max_connections: 2,
+        max_streams_per_connection: 10,
+        idle_timeout_ms: 5000,
+        keep_alive_interval_ms: 1000,
+        stream_priorities: StreamPriorities::default(),
+        stream_idle_timeouts: StreamIdleTimeouts::default(),
+        alpn_protocols: vec!["giano/1".to_string()],
+        ..QuicServerConfig::default()
+    };
+
+    let mut server = QuicServer::new(config);
+    server.initialize().await?;
+    server.start().await?;
+    let server_addr = server.local_addr().unwrap();
+
+    // Hold every accepted connection open until the client closes it
+    let server = Arc::new(server);
+    let run_server = Arc::clone(&server);
+    tokio::spawn(async move {
+        run_server
+            .run(|connection| async move {
+                connection.closed().await;
+                Ok(())
+            })
+            .await
+    });
+
+    let mut client_endpoint = quinn::Endpoint::client("127.0.0.1:0".parse()?)?;
+    client_endpoint.set_default_client_config(create_test_client_config()?);
+
+    let mut accepted = Vec::new();
+    for _ in 0..2 {
+        accepted.push(client_endpoint.connect(server_addr, "localhost")?.await?);
+    }
+    // Let the acceptance loop register both connections
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(server.active_connections(), 2);
+
+    let refused = client_endpoint.connect(server_addr, "localhost")?.await?;
+    let close = timeout(Duration::from_secs(2), refused.closed()).await?;
+    match close {
+        quinn::ConnectionError::ApplicationClosed(close) => {
+            assert_eq!(close.error_code, quinn::VarInt::from_u32(CONNECTION_LIMIT_CLOSE_CODE));
+            assert_eq!(&close.reason[..], CONNECTION_LIMIT_CLOSE_REASON);
+        }
+        other => panic!("expected application close, got {other:?}"),
+    }
+    assert_eq!(server.rejected_at_capacity(), 1);
+
+    // The first connections are unaffected
+    for connection in &accepted {
+        assert!(connection.close_reason().is_none());
+    }
+
+    client_endpoint.close(0u32.into(), b"test complete");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_failed_quic_send_falls_back_to_websocket() -> Result<()> {
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    let config = QuicServerConfig {
+        enabled: true,
+        bind_address: "127.0.0.1".to_string(),
+        port: 14437,
+        cert_path: PathBuf::from("./certs/server.crt"),
+        key_path: PathBuf::from("./certs/server.key"),
+        alpn_protocols: vec!["giano/1".to_string()],
+        ..QuicServerConfig::default()
+    };
+
+    let mut server = QuicServer::new(config);
+    server.initialize().await?;
+    server.start().await?;
+    let server_addr = server.local_addr().unwrap();
+    let server = Arc::new(server);
+    let run_server = Arc::clone(&server);
+    tokio::spawn(async move {
+        run_server
+            .run(|connection| async move {
+                connection.closed().await;
+                Ok(())
+            })
+            .await
+    });
+
+    let mut client_endpoint = quinn::Endpoint::client("127.0.0.1:0".parse()?)?;
+    client_endpoint.set_default_client_config(create_test_client_config()?);
+    let quinn_connection = client_endpoint.connect(server_addr, "localhost")?.await?;
+
+    let sent = Arc::new(std::sync::Mutex::new(Vec::<(ConnectionId, Vec<u8>)>::new()));
+    let sink = Arc::clone(&sent);
+    let manager = ConnectionManager::new().with_websocket_sender(Arc::new(move |_, id, data| {
+        sink.lock().unwrap().push((id, data));
+        Box::pin(async { Ok(()) }) as WebSocketSendFuture
+    }));
+    assert_eq!(manager.preferred_transport(), TransportType::Quic);
+
+    // A phone connected over both transports and a browser tab over WebSocket
+    let user_id = uuid::Uuid::new_v4();
+    let (phone, tab) = (Some(uuid::Uuid::new_v4()), Some(uuid::Uuid::new_v4()));
+    let mut quic = QuicConnection::new(ConnectionId::new(), quinn_connection.clone());
+    quic.set_user_id(user_id);
+    quic.set_session_id(phone);
+    manager.register_connection(ManagedConnection::Quic(quic)).await?;
+    let (phone_ws, tab_ws) = (ConnectionId::new(), ConnectionId::new());
+    for (id, session) in [(phone_ws, phone), (tab_ws, tab)] {
+        manager
+            .register_connection(ManagedConnection::WebSocket(
+                WebSocketConnection::new(id, user_id).with_session(session),
+            ))
+            .await?;
+    }
+
+    // Both transports work: the phone gets it over QUIC, the tab still gets it
+    assert_eq!(manager.send_to_user_preferred(user_id, b"first").await?, 2);
+    assert_eq!(*sent.lock().unwrap(), vec![(tab_ws, b"first".to_vec())]);
+    sent.lock().unwrap().clear();
+
+    // QUIC is gone: the phone's WebSocket connection gets it instead
+    quinn_connection.close(0u32.into(), b"gone");
+    assert_eq!(manager.send_to_user_preferred(user_id, b"second").await?, 2);
+    let mut received = sent.lock().unwrap().clone();
+    received.sort_by_key(|(id, _)| *id == tab_ws);
+    assert_eq!(
+        received,
+        vec![(phone_ws, b"second".to_vec()), (tab_ws, b"second".to_vec())]
+    );
+    assert_eq!(manager.transport_fallback_count(), 1);
+    assert_eq!(manager.get_stats().await.transport_fallbacks, 1);
+
+    client_endpoint.close(0u32.into(), b"test complete");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_connection_without_auth_is_dropped_after_timeout() -> Result<()> {
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    let config = QuicServerConfig {
+        enabled: true,
+        bind_address: "127.0.0.1".to_string(),
+        port: 14436,
+        cert_path: PathBuf::from("./certs/server.crt"),
+        key_path: PathBuf::from("./certs/server.key"),
+        idle_timeout_ms: 5000,
+        keep_alive_interval_ms: 1000,
+        alpn_protocols: vec!["giano/1".to_string()],
+        auth_timeout_secs: 1,
+        ..QuicServerConfig::default()
+    };
+
+    let mut server = QuicServer::new(config);
+    server.set_jwt_secret("test-secret".to_string());
+    server.set_connection_manager(Arc::new(ConnectionManager::new()));
+    server.initialize().await?;
+    server.start().await?;
+    let server_addr = server.local_addr().unwrap();
+
+    let server = Arc::new(server);
+    let run_server = Arc::clone(&server);
+    tokio::spawn(async move {
+        let auth_server = Arc::clone(&run_server);
+        run_server
+            .run(move |connection| {
+                let server = Arc::clone(&auth_server);
+                async move {
+                    server.authenticate_and_register_connection(connection).await?;
+                    Ok(())
+                }
+            })
+            .await
+    });
+
+    let mut client_endpoint = quinn::Endpoint::client("127.0.0.1:0".parse()?)?;
+    client_endpoint.set_default_client_config(create_test_client_config()?);
+
+    // Connect but never open the auth stream
+    let connection = client_endpoint.connect(server_addr, "localhost")?.await?;
+    let close = timeout(Duration::from_secs(3), connection.closed()).await?;
+    match close {
+        quinn::ConnectionError::ApplicationClosed(close) => {
+            assert_eq!(close.error_code, quinn::VarInt::from_u32(AUTH_TIMEOUT_CLOSE_CODE));
+            assert_eq!(&close.reason[..], AUTH_TIMEOUT_CLOSE_REASON);
+        }
+        other => panic!("expected application close, got {other:?}"),
+    }
+    assert_eq!(server.handshake_stats().auth_timeouts, 1);
+
+    client_endpoint.close(0u32.into(), b"test complete");
+    Ok(())
+}
+
 /// Create a test client configuration that accepts self-signed certificates
 fn create_test_client_config() -> Result<quinn::ClientConfig> {
-    let crypto = rustls::ClientConfig::builder()
+    let mut crypto = rustls::ClientConfig::builder()
         .dangerous()
         .with_custom_certificate_verifier(Arc::new(SkipServerVerification))
         .with_no_client_auth();
+    crypto.alpn_protocols = vec![b"giano/1".to_vec()];
 
     Ok(quinn::ClientConfig::new(Arc::new(
         quinn::crypto::rustls::QuicClientConfig::try_from(crypto)?
@@ -147,6 +413,8 @@ impl rustls::client::danger::ServerCertVerifier for SkipServerVerification {
     fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
         vec![
             rustls::SignatureScheme::RSA_PKCS1_SHA256,
+            // TLS 1.3 only signs with PSS, which the RSA test certificate needs
+            rustls::SignatureScheme::RSA_PSS_SHA256,
             rustls::SignatureScheme::ECDSA_NISTP256_SHA256,
             rustls::SignatureScheme::ED25519,
         ]
@@ -166,6 +434,10 @@ async fn test_quic_server_disabled() -> Result<()> {
         max_streams_per_connection: 10,
         idle_timeout_ms: 5000,
         keep_alive_interval_ms: 1000,
+        stream_priorities: StreamPriorities::default(),
+        stream_idle_timeouts: StreamIdleTimeouts::default(),
+        alpn_protocols: vec!["giano/1".to_string()],
+        ..QuicServerConfig::default()
     };
 
     let server = QuicServer::new(config);